@@ -0,0 +1,147 @@
+//! Remove source directories left empty after moving.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// OS metadata files that don't count as content when `junk_as_empty` is set.
+pub const JUNK_FILES: &[&str] = &[".DS_Store", "._.DS_Store", "Thumbs.db", "desktop.ini"];
+
+fn is_junk(name: &std::ffi::OsStr) -> bool {
+    let name = name.to_string_lossy();
+    JUNK_FILES.iter().any(|j| j.eq_ignore_ascii_case(&name))
+}
+
+/// Outcome of an empty-directory cleanup.
+#[derive(Debug, Default)]
+pub struct CleanupReport {
+    /// Directories removed (or that would be removed in dry-run).
+    pub removed: u64,
+    /// Non-fatal problems such as permission errors.
+    pub warnings: Vec<String>,
+}
+
+/// Remove directories under `root` that are empty, working bottom-up; `root` itself is never removed.
+/// With `junk_as_empty`, directories holding only junk files (.DS_Store, Thumbs.db) count as empty
+/// and the junk is deleted with them. In `dry_run` nothing is touched: files listed in `gone`
+/// (the ones the run would move away) are treated as absent and the would-be count is returned.
+pub fn remove_empty_dirs(
+    root: &Path,
+    junk_as_empty: bool,
+    dry_run: bool,
+    gone: &HashSet<PathBuf>,
+) -> CleanupReport {
+    let mut report = CleanupReport::default();
+    let mut removed_dirs: HashSet<PathBuf> = HashSet::new();
+
+    for entry in WalkDir::new(root)
+        .min_depth(1)
+        .follow_links(false)
+        .contents_first(true)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        let dir = entry.path();
+        let children = match fs::read_dir(dir) {
+            Ok(rd) => rd,
+            Err(e) => {
+                report
+                    .warnings
+                    .push(format!("Cannot read {}: {}", dir.display(), e));
+                continue;
+            }
+        };
+        let mut junk = Vec::new();
+        let mut empty = true;
+        for child in children.filter_map(|c| c.ok()) {
+            let path = child.path();
+            if removed_dirs.contains(&path) || gone.contains(&path) {
+                continue;
+            }
+            let is_file = child.file_type().map(|t| t.is_file()).unwrap_or(false);
+            if junk_as_empty && is_file && is_junk(&child.file_name()) {
+                junk.push(path);
+                continue;
+            }
+            empty = false;
+            break;
+        }
+        if !empty {
+            continue;
+        }
+        if !dry_run {
+            let result = junk
+                .iter()
+                .try_for_each(fs::remove_file)
+                .and_then(|_| fs::remove_dir(dir));
+            if let Err(e) = result {
+                report
+                    .warnings
+                    .push(format!("Cannot remove {}: {}", dir.display(), e));
+                continue;
+            }
+        }
+        removed_dirs.insert(dir.to_path_buf());
+        report.removed += 1;
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removes_nested_empty_dirs_but_not_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("DCIM/100CANON")).unwrap();
+        fs::create_dir_all(root.join("DCIM/101CANON")).unwrap();
+        fs::write(root.join("DCIM/101CANON/IMG_0001.JPG"), b"keep").unwrap();
+
+        let report = remove_empty_dirs(root, false, false, &HashSet::new());
+
+        assert_eq!(report.removed, 1);
+        assert!(!root.join("DCIM/100CANON").exists());
+        assert!(root.join("DCIM/101CANON/IMG_0001.JPG").exists());
+        assert!(root.exists());
+    }
+
+    #[test]
+    fn test_junk_only_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("A")).unwrap();
+        fs::write(root.join("A/.DS_Store"), b"junk").unwrap();
+
+        assert_eq!(
+            remove_empty_dirs(root, false, false, &HashSet::new()).removed,
+            0
+        );
+        assert!(root.join("A").exists());
+        assert_eq!(
+            remove_empty_dirs(root, true, false, &HashSet::new()).removed,
+            1
+        );
+        assert!(!root.join("A").exists());
+    }
+
+    #[test]
+    fn test_dry_run_predicts_without_touching() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("DCIM/100CANON")).unwrap();
+        let file = root.join("DCIM/100CANON/IMG_7612.JPG");
+        fs::write(&file, b"pick").unwrap();
+        let gone: HashSet<PathBuf> = [file.clone()].into_iter().collect();
+
+        let report = remove_empty_dirs(root, false, true, &gone);
+
+        assert_eq!(report.removed, 2);
+        assert!(file.exists());
+    }
+}
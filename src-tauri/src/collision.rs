@@ -0,0 +1,218 @@
+//! Collision rename patterns: how "IMG_7612.JPG" is renamed when its target name is taken.
+
+use std::fmt;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Counter,
+    Timestamp,
+}
+
+/// Error for a collision pattern that cannot be used.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum PatternError {
+    #[error("collision pattern must contain {{n}} or {{ts}}")]
+    NoPlaceholder,
+    #[error("unknown token {{{0}}} in collision pattern (valid: {{n}} {{ts}})")]
+    UnknownToken(String),
+    #[error("unclosed '{{' in collision pattern")]
+    Unclosed,
+    #[error("collision pattern must not contain path separators")]
+    Separator,
+}
+
+/// Text inserted between the stem and the extension of a colliding name.
+/// `{n}` is a counter starting at 1 and `{ts}` a local timestamp like "20240506-142233";
+/// e.g. "-{n}" gives "IMG_7612-1.JPG" and " ({n})" gives "IMG_7612 (1).JPG".
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CollisionPattern {
+    source: String,
+    pieces: Vec<Piece>,
+}
+
+impl Default for CollisionPattern {
+    fn default() -> Self {
+        CollisionPattern::parse("-{n}").expect("default collision pattern is valid")
+    }
+}
+
+impl CollisionPattern {
+    /// Parse a pattern; the names "dash", "windows" and "timestamp" select the built-in presets.
+    pub fn parse(input: &str) -> Result<CollisionPattern, PatternError> {
+        let pattern = match input {
+            "dash" => "-{n}",
+            "windows" => " ({n})",
+            "timestamp" => "-{ts}",
+            other => other,
+        };
+        if pattern.contains(['/', '\\']) {
+            return Err(PatternError::Separator);
+        }
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '{' {
+                literal.push(c);
+                continue;
+            }
+            let mut name = String::new();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => name.push(c),
+                    None => return Err(PatternError::Unclosed),
+                }
+            }
+            let piece = match name.as_str() {
+                "n" => Piece::Counter,
+                "ts" => Piece::Timestamp,
+                _ => return Err(PatternError::UnknownToken(name)),
+            };
+            if !literal.is_empty() {
+                pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+            }
+            pieces.push(piece);
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        if !pieces.iter().any(|p| !matches!(p, Piece::Literal(_))) {
+            return Err(PatternError::NoPlaceholder);
+        }
+        Ok(CollisionPattern {
+            source: pattern.to_string(),
+            pieces,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    fn has_counter(&self) -> bool {
+        self.pieces.contains(&Piece::Counter)
+    }
+
+    /// File name for the `attempt`-th (1-based) collision of `file_name`, with `ts` as the timestamp.
+    /// Timestamp-only patterns add "-1", "-2", ... from the second attempt so names stay unique.
+    pub fn candidate(&self, file_name: &str, attempt: u32, ts: &str) -> String {
+        let (stem, ext) = split_stem_ext(file_name);
+        let mut inserted = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Literal(s) => inserted.push_str(s),
+                Piece::Counter => inserted.push_str(&attempt.to_string()),
+                Piece::Timestamp => inserted.push_str(ts),
+            }
+        }
+        if !self.has_counter() && attempt > 1 {
+            inserted.push_str(&format!("-{}", attempt - 1));
+        }
+        if ext.is_empty() {
+            format!("{}{}", stem, inserted)
+        } else {
+            format!("{}{}.{}", stem, inserted, ext)
+        }
+    }
+}
+
+impl TryFrom<String> for CollisionPattern {
+    type Error = PatternError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        CollisionPattern::parse(&input)
+    }
+}
+
+impl From<CollisionPattern> for String {
+    fn from(value: CollisionPattern) -> String {
+        value.source
+    }
+}
+
+impl fmt::Display for CollisionPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Timestamp for `{ts}`, taken once per file so every attempt uses the same value.
+pub fn timestamp_now() -> String {
+    chrono::Local::now().format("%Y%m%d-%H%M%S").to_string()
+}
+
+/// Split a file name into stem and extension. Dotfiles (".hidden") have no extension and
+/// multi-dot names ("IMG.7612.JPG") split at the last dot.
+pub fn split_stem_ext(file_name: &str) -> (&str, &str) {
+    let path = Path::new(file_name);
+    match (
+        path.file_stem().and_then(|s| s.to_str()),
+        path.extension().and_then(|s| s.to_str()),
+    ) {
+        (Some(stem), Some(ext)) => (stem, ext),
+        _ => (file_name, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TS: &str = "20240506-142233";
+
+    fn names(pattern: &str, file_name: &str) -> Vec<String> {
+        let p = CollisionPattern::parse(pattern).unwrap();
+        (1..=3).map(|i| p.candidate(file_name, i, TS)).collect()
+    }
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(
+            names("dash", "IMG_7612.JPG"),
+            ["IMG_7612-1.JPG", "IMG_7612-2.JPG", "IMG_7612-3.JPG"]
+        );
+        assert_eq!(
+            names("windows", "IMG_7612.JPG"),
+            ["IMG_7612 (1).JPG", "IMG_7612 (2).JPG", "IMG_7612 (3).JPG"]
+        );
+        assert_eq!(
+            names("timestamp", "IMG_7612.JPG"),
+            [
+                "IMG_7612-20240506-142233.JPG",
+                "IMG_7612-20240506-142233-1.JPG",
+                "IMG_7612-20240506-142233-2.JPG"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dotfiles_and_multi_dot_names() {
+        assert_eq!(names("-{n}", ".hidden")[0], ".hidden-1");
+        assert_eq!(
+            names("_{ts}_{n}", "IMG.7612.JPG")[1],
+            "IMG.7612_20240506-142233_2.JPG"
+        );
+        assert_eq!(names(" ({n})", "noext")[0], "noext (1)");
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert_eq!(
+            CollisionPattern::parse("-copy"),
+            Err(PatternError::NoPlaceholder)
+        );
+        assert_eq!(
+            CollisionPattern::parse("-{x}"),
+            Err(PatternError::UnknownToken("x".to_string()))
+        );
+        assert_eq!(CollisionPattern::parse("-{n"), Err(PatternError::Unclosed));
+        assert_eq!(
+            CollisionPattern::parse("/{n}"),
+            Err(PatternError::Separator)
+        );
+    }
+}
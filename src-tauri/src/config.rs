@@ -0,0 +1,167 @@
+//! The CLI's configuration file, `framemover.toml`: defaults for the options of a job run
+//! again and again, so that a nightly job does not repeat a long list of flags. Each key is a
+//! flag's long name, e.g. `collision-pattern = "windows"` or `exclude-glob = ["thumb_*"]`; the
+//! CLI decides which keys there are and what their values may be, and a flag given on the
+//! command line overrides the file.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use toml::Spanned;
+
+/// Name of the file in the config folder.
+pub const CONFIG_FILE_NAME: &str = "framemover.toml";
+
+/// The app's identifier (tauri.conf.json), the name of its folder in the config folder.
+const APP_IDENTIFIER: &str = "com.framemover.app";
+
+/// `framemover.toml` in the app's config folder, where the CLI looks for one without `--config`.
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_IDENTIFIER).join(CONFIG_FILE_NAME))
+}
+
+/// The value of a key, as the command line would give it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigValue {
+    /// `true` or `false`, for a flag without a value.
+    Flag(bool),
+    /// A string or a number.
+    Text(String),
+    /// An array, for a flag that can be repeated.
+    List(Vec<String>),
+}
+
+/// One key of the file, with the line it is on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub line: usize,
+    pub value: ConfigValue,
+}
+
+/// Why a configuration file cannot be used.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("cannot read the configuration {}: {source}", path.display())]
+    Unreadable { path: PathBuf, source: io::Error },
+    /// Not TOML; the parser's message says where.
+    #[error("configuration {}: {message}", path.display())]
+    Syntax { path: PathBuf, message: String },
+    #[error("configuration {}, line {line}: `{key}` {message}", path.display())]
+    Key {
+        path: PathBuf,
+        key: String,
+        line: usize,
+        message: String,
+    },
+}
+
+impl ConfigError {
+    /// Stable name of the variant, for scripts.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConfigError::Unreadable { .. } => "config_unreadable",
+            ConfigError::Syntax { .. } => "config_syntax",
+            ConfigError::Key { .. } => "config_key",
+        }
+    }
+
+    /// The error of `key` on `line` of the file at `path`.
+    pub fn key(path: &Path, key: &str, line: usize, message: impl Into<String>) -> ConfigError {
+        ConfigError::Key {
+            path: path.to_path_buf(),
+            key: key.to_string(),
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+/// The keys of the configuration file at `path`, in the order they are written.
+pub fn load_config(path: &Path) -> Result<Vec<ConfigEntry>, ConfigError> {
+    let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Unreadable {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_config(path, &text)
+}
+
+fn parse_config(path: &Path, text: &str) -> Result<Vec<ConfigEntry>, ConfigError> {
+    let table: BTreeMap<Spanned<String>, Spanned<toml::Value>> =
+        toml::from_str(text).map_err(|e| ConfigError::Syntax {
+            path: path.to_path_buf(),
+            message: e.to_string().trim_end().to_string(),
+        })?;
+    let line_of = |offset: usize| text[..offset].matches('\n').count() + 1;
+    let mut entries = Vec::with_capacity(table.len());
+    let mut keys: Vec<_> = table.into_iter().collect();
+    keys.sort_by_key(|(key, _)| key.span().start);
+    for (key, value) in keys {
+        let line = line_of(key.span().start);
+        let invalid = |message: &str| ConfigError::key(path, key.get_ref(), line, message);
+        let value = match value.into_inner() {
+            toml::Value::Boolean(b) => ConfigValue::Flag(b),
+            toml::Value::Array(items) => ConfigValue::List(
+                items
+                    .into_iter()
+                    .map(|item| text_of(item).ok_or_else(|| invalid("must be a list of strings")))
+                    .collect::<Result<_, _>>()?,
+            ),
+            other => {
+                ConfigValue::Text(text_of(other).ok_or_else(|| {
+                    invalid("must be a string, a number, true or false, or a list")
+                })?)
+            }
+        };
+        entries.push(ConfigEntry {
+            key: key.into_inner(),
+            line,
+            value,
+        });
+    }
+    Ok(entries)
+}
+
+/// A string or number as the command line would spell it.
+fn text_of(value: toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(s) => Some(s),
+        toml::Value::Integer(n) => Some(n.to_string()),
+        toml::Value::Float(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_come_with_their_lines() {
+        let path = Path::new("framemover.toml");
+        let text = "# nightly archive\nsource = [\"/cards/a\", \"/cards/b\"]\ndest = \"/archive\"\n\nworkers = 4\ndry-run = true\n";
+        let entries = parse_config(path, text).unwrap();
+        let keys: Vec<(&str, usize)> = entries.iter().map(|e| (e.key.as_str(), e.line)).collect();
+        assert_eq!(
+            keys,
+            [("source", 2), ("dest", 3), ("workers", 5), ("dry-run", 6)]
+        );
+        assert_eq!(
+            entries[0].value,
+            ConfigValue::List(vec!["/cards/a".into(), "/cards/b".into()])
+        );
+        assert_eq!(entries[2].value, ConfigValue::Text("4".into()));
+        assert_eq!(entries[3].value, ConfigValue::Flag(true));
+
+        let error =
+            parse_config(path, "dest = \"/archive\"\nroute = [1, { a = 2 }]\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "configuration framemover.toml, line 2: `route` must be a list of strings"
+        );
+        let error = parse_config(path, "dest = \n").unwrap_err();
+        assert_eq!(error.kind(), "config_syntax");
+        assert!(error.to_string().contains("line 1"));
+    }
+}
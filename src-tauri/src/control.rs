@@ -0,0 +1,300 @@
+//! Run control shared between the engine and whoever drives it: running, paused or cancelled,
+//! and how fast it may read and write.
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// State of a run as set through its `RunControl`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlState {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+const RUNNING: u8 = 0;
+const PAUSED: u8 = 1;
+const CANCELLED: u8 = 2;
+
+/// How often a paused run checks whether it may continue.
+const PAUSE_POLL: Duration = Duration::from_millis(100);
+
+/// How far the throttle lets an idle run read ahead of its rate.
+const THROTTLE_BURST: Duration = Duration::from_millis(250);
+
+/// Checked before each file and between copy/hash chunks.
+#[derive(Debug, Default)]
+pub struct RunControl {
+    state: AtomicU8,
+    /// Bytes per second all workers together may read or write; 0 for no limit.
+    throttle: AtomicU64,
+    /// When the bytes let through so far are paid for at the throttled rate.
+    throttle_until: Mutex<Option<Instant>>,
+}
+
+impl RunControl {
+    pub fn new() -> RunControl {
+        RunControl::default()
+    }
+
+    pub fn state(&self) -> ControlState {
+        match self.state.load(Ordering::SeqCst) {
+            PAUSED => ControlState::Paused,
+            CANCELLED => ControlState::Cancelled,
+            _ => ControlState::Running,
+        }
+    }
+
+    /// Pause a running run; does nothing once it is cancelled.
+    pub fn pause(&self) {
+        let _ = self
+            .state
+            .compare_exchange(RUNNING, PAUSED, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    /// Resume a paused run; does nothing once it is cancelled.
+    pub fn resume(&self) {
+        let _ = self
+            .state
+            .compare_exchange(PAUSED, RUNNING, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    /// Cancel the run, paused or not; it stops at the next check.
+    pub fn cancel(&self) {
+        self.state.store(CANCELLED, Ordering::SeqCst);
+    }
+
+    /// Back to running, when the control is reused for a new run.
+    pub fn reset(&self) {
+        self.state.store(RUNNING, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state() == ControlState::Paused
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state() == ControlState::Cancelled
+    }
+
+    /// Limit reads and writes to `bytes_per_second` from now on (0 lifts the limit). Takes
+    /// effect at the next chunk, so it can be changed while a run is going.
+    pub fn set_throttle(&self, bytes_per_second: u64) {
+        self.throttle.store(bytes_per_second, Ordering::SeqCst);
+    }
+
+    pub fn throttle(&self) -> u64 {
+        self.throttle.load(Ordering::SeqCst)
+    }
+
+    /// Account for `bytes` just read or written, sleeping as long as the throttle requires.
+    /// Returns `false` once the run is cancelled (the wait is cut short), `true` otherwise.
+    pub fn pace(&self, bytes: u64) -> bool {
+        let rate = self.throttle();
+        if rate == 0 {
+            return true;
+        }
+        let wait_until = {
+            let mut until = self
+                .throttle_until
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            // After a pause or between files, start again from now, not from a debt or credit.
+            let from = until.filter(|&t| t + THROTTLE_BURST > now).unwrap_or(now);
+            let next = from + Duration::from_secs_f64(bytes as f64 / rate as f64);
+            *until = Some(next);
+            next.checked_sub(THROTTLE_BURST).unwrap_or(next)
+        };
+        loop {
+            if self.is_cancelled() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= wait_until {
+                return true;
+            }
+            std::thread::sleep((wait_until - now).min(PAUSE_POLL));
+        }
+    }
+
+    /// Block while paused. Returns `false` once the run is cancelled, `true` when it may go on.
+    pub fn proceed(&self) -> bool {
+        loop {
+            match self.state() {
+                ControlState::Running => return true,
+                ControlState::Cancelled => return false,
+                ControlState::Paused => std::thread::sleep(PAUSE_POLL),
+            }
+        }
+    }
+}
+
+/// Error for a run started while another one is still going.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error("Run {0} is still going; wait for it to finish or cancel it first")]
+pub struct RunBusy(pub u64);
+
+/// The one run the app lets go at a time: its id and its control. Ids count up from 1 and
+/// are never reused, so a cancel meant for a run that has ended cannot reach the next one.
+#[derive(Debug, Default)]
+pub struct RunSlot {
+    active: Mutex<Option<(u64, Arc<RunControl>)>>,
+    last_id: AtomicU64,
+    /// Signalled when the slot is freed, for `wait_ended`.
+    ended: Condvar,
+}
+
+impl RunSlot {
+    pub fn new() -> RunSlot {
+        RunSlot::default()
+    }
+
+    /// Take the slot for a new run: its id and a fresh control, or the id of the run that
+    /// still has it.
+    pub fn begin(&self) -> Result<(u64, Arc<RunControl>), RunBusy> {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((id, _)) = &*active {
+            return Err(RunBusy(*id));
+        }
+        let id = self.last_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let control = Arc::new(RunControl::new());
+        *active = Some((id, control.clone()));
+        Ok((id, control))
+    }
+
+    /// Free the slot once the run `id` has ended; an id that no longer has it is ignored.
+    pub fn end(&self, id: u64) {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        if active.as_ref().is_some_and(|(current, _)| *current == id) {
+            *active = None;
+            self.ended.notify_all();
+        }
+    }
+
+    /// Wait up to `timeout` for the run going now to end; returns whether the slot is free.
+    pub fn wait_ended(&self, timeout: Duration) -> bool {
+        let active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        let (active, _) = self
+            .ended
+            .wait_timeout_while(active, timeout, |active| active.is_some())
+            .unwrap_or_else(|e| e.into_inner());
+        active.is_none()
+    }
+
+    /// The id and control of the run going now.
+    pub fn current(&self) -> Option<(u64, Arc<RunControl>)> {
+        self.active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Cancel the run `id`. Returns whether it was cancelled: not when it has already ended,
+    /// whatever run is going now.
+    pub fn cancel(&self, id: u64) -> bool {
+        match self.current() {
+            Some((current, control)) if current == id => {
+                control.cancel();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Cancel whichever run is going, when the app itself stops it (see `cancel`). Returns
+    /// whether there was one.
+    pub fn cancel_current(&self) -> bool {
+        self.current()
+            .map(|(_, control)| control.cancel())
+            .is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_slot_lets_one_run_go_and_cancels_by_id() {
+        let slot = RunSlot::new();
+        assert!(!slot.cancel_current());
+        let (first, control) = slot.begin().unwrap();
+        assert_eq!(slot.begin().unwrap_err(), RunBusy(first));
+        assert!(!slot.cancel(first + 1));
+        assert!(!control.is_cancelled());
+        assert!(slot.cancel(first));
+        assert!(control.is_cancelled());
+
+        slot.end(first + 1);
+        assert_eq!(slot.current().map(|(id, _)| id), Some(first));
+        slot.end(first);
+        let (second, control) = slot.begin().unwrap();
+        assert!(second > first && !control.is_cancelled());
+        // A late cancel of the first run leaves the second alone.
+        assert!(!slot.cancel(first));
+        assert!(!control.is_cancelled());
+        slot.end(first);
+        assert!(slot.begin().is_err());
+
+        assert!(!slot.wait_ended(Duration::from_millis(20)));
+        assert!(slot.cancel_current());
+        assert!(control.is_cancelled());
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+                slot.end(second);
+            });
+            assert!(slot.wait_ended(Duration::from_secs(10)));
+        });
+        assert!(slot.wait_ended(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_cancel_while_paused_releases_waiters() {
+        let control = RunControl::new();
+        control.pause();
+        assert!(control.is_paused());
+        std::thread::scope(|s| {
+            let waiter = s.spawn(|| control.proceed());
+            std::thread::sleep(Duration::from_millis(50));
+            control.cancel();
+            assert!(!waiter.join().unwrap());
+        });
+        // Cancellation is final: resume and pause don't undo it.
+        control.resume();
+        control.pause();
+        assert!(control.is_cancelled());
+        control.reset();
+        assert!(control.proceed());
+    }
+
+    #[test]
+    fn test_pace_limits_rate_and_stops_on_cancel() {
+        let control = RunControl::new();
+        let start = Instant::now();
+        assert!(control.pace(u64::MAX));
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        control.set_throttle(1000);
+        let start = Instant::now();
+        for _ in 0..5 {
+            assert!(control.pace(100));
+        }
+        // 500 bytes at 1000 B/s, less the burst allowance.
+        let took = start.elapsed();
+        assert!(took >= Duration::from_millis(200), "{:?}", took);
+
+        // A long wait is cut short by a cancel.
+        let start = Instant::now();
+        std::thread::scope(|s| {
+            let waiter = s.spawn(|| control.pace(100_000));
+            std::thread::sleep(Duration::from_millis(50));
+            control.cancel();
+            assert!(!waiter.join().unwrap());
+        });
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,257 @@
+//! Read-only duplicate audit of a destination: which images in it have identical content, and
+//! how many bytes the extra copies take. Nothing is moved, renamed or deleted.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use super::meter::ByteMeter;
+use super::report::{ErrorDetail, ErrorStage};
+use super::runlog::RunLog;
+use super::{emitter, Emit, Phase, ProgressEvent, ProgressFn};
+use crate::control::RunControl;
+use crate::hasher;
+use crate::scanner;
+
+/// Files with the same content: the first path is the one that sorts first.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdenticalFiles {
+    pub hash: String,
+    /// Size of each copy.
+    pub size: u64,
+    pub paths: Vec<PathBuf>,
+}
+
+impl IdenticalFiles {
+    /// Bytes the copies beyond the first take.
+    pub fn wasted_bytes(&self) -> u64 {
+        self.size * (self.paths.len() as u64).saturating_sub(1)
+    }
+}
+
+/// Result of [`audit`].
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditReport {
+    pub dest: PathBuf,
+    /// Images found under `dest`.
+    pub files: u64,
+    pub bytes: u64,
+    /// Files hashed: only those sharing their size with another one can have a twin.
+    pub hashed: u64,
+    /// Largest waste first.
+    pub groups: Vec<IdenticalFiles>,
+    /// Copies beyond the first of each group.
+    pub duplicate_files: u64,
+    pub wasted_bytes: u64,
+    /// Files that could not be read; they are in no group.
+    pub errors: Vec<ErrorDetail>,
+    /// Stopped before every candidate was hashed; the groups cover only those that were.
+    pub cancelled: bool,
+}
+
+/// Find the images under `dest` with identical content, hashing on `workers` threads (default
+/// `DEFAULT_PARALLEL_WORKERS`). `control` pauses, throttles and cancels it as it does a run.
+pub fn audit(
+    dest: &Path,
+    workers: usize,
+    verbose: bool,
+    control: &RunControl,
+    progress: Option<ProgressFn>,
+) -> AuditReport {
+    let log = RunLog::new(verbose);
+    let progress = Mutex::new(progress);
+    let emit = emitter(&progress);
+    let mut report = AuditReport {
+        dest: dest.to_path_buf(),
+        ..Default::default()
+    };
+    emit(ProgressEvent {
+        phase: Phase::ScanningSource,
+        ..Default::default()
+    });
+    let files = match scanner::list_images_under(dest) {
+        Ok(files) => files,
+        Err(e) => {
+            let detail = ErrorDetail::new(Some(dest), ErrorStage::Scan, e.to_string());
+            log.error(&detail);
+            report.errors.push(detail);
+            vec![]
+        }
+    };
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for path in files {
+        match std::fs::metadata(&path) {
+            Ok(m) => {
+                report.files += 1;
+                report.bytes += m.len();
+                by_size.entry(m.len()).or_default().push(path);
+            }
+            Err(e) => {
+                let detail = ErrorDetail::new(Some(&path), ErrorStage::Scan, e.to_string());
+                log.warn(&detail);
+                report.errors.push(detail);
+            }
+        }
+    }
+    let mut candidates: Vec<(PathBuf, u64)> = by_size
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .flat_map(|(size, paths)| paths.into_iter().map(move |p| (p, size)))
+        .collect();
+    candidates.sort();
+    log.info(format_args!(
+        "Auditing {}: {} image(s), {} to hash",
+        dest.display(),
+        report.files,
+        candidates.len()
+    ));
+
+    let hashes = hash_all(&candidates, workers, control, &emit, &log);
+    let mut groups: HashMap<String, IdenticalFiles> = HashMap::new();
+    for ((path, size), hash) in candidates.into_iter().zip(hashes) {
+        match hash {
+            Some(Ok(hash)) => {
+                report.hashed += 1;
+                groups
+                    .entry(hash.clone())
+                    .or_insert_with(|| IdenticalFiles {
+                        hash,
+                        size,
+                        paths: vec![],
+                    })
+                    .paths
+                    .push(path);
+            }
+            Some(Err(e)) => {
+                let detail = ErrorDetail::new(Some(&path), ErrorStage::Read, e.to_string());
+                log.warn(&detail);
+                report.errors.push(detail);
+            }
+            None => report.cancelled = true,
+        }
+    }
+    report.groups = groups.into_values().filter(|g| g.paths.len() > 1).collect();
+    report.groups.sort_by(|a, b| {
+        (b.wasted_bytes().cmp(&a.wasted_bytes())).then_with(|| a.paths.cmp(&b.paths))
+    });
+    report.duplicate_files = report.groups.iter().map(|g| g.paths.len() as u64 - 1).sum();
+    report.wasted_bytes = report.groups.iter().map(IdenticalFiles::wasted_bytes).sum();
+    log.info(format_args!(
+        "Audit finished: {} group(s), {} duplicate file(s), {} wasted{}",
+        report.groups.len(),
+        report.duplicate_files,
+        super::human_bytes(report.wasted_bytes),
+        if report.cancelled { " (cancelled)" } else { "" }
+    ));
+    emit(ProgressEvent {
+        phase: Phase::Done,
+        scanned: report.files,
+        skipped_duplicates: report.duplicate_files,
+        errors: report.errors.len() as u64,
+        percent: 100.0,
+        ..Default::default()
+    });
+    report
+}
+
+/// Hash `files` on up to `workers` threads; `None` for those left when the audit was cancelled.
+fn hash_all(
+    files: &[(PathBuf, u64)],
+    workers: usize,
+    control: &RunControl,
+    emit: Emit,
+    log: &RunLog,
+) -> Vec<Option<std::io::Result<String>>> {
+    let workers = match workers {
+        0 => super::DEFAULT_PARALLEL_WORKERS,
+        n => n,
+    };
+    let next = AtomicUsize::new(0);
+    let meter = Mutex::new(ByteMeter::new(files.iter().map(|(_, size)| size).sum()));
+    let hashes: Vec<Mutex<Option<std::io::Result<String>>>> =
+        files.iter().map(|_| Mutex::new(None)).collect();
+    let work = || loop {
+        let i = next.fetch_add(1, Ordering::Relaxed);
+        let Some((path, size)) = files.get(i) else {
+            break;
+        };
+        if !control.proceed() {
+            break;
+        }
+        {
+            let meter = meter.lock().unwrap_or_else(|e| e.into_inner());
+            emit(ProgressEvent {
+                phase: Phase::IndexingDestination,
+                current_file: Some(path.display().to_string()),
+                percent: meter.fraction().unwrap_or(0.0) * 100.0,
+                paused: control.is_paused(),
+                total_bytes: meter.total,
+                bytes_done: meter.done,
+                current_index: i as u64 + 1,
+                total_count: files.len() as u64,
+                throughput: meter.throughput(),
+                eta_seconds: meter.eta_seconds(),
+                ..Default::default()
+            });
+        }
+        let hash = hasher::hash_file_cancellable(path, control);
+        if hash.is_err() && control.is_cancelled() {
+            break;
+        }
+        log.debug(format_args!("Hashed {}", path.display()));
+        *hashes[i].lock().unwrap_or_else(|e| e.into_inner()) = Some(hash);
+        meter.lock().unwrap_or_else(|e| e.into_inner()).add(*size);
+    };
+    std::thread::scope(|s| {
+        for _ in 1..workers.min(files.len()) {
+            s.spawn(work);
+        }
+        work();
+    });
+    hashes
+        .into_iter()
+        .map(|h| h.into_inner().unwrap_or_else(|e| e.into_inner()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_groups_identical_files_and_touches_nothing() {
+        let dest = tempfile::tempdir().unwrap();
+        let root = dest.path();
+        fs::create_dir_all(root.join("2023/05")).unwrap();
+        fs::write(root.join("IMG_7612.JPG"), b"same bytes").unwrap();
+        fs::write(root.join("2023/05/IMG_7612-1.JPG"), b"same bytes").unwrap();
+        fs::write(root.join("2023/IMG_7612 (1).JPG"), b"same bytes").unwrap();
+        fs::write(root.join("IMG_7608.JPG"), b"twin").unwrap();
+        fs::write(root.join("2023/IMG_7608.PNG"), b"twin").unwrap();
+        // Same size, different content; and a size of its own.
+        fs::write(root.join("IMG_7600.JPG"), b"TWIN").unwrap();
+        fs::write(root.join("IMG_7601.JPG"), b"unique").unwrap();
+
+        let report = audit(root, 2, false, &RunControl::new(), None);
+        assert_eq!((report.files, report.hashed), (7, 6));
+        assert_eq!(report.groups.len(), 2);
+        let first = &report.groups[0];
+        assert_eq!(first.paths.len(), 3);
+        assert_eq!(first.paths[0], root.join("2023/05/IMG_7612-1.JPG"));
+        assert_eq!(report.groups[1].paths.len(), 2);
+        assert_eq!(report.duplicate_files, 3);
+        assert_eq!(report.wasted_bytes, 2 * 10 + 4);
+        assert!(report.errors.is_empty() && !report.cancelled);
+        assert_eq!(fs::read_dir(root).unwrap().count(), 5);
+        assert_eq!(fs::read(root.join("IMG_7612.JPG")).unwrap(), b"same bytes");
+
+        let control = RunControl::new();
+        control.cancel();
+        let report = audit(root, 1, false, &control, None);
+        assert!(report.cancelled && report.groups.is_empty());
+    }
+}
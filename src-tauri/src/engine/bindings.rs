@@ -0,0 +1,64 @@
+//! TypeScript declarations of what the engine sends the app, generated from the Rust types so
+//! that a renamed or retyped field breaks the frontend's type check instead of the UI. The
+//! test below checks `src/bindings.d.ts` against them; `UPDATE_BINDINGS=1 cargo test`
+//! rewrites it, to be committed with the change.
+//!
+//! Counts and sizes are u64, which ts-rs declares as bigint; serde_json sends them as plain
+//! numbers, and none comes near 2^53, so those fields are declared `number`.
+
+use std::fs;
+use std::path::Path;
+
+use ts_rs::TS;
+
+use super::*;
+use crate::mover::ReplacedFile;
+
+/// `T` as an exported declaration, with its doc comment.
+fn declaration<T: TS>() -> String {
+    format!("{}export {}\n", T::DOCS.unwrap_or(""), T::decl())
+}
+
+/// The declarations file, types in a fixed order so it only changes with the types.
+fn declarations() -> String {
+    let types = [
+        declaration::<ProgressEvent>(),
+        declaration::<Phase>(),
+        declaration::<StalledFile>(),
+        declaration::<FileEvent>(),
+        declaration::<FileEventBatch>(),
+        declaration::<RunResult>(),
+        declaration::<ErrorDetail>(),
+        declaration::<ErrorStage>(),
+        declaration::<Abort>(),
+        declaration::<RunStats>(),
+        declaration::<PhaseDurations>(),
+        declaration::<Verification>(),
+        declaration::<FileRecord>(),
+        declaration::<FileAction>(),
+        declaration::<ReplacedFile>(),
+        declaration::<DuplicateGroup>(),
+        declaration::<SuffixCount>(),
+        declaration::<Preflight>(),
+        declaration::<PreflightError>(),
+    ];
+    format!(
+        "// Generated from the Rust types by `UPDATE_BINDINGS=1 cargo test` (src-tauri/src/engine/bindings.rs). Do not edit.\n\n{}",
+        types.join("\n")
+    )
+}
+
+#[test]
+fn test_typescript_bindings_are_up_to_date() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../src/bindings.d.ts");
+    let generated = declarations();
+    if std::env::var_os("UPDATE_BINDINGS").is_some() {
+        fs::write(&path, &generated).unwrap();
+        return;
+    }
+    assert!(
+        fs::read_to_string(&path).ok().as_deref() == Some(generated.as_str()),
+        "{} is out of date; regenerate it with `UPDATE_BINDINGS=1 cargo test` and commit it",
+        path.display()
+    );
+}
@@ -0,0 +1,337 @@
+//! Destination hashes kept between the runs of a session, so that a run into the archive the
+//! last one filled does not read it again. A hash is reused only while the file still has the
+//! size and modification time it was hashed with, so a file another program changed in between
+//! is read again. A destination whose hashes are older than the cache's maximum age is indexed
+//! afresh, and the cache holds a bounded number of hashes, dropping the destinations used
+//! longest ago first.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::index::DestIndex;
+use super::runlog::RunLog;
+use super::verify::PlacedFile;
+use super::RunOptions;
+use crate::mover;
+
+/// How long the hashes of a destination are trusted when nothing says the cache should be
+/// dropped sooner.
+pub const DEFAULT_INDEX_CACHE_MAX_AGE: Duration = Duration::from_secs(30 * 60);
+
+/// Most hashes an `IndexCache` holds, a few hundred bytes each.
+pub const MAX_CACHED_HASHES: usize = 500_000;
+
+/// The destination hashes of a session's runs, by canonical destination folder. Shared by the
+/// runs that have it in `RunOptions::index_cache`; only runs that index the destination by
+/// content (`DedupMode::Content`, not tagging) use it.
+#[derive(Debug)]
+pub struct IndexCache {
+    max_files: usize,
+    inner: Mutex<Cached>,
+}
+
+#[derive(Debug)]
+struct Cached {
+    max_age: Duration,
+    dests: HashMap<PathBuf, CachedDest>,
+}
+
+#[derive(Debug)]
+struct CachedDest {
+    /// When a run last stored or added hashes.
+    stored: Instant,
+    /// By path relative to the destination.
+    files: HashMap<PathBuf, CachedFile>,
+}
+
+/// A destination file's hash, with what the file was like when it was made.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct CachedFile {
+    size: u64,
+    modified: SystemTime,
+    hash: String,
+}
+
+impl Default for IndexCache {
+    fn default() -> Self {
+        IndexCache::new(DEFAULT_INDEX_CACHE_MAX_AGE, MAX_CACHED_HASHES)
+    }
+}
+
+impl IndexCache {
+    pub fn new(max_age: Duration, max_files: usize) -> IndexCache {
+        IndexCache {
+            max_files,
+            inner: Mutex::new(Cached {
+                max_age,
+                dests: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Trust hashes for `max_age` after they were stored; zero keeps none.
+    pub fn set_max_age(&self, max_age: Duration) {
+        self.lock().max_age = max_age;
+    }
+
+    /// Drop every hash: the next run of each destination reads what it needs again.
+    pub fn clear(&self) {
+        self.lock().dests.clear();
+    }
+
+    /// Hashes held, of all destinations.
+    pub fn len(&self) -> usize {
+        self.lock().dests.values().map(|d| d.files.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Cached> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The hashes of `dest`, taken out until the run stores them again; none when they are
+    /// older than the maximum age.
+    fn take(&self, dest: &Path, log: &RunLog) -> HashMap<PathBuf, CachedFile> {
+        let mut cached = self.lock();
+        let max_age = cached.max_age;
+        match cached.dests.remove(&key(dest)) {
+            Some(entry) if entry.stored.elapsed() <= max_age => {
+                log.debug(format_args!(
+                    "{} destination hash(es) of {} known from an earlier run",
+                    entry.files.len(),
+                    dest.display()
+                ));
+                entry.files
+            }
+            Some(_) => {
+                log.info(format_args!(
+                    "The hashes of {} from an earlier run are too old to trust; it is indexed afresh",
+                    dest.display()
+                ));
+                HashMap::new()
+            }
+            None => HashMap::new(),
+        }
+    }
+
+    /// Add `files` to the hashes of `dest`, replacing them with `replace`; then drop the
+    /// destinations used longest ago while there are too many hashes.
+    fn store(&self, dest: &Path, files: HashMap<PathBuf, CachedFile>, replace: bool) {
+        let mut cached = self.lock();
+        let entry = cached.dests.entry(key(dest)).or_insert_with(|| CachedDest {
+            stored: Instant::now(),
+            files: HashMap::new(),
+        });
+        entry.stored = Instant::now();
+        if replace {
+            entry.files = files;
+        } else {
+            entry.files.extend(files);
+        }
+        let mut total: usize = cached.dests.values().map(|d| d.files.len()).sum();
+        while total > self.max_files {
+            let oldest = cached
+                .dests
+                .iter()
+                .min_by_key(|(_, d)| d.stored)
+                .map(|(path, _)| path.clone());
+            let Some(oldest) = oldest else { break };
+            if cached.dests.len() == 1 {
+                // The only destination: keep what fits of it.
+                let files = &mut cached.dests.get_mut(&oldest).unwrap().files;
+                let excess = total - self.max_files;
+                let dropped: Vec<PathBuf> = files.keys().take(excess).cloned().collect();
+                for path in dropped {
+                    files.remove(&path);
+                }
+                break;
+            }
+            total -= cached.dests.remove(&oldest).map_or(0, |d| d.files.len());
+        }
+    }
+}
+
+/// A destination's cache key: its canonical path, so that "photos/" and "./photos" share one.
+fn key(dest: &Path) -> PathBuf {
+    dunce::canonicalize(mover::long_path(dest)).unwrap_or_else(|_| dest.to_path_buf())
+}
+
+/// The hashes of `dest` from an earlier run, by path relative to it, when the run has a cache.
+pub(super) fn known_hashes(
+    run_options: &RunOptions,
+    dest: &Path,
+    log: &RunLog,
+) -> HashMap<PathBuf, CachedFile> {
+    match run_options.cache() {
+        Some(cache) => cache.take(dest, log),
+        None => HashMap::new(),
+    }
+}
+
+/// The hash of the file at `relative` in `known`, if it was made from the file as it is now.
+pub(super) fn reuse(
+    known: &mut HashMap<PathBuf, CachedFile>,
+    relative: &Path,
+    size: u64,
+    modified: Option<SystemTime>,
+) -> Option<String> {
+    let cached = known.remove(relative)?;
+    (cached.size == size && Some(cached.modified) == modified).then_some(cached.hash)
+}
+
+/// Keep the hashes of the destinations' `indexes` (in route order) for the next run.
+pub(super) fn keep_indexes(run_options: &RunOptions, indexes: &[DestIndex]) {
+    let Some(cache) = run_options.cache() else {
+        return;
+    };
+    for (dest, index) in run_options.destinations().zip(indexes) {
+        let files = index
+            .hashed_files()
+            .filter_map(|(file, hash)| {
+                let relative = file.path.strip_prefix(dest).ok()?;
+                let cached = CachedFile {
+                    size: file.size,
+                    modified: file.modified?,
+                    hash: hash.to_string(),
+                };
+                Some((relative.to_path_buf(), cached))
+            })
+            .collect();
+        cache.store(dest, files, true);
+    }
+}
+
+/// Add the files a run put in the destinations to their hashes, so the next run does not read
+/// them either.
+pub(super) fn keep_placed(run_options: &RunOptions, placed: &[PlacedFile]) {
+    let Some(cache) = run_options.cache() else {
+        return;
+    };
+    let mut by_dest: HashMap<&Path, HashMap<PathBuf, CachedFile>> = HashMap::new();
+    for file in placed.iter().filter(|f| !f.hash.is_empty()) {
+        let Some(dest) = run_options
+            .destinations()
+            .find(|d| file.dest.starts_with(d))
+        else {
+            continue;
+        };
+        let Ok(modified) = std::fs::metadata(&file.dest).and_then(|m| m.modified()) else {
+            continue;
+        };
+        let relative = file.dest.strip_prefix(dest).unwrap_or(&file.dest);
+        by_dest.entry(dest).or_default().insert(
+            relative.to_path_buf(),
+            CachedFile {
+                size: file.size,
+                modified,
+                hash: file.hash.clone(),
+            },
+        );
+    }
+    for (dest, files) in by_dest {
+        cache.store(dest, files, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+
+    use super::super::{run, RunControl};
+    use super::*;
+
+    #[test]
+    fn test_a_second_run_reuses_the_destination_hashes() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(dest.path().join("IMG_7608.JPG"), "7608").unwrap();
+        fs::write(dest.path().join("IMG_0001.JPG"), "0001").unwrap();
+        fs::write(dest.path().join("IMG_0002.JPG"), "changed").unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), "7612").unwrap();
+        let cache = Arc::new(IndexCache::default());
+        let options = RunOptions::new(src.path(), dest.path(), "7612 7608 7600")
+            .workers(1)
+            .index_cache(Some(cache.clone()));
+
+        let first = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((first.moved, first.stats.dest_hashed), (1, 2));
+        // Both read, and the moved file added.
+        assert_eq!(cache.len(), 3);
+
+        fs::write(src.path().join("IMG_7608.JPG"), "7608").unwrap();
+        fs::write(src.path().join("IMG_7600.JPG"), "7600").unwrap();
+        let second = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((second.moved, second.skipped_duplicates), (1, 1));
+        assert_eq!((second.stats.dest_hashed, second.stats.dest_reused), (0, 3));
+        assert_eq!(cache.len(), 4);
+
+        // A file changed behind the cache's back is read again.
+        fs::write(dest.path().join("IMG_0001.JPG"), "7609").unwrap();
+        filetime::set_file_mtime(
+            dest.path().join("IMG_0001.JPG"),
+            filetime::FileTime::from_unix_time(1_700_000_000, 0),
+        )
+        .unwrap();
+        fs::write(src.path().join("IMG_7609.JPG"), "7609").unwrap();
+        let options = options.clone().dry_run(true);
+        let options = RunOptions {
+            suffix_input: "7609".to_string(),
+            ..options
+        };
+        let third = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((third.skipped_duplicates, third.stats.dest_hashed), (1, 1));
+
+        cache.set_max_age(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(5));
+        let stale = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!(stale.stats.dest_reused, 0);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_the_cache_keeps_the_destinations_used_last() {
+        let cache = IndexCache::new(DEFAULT_INDEX_CACHE_MAX_AGE, 3);
+        let file = |n: u64| {
+            let cached = CachedFile {
+                size: n,
+                modified: SystemTime::UNIX_EPOCH,
+                hash: n.to_string(),
+            };
+            (PathBuf::from(format!("{}.jpg", n)), cached)
+        };
+        let log = RunLog::new(false);
+        cache.store(Path::new("/archive-a"), (1..=2).map(file).collect(), true);
+        std::thread::sleep(Duration::from_millis(2));
+        cache.store(Path::new("/archive-b"), (3..=4).map(file).collect(), true);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.take(Path::new("/archive-a"), &log).is_empty());
+        cache.store(Path::new("/archive-b"), (5..=9).map(file).collect(), false);
+        assert_eq!(cache.len(), 3);
+        let mut known = cache.take(Path::new("/archive-b"), &log);
+        assert_eq!(known.len(), 3);
+        let (path, kept) = known
+            .iter()
+            .next()
+            .map(|(p, f)| (p.clone(), f.clone()))
+            .unwrap();
+        let modified = Some(SystemTime::UNIX_EPOCH);
+        assert_eq!(reuse(&mut known, &path, kept.size + 1, modified), None);
+        assert!(known.len() == 2 && !known.contains_key(&path));
+        let (path, kept) = known
+            .iter()
+            .next()
+            .map(|(p, f)| (p.clone(), f.clone()))
+            .unwrap();
+        assert_eq!(
+            reuse(&mut known, &path, kept.size, modified),
+            Some(kept.hash)
+        );
+    }
+}
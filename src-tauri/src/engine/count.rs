@@ -0,0 +1,215 @@
+//! Match counts for the suffix box, shown while the suffixes are typed: how many files a run
+//! would take and their bytes. Counts are made against a listing of the sources kept from the
+//! previous count, so each key typed does not walk a large tree again.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::{parse_rules, resolve_sources, MatchBy, RunError, RunOptions, SymlinkPolicy};
+use crate::control::RunControl;
+use crate::scanner::{self, ListedImage};
+
+/// What a run of the options would take, as counted by [`count_matches`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchCount {
+    /// Matches and their bytes, the rejects folder's left out, as `ScanPreview` counts them.
+    pub matched: u64,
+    pub total_bytes: u64,
+    pub excluded: u64,
+    pub skipped_symlinks: u64,
+    pub over_limit: u64,
+    /// Every source was listed already, by an earlier count: nothing was walked for this one.
+    pub cached: bool,
+    /// Stopped by a newer count: the counts are not to be shown.
+    pub cancelled: bool,
+}
+
+/// The image listings of the sources counted last, kept for the next count. Only the last
+/// sources (and way of matching) are kept; `clear` drops them when the folders changed, as
+/// after a run moved files out of them.
+#[derive(Debug, Default)]
+pub struct SourceListings {
+    roots: Mutex<HashMap<ListingKey, Arc<RootListing>>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct ListingKey {
+    root: PathBuf,
+    by_metadata: bool,
+}
+
+#[derive(Debug, Default)]
+struct RootListing {
+    /// Filled by the first count that needs it; the counts after it wait for the walk instead
+    /// of making their own.
+    images: Mutex<Option<Arc<Vec<ListedImage>>>>,
+    /// No longer kept: a walk still making it stops.
+    dropped: AtomicBool,
+}
+
+impl SourceListings {
+    /// Drop every listing: the next count walks the sources again.
+    pub fn clear(&self) {
+        let mut roots = self.roots.lock().unwrap_or_else(|e| e.into_inner());
+        for (_, listing) in roots.drain() {
+            listing.dropped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// The listings of `roots`, dropping those of other sources.
+    fn of(&self, roots: &[PathBuf], by_metadata: bool) -> Vec<Arc<RootListing>> {
+        let keys: Vec<ListingKey> = roots
+            .iter()
+            .map(|root| ListingKey {
+                root: root.clone(),
+                by_metadata,
+            })
+            .collect();
+        let mut kept = self.roots.lock().unwrap_or_else(|e| e.into_inner());
+        kept.retain(|key, listing| {
+            let wanted = keys.contains(key);
+            if !wanted {
+                listing.dropped.store(true, Ordering::Relaxed);
+            }
+            wanted
+        });
+        keys.into_iter()
+            .map(|key| kept.entry(key).or_default().clone())
+            .collect()
+    }
+}
+
+/// Count the files a run of `run_options` would take, with its suffixes (the routes' too),
+/// exclusion globs, matching, symlink policy and limit, from the `listings` of its sources,
+/// listing those not listed yet. Nothing is read beyond metadata, except the camera's names
+/// when matching by metadata. A cancel on `control` makes the count return `cancelled`; it
+/// does not stop a walk that a later count can still use.
+pub fn count_matches(
+    run_options: &RunOptions,
+    listings: &SourceListings,
+    control: &RunControl,
+) -> Result<MatchCount, RunError> {
+    let suffixes: HashSet<u32> = run_options
+        .rules()
+        .zip(parse_rules(run_options)?)
+        .filter(|((input, _), _)| input.is_some())
+        .flat_map(|(_, suffixes)| suffixes)
+        .collect();
+    let roots = resolve_sources(run_options)?;
+    let by_metadata = run_options.match_by == MatchBy::Metadata;
+    let cancelled = MatchCount {
+        cancelled: true,
+        ..Default::default()
+    };
+
+    let mut count = MatchCount {
+        cached: true,
+        ..Default::default()
+    };
+    let mut listed = Vec::with_capacity(roots.len());
+    for (root, listing) in roots.iter().zip(listings.of(&roots, by_metadata)) {
+        if control.is_cancelled() {
+            return Ok(cancelled);
+        }
+        let mut images = listing.images.lock().unwrap_or_else(|e| e.into_inner());
+        let images = match &*images {
+            Some(found) => found.clone(),
+            None => {
+                count.cached = false;
+                let walk = scanner::list_source_images(root, by_metadata, |_| {
+                    !listing.dropped.load(Ordering::Relaxed)
+                });
+                let Some(found) = walk else {
+                    return Ok(cancelled);
+                };
+                images.insert(Arc::new(found)).clone()
+            }
+        };
+        listed.push(images);
+    }
+
+    let options = &run_options.move_options;
+    let limit = run_options.file_limit();
+    for image in listed.iter().flat_map(|images| images.iter()) {
+        if image.suffix(&suffixes, options.normalize_unicode).is_none() {
+            continue;
+        }
+        let name = scanner::match_path(&image.path, options.normalize_unicode);
+        if run_options.exclude_globs.matching(&name).is_some() {
+            count.excluded += 1;
+        } else if image.symlink && options.symlinks == SymlinkPolicy::Skip {
+            count.skipped_symlinks += 1;
+        } else if limit.is_some_and(|limit| count.matched >= limit) {
+            count.over_limit += 1;
+        } else {
+            count.matched += 1;
+            count.total_bytes += image.size;
+        }
+    }
+    if control.is_cancelled() {
+        return Ok(cancelled);
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::exclude::ExcludeGlobs;
+
+    #[test]
+    fn test_counts_reuse_the_listing_until_it_is_cleared() {
+        let src = tempfile::tempdir().unwrap();
+        fs::create_dir(src.path().join("b")).unwrap();
+        fs::write(src.path().join("b/IMG_7612.JPG"), "12345").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), "123").unwrap();
+        fs::write(src.path().join("thumb_7608.JPG"), "1").unwrap();
+        fs::write(src.path().join("IMG_7608.txt"), "not an image").unwrap();
+        let options = RunOptions::new(src.path(), "/unused", "7612, 7608")
+            .exclude_globs(ExcludeGlobs::new(["thumb_*"]).unwrap());
+        let (listings, control) = (SourceListings::default(), RunControl::new());
+
+        let first = count_matches(&options, &listings, &control).unwrap();
+        assert_eq!(
+            (first.matched, first.total_bytes, first.excluded),
+            (2, 8, 1)
+        );
+        assert!(!first.cached && !first.cancelled);
+
+        // Typing on: the files listed are matched again, and a new one is not seen.
+        fs::write(src.path().join("IMG_7600.JPG"), "7600").unwrap();
+        let options = RunOptions {
+            suffix_input: "7612 7600".to_string(),
+            ..options
+        };
+        let second = count_matches(&options, &listings, &control).unwrap();
+        assert_eq!((second.matched, second.total_bytes), (1, 5));
+        assert!(second.cached);
+
+        listings.clear();
+        let third = count_matches(&options.clone().limit(1), &listings, &control).unwrap();
+        assert_eq!(
+            (third.matched, third.over_limit, third.cached),
+            (1, 1, false)
+        );
+        assert_eq!(third.total_bytes, 4);
+
+        let bad = RunOptions {
+            suffix_input: "IMG".to_string(),
+            ..options.clone()
+        };
+        let error = count_matches(&bad, &listings, &control).unwrap_err();
+        assert_eq!(error.kind(), "invalid_suffixes");
+        control.cancel();
+        assert!(
+            count_matches(&options, &listings, &control)
+                .unwrap()
+                .cancelled
+        );
+    }
+}
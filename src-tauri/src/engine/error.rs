@@ -0,0 +1,162 @@
+//! Why a run could not start, or stopped on its own. Failures of single files are not errors
+//! of the run: they are counted in its result, which goes on with the other files until too
+//! many of them in a row suggest the rest would fail too.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::report::{ErrorDetail, ErrorStage};
+use super::resume::ResumeError;
+
+/// A run, plan, watch or resume that stopped before touching any file.
+#[derive(Debug, thiserror::Error)]
+pub enum RunError {
+    #[error("no valid suffixes in \"{input}\"")]
+    InvalidSuffixes { input: String },
+    #[error("no source folder given")]
+    NoSources,
+    #[error("source {} cannot be used: {source}", path.display())]
+    SourceUnavailable { path: PathBuf, source: io::Error },
+    #[error("cannot create destination {}: {source}", path.display())]
+    DestinationUnwritable { path: PathBuf, source: io::Error },
+    #[error(
+        "destinations {} and {} overlap: each route needs a folder of its own",
+        path.display(),
+        other.display()
+    )]
+    DestinationsOverlap { path: PathBuf, other: PathBuf },
+    #[error(
+        "rejects folder {} overlaps {}: it must be apart from the sources and destinations",
+        path.display(),
+        other.display()
+    )]
+    RejectsOverlap { path: PathBuf, other: PathBuf },
+    #[error("cannot scan {}: {source}", path.display())]
+    ScanFailed { path: PathBuf, source: io::Error },
+    #[error("duplicate sources cannot be {action} with duplicate detection off")]
+    DuplicatesWithoutDedup { action: &'static str },
+    #[error("cannot watch for new files: {reason}")]
+    WatcherUnavailable { reason: String },
+    #[error("cannot watch {}: {reason}", path.display())]
+    WatchFailed { path: PathBuf, reason: String },
+    #[error(transparent)]
+    StateUnusable(#[from] ResumeError),
+    #[error("cannot write run state {}: {source}", path.display())]
+    StateUnwritable { path: PathBuf, source: io::Error },
+    #[error("no files selected")]
+    EmptySelection,
+    #[error("selected file {} cannot be used: {source}", path.display())]
+    SelectionUnavailable { path: PathBuf, source: io::Error },
+    #[error("selected file {} is not in any of the source folders", path.display())]
+    NotInSources { path: PathBuf },
+}
+
+impl RunError {
+    /// Stable name of the variant, for the GUI and scripts.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RunError::InvalidSuffixes { .. } => "invalid_suffixes",
+            RunError::NoSources => "no_sources",
+            RunError::SourceUnavailable { .. } => "source_unavailable",
+            RunError::DestinationUnwritable { .. } => "destination_unwritable",
+            RunError::DestinationsOverlap { .. } => "destinations_overlap",
+            RunError::RejectsOverlap { .. } => "rejects_overlap",
+            RunError::ScanFailed { .. } => "scan_failed",
+            RunError::DuplicatesWithoutDedup { .. } => "duplicates_without_dedup",
+            RunError::WatcherUnavailable { .. } => "watcher_unavailable",
+            RunError::WatchFailed { .. } => "watch_failed",
+            RunError::StateUnusable(_) => "state_unusable",
+            RunError::StateUnwritable { .. } => "state_unwritable",
+            RunError::EmptySelection => "empty_selection",
+            RunError::SelectionUnavailable { .. } => "selection_unavailable",
+            RunError::NotInSources { .. } => "not_in_sources",
+        }
+    }
+
+    /// The folder or file the error is about, if there is one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            RunError::InvalidSuffixes { .. }
+            | RunError::NoSources
+            | RunError::EmptySelection
+            | RunError::DuplicatesWithoutDedup { .. }
+            | RunError::WatcherUnavailable { .. } => None,
+            RunError::SourceUnavailable { path, .. }
+            | RunError::DestinationUnwritable { path, .. }
+            | RunError::DestinationsOverlap { path, .. }
+            | RunError::RejectsOverlap { path, .. }
+            | RunError::ScanFailed { path, .. }
+            | RunError::WatchFailed { path, .. }
+            | RunError::StateUnwritable { path, .. }
+            | RunError::SelectionUnavailable { path, .. }
+            | RunError::NotInSources { path } => Some(path),
+            RunError::StateUnusable(e) => Some(e.path()),
+        }
+    }
+
+    /// The error as the report and the final progress event list it.
+    pub fn detail(&self) -> ErrorDetail {
+        let stage = match self {
+            RunError::ScanFailed { .. } => ErrorStage::Scan,
+            _ => ErrorStage::Setup,
+        };
+        ErrorDetail::new(self.path(), stage, self.to_string())
+    }
+}
+
+/// Why a run stopped before attempting all its files, other than a cancel. The files it did
+/// not get to are counted in `RunResult::not_attempted`, and a run with a state file keeps it
+/// to resume from.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Abort {
+    /// `RunOptions::max_consecutive_errors` moves in a row failed the same way, as when the
+    /// destination volume was unmounted; `message` is the last one's error.
+    #[error(
+        "destination {} appears to be unavailable: {failures} moves in a row failed ({message})",
+        dest.display()
+    )]
+    DestinationUnavailable {
+        dest: PathBuf,
+        failures: u32,
+        message: String,
+    },
+    /// `RunOptions::fail_fast`: the file at `path` failed with `message`.
+    #[error("stopped at the first error (fail-fast): {}: {message}", path.display())]
+    FailFast { path: PathBuf, message: String },
+}
+
+impl Abort {
+    /// The destination, or the file the run stopped at.
+    pub fn path(&self) -> &Path {
+        match self {
+            Abort::DestinationUnavailable { dest, .. } => dest,
+            Abort::FailFast { path, .. } => path,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detail_has_the_stage_and_the_path() {
+        let error = RunError::ScanFailed {
+            path: PathBuf::from("/photos"),
+            source: io::Error::new(io::ErrorKind::PermissionDenied, "denied"),
+        };
+        let detail = error.detail();
+        assert_eq!(detail.stage, ErrorStage::Scan);
+        assert_eq!(detail.path.as_deref(), Some(Path::new("/photos")));
+        assert_eq!(detail.message, "cannot scan /photos: denied");
+        assert_eq!(error.kind(), "scan_failed");
+
+        let error = RunError::InvalidSuffixes {
+            input: "abc".to_string(),
+        };
+        assert_eq!(error.detail().stage, ErrorStage::Setup);
+        assert!(error.path().is_none());
+    }
+}
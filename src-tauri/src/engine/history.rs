@@ -0,0 +1,258 @@
+//! A bounded history of past runs, one summary each, kept in a JSON file so a delivery can be
+//! confirmed weeks later: when, from where to where, which suffixes and what came of it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::report::write_atomically;
+use super::{RunError, RunOptions, RunResult};
+
+/// Runs a history file keeps; the oldest go first.
+pub const MAX_HISTORY_RECORDS: usize = 200;
+
+/// What kind of run a record is of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunKind {
+    Run,
+    /// Executing a reviewed plan.
+    Plan,
+    Resume,
+    Watch,
+    /// Moving files picked from a preview (`run_selected`).
+    Selection,
+}
+
+/// How a recorded run ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunOutcome {
+    /// Went through every file; some may have failed (see `errors`).
+    Completed,
+    Cancelled,
+    /// Stopped by itself (see `RunResult::aborted`); `error` says why.
+    Aborted,
+    /// Stopped before moving anything: a setup error or a failed pre-flight check, in `error`.
+    Failed,
+}
+
+/// One past run.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryRecord {
+    /// RFC 3339, local time; `started_at` is empty for a run that failed to start.
+    pub started_at: String,
+    pub finished_at: String,
+    pub kind: RunKind,
+    pub outcome: RunOutcome,
+    pub error: Option<String>,
+    pub sources: Vec<PathBuf>,
+    pub dest: PathBuf,
+    /// The routes' and rejects folder's destinations, after `dest`.
+    #[serde(default)]
+    pub other_dests: Vec<PathBuf>,
+    pub suffix_input: String,
+    pub dry_run: bool,
+    pub matched: u64,
+    pub moved: u64,
+    pub skipped_duplicates: u64,
+    pub rejected: u64,
+    pub errors: u64,
+    pub bytes_moved: u64,
+    pub duration_ms: u64,
+    /// Files the run wrote, for the app to open: the JSON report, the log and the state file
+    /// kept to resume from. A run that failed to start still writes its report and log.
+    pub report_path: Option<PathBuf>,
+    pub log_path: Option<PathBuf>,
+    pub state_path: Option<PathBuf>,
+}
+
+impl HistoryRecord {
+    /// The record of a run of `kind` with `options` that ended with `outcome`.
+    pub fn new(
+        kind: RunKind,
+        options: &RunOptions,
+        outcome: &Result<RunResult, RunError>,
+    ) -> HistoryRecord {
+        let failed = RunResult::default();
+        let result = outcome.as_ref().unwrap_or(&failed);
+        let (ended, error) = match outcome {
+            Err(e) => (RunOutcome::Failed, Some(e.to_string())),
+            Ok(r) if r.preflight_error.is_some() => (
+                RunOutcome::Failed,
+                r.preflight_error.as_ref().map(ToString::to_string),
+            ),
+            Ok(r) if r.cancelled => (RunOutcome::Cancelled, None),
+            Ok(r) => match &r.aborted {
+                Some(abort) => (RunOutcome::Aborted, Some(abort.to_string())),
+                None => (RunOutcome::Completed, None),
+            },
+        };
+        let stats = &result.stats;
+        HistoryRecord {
+            started_at: stats.started_at.clone().unwrap_or_default(),
+            finished_at: stats
+                .finished_at
+                .clone()
+                .unwrap_or_else(|| chrono::Local::now().to_rfc3339()),
+            kind,
+            outcome: ended,
+            error,
+            sources: options.sources.clone(),
+            dest: options.dest.clone(),
+            other_dests: options.destinations().skip(1).map(PathBuf::from).collect(),
+            suffix_input: options.suffix_input.trim().to_string(),
+            dry_run: options.dry_run,
+            matched: result.matched,
+            moved: result.moved,
+            skipped_duplicates: result.skipped_duplicates,
+            rejected: result.rejected,
+            errors: result.errors,
+            bytes_moved: stats.bytes_moved,
+            duration_ms: stats.duration_ms,
+            report_path: match outcome {
+                Ok(r) => r.report_path.clone(),
+                Err(_) => options.report_path.clone(),
+            },
+            log_path: match outcome {
+                Ok(r) => r.log_path.clone(),
+                Err(_) => options.log_path.clone(),
+            },
+            state_path: result.state_path.clone(),
+        }
+    }
+}
+
+/// A page of [`load_history`].
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunHistory {
+    /// Newest first.
+    pub records: Vec<HistoryRecord>,
+    /// Records in the file, of all pages.
+    pub total: usize,
+    /// The file could not be read or parsed; it is started afresh at the next run.
+    pub warning: Option<String>,
+}
+
+/// The records of the history at `path`, newest first, skipping `offset` and at most `limit`
+/// of them (all with `None`). A missing file is an empty history; one that cannot be read is
+/// one too, with the warning.
+pub fn load_history(path: &Path, offset: usize, limit: Option<usize>) -> RunHistory {
+    let (mut records, warning) = match read(path) {
+        Ok(records) => (records, None),
+        Err(warning) => (Vec::new(), Some(warning)),
+    };
+    let total = records.len();
+    records.reverse();
+    let records = records
+        .into_iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+    RunHistory {
+        records,
+        total,
+        warning,
+    }
+}
+
+/// Add `record` to the history at `path`, dropping the oldest past `MAX_HISTORY_RECORDS`.
+/// A history that cannot be parsed is set aside next to it (as `<name>.corrupt`) and started
+/// afresh; the warning saying so is returned.
+pub fn append_history(path: &Path, record: HistoryRecord) -> std::io::Result<Option<String>> {
+    let (mut records, warning) = match read(path) {
+        Ok(records) => (records, None),
+        Err(warning) => {
+            let mut aside = path.as_os_str().to_owned();
+            aside.push(".corrupt");
+            fs::rename(path, &aside)?;
+            let warning = format!(
+                "{}; it was moved to {} and the history started afresh",
+                warning,
+                Path::new(&aside).display()
+            );
+            (Vec::new(), Some(warning))
+        }
+    };
+    records.push(record);
+    let excess = records.len().saturating_sub(MAX_HISTORY_RECORDS);
+    records.drain(..excess);
+    let json = serde_json::to_vec_pretty(&records).map_err(std::io::Error::other)?;
+    write_atomically(path, &json)?;
+    Ok(warning)
+}
+
+/// Remove the history at `path`; one that does not exist is already clear.
+pub fn clear_history(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// The records at `path`, oldest first; the error is a warning to show.
+fn read(path: &Path) -> Result<Vec<HistoryRecord>, String> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Cannot read run history {}: {}", path.display(), e)),
+    };
+    serde_json::from_slice(&data)
+        .map_err(|e| format!("Run history {} is unreadable: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::RunControl;
+
+    #[test]
+    fn test_history_is_bounded_newest_first_and_survives_corruption() {
+        let (src, dest) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        fs::write(src.path().join("IMG_7612.JPG"), "pick").unwrap();
+        let report = dest.path().join("report.json");
+        let options =
+            RunOptions::new(src.path(), dest.path(), " 7612 ").report_path(Some(report.clone()));
+        let moved = super::super::run(&options, &RunControl::new(), None);
+        assert_eq!(moved.as_ref().unwrap().report_path.as_ref(), Some(&report));
+        let record = HistoryRecord::new(RunKind::Run, &options, &moved);
+        assert_eq!((record.outcome, record.moved), (RunOutcome::Completed, 1));
+        assert_eq!(record.report_path, Some(report));
+        assert_eq!(record.suffix_input, "7612");
+        assert!(!record.started_at.is_empty());
+        let bad = options.clone().sources([src.path().join("gone")]);
+        let failed = super::super::run(&bad, &RunControl::new(), None);
+        let failed = HistoryRecord::new(RunKind::Run, &bad, &failed);
+        assert_eq!(failed.outcome, RunOutcome::Failed);
+        assert!(failed.error.as_deref().unwrap().contains("gone"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app/history.json");
+        for _ in 0..MAX_HISTORY_RECORDS {
+            assert_eq!(append_history(&path, record.clone()).unwrap(), None);
+        }
+        append_history(&path, failed.clone()).unwrap();
+        let history = load_history(&path, 0, Some(2));
+        assert_eq!(history.total, MAX_HISTORY_RECORDS);
+        assert_eq!(history.records, [failed.clone(), record.clone()]);
+        assert_eq!(
+            load_history(&path, MAX_HISTORY_RECORDS - 1, None)
+                .records
+                .len(),
+            1
+        );
+
+        fs::write(&path, "{ not a list").unwrap();
+        let history = load_history(&path, 0, None);
+        assert!(history.records.is_empty() && history.warning.is_some());
+        let warning = append_history(&path, record.clone()).unwrap().unwrap();
+        assert!(warning.contains("history.json.corrupt"));
+        assert!(dir.path().join("app/history.json.corrupt").exists());
+        assert_eq!(load_history(&path, 0, None).records, [record]);
+
+        clear_history(&path).unwrap();
+        clear_history(&path).unwrap();
+        assert_eq!(load_history(&path, 0, None).total, 0);
+    }
+}
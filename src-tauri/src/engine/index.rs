@@ -0,0 +1,285 @@
+//! What is already in the destination. Listing it reads only names and sizes; a file is hashed
+//! once a candidate of the same size has to be compared with it, and each size at most once per
+//! run, so a large archive costs little when only a few files are moved into it. Hashes an
+//! earlier run of the session made are not read again (see `IndexCache`).
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use super::runlog::RunLog;
+use crate::control::RunControl;
+use crate::hasher;
+
+/// The destination's images by size, each size hashed on first use.
+#[derive(Debug, Default)]
+pub(super) struct DestIndex {
+    by_size: HashMap<u64, SizeGroup>,
+    files: u64,
+    hashed: AtomicU64,
+    reused: u64,
+}
+
+/// One image listed in the destination.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct DestFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// Its hash, when an earlier run made it from the file as it is now.
+    pub hash: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct SizeGroup {
+    files: Vec<DestFile>,
+    /// Set once the group's files were read: waiting on it keeps other workers from looking
+    /// up a size while it is being hashed.
+    hashes: OnceLock<GroupHashes>,
+}
+
+#[derive(Debug, Default)]
+struct GroupHashes {
+    /// The hash of each of the group's files; `None` for one that could not be read.
+    of_file: Vec<Option<String>>,
+    /// Each hash with the first of the files that has it.
+    first: HashMap<String, usize>,
+}
+
+/// `DestIndex::pending_bytes` of the destinations' `indexes`, for candidates given by route
+/// and size; a destination without an index has nothing to hash.
+pub(super) fn pending_bytes(
+    indexes: &[DestIndex],
+    candidates: impl Iterator<Item = (usize, u64)>,
+) -> u64 {
+    let mut sizes = vec![Vec::new(); indexes.len()];
+    for (route, size) in candidates {
+        if let Some(sizes) = sizes.get_mut(route) {
+            sizes.push(size);
+        }
+    }
+    indexes
+        .iter()
+        .zip(sizes)
+        .map(|(index, sizes)| index.pending_bytes(sizes.into_iter()))
+        .sum()
+}
+
+impl DestIndex {
+    /// Index `files` (path and size each); nothing is read yet.
+    #[cfg(test)]
+    pub fn new(files: impl IntoIterator<Item = (PathBuf, u64)>) -> DestIndex {
+        DestIndex::listed(files.into_iter().map(|(path, size)| DestFile {
+            path,
+            size,
+            modified: None,
+            hash: None,
+        }))
+    }
+
+    /// Index the listed `files`; only those whose hash is not known are read, when needed.
+    /// Empty files are counted but not indexed: they all share one hash, which would make
+    /// every empty candidate a duplicate.
+    pub fn listed(files: impl IntoIterator<Item = DestFile>) -> DestIndex {
+        let mut index = DestIndex::default();
+        for file in files {
+            index.files += 1;
+            if file.size > 0 {
+                index.reused += u64::from(file.hash.is_some());
+                index.by_size.entry(file.size).or_default().files.push(file);
+            }
+        }
+        index
+    }
+
+    /// Images listed in the destination.
+    pub fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// Of those, the ones hashed so far.
+    pub fn hashed(&self) -> u64 {
+        self.hashed.load(Ordering::Relaxed)
+    }
+
+    /// Of those, the ones whose hash was known from an earlier run.
+    pub fn reused(&self) -> u64 {
+        self.reused
+    }
+
+    /// Every file with its hash, where it is known; the others were not needed.
+    pub fn hashed_files(&self) -> impl Iterator<Item = (&DestFile, &str)> {
+        self.by_size.values().flat_map(|group| {
+            let hashes = group.hashes.get().map(|h| &h.of_file);
+            group.files.iter().enumerate().filter_map(move |(i, file)| {
+                let hash = match hashes {
+                    Some(of_file) => of_file.get(i)?.as_ref()?,
+                    None => file.hash.as_ref()?,
+                };
+                Some((file, hash.as_str()))
+            })
+        })
+    }
+
+    /// Bytes still to hash before candidates of `sizes` can be compared; each size counts once.
+    pub fn pending_bytes(&self, sizes: impl Iterator<Item = u64>) -> u64 {
+        let sizes: HashSet<u64> = sizes.collect();
+        sizes
+            .into_iter()
+            .filter_map(|size| Some((size, self.by_size.get(&size)?)))
+            .filter(|(_, group)| group.hashes.get().is_none())
+            .map(|(size, group)| {
+                let unknown = group.files.iter().filter(|f| f.hash.is_none()).count();
+                size * unknown as u64
+            })
+            .sum()
+    }
+
+    /// Hash the destination files of `size` unless that was done; returns the bytes read by
+    /// this call. A file that cannot be read is logged and left out; once `control` is
+    /// cancelled the rest of the group is skipped.
+    pub fn hash_size(&self, size: u64, control: &RunControl, log: &RunLog) -> u64 {
+        let Some(group) = self.by_size.get(&size) else {
+            return 0;
+        };
+        let mut read = 0;
+        group.hashes.get_or_init(|| {
+            let mut hashes = GroupHashes::default();
+            let mut reading = 0;
+            for (i, file) in group.files.iter().enumerate() {
+                let hash = match &file.hash {
+                    Some(hash) => Some(hash.clone()),
+                    // Left unread: `None` like a file that could not be read.
+                    None if control.is_cancelled() => None,
+                    None => {
+                        reading += 1;
+                        match hasher::hash_file_cancellable(&file.path, control) {
+                            Ok(hash) => {
+                                self.hashed.fetch_add(1, Ordering::Relaxed);
+                                read += size;
+                                Some(hash)
+                            }
+                            Err(_) if control.is_cancelled() => None,
+                            Err(e) => {
+                                let path = file.path.display();
+                                log.warn(format_args!("Cannot index {}: {}", path, e));
+                                None
+                            }
+                        }
+                    }
+                };
+                if let Some(hash) = &hash {
+                    hashes.first.entry(hash.clone()).or_insert(i);
+                }
+                hashes.of_file.push(hash);
+            }
+            log.debug(format_args!(
+                "Hashed {} destination file(s) of {} bytes, {} known from an earlier run",
+                reading,
+                size,
+                group.files.iter().filter(|f| f.hash.is_some()).count()
+            ));
+            hashes
+        });
+        read
+    }
+
+    /// The destination file with content `hash` of a file of `size`; `None` until `hash_size`
+    /// was called for `size`.
+    pub fn find(&self, size: u64, hash: &str) -> Option<&Path> {
+        let group = self.by_size.get(&size)?;
+        let first = *group.hashes.get()?.first.get(hash)?;
+        Some(&group.files[first].path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_only_the_needed_size_is_hashed_once() {
+        let dest = tempfile::tempdir().unwrap();
+        let files: Vec<(PathBuf, u64)> =
+            [("a.jpg", "same"), ("b.jpg", "diff"), ("c.jpg", "longer")]
+                .iter()
+                .map(|(name, body)| {
+                    let path = dest.path().join(name);
+                    fs::write(&path, body).unwrap();
+                    (path, body.len() as u64)
+                })
+                .collect();
+        let index = DestIndex::new(files);
+        let (control, log) = (RunControl::new(), RunLog::new(false));
+        assert_eq!(index.files(), 3);
+        assert_eq!(index.pending_bytes([4, 4, 6, 9].into_iter()), 8 + 6);
+
+        let same = hasher::hash_file(&dest.path().join("a.jpg")).unwrap();
+        assert!(index.find(4, &same).is_none());
+        assert_eq!(index.hash_size(4, &control, &log), 8);
+        assert_eq!(
+            index.find(4, &same),
+            Some(dest.path().join("a.jpg").as_path())
+        );
+        assert!(index.find(6, &same).is_none());
+        assert_eq!(index.hash_size(4, &control, &log), 0);
+        assert_eq!(index.hash_size(9, &control, &log), 0);
+        assert_eq!(index.hashed(), 2);
+        assert_eq!(index.pending_bytes([4, 6].into_iter()), 6);
+    }
+
+    #[test]
+    fn test_known_hashes_are_not_read_again() {
+        let dest = tempfile::tempdir().unwrap();
+        let listed = |name: &str, body: &str, hash: Option<&str>| {
+            let path = dest.path().join(name);
+            fs::write(&path, body).unwrap();
+            DestFile {
+                path,
+                size: body.len() as u64,
+                modified: None,
+                hash: hash.map(str::to_string),
+            }
+        };
+        let known = listed("a.jpg", "same", Some("cached"));
+        let unknown = listed("b.jpg", "diff", None);
+        let index = DestIndex::listed([known.clone(), listed("c.jpg", "longer", Some("c"))]);
+        assert_eq!((index.files(), index.reused()), (2, 2));
+        assert_eq!(index.pending_bytes([4, 6].into_iter()), 0);
+        let index = DestIndex::listed([known.clone(), unknown.clone()]);
+        let (control, log) = (RunControl::new(), RunLog::new(false));
+        assert_eq!(index.pending_bytes([4].into_iter()), 4);
+
+        assert_eq!(index.hash_size(4, &control, &log), 4);
+        assert_eq!(index.hashed(), 1);
+        assert_eq!(index.find(4, "cached"), Some(known.path.as_path()));
+        let diff = hasher::hash_file(&unknown.path).unwrap();
+        assert_eq!(index.find(4, &diff), Some(unknown.path.as_path()));
+        let mut hashed: Vec<_> = index
+            .hashed_files()
+            .map(|(f, hash)| (f.path.clone(), hash.to_string()))
+            .collect();
+        hashed.sort();
+        assert_eq!(
+            hashed,
+            [(known.path, "cached".to_string()), (unknown.path, diff)]
+        );
+    }
+
+    #[test]
+    fn test_empty_files_are_never_found() {
+        let dest = tempfile::tempdir().unwrap();
+        let empty = dest.path().join("IMG_7612.JPG");
+        fs::write(&empty, "").unwrap();
+        let index = DestIndex::new([(empty.clone(), 0)]);
+        let (control, log) = (RunControl::new(), RunLog::new(false));
+
+        assert_eq!(index.files(), 1);
+        assert_eq!(index.hash_size(0, &control, &log), 0);
+        assert!(index.find(0, &hasher::hash_file(&empty).unwrap()).is_none());
+        assert_eq!(index.hashed(), 0);
+    }
+}
@@ -0,0 +1,274 @@
+//! Read-only check of where requested frames are: in the sources, in the destination, in both
+//! (and whether the copies are the same) or nowhere. Nothing is created, moved or written; a
+//! destination that does not exist yet is taken as empty rather than created as a run would.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::report::{ErrorDetail, ErrorStage};
+use super::RunError;
+use crate::control::RunControl;
+use crate::exclude::ExcludeGlobs;
+use crate::hasher;
+use crate::scanner;
+use crate::suffix_parser;
+
+/// Where the files of one suffix are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Presence {
+    SourceOnly,
+    DestinationOnly,
+    Both,
+    Missing,
+}
+
+impl Presence {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Presence::SourceOnly => "source only",
+            Presence::DestinationOnly => "destination only",
+            Presence::Both => "both",
+            Presence::Missing => "missing",
+        }
+    }
+}
+
+/// One requested suffix and the images that match it on each side.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameLocation {
+    pub suffix: u32,
+    pub presence: Presence,
+    pub source_files: Vec<PathBuf>,
+    pub dest_files: Vec<PathBuf>,
+    /// With `Presence::Both`: whether every source file has one of the same content in the
+    /// destination. `None` otherwise, or when a file could not be read.
+    pub identical: Option<bool>,
+}
+
+/// Result of [`locate_frames`].
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameAudit {
+    pub sources: Vec<PathBuf>,
+    pub dest: PathBuf,
+    /// The destination is not a folder (yet); nothing can be in it.
+    pub dest_missing: bool,
+    /// Every requested suffix, sorted.
+    pub frames: Vec<FrameLocation>,
+    /// Files that could not be hashed; the frames they belong to have no `identical`.
+    pub errors: Vec<ErrorDetail>,
+    /// Stopped while comparing; the frames not compared yet have no `identical`.
+    pub cancelled: bool,
+}
+
+impl FrameAudit {
+    /// Frames found where `presence` says.
+    pub fn count(&self, presence: Presence) -> u64 {
+        self.frames
+            .iter()
+            .filter(|f| f.presence == presence)
+            .count() as u64
+    }
+
+    /// Of the frames in both places, those whose copies are the same.
+    pub fn identical(&self) -> u64 {
+        self.frames
+            .iter()
+            .filter(|f| f.identical == Some(true))
+            .count() as u64
+    }
+}
+
+/// Find the images of each suffix in `suffix_input` under `sources` and `dest`, with the
+/// scanner's matching, and hash the frames found on both sides to compare them. Only the
+/// destination files of a size some source file of the frame has are read.
+pub fn locate_frames(
+    sources: &[PathBuf],
+    dest: &Path,
+    suffix_input: &str,
+    control: &RunControl,
+) -> Result<FrameAudit, RunError> {
+    let suffixes = suffix_parser::parse_suffixes(suffix_input);
+    if suffixes.is_empty() {
+        return Err(RunError::InvalidSuffixes {
+            input: suffix_input.trim().to_string(),
+        });
+    }
+    if sources.is_empty() {
+        return Err(RunError::NoSources);
+    }
+    let scan = |dir: &Path| {
+        let found = scanner::scan_source_for_suffixes(
+            dir,
+            &suffixes,
+            &ExcludeGlobs::default(),
+            false,
+            true,
+            false,
+            |_| true,
+        );
+        found.map_err(|source| RunError::ScanFailed {
+            path: dir.to_path_buf(),
+            source,
+        })
+    };
+    let mut in_sources: HashMap<u32, Vec<scanner::ImageEntry>> = HashMap::new();
+    for source in sources {
+        if let Err(source_error) = std::fs::read_dir(source) {
+            return Err(RunError::SourceUnavailable {
+                path: source.clone(),
+                source: source_error,
+            });
+        }
+        for entry in scan(source)?.entries {
+            in_sources.entry(entry.suffix).or_default().push(entry);
+        }
+    }
+    let dest_missing = !dest.is_dir();
+    let mut in_dest: HashMap<u32, Vec<scanner::ImageEntry>> = HashMap::new();
+    if !dest_missing {
+        for entry in scan(dest)?.entries {
+            in_dest.entry(entry.suffix).or_default().push(entry);
+        }
+    }
+
+    let mut audit = FrameAudit {
+        sources: sources.to_vec(),
+        dest: dest.to_path_buf(),
+        dest_missing,
+        ..Default::default()
+    };
+    let mut suffixes: Vec<u32> = suffixes.into_iter().collect();
+    suffixes.sort_unstable();
+    for suffix in suffixes {
+        let mut source = in_sources.remove(&suffix).unwrap_or_default();
+        let mut found = in_dest.remove(&suffix).unwrap_or_default();
+        source.sort_by(|a, b| a.path.cmp(&b.path));
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        let presence = match (source.is_empty(), found.is_empty()) {
+            (false, false) => Presence::Both,
+            (false, true) => Presence::SourceOnly,
+            (true, false) => Presence::DestinationOnly,
+            (true, true) => Presence::Missing,
+        };
+        let identical = if presence == Presence::Both && !audit.cancelled {
+            same_copies(&source, &found, control, &mut audit)
+        } else {
+            None
+        };
+        audit.frames.push(FrameLocation {
+            suffix,
+            presence,
+            source_files: source.into_iter().map(|e| e.path).collect(),
+            dest_files: found.into_iter().map(|e| e.path).collect(),
+            identical,
+        });
+    }
+    Ok(audit)
+}
+
+/// Whether each of `source` has a file of the same content among `dest`; `None` if one could
+/// not be read (recorded in `audit`) or the check was cancelled.
+fn same_copies(
+    source: &[scanner::ImageEntry],
+    dest: &[scanner::ImageEntry],
+    control: &RunControl,
+    audit: &mut FrameAudit,
+) -> Option<bool> {
+    let mut hash = |path: &Path| match hasher::hash_file_cancellable(path, control) {
+        Ok(hash) => Some(hash),
+        Err(_) if control.is_cancelled() => {
+            audit.cancelled = true;
+            None
+        }
+        Err(e) => {
+            let detail = ErrorDetail::new(Some(path), ErrorStage::Read, e.to_string());
+            audit.errors.push(detail);
+            None
+        }
+    };
+    let mut dest_hashes: HashMap<&Path, String> = HashMap::new();
+    for file in source {
+        let candidates: Vec<&scanner::ImageEntry> =
+            dest.iter().filter(|d| d.size == file.size).collect();
+        if candidates.is_empty() {
+            return Some(false);
+        }
+        let wanted = hash(&file.path)?;
+        let mut matched = false;
+        for candidate in candidates {
+            let known = match dest_hashes.get(candidate.path.as_path()) {
+                Some(known) => known.clone(),
+                None => {
+                    let known = hash(&candidate.path)?;
+                    dest_hashes.insert(&candidate.path, known.clone());
+                    known
+                }
+            };
+            if known == wanted {
+                matched = true;
+                break;
+            }
+        }
+        if !matched {
+            return Some(false);
+        }
+    }
+    Some(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_frames_are_placed_and_nothing_is_written() {
+        let (src, archive) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let dest = archive.path().join("2024");
+        fs::create_dir_all(dest.join("05")).unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), "pick").unwrap();
+        fs::write(dest.join("05/IMG_7612.JPG"), "pick").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), "edited").unwrap();
+        fs::write(dest.join("IMG_7608.JPG"), "camera").unwrap();
+        fs::write(src.path().join("IMG_7600.JPG"), "new").unwrap();
+        fs::write(dest.join("IMG_7601.JPG"), "archived").unwrap();
+        let control = RunControl::new();
+
+        let audit = locate_frames(
+            &[src.path().to_path_buf()],
+            &dest,
+            "7612, 7608, 7600-7602",
+            &control,
+        )
+        .unwrap();
+        let presence: Vec<(u32, Presence, Option<bool>)> = audit
+            .frames
+            .iter()
+            .map(|f| (f.suffix, f.presence, f.identical))
+            .collect();
+        assert_eq!(
+            presence,
+            [
+                (7600, Presence::SourceOnly, None),
+                (7601, Presence::DestinationOnly, None),
+                (7602, Presence::Missing, None),
+                (7608, Presence::Both, Some(false)),
+                (7612, Presence::Both, Some(true)),
+            ]
+        );
+        assert_eq!(audit.frames[4].dest_files, [dest.join("05/IMG_7612.JPG")]);
+        assert_eq!((audit.count(Presence::Both), audit.identical()), (2, 1));
+        assert!(audit.errors.is_empty() && !audit.dest_missing);
+
+        // A destination that does not exist is not created.
+        let missing = archive.path().join("not yet");
+        let audit = locate_frames(&[src.path().to_path_buf()], &missing, "7612", &control).unwrap();
+        assert!(audit.dest_missing);
+        assert_eq!(audit.frames[0].presence, Presence::SourceOnly);
+        assert!(!missing.exists());
+        assert_eq!(fs::read_dir(src.path()).unwrap().count(), 3);
+    }
+}
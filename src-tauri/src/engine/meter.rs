@@ -0,0 +1,225 @@
+//! Byte progress for a phase: totals, throughput over a sliding window, and ETA.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// How far back throughput is measured.
+const WINDOW: Duration = Duration::from_secs(10);
+
+/// Throughput is reported only once this much time has been measured.
+const MIN_SPAN: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub(super) struct ByteMeter {
+    pub total: u64,
+    pub done: u64,
+    /// (time, `done` at that time); the front is the oldest sample still inside the window.
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl Default for ByteMeter {
+    fn default() -> Self {
+        ByteMeter::new(0)
+    }
+}
+
+impl ByteMeter {
+    pub fn new(total: u64) -> ByteMeter {
+        ByteMeter::started_at(total, Instant::now())
+    }
+
+    fn started_at(total: u64, now: Instant) -> ByteMeter {
+        ByteMeter {
+            total,
+            done: 0,
+            samples: VecDeque::from([(now, 0)]),
+        }
+    }
+
+    pub fn add(&mut self, bytes: u64) {
+        self.add_at(bytes, Instant::now());
+    }
+
+    fn add_at(&mut self, bytes: u64, now: Instant) {
+        self.done += bytes;
+        self.samples.push_back((now, self.done));
+        // Keep one sample at or before the window start as the baseline.
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Bytes per second over the window; 0 until enough time has passed.
+    pub fn throughput(&self) -> f64 {
+        self.throughput_at(Instant::now())
+    }
+
+    fn throughput_at(&self, now: Instant) -> f64 {
+        let Some(&(since, base)) = self.samples.front() else {
+            return 0.0;
+        };
+        let span = now.duration_since(since);
+        if span < MIN_SPAN {
+            return 0.0;
+        }
+        (self.done - base) as f64 / span.as_secs_f64()
+    }
+
+    /// Seconds left at the current throughput, once there is one.
+    pub fn eta_seconds(&self) -> Option<u64> {
+        self.eta_at(Instant::now())
+    }
+
+    fn eta_at(&self, now: Instant) -> Option<u64> {
+        let rate = self.throughput_at(now);
+        (rate > 0.0).then(|| (self.total.saturating_sub(self.done) as f64 / rate).ceil() as u64)
+    }
+
+    /// Fraction done by bytes, or `None` when there are no bytes to count.
+    pub fn fraction(&self) -> Option<f64> {
+        (self.total > 0).then(|| (self.done as f64 / self.total as f64).min(1.0))
+    }
+}
+
+/// Decimal units, as Finder and Explorer show them: "3.1 MB", "18.7 GB".
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// `path` as is when it takes at most `max_width` terminal columns, else its start and end
+/// around "...": the file name is kept whole when it fits, along with as much of the folders
+/// just above it as of the start. Cuts fall between characters (wide ones count twice), never
+/// between a letter and its combining accents.
+pub fn shorten_path(path: &str, max_width: usize) -> String {
+    if path.width() <= max_width {
+        return path.to_string();
+    }
+    let budget = max_width.saturating_sub(3);
+    let name = path.rfind(['/', '\\']).map_or(path, |i| &path[i..]);
+    let head = front(path, (budget - name.width().min(budget)) / 2);
+    let tail = back(path, budget - head.width());
+    format!("{}...{}", head, tail)
+}
+
+/// The longest start of `text` at most `width` columns wide.
+fn front(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    let mut end = 0;
+    for (i, c) in text.char_indices() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width {
+            break;
+        }
+        used += w;
+        end = i + c.len_utf8();
+    }
+    &text[..end]
+}
+
+/// The longest end of `text` at most `width` columns wide that does not begin with the
+/// combining marks of a letter left out.
+fn back(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    let mut start = text.len();
+    for (i, c) in text.char_indices().rev() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width {
+            break;
+        }
+        used += w;
+        start = i;
+    }
+    text[start..].trim_start_matches(|c: char| c.width() == Some(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throughput_and_eta_over_window() {
+        const MB: u64 = 1_000_000;
+        let t0 = Instant::now();
+        let mut meter = ByteMeter::started_at(400 * MB, t0);
+        assert_eq!(meter.eta_at(t0), None);
+
+        meter.add_at(100 * MB, t0 + Duration::from_secs(2));
+        meter.add_at(100 * MB, t0 + Duration::from_secs(4));
+        let now = t0 + Duration::from_secs(4);
+        assert_eq!(meter.throughput_at(now), 50.0 * MB as f64);
+        assert_eq!(meter.eta_at(now), Some(4));
+        assert_eq!(meter.fraction(), Some(0.5));
+
+        // Older samples drop out; the last one before the window stays as the baseline.
+        meter.add_at(100 * MB, t0 + Duration::from_secs(30));
+        let now = t0 + Duration::from_secs(30);
+        assert_eq!(meter.throughput_at(now), 100.0 * MB as f64 / 26.0);
+        assert_eq!(meter.done, 300 * MB);
+    }
+
+    /// Paths with multi-byte, wide and combining characters, shortened to every width from
+    /// too narrow for "..." to wider than the path.
+    #[test]
+    fn test_shorten_path_is_char_safe_at_every_width() {
+        let paths = [
+            "/Volumes/CARD/São Paulo/2024/IMG_7612.JPG",
+            // "ã" as "a" and a combining tilde, as macOS spells it.
+            "/Volumes/CARD/Sa\u{303}o Paulo/2024/IMG_7612.JPG",
+            "/写真/東京/二〇二四年/旅行/IMG_7612.JPG",
+            "/Users/me/📷 Picks/🌅 Sunset/IMG_7612.JPG",
+            "C:\\Photos\\Zürich\\Café\\IMG_7612.JPG",
+        ];
+        for path in paths {
+            let name = path.rfind(['/', '\\']).map(|i| &path[i..]).unwrap();
+            for max in 0..path.width() + 2 {
+                let short = shorten_path(path, max);
+                if path.width() <= max {
+                    assert_eq!(short, path);
+                    continue;
+                }
+                assert!(short.contains("..."), "{:?} at {}", short, max);
+                assert!(short.width() <= max.max(3), "{:?} at {}", short, max);
+                // Wide characters may leave one column unused, no more.
+                assert!(short.width() + 1 >= max.max(3), "{:?} at {}", short, max);
+                if name.width() + 3 <= max {
+                    assert!(short.ends_with(name), "{:?} at {}", short, max);
+                }
+                // A combining tilde is only ever kept with its "a".
+                let marks = short.matches('\u{303}').count();
+                assert_eq!(marks, short.matches("a\u{303}").count(), "{:?}", short);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shorten_path_keeps_start_and_name() {
+        let path = "/Volumes/CARD/DCIM/100CANON/São Paulo/IMG_7612.JPG";
+        assert_eq!(shorten_path(path, 60), path);
+        assert_eq!(
+            shorten_path(path, 40),
+            "/Volumes/CAR...ON/São Paulo/IMG_7612.JPG"
+        );
+        // The byte offset 57 from the end of this one falls inside "ã".
+        let long = format!("/Volumes/CARD/São{}/IMG_7612.JPG", "x".repeat(42));
+        assert!(!long.is_char_boundary(long.len() - 57));
+        let short = shorten_path(&long, 60);
+        assert!(short.ends_with("/IMG_7612.JPG") && short.width() == 60);
+        // A name too long for the width keeps its end, the extension.
+        assert_eq!(shorten_path("/a/bcdefghij.JPG", 10), "...hij.JPG");
+        // Wide characters count two columns: one is left out rather than overflow.
+        assert_eq!(shorten_path("/東京/写真.JPG", 9), "...真.JPG");
+        assert_eq!(shorten_path("/東京/写真.JPG", 8), "....JPG");
+    }
+}
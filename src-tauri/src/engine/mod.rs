@@ -1,301 +1,3238 @@
-//! Core engine: scan source, index destination, move matching files with progress and cancellation.
+//! Core engine: scan source, index destination, plan and move matching files with progress and
+//! cancellation.
 
 use std::collections::HashSet;
-use std::path::Path;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use crate::hasher;
+use crate::cleanup;
 use crate::mover;
-use crate::scanner;
+use crate::scanner::{self, ScanCount};
 use crate::suffix_parser;
+use index::{DestFile, DestIndex};
+use runlog::RunLog;
+use stats::RunClock;
+
+mod audit;
+#[cfg(test)]
+mod bindings;
+mod cache;
+mod count;
+mod error;
+mod history;
+mod index;
+mod locate;
+mod meter;
+mod options;
+mod percent;
+mod plan;
+mod pool;
+mod preflight;
+mod preview;
+mod report;
+mod resume;
+mod runlog;
+mod select;
+mod stats;
+mod undo;
+mod validate;
+mod verify;
+mod volume;
+mod watch;
+
+pub use crate::collision::{CollisionPattern, PatternError};
+pub use crate::control::{ControlState, RunBusy, RunControl, RunSlot};
+pub use crate::exclude::{ExcludeGlobs, GlobError};
+pub use crate::mover::{
+    CollisionMode, DestLayout, DuplicateAction, EmptyFilePolicy, MoveOptions, ReplacedFile,
+    SymlinkPolicy, TransferMethod, TransferMode,
+};
+pub use crate::path_input::{normalize_path, PathInputError};
+pub use crate::sanitize::SanitizeMode;
+pub use crate::tag::{NameTag, TagError};
+pub use crate::template::{DestTemplate, TemplateError};
+pub use audit::{audit, AuditReport, IdenticalFiles};
+pub use cache::{IndexCache, DEFAULT_INDEX_CACHE_MAX_AGE, MAX_CACHED_HASHES};
+pub use count::{count_matches, MatchCount, SourceListings};
+pub use error::{Abort, RunError};
+pub use history::{
+    append_history, clear_history, load_history, HistoryRecord, RunHistory, RunKind, RunOutcome,
+    MAX_HISTORY_RECORDS,
+};
+pub use locate::{locate_frames, FrameAudit, FrameLocation, Presence};
+pub use meter::{human_bytes, shorten_path};
+pub use options::{
+    DedupMode, MatchBy, Route, RunOptions, VerifyMode, DEFAULT_MAX_CONSECUTIVE_ERRORS,
+    DEFAULT_STALL_WARNING_SECONDS, DEFAULT_WATCH_MIN_AGE_SECONDS,
+};
+pub use plan::{execute, plan, MovePlan, PlannedAction, PlannedFile};
+pub use pool::DEFAULT_PARALLEL_WORKERS;
+pub use preflight::{Preflight, PreflightError};
+pub use preview::{preview_scan, PreviewEntry, ScanPreview, DEFAULT_PREVIEW_ENTRIES};
+pub use report::{read_report, write_atomically, ReportError};
+pub use report::{
+    DuplicateGroup, ErrorDetail, ErrorStage, FileAction, FileRecord, ReportOptions, RunReport,
+    SuffixCount, MAX_FILE_RECORDS,
+};
+pub use resume::{load_state, resume, ResumeError, SavedRun, SavedRunSummary};
+pub use stats::{human_duration, PhaseDurations, RunStats};
+pub use undo::{undo, UndoResult};
+pub use validate::{validate_inputs, Finding, FindingKind, Severity};
+pub use verify::{verify_report, ReportCheck, Verification};
+pub use volume::{volume_info, VolumeError, VolumeInfo};
+pub use watch::watch;
 
 /// Progress phase for UI/CLI.
-#[derive(Clone, serde::Serialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
 #[serde(rename_all = "snake_case")]
 pub enum Phase {
+    #[default]
     ScanningSource,
     IndexingDestination,
+    /// Hashing the matches and working out where each one goes; a dry-run ends here.
+    Planning,
     Moving,
+    /// Checking the moved files at their destination (`RunOptions::verify`).
+    Verifying,
+    /// Watch mode, between batches: waiting for new files. Counts are totals since the start.
+    Watching,
     Done,
 }
 
 /// Progress event payload for frontend.
-#[derive(Clone, serde::Serialize)]
+#[derive(Clone, Default, serde::Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct ProgressEvent {
     pub phase: Phase,
     pub current_file: Option<String>,
+    /// Files the scan of the sources looked at so far, and the images among them (see
+    /// `RunResult::scanned`); they go up while scanning and stay put after.
+    #[cfg_attr(test, ts(type = "number"))]
     pub scanned: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub images: u64,
+    #[cfg_attr(test, ts(type = "number"))]
     pub matched: u64,
+    #[cfg_attr(test, ts(type = "number"))]
     pub moved: u64,
+    #[cfg_attr(test, ts(type = "number"))]
     pub skipped_duplicates: u64,
+    /// Files moved to the rejects folder (see `RunResult::rejected`).
+    #[cfg_attr(test, ts(type = "number"))]
+    pub rejected: u64,
+    /// See `RunResult::replaced`, `RunResult::skipped_newer`, `RunResult::tagged` and
+    /// `RunResult::read_only`.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub replaced: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub skipped_newer: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub tagged: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub read_only: u64,
+    #[cfg_attr(test, ts(type = "number"))]
     pub errors: u64,
     pub percent: f64,
+    /// Set while the run is paused; counts and phase are those at the time of pausing.
+    pub paused: bool,
+    /// Bytes this phase goes through: destination files while indexing, candidates while
+    /// planning and moving.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub total_bytes: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub bytes_done: u64,
+    /// 1-based position of the current file among the phase's `total_count` files. Files for
+    /// the rejects folder come after the matches and are not counted in either.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub current_index: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub total_count: u64,
+    /// Bytes per second over the last few seconds; 0 until measured.
+    pub throughput: f64,
+    /// Estimated seconds left in the phase, once there is a throughput.
+    #[cfg_attr(test, ts(type = "number | null"))]
+    pub eta_seconds: Option<u64>,
+    /// An error that just happened; only the first `MAX_PROBLEM_EVENTS` errors and warnings
+    /// of a run are sent.
+    pub error: Option<ErrorDetail>,
+    pub warning: Option<String>,
+    /// A file that has been worked on longer than the run's stall warning; sent again each
+    /// time that much more has passed. Not counted against `MAX_PROBLEM_EVENTS`.
+    pub stalled: Option<StalledFile>,
+    /// In the final event: errors and warnings that were not sent; the result has them all.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub problems_not_sent: u64,
+    /// In the final event: the run was cancelled; `current_index` of the `total_count` files
+    /// were processed.
+    pub cancelled: bool,
+    /// In the final event: matches left out by `RunOptions::limit`; they are not in `matched`.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub over_limit: u64,
+    /// In the final event: the result's `hash_mismatches`.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub hash_mismatches: u64,
+    /// In the final event: why the run stopped by itself, with `current_index` of the
+    /// `total_count` files processed.
+    pub aborted: Option<Abort>,
+    /// In the final event: the result's timing and byte counts.
+    pub stats: Option<RunStats>,
+    /// Requested suffixes no file matched, in the event after scanning (with the warning) and
+    /// in the final one.
+    pub unmatched_suffixes: Vec<u32>,
+    /// In the final event: the result's check after moving, if there was one.
+    pub verification: Option<Verification>,
+    /// A file just moved, skipped as a duplicate or failed, for a live activity log: sent in
+    /// an event of its own, with the counts after it. Not while planning (nor in a dry-run).
+    pub file: Option<FileEvent>,
+}
+
+/// A file still being read or written after a while, as in "still copying X (2 min)".
+#[derive(Clone, Debug, serde::Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct StalledFile {
+    pub path: String,
+    /// Time spent on the file so far, not counting time paused.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub elapsed_seconds: u64,
+}
+
+/// What just happened to one file of a run (see `ProgressEvent::file`).
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FileEvent {
+    /// Put at `dest`; also when copied there but its source could not be removed.
+    Moved {
+        source: PathBuf,
+        dest: PathBuf,
+    },
+    /// Not moved: its content is already at `duplicate_of` (in the destination, or on its way
+    /// there from another file of the run); `None` when that is not known.
+    #[serde(rename_all = "camelCase")]
+    Duplicate {
+        source: PathBuf,
+        duplicate_of: Option<PathBuf>,
+    },
+    Error {
+        source: PathBuf,
+        message: String,
+    },
+}
+
+impl FileEvent {
+    /// Name of the event the app sends for it: "file-moved", "file-duplicate" or "file-error".
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            FileEvent::Moved { .. } => "file-moved",
+            FileEvent::Duplicate { .. } => "file-duplicate",
+            FileEvent::Error { .. } => "file-error",
+        }
+    }
+}
+
+/// File events of one kind sent at once (see `FileEventQueue`), tagged with the run they are
+/// of so a log can tell runs apart.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct FileEventBatch {
+    #[cfg_attr(test, ts(type = "number"))]
+    pub run_id: u64,
+    /// In the order they happened.
+    pub files: Vec<FileEvent>,
+}
+
+/// Least time between two sends of a `FileEventQueue`.
+pub const FILE_EVENT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// File events a `FileEventQueue` holds before it sends them whatever the time.
+pub const MAX_FILE_EVENT_BATCH: usize = 500;
+
+/// Collects the file events of a run's progress events, so that thousands of files a second
+/// make a few batches rather than thousands of messages: what is waiting goes out at most
+/// every `FILE_EVENT_INTERVAL` (or once `MAX_FILE_EVENT_BATCH` wait), checked on every
+/// progress event, and all of it with the final one or when watch mode starts waiting.
+#[derive(Debug)]
+pub struct FileEventQueue {
+    run_id: u64,
+    waiting: Vec<FileEvent>,
+    sent: Option<Instant>,
+}
+
+impl FileEventQueue {
+    pub fn new(run_id: u64) -> FileEventQueue {
+        FileEventQueue {
+            run_id,
+            waiting: Vec::new(),
+            sent: None,
+        }
+    }
+
+    /// Take the file of `event`, if it has one; returns the batches due, one for each kind of
+    /// event that has files waiting, in the order of `FileEvent::event_name`.
+    pub fn push(&mut self, event: &ProgressEvent, now: Instant) -> Vec<FileEventBatch> {
+        self.waiting.extend(event.file.clone());
+        let last = matches!(event.phase, Phase::Done | Phase::Watching);
+        let due = self
+            .sent
+            .is_none_or(|sent| now.duration_since(sent) >= FILE_EVENT_INTERVAL);
+        if self.waiting.is_empty() || !(last || due || self.waiting.len() >= MAX_FILE_EVENT_BATCH) {
+            return Vec::new();
+        }
+        self.sent = Some(now);
+        let mut batches: Vec<FileEventBatch> = Vec::new();
+        for file in self.waiting.drain(..) {
+            match batches
+                .iter_mut()
+                .find(|b| b.files[0].event_name() == file.event_name())
+            {
+                Some(batch) => batch.files.push(file),
+                None => batches.push(FileEventBatch {
+                    run_id: self.run_id,
+                    files: vec![file],
+                }),
+            }
+        }
+        batches.sort_by_key(|b| b.files[0].event_name());
+        batches
+    }
+}
+
+/// Least time between two progress events a `ProgressThrottle` lets through, but for those it
+/// never holds back.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Progress events a `ProgressThrottle` drops in a row before it lets one through whatever
+/// the time.
+pub const MAX_PROGRESS_DROPPED: u64 = 1000;
+
+/// Thins out a run's progress events for a frontend, which gains nothing from hundreds a
+/// second: every event carries the counts so far, so dropping some in between loses nothing.
+/// One goes through at most every `PROGRESS_INTERVAL` (or after `MAX_PROGRESS_DROPPED`
+/// dropped ones), and these always at once: the first, those of a new phase, pausing or
+/// resuming, those with an error, a warning, a stalled file or unmatched suffixes, and the
+/// final one (`Phase::Done`, or `Phase::Watching` between batches).
+#[derive(Debug, Default)]
+pub struct ProgressThrottle {
+    sent: Option<(Instant, Phase, bool)>,
+    dropped: u64,
 }
 
+impl ProgressThrottle {
+    pub fn new() -> ProgressThrottle {
+        ProgressThrottle::default()
+    }
+
+    /// Whether to send `event` now.
+    pub fn admit(&mut self, event: &ProgressEvent, now: Instant) -> bool {
+        let urgent = matches!(event.phase, Phase::Done | Phase::Watching)
+            || event.error.is_some()
+            || event.warning.is_some()
+            || event.stalled.is_some()
+            || !event.unmatched_suffixes.is_empty();
+        let admit = match self.sent {
+            None => true,
+            Some((at, phase, paused)) => {
+                urgent
+                    || phase != event.phase
+                    || paused != event.paused
+                    || now.duration_since(at) >= PROGRESS_INTERVAL
+                    || self.dropped >= MAX_PROGRESS_DROPPED
+            }
+        };
+        if admit {
+            self.sent = Some((now, event.phase, event.paused));
+            self.dropped = 0;
+        } else {
+            self.dropped += 1;
+        }
+        admit
+    }
+}
+
+/// Most errors and warnings sent with progress events in one run.
+pub const MAX_PROBLEM_EVENTS: u64 = 200;
+
 /// Result of a single run.
-#[derive(Clone, Default, serde::Serialize)]
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct RunResult {
+    /// Files the scan of the sources looked at, of any type, and how many of them have an image
+    /// extension; `matched` are among those. In watch mode, the count of the first scan.
+    #[cfg_attr(test, ts(type = "number"))]
     pub scanned: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub images: u64,
+    /// With `MatchBy::Metadata`: the images matched by the frame name in their metadata, and
+    /// those that had none or could not be read, matched by name.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub numbered: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub unnumbered: u64,
+    #[cfg_attr(test, ts(type = "number"))]
     pub matched: u64,
+    #[cfg_attr(test, ts(type = "number"))]
     pub moved: u64,
+    /// With `CollisionMode::Update`: files moved over an older file of the same name (not in
+    /// `moved`), and files left alone because the one holding their name was not older.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub replaced: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub skipped_newer: u64,
+    /// With `MoveOptions::tag`: files renamed with the tag in their own folder. Nothing is
+    /// moved then, so `moved` stays 0.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub tagged: u64,
+    #[cfg_attr(test, ts(type = "number"))]
     pub skipped_duplicates: u64,
+    /// Duplicate source files deleted or trashed (predicted in dry-run).
+    #[cfg_attr(test, ts(type = "number"))]
+    pub duplicates_removed: u64,
+    /// Empty source directories removed after moving (predicted in dry-run).
+    #[cfg_attr(test, ts(type = "number"))]
+    pub removed_dirs: u64,
+    /// Cross-volume copies re-hashed and confirmed before their source was deleted.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub verified: u64,
+    /// Files copied to the destination whose source could not be removed (e.g. read-only media).
+    #[cfg_attr(test, ts(type = "number"))]
+    pub source_kept: u64,
+    /// Destination names changed to suit exFAT/FAT32/SMB (predicted in dry-run).
+    #[cfg_attr(test, ts(type = "number"))]
+    pub sanitized: u64,
+    /// Files made read-only in the destination, with `RunOptions::read_only` (predicted in
+    /// dry-run); one the filesystem kept writable is a warning instead.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub read_only: u64,
+    #[cfg_attr(test, ts(type = "number"))]
     pub errors: u64,
+    /// What each error was, in the order they happened.
+    pub error_details: Vec<ErrorDetail>,
+    /// Files matching a suffix that were left out by the exclusion globs.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub excluded: u64,
+    /// Matches that are symbolic links, left where they are with `SymlinkPolicy::Skip`.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub skipped_symlinks: u64,
+    /// Symbolic links whose name matched but which point to nothing; each is a warning.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub broken_links: u64,
+    /// `RunOptions::limit`, when one was set.
+    #[cfg_attr(test, ts(type = "number | null"))]
+    pub limit: Option<u64>,
+    /// Matches past that limit, left for a later run; they are not in `matched`.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub over_limit: u64,
+    /// A run of selected files (`run_selected`): those the scan did not take as matches, left
+    /// where they are.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub selected_unmatched: u64,
+    /// Extensions (".CR3") of the files whose name matched but whose type is not an image
+    /// one; how many of each suffix is in `suffixes`.
+    pub other_type_extensions: Vec<String>,
+    /// Planned files left alone because they changed or disappeared before they were moved.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub skipped_changed: u64,
+    /// Matches of zero bytes, each warned about; `MoveOptions::empty_files` says whether they
+    /// were moved (and are in `moved` too), skipped or counted in `errors`.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub empty_files: u64,
+    /// Files skipped because they were modified within `RunOptions::min_age`; they are in
+    /// `busy_files` and get moved by a later run.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub skipped_busy: u64,
+    pub busy_files: Vec<PathBuf>,
+    /// Duplicates that copied another file of this run rather than destination content.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub source_duplicates: u64,
+    /// Files whose hash matched other content but whose bytes did not (or could not be
+    /// compared), with `RunOptions::confirm_duplicates`; they were moved instead of skipped.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub hash_mismatches: u64,
+    /// With `RunOptions::rejects`: images that matched no suffix, taken for the rejects
+    /// folder. They are not in `matched`, nor in `moved` or `skipped_duplicates`.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub rejects_found: u64,
+    /// Of those, the ones moved to the rejects folder, and the ones whose content was there
+    /// already (handled like any duplicate).
+    #[cfg_attr(test, ts(type = "number"))]
+    pub rejected: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub rejected_duplicates: u64,
+    /// Those in-run duplicates grouped under the file that was moved (from the kept records).
+    pub duplicate_groups: Vec<DuplicateGroup>,
+    /// Matched, moved and duplicate files of each requested suffix, sorted by suffix.
+    pub suffixes: Vec<SuffixCount>,
+    /// Whether this was a dry-run; `files` then describes what would have happened.
+    pub dry_run: bool,
+    /// Per-file actions, up to `MAX_FILE_RECORDS`.
+    pub files: Vec<FileRecord>,
+    /// Files left out of `files` because of the cap.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub files_omitted: u64,
+    /// The run was cancelled before it finished; the counts cover what was done until then.
+    pub cancelled: bool,
+    /// Cancelled or aborted runs: matched files that were never attempted; like `matched`, it
+    /// leaves out the files for the rejects folder.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub not_attempted: u64,
+    /// Why the run stopped by itself before attempting every file; not a cancel.
+    pub aborted: Option<Abort>,
+    /// Non-fatal problems, e.g. folders that could not be cleaned up or a report that failed.
+    pub warnings: Vec<String>,
+    /// What the checks before moving found: bytes to write, free space, cross-volume roots.
+    pub preflight: Option<Preflight>,
+    /// Set when those checks stopped the run before anything was moved.
+    pub preflight_error: Option<PreflightError>,
+    /// The log file this run wrote, when one was asked for and could be opened.
+    pub log_path: Option<PathBuf>,
+    /// The JSON report of this run, when one was asked for and could be written.
+    pub report_path: Option<PathBuf>,
+    /// When the run started and finished, how long each phase took and how many bytes it
+    /// moved; for a cancelled run, up to the point it stopped.
+    pub stats: RunStats,
+    /// The check after moving, when `RunOptions::verify` asked for one; its failures are also
+    /// counted in `errors`.
+    pub verification: Option<Verification>,
+    /// The state file to resume this run from (`resume`), kept because it was cancelled.
+    pub state_path: Option<PathBuf>,
+    /// Resumed runs: files done before the interruption, which the other counts leave out.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub resumed: u64,
+    /// Run with `DedupMode::Off`: no file was checked for duplicates, so none were skipped.
+    pub dedup_disabled: bool,
 }
 
 /// Callback for progress (GUI: emit event; CLI: print).
 pub type ProgressFn = Box<dyn Fn(ProgressEvent) + Send>;
 
-/// Run the move operation. If `dry_run` is true, no files are moved.
-/// `cancel` is checked periodically; when true, the run stops gracefully.
+/// Run the move operation described by `run_options`: [`plan`] it, then [`execute`] the plan.
+/// With `dry_run` set, only the plan is made and its preview returned; no files are moved.
+/// `control` is checked before each file and between copy chunks: while it is paused the run
+/// waits, and once it is cancelled the run stops gracefully.
 /// `progress` is called with updates; in CLI mode it can print to stdout.
+/// With `report_path` set, a JSON report is written when the run ends; with `log_path` set,
+/// every action, warning and error is logged to that file as it happens. The options'
+/// `throttle` is set on `control`, where it can be changed while the run goes on.
+/// A run that cannot start (bad suffixes, a missing source, an unwritable destination, a
+/// failed scan) returns the error; failures of single files are counted in the result.
 pub fn run(
-    source_dir: &Path,
-    dest_dir: &Path,
-    suffix_input: &str,
-    dry_run: bool,
-    verbose: bool,
-    cancel: &AtomicBool,
+    run_options: &RunOptions,
+    control: &RunControl,
     progress: Option<ProgressFn>,
-) -> RunResult {
-    let suffixes = suffix_parser::parse_suffixes(suffix_input);
-    if suffixes.is_empty() {
-        let ev = ProgressEvent {
-            phase: Phase::Done,
-            current_file: None,
-            scanned: 0,
-            matched: 0,
-            moved: 0,
-            skipped_duplicates: 0,
-            errors: 1,
-            percent: 100.0,
+) -> Result<RunResult, RunError> {
+    run_with(run_options, None, control, progress)
+}
+
+/// [`run`] for the files of `selection` only, as picked from a preview: the sources are
+/// scanned and the files planned and moved as in a whole run, but matches that are not
+/// selected are left alone. Every selected file must still exist in one of the sources, or the
+/// run does not start; those that are no longer matches are counted in
+/// `RunResult::selected_unmatched` and left alone too.
+pub fn run_selected(
+    run_options: &RunOptions,
+    selection: &[PathBuf],
+    control: &RunControl,
+    progress: Option<ProgressFn>,
+) -> Result<RunResult, RunError> {
+    run_with(run_options, Some(selection), control, progress)
+}
+
+fn run_with(
+    run_options: &RunOptions,
+    selection: Option<&[PathBuf]>,
+    control: &RunControl,
+    progress: Option<ProgressFn>,
+) -> Result<RunResult, RunError> {
+    control.set_throttle(run_options.throttle);
+    with_report(run_options, |log, clock| {
+        let progress = Mutex::new(progress);
+        let emit = emitter(&progress);
+        let scale = percent::Scale::new(!run_options.dry_run);
+        let (plan, bytes) =
+            plan::plan_with(run_options, selection, control, &emit, log, &scale, clock)?;
+        if !plan.complete {
+            return Ok(plan.preview);
+        }
+        let (mut result, bytes) = if run_options.dry_run {
+            (plan.preview, bytes)
+        } else {
+            plan::execute_with(&plan, control, &emit, log, &scale, None)?
         };
-        if let Some(ref p) = progress {
-            p(ev);
+        emit_done(&emit, &scale, clock, &mut result, &bytes);
+        Ok(result)
+    })
+}
+
+/// Run `body` with the run's log (opening `log_path`, if set) and its clock, and with
+/// `report_path` set, write the JSON report of its result. A log file that cannot be opened is
+/// only a warning. The result's stats are stamped here if `body` did not (see `emit_done`).
+/// When `body` fails, the report lists its error as the run's only one.
+fn with_report(
+    run_options: &RunOptions,
+    body: impl FnOnce(&RunLog, &RunClock) -> Result<RunResult, RunError>,
+) -> Result<RunResult, RunError> {
+    let clock = RunClock::start();
+    let mut log = RunLog::new(run_options.verbose);
+    let mut log_path = None;
+    let mut log_warning = None;
+    if let Some(path) = &run_options.log_path {
+        match log.open_file(path) {
+            Ok(path) => log_path = Some(path),
+            Err(e) => {
+                let warning = format!("Cannot write log file {}: {}", path.display(), e);
+                log.warn(&warning);
+                log_warning = Some(warning);
+            }
         }
-        return RunResult {
-            errors: 1,
-            ..Default::default()
+    }
+    log.debug(format_args!(
+        "Run started: {} -> {}, suffixes {}{}",
+        run_options
+            .sources
+            .iter()
+            .map(|s| s.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        run_options.dest.display(),
+        run_options.suffix_input.trim(),
+        if run_options.dry_run {
+            " (dry-run)"
+        } else {
+            ""
+        }
+    ));
+    let outcome = body(&log, &clock);
+    let mut result = match &outcome {
+        Ok(result) => result.clone(),
+        Err(e) => RunResult::from_error(e),
+    };
+    if result.stats.finished_at.is_none() {
+        clock.stamp(&mut result.stats);
+    }
+    result.log_path = log_path;
+    result.warnings.extend(log_warning);
+    result.dedup_disabled = run_options.dedup_mode() == DedupMode::Off;
+    result.limit = run_options.file_limit();
+    log.debug(format_args!(
+        "Run finished: {} moved ({}), {} duplicate(s), {} error(s) in {}{}",
+        result.moved,
+        human_bytes(result.stats.bytes_moved),
+        result.skipped_duplicates,
+        result.errors,
+        human_duration(result.stats.duration_ms),
+        if result.cancelled {
+            ", cancelled"
+        } else if result.aborted.is_some() {
+            ", aborted"
+        } else {
+            ""
+        }
+    ));
+    if let Some(path) = &run_options.report_path {
+        let stats = &result.stats;
+        let report = RunReport {
+            started_at: stats.started_at.clone().unwrap_or_default(),
+            finished_at: stats.finished_at.clone().unwrap_or_default(),
+            duration_ms: stats.duration_ms,
+            options: ReportOptions::from_run_options(run_options),
+            result: result.clone(),
         };
+        match report::write_report(path, &report) {
+            Ok(()) => result.report_path = Some(path.clone()),
+            Err(e) => {
+                let warning = format!("Cannot write report {}: {}", path.display(), e);
+                log.error(&warning);
+                result.warnings.push(warning);
+            }
+        }
+    }
+    outcome.map(|_| result)
+}
+
+/// Drop roots that lie inside another root (or repeat one), which would be scanned twice.
+/// The remaining roots keep their order.
+fn outermost_roots(roots: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut kept: Vec<PathBuf> = Vec::new();
+    for root in roots {
+        if kept.iter().any(|k| root.starts_with(k)) {
+            continue;
+        }
+        kept.retain(|k| !k.starts_with(&root));
+        kept.push(root);
     }
+    kept
+}
 
-    let emit = |ev: ProgressEvent| {
-        if let Some(ref p) = progress {
+/// Shared by a run's threads; the callback itself is only `Send`.
+type Emit<'a> = &'a (dyn Fn(ProgressEvent) + Sync);
+
+/// The callback as workers can share it: it is only `Send`, so calls go through the mutex.
+/// Past `MAX_PROBLEM_EVENTS`, errors and warnings are counted instead of sent, and the final
+/// event says how many were held back.
+fn emitter(progress: &Mutex<Option<ProgressFn>>) -> impl Fn(ProgressEvent) + Sync + '_ {
+    let problems = AtomicU64::new(0);
+    move |mut ev: ProgressEvent| {
+        if ev.error.is_some() || ev.warning.is_some() {
+            let n = problems.fetch_add(1, Ordering::Relaxed);
+            if n >= MAX_PROBLEM_EVENTS {
+                ev.error = None;
+                ev.warning = None;
+            }
+        }
+        if matches!(ev.phase, Phase::Done) {
+            let problems = problems.load(Ordering::Relaxed);
+            ev.problems_not_sent = problems.saturating_sub(MAX_PROBLEM_EVENTS);
+        }
+        if let Some(p) = progress.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
             p(ev);
         }
-    };
+    }
+}
 
-    // Ensure destination exists
-    if let Err(e) = std::fs::create_dir_all(dest_dir) {
-        emit(ProgressEvent {
-            phase: Phase::Done,
-            current_file: None,
-            scanned: 0,
-            matched: 0,
-            moved: 0,
-            skipped_duplicates: 0,
-            errors: 1,
-            percent: 100.0,
-        });
-        if verbose {
-            eprintln!("Destination create error: {}", e);
+/// A run that could not start: emit the final event with `error` and return it.
+fn setup_failed(emit: Emit, log: &RunLog, error: RunError) -> RunError {
+    failed(emit, log, error.detail());
+    error
+}
+
+/// A run stopped before moving anything: emit the final event and return the single error.
+fn failed(emit: Emit, log: &RunLog, error: ErrorDetail) -> RunResult {
+    emit(ProgressEvent {
+        phase: Phase::Done,
+        errors: 1,
+        percent: 100.0,
+        error: Some(error.clone()),
+        ..Default::default()
+    });
+    log.error(&error.message);
+    RunResult {
+        errors: 1,
+        error_details: vec![error],
+        ..Default::default()
+    }
+}
+
+/// What a run works from once its options are checked.
+struct Setup {
+    /// Every requested suffix, of all destinations.
+    suffixes: HashSet<u32>,
+    /// Canonical, with nested roots dropped.
+    source_roots: Vec<PathBuf>,
+    /// `dest`, the routes' and the rejects folder, in the order a file's route indexes them.
+    destinations: Vec<Destination>,
+}
+
+/// A destination root of the run.
+struct Destination {
+    dir: PathBuf,
+    /// The suffixes routed there.
+    suffixes: HashSet<u32>,
+    sanitize_names: bool,
+    /// The rejects folder, for the images that matched no suffix.
+    rejects: bool,
+}
+
+impl Setup {
+    /// Where files of `suffix` go: the first destination that has it.
+    fn route(&self, suffix: u32) -> usize {
+        self.destinations
+            .iter()
+            .position(|d| d.suffixes.contains(&suffix))
+            .unwrap_or(0)
+    }
+
+    /// The route of the rejects folder, if the run has one.
+    fn rejects(&self) -> Option<usize> {
+        self.destinations.iter().position(|d| d.rejects)
+    }
+
+    /// Whether `entry` matched a suffix, rather than going to the rejects folder.
+    fn is_match(&self, entry: &scanner::ImageEntry) -> bool {
+        !self.destinations[entry.route].rejects
+    }
+}
+
+/// Parse the suffixes, resolve the source roots and check the destinations. A destination
+/// that does not exist yet is not created (see `create_destinations`): it must be possible to
+/// create it in the nearest folder of it that exists.
+fn set_up(run_options: &RunOptions) -> Result<Setup, RunError> {
+    let routed = parse_rules(run_options)?;
+    let suffixes = routed.iter().flatten().copied().collect();
+    if run_options.dedup == DedupMode::Off {
+        let action = match run_options.move_options.duplicate_action {
+            DuplicateAction::Leave => None,
+            DuplicateAction::Delete => Some("deleted"),
+            DuplicateAction::Trash => Some("trashed"),
+        };
+        if let Some(action) = action {
+            return Err(RunError::DuplicatesWithoutDedup { action });
         }
-        return RunResult {
-            errors: 1,
-            ..Default::default()
+    }
+
+    let source_roots = resolve_sources(run_options)?;
+
+    let mut destinations: Vec<Destination> = Vec::new();
+    let mut resolved: Vec<(PathBuf, &Path)> = Vec::new();
+    for (dest, suffixes) in run_options.destinations().zip(routed) {
+        let unwritable = |source| RunError::DestinationUnwritable {
+            path: dest.to_path_buf(),
+            source,
         };
+        let Some((existing, canonical)) = validate::resolve(dest) else {
+            let missing =
+                std::io::Error::new(std::io::ErrorKind::NotFound, "no folder of it exists");
+            return Err(unwritable(missing));
+        };
+        if existing == canonical && !existing.is_dir() {
+            let file = std::io::Error::new(std::io::ErrorKind::AlreadyExists, "not a folder");
+            return Err(unwritable(file));
+        }
+        mover::probe_writable(&existing).map_err(unwritable)?;
+        // One destination inside another would index the other's files as its own.
+        let overlaps = |other: &Path| other.starts_with(&canonical) || canonical.starts_with(other);
+        let rejects = Some(dest) == run_options.rejects.as_deref();
+        if rejects {
+            // Rejects inside a source would be scanned as rejects again by the next run.
+            let other = resolved.iter().map(|(c, d)| (c.as_path(), *d));
+            let sources = source_roots.iter().map(|s| (s.as_path(), s.as_path()));
+            if let Some((_, other)) = other.chain(sources).find(|(c, _)| overlaps(c)) {
+                return Err(RunError::RejectsOverlap {
+                    path: dest.to_path_buf(),
+                    other: other.to_path_buf(),
+                });
+            }
+        } else if let Some((_, other)) = resolved.iter().find(|(c, _)| overlaps(c)) {
+            return Err(RunError::DestinationsOverlap {
+                path: dest.to_path_buf(),
+                other: other.to_path_buf(),
+            });
+        }
+        destinations.push(Destination {
+            dir: dest.to_path_buf(),
+            suffixes,
+            sanitize_names: run_options.move_options.sanitize.applies_to(&existing),
+            rejects,
+        });
+        resolved.push((canonical, dest));
     }
 
-    // Phase 1: scan source for matching files
+    Ok(Setup {
+        suffixes,
+        source_roots,
+        destinations,
+    })
+}
+
+/// Create the destinations that do not exist yet, once the run is about to put files there.
+fn create_destinations(run_options: &RunOptions) -> Result<(), RunError> {
+    for dest in run_options.destinations() {
+        std::fs::create_dir_all(mover::long_path(dest)).map_err(|source| {
+            RunError::DestinationUnwritable {
+                path: dest.to_path_buf(),
+                source,
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// The suffixes of each of `RunOptions::rules`, parsed; an empty set for the rejects folder.
+fn parse_rules(run_options: &RunOptions) -> Result<Vec<HashSet<u32>>, RunError> {
+    let mut routed = Vec::new();
+    for (input, _) in run_options.rules() {
+        let Some(input) = input else {
+            routed.push(HashSet::new());
+            continue;
+        };
+        let suffixes = suffix_parser::parse_suffixes(input);
+        if suffixes.is_empty() {
+            return Err(RunError::InvalidSuffixes {
+                input: input.trim().to_string(),
+            });
+        }
+        routed.push(suffixes);
+    }
+    Ok(routed)
+}
+
+/// The source roots, canonical, with those inside another dropped.
+fn resolve_sources(run_options: &RunOptions) -> Result<Vec<PathBuf>, RunError> {
+    // Resolve each source root once ("./photos", trailing slashes, symlinks, \\?\ forms) so that
+    // every scanned path shares its prefix and the mirror layout can strip it reliably.
+    let mut source_roots = Vec::new();
+    for source_dir in &run_options.sources {
+        match dunce::canonicalize(mover::long_path(source_dir)) {
+            Ok(p) => source_roots.push(p),
+            Err(source) => {
+                return Err(RunError::SourceUnavailable {
+                    path: source_dir.clone(),
+                    source,
+                })
+            }
+        }
+    }
+    let source_roots = outermost_roots(source_roots);
+    if source_roots.is_empty() {
+        return Err(RunError::NoSources);
+    }
+    Ok(source_roots)
+}
+
+/// Phase 1: scan every source root for matching files, sorted by path within each root. Returns
+/// them with the matches left out by the exclusion globs, each of which is logged, and the
+/// links left out by the symlink policy. Broken links are warned about at once. A cancel stops
+/// the scan (a pause does not); what was found by then is returned.
+fn scan_sources(
+    setup: &Setup,
+    run_options: &RunOptions,
+    control: &RunControl,
+    emit: Emit,
+    log: &RunLog,
+) -> Result<Scanned, RunError> {
+    let options = &run_options.move_options;
     emit(ProgressEvent {
         phase: Phase::ScanningSource,
-        current_file: None,
-        scanned: 0,
-        matched: 0,
-        moved: 0,
-        skipped_duplicates: 0,
-        errors: 0,
-        percent: 0.0,
+        ..Default::default()
     });
+    let mut candidates = Vec::new();
+    let mut excluded = Vec::new();
+    let mut other_types = Vec::new();
+    let mut rejects = Vec::new();
+    let mut skipped_symlinks = 0;
+    let mut link_warnings = Vec::new();
+    let mut count = ScanCount::default();
+    let rejects_route = setup.rejects();
+    for (root, source_dir) in setup.source_roots.iter().enumerate() {
+        if control.is_cancelled() {
+            break;
+        }
+        let found = scanner::scan_source_for_suffixes(
+            source_dir,
+            &setup.suffixes,
+            &run_options.exclude_globs,
+            rejects_route.is_some(),
+            options.normalize_unicode,
+            run_options.match_by == MatchBy::Metadata,
+            |so_far| {
+                emit(ProgressEvent {
+                    phase: Phase::ScanningSource,
+                    current_file: Some(source_dir.display().to_string()),
+                    scanned: count.files + so_far.files,
+                    images: count.images + so_far.images,
+                    ..Default::default()
+                });
+                !control.is_cancelled()
+            },
+        );
+        match found {
+            Ok(mut found) => {
+                count.files += found.count.files;
+                count.images += found.count.images;
+                count.numbered += found.count.numbered;
+                count.unnumbered += found.count.unnumbered;
+                if options.symlinks == SymlinkPolicy::Skip {
+                    let before = found.entries.len() + found.unmatched.len();
+                    found.entries.retain(|e| !e.symlink);
+                    found.unmatched.retain(|e| !e.symlink);
+                    skipped_symlinks +=
+                        (before - found.entries.len() - found.unmatched.len()) as u64;
+                }
+                for path in found.broken_links {
+                    let warning = format!("{}: broken symbolic link, skipped", path.display());
+                    log.warn(&warning);
+                    emit(ProgressEvent {
+                        phase: Phase::ScanningSource,
+                        scanned: count.files,
+                        images: count.images,
+                        warning: Some(warning.clone()),
+                        ..Default::default()
+                    });
+                    link_warnings.push(warning);
+                }
+                found.entries.sort_by(|a, b| a.path.cmp(&b.path));
+                found.unmatched.sort_by(|a, b| a.path.cmp(&b.path));
+                rejects.extend(
+                    found
+                        .unmatched
+                        .into_iter()
+                        .map(|entry| scanner::ImageEntry {
+                            root,
+                            route: rejects_route.unwrap_or(0),
+                            ..entry
+                        }),
+                );
+                for (path, glob) in found.excluded {
+                    log_excluded(log, &path, &glob);
+                    excluded.push(path);
+                }
+                other_types.extend(found.other_types);
+                candidates.extend(found.entries.into_iter().map(|entry| scanner::ImageEntry {
+                    root,
+                    route: setup.route(entry.suffix),
+                    ..entry
+                }));
+            }
+            Err(source) => {
+                return Err(RunError::ScanFailed {
+                    path: source_dir.clone(),
+                    source,
+                })
+            }
+        }
+    }
+    // After all the matches, so a limit keeps a prefix of them (see `take_first`).
+    candidates.extend(rejects);
+    log.info(format_args!(
+        "Scanned {} file(s), {} of them images",
+        count.files, count.images
+    ));
+    if run_options.match_by == MatchBy::Metadata {
+        log.info(format_args!(
+            "Read a frame name from the metadata of {} image(s); {} had none and were matched by name",
+            count.numbered, count.unnumbered
+        ));
+    }
+    if skipped_symlinks > 0 {
+        log.info(format_args!(
+            "Skipped {} symbolic link(s) to images",
+            skipped_symlinks
+        ));
+    }
+    Ok(Scanned {
+        count,
+        candidates,
+        excluded,
+        other_types,
+        skipped_symlinks,
+        link_warnings,
+    })
+}
 
-    let candidates = match scanner::scan_source_for_suffixes(source_dir, &suffixes) {
-        Ok(c) => c,
-        Err(e) => {
-            emit(ProgressEvent {
-                phase: Phase::Done,
-                current_file: None,
-                scanned: 0,
-                matched: 0,
-                moved: 0,
-                skipped_duplicates: 0,
-                errors: 1,
-                percent: 100.0,
-            });
-            if verbose {
-                eprintln!("Scan error: {}", e);
+/// What scanning the source roots found.
+struct Scanned {
+    /// Files looked at under all the roots.
+    count: ScanCount,
+    /// The matches, then any images for the rejects folder (see `Setup::is_match`).
+    candidates: Vec<scanner::ImageEntry>,
+    /// Matches left out by the exclusion globs.
+    excluded: Vec<PathBuf>,
+    /// Suffix and extension of each name match of another file type.
+    other_types: Vec<(u32, String)>,
+    /// Links left out by `SymlinkPolicy::Skip`.
+    skipped_symlinks: u64,
+    /// One warning for each broken link.
+    link_warnings: Vec<String>,
+}
+
+/// The per-suffix table of a scan, and the extensions of its matches of another file type.
+/// Requested suffixes that matched nothing and those other types are warned about at once
+/// (logged and sent with the counts); the warnings are returned too.
+fn count_suffixes(
+    setup: &Setup,
+    scanned: &Scanned,
+    emit: Emit,
+    log: &RunLog,
+) -> (Vec<SuffixCount>, Vec<String>, Vec<String>) {
+    let candidates: Vec<_> = scanned
+        .candidates
+        .iter()
+        .filter(|c| setup.is_match(c))
+        .collect();
+    let mut table = report::suffix_table(&setup.suffixes, candidates.iter().map(|c| c.suffix));
+    let (other_types, extensions) = report::add_other_types(&mut table, &scanned.other_types);
+    let mut warnings = Vec::new();
+    if let Some(warning) = report::other_types_warning(other_types, &extensions) {
+        log.warn(&warning);
+        emit(ProgressEvent {
+            phase: Phase::ScanningSource,
+            scanned: scanned.count.files,
+            images: scanned.count.images,
+            matched: candidates.len() as u64,
+            warning: Some(warning.clone()),
+            ..Default::default()
+        });
+        warnings.push(warning);
+    }
+    let unmatched: Vec<u32> = table
+        .iter()
+        .filter(|s| s.matched == 0)
+        .map(|s| s.suffix)
+        .collect();
+    let warning = report::unmatched_warning(&unmatched);
+    if let Some(warning) = &warning {
+        log.warn(warning);
+        emit(ProgressEvent {
+            phase: Phase::ScanningSource,
+            scanned: scanned.count.files,
+            images: scanned.count.images,
+            matched: candidates.len() as u64,
+            warning: Some(warning.clone()),
+            unmatched_suffixes: unmatched,
+            ..Default::default()
+        });
+    }
+    warnings.splice(0..0, warning);
+    (table, extensions, warnings)
+}
+
+/// Keep the first `allowance` of `candidates` (all of them with `None`); returns how many were
+/// left out. Images for the rejects folder are not matches: they come last and are all kept.
+fn take_first(
+    setup: &Setup,
+    candidates: &mut Vec<scanner::ImageEntry>,
+    allowance: Option<u64>,
+    log: &RunLog,
+) -> u64 {
+    let Some(allowance) = allowance else {
+        return 0;
+    };
+    let matches = candidates.iter().filter(|c| setup.is_match(c)).count();
+    let over = (matches as u64).saturating_sub(allowance);
+    if over > 0 {
+        candidates.drain(allowance as usize..matches);
+        log.info(format_args!(
+            "Limited to the first {} of {} matches; {} left for a later run",
+            allowance,
+            allowance + over,
+            over
+        ));
+    }
+    over
+}
+
+fn log_excluded(log: &RunLog, path: &Path, glob: &str) {
+    log.info(format_args!(
+        "Excluded {} (matches {})",
+        path.display(),
+        glob
+    ));
+}
+
+/// Measure the destination for `candidates` and check it unless `skip_preflight` is set.
+/// A dry-run goes on with a warning when the check fails; a real run gets the error.
+fn check_destination(
+    run_options: &RunOptions,
+    log: &RunLog,
+    source_roots: &[PathBuf],
+    candidates: &[scanner::ImageEntry],
+) -> Result<(Preflight, Option<String>), (Preflight, PreflightError)> {
+    // The result has the figures of `dest`; a route's destination that fails stops the run
+    // just the same.
+    let mut checked = None;
+    for (route, dest) in run_options.destinations().enumerate() {
+        let routed = candidates.iter().filter(|c| c.route == route);
+        let found = Preflight::measure(run_options, dest, source_roots, routed);
+        for root in &found.cross_volume {
+            log.info(format_args!(
+                "{} is on another volume: files are copied, then removed",
+                root.display()
+            ));
+        }
+        let warning = if run_options.skip_preflight {
+            None
+        } else {
+            match found.verify(dest, run_options.dry_run) {
+                Ok(()) => None,
+                Err(e) if run_options.dry_run => Some(format!("The real run would stop: {}", e)),
+                Err(e) => return Err((checked.map_or(found, |(main, _)| main), e)),
             }
-            return RunResult {
-                errors: 1,
-                ..Default::default()
-            };
+        };
+        let (_, first_warning) = checked.get_or_insert((found, None));
+        if first_warning.is_none() {
+            *first_warning = warning;
         }
+    }
+    Ok(checked.expect("a run has a destination"))
+}
+
+/// A run stopped by `check_destination`.
+fn preflight_failed(
+    emit: Emit,
+    log: &RunLog,
+    preflight: Preflight,
+    error: PreflightError,
+) -> RunResult {
+    let dest = match &error {
+        PreflightError::InsufficientSpace { dest, .. }
+        | PreflightError::NotWritable { dest, .. } => dest.clone(),
     };
+    let detail = ErrorDetail::new(Some(&dest), ErrorStage::Preflight, error.to_string());
+    RunResult {
+        preflight: Some(preflight),
+        preflight_error: Some(error),
+        ..failed(emit, log, detail)
+    }
+}
 
-    let matched_count = candidates.len() as u64;
+/// Phase 2: list the image files already in each destination with their sizes (stops early
+/// when cancelled); they are hashed later, as candidates of the same size need them (see
+/// `DestIndex`). Listing is the indexing work on `scale`. Returns an index per destination, in
+/// route order; none with `DedupMode::SamePath` or `DedupMode::Off`, which index nothing.
+fn index_destination(
+    run_options: &RunOptions,
+    log: &RunLog,
+    scan: ScanCount,
+    matched_count: u64,
+    control: &RunControl,
+    emit: Emit,
+    scale: &percent::Scale,
+) -> Vec<DestIndex> {
+    if run_options.move_options.tag.is_some() {
+        log.info("Not indexing a destination: files are tagged where they are");
+        return Vec::new();
+    }
+    match run_options.dedup {
+        DedupMode::Content => {}
+        DedupMode::SamePath => {
+            log.info(
+                "Not indexing the destination: duplicates are checked at their target path only",
+            );
+            return Vec::new();
+        }
+        DedupMode::Off => {
+            log.info("Not indexing the destination: duplicate detection is off");
+            return Vec::new();
+        }
+    }
     emit(ProgressEvent {
         phase: Phase::IndexingDestination,
-        current_file: None,
-        scanned: matched_count,
+        scanned: scan.files,
+        images: scan.images,
         matched: matched_count,
-        moved: 0,
-        skipped_duplicates: 0,
-        errors: 0,
-        percent: 5.0,
+        percent: scale.percent(Phase::IndexingDestination, 0.0),
+        ..Default::default()
     });
 
-    if cancel.load(Ordering::Relaxed) {
-        emit(ProgressEvent {
+    let listings: Vec<Vec<PathBuf>> = run_options
+        .destinations()
+        .map(|dest_dir| match scanner::list_images_under(dest_dir) {
+            Ok(f) => f,
+            Err(e) => {
+                log.error(format_args!("Destination list error: {}", e));
+                vec![]
+            }
+        })
+        .collect();
+    let count = listings.iter().map(Vec::len).sum::<usize>() as u64;
+    scale.set_work(Phase::IndexingDestination, count * percent::SCAN_COST);
+    let mut indexes = Vec::with_capacity(listings.len());
+    let mut i = 0;
+    for (dest_dir, dest_files) in run_options.destinations().zip(listings) {
+        let mut known = cache::known_hashes(run_options, dest_dir, log);
+        let mut listed = Vec::with_capacity(dest_files.len());
+        for path in dest_files {
+            if !control.proceed() {
+                break;
+            }
+            if i % 500 == 0 {
+                emit(ProgressEvent {
+                    phase: Phase::IndexingDestination,
+                    current_file: Some(path.display().to_string()),
+                    scanned: scan.files,
+                    images: scan.images,
+                    matched: matched_count,
+                    percent: scale.percent(Phase::IndexingDestination, i as f64 / count as f64),
+                    paused: control.is_paused(),
+                    current_index: i + 1,
+                    total_count: count,
+                    ..Default::default()
+                });
+            }
+            i += 1;
+            match std::fs::metadata(&path) {
+                Ok(m) => {
+                    let (size, modified) = (m.len(), m.modified().ok());
+                    let hash = match path.strip_prefix(dest_dir) {
+                        Ok(relative) => cache::reuse(&mut known, relative, size, modified),
+                        Err(_) => None,
+                    };
+                    listed.push(DestFile {
+                        path,
+                        size,
+                        modified,
+                        hash,
+                    });
+                }
+                Err(e) => log.warn(format_args!("Cannot index {}: {}", path.display(), e)),
+            }
+        }
+        log.info(format_args!(
+            "Indexed {} image(s) in {}; they are hashed when a match of the same size needs them",
+            listed.len(),
+            dest_dir.display()
+        ));
+        indexes.push(DestIndex::listed(listed));
+    }
+    indexes
+}
+
+/// Stamp `result` with the finish time on `clock` and emit the final event, with its counts,
+/// its stats and the bytes of its last batch. A cancelled or aborted run ends where its bar
+/// stopped on `scale`.
+fn emit_done(
+    emit: Emit,
+    scale: &percent::Scale,
+    clock: &RunClock,
+    result: &mut RunResult,
+    bytes: &meter::ByteMeter,
+) {
+    clock.stamp(&mut result.stats);
+    let processed = result.matched.saturating_sub(result.not_attempted);
+    let percent = if result.cancelled || result.aborted.is_some() {
+        scale.shown()
+    } else {
+        scale.percent(Phase::Done, 1.0)
+    };
+    emit(ProgressEvent {
+        phase: Phase::Done,
+        scanned: result.scanned,
+        images: result.images,
+        matched: result.matched,
+        moved: result.moved,
+        skipped_duplicates: result.skipped_duplicates,
+        rejected: result.rejected,
+        replaced: result.replaced,
+        skipped_newer: result.skipped_newer,
+        tagged: result.tagged,
+        read_only: result.read_only,
+        errors: result.errors,
+        percent,
+        total_bytes: bytes.total,
+        bytes_done: bytes.done,
+        current_index: processed,
+        total_count: result.matched,
+        cancelled: result.cancelled,
+        aborted: result.aborted.clone(),
+        over_limit: result.over_limit,
+        hash_mismatches: result.hash_mismatches,
+        stats: Some(result.stats.clone()),
+        unmatched_suffixes: result.unmatched_suffixes(),
+        verification: result.verification.clone(),
+        ..Default::default()
+    });
+}
+
+/// Remove source folders left empty, adding the count and warnings to `result`. With
+/// `dry_run`, only count the folders that would be empty once `would_remove` is gone.
+fn clean_up(
+    run_options: &RunOptions,
+    source_roots: &[PathBuf],
+    would_remove: &HashSet<PathBuf>,
+    dry_run: bool,
+    log: &RunLog,
+    result: &mut RunResult,
+) {
+    let options = &run_options.move_options;
+    for source_dir in source_roots {
+        let report =
+            cleanup::remove_empty_dirs(source_dir, options.junk_as_empty, dry_run, would_remove);
+        result.removed_dirs += report.removed;
+        for w in &report.warnings {
+            log.warn(w);
+        }
+        result.warnings.extend(report.warnings);
+    }
+    if dry_run {
+        log.info(format_args!(
+            "[dry-run] would remove {} empty directories",
+            result.removed_dirs
+        ));
+    } else if result.removed_dirs > 0 {
+        log.info(format_args!(
+            "removed {} empty directories",
+            result.removed_dirs
+        ));
+    }
+}
+
+impl RunResult {
+    /// A run that could not start, with `error` as its only one.
+    fn from_error(error: &RunError) -> RunResult {
+        RunResult {
+            errors: 1,
+            error_details: vec![error.detail()],
+            ..Default::default()
+        }
+    }
+
+    /// A run cancelled before any of its `candidates`, of those `scan` found, was attempted.
+    fn cancelled_before(
+        setup: &Setup,
+        candidates: &[scanner::ImageEntry],
+        scan: ScanCount,
+    ) -> RunResult {
+        let matched = candidates.iter().filter(|c| setup.is_match(c)).count() as u64;
+        RunResult {
+            scanned: scan.files,
+            images: scan.images,
+            numbered: scan.numbered,
+            unnumbered: scan.unnumbered,
+            matched,
+            cancelled: true,
+            not_attempted: matched,
+            ..Default::default()
+        }
+    }
+
+    /// The counts and records of one batch; `duplicate_groups` is filled by `finish`.
+    fn from_tally(mut tally: pool::Tally, dry_run: bool) -> RunResult {
+        let matched = tally.matches();
+        let not_attempted = if tally.cancelled || tally.aborted.is_some() {
+            tally.not_attempted()
+        } else {
+            0
+        };
+        let (mut files, files_omitted) = tally.file_log.finish();
+        for f in &mut files {
+            if let Some((route, also)) = tally.routes.remove(&f.source) {
+                f.route = Some(route);
+                f.also_routed = also;
+            }
+        }
+        RunResult {
+            scanned: tally.scan.files,
+            images: tally.scan.images,
+            numbered: tally.scan.numbered,
+            unnumbered: tally.scan.unnumbered,
+            matched,
+            moved: tally.moved,
+            replaced: tally.replaced,
+            skipped_newer: tally.skipped_newer,
+            tagged: tally.tagged,
+            read_only: tally.read_only,
+            skipped_duplicates: tally.skipped_duplicates,
+            duplicates_removed: tally.duplicates_removed,
+            removed_dirs: 0,
+            verified: tally.verified,
+            source_kept: tally.source_kept,
+            sanitized: tally.sanitized,
+            errors: tally.errors,
+            error_details: tally.error_details,
+            skipped_changed: tally.skipped_changed,
+            empty_files: tally.empty_files,
+            skipped_busy: tally.busy.len() as u64,
+            busy_files: tally.busy,
+            excluded: 0,
+            skipped_symlinks: 0,
+            broken_links: 0,
+            limit: None,
+            over_limit: 0,
+            selected_unmatched: 0,
+            other_type_extensions: Vec::new(),
+            source_duplicates: tally.source_duplicates,
+            hash_mismatches: tally.hash_mismatches,
+            rejects_found: tally.rejects,
+            rejected: tally.rejected,
+            rejected_duplicates: tally.rejected_duplicates,
+            duplicate_groups: Vec::new(),
+            suffixes: Vec::new(),
+            dry_run,
+            files,
+            files_omitted,
+            cancelled: tally.cancelled,
+            not_attempted,
+            aborted: tally.aborted,
+            warnings: tally.warnings,
+            preflight: None,
+            preflight_error: None,
+            log_path: None,
+            report_path: None,
+            verification: None,
+            state_path: None,
+            resumed: 0,
+            dedup_disabled: false,
+            stats: RunStats {
+                bytes_moved: tally.bytes_moved,
+                bytes_skipped_duplicates: tally.bytes_duplicates,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Add a later batch of the same run (watch mode) to this result. The scan counts stay, as
+    /// every batch has those of the first scan.
+    fn absorb(&mut self, batch: RunResult) {
+        self.matched += batch.matched;
+        self.moved += batch.moved;
+        self.replaced += batch.replaced;
+        self.skipped_newer += batch.skipped_newer;
+        self.tagged += batch.tagged;
+        self.read_only += batch.read_only;
+        self.skipped_duplicates += batch.skipped_duplicates;
+        self.duplicates_removed += batch.duplicates_removed;
+        self.removed_dirs += batch.removed_dirs;
+        self.verified += batch.verified;
+        self.source_kept += batch.source_kept;
+        self.sanitized += batch.sanitized;
+        self.errors += batch.errors;
+        self.error_details.extend(batch.error_details);
+        self.skipped_changed += batch.skipped_changed;
+        self.empty_files += batch.empty_files;
+        self.skipped_busy += batch.skipped_busy;
+        self.busy_files.extend(batch.busy_files);
+        self.excluded += batch.excluded;
+        self.skipped_symlinks += batch.skipped_symlinks;
+        self.over_limit += batch.over_limit;
+        for ext in batch.other_type_extensions {
+            if !self.other_type_extensions.contains(&ext) {
+                self.other_type_extensions.push(ext);
+            }
+        }
+        self.cancelled |= batch.cancelled;
+        self.not_attempted += batch.not_attempted;
+        self.aborted = self.aborted.take().or(batch.aborted);
+        self.source_duplicates += batch.source_duplicates;
+        self.hash_mismatches += batch.hash_mismatches;
+        self.rejects_found += batch.rejects_found;
+        self.rejected += batch.rejected;
+        self.rejected_duplicates += batch.rejected_duplicates;
+        let room = MAX_FILE_RECORDS.saturating_sub(self.files.len());
+        self.files_omitted += batch.files_omitted + batch.files.len().saturating_sub(room) as u64;
+        self.files.extend(batch.files.into_iter().take(room));
+        self.warnings.extend(batch.warnings);
+        self.stats.bytes_moved += batch.stats.bytes_moved;
+        self.stats.bytes_skipped_duplicates += batch.stats.bytes_skipped_duplicates;
+        self.stats.phases.add(batch.stats.phases);
+        report::add_suffix_counts(&mut self.suffixes, batch.suffixes);
+        if let Some(check) = &batch.verification {
+            self.verification
+                .get_or_insert_with(Verification::default)
+                .absorb(check);
+        }
+    }
+
+    /// Files whose name matched but whose type is not an image one, so the scan left them out.
+    pub fn other_types(&self) -> u64 {
+        self.suffixes.iter().map(|s| s.other_types).sum()
+    }
+
+    /// Requested suffixes that no file matched: a typo in the pick list, or files elsewhere.
+    pub fn unmatched_suffixes(&self) -> Vec<u32> {
+        self.suffixes
+            .iter()
+            .filter(|s| s.matched == 0)
+            .map(|s| s.suffix)
+            .collect()
+    }
+
+    /// Tag each record with its source root and group the in-run duplicates.
+    fn finish(mut self, source_roots: &[PathBuf]) -> RunResult {
+        for f in &mut self.files {
+            f.root = source_roots
+                .iter()
+                .find(|r| f.source.starts_with(r))
+                .cloned();
+        }
+        self.duplicate_groups = report::duplicate_groups(&self.files);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn run_mirror(source: &Path, dest: &Path) -> RunResult {
+        run(
+            &RunOptions::new(source, dest, "7612"),
+            &RunControl::new(),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_relative_source_root_with_trailing_slash() {
+        let work = tempfile::tempdir().unwrap();
+        fs::create_dir_all(work.path().join("photos/DCIM")).unwrap();
+        fs::write(work.path().join("photos/DCIM/IMG_7612.JPG"), b"pick").unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        // Relative to the test's working directory, without changing it: up to the root, then
+        // down to the temporary folder.
+        let below_root = |path: &Path| -> PathBuf {
+            path.components()
+                .filter(|c| matches!(c, std::path::Component::Normal(_)))
+                .collect()
+        };
+        let cwd = std::env::current_dir().unwrap();
+        let up = "../".repeat(below_root(&cwd).components().count());
+        let source = PathBuf::from(format!(
+            "./{}{}/photos/",
+            up,
+            below_root(work.path()).display()
+        ));
+
+        let result = run_mirror(&source, dest.path());
+
+        assert_eq!(result.errors, 0);
+        assert_eq!(result.moved, 1);
+        assert!(dest.path().join("DCIM/IMG_7612.JPG").exists());
+    }
+
+    #[test]
+    fn test_multiple_sources_share_one_index() {
+        let card_a = tempfile::tempdir().unwrap();
+        let card_b = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::create_dir_all(card_b.path().join("DCIM")).unwrap();
+        fs::write(card_a.path().join("IMG_7612.JPG"), b"camera A").unwrap();
+        fs::write(card_b.path().join("DCIM/IMG_7612.JPG"), b"camera B").unwrap();
+        fs::write(card_b.path().join("DCIM/IMG_8612.JPG"), b"camera A").unwrap();
+        let options = RunOptions::new(card_a.path(), dest.path(), "7612 8612")
+            .sources([card_a.path(), card_b.path(), &card_b.path().join("DCIM")])
+            .workers(1);
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+
+        assert_eq!(
+            (result.moved, result.skipped_duplicates, result.errors),
+            (2, 1, 0)
+        );
+        assert!(dest.path().join("IMG_7612.JPG").exists());
+        assert!(dest.path().join("DCIM/IMG_7612.JPG").exists());
+        let root_b = dunce::canonicalize(card_b.path()).unwrap();
+        let from_b = result
+            .files
+            .iter()
+            .filter(|f| f.root.as_ref() == Some(&root_b));
+        assert_eq!(from_b.count(), 2);
+    }
+
+    #[test]
+    fn test_exclude_globs_leave_out_matching_names() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"full").unwrap();
+        fs::write(src.path().join("thumb_IMG_7612.jpg"), b"thumb").unwrap();
+        fs::write(src.path().join("IMG_preview_7612.JPG"), b"preview").unwrap();
+        let globs = ExcludeGlobs::new(["thumb_*", "*_preview_*.jpg"]).unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .exclude_globs(globs)
+            .workers(1);
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+
+        assert_eq!((result.matched, result.moved, result.excluded), (1, 1, 2));
+        assert!(dest.path().join("IMG_7612.JPG").exists());
+        assert!(src.path().join("thumb_IMG_7612.jpg").exists());
+    }
+
+    #[test]
+    fn test_decomposed_names_match_and_collide_like_composed_ones() {
+        let (src, dest) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        // Decomposed, as macOS writes them: "e" followed by a combining acute accent.
+        fs::write(src.path().join("cafe\u{301}_7612.jpg"), "new").unwrap();
+        fs::write(src.path().join("cafe\u{301} thumb_7608.jpg"), "thumb").unwrap();
+        fs::write(dest.path().join("cafe\u{301}_7612.jpg"), "old").unwrap();
+        let globs = ExcludeGlobs::new(["caf\u{e9} thumb_*"]).unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612, 7608").exclude_globs(globs);
+        // The same name as the file in the destination, composed.
+        let composed = tempfile::tempdir().unwrap();
+        fs::write(composed.path().join("caf\u{e9}_7612.jpg"), "new").unwrap();
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((result.moved, result.excluded), (1, 1));
+        assert!(dest.path().join("cafe\u{301}_7612-1.jpg").exists());
+        let composed_options = RunOptions::new(composed.path(), dest.path(), "7612");
+        let result = run(&composed_options, &RunControl::new(), None).unwrap();
+        assert_eq!((result.moved, result.skipped_duplicates), (0, 1));
+
+        #[cfg(target_os = "linux")]
+        {
+            // Byte for byte, the composed name is another file on filesystems that keep both.
+            fs::write(composed.path().join("caf\u{e9}_7612.jpg"), "newer").unwrap();
+            let exact = composed_options.normalize_unicode(false);
+            let result = run(&exact, &RunControl::new(), None).unwrap();
+            assert_eq!(result.moved, 1);
+            assert!(dest.path().join("caf\u{e9}_7612.jpg").exists());
+        }
+    }
+
+    #[test]
+    fn test_empty_files_are_never_duplicates_and_follow_the_policy() {
+        let (src, dest) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        fs::write(src.path().join("IMG_7612.JPG"), "").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), "").unwrap();
+        fs::write(dest.path().join("empty.JPG"), "").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612, 7608");
+
+        let skip = options.clone().empty_files(EmptyFilePolicy::Skip);
+        let result = run(&skip, &RunControl::new(), None).unwrap();
+        assert_eq!((result.moved, result.empty_files, result.errors), (0, 2, 0));
+        assert_eq!(result.warnings.len(), 2);
+        assert!(src.path().join("IMG_7612.JPG").exists());
+
+        let error = options.clone().empty_files(EmptyFilePolicy::Error);
+        let result = run(&error, &RunControl::new(), None).unwrap();
+        assert_eq!((result.moved, result.empty_files, result.errors), (0, 2, 2));
+        assert!(result.error_details[0].message.contains("0 bytes"));
+
+        let preview = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        assert_eq!((preview.moved, preview.empty_files), (2, 2));
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!(
+            (result.moved, result.skipped_duplicates, result.empty_files),
+            (2, 0, 2)
+        );
+        assert!(result.warnings.iter().all(|w| w.contains("0 bytes")));
+        assert!(dest.path().join("IMG_7612.JPG").exists());
+        assert!(dest.path().join("IMG_7608.JPG").exists());
+    }
+
+    #[test]
+    fn test_renamed_files_match_by_their_original_frame_name() {
+        let (src, dest) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let xmp = |name: &str| {
+            let mut jpeg = b"\xff\xd8\xff\xe1\x00\x40http://ns.adobe.com/xap/1.0/\0".to_vec();
+            jpeg.extend_from_slice(format!("<x crs:RawFileName=\"{}\"/>", name).as_bytes());
+            jpeg
+        };
+        fs::write(
+            src.path().join("2024-03-01_wedding_0045.jpg"),
+            xmp("IMG_7612.CR2"),
+        )
+        .unwrap();
+        // Its name ends with a requested suffix, but the camera called it otherwise.
+        fs::write(
+            src.path().join("2024-03-01_wedding_7608.jpg"),
+            xmp("IMG_0001.CR2"),
+        )
+        .unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), "no metadata").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612, 7608");
+
+        let by_name = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        assert_eq!((by_name.matched, by_name.numbered), (2, 0));
+        let options = options.match_by(MatchBy::Metadata);
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!(
+            (result.images, result.numbered, result.unnumbered),
+            (3, 2, 1)
+        );
+        assert_eq!((result.matched, result.moved), (2, 2));
+        assert!(dest.path().join("2024-03-01_wedding_0045.jpg").exists());
+        assert!(dest.path().join("IMG_7608.JPG").exists());
+        assert!(src.path().join("2024-03-01_wedding_7608.jpg").exists());
+    }
+
+    #[test]
+    fn test_file_events_are_sent_and_batched_by_kind() {
+        let (src, dest) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        fs::write(src.path().join("IMG_7612.JPG"), b"pick").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), b"archived").unwrap();
+        fs::write(dest.path().join("IMG_7608.JPG"), b"archived").unwrap();
+        let events = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let progress: ProgressFn = Box::new(move |ev| sink.lock().unwrap().push(ev));
+        let options = RunOptions::new(src.path(), dest.path(), "7612 7608");
+
+        let preview = options.clone().dry_run(true);
+        run(&preview, &RunControl::new(), Some(progress)).unwrap();
+        assert!(events.lock().unwrap().iter().all(|ev| ev.file.is_none()));
+        let sink = events.clone();
+        let progress: ProgressFn = Box::new(move |ev| sink.lock().unwrap().push(ev));
+        run(&options, &RunControl::new(), Some(progress)).unwrap();
+
+        let root = src.path().canonicalize().unwrap();
+        let start = Instant::now();
+        let mut queue = FileEventQueue::new(7);
+        let mut batches = Vec::new();
+        for ev in events.lock().unwrap().iter() {
+            // All at once: only the final event sends what waits.
+            batches.extend(queue.push(ev, start));
+        }
+        let moved = FileEvent::Moved {
+            source: root.join("IMG_7612.JPG"),
+            dest: dest.path().join("IMG_7612.JPG"),
+        };
+        let duplicate = FileEvent::Duplicate {
+            source: root.join("IMG_7608.JPG"),
+            duplicate_of: Some(dest.path().join("IMG_7608.JPG")),
+        };
+        let names: Vec<_> = batches.iter().map(|b| b.files[0].event_name()).collect();
+        assert_eq!(names, ["file-duplicate", "file-moved"]);
+        assert_eq!(batches[1].files, vec![moved.clone()]);
+        assert_eq!(batches[0].files, [duplicate]);
+        assert!(batches.iter().all(|b| b.run_id == 7));
+
+        // Events in between wait for the interval or a full batch.
+        let event = ProgressEvent {
+            phase: Phase::Moving,
+            file: Some(moved),
+            ..Default::default()
+        };
+        assert_eq!(queue.push(&event, start).len(), 0);
+        assert_eq!(
+            queue.push(&event, start + FILE_EVENT_INTERVAL)[0]
+                .files
+                .len(),
+            2
+        );
+        for _ in 1..MAX_FILE_EVENT_BATCH {
+            assert!(queue.push(&event, start + FILE_EVENT_INTERVAL).is_empty());
+        }
+        let full = queue.push(&event, start + FILE_EVENT_INTERVAL);
+        assert_eq!(full[0].files.len(), MAX_FILE_EVENT_BATCH);
+    }
+
+    #[test]
+    fn test_progress_throttle_thins_out_events_but_never_the_final_one() {
+        let src = tempfile::tempdir().unwrap();
+        for i in 0..300 {
+            fs::write(
+                src.path().join(format!("IMG_{}.JPG", 7000 + i)),
+                i.to_string(),
+            )
+            .unwrap();
+        }
+        let dest = tempfile::tempdir().unwrap();
+        let events = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let progress: ProgressFn = Box::new(move |ev| sink.lock().unwrap().push(ev));
+        let options = RunOptions::new(src.path(), dest.path(), "7000-7299").workers(1);
+        run(&options, &RunControl::new(), Some(progress)).unwrap();
+        let events = events.lock().unwrap();
+
+        // All at once, as fast as the run makes them.
+        let start = Instant::now();
+        let mut throttle = ProgressThrottle::new();
+        let sent: Vec<_> = events
+            .iter()
+            .filter(|ev| throttle.admit(ev, start))
+            .collect();
+        assert!(
+            sent.len() < events.len() / 10,
+            "{} of {}",
+            sent.len(),
+            events.len()
+        );
+        let phases = |events: &mut dyn Iterator<Item = &ProgressEvent>| {
+            let mut phases: Vec<Phase> = events.map(|ev| ev.phase).collect();
+            phases.dedup();
+            phases
+        };
+        assert_eq!(
+            phases(&mut sent.iter().copied()),
+            phases(&mut events.iter())
+        );
+        let last = sent.last().unwrap();
+        assert!(matches!(last.phase, Phase::Done) && last.stats.is_some());
+        assert_eq!(last.moved, 300);
+
+        // An error, a pause or the interval passing go through; the rest wait.
+        let mut throttle = ProgressThrottle::new();
+        let moving = ProgressEvent {
+            phase: Phase::Moving,
+            ..Default::default()
+        };
+        assert!(throttle.admit(&moving, start));
+        assert!(!throttle.admit(&moving, start));
+        let failed = ProgressEvent {
+            error: Some(ErrorDetail::new(None, ErrorStage::Move, "disk full")),
+            ..moving.clone()
+        };
+        assert!(throttle.admit(&failed, start));
+        let paused = ProgressEvent {
+            paused: true,
+            ..moving.clone()
+        };
+        assert!(throttle.admit(&paused, start));
+        assert!(!throttle.admit(&paused, start));
+        assert!(throttle.admit(&paused, start + PROGRESS_INTERVAL));
+        for _ in 0..MAX_PROGRESS_DROPPED {
+            assert!(!throttle.admit(&paused, start + PROGRESS_INTERVAL));
+        }
+        assert!(throttle.admit(&paused, start + PROGRESS_INTERVAL));
+        let done = ProgressEvent {
             phase: Phase::Done,
-            current_file: None,
-            scanned: matched_count,
-            matched: matched_count,
-            moved: 0,
-            skipped_duplicates: 0,
-            errors: 0,
-            percent: 100.0,
-        });
-        return RunResult {
-            scanned: matched_count,
-            matched: matched_count,
             ..Default::default()
         };
+        assert!(throttle.admit(&done, start + PROGRESS_INTERVAL));
+        assert!(throttle.admit(&done, start + PROGRESS_INTERVAL));
+    }
+
+    #[test]
+    fn test_same_path_dedup_skips_the_index() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"already there").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), b"renamed copy").unwrap();
+        fs::write(dest.path().join("IMG_7612.JPG"), b"already there").unwrap();
+        fs::write(dest.path().join("old name.JPG"), b"renamed copy").unwrap();
+        let indexed = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let sink = indexed.clone();
+        let progress: ProgressFn = Box::new(move |ev| {
+            if matches!(ev.phase, Phase::IndexingDestination) {
+                sink.store(true, Ordering::SeqCst);
+            }
+        });
+        let options = RunOptions::new(src.path(), dest.path(), "7612 7608")
+            .dedup(DedupMode::SamePath)
+            .workers(1);
+
+        let result = run(&options, &RunControl::new(), Some(progress)).unwrap();
+
+        assert!(!indexed.load(Ordering::SeqCst));
+        assert_eq!((result.moved, result.skipped_duplicates), (1, 1));
+        // Same content under another name is not caught in this mode.
+        assert!(dest.path().join("IMG_7608.JPG").exists());
+        assert!(src.path().join("IMG_7612.JPG").exists());
+    }
+
+    #[test]
+    fn test_dedup_off_moves_every_match() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"same").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), b"same").unwrap();
+        fs::write(dest.path().join("IMG_7612.JPG"), b"same").unwrap();
+        let indexed = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let sink = indexed.clone();
+        let progress: ProgressFn = Box::new(move |ev| {
+            if matches!(ev.phase, Phase::IndexingDestination) {
+                sink.store(true, Ordering::SeqCst);
+            }
+        });
+        let options = RunOptions::new(src.path(), dest.path(), "7612 7608")
+            .dedup(DedupMode::Off)
+            .workers(1);
+
+        let result = run(&options, &RunControl::new(), Some(progress)).unwrap();
+
+        assert!(!indexed.load(Ordering::SeqCst));
+        assert_eq!((result.moved, result.skipped_duplicates), (2, 0));
+        assert!(result.dedup_disabled);
+        assert!(dest.path().join("IMG_7612-1.JPG").exists());
+        assert!(dest.path().join("IMG_7608.JPG").exists());
+
+        let options = options.duplicate_action(DuplicateAction::Trash);
+        let err = run(&options, &RunControl::new(), None).unwrap_err();
+        assert!(matches!(err, RunError::DuplicatesWithoutDedup { .. }));
     }
 
-    // Phase 2: build destination hash index (only image files under dest)
-    let dest_files = match scanner::list_images_under(dest_dir) {
-        Ok(f) => f,
-        Err(e) => {
-            if verbose {
-                eprintln!("Destination list error: {}", e);
+    #[test]
+    fn test_slow_file_is_reported_stalled() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), vec![7u8; 500_000]).unwrap();
+        let stalled = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = stalled.clone();
+        let progress: ProgressFn = Box::new(move |ev| {
+            if let Some(s) = ev.stalled {
+                sink.lock().unwrap().push(s);
             }
-            vec![]
+        });
+        // Hashing 500 kB at 250 kB/s takes well over the one-second stall warning.
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .throttle(250_000)
+            .stall_warning(1);
+
+        let result = run(&options, &RunControl::new(), Some(progress)).unwrap();
+
+        assert_eq!(result.moved, 1);
+        let stalled = stalled.lock().unwrap();
+        assert!(!stalled.is_empty());
+        assert!(
+            stalled[0].path.ends_with("IMG_7612.JPG"),
+            "{}",
+            stalled[0].path
+        );
+        assert!(stalled[0].elapsed_seconds >= 1);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_per_file_report() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"new pick").unwrap();
+        fs::write(src.path().join("IMG_8612.JPG"), b"already there").unwrap();
+        fs::write(dest.path().join("old.JPG"), b"already there").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612 8612");
+
+        let dry = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        let result = run(&options, &RunControl::new(), None).unwrap();
+
+        for r in [&dry, &result] {
+            let mut files = r.files.clone();
+            files.sort_by(|a, b| a.source.cmp(&b.source));
+            assert_eq!(files.len(), 2);
+            assert_eq!(files[0].action, FileAction::Moved);
+            assert_eq!(files[0].dest, Some(dest.path().join("IMG_7612.JPG")));
+            assert_eq!(files[1].action, FileAction::Duplicate);
+            assert_eq!(r.files_omitted, 0);
         }
-    };
+        assert!(dry.dry_run && !result.dry_run);
+        let detail = dry.files.iter().find_map(|f| f.detail.as_deref());
+        assert_eq!(detail, Some("would move"));
+    }
 
-    let mut dest_hash_index = HashSet::new();
-    for (i, path) in dest_files.iter().enumerate() {
-        if cancel.load(Ordering::Relaxed) {
-            break;
+    #[test]
+    fn test_parallel_workers_move_each_content_once() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        for card in 0..6 {
+            let dir = src.path().join(format!("card{}", card));
+            fs::create_dir_all(&dir).unwrap();
+            for shot in 0..4 {
+                let content = format!("shot {}", shot);
+                fs::write(dir.join(format!("IMG_{}7612.JPG", shot)), content).unwrap();
+            }
         }
-        if (i % 50 == 0 || i == dest_files.len() - 1) && i < dest_files.len() {
-            let pct = 5.0 + (i as f64 / dest_files.len().max(1) as f64) * 15.0;
-            emit(ProgressEvent {
-                phase: Phase::IndexingDestination,
-                current_file: Some(path.display().to_string()),
-                scanned: matched_count,
-                matched: matched_count,
-                moved: 0,
-                skipped_duplicates: 0,
-                errors: 0,
-                percent: pct,
-            });
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .layout(DestLayout::Flatten)
+            .workers(4);
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+
+        assert_eq!(result.errors, 0);
+        assert_eq!(result.moved, 4);
+        assert_eq!(result.skipped_duplicates, 20);
+        assert_eq!(result.files.len(), 24);
+        let mut landed: Vec<String> = fs::read_dir(dest.path())
+            .unwrap()
+            .map(|e| fs::read_to_string(e.unwrap().path()).unwrap())
+            .collect();
+        landed.sort();
+        assert_eq!(landed, ["shot 0", "shot 1", "shot 2", "shot 3"]);
+    }
+
+    #[test]
+    fn test_parallel_workers_keep_same_name_files_apart() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        for card in 0..8 {
+            let dir = src.path().join(format!("card{}", card));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("IMG_7612.JPG"), format!("card {}", card)).unwrap();
+        }
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .layout(DestLayout::Flatten)
+            .workers(4);
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+
+        assert_eq!(result.errors, 0);
+        assert_eq!(result.moved, 8);
+        let mut landed: Vec<String> = fs::read_dir(dest.path())
+            .unwrap()
+            .map(|e| fs::read_to_string(e.unwrap().path()).unwrap())
+            .collect();
+        landed.sort();
+        let expected: Vec<String> = (0..8).map(|c| format!("card {}", c)).collect();
+        assert_eq!(landed, expected);
+    }
+
+    #[test]
+    fn test_dry_run_predicts_batch_duplicates_and_collisions() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        for (card, content) in [("card0", "a"), ("card1", "a"), ("card2", "b")] {
+            fs::create_dir_all(src.path().join(card)).unwrap();
+            fs::write(src.path().join(card).join("IMG_7612.JPG"), content).unwrap();
         }
-        if let Ok(h) = hasher::hash_file(path) {
-            dest_hash_index.insert(h);
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .layout(DestLayout::Flatten)
+            .workers(1);
+
+        let dry = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        let real = run(&options, &RunControl::new(), None).unwrap();
+
+        for r in [&dry, &real] {
+            assert_eq!((r.moved, r.skipped_duplicates, r.errors), (2, 1, 0));
         }
+        let targets = |r: &RunResult| {
+            let mut t: Vec<_> = r.files.iter().filter_map(|f| f.dest.clone()).collect();
+            t.sort();
+            t
+        };
+        assert_eq!(targets(&dry), targets(&real));
+        assert_eq!(
+            targets(&real),
+            [
+                dest.path().join("IMG_7612-1.JPG"),
+                dest.path().join("IMG_7612.JPG")
+            ]
+        );
     }
 
-    emit(ProgressEvent {
-        phase: Phase::Moving,
-        current_file: None,
-        scanned: matched_count,
-        matched: matched_count,
-        moved: 0,
-        skipped_duplicates: 0,
-        errors: 0,
-        percent: 20.0,
-    });
+    #[test]
+    fn test_dry_run_and_plan_predict_renames_around_existing_files() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        for (card, content) in [("card0", "a"), ("card1", "b")] {
+            fs::create_dir_all(src.path().join(card)).unwrap();
+            fs::write(src.path().join(card).join("IMG_7612.JPG"), content).unwrap();
+        }
+        // A different file under the planned name, and a same-content one under another.
+        fs::write(dest.path().join("IMG_7612.JPG"), "older").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), "known").unwrap();
+        fs::write(dest.path().join("IMG_7608.JPG"), "known").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612, 7608")
+            .layout(DestLayout::Flatten)
+            .workers(1);
 
-    let total = candidates.len().max(1);
-    let mut moved = 0u64;
-    let mut skipped_duplicates = 0u64;
-    let mut errors = 0u64;
+        let dry = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        let planned = plan::plan(&options, &RunControl::new(), None).unwrap();
+        let real = run(&options, &RunControl::new(), None).unwrap();
 
-    for (i, entry) in candidates.into_iter().enumerate() {
-        if cancel.load(Ordering::Relaxed) {
-            break;
+        let targets = |r: &RunResult| {
+            let mut t: Vec<_> = r
+                .files
+                .iter()
+                .filter(|f| f.action == FileAction::Moved)
+                .filter_map(|f| f.dest.clone())
+                .collect();
+            t.sort();
+            t
+        };
+        let expected = [
+            dest.path().join("IMG_7612-1.JPG"),
+            dest.path().join("IMG_7612-2.JPG"),
+        ];
+        assert_eq!(targets(&dry), expected);
+        assert_eq!(targets(&real), expected);
+        let mut from_plan: Vec<_> = planned
+            .files
+            .iter()
+            .filter(|f| f.action == plan::PlannedAction::Move)
+            .filter_map(|f| f.dest.clone())
+            .collect();
+        from_plan.sort();
+        assert_eq!(from_plan, expected);
+        assert!(planned
+            .files
+            .iter()
+            .all(|f| f.action != plan::PlannedAction::Move || f.renamed));
+        for r in [&dry, &real] {
+            assert_eq!((r.moved, r.skipped_duplicates, r.errors), (2, 1, 0));
+        }
+        assert_eq!(
+            fs::read(dest.path().join("IMG_7612.JPG")).unwrap(),
+            b"older"
+        );
+    }
+
+    #[test]
+    fn test_moved_files_are_hashed_once() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::create_dir_all(src.path().join("backup")).unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"pick").unwrap();
+        fs::write(src.path().join("backup/IMG_7612.JPG"), b"pick").unwrap();
+        // One worker runs on this thread, so the thread-local counter sees every read.
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .transfer_mode(TransferMode::Copy)
+            .workers(1);
+
+        let before = hasher::FILES_HASHED.with(|n| n.get());
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        let hashed = hasher::FILES_HASHED.with(|n| n.get()) - before;
+
+        // Each source is read once; the second is found in the index the first one updated.
+        assert_eq!((result.moved, result.skipped_duplicates), (1, 1));
+        assert_eq!(hashed, 2);
+    }
+
+    #[test]
+    fn test_destination_is_hashed_only_for_the_sizes_of_matches() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dest.path().join("2023")).unwrap();
+        // An archive of 40 images of other sizes, and three sharing a size with a match.
+        for i in 0..40 {
+            let body = vec![b'a'; 100 + i];
+            fs::write(dest.path().join(format!("2023/IMG_{:04}.JPG", i)), body).unwrap();
         }
+        fs::write(dest.path().join("2023/archived.JPG"), b"pick one").unwrap();
+        fs::write(dest.path().join("2023/other.JPG"), b"pick two").unwrap();
+        fs::write(dest.path().join("IMG_7601.JPG"), b"unique 1").unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"pick one").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), b"new shot").unwrap();
+        fs::write(src.path().join("IMG_7600.JPG"), b"a").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612 7608 7600")
+            .dry_run(true)
+            .workers(1);
 
-        let src = &entry.path;
-        let dest = mover::dest_path_for(source_dir, dest_dir, src);
+        let before = hasher::FILES_HASHED.with(|n| n.get());
+        let plan = plan::plan(&options, &RunControl::new(), None).unwrap();
+        let hashed = hasher::FILES_HASHED.with(|n| n.get()) - before;
 
-        let percent = 20.0 + (i as f64 / total as f64) * 80.0;
-        emit(ProgressEvent {
-            phase: Phase::Moving,
-            current_file: Some(src.display().to_string()),
-            scanned: matched_count,
-            matched: matched_count,
-            moved,
-            skipped_duplicates,
-            errors,
-            percent,
+        // What indexing every destination file up front would decide.
+        let everything: HashSet<String> = scanner::list_images_under(dest.path())
+            .unwrap()
+            .iter()
+            .map(|p| hasher::hash_file(p).unwrap())
+            .collect();
+        assert_eq!(plan.files.len(), 3);
+        for f in &plan.files {
+            let duplicate = f.action == plan::PlannedAction::Duplicate;
+            assert_eq!(duplicate, everything.contains(&f.hash), "{:?}", f.source);
+        }
+        assert_eq!(plan.preview.skipped_duplicates, 1);
+        // The three matches, then the three destination files of 8 bytes; nothing else.
+        assert_eq!(hashed, 3 + 3);
+        let stats = &plan.preview.stats;
+        assert_eq!((stats.dest_indexed, stats.dest_hashed), (43, 3));
+    }
+
+    #[test]
+    fn test_in_batch_duplicates_are_grouped() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::create_dir_all(src.path().join("backup")).unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"pick").unwrap();
+        fs::write(src.path().join("backup/IMG_7612.JPG"), b"pick").unwrap();
+        fs::write(src.path().join("IMG_8612.JPG"), b"archived").unwrap();
+        fs::write(dest.path().join("old.JPG"), b"archived").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612 8612").workers(1);
+
+        let dry = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        let real = run(&options, &RunControl::new(), None).unwrap();
+
+        for r in [&dry, &real] {
+            assert_eq!((r.skipped_duplicates, r.source_duplicates), (2, 1));
+            assert_eq!(r.duplicate_groups.len(), 1);
+            let group = &r.duplicate_groups[0];
+            assert_eq!(group.copies.len(), 1);
+            assert_ne!(group.source, group.copies[0]);
+            assert_eq!(group.dest.as_ref().unwrap().parent(), Some(dest.path()));
+            let copy = r
+                .files
+                .iter()
+                .find(|f| f.source == group.copies[0])
+                .unwrap();
+            assert!(copy.detail.as_deref().unwrap().starts_with("copy of "));
+        }
+        assert_eq!(dry.duplicate_groups, real.duplicate_groups);
+    }
+
+    #[test]
+    fn test_cancel_during_execution_counts_unattempted_files() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        for shot in 0..3 {
+            fs::write(src.path().join(format!("IMG_{}7612.JPG", shot)), [shot]).unwrap();
+        }
+        let control = std::sync::Arc::new(RunControl::new());
+        let on_move = control.clone();
+        let done = std::sync::Arc::new(Mutex::new(None));
+        let sink = done.clone();
+        // Cancel as soon as the first file is being moved.
+        let progress: ProgressFn = Box::new(move |ev| {
+            if matches!(ev.phase, Phase::Moving) && ev.current_file.is_some() {
+                on_move.cancel();
+            }
+            if matches!(ev.phase, Phase::Done) {
+                *sink.lock().unwrap() = Some((ev.cancelled, ev.current_index, ev.percent));
+            }
         });
+        let options = RunOptions::new(src.path(), dest.path(), "7612").workers(1);
 
-        if dry_run {
-            if dest_hash_index.contains(&match hasher::hash_file(src) {
-                Ok(h) => h,
-                Err(_) => {
-                    errors += 1;
-                    continue;
-                }
-            }) {
-                skipped_duplicates += 1;
-            } else {
-                moved += 1;
+        let result = run(&options, &control, Some(progress)).unwrap();
+
+        assert!(result.cancelled);
+        assert_eq!(
+            (result.matched, result.moved, result.not_attempted),
+            (3, 1, 2)
+        );
+        // The stats cover what was done before the cancel.
+        assert_eq!(result.stats.bytes_moved, 1);
+        assert!(result.stats.finished_at.is_some());
+        let (cancelled, processed, percent) = done.lock().unwrap().unwrap();
+        assert!(cancelled);
+        assert_eq!(processed, 1);
+        assert!(percent < 100.0);
+    }
+
+    #[test]
+    fn test_cancel_with_rejects_counts_only_matches() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        for shot in 0..2 {
+            fs::write(src.path().join(format!("IMG_{}7612.JPG", shot)), [shot]).unwrap();
+            fs::write(src.path().join(format!("IMG_000{}.JPG", shot)), [shot]).unwrap();
+        }
+        let control = std::sync::Arc::new(RunControl::new());
+        let on_move = control.clone();
+        let events = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let progress: ProgressFn = Box::new(move |ev| {
+            if matches!(ev.phase, Phase::Moving) && ev.current_file.is_some() {
+                on_move.cancel();
             }
-            if verbose {
-                println!("[dry-run] would move {} -> {}", src.display(), dest.display());
+            sink.lock()
+                .unwrap()
+                .push((ev.phase, ev.current_index, ev.total_count));
+        });
+        let options = RunOptions::new(src.path(), dest.path().join("keep"), "7612")
+            .rejects(Some(dest.path().join("rejects")))
+            .workers(1);
+
+        let result = run(&options, &control, Some(progress)).unwrap();
+
+        assert!(result.cancelled);
+        assert_eq!(
+            (result.matched, result.rejects_found, result.moved),
+            (2, 2, 1)
+        );
+        assert_eq!(result.not_attempted, 1);
+        let events = events.lock().unwrap();
+        assert!(events
+            .iter()
+            .filter(|(phase, ..)| matches!(phase, Phase::Moving))
+            .all(|&(_, index, count)| index <= count && count == 2));
+        let &(_, processed, count) = events.last().unwrap();
+        assert_eq!((processed, count), (1, 2));
+    }
+
+    #[test]
+    fn test_failed_move_reports_error_detail() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::create_dir_all(src.path().join("DCIM")).unwrap();
+        fs::write(src.path().join("DCIM/IMG_7612.JPG"), b"pick").unwrap();
+        // A file where the mirrored folder should go: the move cannot create its target, even
+        // for root, who ignores permission bits.
+        fs::write(dest.path().join("DCIM"), b"not a folder").unwrap();
+        let errors = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = errors.clone();
+        let progress: ProgressFn = Box::new(move |ev| {
+            if let Some(e) = ev.error {
+                sink.lock().unwrap().push(e);
             }
-            continue;
+        });
+        let options = RunOptions::new(src.path(), dest.path(), "7612");
+
+        let result = run(&options, &RunControl::new(), Some(progress)).unwrap();
+
+        assert_eq!(result.errors, 1);
+        let detail = &result.error_details[0];
+        assert_eq!(detail.stage, ErrorStage::Move);
+        assert!(detail.path.as_ref().unwrap().ends_with("DCIM/IMG_7612.JPG"));
+        assert!(!detail.message.is_empty());
+        assert_eq!(*errors.lock().unwrap(), result.error_details);
+        assert!(src.path().join("DCIM/IMG_7612.JPG").exists());
+    }
+
+    #[test]
+    fn test_run_stops_after_consecutive_failures_and_can_resume() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let state = dest.path().join("run.state");
+        fs::create_dir_all(src.path().join("DCIM")).unwrap();
+        for shot in 0..6 {
+            let name = format!("DCIM/IMG_{}7612.JPG", shot);
+            fs::write(src.path().join(name), [shot]).unwrap();
         }
+        // Every move fails the same way, as into an unmounted volume.
+        fs::write(dest.path().join("DCIM"), b"not a folder").unwrap();
+        let done = std::sync::Arc::new(Mutex::new(None));
+        let sink = done.clone();
+        let progress: ProgressFn = Box::new(move |ev| {
+            if matches!(ev.phase, Phase::Done) {
+                *sink.lock().unwrap() = Some((ev.aborted.is_some(), ev.cancelled, ev.percent));
+            }
+        });
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .workers(1)
+            .max_consecutive_errors(3)
+            .state_path(Some(state.clone()));
 
-        match mover::move_file(src, &dest, &dest_hash_index) {
-            Ok(mover::MoveResult::Moved(actual_dest)) => {
-                moved += 1;
-                let new_hash = hasher::hash_file(&actual_dest).ok();
-                if let Some(h) = new_hash {
-                    dest_hash_index.insert(h);
-                }
+        let result = run(&options, &RunControl::new(), Some(progress)).unwrap();
+
+        assert!(!result.cancelled);
+        assert!(matches!(
+            result.aborted,
+            Some(Abort::DestinationUnavailable { failures: 3, .. })
+        ));
+        assert_eq!(
+            (result.matched, result.errors, result.not_attempted),
+            (6, 3, 3)
+        );
+        let (aborted, cancelled, percent) = done.lock().unwrap().unwrap();
+        assert!(aborted && !cancelled && percent < 100.0);
+        assert_eq!(result.state_path.as_deref(), Some(state.as_path()));
+
+        // Once the destination is back, the files not attempted are picked up.
+        fs::remove_file(dest.path().join("DCIM")).unwrap();
+        let resumed = resume(&state, &RunControl::new(), None).unwrap();
+        assert_eq!((resumed.moved, resumed.errors, resumed.resumed), (3, 0, 3));
+        assert!(resumed.aborted.is_none());
+    }
+
+    #[test]
+    fn test_recently_modified_files_are_skipped_as_busy() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let (done, busy) = (
+            src.path().join("IMG_07612.JPG"),
+            src.path().join("IMG_17612.JPG"),
+        );
+        fs::write(&done, b"done").unwrap();
+        fs::write(&busy, b"still importing").unwrap();
+        let old = filetime::FileTime::from_unix_time(1_500_000_000, 0);
+        filetime::set_file_mtime(&done, old).unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .workers(1)
+            .min_age(600);
+
+        let dry = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        assert_eq!((dry.moved, dry.skipped_busy, dry.errors), (1, 1, 0));
+        assert_eq!(dry.busy_files, [busy.as_path()]);
+
+        // Written to again between planning and moving: checked just before the move.
+        let plan = plan(&options, &RunControl::new(), None).unwrap();
+        filetime::set_file_mtime(&done, filetime::FileTime::now()).unwrap();
+        let result = execute(&plan, &RunControl::new(), None);
+        assert_eq!(
+            (result.moved, result.skipped_busy, result.skipped_changed),
+            (0, 2, 0)
+        );
+        assert!(done.exists() && busy.exists());
+        assert!(result.files.iter().all(|f| f.action == FileAction::Skipped
+            && f.detail
+                .as_deref()
+                .unwrap()
+                .starts_with("still being written")));
+    }
+
+    #[test]
+    fn test_limit_takes_the_first_matches_in_path_order() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        for shot in [4, 1, 3, 0, 2] {
+            fs::write(src.path().join(format!("IMG_{}7612.JPG", shot)), [shot]).unwrap();
+        }
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .layout(DestLayout::Flatten)
+            .limit(2);
+
+        let dry = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        assert_eq!((dry.matched, dry.moved, dry.over_limit), (2, 2, 3));
+        assert_eq!(dry.limit, Some(2));
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((result.matched, result.moved, result.over_limit), (2, 2, 3));
+        assert_eq!(result.not_attempted, 0);
+        assert!(dest.path().join("IMG_07612.JPG").exists());
+        assert!(dest.path().join("IMG_17612.JPG").exists());
+        assert!(src.path().join("IMG_27612.JPG").exists());
+    }
+
+    #[test]
+    fn test_fail_fast_stops_at_the_first_failed_file() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        for name in [
+            "A/IMG_07612.JPG",
+            "DCIM/IMG_17612.JPG",
+            "DCIM/IMG_27612.JPG",
+            "Z/IMG_37612.JPG",
+        ] {
+            let path = src.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, name).unwrap();
+        }
+        fs::write(dest.path().join("DCIM"), b"not a folder").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .workers(1)
+            .fail_fast(true);
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+
+        assert!(!result.cancelled);
+        let Some(Abort::FailFast { path, .. }) = &result.aborted else {
+            panic!("not stopped by fail-fast: {:?}", result.aborted);
+        };
+        assert!(path.ends_with("DCIM/IMG_17612.JPG"));
+        assert_eq!(
+            (result.moved, result.errors, result.not_attempted),
+            (1, 1, 2)
+        );
+        let failed = result
+            .files
+            .iter()
+            .find(|f| f.action == FileAction::Error)
+            .unwrap();
+        assert!(failed
+            .detail
+            .as_deref()
+            .unwrap()
+            .ends_with("run stopped here (fail-fast)"));
+        assert!(src.path().join("Z/IMG_37612.JPG").exists());
+    }
+
+    #[test]
+    fn test_paranoid_mode_moves_a_hash_match_whose_bytes_differ() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let existing = dest.path().join("IMG_07612.JPG");
+        let (mismatched, copy) = (
+            src.path().join("IMG_07612.JPG"),
+            src.path().join("IMG_17612.JPG"),
+        );
+        fs::write(&existing, b"original!").unwrap();
+        fs::write(&mismatched, b"corrupted").unwrap();
+        fs::write(&copy, b"original!").unwrap();
+        let control = RunControl::new();
+        assert!(!hasher::same_bytes(&existing, &mismatched, &control).unwrap());
+        assert!(hasher::same_bytes(&existing, &copy, &control).unwrap());
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .layout(DestLayout::Flatten)
+            .workers(1)
+            .duplicate_action(DuplicateAction::Delete)
+            .confirm_duplicates(true);
+
+        let mut plan = plan(&options, &control, None).unwrap();
+        let (a, b) = (&plan.files[0], &plan.files[1]);
+        assert_eq!(
+            (a.action, b.action),
+            (PlannedAction::Move, PlannedAction::Duplicate)
+        );
+        assert_eq!(b.existing.as_deref(), Some(existing.as_path()));
+        // As if the read while planning had been corrupted: the same size and hash as the
+        // destination file, planned to be skipped at its name.
+        let f = &mut plan.files[0];
+        f.action = PlannedAction::Duplicate;
+        f.hash = hasher::hash_file(&existing).unwrap();
+        f.dest = Some(existing.clone());
+        f.renamed = false;
+        f.existing = Some(existing.clone());
+
+        let result = execute(&plan, &control, None);
+        assert_eq!(
+            (
+                result.moved,
+                result.skipped_duplicates,
+                result.hash_mismatches
+            ),
+            (1, 1, 1)
+        );
+        assert_eq!(result.duplicates_removed, 1);
+        assert_eq!(fs::read(&existing).unwrap(), b"original!");
+        let renamed = dest.path().join("IMG_07612-1.JPG");
+        assert_eq!(fs::read(renamed).unwrap(), b"corrupted");
+        assert!(!mismatched.exists() && !copy.exists());
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("but different content"));
+    }
+
+    #[test]
+    fn test_routes_send_suffixes_to_their_own_folders() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let (main, wedding, other) = (
+            dest.path().join("main"),
+            dest.path().join("wedding"),
+            dest.path().join("other"),
+        );
+        for (name, body) in [
+            ("IMG_7601.JPG", "a"),
+            ("IMG_7612.JPG", "b"),
+            ("IMG_7620.JPG", "c"),
+        ] {
+            fs::write(src.path().join(name), body).unwrap();
+        }
+        // Content already in one destination is no duplicate for a file routed elsewhere.
+        fs::create_dir_all(&main).unwrap();
+        fs::write(main.join("old.jpg"), "a").unwrap();
+        fs::create_dir_all(&other).unwrap();
+        fs::write(other.join("old.jpg"), "c").unwrap();
+        let options = RunOptions::new(src.path(), &main, "7612")
+            .layout(DestLayout::Flatten)
+            .route("7600-7610, 7620", &wedding)
+            .route("7620", &other);
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((result.moved, result.skipped_duplicates), (3, 0));
+        assert_eq!(fs::read(main.join("IMG_7612.JPG")).unwrap(), b"b");
+        assert_eq!(fs::read(wedding.join("IMG_7601.JPG")).unwrap(), b"a");
+        assert_eq!(fs::read(wedding.join("IMG_7620.JPG")).unwrap(), b"c");
+        let record = |name: &str| {
+            result
+                .files
+                .iter()
+                .find(|f| f.source.ends_with(name))
+                .unwrap()
+        };
+        assert_eq!(
+            record("IMG_7612.JPG").route.as_deref(),
+            Some(main.as_path())
+        );
+        assert!(record("IMG_7601.JPG").also_routed.is_empty());
+        let both = record("IMG_7620.JPG");
+        assert_eq!(both.route.as_deref(), Some(wedding.as_path()));
+        assert_eq!(both.also_routed, vec![other.clone()]);
+        assert!(both
+            .detail
+            .as_deref()
+            .unwrap()
+            .contains("the first rule was followed"));
+
+        // A file matching the route's content is a duplicate there.
+        fs::write(src.path().join("IMG_8601.JPG"), "a").unwrap();
+        let options = RunOptions::new(src.path(), &main, "7612").route("8601", &wedding);
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((result.moved, result.skipped_duplicates), (0, 1));
+    }
+
+    #[test]
+    fn test_nested_route_destinations_are_refused() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), "a").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .route("7601", dest.path().join("wedding"));
+
+        let err = run(&options, &RunControl::new(), None).unwrap_err();
+        assert!(
+            matches!(err, RunError::DestinationsOverlap { .. }),
+            "{}",
+            err
+        );
+        assert_eq!(err.kind(), "destinations_overlap");
+        assert!(src.path().join("IMG_7612.JPG").exists());
+    }
+
+    #[test]
+    fn test_rejects_folder_takes_the_images_no_suffix_matched() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let (keep, rejects) = (dest.path().join("keep"), dest.path().join("rejects"));
+        fs::create_dir_all(src.path().join("100CANON")).unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), "pick").unwrap();
+        fs::write(src.path().join("IMG_0001.JPG"), "blurry").unwrap();
+        fs::write(src.path().join("100CANON/IMG_0002.JPG"), "closed eyes").unwrap();
+        fs::write(src.path().join("IMG_0003.JPG"), "seen").unwrap();
+        fs::write(src.path().join("notes.txt"), "not an image").unwrap();
+        fs::create_dir_all(&rejects).unwrap();
+        fs::write(rejects.join("old.jpg"), "seen").unwrap();
+        let options = RunOptions::new(src.path(), &keep, "7612").rejects(Some(rejects.clone()));
+
+        let preview = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        let counts = |r: &RunResult| {
+            (
+                r.matched,
+                r.moved,
+                r.rejects_found,
+                r.rejected,
+                r.rejected_duplicates,
+            )
+        };
+        assert_eq!(counts(&preview), (1, 1, 3, 2, 1));
+        assert!(src.path().join("IMG_0001.JPG").exists());
+        assert!(!rejects.join("IMG_0001.JPG").exists());
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!(counts(&result), (1, 1, 3, 2, 1));
+        assert_eq!(result.suffixes.len(), 1);
+        assert_eq!(result.suffixes[0].moved, 1);
+        assert_eq!(fs::read(keep.join("IMG_7612.JPG")).unwrap(), b"pick");
+        assert_eq!(fs::read(rejects.join("IMG_0001.JPG")).unwrap(), b"blurry");
+        let nested = rejects.join("100CANON").join("IMG_0002.JPG");
+        assert_eq!(fs::read(nested).unwrap(), b"closed eyes");
+        assert!(src.path().join("IMG_0003.JPG").exists());
+        assert!(src.path().join("notes.txt").exists());
+        let record = result
+            .files
+            .iter()
+            .find(|f| f.source.ends_with("IMG_0001.JPG"))
+            .unwrap();
+        assert_eq!(record.route.as_deref(), Some(rejects.as_path()));
+    }
+
+    #[test]
+    fn test_duplicates_record_where_their_content_is() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let delivered = dest.path().join("2024/05/wedding/IMG_7612.JPG");
+        fs::create_dir_all(delivered.parent().unwrap()).unwrap();
+        fs::write(&delivered, "pick").unwrap();
+        fs::create_dir_all(src.path().join("backup")).unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), "pick").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), "second").unwrap();
+        fs::write(src.path().join("backup/IMG_7608.JPG"), "second").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612, 7608");
+
+        for dry_run in [true, false] {
+            let result = run(&options.clone().dry_run(dry_run), &RunControl::new(), None).unwrap();
+            let existing = |name: &str| {
+                let record = result.files.iter().find(|f| f.source.ends_with(name));
+                record.unwrap().existing.clone()
+            };
+            assert_eq!(existing("IMG_7612.JPG"), Some(delivered.clone()));
+            let copy = result
+                .files
+                .iter()
+                .find(|f| f.action == FileAction::Duplicate && f.duplicate_of.is_some())
+                .unwrap();
+            let original = result
+                .files
+                .iter()
+                .find(|f| Some(&f.source) == copy.duplicate_of.as_ref())
+                .unwrap();
+            assert_eq!(copy.existing, original.dest);
+            assert!(copy.existing.is_some());
+        }
+    }
+
+    #[test]
+    fn test_read_only_marks_moved_files_including_renamed_ones() {
+        let (src, dest) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        fs::write(src.path().join("IMG_7612.JPG"), "new").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), "second").unwrap();
+        fs::write(dest.path().join("IMG_7612.JPG"), "old").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612, 7608").read_only(true);
+        let read_only = |p: &Path| fs::metadata(p).unwrap().permissions().readonly();
+
+        let preview = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        assert_eq!(preview.read_only, 2);
+        assert!(preview.files.iter().all(|f| f
+            .detail
+            .as_deref()
+            .is_some_and(|d| d.contains("would be made read-only"))));
+        assert!(!read_only(&src.path().join("IMG_7612.JPG")));
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((result.moved, result.read_only), (2, 2));
+        assert!(read_only(&dest.path().join("IMG_7612-1.JPG")));
+        assert!(read_only(&dest.path().join("IMG_7608.JPG")));
+        assert!(!read_only(&dest.path().join("IMG_7612.JPG")));
+        assert!(result.files.iter().all(|f| f.read_only));
+    }
+
+    /// A source holding a link IMG_7612.JPG to an image in another folder, and a regular
+    /// IMG_7608.JPG; the folder with the image is returned too.
+    #[cfg(unix)]
+    fn linked_source() -> (tempfile::TempDir, tempfile::TempDir) {
+        let (src, elsewhere) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        fs::write(elsewhere.path().join("7612.JPG"), "linked").unwrap();
+        std::os::unix::fs::symlink(
+            elsewhere.path().join("7612.JPG"),
+            src.path().join("IMG_7612.JPG"),
+        )
+        .unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), "regular").unwrap();
+        (src, elsewhere)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_are_skipped_by_default() {
+        let (src, _elsewhere) = linked_source();
+        let dest = tempfile::tempdir().unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612, 7608");
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((result.matched, result.moved), (1, 1));
+        assert_eq!((result.skipped_symlinks, result.broken_links), (1, 0));
+        assert!(mover::is_symlink(&src.path().join("IMG_7612.JPG")));
+        assert!(!dest.path().join("IMG_7612.JPG").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policy_link_moves_the_link() {
+        let (src, elsewhere) = linked_source();
+        let dest = tempfile::tempdir().unwrap();
+        let options =
+            RunOptions::new(src.path(), dest.path(), "7612, 7608").symlinks(SymlinkPolicy::Link);
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((result.moved, result.skipped_symlinks), (2, 0));
+        let moved = dest.path().join("IMG_7612.JPG");
+        assert!(mover::is_symlink(&moved));
+        assert_eq!(fs::read_to_string(&moved).unwrap(), "linked");
+        assert!(!src.path().join("IMG_7612.JPG").exists());
+        assert!(elsewhere.path().join("7612.JPG").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policy_target_puts_the_image_in_place() {
+        let (src, elsewhere) = linked_source();
+        let dest = tempfile::tempdir().unwrap();
+        let options =
+            RunOptions::new(src.path(), dest.path(), "7612, 7608").symlinks(SymlinkPolicy::Target);
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((result.moved, result.errors), (2, 0));
+        let moved = dest.path().join("IMG_7612.JPG");
+        assert!(!mover::is_symlink(&moved));
+        assert_eq!(fs::read_to_string(&moved).unwrap(), "linked");
+        // The link is gone from the source; the image it pointed to stays.
+        assert!(fs::symlink_metadata(src.path().join("IMG_7612.JPG")).is_err());
+        assert!(elsewhere.path().join("7612.JPG").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_verifying_a_copy_advances_the_byte_progress() {
+        let (src, _elsewhere) = linked_source();
+        let dest = tempfile::tempdir().unwrap();
+        let events = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let progress: ProgressFn = Box::new(move |ev| {
+            if matches!(ev.phase, Phase::Moving | Phase::Done) {
+                sink.lock().unwrap().push((ev.bytes_done, ev.total_bytes));
+            }
+        });
+        // Moving the image a link points to copies it, and the copy is verified.
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .symlinks(SymlinkPolicy::Target)
+            .workers(1);
+
+        let result = run(&options, &RunControl::new(), Some(progress)).unwrap();
+        assert_eq!((result.moved, result.verified), (1, 1));
+        let size = "linked".len() as u64;
+        let events = events.lock().unwrap();
+        assert_eq!(events.first(), Some(&(0, size)));
+        // The file's event counts the re-hash; the copy is counted once the file is done.
+        assert!(events.contains(&(size, 2 * size)), "{:?}", events);
+        assert_eq!(events.last(), Some(&(2 * size, 2 * size)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_broken_links_are_counted_and_warned_about() {
+        let (src, dest) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        std::os::unix::fs::symlink(src.path().join("gone.JPG"), src.path().join("IMG_7612.JPG"))
+            .unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), "regular").unwrap();
+        let options =
+            RunOptions::new(src.path(), dest.path(), "7612, 7608").symlinks(SymlinkPolicy::Target);
+
+        let preview = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        assert_eq!((preview.matched, preview.broken_links), (1, 1));
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!(
+            (result.moved, result.errors, result.broken_links),
+            (1, 0, 1)
+        );
+        assert!(result.warnings[0].contains("broken symbolic link"));
+        assert!(mover::is_symlink(&src.path().join("IMG_7612.JPG")));
+    }
+
+    #[test]
+    fn test_tag_mode_renames_matches_where_they_are() {
+        let src = tempfile::tempdir().unwrap();
+        let unused = src.path().join("never-created");
+        fs::create_dir_all(src.path().join("100CANON")).unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), "pick").unwrap();
+        fs::write(src.path().join("100CANON/IMG_7608.JPG"), "second").unwrap();
+        fs::write(src.path().join("100CANON/IMG_7608_pick.JPG"), "other").unwrap();
+        fs::write(src.path().join("IMG_0001.JPG"), "not picked").unwrap();
+        let tag = NameTag::parse("_pick").unwrap();
+        let options = RunOptions::new(src.path(), &unused, "7612, 7608")
+            .tag(Some(tag))
+            .remove_empty_dirs(true, false);
+
+        let preview = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        assert_eq!(
+            (preview.tagged, preview.moved, preview.removed_dirs),
+            (2, 0, 0)
+        );
+        assert!(src.path().join("IMG_7612.JPG").exists());
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((result.tagged, result.moved, result.errors), (2, 0, 0));
+        assert_eq!(result.stats.dest_indexed, 0);
+        assert_eq!(
+            fs::read(src.path().join("IMG_7612_pick.JPG")).unwrap(),
+            b"pick"
+        );
+        let renamed = src.path().join("100CANON/IMG_7608_pick-1.JPG");
+        assert_eq!(fs::read(&renamed).unwrap(), b"second");
+        assert!(!src.path().join("IMG_7612.JPG").exists());
+        assert!(src.path().join("IMG_0001.JPG").exists());
+        assert!(!unused.exists());
+        let record = result
+            .files
+            .iter()
+            .find(|f| f.source.ends_with("IMG_7608.JPG"))
+            .unwrap();
+        assert_eq!(record.dest.as_deref(), Some(renamed.as_path()));
+
+        // Tagged names no longer match, so a second run has nothing to do.
+        let again = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!(again.tagged, 0);
+    }
+
+    #[test]
+    fn test_update_mode_replaces_older_destination_files() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let old = filetime::FileTime::from_unix_time(1_500_000_000, 0);
+        for (name, body) in [("IMG_7612.JPG", "edit"), ("IMG_7608.JPG", "archived edit")] {
+            fs::write(dest.path().join(name), body).unwrap();
+            filetime::set_file_mtime(dest.path().join(name), old).unwrap();
+        }
+        fs::write(src.path().join("IMG_7612.JPG"), "newer edit").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), "older edit").unwrap();
+        let older = filetime::FileTime::from_unix_time(1_400_000_000, 0);
+        filetime::set_file_mtime(src.path().join("IMG_7608.JPG"), older).unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612, 7608")
+            .on_collision(CollisionMode::Update);
+        let counts = |r: &RunResult| (r.moved, r.replaced, r.skipped_newer);
+
+        let preview = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        assert_eq!(counts(&preview), (0, 1, 1));
+        assert_eq!(fs::read(dest.path().join("IMG_7612.JPG")).unwrap(), b"edit");
+
+        let old_hash = hasher::hash_file(&dest.path().join("IMG_7612.JPG")).unwrap();
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!(counts(&result), (0, 1, 1));
+        assert_eq!(
+            fs::read(dest.path().join("IMG_7612.JPG")).unwrap(),
+            b"newer edit"
+        );
+        assert_eq!(
+            fs::read(dest.path().join("IMG_7608.JPG")).unwrap(),
+            b"archived edit"
+        );
+        assert!(src.path().join("IMG_7608.JPG").exists());
+        // The replaced file is kept next to its successor.
+        assert_eq!(fs::read_dir(dest.path()).unwrap().count(), 3);
+        let record = |name: &str| {
+            result
+                .files
+                .iter()
+                .find(|f| f.source.ends_with(name))
+                .unwrap()
+                .clone()
+        };
+        let replaced = record("IMG_7612.JPG").replaced.unwrap();
+        assert_eq!(replaced.hash, old_hash);
+        assert!(replaced.modified.is_some());
+        assert_eq!(fs::read(&replaced.kept_at).unwrap(), b"edit");
+        assert_eq!(record("IMG_7608.JPG").action, FileAction::Skipped);
+    }
+
+    #[test]
+    fn test_rejects_folder_must_not_overlap_sources_or_dest() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_0001.JPG"), "reject").unwrap();
+        for rejects in [src.path().join("rejects"), dest.path().join("rejects")] {
+            let options = RunOptions::new(src.path(), dest.path(), "7612").rejects(Some(rejects));
+            let err = run(&options, &RunControl::new(), None).unwrap_err();
+            assert_eq!(err.kind(), "rejects_overlap", "{}", err);
+        }
+        assert!(src.path().join("IMG_0001.JPG").exists());
+    }
+
+    #[test]
+    fn test_setup_error_detail() {
+        let dest = tempfile::tempdir().unwrap();
+        let report_path = dest.path().join("report.json");
+        let options = RunOptions::new(dest.path().join("missing"), dest.path(), "7612")
+            .report_path(Some(report_path.clone()));
+
+        let err = run(&options, &RunControl::new(), None).unwrap_err();
+
+        assert!(matches!(err, RunError::SourceUnavailable { .. }), "{}", err);
+        assert!(err.to_string().starts_with("source "));
+        let report = fs::read_to_string(&report_path).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+        assert_eq!(report["result"]["errors"], 1);
+        assert_eq!(report["result"]["errorDetails"][0]["stage"], "setup");
+
+        let options = RunOptions::new(dest.path(), dest.path(), "abc");
+        let err = run(&options, &RunControl::new(), None).unwrap_err();
+        assert!(matches!(err, RunError::InvalidSuffixes { .. }));
+    }
+
+    #[test]
+    fn test_log_file_records_actions_and_its_failure_is_a_warning() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let logs = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"pick").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), b"second").unwrap();
+        let log_path = logs.path().join("runs").join("move.log");
+        let options =
+            RunOptions::new(src.path(), dest.path(), "7612").log_path(Some(log_path.clone()));
+
+        let result = run(&options, &RunControl::new(), None).unwrap();
+
+        assert_eq!(result.moved, 1);
+        assert_eq!(result.log_path.as_deref(), Some(log_path.as_path()));
+        let text = fs::read_to_string(&log_path).unwrap();
+        assert!(text.contains(" INFO  moved "), "{}", text);
+        assert!(text.contains(" DEBUG [dry-run] would move "), "{}", text);
+        assert!(text.contains(" DEBUG Run finished: 1 moved"), "{}", text);
+
+        // Under a regular file, the log cannot be created; the run goes on.
+        let options = RunOptions::new(src.path(), dest.path(), "7608")
+            .log_path(Some(log_path.join("nested.log")));
+        let result = run(&options, &RunControl::new(), None).unwrap();
+
+        assert_eq!(result.moved, 1);
+        assert!(result.log_path.is_none());
+        assert!(result.warnings[0].starts_with("Cannot write log file "));
+    }
+
+    #[test]
+    fn test_stats_count_bytes_for_dry_and_real_runs() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"new pick").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), b"in the archive").unwrap();
+        fs::write(dest.path().join("old.JPG"), b"in the archive").unwrap();
+        let done = std::sync::Arc::new(Mutex::new(None));
+        let sink = done.clone();
+        let progress: ProgressFn = Box::new(move |ev| {
+            if matches!(ev.phase, Phase::Done) {
+                *sink.lock().unwrap() = ev.stats;
             }
-            Ok(mover::MoveResult::SkippedDuplicate) => {
-                skipped_duplicates += 1;
+        });
+        let options = RunOptions::new(src.path(), dest.path(), "7612 7608");
+
+        let dry = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        let real = run(&options, &RunControl::new(), Some(progress)).unwrap();
+
+        for r in [&dry, &real] {
+            let stats = &r.stats;
+            assert_eq!((stats.bytes_moved, stats.bytes_skipped_duplicates), (8, 14));
+            let started = stats.started_at.as_deref().unwrap();
+            let finished = stats.finished_at.as_deref().unwrap();
+            assert!(started <= finished, "{} {}", started, finished);
+        }
+        assert_eq!(dry.stats.phases.move_ms, 0);
+        // The final event has the same stats as the result.
+        assert_eq!(done.lock().unwrap().as_ref(), Some(&real.stats));
+    }
+
+    #[test]
+    fn test_scan_counts_every_file_it_looks_at() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::create_dir_all(src.path().join("sub")).unwrap();
+        for name in [
+            "IMG_7612.JPG",
+            "IMG_0001.JPG",
+            "notes.txt",
+            "sub/IMG_0002.png",
+            "README",
+        ] {
+            fs::write(src.path().join(name), name).unwrap();
+        }
+        let events = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let progress: ProgressFn = Box::new(move |ev| {
+            sink.lock().unwrap().push((ev.phase, ev.scanned, ev.images));
+        });
+        let options = RunOptions::new(src.path(), dest.path(), "7612");
+
+        let planned = plan::plan(&options, &RunControl::new(), None).unwrap();
+        assert_eq!((planned.preview.scanned, planned.preview.images), (5, 3));
+        let result = plan::execute(&planned, &RunControl::new(), Some(progress));
+        assert_eq!(
+            (result.scanned, result.images, result.matched, result.moved),
+            (5, 3, 1, 1)
+        );
+        let events = events.lock().unwrap();
+        assert!(matches!(events.last(), Some((Phase::Done, 5, 3))));
+        assert!(events
+            .iter()
+            .all(|&(_, scanned, images)| (scanned, images) == (5, 3)));
+    }
+
+    #[test]
+    fn test_per_suffix_counts_and_unmatched_warning() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"new pick").unwrap();
+        fs::write(src.path().join("IMG_7612.PNG"), b"its export").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), b"in the archive").unwrap();
+        fs::write(dest.path().join("old.JPG"), b"in the archive").unwrap();
+        let events = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let progress: ProgressFn = Box::new(move |ev| {
+            if !ev.unmatched_suffixes.is_empty() {
+                sink.lock()
+                    .unwrap()
+                    .push((ev.phase, ev.warning, ev.unmatched_suffixes));
             }
-            Err(e) => {
-                errors += 1;
-                if verbose {
-                    eprintln!("Move error {} -> {}: {}", src.display(), dest.display(), e);
-                }
+        });
+        let options = RunOptions::new(src.path(), dest.path(), "7612, 7608, 7611, 7620");
+
+        let dry = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        let result = run(&options, &RunControl::new(), Some(progress)).unwrap();
+
+        for r in [&dry, &result] {
+            let rows: Vec<(u32, u64, u64, u64)> = r
+                .suffixes
+                .iter()
+                .map(|s| (s.suffix, s.matched, s.moved, s.skipped_duplicates))
+                .collect();
+            assert_eq!(
+                rows,
+                [
+                    (7608, 1, 0, 1),
+                    (7611, 0, 0, 0),
+                    (7612, 2, 2, 0),
+                    (7620, 0, 0, 0)
+                ]
+            );
+            assert_eq!(r.unmatched_suffixes(), [7611, 7620]);
+            assert_eq!(r.warnings[0], "No files found for: 7611, 7620");
+        }
+        // Sent once scanning is done, and again with the final event.
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0].0, Phase::ScanningSource));
+        assert_eq!(
+            events[0].1.as_deref(),
+            Some("No files found for: 7611, 7620")
+        );
+        assert!(matches!(events[1].0, Phase::Done));
+        assert_eq!(events[1].2, [7611, 7620]);
+    }
+
+    #[test]
+    fn test_matches_of_other_file_types_are_reported() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"pick").unwrap();
+        fs::write(src.path().join("IMG_7612.CR3"), b"raw").unwrap();
+        fs::write(src.path().join("IMG_7608.cr3"), b"raw").unwrap();
+        fs::write(src.path().join("MVI_7608.MP4"), b"clip").unwrap();
+        fs::write(src.path().join("IMG_7600.CR3"), b"not asked for").unwrap();
+        let warnings = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = warnings.clone();
+        let progress: ProgressFn = Box::new(move |ev| sink.lock().unwrap().extend(ev.warning));
+        let options = RunOptions::new(src.path(), dest.path(), "7612 7608");
+
+        let dry = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        let real = run(&options, &RunControl::new(), Some(progress)).unwrap();
+        let expected = "3 files matched your numbers but were skipped by the file-type filter \
+                        (.CR3, .MP4)";
+        for r in [&dry, &real] {
+            assert_eq!((r.matched, r.other_types()), (1, 3));
+            assert_eq!(r.other_type_extensions, [".CR3", ".MP4"]);
+            let rows: Vec<_> = r
+                .suffixes
+                .iter()
+                .map(|s| (s.suffix, s.other_types))
+                .collect();
+            assert_eq!(rows, [(7608, 2), (7612, 1)]);
+            assert!(r.warnings.iter().any(|w| w == expected), "{:?}", r.warnings);
+        }
+        assert!(warnings.lock().unwrap().iter().any(|w| w == expected));
+        assert!(src.path().join("IMG_7612.CR3").exists());
+    }
+
+    #[test]
+    fn test_percent_never_goes_backwards() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), vec![1u8; 200_000]).unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), vec![2u8; 10]).unwrap();
+        for i in 0..20 {
+            fs::write(dest.path().join(format!("OLD_{}.JPG", i)), vec![i; 50_000]).unwrap();
+        }
+        let percents = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = percents.clone();
+        let progress: ProgressFn = Box::new(move |ev| sink.lock().unwrap().push(ev.percent));
+        let options = RunOptions::new(src.path(), dest.path(), "7612 7608").workers(1);
+
+        run(&options, &RunControl::new(), Some(progress)).unwrap();
+
+        let percents = percents.lock().unwrap();
+        assert!(percents.windows(2).all(|w| w[0] <= w[1]), "{:?}", percents);
+        assert_eq!(percents.last(), Some(&100.0));
+        // Indexing the destination is most of this run's work.
+        assert!(
+            percents.iter().any(|&p| p > 20.0 && p < 80.0),
+            "{:?}",
+            percents
+        );
+    }
+
+    #[test]
+    fn test_byte_progress_in_dry_run() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("MOV_7612.JPG"), vec![1u8; 3000]).unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), vec![2u8; 100]).unwrap();
+        let events = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let progress: ProgressFn = Box::new(move |ev| {
+            if matches!(ev.phase, Phase::Planning | Phase::Done) {
+                sink.lock()
+                    .unwrap()
+                    .push((ev.bytes_done, ev.total_bytes, ev.total_count));
             }
+        });
+        let options = RunOptions::new(src.path(), dest.path(), "7612").dry_run(true);
+
+        run(&options, &RunControl::new(), Some(progress)).unwrap();
+
+        let events = events.lock().unwrap();
+        assert!(events
+            .iter()
+            .all(|&(_, total, count)| total == 3100 && count == 2));
+        assert!(events.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert_eq!(events.last().unwrap().0, 3100);
+    }
+
+    #[test]
+    fn test_paused_run_waits_and_can_be_resumed_or_cancelled() {
+        for cancel in [false, true] {
+            let src = tempfile::tempdir().unwrap();
+            let dest = tempfile::tempdir().unwrap();
+            fs::write(src.path().join("IMG_7612.JPG"), b"pick").unwrap();
+            let options = RunOptions::new(src.path(), dest.path(), "7612");
+            let control = RunControl::new();
+            control.pause();
+
+            let result = std::thread::scope(|s| {
+                let handle = s.spawn(|| run(&options, &control, None).unwrap());
+                std::thread::sleep(Duration::from_millis(300));
+                assert!(!dest.path().join("IMG_7612.JPG").exists());
+                if cancel {
+                    control.cancel();
+                } else {
+                    control.resume();
+                }
+                handle.join().unwrap()
+            });
+
+            assert_eq!(result.errors, 0);
+            assert_eq!(result.moved, u64::from(!cancel));
+            assert_eq!(result.cancelled, cancel);
+            assert_eq!(result.not_attempted, u64::from(cancel));
+            assert_eq!(dest.path().join("IMG_7612.JPG").exists(), !cancel);
+            assert_eq!(src.path().join("IMG_7612.JPG").exists(), cancel);
         }
     }
 
-    emit(ProgressEvent {
-        phase: Phase::Done,
-        current_file: None,
-        scanned: matched_count,
-        matched: matched_count,
-        moved,
-        skipped_duplicates,
-        errors,
-        percent: 100.0,
-    });
+    #[cfg(windows)]
+    #[test]
+    fn test_verbatim_source_root() {
+        let src = tempfile::tempdir().unwrap();
+        fs::create_dir_all(src.path().join("DCIM")).unwrap();
+        fs::write(src.path().join("DCIM/IMG_7612.JPG"), b"pick").unwrap();
+        let dest = tempfile::tempdir().unwrap();
 
-    RunResult {
-        scanned: matched_count,
-        matched: matched_count,
-        moved,
-        skipped_duplicates,
-        errors,
+        let verbatim = mover::long_path(src.path()).into_owned();
+        assert!(verbatim.to_string_lossy().starts_with(r"\\?\"));
+        let result = run_mirror(&verbatim, dest.path());
+
+        assert_eq!(result.errors, 0);
+        assert!(dest.path().join("DCIM").join("IMG_7612.JPG").exists());
     }
 }
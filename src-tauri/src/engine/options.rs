@@ -0,0 +1,536 @@
+//! Run configuration shared by the CLI and the Tauri command.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Default for `RunOptions::stall_warning`.
+pub const DEFAULT_STALL_WARNING_SECONDS: u64 = 30;
+
+/// `RunOptions::min_age` the CLI and the app use in watch mode unless told otherwise.
+pub const DEFAULT_WATCH_MIN_AGE_SECONDS: u64 = 10;
+
+/// Default for `RunOptions::max_consecutive_errors`.
+pub const DEFAULT_MAX_CONSECUTIVE_ERRORS: u32 = 10;
+
+use super::cache::IndexCache;
+use crate::collision::CollisionPattern;
+use crate::exclude::ExcludeGlobs;
+use crate::mover::{
+    CollisionMode, DestLayout, DuplicateAction, EmptyFilePolicy, MoveOptions, SymlinkPolicy,
+    TransferMode,
+};
+use crate::sanitize::SanitizeMode;
+use crate::tag::NameTag;
+
+/// How files already in the destination are recognized as duplicates.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupMode {
+    /// Hash every image in the destination first: the same content under any name or folder is
+    /// a duplicate.
+    #[default]
+    Content,
+    /// Skip indexing the destination; only a file with the same content at the exact path a
+    /// file would be moved to is a duplicate. Identical content under another name is not
+    /// caught.
+    SamePath,
+    /// No duplicate check at all: the destination is not indexed and sources are not hashed
+    /// (unless the check after moving re-hashes them). Every match is moved, renamed when its
+    /// name is taken. For a destination known to be empty; deleting or trashing duplicate
+    /// sources is refused.
+    Off,
+}
+
+impl DedupMode {
+    /// For summaries: "by content", "same path only" or "off".
+    pub fn describe(self) -> &'static str {
+        match self {
+            DedupMode::Content => "by content",
+            DedupMode::SamePath => "same path only",
+            DedupMode::Off => "off",
+        }
+    }
+}
+
+/// How an image is matched against the requested suffixes.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchBy {
+    /// The file name's stem must end with the suffix.
+    #[default]
+    Name,
+    /// The name the camera gave the file, from its XMP OriginalFileName or RawFileName, else
+    /// its EXIF ImageNumber, for files another tool renamed; files without them (or of a type
+    /// that carries none) are matched by name. Reads the start of every image: slower.
+    Metadata,
+}
+
+/// The check after moving that every file the run put in the destination is there.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyMode {
+    #[default]
+    Off,
+    /// Each destination file exists with the size it was moved with.
+    Exists,
+    /// Each destination file is also read again and must hash as it did when it was moved.
+    Content,
+}
+
+/// Files of some suffixes sent to another destination root (see `RunOptions::routes`).
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Route {
+    /// As `RunOptions::suffix_input`; may have ranges ("7600-7650").
+    pub suffixes: String,
+    pub dest: PathBuf,
+}
+
+impl Route {
+    pub fn new(suffixes: impl Into<String>, dest: impl Into<PathBuf>) -> Route {
+        Route {
+            suffixes: suffixes.into(),
+            dest: dest.into(),
+        }
+    }
+}
+
+/// Everything one run needs besides the cancel flag and progress callback.
+/// Deserializes from the frontend's camelCase options object; missing fields take defaults,
+/// and the move options are flattened in ("transferMode", "layout", ...). Serializes the same
+/// way, e.g. inside a saved `MovePlan`.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RunOptions {
+    /// Source roots; files from all of them share one destination index.
+    pub sources: Vec<PathBuf>,
+    pub dest: PathBuf,
+    /// Suffix list as typed: comma-, space- or newline-separated numbers.
+    pub suffix_input: String,
+    /// More destinations, each for its own suffixes: a match goes to the first of `dest` (for
+    /// `suffix_input`) and these routes that has its suffix. Every destination has its own
+    /// duplicate index and name collisions; they must not overlap. Empty for one destination.
+    pub routes: Vec<Route>,
+    /// Move the images that match no suffix here, in the same run and with the same layout
+    /// (culling: picks to `dest`, the rest to rejects). Counted apart from the matches; must
+    /// not overlap the sources or any destination.
+    pub rejects: Option<PathBuf>,
+    /// Simulate only; nothing is moved.
+    pub dry_run: bool,
+    pub verbose: bool,
+    /// Write a JSON report of the run (options, counters, per-file actions, timing) here.
+    pub report_path: Option<PathBuf>,
+    /// Log every action, warning and error of the run, with timestamps, to this file (or to a
+    /// timestamped file in this directory), whatever `verbose` says.
+    pub log_path: Option<PathBuf>,
+    /// Keep the plan and a journal of the files done in this file while the run moves, so an
+    /// interrupted run can be resumed (`engine::resume`). Removed once the run finishes.
+    pub state_path: Option<PathBuf>,
+    /// Files moved at once; 0 picks one for same-volume moves and a small pool otherwise.
+    pub workers: usize,
+    /// How duplicates of destination content are found; `SamePath` skips the destination index
+    /// and `Off` every duplicate check.
+    pub dedup: DedupMode,
+    /// Before a file is skipped as a duplicate, compare it byte for byte with the file its hash
+    /// matched; one that differs (a corrupted read) is moved under a new name instead and
+    /// counted in `RunResult::hash_mismatches`.
+    pub confirm_duplicates: bool,
+    /// Matching files whose name matches one of these globs are left out ("excludeGlobs": a
+    /// list of patterns like "thumb_*").
+    pub exclude_globs: ExcludeGlobs,
+    /// Match by file name, or by the original frame name in each image's metadata; see
+    /// `RunResult::numbered` for how many images had one.
+    pub match_by: MatchBy,
+    /// Don't stop the run when the destination looks too full or not writable.
+    pub skip_preflight: bool,
+    /// Bytes per second the run may read or write (hashing and copying, all workers together),
+    /// to leave the disk usable for other work; 0 for no limit.
+    pub throttle: u64,
+    /// Seconds one file may take before a "stalled" progress event says it is still being
+    /// worked on (a hung network share); repeated each time as long again. 0 for none.
+    pub stall_warning: u64,
+    /// Take only the first this many matches, in scan order (by path within each source, the
+    /// sources in the order given); the rest are counted in `RunResult::over_limit`. 0 takes
+    /// them all.
+    pub limit: u64,
+    /// Seconds since its last modification a file needs before it is read or moved; younger
+    /// ones may still be written (a card import or tethering app) and are skipped as busy,
+    /// checked again just before each file is moved. 0 takes every file.
+    pub min_age: u64,
+    /// After moving, check the files put in the destination (a failed check is an error).
+    pub verify: VerifyMode,
+    /// Stop the run at the first file that fails to move (a duplicate or a warning does not
+    /// count); the files after it are not attempted.
+    pub fail_fast: bool,
+    /// Stop the run once this many moves in a row failed with the same kind of error (the
+    /// destination went away); any file moved in between starts the count over. 0 never stops.
+    pub max_consecutive_errors: u32,
+    /// Destination hashes kept from the session's earlier runs, and updated with this one's
+    /// (see `IndexCache`); `None` hashes each destination afresh. Not part of a saved plan.
+    #[serde(skip)]
+    pub index_cache: Option<Arc<IndexCache>>,
+    #[serde(flatten)]
+    pub move_options: MoveOptions,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            sources: Vec::new(),
+            dest: PathBuf::new(),
+            suffix_input: String::new(),
+            routes: Vec::new(),
+            rejects: None,
+            dry_run: false,
+            verbose: false,
+            report_path: None,
+            log_path: None,
+            state_path: None,
+            workers: 0,
+            dedup: DedupMode::default(),
+            confirm_duplicates: false,
+            exclude_globs: ExcludeGlobs::default(),
+            match_by: MatchBy::default(),
+            skip_preflight: false,
+            throttle: 0,
+            stall_warning: DEFAULT_STALL_WARNING_SECONDS,
+            limit: 0,
+            min_age: 0,
+            verify: VerifyMode::Off,
+            fail_fast: false,
+            max_consecutive_errors: DEFAULT_MAX_CONSECUTIVE_ERRORS,
+            index_cache: None,
+            move_options: MoveOptions::default(),
+        }
+    }
+}
+
+impl RunOptions {
+    pub fn new(
+        source: impl Into<PathBuf>,
+        dest: impl Into<PathBuf>,
+        suffix_input: impl Into<String>,
+    ) -> RunOptions {
+        RunOptions {
+            sources: vec![source.into()],
+            dest: dest.into(),
+            suffix_input: suffix_input.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Replace the source roots (`new` starts with one).
+    pub fn sources(mut self, sources: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        self.sources = sources.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Add a destination for the files of `suffixes`, after those already given.
+    pub fn route(mut self, suffixes: impl Into<String>, dest: impl Into<PathBuf>) -> Self {
+        self.routes.push(Route::new(suffixes, dest));
+        self
+    }
+
+    pub fn routes(mut self, routes: impl IntoIterator<Item = Route>) -> Self {
+        self.routes.extend(routes);
+        self
+    }
+
+    pub fn rejects(mut self, rejects: Option<PathBuf>) -> Self {
+        self.rejects = rejects;
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn report_path(mut self, path: Option<PathBuf>) -> Self {
+        self.report_path = path;
+        self
+    }
+
+    pub fn log_path(mut self, path: Option<PathBuf>) -> Self {
+        self.log_path = path;
+        self
+    }
+
+    pub fn state_path(mut self, path: Option<PathBuf>) -> Self {
+        self.state_path = path;
+        self
+    }
+
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    pub fn index_cache(mut self, cache: Option<Arc<IndexCache>>) -> Self {
+        self.index_cache = cache;
+        self
+    }
+
+    pub fn dedup(mut self, mode: DedupMode) -> Self {
+        self.dedup = mode;
+        self
+    }
+
+    pub fn confirm_duplicates(mut self, confirm: bool) -> Self {
+        self.confirm_duplicates = confirm;
+        self
+    }
+
+    pub fn exclude_globs(mut self, globs: ExcludeGlobs) -> Self {
+        self.exclude_globs = globs;
+        self
+    }
+
+    /// Match images by name (the default) or by the frame name in their metadata.
+    pub fn match_by(mut self, mode: MatchBy) -> Self {
+        self.match_by = mode;
+        self
+    }
+
+    pub fn skip_preflight(mut self, skip: bool) -> Self {
+        self.skip_preflight = skip;
+        self
+    }
+
+    pub fn throttle(mut self, bytes_per_second: u64) -> Self {
+        self.throttle = bytes_per_second;
+        self
+    }
+
+    pub fn stall_warning(mut self, seconds: u64) -> Self {
+        self.stall_warning = seconds;
+        self
+    }
+
+    pub fn limit(mut self, files: u64) -> Self {
+        self.limit = files;
+        self
+    }
+
+    pub fn min_age(mut self, seconds: u64) -> Self {
+        self.min_age = seconds;
+        self
+    }
+
+    pub fn verify(mut self, mode: VerifyMode) -> Self {
+        self.verify = mode;
+        self
+    }
+
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    pub fn max_consecutive_errors(mut self, errors: u32) -> Self {
+        self.max_consecutive_errors = errors;
+        self
+    }
+
+    pub fn move_options(mut self, move_options: MoveOptions) -> Self {
+        self.move_options = move_options;
+        self
+    }
+
+    pub fn layout(mut self, layout: DestLayout) -> Self {
+        self.move_options.layout = layout;
+        self
+    }
+
+    pub fn duplicate_action(mut self, action: DuplicateAction) -> Self {
+        self.move_options.duplicate_action = action;
+        self
+    }
+
+    pub fn transfer_mode(mut self, mode: TransferMode) -> Self {
+        self.move_options.transfer_mode = mode;
+        self
+    }
+
+    pub fn collision_pattern(mut self, pattern: CollisionPattern) -> Self {
+        self.move_options.collision_pattern = pattern;
+        self
+    }
+
+    /// Rename colliding files (the default), or replace older destination files.
+    pub fn on_collision(mut self, mode: CollisionMode) -> Self {
+        self.move_options.on_collision = mode;
+        self
+    }
+
+    /// Remove emptied source directories afterwards; `junk_as_empty` ignores .DS_Store/Thumbs.db.
+    pub fn remove_empty_dirs(mut self, remove: bool, junk_as_empty: bool) -> Self {
+        self.move_options.remove_empty_dirs = remove;
+        self.move_options.junk_as_empty = junk_as_empty;
+        self
+    }
+
+    pub fn verify_copies(mut self, verify: bool) -> Self {
+        self.move_options.verify_copies = verify;
+        self
+    }
+
+    pub fn preserve_xattrs(mut self, preserve: bool) -> Self {
+        self.move_options.preserve_xattrs = preserve;
+        self
+    }
+
+    /// Make files read-only once they are in the destination.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.move_options.read_only = read_only;
+        self
+    }
+
+    pub fn sanitize(mut self, mode: SanitizeMode) -> Self {
+        self.move_options.sanitize = mode;
+        self
+    }
+
+    /// Match names and compare destination names regardless of their Unicode normalization
+    /// (on by default; see `MoveOptions::normalize_unicode`).
+    pub fn normalize_unicode(mut self, normalize: bool) -> Self {
+        self.move_options.normalize_unicode = normalize;
+        self
+    }
+
+    /// What to do with matches of zero bytes (moved, with a warning, by default).
+    pub fn empty_files(mut self, policy: EmptyFilePolicy) -> Self {
+        self.move_options.empty_files = policy;
+        self
+    }
+
+    /// What to do with matches that are symbolic links (skipped by default).
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.move_options.symlinks = policy;
+        self
+    }
+
+    /// Tag matches in place instead of moving them (see `MoveOptions::tag`).
+    pub fn tag(mut self, tag: Option<NameTag>) -> Self {
+        self.move_options.tag = tag;
+        self
+    }
+
+    /// `dest`, the routes' destinations, then `rejects`: the roots a file's route indexes.
+    pub(super) fn destinations(&self) -> impl Iterator<Item = &Path> {
+        self.rules().map(|(_, dest)| dest)
+    }
+
+    /// The suffix rule of each of `destinations`; `None` for the rejects folder. Tagging in
+    /// place, the only one is the first source, where the files stay.
+    pub(super) fn rules(&self) -> impl Iterator<Item = (Option<&str>, &Path)> {
+        let tagging = self.move_options.tag.is_some();
+        let (dest, routes, rejects) = match self.sources.first() {
+            Some(source) if tagging => (source, &[][..], None),
+            _ => (&self.dest, &self.routes[..], self.rejects.as_deref()),
+        };
+        std::iter::once((Some(self.suffix_input.as_str()), dest.as_path()))
+            .chain(
+                routes
+                    .iter()
+                    .map(|r| (Some(r.suffixes.as_str()), r.dest.as_path())),
+            )
+            .chain(rejects.map(|dir| (None, dir)))
+    }
+
+    /// `dedup`, except that tagging in place checks no duplicates: nothing goes anywhere that
+    /// could hold them.
+    pub(super) fn dedup_mode(&self) -> DedupMode {
+        match self.move_options.tag {
+            Some(_) => DedupMode::Off,
+            None => self.dedup,
+        }
+    }
+
+    /// `index_cache`, when the run indexes its destinations by content.
+    pub(super) fn cache(&self) -> Option<&IndexCache> {
+        self.index_cache
+            .as_deref()
+            .filter(|_| self.dedup_mode() == DedupMode::Content)
+    }
+
+    /// `limit`, unless it is 0.
+    pub(super) fn file_limit(&self) -> Option<u64> {
+        Some(self.limit).filter(|&n| n > 0)
+    }
+
+    /// Whether planning hashes the sources: for the duplicate check, or without one for the
+    /// content check after moving.
+    pub(super) fn hashes_sources(&self) -> bool {
+        self.dedup_mode() != DedupMode::Off || self.verify == VerifyMode::Content
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_frontend_options() {
+        let json = r#"{
+            "sources": ["/cards/A", "/cards/B"],
+            "dest": "/archive",
+            "suffixInput": "7612, 7608",
+            "dryRun": true,
+            "layout": { "kind": "template", "template": "{year}/{month}" },
+            "transferMode": "copy",
+            "collisionPattern": " ({n})",
+            "verifyCopies": false
+        }"#;
+        let opts: RunOptions = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            opts.sources,
+            [PathBuf::from("/cards/A"), PathBuf::from("/cards/B")]
+        );
+        assert!(opts.dry_run && !opts.verbose);
+        let m = &opts.move_options;
+        assert!(matches!(&m.layout, DestLayout::Template(t) if t.as_str() == "{year}/{month}"));
+        assert_eq!(m.transfer_mode, TransferMode::Copy);
+        assert_eq!(m.collision_pattern.as_str(), " ({n})");
+        assert!(!m.verify_copies);
+        // Unset fields keep their defaults, including the ones that default to on.
+        assert!(m.preserve_xattrs);
+        assert_eq!(m.duplicate_action, DuplicateAction::Leave);
+        assert_eq!(opts.stall_warning, DEFAULT_STALL_WARNING_SECONDS);
+    }
+
+    #[test]
+    fn test_deserialize_rejects_bad_pattern() {
+        let json = r#"{ "collisionPattern": "-copy", "layout": { "kind": "flatten" } }"#;
+        assert!(serde_json::from_str::<RunOptions>(json).is_err());
+        let json = r#"{ "layout": { "kind": "flatten" } }"#;
+        let opts: RunOptions = serde_json::from_str(json).unwrap();
+        assert_eq!(opts.move_options.layout, DestLayout::Flatten);
+    }
+}
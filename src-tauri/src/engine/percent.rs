@@ -0,0 +1,168 @@
+//! Overall percent of a run: each phase's share follows its estimated work, and the percent
+//! reported never goes backwards when the estimates change.
+
+use std::sync::Mutex;
+
+use super::Phase;
+
+/// Work of handling one file besides reading or writing its bytes (opening, renaming,
+/// updating the index), counted like this many bytes.
+pub(super) const FILE_COST: u64 = 64 * 1024;
+
+/// Work of finding one matching file while scanning, in the same units.
+pub(super) const SCAN_COST: u64 = 4 * 1024;
+
+/// Work of `sizes` files whose bytes are read or written once, plus `FILE_COST` each.
+pub(super) fn file_work(sizes: impl Iterator<Item = u64>) -> u64 {
+    sizes.map(|s| s + FILE_COST).sum()
+}
+
+/// The percent scale of one progress bar, shared by the phases of a run.
+#[derive(Debug)]
+pub(super) struct Scale {
+    /// Whether the bar covers moving (and verifying); a plan made on its own ends at 100%.
+    executes: bool,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Estimated work of scanning, indexing, planning, moving and verifying, in byte units.
+    work: [u64; 5],
+    /// Highest percent returned so far.
+    shown: f64,
+}
+
+fn slot(phase: Phase) -> Option<usize> {
+    match phase {
+        Phase::ScanningSource => Some(0),
+        Phase::IndexingDestination => Some(1),
+        Phase::Planning => Some(2),
+        Phase::Moving => Some(3),
+        Phase::Verifying => Some(4),
+        Phase::Watching | Phase::Done => None,
+    }
+}
+
+impl Scale {
+    pub fn new(executes: bool) -> Scale {
+        Scale {
+            executes,
+            state: Mutex::default(),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Set or revise the estimated work of `phase`.
+    pub fn set_work(&self, phase: Phase, units: u64) {
+        if matches!(phase, Phase::Moving | Phase::Verifying) && !self.executes {
+            return;
+        }
+        if let Some(i) = slot(phase) {
+            self.state().work[i] = units;
+        }
+    }
+
+    /// Percent of the run done with `fraction` (0 to 1) of `phase` done: the phases before it
+    /// count in full, the ones after it not at all. Never less than a percent returned before.
+    pub fn percent(&self, phase: Phase, fraction: f64) -> f64 {
+        let mut state = self.state();
+        let raw = match slot(phase) {
+            Some(i) => {
+                let total: u64 = state.work.iter().sum();
+                if total == 0 {
+                    0.0
+                } else {
+                    let before: u64 = state.work[..i].iter().sum();
+                    let within = state.work[i] as f64 * fraction.clamp(0.0, 1.0);
+                    100.0 * (before as f64 + within) / total as f64
+                }
+            }
+            None => 100.0,
+        };
+        state.shown = state.shown.max(raw.min(100.0));
+        state.shown
+    }
+
+    /// The percent last returned.
+    pub fn shown(&self) -> f64 {
+        self.state().shown
+    }
+
+    /// Start the bar over with no work known (watch mode: each batch of new files).
+    pub fn restart(&self) {
+        *self.state() = State::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1_000_000_000;
+
+    #[test]
+    fn test_huge_index_small_move() {
+        // 200k photos in the archive, 12 small picks to move.
+        let scale = Scale::new(true);
+        scale.set_work(Phase::ScanningSource, 12 * SCAN_COST);
+        scale.set_work(
+            Phase::IndexingDestination,
+            file_work((0..200_000).map(|_| 5_000_000)),
+        );
+        scale.set_work(Phase::Planning, file_work((0..12).map(|_| 5_000_000)));
+        scale.set_work(Phase::Moving, file_work((0..12).map(|_| 0)));
+
+        let index_half = scale.percent(Phase::IndexingDestination, 0.5);
+        assert!((49.0..51.0).contains(&index_half), "{}", index_half);
+        let index_done = scale.percent(Phase::IndexingDestination, 1.0);
+        assert!(index_done > 99.9, "{}", index_done);
+        assert!(scale.percent(Phase::Moving, 0.5) < 100.0);
+        assert_eq!(scale.percent(Phase::Done, 1.0), 100.0);
+    }
+
+    #[test]
+    fn test_empty_destination_large_copy() {
+        let scale = Scale::new(true);
+        scale.set_work(Phase::IndexingDestination, 0);
+        scale.set_work(Phase::Planning, 100 * GB);
+        scale.set_work(Phase::Moving, 300 * GB);
+
+        assert_eq!(scale.percent(Phase::IndexingDestination, 1.0), 0.0);
+        assert!((scale.percent(Phase::Planning, 1.0) - 25.0).abs() < 1e-9);
+        assert!((scale.percent(Phase::Moving, 0.5) - 62.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_revised_estimate_never_goes_backwards() {
+        let scale = Scale::new(true);
+        scale.set_work(Phase::Planning, 10 * GB);
+        scale.set_work(Phase::Moving, 10 * GB);
+        assert!((scale.percent(Phase::Planning, 1.0) - 50.0).abs() < 1e-9);
+
+        // Planning found more to copy than estimated: the raw percent drops to 10%.
+        scale.set_work(Phase::Moving, 90 * GB);
+        assert!((scale.percent(Phase::Moving, 0.0) - 50.0).abs() < 1e-9);
+        assert!((scale.percent(Phase::Moving, 0.25) - 50.0).abs() < 1e-9);
+        let later = scale.percent(Phase::Moving, 0.6);
+        assert!((later - 64.0).abs() < 1e-9, "{}", later);
+        // Out-of-order events of an earlier phase do not pull it back either.
+        assert_eq!(scale.percent(Phase::IndexingDestination, 0.5), later);
+        assert_eq!(scale.shown(), later);
+    }
+
+    #[test]
+    fn test_plan_alone_ignores_moving_work() {
+        let scale = Scale::new(false);
+        scale.set_work(Phase::Planning, GB);
+        scale.set_work(Phase::Moving, 9 * GB);
+        assert_eq!(scale.percent(Phase::Planning, 1.0), 100.0);
+
+        scale.restart();
+        assert_eq!(scale.shown(), 0.0);
+        assert_eq!(scale.percent(Phase::Planning, 0.5), 0.0);
+    }
+}
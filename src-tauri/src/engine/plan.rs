@@ -0,0 +1,679 @@
+//! Planning and executing a run: `plan` works out what happens to every matched file without
+//! touching anything, `execute` carries a (possibly reviewed or trimmed) plan out.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use super::cache;
+use super::index::{self, DestIndex};
+use super::meter::ByteMeter;
+use super::percent::{self, Scale};
+use super::preflight::Preflight;
+use super::report::{self, ErrorDetail, SuffixCount};
+use super::resume::{self, Journal};
+use super::runlog::RunLog;
+use super::select;
+use super::stats::{timed, PhaseDurations, RunClock};
+use super::verify;
+use super::{
+    check_destination, clean_up, count_suffixes, create_destinations, emit_done, emitter,
+    index_destination, pool, preflight_failed, scan_sources, set_up, setup_failed, take_first,
+    DedupMode, Destination, Emit, Phase, ProgressEvent, ProgressFn, RunError, RunOptions,
+    RunResult, Setup,
+};
+use crate::control::RunControl;
+use crate::scanner::{ImageEntry, ScanCount};
+use crate::suffix_parser;
+
+/// What the plan does with one file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannedAction {
+    /// Put into the destination at `dest`.
+    Move,
+    /// Content already in the destination, or planned to get there from another file.
+    Duplicate,
+    /// The file cannot be handled (no destination path, unreadable); execution reports it.
+    Error,
+    /// Modified within `RunOptions::min_age`, so it may still be written to; left alone and
+    /// not read. Execution skips it again.
+    Busy,
+    /// Zero bytes, and `MoveOptions::empty_files` says to leave it: skipped or an error,
+    /// again at execution.
+    Empty,
+}
+
+/// One matched file and what the plan does with it.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedFile {
+    pub source: PathBuf,
+    /// Index into `MovePlan::source_roots`.
+    pub root: usize,
+    /// The destination it goes to: 0 for `RunOptions::dest`, then the routes in order.
+    #[serde(default)]
+    pub route: usize,
+    pub suffix: u32,
+    /// Size and modification time at planning; execution skips the file if either changed.
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// SHA-256 of the content at planning; empty for errors and busy files.
+    pub hash: String,
+    pub action: PlannedAction,
+    /// For `Error`: what went wrong while planning.
+    pub error: Option<ErrorDetail>,
+    /// For `Move` the resolved destination (collision-renamed if needed); for a duplicate the
+    /// path it would have had. `None` when no destination could be derived.
+    pub dest: Option<PathBuf>,
+    /// `dest` got another name because the layout's name was taken.
+    pub renamed: bool,
+    /// `dest` was changed to suit the destination filesystem.
+    pub sanitized: bool,
+    /// For a duplicate of another file of the plan: that file's source.
+    pub duplicate_of: Option<PathBuf>,
+    /// For a duplicate of destination content: the destination file it matched; also for a
+    /// move whose hash matched that file but whose bytes did not, so execution compares again
+    /// (see `RunOptions::confirm_duplicates`).
+    pub existing: Option<PathBuf>,
+    /// Notes for the file's record, e.g. "name sanitized from ..." or the error.
+    pub details: Vec<String>,
+}
+
+/// Everything `execute` needs, and what a dry-run reports. Serializable, so a plan can be
+/// shown for review, saved and executed later.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MovePlan {
+    pub options: RunOptions,
+    /// Canonical source roots the files were found under.
+    pub source_roots: Vec<PathBuf>,
+    pub sanitize_names: bool,
+    /// In planning order, which is also the execution order.
+    pub files: Vec<PlannedFile>,
+    /// False when planning stopped early (an error before scanning, the pre-flight check or a
+    /// cancel); `run` then executes nothing.
+    pub complete: bool,
+    /// The predicted outcome, as a dry-run reports it.
+    pub preview: RunResult,
+}
+
+impl MovePlan {
+    fn stopped(run_options: &RunOptions, preview: RunResult) -> MovePlan {
+        MovePlan {
+            options: run_options.clone(),
+            source_roots: Vec::new(),
+            sanitize_names: false,
+            files: Vec::new(),
+            complete: false,
+            preview,
+        }
+    }
+}
+
+/// Plan the run described by `run_options` without moving anything: scan, check and index the
+/// destination, hash every match and resolve its destination, duplicates and name collisions.
+/// Fails as [`run`](super::run) does when the run could not start.
+pub fn plan(
+    run_options: &RunOptions,
+    control: &RunControl,
+    progress: Option<ProgressFn>,
+) -> Result<MovePlan, RunError> {
+    control.set_throttle(run_options.throttle);
+    let progress = Mutex::new(progress);
+    let emit = emitter(&progress);
+    // No log file: what planning finds is logged again when the plan is executed.
+    let log = RunLog::new(run_options.verbose);
+    let scale = Scale::new(false);
+    let clock = RunClock::start();
+    let (mut plan, bytes) = plan_with(run_options, None, control, &emit, &log, &scale, &clock)?;
+    plan.preview.dedup_disabled = run_options.dedup_mode() == DedupMode::Off;
+    plan.preview.limit = run_options.file_limit();
+    if plan.complete {
+        emit_done(&emit, &scale, &clock, &mut plan.preview, &bytes);
+    }
+    Ok(plan)
+}
+
+/// Carry out `plan`. Files are re-checked first: one that changed or disappeared since planning
+/// is skipped with a warning. A plan made with `dry_run` set is not executed; its preview is
+/// returned. With `report_path` set in the plan's options, the JSON report is written.
+pub fn execute(plan: &MovePlan, control: &RunControl, progress: Option<ProgressFn>) -> RunResult {
+    if plan.options.dry_run {
+        return plan.preview.clone();
+    }
+    control.set_throttle(plan.options.throttle);
+    super::with_report(&plan.options, |log, clock| {
+        let progress = Mutex::new(progress);
+        let emit = emitter(&progress);
+        let scale = Scale::new(true);
+        let (mut result, bytes) = execute_with(plan, control, &emit, log, &scale, None)?;
+        emit_done(&emit, &scale, clock, &mut result, &bytes);
+        Ok(result)
+    })
+    .unwrap_or_else(|e| RunResult::from_error(&e))
+}
+
+/// `plan` on the run's percent `scale`, which gets the estimated work of every phase; the final
+/// event is left to the caller unless planning stops early. The preview has the time each
+/// phase took; `clock` stamps it when planning stops early. With a `selection`, only those of
+/// the matches are planned (see `select::check`).
+pub(super) fn plan_with(
+    run_options: &RunOptions,
+    selection: Option<&[PathBuf]>,
+    control: &RunControl,
+    emit: Emit,
+    log: &RunLog,
+    scale: &Scale,
+    clock: &RunClock,
+) -> Result<(MovePlan, ByteMeter), RunError> {
+    let stopped = |preview| {
+        (
+            MovePlan::stopped(run_options, preview),
+            ByteMeter::default(),
+        )
+    };
+
+    let setup = match set_up(run_options) {
+        Ok(s) => s,
+        Err(error) => return Err(setup_failed(emit, log, error)),
+    };
+    let selected = selection
+        .map(|paths| select::check(&setup.source_roots, paths))
+        .transpose()
+        .map_err(|error| setup_failed(emit, log, error))?;
+    let mut phases = PhaseDurations::default();
+    let scanned = timed(&mut phases.scan_ms, || {
+        scan_sources(&setup, run_options, control, emit, log)
+    });
+    let mut scanned = match scanned {
+        Ok(found) => found,
+        Err(error) => return Err(setup_failed(emit, log, error)),
+    };
+    let planning = planning_log(run_options, log);
+    let selected_unmatched = selected.as_ref().map_or(0, |selected| {
+        select::keep(&mut scanned, selected, &planning)
+    });
+    let (suffixes, other_type_extensions, mut scan_warnings) =
+        count_suffixes(&setup, &scanned, emit, &planning);
+    if let Some(warning) = select::unmatched_warning(selected_unmatched) {
+        planning.warn(&warning);
+        emit(ProgressEvent {
+            phase: Phase::ScanningSource,
+            scanned: scanned.count.files,
+            images: scanned.count.images,
+            warning: Some(warning.clone()),
+            ..Default::default()
+        });
+        scan_warnings.push(warning);
+    }
+    let scan = scanned.count;
+    let mut candidates = scanned.candidates;
+    let over_limit = take_first(&setup, &mut candidates, run_options.file_limit(), &planning);
+    let excluded = scanned.excluded.len() as u64;
+    let skipped_symlinks = scanned.skipped_symlinks;
+    let link_warnings = scanned.link_warnings;
+    let broken_links = link_warnings.len() as u64;
+    let matched_count = candidates.len() as u64;
+    let (preflight, preflight_warning) =
+        match check_destination(run_options, log, &setup.source_roots, &candidates) {
+            Ok(found) => found,
+            Err((found, e)) => return Ok(stopped(preflight_failed(emit, log, found, e))),
+        };
+    scale.set_work(Phase::ScanningSource, matched_count * percent::SCAN_COST);
+    estimate_work(
+        scale,
+        run_options,
+        &preflight,
+        &setup.source_roots,
+        &candidates,
+        &[],
+    );
+
+    let index = timed(&mut phases.index_ms, || {
+        index_destination(run_options, log, scan, matched_count, control, emit, scale)
+    });
+    scale.set_work(
+        Phase::Planning,
+        planning_work(run_options, &candidates, &index),
+    );
+    if control.is_cancelled() {
+        let mut preview = RunResult {
+            excluded,
+            skipped_symlinks,
+            broken_links,
+            warnings: link_warnings,
+            over_limit,
+            suffixes,
+            other_type_extensions,
+            ..RunResult::cancelled_before(&setup, &candidates, scan)
+        };
+        preview.stats.phases = phases;
+        emit_done(emit, scale, clock, &mut preview, &ByteMeter::default());
+        return Ok(stopped(preview));
+    }
+
+    let mut pool = planning_pool(run_options, &setup, control, emit, log, index, scale);
+    pool.scan = scan;
+    let workers = worker_count(run_options, &setup);
+    let (files, mut tally) = timed(&mut phases.plan_ms, || {
+        plan_batch(&pool, workers, &candidates)
+    });
+    let would_remove = std::mem::take(&mut tally.would_remove);
+    let bytes = std::mem::take(&mut tally.bytes);
+    let by_suffix = std::mem::take(&mut tally.by_suffix);
+    if pool.cancelled.load(Ordering::Relaxed) {
+        // A partial prediction would read as an outcome; report only what was found.
+        let mut preview = RunResult {
+            preflight: Some(preflight),
+            excluded,
+            skipped_symlinks,
+            broken_links,
+            warnings: link_warnings,
+            over_limit,
+            suffixes,
+            other_type_extensions,
+            ..RunResult::cancelled_before(&setup, &candidates, scan)
+        };
+        preview.stats.phases = phases;
+        emit_done(emit, scale, clock, &mut preview, &bytes);
+        return Ok(stopped(preview));
+    }
+
+    let mut preview = RunResult::from_tally(tally, true);
+    preview.stats.phases = phases;
+    preview.stats.dest_indexed = pool.dest.iter().map(DestIndex::files).sum();
+    preview.stats.dest_hashed = pool.dest.iter().map(DestIndex::hashed).sum();
+    preview.stats.dest_reused = pool.dest.iter().map(DestIndex::reused).sum();
+    log.debug(format_args!(
+        "Hashed {} of {} destination image(s); {} known from an earlier run",
+        preview.stats.dest_hashed, preview.stats.dest_indexed, preview.stats.dest_reused
+    ));
+    cache::keep_indexes(run_options, &pool.dest);
+    preview.preflight = Some(preflight);
+    preview.excluded = excluded;
+    preview.skipped_symlinks = skipped_symlinks;
+    preview.broken_links = broken_links;
+    preview.over_limit = over_limit;
+    preview.selected_unmatched = selected_unmatched;
+    preview.suffixes = suffixes;
+    preview.other_type_extensions = other_type_extensions;
+    report::add_suffix_counts(&mut preview.suffixes, by_suffix.into_values());
+    preview
+        .warnings
+        .splice(0..0, link_warnings.into_iter().chain(scan_warnings));
+    if let Some(warning) = preflight_warning {
+        log.warn(&warning);
+        preview.warnings.push(warning);
+    }
+    if run_options.move_options.remove_empty_dirs {
+        clean_up(
+            run_options,
+            &setup.source_roots,
+            &would_remove,
+            true,
+            &planning,
+            &mut preview,
+        );
+    }
+    let plan = MovePlan {
+        options: run_options.clone(),
+        preview: preview.finish(&setup.source_roots),
+        sanitize_names: setup.destinations[0].sanitize_names,
+        source_roots: setup.source_roots,
+        files,
+        complete: true,
+    };
+    Ok((plan, bytes))
+}
+
+/// `execute` on the run's percent `scale`, after revising the moving estimate from the plan;
+/// the caller emits the final event. `journal` is a resumed run's state file; without one, a
+/// new one is started if the options have a `state_path`. The destinations are created here,
+/// not while planning, so a plan that is not carried out leaves nothing behind.
+pub(super) fn execute_with(
+    plan: &MovePlan,
+    control: &RunControl,
+    emit: Emit,
+    log: &RunLog,
+    scale: &Scale,
+    journal: Option<Journal>,
+) -> Result<(RunResult, ByteMeter), RunError> {
+    let run_options = &plan.options;
+    create_destinations(run_options).map_err(|error| setup_failed(emit, log, error))?;
+    let (journal, journal_warning) = match journal {
+        Some(journal) => (Some(journal), None),
+        None => resume::start(plan, log),
+    };
+    let setup = Setup {
+        suffixes: HashSet::new(),
+        source_roots: plan.source_roots.clone(),
+        // The files have their route; the rules only show which later ones also matched.
+        destinations: run_options
+            .rules()
+            .enumerate()
+            .map(|(route, (rule, dir))| Destination {
+                dir: dir.to_path_buf(),
+                suffixes: rule.map(suffix_parser::parse_suffixes).unwrap_or_default(),
+                sanitize_names: route == 0 && plan.sanitize_names,
+                rejects: rule.is_none(),
+            })
+            .collect(),
+    };
+    let workers = worker_count(run_options, &setup);
+    if workers > 1 {
+        log.info(format_args!("Moving with {} workers", workers));
+    }
+    let preflight = plan.preview.preflight.clone().unwrap_or_default();
+    revise_moving_work(
+        scale,
+        run_options,
+        &preflight,
+        &setup.source_roots,
+        &plan.files,
+    );
+    let mut pool = pool::Pool::new(run_options, &setup, control, emit, log, scale, Vec::new());
+    pool.scan = ScanCount {
+        files: plan.preview.scanned,
+        images: plan.preview.images,
+        numbered: plan.preview.numbered,
+        unnumbered: plan.preview.unnumbered,
+    };
+    let mut phases = PhaseDurations {
+        move_ms: 0,
+        ..plan.preview.stats.phases
+    };
+    let mut tally = timed(&mut phases.move_ms, || {
+        execute_batch(&pool, workers, &plan.files, journal.as_ref())
+    });
+    let would_remove = std::mem::take(&mut tally.would_remove);
+    let bytes = std::mem::take(&mut tally.bytes);
+    let by_suffix = std::mem::take(&mut tally.by_suffix);
+    let placed = std::mem::take(&mut tally.placed);
+    cache::keep_placed(run_options, &placed);
+
+    let mut result = RunResult::from_tally(tally, false);
+    result.warnings.extend(journal_warning);
+    result.preflight = plan.preview.preflight.clone();
+    result.excluded = plan.preview.excluded;
+    result.skipped_symlinks = plan.preview.skipped_symlinks;
+    result.broken_links = plan.preview.broken_links;
+    result.over_limit = plan.preview.over_limit;
+    result.selected_unmatched = plan.preview.selected_unmatched;
+    result.other_type_extensions = plan.preview.other_type_extensions.clone();
+    // Matches are the plan's; what was moved and skipped is this run's.
+    result.suffixes = plan
+        .preview
+        .suffixes
+        .iter()
+        .map(|s| SuffixCount {
+            suffix: s.suffix,
+            matched: s.matched,
+            other_types: s.other_types,
+            ..Default::default()
+        })
+        .collect();
+    report::add_suffix_counts(&mut result.suffixes, by_suffix.into_values());
+    let scan_warnings = [
+        report::unmatched_warning(&result.unmatched_suffixes()),
+        report::other_types_warning(result.other_types(), &result.other_type_extensions),
+        select::unmatched_warning(result.selected_unmatched),
+    ];
+    for warning in scan_warnings.into_iter().flatten().rev() {
+        log.warn(&warning);
+        result.warnings.insert(0, warning);
+    }
+    // The plan's broken-link warnings come first among its own.
+    let links = plan.preview.broken_links as usize;
+    result
+        .warnings
+        .splice(0..0, plan.preview.warnings.iter().take(links).cloned());
+    let stopped = result.cancelled || result.aborted.is_some();
+    if run_options.move_options.remove_empty_dirs && !stopped {
+        timed(&mut phases.move_ms, || {
+            clean_up(
+                run_options,
+                &setup.source_roots,
+                &would_remove,
+                false,
+                log,
+                &mut result,
+            )
+        });
+    }
+    if !stopped {
+        timed(&mut phases.verify_ms, || {
+            let mode = run_options.verify;
+            verify::verify_placed(mode, &placed, &mut result, control, emit, log, scale)
+        });
+    }
+    result.stats.phases = phases;
+    result.stats.dest_indexed = plan.preview.stats.dest_indexed;
+    result.stats.dest_hashed = plan.preview.stats.dest_hashed;
+    result.stats.dest_reused = plan.preview.stats.dest_reused;
+    if let Some(journal) = journal {
+        journal.close(&mut result, log);
+    }
+    Ok((result.finish(&setup.source_roots), bytes))
+}
+
+pub(super) fn worker_count(run_options: &RunOptions, setup: &Setup) -> usize {
+    pool::worker_count(
+        run_options.workers,
+        &setup.source_roots,
+        &run_options.dest,
+        run_options.move_options.transfer_mode,
+    )
+}
+
+/// Set the work of planning `candidates` (see `planning_work`) and of moving them (copying
+/// those that are copied) and verifying them, before it is known which ones are duplicates.
+pub(super) fn estimate_work(
+    scale: &Scale,
+    run_options: &RunOptions,
+    preflight: &Preflight,
+    roots: &[PathBuf],
+    candidates: &[ImageEntry],
+    indexes: &[DestIndex],
+) {
+    let mode = run_options.move_options.transfer_mode;
+    scale.set_work(
+        Phase::Planning,
+        planning_work(run_options, candidates, indexes),
+    );
+    if !run_options.dry_run {
+        let copied = candidates
+            .iter()
+            .map(|c| c.size * u64::from(preflight.copies_from(mode, &roots[c.root])));
+        scale.set_work(Phase::Moving, percent::file_work(copied));
+        let sizes = candidates.iter().map(|c| c.size);
+        scale.set_work(Phase::Verifying, verify::work(run_options.verify, sizes));
+    }
+}
+
+/// Work of planning `candidates`: reading each one (unless sources are not hashed), and the
+/// destination files of their sizes the `indexes` of their destinations have yet to hash.
+fn planning_work(
+    run_options: &RunOptions,
+    candidates: &[ImageEntry],
+    indexes: &[DestIndex],
+) -> u64 {
+    let hashed = u64::from(run_options.hashes_sources());
+    let sizes = candidates.iter().map(|c| c.size * hashed);
+    percent::file_work(sizes)
+        + index::pending_bytes(indexes, candidates.iter().map(|c| (c.route, c.size)))
+}
+
+/// Set the work of moving (and verifying) the planned `files`: only the ones that are moved get
+/// copied.
+pub(super) fn revise_moving_work(
+    scale: &Scale,
+    run_options: &RunOptions,
+    preflight: &Preflight,
+    roots: &[PathBuf],
+    files: &[PlannedFile],
+) {
+    let mode = run_options.move_options.transfer_mode;
+    let copied = files.iter().map(|f| {
+        let copies = f.action == PlannedAction::Move && preflight.copies_from(mode, &roots[f.root]);
+        f.size * u64::from(copies)
+    });
+    scale.set_work(Phase::Moving, percent::file_work(copied));
+    let moved = files.iter().filter(|f| f.action == PlannedAction::Move);
+    let sizes = moved.map(|f| f.size);
+    scale.set_work(Phase::Verifying, verify::work(run_options.verify, sizes));
+}
+
+/// The log for predictions: as is for a dry-run, demoted to debug when the plan is executed
+/// next and its messages would show twice.
+pub(super) fn planning_log(run_options: &RunOptions, log: &RunLog) -> RunLog {
+    if run_options.dry_run {
+        log.clone()
+    } else {
+        log.demoted()
+    }
+}
+
+/// A pool that plans: hashing and predicting only. Its per-file lines and error events are only
+/// sent when the plan is the whole run (dry-run); a real run reports them while executing.
+pub(super) fn planning_pool<'a>(
+    run_options: &'a RunOptions,
+    setup: &'a Setup,
+    control: &'a RunControl,
+    emit: Emit<'a>,
+    log: &RunLog,
+    indexes: Vec<DestIndex>,
+    scale: &'a Scale,
+) -> pool::Pool<'a> {
+    let log = planning_log(run_options, log);
+    let mut pool = pool::Pool::new(run_options, setup, control, emit, &log, scale, indexes);
+    pool.phase = Phase::Planning;
+    pool.send_problems = run_options.dry_run;
+    pool
+}
+
+/// Plan `candidates` with the pool; returns the planned files and the predicted tally. The
+/// bytes read include the destination files hashed for the candidates' sizes.
+pub(super) fn plan_batch(
+    pool: &pool::Pool,
+    workers: usize,
+    candidates: &[ImageEntry],
+) -> (Vec<PlannedFile>, pool::Tally) {
+    let deferred = index::pending_bytes(&pool.dest, candidates.iter().map(|c| (c.route, c.size)));
+    start_batch(pool, candidates.iter().map(|c| (c.route, c.size)), deferred);
+    pool.run(candidates, workers, |c| c.size, |c| pool.plan(c));
+    let files = std::mem::take(&mut *pool.planned.lock().unwrap_or_else(|e| e.into_inner()));
+    (files, pool.take_tally())
+}
+
+/// Execute `files` with the pool, whose index learns the planned destination duplicates first.
+/// Each file finished (not cut short by a cancel) is added to `journal`.
+pub(super) fn execute_batch(
+    pool: &pool::Pool,
+    workers: usize,
+    files: &[PlannedFile],
+    journal: Option<&Journal>,
+) -> pool::Tally {
+    pool.expect_in_destination(files);
+    start_batch(pool, files.iter().map(|f| (f.route, f.size)), 0);
+    pool.run(
+        files,
+        workers,
+        |f| f.size,
+        |f| {
+            if pool.execute(f) {
+                if let Some(journal) = journal {
+                    journal.done(&f.source, &pool.log);
+                }
+            }
+        },
+    );
+    pool.take_tally()
+}
+
+/// Start a batch of files given by route and size, plus `extra_bytes` of other reads.
+fn start_batch(pool: &pool::Pool, files: impl Iterator<Item = (usize, u64)>, extra_bytes: u64) {
+    let (mut count, mut rejects, mut bytes) = (0, 0, 0);
+    for (route, size) in files {
+        count += 1;
+        rejects += u64::from(pool.is_rejects(route));
+        bytes += size;
+    }
+    let total_bytes = bytes + extra_bytes;
+    pool.begin_batch(count, rejects, total_bytes);
+    (pool.emit)(ProgressEvent {
+        phase: pool.phase,
+        scanned: pool.scan.files,
+        images: pool.scan.images,
+        matched: count - rejects,
+        percent: pool.scale.percent(pool.phase, 0.0),
+        total_bytes,
+        total_count: count - rejects,
+        ..Default::default()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{DestLayout, FileAction};
+    use std::fs;
+
+    #[test]
+    fn test_plan_then_execute_selected_files() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"keep").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), b"later").unwrap();
+        fs::write(src.path().join("IMG_7605.JPG"), b"old").unwrap();
+        fs::write(dest.path().join("IMG_7612.JPG"), b"other").unwrap();
+        fs::write(dest.path().join("archived.JPG"), b"old").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612 7608 7605")
+            .layout(DestLayout::Flatten)
+            .workers(1);
+
+        let plan = plan(&options, &RunControl::new(), None).unwrap();
+        assert!(plan.complete);
+        let planned = |name: &str| {
+            plan.files
+                .iter()
+                .find(|f| f.source.ends_with(name))
+                .unwrap()
+                .clone()
+        };
+        let pick = planned("IMG_7612.JPG");
+        assert_eq!(pick.action, PlannedAction::Move);
+        assert_eq!(pick.dest, Some(dest.path().join("IMG_7612-1.JPG")));
+        assert!(pick.renamed);
+        assert_eq!(planned("IMG_7605.JPG").action, PlannedAction::Duplicate);
+        // Planning touched nothing, and the plan survives a round trip.
+        assert!(src.path().join("IMG_7612.JPG").exists());
+        let json = serde_json::to_string(&plan).unwrap();
+        let mut plan: MovePlan = serde_json::from_str(&json).unwrap();
+
+        // Execute without IMG_7608, after IMG_7605 was edited.
+        plan.files.retain(|f| !f.source.ends_with("IMG_7608.JPG"));
+        fs::write(src.path().join("IMG_7605.JPG"), b"edited!").unwrap();
+        let result = execute(&plan, &RunControl::new(), None);
+
+        assert_eq!(
+            (result.moved, result.errors, result.skipped_changed),
+            (1, 0, 1)
+        );
+        assert_eq!(
+            fs::read(dest.path().join("IMG_7612-1.JPG")).unwrap(),
+            b"keep"
+        );
+        assert!(src.path().join("IMG_7608.JPG").exists());
+        assert!(src.path().join("IMG_7605.JPG").exists());
+        assert_eq!(result.warnings.len(), 1);
+        let skipped = result
+            .files
+            .iter()
+            .find(|f| f.action == FileAction::Skipped)
+            .unwrap();
+        assert!(skipped.source.ends_with("IMG_7605.JPG"));
+    }
+}
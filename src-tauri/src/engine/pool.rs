@@ -0,0 +1,1514 @@
+//! Planning and moving phases: per-file processing, on one thread or a pool of workers.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::error::Abort;
+use super::index::DestIndex;
+use super::meter::ByteMeter;
+use super::percent::Scale;
+use super::plan::{PlannedAction, PlannedFile};
+use super::report::{ErrorDetail, ErrorStage, FileAction, FileLog, SuffixCount};
+use super::runlog::RunLog;
+use super::verify::PlacedFile;
+use super::{
+    DedupMode, Emit, FileEvent, Phase, ProgressEvent, RunOptions, Setup, StalledFile, VerifyMode,
+};
+use crate::collision;
+use crate::control::RunControl;
+use crate::hasher;
+use crate::mover::{
+    self, CollisionMode, DestClaims, DuplicateAction, EmptyFilePolicy, MoveOptions, ReplacedFile,
+    TransferMethod, TransferMode,
+};
+use crate::sanitize;
+use crate::scanner::{ImageEntry, ScanCount};
+
+/// Workers used when the run copies data (across volumes, or in a copy/link mode).
+pub const DEFAULT_PARALLEL_WORKERS: usize = 4;
+
+/// How often the stall watch looks at the files being worked on.
+const STALL_TICK: Duration = Duration::from_millis(250);
+
+/// Detail of a file whose layout name was taken.
+const RENAMED: &str = "name taken, renamed";
+const NEWER: &str = "destination file is not older, kept";
+const READ_ONLY: &str = "made read-only";
+const EMPTY: &str = "empty file (0 bytes)";
+
+/// Worker count for a run: `requested` if set, else one for same-volume renames (they are
+/// metadata-only) and `DEFAULT_PARALLEL_WORKERS` when files are copied.
+pub fn worker_count(
+    requested: usize,
+    sources: &[PathBuf],
+    dest: &Path,
+    mode: TransferMode,
+) -> usize {
+    if requested > 0 {
+        requested
+    } else if mode == TransferMode::Move && sources.iter().all(|s| mover::same_volume(s, dest)) {
+        1
+    } else {
+        DEFAULT_PARALLEL_WORKERS
+    }
+}
+
+/// Whether a file last modified at `modified` was written to within `min_age`, so whoever
+/// writes it may not be done. A time ahead of the clock counts as just now; an unknown one
+/// as old.
+pub(super) fn recently_modified(modified: Option<SystemTime>, min_age: Duration) -> bool {
+    modified.is_some_and(|m| {
+        SystemTime::now()
+            .duration_since(m)
+            .map_or(true, |age| age < min_age)
+    })
+}
+
+/// Where content in the index came from.
+#[derive(Clone, Debug)]
+pub(super) enum Origin {
+    /// Already in the destination when the run started, in the file at `path` if known.
+    Dest { path: Option<PathBuf> },
+    /// Brought in by this run's `source`; `done` once that file's move has finished, with
+    /// `dest` where it went.
+    Batch {
+        source: PathBuf,
+        done: bool,
+        dest: Option<PathBuf>,
+    },
+}
+
+impl Origin {
+    /// The source file this content is a copy of, if it came from this run.
+    fn source(&self) -> Option<&Path> {
+        match self {
+            Origin::Dest { .. } => None,
+            Origin::Batch { source, .. } => Some(source),
+        }
+    }
+
+    /// The file holding this content now, to compare a duplicate with.
+    fn file(&self) -> Option<&Path> {
+        match self {
+            Origin::Dest { path } => path.as_deref(),
+            Origin::Batch {
+                dest: Some(dest), ..
+            } => Some(dest),
+            Origin::Batch { source, .. } => Some(source),
+        }
+    }
+
+    /// The destination file holding this content, if it is there yet.
+    fn location(&self) -> Option<&Path> {
+        match self {
+            Origin::Dest { path } => path.as_deref(),
+            Origin::Batch { dest, .. } => dest.as_deref(),
+        }
+    }
+
+    /// The destination file, for content that was there before the run.
+    fn existing(&self) -> Option<PathBuf> {
+        match self {
+            Origin::Dest { path } => path.clone(),
+            Origin::Batch { .. } => None,
+        }
+    }
+}
+
+/// The event of `src` skipped as a duplicate of `origin`: the destination file that has the
+/// content, or the source on its way there.
+fn duplicate_event(src: &Path, origin: &Origin) -> FileEvent {
+    FileEvent::Duplicate {
+        source: src.to_path_buf(),
+        duplicate_of: origin.location().or(origin.source()).map(Path::to_path_buf),
+    }
+}
+
+/// Detail of a file that took the place of the `replaced` one.
+fn replaced_detail(replaced: &ReplacedFile) -> String {
+    format!(
+        "replaced an older file, kept as {}",
+        replaced
+            .kept_at
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+    )
+}
+
+/// Counters and records updated by all workers. Progress is emitted while holding the same
+/// lock, so the counts in successive events never go backwards.
+#[derive(Debug, Default)]
+pub(super) struct Tally {
+    /// What the scan the batch came from looked at (`Pool::scan`).
+    pub scan: ScanCount,
+    /// Candidates in the batch being processed.
+    pub matched: u64,
+    pub started: u64,
+    /// Of the started files, those for the rejects folder.
+    pub rejects_started: u64,
+    pub moved: u64,
+    /// Files moved over an older file of the same name (`CollisionMode::Update`); they are
+    /// not in `moved`.
+    pub replaced: u64,
+    /// Files left alone because the file holding their name was not older.
+    pub skipped_newer: u64,
+    /// Tagging in place: files renamed with the tag; they are not in `moved`.
+    pub tagged: u64,
+    /// Files made read-only once in place (`MoveOptions::read_only`).
+    pub read_only: u64,
+    pub skipped_duplicates: u64,
+    /// Bytes of the files counted in `moved` or `replaced`, and in `skipped_duplicates`.
+    pub bytes_moved: u64,
+    pub bytes_duplicates: u64,
+    /// Files moved (or replacing others) and duplicates skipped, by the suffix they matched.
+    pub by_suffix: HashMap<u32, SuffixCount>,
+    /// Files put in the destination, for the check after moving (see `Pool::record_placed`).
+    pub placed: Vec<PlacedFile>,
+    pub duplicates_removed: u64,
+    pub verified: u64,
+    pub source_kept: u64,
+    pub sanitized: u64,
+    pub errors: u64,
+    pub error_details: Vec<ErrorDetail>,
+    /// Planned files left alone because they changed or vanished before execution.
+    pub skipped_changed: u64,
+    /// Candidates of zero bytes, whatever `MoveOptions::empty_files` did with them.
+    pub empty_files: u64,
+    /// Files left alone because they were modified too recently (see `Pool::min_age`).
+    pub busy: Vec<PathBuf>,
+    /// Duplicates whose content came from another file of this run, not from the destination.
+    pub source_duplicates: u64,
+    /// Files whose hash matched but whose bytes did not (see `Pool::confirm_duplicates`).
+    pub hash_mismatches: u64,
+    /// Of the candidates, those for the rejects folder; they are counted apart from the matches.
+    pub rejects: u64,
+    pub rejected: u64,
+    pub rejected_duplicates: u64,
+    pub file_log: FileLog,
+    pub warnings: Vec<String>,
+    /// Candidate bytes; a file counts as done once it has been handled, whatever the outcome.
+    pub bytes: ByteMeter,
+    /// Set by `take_tally` when the batch stopped because the run was cancelled.
+    pub cancelled: bool,
+    /// Moves that failed in a row, for `Pool::max_consecutive_errors`.
+    pub failures: FailureStreak,
+    /// Set when the batch stopped by itself.
+    pub aborted: Option<Abort>,
+    /// With routes: each file's destination, and those of later routes it also matched.
+    pub routes: HashMap<PathBuf, (PathBuf, Vec<PathBuf>)>,
+    /// Planning: source files the run would take away, for predicting empty-directory cleanup.
+    pub would_remove: HashSet<PathBuf>,
+}
+
+/// Consecutive failed moves with the same kind of error; another kind starts a new streak.
+#[derive(Debug, Default)]
+pub(super) struct FailureStreak {
+    kind: Option<std::io::ErrorKind>,
+    count: u32,
+}
+
+/// A file a worker is on, for the stall watch.
+#[derive(Debug)]
+pub(super) struct ActiveFile {
+    path: PathBuf,
+    /// Time spent on it, growing only while the run is not paused.
+    elapsed: Duration,
+    /// When the next stall warning is due.
+    next_warning: Duration,
+}
+
+/// Sets the flag and wakes the stall watch when dropped.
+struct Finish<'a>(&'a AtomicBool, Option<&'a thread::Thread>);
+
+impl Drop for Finish<'_> {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+        if let Some(watch) = self.1 {
+            watch.unpark();
+        }
+    }
+}
+
+impl Tally {
+    /// Count a copy of `size` bytes that was verified. The re-hash read it again, so its bytes
+    /// count twice, like the other reads of the batch.
+    pub fn count_verified(&mut self, size: u64) {
+        self.verified += 1;
+        self.bytes.total += size;
+        self.bytes.add(size);
+    }
+
+    /// Candidates of the batch that matched a suffix, the rejects left out.
+    pub fn matches(&self) -> u64 {
+        self.matched - self.rejects
+    }
+
+    /// Matches never started, once the batch stopped early; the rejects are not counted.
+    pub fn not_attempted(&self) -> u64 {
+        self.matches()
+            .saturating_sub(self.started - self.rejects_started)
+    }
+
+    /// The per-suffix counts of `suffix`, started if needed.
+    fn suffix(&mut self, suffix: u32) -> &mut SuffixCount {
+        self.by_suffix.entry(suffix).or_insert(SuffixCount {
+            suffix,
+            ..Default::default()
+        })
+    }
+}
+
+/// A destination of the run and what the workers know about it.
+pub(super) struct Target {
+    pub dir: PathBuf,
+    /// The suffixes routed there, to flag a file that a later route has too.
+    pub suffixes: HashSet<u32>,
+    pub sanitize_names: bool,
+    /// The rejects folder: its files matched no suffix.
+    pub rejects: bool,
+    /// Hashes of content this run put there or expects there, including files being moved
+    /// there right now.
+    pub index: Mutex<HashMap<String, Origin>>,
+    pub claims: DestClaims,
+}
+
+/// Run settings plus the state the workers share.
+pub(super) struct Pool<'a> {
+    /// Canonical source roots; `ImageEntry::root` indexes into them.
+    pub source_roots: &'a [PathBuf],
+    /// `dest`, then the routes' destinations; `ImageEntry::route` indexes into them.
+    pub targets: Vec<Target>,
+    pub options: &'a MoveOptions,
+    pub log: RunLog,
+    /// Send errors and warnings with progress events as they happen.
+    pub send_problems: bool,
+    /// How duplicates are looked for: `SamePath` only at each file's target path (no index),
+    /// `Off` not at all.
+    pub dedup: DedupMode,
+    /// Compare a duplicate byte for byte with the file it matched before skipping it.
+    pub confirm_duplicates: bool,
+    /// Hash sources while planning; without a duplicate check only the content check after
+    /// moving needs their hashes.
+    pub hash_sources: bool,
+    /// Keep the destination, hash and size of every file put there, to verify them afterwards.
+    pub record_placed: bool,
+    /// Watch mode: files that disappear before they are read were never meant to be moved
+    /// (e.g. temporary files), so they are skipped instead of reported as errors.
+    pub skip_vanished: bool,
+    /// Files modified more recently than this are skipped as busy (`None`: none are).
+    pub min_age: Option<Duration>,
+    /// What the scan of the sources looked at, for the progress events and results.
+    pub scan: ScanCount,
+    pub control: &'a RunControl,
+    pub emit: Emit<'a>,
+    /// Phase of the progress events, and the run's scale their percent is on.
+    pub phase: Phase,
+    pub scale: &'a Scale,
+    /// Each destination's files as the run started, hashed by size when first needed; empty
+    /// when the destinations were not indexed.
+    pub dest: Vec<DestIndex>,
+    /// What planning decided for each file, in no particular order.
+    pub planned: Mutex<Vec<PlannedFile>>,
+    pub tally: Mutex<Tally>,
+    pub cancelled: AtomicBool,
+    /// Stop taking files once this many moves in a row failed alike (`None`: never).
+    pub max_consecutive_errors: Option<u32>,
+    /// Stop taking files after the first one that failed while moving.
+    pub fail_fast: bool,
+    /// Set with `Tally::aborted`; workers finish their file and take no more.
+    pub aborted: AtomicBool,
+    /// Warn about a file worked on longer than this, and again each time as long again.
+    pub stall_after: Option<Duration>,
+    /// The file each worker is on.
+    pub active: Mutex<HashMap<ThreadId, ActiveFile>>,
+}
+
+impl<'a> Pool<'a> {
+    pub fn new(
+        run_options: &'a RunOptions,
+        setup: &'a Setup,
+        control: &'a RunControl,
+        emit: Emit<'a>,
+        log: &RunLog,
+        scale: &'a Scale,
+        dest: Vec<DestIndex>,
+    ) -> Pool<'a> {
+        let targets = setup
+            .destinations
+            .iter()
+            .map(|d| Target {
+                dir: d.dir.clone(),
+                suffixes: d.suffixes.clone(),
+                sanitize_names: d.sanitize_names,
+                rejects: d.rejects,
+                index: Mutex::default(),
+                claims: DestClaims::new(run_options.move_options.normalize_unicode),
+            })
+            .collect();
+        Pool {
+            source_roots: &setup.source_roots,
+            targets,
+            options: &run_options.move_options,
+            log: log.clone(),
+            send_problems: true,
+            dedup: run_options.dedup_mode(),
+            confirm_duplicates: run_options.confirm_duplicates,
+            hash_sources: run_options.hashes_sources(),
+            record_placed: run_options.verify != VerifyMode::Off || run_options.cache().is_some(),
+            skip_vanished: false,
+            min_age: Some(Duration::from_secs(run_options.min_age)).filter(|d| !d.is_zero()),
+            scan: ScanCount::default(),
+            control,
+            emit,
+            phase: Phase::Moving,
+            scale,
+            dest,
+            planned: Mutex::default(),
+            tally: Mutex::default(),
+            cancelled: AtomicBool::new(false),
+            max_consecutive_errors: Some(run_options.max_consecutive_errors).filter(|&n| n > 0),
+            fail_fast: run_options.fail_fast,
+            aborted: AtomicBool::new(false),
+            stall_after: Some(Duration::from_secs(run_options.stall_warning))
+                .filter(|d| !d.is_zero()),
+            active: Mutex::default(),
+        }
+    }
+
+    /// Start counting a new batch of `matched` candidates, `rejects` of them for the rejects
+    /// folder; the index and claims carry over.
+    pub fn begin_batch(&self, matched: u64, rejects: u64, total_bytes: u64) {
+        *self.tally() = Tally {
+            scan: self.scan,
+            matched,
+            rejects,
+            bytes: ByteMeter::new(total_bytes),
+            ..Default::default()
+        };
+    }
+
+    /// The finished batch's counts and records.
+    pub fn take_tally(&self) -> Tally {
+        Tally {
+            cancelled: self.cancelled.load(Ordering::Relaxed),
+            ..std::mem::take(&mut *self.tally())
+        }
+    }
+
+    /// Call `each` for `items` on `workers` threads; with one worker they run in order on this
+    /// thread. `size` is an item's share of the byte progress. Meanwhile a watch thread sends
+    /// stall warnings (see `stall_after`).
+    pub fn run<T: Sync>(
+        &self,
+        items: &[T],
+        workers: usize,
+        size: impl Fn(&T) -> u64 + Sync,
+        each: impl Fn(&T) + Sync,
+    ) {
+        let next = AtomicUsize::new(0);
+        let work = || loop {
+            if self.control.is_paused() {
+                self.emit_progress(&self.tally(), None);
+            }
+            if !self.control.proceed() {
+                self.cancelled.store(true, Ordering::Relaxed);
+            }
+            if self.cancelled.load(Ordering::Relaxed) || self.aborted.load(Ordering::Relaxed) {
+                break;
+            }
+            let Some(item) = items.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                break;
+            };
+            each(item);
+            self.active().remove(&thread::current().id());
+            self.tally().bytes.add(size(item));
+        };
+        let finished = &AtomicBool::new(false);
+        thread::scope(|s| {
+            let watch = self
+                .stall_after
+                .filter(|_| !items.is_empty())
+                .map(|limit| s.spawn(move || self.watch_stalls(limit, finished)));
+            // Stops the watch however the workers end, panics included.
+            let _finish = Finish(finished, watch.as_ref().map(|w| w.thread()));
+            if workers <= 1 {
+                work();
+            } else {
+                let handles: Vec<_> = (0..workers.min(items.len()))
+                    .map(|_| s.spawn(work))
+                    .collect();
+                for handle in handles {
+                    if let Err(panic) = handle.join() {
+                        std::panic::resume_unwind(panic);
+                    }
+                }
+            }
+        });
+    }
+
+    fn active(&self) -> MutexGuard<'_, HashMap<ThreadId, ActiveFile>> {
+        self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Until `finished`: time the files being worked on and warn about the ones past `limit`.
+    /// Time paused is not counted; while cancelling it is, since a hung file is what keeps
+    /// the run from stopping.
+    fn watch_stalls(&self, limit: Duration, finished: &AtomicBool) {
+        let mut last = Instant::now();
+        while !finished.load(Ordering::Relaxed) {
+            thread::park_timeout(STALL_TICK);
+            let now = Instant::now();
+            let tick = now - last;
+            last = now;
+            if self.control.is_paused() {
+                continue;
+            }
+            let mut stalled = Vec::new();
+            for file in self.active().values_mut() {
+                file.elapsed += tick;
+                if file.elapsed >= file.next_warning {
+                    file.next_warning += limit;
+                    stalled.push(StalledFile {
+                        path: file.path.display().to_string(),
+                        elapsed_seconds: file.elapsed.as_secs(),
+                    });
+                }
+            }
+            for file in stalled {
+                self.stalled(file);
+            }
+        }
+    }
+
+    /// Log and emit a stall warning; sent even when `send_problems` is off, as it is news only
+    /// while it lasts.
+    fn stalled(&self, file: StalledFile) {
+        self.log.warn(format_args!(
+            "Still working on {} after {} s",
+            file.path, file.elapsed_seconds
+        ));
+        let t = self.tally();
+        (self.emit)(ProgressEvent {
+            stalled: Some(file),
+            ..self.progress_event(&t, None)
+        });
+    }
+
+    pub fn tally(&self) -> MutexGuard<'_, Tally> {
+        self.tally.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Emit an event of the pool's phase; `t` is the locked tally, so counts stay monotonic.
+    fn emit_progress(&self, t: &Tally, current_file: Option<String>) {
+        (self.emit)(self.progress_event(t, current_file));
+    }
+
+    /// Emit an error or a warning with the current counts (see `send_problems`).
+    fn emit_problem(&self, t: &Tally, error: Option<ErrorDetail>, warning: Option<String>) {
+        if self.send_problems {
+            (self.emit)(ProgressEvent {
+                error,
+                warning,
+                ..self.progress_event(t, None)
+            });
+        }
+    }
+
+    /// Emit what just happened to a file, with the counts after it, unless planning.
+    fn emit_file(&self, t: &Tally, file: FileEvent) {
+        if !matches!(self.phase, Phase::Planning) {
+            (self.emit)(ProgressEvent {
+                file: Some(file),
+                ..self.progress_event(t, None)
+            });
+        }
+    }
+
+    fn progress_event(&self, t: &Tally, current_file: Option<String>) -> ProgressEvent {
+        let done = t
+            .bytes
+            .fraction()
+            .unwrap_or(t.started as f64 / t.matched.max(1) as f64);
+        let started = t.started - t.rejects_started + u64::from(current_file.is_some());
+        let current_index = started.min(t.matches());
+        ProgressEvent {
+            phase: self.phase,
+            current_file,
+            scanned: t.scan.files,
+            images: t.scan.images,
+            matched: t.matches(),
+            moved: t.moved,
+            skipped_duplicates: t.skipped_duplicates,
+            rejected: t.rejected,
+            replaced: t.replaced,
+            skipped_newer: t.skipped_newer,
+            tagged: t.tagged,
+            read_only: t.read_only,
+            errors: t.errors,
+            current_index,
+            percent: self.scale.percent(self.phase, done),
+            paused: self.control.is_paused(),
+            total_bytes: t.bytes.total,
+            bytes_done: t.bytes.done,
+            total_count: t.matches(),
+            throughput: t.bytes.throughput(),
+            eta_seconds: t.bytes.eta_seconds(),
+            ..Default::default()
+        }
+    }
+
+    /// The index of the destination `route`.
+    fn index(&self, route: usize) -> MutexGuard<'_, HashMap<String, Origin>> {
+        self.targets[route]
+            .index
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Where content `hash` of a file of `size` going to `route` is known from; `index` is
+    /// that route's locked index. `hash_destination(route, size)` must have been called.
+    fn known(
+        &self,
+        index: &HashMap<String, Origin>,
+        route: usize,
+        size: u64,
+        hash: &str,
+    ) -> Option<Origin> {
+        index.get(hash).cloned().or_else(|| {
+            let path = self.dest.get(route)?.find(size, hash)?.to_path_buf();
+            Some(Origin::Dest { path: Some(path) })
+        })
+    }
+
+    /// Hash the files of `size` in the destination `route` unless done; their bytes are part
+    /// of the batch's progress (see `plan::plan_batch`). Done before taking the index lock,
+    /// which other workers need meanwhile.
+    fn hash_destination(&self, route: usize, size: u64) {
+        let Some(dest) = self.dest.get(route) else {
+            return;
+        };
+        let read = dest.hash_size(size, self.control, &self.log);
+        if read > 0 {
+            self.tally().bytes.add(read);
+        }
+    }
+
+    /// Whether `route` is the rejects folder, whose files matched no suffix.
+    pub fn is_rejects(&self, route: usize) -> bool {
+        self.targets.get(route).is_some_and(|t| t.rejects)
+    }
+
+    /// With routes, note that `file` goes to `route`, and which later routes also have its
+    /// `suffix`; returns those routes' destinations.
+    fn route(&self, file: &Path, route: usize, suffix: u32) -> Vec<&Path> {
+        if self.targets.len() < 2 {
+            return Vec::new();
+        }
+        let also: Vec<&Path> = self.targets[route + 1..]
+            .iter()
+            .filter(|t| t.suffixes.contains(&suffix))
+            .map(|t| t.dir.as_path())
+            .collect();
+        let dirs = also.iter().map(|d| d.to_path_buf()).collect();
+        let dest = self.targets[route].dir.clone();
+        self.tally().routes.insert(file.to_path_buf(), (dest, dirs));
+        also
+    }
+
+    /// Whether `err` means `src` is gone and should be skipped quietly (see `skip_vanished`).
+    fn vanished(&self, src: &Path, err: &std::io::Error) -> bool {
+        self.skip_vanished
+            && err.kind() == std::io::ErrorKind::NotFound
+            && !mover::long_path(src).exists()
+    }
+
+    /// Whether a file modified at `modified` is too recent to take (see `min_age`).
+    fn busy(&self, modified: Option<SystemTime>) -> bool {
+        self.min_age
+            .is_some_and(|min_age| recently_modified(modified, min_age))
+    }
+
+    /// Leave alone a file that may still be written to; it is listed in the result.
+    fn skip_busy(&self, src: &Path) {
+        let seconds = self.min_age.unwrap_or_default().as_secs();
+        let reason = format!("still being written (modified in the last {} s)", seconds);
+        let warning = format!("Skipped {}: {}", src.display(), reason);
+        self.log.warn(&warning);
+        let mut t = self.tally();
+        t.busy.push(src.to_path_buf());
+        t.file_log.push(src, None, FileAction::Skipped, &[reason]);
+        self.emit_problem(&t, None, Some(warning));
+    }
+
+    /// Count and warn about a candidate of zero bytes, and handle it by `empty_files`: whether
+    /// it is still to be moved. One that is not gets its record here.
+    fn empty_file(&self, src: &Path, details: &[String]) -> bool {
+        let policy = self.options.empty_files;
+        let warning = match policy {
+            EmptyFilePolicy::Move => {
+                format!("{}: {}, it may be a failed write", src.display(), EMPTY)
+            }
+            EmptyFilePolicy::Skip => format!("Skipped {}: {}", src.display(), EMPTY),
+            EmptyFilePolicy::Error => {
+                self.tally().empty_files += 1;
+                let mut details = details.to_vec();
+                details.push(EMPTY.to_string());
+                self.error(src, None, ErrorStage::Read, &details);
+                return false;
+            }
+        };
+        self.log.warn(&warning);
+        let mut t = self.tally();
+        t.empty_files += 1;
+        if policy == EmptyFilePolicy::Skip {
+            t.file_log
+                .push(src, None, FileAction::Skipped, &[EMPTY.to_string()]);
+        }
+        t.warnings.push(warning.clone());
+        self.emit_problem(&t, None, Some(warning));
+        policy == EmptyFilePolicy::Move
+    }
+
+    /// Whether `planned` is compared with other content: an empty file never is, as all of
+    /// them have the same hash.
+    fn compares(&self, planned: &PlannedFile) -> bool {
+        self.dedup != DedupMode::Off && planned.size > 0
+    }
+
+    /// Record and log a failed file; the last of `details` is the error message. With
+    /// `fail_fast`, a file that failed while moving (not while disposing of a duplicate) ends
+    /// the batch, and its record says so.
+    fn error(&self, src: &Path, dest: Option<PathBuf>, stage: ErrorStage, details: &[String]) {
+        let message = details.last().cloned().unwrap_or_default();
+        let detail = ErrorDetail::new(Some(src), stage, message.clone());
+        self.log.error(&detail);
+        let mut t = self.tally();
+        let stop = self.fail_fast
+            && matches!(self.phase, Phase::Moving)
+            && stage != ErrorStage::Duplicate
+            && t.aborted.is_none();
+        let mut details = details.to_vec();
+        if stop {
+            details.push("run stopped here (fail-fast)".to_string());
+        }
+        t.errors += 1;
+        t.file_log.push(src, dest, FileAction::Error, &details);
+        t.error_details.push(detail.clone());
+        self.emit_problem(&t, Some(detail), None);
+        let source = src.to_path_buf();
+        let failed = FileEvent::Error {
+            source,
+            message: message.clone(),
+        };
+        self.emit_file(&t, failed);
+        if stop {
+            let path = src.to_path_buf();
+            self.abort(&mut t, Abort::FailFast { path, message });
+        }
+    }
+
+    /// End the batch with `abort`: workers finish the file they are on and take no more. The
+    /// files left are not attempted, which the single error sent for the abort says (rather
+    /// than one error for each of them).
+    fn abort(&self, t: &mut Tally, abort: Abort) {
+        self.aborted.store(true, Ordering::Relaxed);
+        let detail = ErrorDetail::new(Some(abort.path()), ErrorStage::Move, abort.to_string());
+        self.log.error(format_args!(
+            "{}; stopping, {} file(s) not attempted",
+            abort,
+            t.not_attempted()
+        ));
+        t.aborted = Some(abort);
+        self.emit_problem(t, Some(detail), None);
+    }
+
+    /// Count a failed move of `kind`; once `max_consecutive_errors` in a row failed alike, the
+    /// batch is aborted.
+    fn move_failed(&self, dest: &Path, kind: std::io::ErrorKind, message: &str) {
+        let Some(limit) = self.max_consecutive_errors else {
+            return;
+        };
+        let mut t = self.tally();
+        let streak = &mut t.failures;
+        if streak.kind == Some(kind) {
+            streak.count += 1;
+        } else {
+            *streak = FailureStreak {
+                kind: Some(kind),
+                count: 1,
+            };
+        }
+        let failures = streak.count;
+        if failures < limit || t.aborted.is_some() {
+            return;
+        }
+        let abort = Abort::DestinationUnavailable {
+            dest: dest.to_path_buf(),
+            failures,
+            message: message.to_string(),
+        };
+        self.abort(&mut t, abort);
+    }
+
+    /// Planned files of the batch so far.
+    fn planned(&self) -> MutexGuard<'_, Vec<PlannedFile>> {
+        self.planned.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Content known to be in the destination already, from the plan of `files`; anything the
+    /// index has is kept.
+    pub fn expect_in_destination(&self, files: &[PlannedFile]) {
+        for f in files {
+            let expected = f.action == PlannedAction::Duplicate || f.existing.is_some();
+            if expected && f.duplicate_of.is_none() {
+                let path = f.existing.clone();
+                self.index(f.route)
+                    .entry(f.hash.clone())
+                    .or_insert(Origin::Dest { path });
+            }
+        }
+    }
+
+    /// With `confirm_duplicates`: whether `file` has the same bytes as `other`, the file its
+    /// hash matched. When it does not, or the two cannot be compared, that is counted and
+    /// noted in `details`, and the file is to be moved rather than skipped. A cancel during
+    /// the comparison gives false without counting it.
+    fn confirmed(
+        &self,
+        file: &PlannedFile,
+        other: Option<&Path>,
+        details: &mut Vec<String>,
+    ) -> bool {
+        if !self.confirm_duplicates {
+            return true;
+        }
+        let src = file.source.as_path();
+        let note = match other {
+            None => "same hash as destination content, but no file to compare with".to_string(),
+            Some(other) => match hasher::same_bytes(
+                &mover::long_path(src),
+                &mover::long_path(other),
+                self.control,
+            ) {
+                Ok(true) => return true,
+                Ok(false) => format!("same hash as {}, but different content", other.display()),
+                Err(_) if self.control.is_cancelled() => return false,
+                Err(e) => format!(
+                    "same hash as {}, but cannot compare: {}",
+                    other.display(),
+                    e
+                ),
+            },
+        };
+        let warning = format!("{}: {}; not skipped as a duplicate", src.display(), note);
+        self.log.warn(&warning);
+        details.push(note);
+        let mut t = self.tally();
+        t.hash_mismatches += 1;
+        t.warnings.push(warning.clone());
+        self.emit_problem(&t, None, Some(warning));
+        false
+    }
+
+    fn started(&self, src: &Path, route: usize) {
+        if let Some(limit) = self.stall_after {
+            let file = ActiveFile {
+                path: src.to_path_buf(),
+                elapsed: Duration::ZERO,
+                next_warning: limit,
+            };
+            self.active().insert(thread::current().id(), file);
+        }
+        let mut t = self.tally();
+        self.emit_progress(&t, Some(src.display().to_string()));
+        t.started += 1;
+        t.rejects_started += u64::from(self.is_rejects(route));
+    }
+
+    /// Planning: resolve the destination of `entry`, hash it and predict what happens to it.
+    pub fn plan(&self, entry: &ImageEntry) {
+        let options = self.options;
+        let src = &entry.path;
+        let target = &self.targets[entry.route];
+        let mut planned = PlannedFile {
+            source: src.clone(),
+            root: entry.root,
+            route: entry.route,
+            suffix: entry.suffix,
+            size: entry.size,
+            modified: None,
+            hash: String::new(),
+            action: PlannedAction::Error,
+            error: None,
+            dest: None,
+            renamed: false,
+            sanitized: false,
+            duplicate_of: None,
+            existing: None,
+            details: Vec::new(),
+        };
+        let also = self.route(src, entry.route, entry.suffix);
+        if !also.is_empty() {
+            let dirs: Vec<String> = also.iter().map(|d| d.display().to_string()).collect();
+            planned.details.push(format!(
+                "also matches the route to {}; the first rule was followed",
+                dirs.join(", ")
+            ));
+        }
+        if target.rejects {
+            planned
+                .details
+                .push("matched no suffix, rejected".to_string());
+        }
+        let dest = match &options.tag {
+            Some(tag) => Ok(tag.apply(src)),
+            None => mover::dest_path_for(
+                &self.source_roots[entry.root],
+                &target.dir,
+                src,
+                (!target.rejects).then_some(entry.suffix),
+                &options.layout,
+            ),
+        };
+        let dest = match dest {
+            Ok(d) => d,
+            Err(e) => {
+                planned.details.push(e.to_string());
+                planned.error = Some(ErrorDetail::new(
+                    Some(src),
+                    ErrorStage::Destination,
+                    e.to_string(),
+                ));
+                self.error(src, None, ErrorStage::Destination, &planned.details);
+                self.planned().push(planned);
+                return;
+            }
+        };
+        let dest = if target.sanitize_names {
+            let safe = sanitize::sanitize_under(&target.dir, &dest);
+            if safe != dest {
+                planned.sanitized = true;
+                self.tally().sanitized += 1;
+                self.log.info(format_args!(
+                    "Sanitized name: {} -> {}",
+                    dest.display(),
+                    safe.display()
+                ));
+                planned
+                    .details
+                    .push(format!("name sanitized from {}", dest.display()));
+            }
+            safe
+        } else {
+            dest
+        };
+
+        self.started(src, entry.route);
+
+        // Stamped before hashing, so a write during the read shows up as a change later.
+        let read = mover::file_stamp(src).and_then(|stamp| {
+            if !self.hash_sources || self.busy(stamp.1) {
+                return Ok((stamp, String::new()));
+            }
+            let hash = hasher::hash_file_cancellable(&mover::long_path(src), self.control)?;
+            Ok((stamp, hash))
+        });
+        match read {
+            Ok(((size, modified), hash)) => {
+                planned.size = size;
+                planned.modified = modified;
+                planned.hash = hash;
+            }
+            Err(_) if self.control.is_cancelled() => {
+                self.cancelled.store(true, Ordering::Relaxed);
+                return;
+            }
+            Err(e) if self.vanished(src, &e) => return,
+            Err(e) => {
+                planned.details = vec![e.to_string()];
+                planned.error = Some(ErrorDetail::new(Some(src), ErrorStage::Read, e.to_string()));
+                self.error(src, None, ErrorStage::Read, &planned.details);
+                self.planned().push(planned);
+                return;
+            }
+        }
+        if self.busy(planned.modified) {
+            planned.action = PlannedAction::Busy;
+            self.skip_busy(src);
+            self.planned().push(planned);
+            return;
+        }
+        if planned.size == 0 {
+            if !self.empty_file(src, &planned.details) {
+                planned.action = PlannedAction::Empty;
+                self.planned().push(planned);
+                return;
+            }
+            planned.details.push(EMPTY.to_string());
+        }
+        self.simulate(planned, dest);
+    }
+
+    /// Execution: re-check `planned` and carry out its move or duplicate handling. False when
+    /// the file still has to be done: a cancel interrupted it, or it was busy.
+    pub fn execute(&self, planned: &PlannedFile) -> bool {
+        let src = &planned.source;
+        self.route(src, planned.route, planned.suffix);
+        self.started(src, planned.route);
+        if planned.action == PlannedAction::Busy {
+            self.skip_busy(src);
+            return false;
+        }
+        if planned.action == PlannedAction::Empty {
+            self.empty_file(src, &planned.details);
+            return true;
+        }
+        if planned.action == PlannedAction::Error {
+            let stage = planned.error.as_ref().map_or(ErrorStage::Read, |e| e.stage);
+            self.error(src, planned.dest.clone(), stage, &planned.details);
+            return true;
+        }
+        let Some(dest) = planned.dest.clone() else {
+            let details = ["no destination planned".to_string()];
+            self.error(src, None, ErrorStage::Destination, &details);
+            return true;
+        };
+
+        let reason = match mover::file_stamp(src) {
+            // Written to since planning, maybe still: busy rather than changed.
+            Ok((_, modified)) if self.busy(modified) => {
+                self.skip_busy(src);
+                return false;
+            }
+            Ok(stamp) if stamp == (planned.size, planned.modified) => None,
+            Ok(_) => Some("changed since it was planned".to_string()),
+            Err(e) if self.vanished(src, &e) => return true,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Some("no longer exists".to_string())
+            }
+            Err(e) => Some(e.to_string()),
+        };
+        if let Some(reason) = reason {
+            let warning = format!("Skipped {}: {}", src.display(), reason);
+            self.log.warn(&warning);
+            let mut t = self.tally();
+            t.skipped_changed += 1;
+            t.file_log.push(src, None, FileAction::Skipped, &[reason]);
+            t.warnings.push(warning.clone());
+            self.emit_problem(&t, None, Some(warning));
+            return true;
+        }
+
+        if planned.sanitized {
+            self.tally().sanitized += 1;
+            self.log
+                .info(format_args!("Sanitized name: {}", dest.display()));
+        }
+        if planned.size == 0 && !self.empty_file(src, &[]) {
+            return true;
+        }
+        let mut details = planned.details.clone();
+        if planned.renamed {
+            details.push(RENAMED.to_string());
+        }
+        self.apply(planned, dest, details)
+    }
+
+    /// Move the planned file to `dest`, unless its content is already known. False when a
+    /// cancel interrupted the move.
+    fn apply(&self, planned: &PlannedFile, dest: PathBuf, mut details: Vec<String>) -> bool {
+        let (src, hash) = (planned.source.as_path(), planned.hash.as_str());
+        let (options, route) = (self.options, planned.route);
+        // Claim the content before moving: another copy of it, in this worker or another one,
+        // is then a duplicate instead of a second transfer.
+        let known = if !self.compares(planned) {
+            None
+        } else {
+            self.hash_destination(route, planned.size);
+            let mut index = self.index(route);
+            let known = self.known(&index, route, planned.size, hash);
+            if known.is_none() {
+                let source = src.to_path_buf();
+                index.insert(
+                    hash.to_string(),
+                    Origin::Batch {
+                        source,
+                        done: false,
+                        dest: None,
+                    },
+                );
+            }
+            known
+        };
+        // Without a duplicate check, the file at the target path is never taken for this one.
+        let mut move_hash = if self.compares(planned) { hash } else { "" };
+        // Whether the content was claimed above; a file that only matched a hash leaves the
+        // index as it was.
+        let claimed = known.is_none();
+        if let Some(origin) = known {
+            if self.confirmed(planned, origin.file(), &mut details) {
+                self.duplicate(planned, &origin);
+                return true;
+            }
+            move_hash = "";
+        } else if self.confirm_duplicates
+            && !move_hash.is_empty()
+            && self.targets[route].claims.same_content_at(&dest, hash)
+        {
+            // The move would skip it for the file already at `dest`; compare with that first.
+            let existing = self.targets[route].claims.existing_variant(&dest);
+            if !self.confirmed(planned, existing.as_deref(), &mut details) {
+                move_hash = "";
+            }
+        }
+        let release = || {
+            if claimed {
+                self.index(route).remove(hash);
+            }
+        };
+        let claims = &self.targets[route].claims;
+        match mover::move_file(src, &dest, move_hash, options, claims, self.control) {
+            Ok(mover::MoveResult::Moved {
+                dest: actual_dest,
+                method,
+                verified: copy_verified,
+                hash: moved_hash,
+                replaced,
+            }) => {
+                self.tally().failures = FailureStreak::default();
+                let linked = matches!(
+                    options.transfer_mode,
+                    TransferMode::Hardlink | TransferMode::Reflink
+                );
+                if linked && method == TransferMethod::Copied {
+                    self.log.warn(format_args!(
+                        "could not {} {}, copied instead",
+                        options.transfer_mode.verb(),
+                        src.display()
+                    ));
+                    details.push(format!(
+                        "could not {}, copied instead",
+                        options.transfer_mode.verb()
+                    ));
+                }
+                if copy_verified {
+                    details.push("copied across volumes, verified".to_string());
+                }
+                if let Some(replaced) = &replaced {
+                    details.push(replaced_detail(replaced));
+                } else if actual_dest != dest && !details.iter().any(|d| d == RENAMED) {
+                    details.push(RENAMED.to_string());
+                }
+                let read_only = self.make_read_only(&actual_dest, method, &mut details);
+                self.log.info(format_args!(
+                    "moved {} -> {} ({}{}{})",
+                    src.display(),
+                    actual_dest.display(),
+                    method.as_str(),
+                    if copy_verified { ", verified" } else { "" },
+                    if replaced.is_some() { ", replaced" } else { "" }
+                ));
+                let placed = self.record_placed.then(|| PlacedFile {
+                    dest: actual_dest.clone(),
+                    hash: hash.to_string(),
+                    size: planned.size,
+                });
+                // The moved file's hash is the claimed one, so no re-read is needed.
+                if claimed {
+                    self.index(route).insert(
+                        moved_hash,
+                        Origin::Batch {
+                            source: src.to_path_buf(),
+                            done: true,
+                            dest: Some(actual_dest.clone()),
+                        },
+                    );
+                }
+                let mut t = self.tally();
+                self.count_moved(&mut t, planned, replaced.is_some());
+                t.placed.extend(placed);
+                if copy_verified {
+                    t.count_verified(planned.size);
+                }
+                t.read_only += u64::from(read_only);
+                let moved = FileEvent::Moved {
+                    source: src.to_path_buf(),
+                    dest: actual_dest.clone(),
+                };
+                t.file_log.push_replacing(
+                    src,
+                    actual_dest,
+                    FileAction::Moved,
+                    replaced,
+                    read_only,
+                    &details,
+                );
+                self.emit_file(&t, moved);
+            }
+            Ok(mover::MoveResult::SourceKept {
+                dest: actual_dest,
+                verified: copy_verified,
+                hash: kept_hash,
+                reason,
+                replaced,
+            }) => {
+                self.tally().failures = FailureStreak::default();
+                self.log.warn(format_args!(
+                    "copied {} -> {} but could not remove the source: {}",
+                    src.display(),
+                    actual_dest.display(),
+                    reason
+                ));
+                let placed = self.record_placed.then(|| PlacedFile {
+                    dest: actual_dest.clone(),
+                    hash: hash.to_string(),
+                    size: planned.size,
+                });
+                if claimed {
+                    self.index(route).insert(
+                        kept_hash,
+                        Origin::Batch {
+                            source: src.to_path_buf(),
+                            done: true,
+                            dest: Some(actual_dest.clone()),
+                        },
+                    );
+                }
+                if let Some(replaced) = &replaced {
+                    details.push(replaced_detail(replaced));
+                }
+                details.push(format!("source not removed: {}", reason));
+                let read_only =
+                    self.make_read_only(&actual_dest, TransferMethod::Copied, &mut details);
+                let mut t = self.tally();
+                t.source_kept += 1;
+                t.placed.extend(placed);
+                if copy_verified {
+                    t.count_verified(planned.size);
+                }
+                t.read_only += u64::from(read_only);
+                let moved = FileEvent::Moved {
+                    source: src.to_path_buf(),
+                    dest: actual_dest.clone(),
+                };
+                t.file_log.push_replacing(
+                    src,
+                    actual_dest,
+                    FileAction::SourceKept,
+                    replaced,
+                    read_only,
+                    &details,
+                );
+                self.emit_file(&t, moved);
+            }
+            Ok(mover::MoveResult::KeptNewer { existing }) => {
+                self.tally().failures = FailureStreak::default();
+                release();
+                self.keep_newer(src, existing, details);
+            }
+            Ok(mover::MoveResult::SkippedDuplicate) => {
+                self.tally().failures = FailureStreak::default();
+                // Same content under the same name, but not in the index (e.g. not an image
+                // extension the index covers); it was in the destination all along.
+                let origin = Origin::Dest {
+                    path: claims.existing_variant(&dest),
+                };
+                self.index(route).insert(hash.to_string(), origin.clone());
+                self.duplicate(planned, &origin);
+            }
+            Err(_) if self.control.is_cancelled() => {
+                // Copy was interrupted by cancel; the partial file is already gone.
+                release();
+                self.cancelled.store(true, Ordering::Relaxed);
+                return false;
+            }
+            Err(e) if self.vanished(src, &e) => release(),
+            Err(e) => {
+                release();
+                details.push(e.to_string());
+                self.error(src, Some(dest), ErrorStage::Move, &details);
+                self.move_failed(&self.targets[route].dir, e.kind(), &e.to_string());
+            }
+        }
+        true
+    }
+
+    /// Count `file` as moved: to the rejects folder, or as a match of its suffix; `replaced`
+    /// when it took the place of an older file.
+    fn count_moved(&self, t: &mut Tally, file: &PlannedFile, replaced: bool) {
+        if self.is_rejects(file.route) {
+            t.rejected += 1;
+            return;
+        }
+        if self.options.tag.is_some() {
+            t.tagged += 1;
+            t.suffix(file.suffix).moved += 1;
+            return;
+        }
+        if replaced {
+            t.replaced += 1;
+        } else {
+            t.moved += 1;
+        }
+        t.bytes_moved += file.size;
+        t.suffix(file.suffix).moved += 1;
+    }
+
+    /// With `MoveOptions::read_only`, make the file just put at `dest` by `method` read-only;
+    /// whether it now is. A hardlink or a symbolic link is left alone, and a filesystem that
+    /// cannot do it gets a warning and a note in `details`; neither fails the move.
+    fn make_read_only(
+        &self,
+        dest: &Path,
+        method: TransferMethod,
+        details: &mut Vec<String>,
+    ) -> bool {
+        let linked = matches!(
+            method,
+            TransferMethod::Hardlinked | TransferMethod::Symlinked
+        );
+        if !self.options.read_only || linked {
+            return false;
+        }
+        let reason = match mover::set_read_only(dest, true) {
+            Ok(true) => {
+                details.push(READ_ONLY.to_string());
+                return true;
+            }
+            Ok(false) => "the filesystem does not support it".to_string(),
+            Err(e) => e.to_string(),
+        };
+        let warning = format!("Could not make {} read-only: {}", dest.display(), reason);
+        self.log.warn(&warning);
+        details.push(format!("not made read-only: {}", reason));
+        self.tally().warnings.push(warning);
+        false
+    }
+
+    /// A source left where it is because the file at `existing`, holding its name, is not
+    /// older (`CollisionMode::Update`).
+    fn keep_newer(&self, src: &Path, existing: PathBuf, mut details: Vec<String>) {
+        self.log.info(format_args!(
+            "{}: {} is not older, kept",
+            src.display(),
+            existing.display()
+        ));
+        details.push(NEWER.to_string());
+        let mut t = self.tally();
+        t.skipped_newer += 1;
+        t.file_log
+            .push(src, Some(existing), FileAction::Skipped, &details);
+    }
+
+    /// Count a duplicate; `origin` says whether it copies destination content or another file
+    /// of this run. Returns the details to record, starting with "copy of ..." for the latter.
+    fn count_duplicate(&self, t: &mut Tally, file: &PlannedFile, origin: &Origin) -> Vec<String> {
+        let src = &file.source;
+        if self.is_rejects(file.route) {
+            t.rejected_duplicates += 1;
+        } else {
+            t.skipped_duplicates += 1;
+            t.bytes_duplicates += file.size;
+            t.suffix(file.suffix).skipped_duplicates += 1;
+        }
+        let at = match origin.location() {
+            Some(path) => format!("already at {}", path.display()),
+            None => "already in the destination".to_string(),
+        };
+        match origin.source() {
+            Some(original) => {
+                t.source_duplicates += 1;
+                self.log.info(format_args!(
+                    "skipped {} — copy of {}, {}",
+                    src.display(),
+                    original.display(),
+                    at
+                ));
+                vec![format!("copy of {}", original.display())]
+            }
+            None => {
+                self.log
+                    .info(format_args!("skipped {} — {}", src.display(), at));
+                Vec::new()
+            }
+        }
+    }
+
+    /// A source file whose content is already in the destination, or on its way there.
+    fn duplicate(&self, file: &PlannedFile, origin: &Origin) {
+        let src = file.source.as_path();
+        let mut details = self.count_duplicate(&mut self.tally(), file, origin);
+        let mut action = self.options.duplicate_action;
+        if matches!(origin, Origin::Batch { done: false, .. }) && action != DuplicateAction::Leave {
+            // The original's move may still fail; keep this copy until it is safe.
+            details.push("source kept: original is still being moved".to_string());
+            action = DuplicateAction::Leave;
+        }
+        match mover::dispose_duplicate(src, action) {
+            Ok(true) => {
+                let removed = match action {
+                    DuplicateAction::Trash => "source trashed",
+                    _ => "source deleted",
+                };
+                self.log
+                    .info(format_args!("{}: {}", src.display(), removed));
+                details.push(removed.to_string());
+                let mut t = self.tally();
+                t.duplicates_removed += 1;
+                t.file_log
+                    .push_duplicate(src, origin.source(), origin.location(), &details);
+                self.emit_file(&t, duplicate_event(src, origin));
+            }
+            Ok(false) => {
+                let mut t = self.tally();
+                t.file_log
+                    .push_duplicate(src, origin.source(), origin.location(), &details);
+                self.emit_file(&t, duplicate_event(src, origin));
+            }
+            Err(e) => {
+                details.push(format!("duplicate; could not remove source: {}", e));
+                self.error(src, None, ErrorStage::Duplicate, &details);
+            }
+        }
+    }
+
+    /// Planning: record what would happen to the file without touching anything. The index
+    /// and name claims are updated as a real move would update them (claims are never released,
+    /// standing in for the file that would exist), so later candidates are predicted alike.
+    fn simulate(&self, mut planned: PlannedFile, dest: PathBuf) {
+        let (options, route) = (self.options, planned.route);
+        let src = planned.source.clone();
+        let src = src.as_path();
+        // The file at the target path is what the move compares with when the index has no
+        // match, as for content the index does not cover.
+        let at_target = self.compares(&planned)
+            && self.targets[route]
+                .claims
+                .same_content_at(&dest, &planned.hash);
+        let known = if !self.compares(&planned) {
+            None
+        } else {
+            self.hash_destination(route, planned.size);
+            let mut index = self.index(route);
+            let mut known = self.known(&index, route, planned.size, &planned.hash);
+            if known.is_none() && at_target {
+                let path = self.targets[route].claims.existing_variant(&dest);
+                known = Some(Origin::Dest { path });
+            }
+            if known.is_none() {
+                let source = src.to_path_buf();
+                let origin = Origin::Batch {
+                    source,
+                    done: true,
+                    dest: None,
+                };
+                index.insert(planned.hash.clone(), origin);
+            }
+            known
+        };
+        let mut mismatch = Vec::new();
+        let confirmed = known
+            .clone()
+            .filter(|origin| self.confirmed(&planned, origin.file(), &mut mismatch));
+        if !mismatch.is_empty() {
+            // Planned as a move; execution compares again with the destination file.
+            planned.existing = known.and_then(|origin| origin.existing());
+        }
+        if let Some(origin) = confirmed {
+            planned.action = PlannedAction::Duplicate;
+            planned.dest = Some(dest);
+            planned.duplicate_of = origin.source().map(Path::to_path_buf);
+            planned.existing = origin.existing();
+            let mut t = self.tally();
+            let mut details = self.count_duplicate(&mut t, &planned, &origin);
+            if options.duplicate_action != DuplicateAction::Leave {
+                t.duplicates_removed += 1;
+                t.would_remove.insert(src.to_path_buf());
+                let verb = match options.duplicate_action {
+                    DuplicateAction::Trash => "trash",
+                    _ => "delete",
+                };
+                self.log.info(format_args!(
+                    "[dry-run] would {} duplicate {}",
+                    verb,
+                    src.display()
+                ));
+                details.push(format!("would {} the source", verb));
+            }
+            t.file_log
+                .push_duplicate(src, origin.source(), origin.location(), &details);
+            drop(t);
+            self.planned().push(planned);
+            return;
+        }
+
+        let mut details = planned.details.clone();
+        details.extend(mismatch);
+        let claims = &self.targets[route].claims;
+        let replaces = match options.on_collision {
+            CollisionMode::Update => claims.claim_existing(&dest),
+            CollisionMode::Rename => None,
+        };
+        if let Some(existing) = &replaces {
+            if !mover::source_is_newer(src, existing) {
+                claims.release(existing);
+                self.keep_newer(src, existing.clone(), details);
+                planned.action = PlannedAction::Move;
+                planned.dest = Some(dest);
+                self.planned().push(planned);
+                return;
+            }
+            let aside = claims.claim(
+                existing,
+                &options.collision_pattern,
+                &collision::timestamp_now(),
+            );
+            details.push(format!(
+                "would replace an older file, keeping it as {}",
+                aside.file_name().unwrap_or_default().to_string_lossy()
+            ));
+        }
+        let replaced = replaces.is_some();
+        let target = replaces.unwrap_or_else(|| {
+            claims.claim(
+                &dest,
+                &options.collision_pattern,
+                &collision::timestamp_now(),
+            )
+        });
+        if target != dest && !replaced {
+            planned.renamed = true;
+            details.push(RENAMED.to_string());
+        }
+        self.log.info(format_args!(
+            "[dry-run] would {} {} -> {}{}",
+            options.transfer_mode.verb(),
+            src.display(),
+            target.display(),
+            if replaced { ", replacing it" } else { "" }
+        ));
+        details.insert(0, format!("would {}", options.transfer_mode.verb()));
+        let read_only = options.read_only && options.transfer_mode != TransferMode::Hardlink;
+        if read_only {
+            details.push("would be made read-only".to_string());
+        }
+        let mut t = self.tally();
+        self.count_moved(&mut t, &planned, replaced);
+        t.read_only += u64::from(read_only);
+        // A tagged file stays in its folder, which is then not left empty.
+        if options.transfer_mode.removes_source() && options.tag.is_none() {
+            t.would_remove.insert(src.to_path_buf());
+        }
+        t.file_log
+            .push(src, Some(target.clone()), FileAction::Moved, &details);
+        drop(t);
+        planned.action = PlannedAction::Move;
+        planned.dest = Some(target);
+        self.planned().push(planned);
+    }
+}
@@ -0,0 +1,172 @@
+//! Checks before the moving phase: room and write access on the destination volume.
+
+use std::path::{Path, PathBuf};
+
+use super::meter::human_bytes;
+use super::{validate, RunOptions};
+use crate::mover::{self, TransferMode};
+use crate::scanner::ImageEntry;
+
+/// What the pre-flight found out about the destination.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct Preflight {
+    /// Bytes the run writes to the destination volume: every candidate that is copied rather
+    /// than renamed or hard-linked. Duplicates are not known yet, so they are included.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub needed_bytes: u64,
+    /// Free bytes on the destination volume, when the system reports them.
+    #[cfg_attr(test, ts(type = "number | null"))]
+    pub free_bytes: Option<u64>,
+    /// Source roots on another volume than the destination; their files are copied, verified
+    /// and then deleted instead of renamed.
+    pub cross_volume: Vec<PathBuf>,
+}
+
+/// Why a run was stopped before moving anything.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PreflightError {
+    #[error(
+        "not enough free space in {}: {} needed, {} available",
+        dest.display(),
+        human_bytes(*needed),
+        human_bytes(*available)
+    )]
+    InsufficientSpace {
+        dest: PathBuf,
+        #[cfg_attr(test, ts(type = "number"))]
+        needed: u64,
+        #[cfg_attr(test, ts(type = "number"))]
+        available: u64,
+    },
+    #[error("destination {} is not writable: {reason}", dest.display())]
+    NotWritable { dest: PathBuf, reason: String },
+}
+
+impl Preflight {
+    /// Work out how much the run writes to `dest` (the files of `candidates` that go there)
+    /// and which roots are on another volume; `roots` are the canonical source roots
+    /// `candidates` index into. A `dest` that does not exist yet is measured on the folder it
+    /// will be created in.
+    pub fn measure<'a>(
+        run_options: &RunOptions,
+        dest: &Path,
+        roots: &[PathBuf],
+        candidates: impl IntoIterator<Item = &'a ImageEntry>,
+    ) -> Preflight {
+        let existing = existing_folder(dest);
+        let dest = mover::long_path(&existing);
+        let crosses: Vec<bool> = roots
+            .iter()
+            .map(|r| !mover::same_volume(r, &dest))
+            .collect();
+        let mode = run_options.move_options.transfer_mode;
+        let copies = |root: usize| copies_data(mode, crosses[root]);
+        Preflight {
+            needed_bytes: candidates
+                .into_iter()
+                .filter(|c| copies(c.root))
+                .map(|c| c.size)
+                .sum(),
+            free_bytes: mover::volume_space(&dest).map(|space| space.available),
+            cross_volume: roots
+                .iter()
+                .zip(&crosses)
+                .filter(|(_, &crosses)| crosses)
+                .map(|(r, _)| r.clone())
+                .collect(),
+        }
+    }
+
+    /// Whether files from `root` are copied in `mode`, rather than renamed or hard-linked.
+    pub fn copies_from(&self, mode: TransferMode, root: &Path) -> bool {
+        copies_data(mode, self.cross_volume.iter().any(|r| r == root))
+    }
+
+    /// Fail when the destination lacks room for `needed_bytes` or (unless `dry_run`, which must
+    /// not write) files cannot be created in it, or in the folder it will be created in.
+    pub fn verify(&self, dest: &Path, dry_run: bool) -> Result<(), PreflightError> {
+        if let Some(available) = self.free_bytes.filter(|&free| free < self.needed_bytes) {
+            return Err(PreflightError::InsufficientSpace {
+                dest: dest.to_path_buf(),
+                needed: self.needed_bytes,
+                available,
+            });
+        }
+        if !dry_run {
+            mover::probe_writable(&existing_folder(dest)).map_err(|e| {
+                PreflightError::NotWritable {
+                    dest: dest.to_path_buf(),
+                    reason: e.to_string(),
+                }
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// `dest`, or its nearest folder that exists when it does not exist yet.
+fn existing_folder(dest: &Path) -> PathBuf {
+    validate::resolve(dest).map_or_else(|| dest.to_path_buf(), |(existing, _)| existing)
+}
+
+/// Whether `mode` writes a file's bytes, for a source on another volume when `crosses`.
+fn copies_data(mode: TransferMode, crosses: bool) -> bool {
+    match mode {
+        // Reflinks fall back to a copy on filesystems without them.
+        TransferMode::Copy | TransferMode::Reflink => true,
+        TransferMode::Move | TransferMode::Hardlink => crosses,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_verify_space_and_writability() {
+        let dest = tempfile::tempdir().unwrap();
+        let roomy = Preflight {
+            needed_bytes: 10,
+            free_bytes: Some(100),
+            cross_volume: vec![],
+        };
+        assert_eq!(roomy.verify(dest.path(), false), Ok(()));
+        // The probe file is gone again.
+        assert_eq!(fs::read_dir(dest.path()).unwrap().count(), 0);
+
+        let full = Preflight {
+            free_bytes: Some(5),
+            ..roomy.clone()
+        };
+        let err = full.verify(dest.path(), true).unwrap_err();
+        assert!(matches!(
+            err,
+            PreflightError::InsufficientSpace {
+                needed: 10,
+                available: 5,
+                ..
+            }
+        ));
+        assert!(err.to_string().starts_with("not enough free space in "));
+
+        // A destination that is not there yet is probed in the folder it will be created in.
+        let missing = dest.path().join("gone/2024");
+        assert_eq!(roomy.verify(&missing, false), Ok(()));
+        assert!(!dest.path().join("gone").exists());
+
+        let file = dest.path().join("file");
+        fs::write(&file, b"x").unwrap();
+        let beneath = file.join("gone");
+        assert!(matches!(
+            roomy.verify(&beneath, false),
+            Err(PreflightError::NotWritable { .. })
+        ));
+        // Dry-runs don't write, so they don't probe.
+        assert_eq!(roomy.verify(&beneath, true), Ok(()));
+    }
+}
@@ -0,0 +1,176 @@
+//! The scanning phase of a run on its own, to show what a run would touch before it starts.
+//! Nothing is created, not even the destination, and no file is read beyond its metadata.
+
+use super::report::SuffixCount;
+use super::runlog::RunLog;
+use super::{
+    count_suffixes, parse_rules, resolve_sources, scan_sources, take_first, Destination, RunError,
+    RunOptions, Setup,
+};
+use crate::control::RunControl;
+use std::path::PathBuf;
+
+/// `max_entries` the app asks `preview_scan` for: enough to look through, few enough to send
+/// and sort at once.
+pub const DEFAULT_PREVIEW_ENTRIES: usize = 5000;
+
+/// One file a run would take, as the scan found it.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Last modification, RFC 3339 in local time; `None` when it can't be read.
+    pub modified: Option<String>,
+    /// The requested suffix it matched.
+    pub suffix: u32,
+}
+
+/// Result of [`preview_scan`].
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanPreview {
+    /// The first matches, sorted by path within each source (the order a run takes them).
+    pub entries: Vec<PreviewEntry>,
+    /// All the matches and their bytes, those past `entries` too; `truncated` when there are
+    /// more than `entries`.
+    pub matched: u64,
+    pub total_bytes: u64,
+    pub truncated: bool,
+    /// Every file looked at, and those with an image extension.
+    pub scanned: u64,
+    pub images: u64,
+    /// Matches left out by the exclusion globs, links skipped by the symlink policy, and those
+    /// past `RunOptions::limit`, as a run would count them; none of them are in `matched`.
+    pub excluded: u64,
+    pub skipped_symlinks: u64,
+    pub over_limit: u64,
+    /// Files of each requested suffix.
+    pub suffixes: Vec<SuffixCount>,
+    /// What a run would warn about while scanning: broken links, suffixes that matched nothing
+    /// and matches of other file types.
+    pub warnings: Vec<String>,
+    /// Stopped by a cancel before the scan was done (the inputs changed): the counts are partial.
+    pub cancelled: bool,
+}
+
+/// Scan the sources of `run_options` for the files a run would take, with its suffixes (the
+/// routes' too), exclusion globs, matching and symlink policy and limit, and return them: at
+/// most `max_entries`, with the totals of all. Images for a rejects folder are left out; the
+/// destinations are not looked at. A cancel on `control` stops the scan and returns what was
+/// found, marked `cancelled`.
+pub fn preview_scan(
+    run_options: &RunOptions,
+    max_entries: usize,
+    control: &RunControl,
+) -> Result<ScanPreview, RunError> {
+    let routed = parse_rules(run_options)?;
+    let source_roots = resolve_sources(run_options)?;
+    let destinations = run_options
+        .rules()
+        .zip(routed)
+        .filter(|((input, _), _)| input.is_some())
+        .map(|((_, dest), suffixes)| Destination {
+            dir: dest.to_path_buf(),
+            suffixes,
+            sanitize_names: false,
+            rejects: false,
+        })
+        .collect::<Vec<_>>();
+    let setup = Setup {
+        suffixes: destinations
+            .iter()
+            .flat_map(|d| &d.suffixes)
+            .copied()
+            .collect(),
+        source_roots,
+        destinations,
+    };
+    let log = RunLog::new(false);
+    let emit = |_| {};
+    let scanned = scan_sources(&setup, run_options, control, &emit, &log)?;
+    let (suffixes, _, scan_warnings) = count_suffixes(&setup, &scanned, &emit, &log);
+    let mut candidates = scanned.candidates;
+    let over_limit = take_first(&setup, &mut candidates, run_options.file_limit(), &log);
+
+    let entries = candidates
+        .iter()
+        .take(max_entries)
+        .map(|entry| PreviewEntry {
+            path: entry.path.clone(),
+            size: entry.size,
+            modified: std::fs::metadata(&entry.path)
+                .and_then(|m| m.modified())
+                .ok()
+                .map(|t| chrono::DateTime::<chrono::Local>::from(t).to_rfc3339()),
+            suffix: entry.suffix,
+        })
+        .collect();
+    Ok(ScanPreview {
+        entries,
+        matched: candidates.len() as u64,
+        total_bytes: candidates.iter().map(|c| c.size).sum(),
+        truncated: candidates.len() > max_entries,
+        scanned: scanned.count.files,
+        images: scanned.count.images,
+        excluded: scanned.excluded.len() as u64,
+        skipped_symlinks: scanned.skipped_symlinks,
+        over_limit,
+        suffixes,
+        warnings: scanned
+            .link_warnings
+            .into_iter()
+            .chain(scan_warnings)
+            .collect(),
+        cancelled: control.is_cancelled(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exclude::ExcludeGlobs;
+    use std::fs;
+
+    #[test]
+    fn test_preview_lists_the_matches_and_creates_nothing() {
+        let (src, archive) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let dest = archive.path().join("picks");
+        fs::create_dir(src.path().join("b")).unwrap();
+        fs::write(src.path().join("b/IMG_7612.JPG"), "12345").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), "123").unwrap();
+        fs::write(src.path().join("thumb_7608.JPG"), "1").unwrap();
+        fs::write(src.path().join("IMG_7600.JPG"), "other").unwrap();
+        let options = RunOptions::new(src.path(), &dest, "7612, 7608, 7650")
+            .exclude_globs(ExcludeGlobs::new(["thumb_*"]).unwrap());
+        let control = RunControl::new();
+
+        let preview = preview_scan(&options, 1, &control).unwrap();
+        assert_eq!(
+            (preview.matched, preview.total_bytes, preview.excluded),
+            (2, 8, 1)
+        );
+        assert!(preview.truncated && !preview.cancelled);
+        assert_eq!(preview.entries.len(), 1);
+        let first = &preview.entries[0];
+        assert_eq!((first.suffix, first.size), (7608, 3));
+        assert!(first.path.ends_with("IMG_7608.JPG") && first.modified.is_some());
+        assert!(preview.warnings.iter().any(|w| w.contains("7650")));
+        assert!(!dest.exists());
+
+        let bad = RunOptions::new(src.path(), &dest, "IMG");
+        assert_eq!(
+            preview_scan(&bad, 10, &control).unwrap_err().kind(),
+            "invalid_suffixes"
+        );
+        let missing = RunOptions::new(src.path().join("gone"), &dest, "7612");
+        assert_eq!(
+            preview_scan(&missing, 10, &control).unwrap_err().kind(),
+            "source_unavailable"
+        );
+
+        control.cancel();
+        let preview = preview_scan(&options, 10, &control).unwrap();
+        assert!(preview.cancelled && preview.entries.is_empty());
+    }
+}
@@ -0,0 +1,671 @@
+//! Per-file outcomes collected during a run, and the JSON run report.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::{DedupMode, Route, RunOptions, RunResult};
+use crate::mover::{CollisionMode, DestLayout, DuplicateAction, ReplacedFile, TransferMode};
+use crate::sanitize::SanitizeMode;
+use crate::suffix_parser;
+
+/// Most per-file records kept in a `RunResult`; later files are only counted.
+pub const MAX_FILE_RECORDS: usize = 20_000;
+
+/// What happened to one matched file (or would happen, in dry-run).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum FileAction {
+    /// Put into the destination (renamed, copied or linked depending on the mode).
+    Moved,
+    /// Content already in the destination; not transferred.
+    Duplicate,
+    /// Copied, but the source could not be removed.
+    SourceKept,
+    /// Planned, but left in place because it changed or disappeared before it was moved.
+    Skipped,
+    Error,
+}
+
+impl FileAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileAction::Moved => "moved",
+            FileAction::Duplicate => "duplicate",
+            FileAction::SourceKept => "source_kept",
+            FileAction::Skipped => "skipped",
+            FileAction::Error => "error",
+        }
+    }
+}
+
+/// Where in a run an error happened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorStage {
+    /// Checking the options and folders before scanning.
+    Setup,
+    Scan,
+    Preflight,
+    /// Deriving the destination path (layout, template).
+    Destination,
+    /// Reading a source file to hash it.
+    Read,
+    Move,
+    /// Deleting or trashing a duplicate source.
+    Duplicate,
+    /// The check after moving: a moved file is missing or differs.
+    Verify,
+}
+
+impl ErrorStage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorStage::Setup => "setup",
+            ErrorStage::Scan => "scan",
+            ErrorStage::Preflight => "preflight",
+            ErrorStage::Destination => "destination",
+            ErrorStage::Read => "read",
+            ErrorStage::Move => "move",
+            ErrorStage::Duplicate => "duplicate",
+            ErrorStage::Verify => "verify",
+        }
+    }
+}
+
+/// One error of a run: which file (if any), at what stage, and what went wrong.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorDetail {
+    pub path: Option<PathBuf>,
+    pub stage: ErrorStage,
+    pub message: String,
+}
+
+impl ErrorDetail {
+    pub fn new(path: Option<&Path>, stage: ErrorStage, message: impl Into<String>) -> ErrorDetail {
+        ErrorDetail {
+            path: path.map(Path::to_path_buf),
+            stage,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorDetail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(p) => write!(
+                f,
+                "{} ({}): {}",
+                p.display(),
+                self.stage.as_str(),
+                self.message
+            ),
+            None => write!(f, "{}: {}", self.stage.as_str(), self.message),
+        }
+    }
+}
+
+/// One line of the per-file report.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct FileRecord {
+    pub source: PathBuf,
+    /// Where the file went (or would go); `None` when it never got a destination.
+    pub dest: Option<PathBuf>,
+    pub action: FileAction,
+    /// Short explanation, e.g. "name taken, renamed" or the error message.
+    pub detail: Option<String>,
+    /// For a duplicate of another file in this run: that file's source path.
+    #[serde(default)]
+    pub duplicate_of: Option<PathBuf>,
+    /// For a duplicate: the destination file with its content, where known; for a copy of
+    /// another file of this run, where that file went.
+    #[serde(default)]
+    pub existing: Option<PathBuf>,
+    /// The source root the file was found under.
+    #[serde(default)]
+    pub root: Option<PathBuf>,
+    /// With routes: the destination folder the file's suffix was routed to.
+    #[serde(default)]
+    pub route: Option<PathBuf>,
+    /// Destinations of later routes that list the suffix too; the first rule was followed.
+    #[serde(default)]
+    pub also_routed: Vec<PathBuf>,
+    /// The older file that was at `dest` before this one replaced it.
+    #[serde(default)]
+    pub replaced: Option<ReplacedFile>,
+    /// The file at `dest` was made read-only (`RunOptions::read_only`); moving it back should
+    /// clear that first.
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// Source files of one run with identical content: `source` was moved to `dest`, the `copies`
+/// were skipped as its duplicates.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    pub source: PathBuf,
+    pub dest: Option<PathBuf>,
+    pub copies: Vec<PathBuf>,
+}
+
+/// Group the records' in-run duplicates under the file they copy, sorted by that file.
+pub fn duplicate_groups(records: &[FileRecord]) -> Vec<DuplicateGroup> {
+    let mut groups: BTreeMap<&Path, Vec<PathBuf>> = BTreeMap::new();
+    for r in records {
+        if let Some(original) = &r.duplicate_of {
+            groups.entry(original).or_default().push(r.source.clone());
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(source, copies)| DuplicateGroup {
+            source: source.to_path_buf(),
+            dest: records
+                .iter()
+                .find(|r| r.source == source)
+                .and_then(|r| r.dest.clone()),
+            copies,
+        })
+        .collect()
+}
+
+/// What became of the files of one requested suffix.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct SuffixCount {
+    pub suffix: u32,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub matched: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub moved: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub skipped_duplicates: u64,
+    /// Files whose name matched but whose type is not an image one (e.g. .CR3), left out.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub other_types: u64,
+}
+
+/// A row for every suffix in `requested`, sorted, with the number of `matched` files of each.
+pub(super) fn suffix_table(
+    requested: &HashSet<u32>,
+    matched: impl IntoIterator<Item = u32>,
+) -> Vec<SuffixCount> {
+    let mut table: Vec<SuffixCount> = requested
+        .iter()
+        .map(|&suffix| SuffixCount {
+            suffix,
+            ..Default::default()
+        })
+        .collect();
+    table.sort_by_key(|row| row.suffix);
+    add_suffix_counts(
+        &mut table,
+        matched.into_iter().map(|suffix| SuffixCount {
+            suffix,
+            matched: 1,
+            ..Default::default()
+        }),
+    );
+    table
+}
+
+/// Add `counts` to the rows of `table` with the same suffix; new suffixes get a row of their
+/// own, keeping the table sorted.
+pub(super) fn add_suffix_counts(
+    table: &mut Vec<SuffixCount>,
+    counts: impl IntoIterator<Item = SuffixCount>,
+) {
+    for count in counts {
+        match table.binary_search_by_key(&count.suffix, |row| row.suffix) {
+            Ok(i) => {
+                let row = &mut table[i];
+                row.matched += count.matched;
+                row.moved += count.moved;
+                row.skipped_duplicates += count.skipped_duplicates;
+                row.other_types += count.other_types;
+            }
+            Err(i) => table.insert(i, count),
+        }
+    }
+}
+
+/// "No files found for: 7611, 7620, 7688", when there are such suffixes.
+pub(super) fn unmatched_warning(unmatched: &[u32]) -> Option<String> {
+    if unmatched.is_empty() {
+        return None;
+    }
+    let list: Vec<String> = unmatched.iter().map(u32::to_string).collect();
+    Some(format!("No files found for: {}", list.join(", ")))
+}
+
+/// Count the name matches of another file type (suffix and extension, from the scan) in
+/// `table`; returns their number and their extensions (".CR3"), sorted.
+pub(super) fn add_other_types(
+    table: &mut Vec<SuffixCount>,
+    other_types: &[(u32, String)],
+) -> (u64, Vec<String>) {
+    add_suffix_counts(
+        table,
+        other_types.iter().map(|&(suffix, _)| SuffixCount {
+            suffix,
+            other_types: 1,
+            ..Default::default()
+        }),
+    );
+    let extensions: BTreeSet<String> = other_types
+        .iter()
+        .map(|(_, ext)| format!(".{}", ext.to_uppercase()))
+        .collect();
+    (other_types.len() as u64, extensions.into_iter().collect())
+}
+
+/// "19 files matched your numbers but were skipped by the file-type filter (.CR3, .MP4)".
+pub(super) fn other_types_warning(count: u64, extensions: &[String]) -> Option<String> {
+    let (files, were) = match count {
+        0 => return None,
+        1 => ("file", "was"),
+        _ => ("files", "were"),
+    };
+    Some(format!(
+        "{} {} matched your numbers but {} skipped by the file-type filter ({})",
+        count,
+        files,
+        were,
+        extensions.join(", ")
+    ))
+}
+
+/// Collects records up to `MAX_FILE_RECORDS` so memory stays bounded on huge runs.
+#[derive(Debug, Default)]
+pub(crate) struct FileLog {
+    records: Vec<FileRecord>,
+    omitted: u64,
+}
+
+impl FileLog {
+    pub fn push(
+        &mut self,
+        source: &Path,
+        dest: Option<PathBuf>,
+        action: FileAction,
+        details: &[String],
+    ) {
+        self.record(source, dest, action, details, None);
+    }
+
+    /// A duplicate; `duplicate_of` is the file of this run it copies, if any, and `existing`
+    /// the destination file holding the content.
+    pub fn push_duplicate(
+        &mut self,
+        source: &Path,
+        duplicate_of: Option<&Path>,
+        existing: Option<&Path>,
+        details: &[String],
+    ) {
+        let record = self.record(source, None, FileAction::Duplicate, details, duplicate_of);
+        if let Some(record) = record {
+            record.existing = existing.map(Path::to_path_buf);
+        }
+    }
+
+    /// A file put at `dest`, which may have taken the place of the `replaced` one and been
+    /// made `read_only`.
+    pub fn push_replacing(
+        &mut self,
+        source: &Path,
+        dest: PathBuf,
+        action: FileAction,
+        replaced: Option<ReplacedFile>,
+        read_only: bool,
+        details: &[String],
+    ) {
+        if let Some(record) = self.record(source, Some(dest), action, details, None) {
+            record.replaced = replaced;
+            record.read_only = read_only;
+        }
+    }
+
+    fn record(
+        &mut self,
+        source: &Path,
+        dest: Option<PathBuf>,
+        action: FileAction,
+        details: &[String],
+        duplicate_of: Option<&Path>,
+    ) -> Option<&mut FileRecord> {
+        if self.records.len() >= MAX_FILE_RECORDS {
+            self.omitted += 1;
+            return None;
+        }
+        self.records.push(FileRecord {
+            source: source.to_path_buf(),
+            dest,
+            action,
+            detail: (!details.is_empty()).then(|| details.join("; ")),
+            duplicate_of: duplicate_of.map(Path::to_path_buf),
+            existing: None,
+            root: None,
+            route: None,
+            also_routed: Vec::new(),
+            replaced: None,
+            read_only: false,
+        });
+        self.records.last_mut()
+    }
+
+    /// The kept records and how many were dropped past the cap. A copy of another file of
+    /// the run that did not know where that file went (it was still being moved, or this is a
+    /// plan) gets that file's destination as `existing`.
+    pub fn finish(mut self) -> (Vec<FileRecord>, u64) {
+        let placed: HashMap<PathBuf, PathBuf> = self
+            .records
+            .iter()
+            .filter(|r| matches!(r.action, FileAction::Moved | FileAction::SourceKept))
+            .filter_map(|r| Some((r.source.clone(), r.dest.clone()?)))
+            .collect();
+        for r in &mut self.records {
+            if let (None, Some(original)) = (&r.existing, &r.duplicate_of) {
+                r.existing = placed.get(original).cloned();
+            }
+        }
+        (self.records, self.omitted)
+    }
+}
+
+/// The options a run was started with, as recorded in the report.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportOptions {
+    pub sources: Vec<PathBuf>,
+    pub dest: PathBuf,
+    pub suffixes: Vec<u32>,
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    #[serde(default)]
+    pub rejects: Option<PathBuf>,
+    pub dry_run: bool,
+    /// "mirror", "flatten" or "template".
+    pub layout: String,
+    pub dest_template: Option<String>,
+    pub transfer_mode: TransferMode,
+    pub duplicate_action: DuplicateAction,
+    pub collision_pattern: String,
+    #[serde(default)]
+    pub on_collision: CollisionMode,
+    pub dedup: DedupMode,
+    pub exclude_globs: Vec<String>,
+    pub verify_copies: bool,
+    pub sanitize: SanitizeMode,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+impl ReportOptions {
+    pub fn from_run_options(opts: &RunOptions) -> ReportOptions {
+        let m = &opts.move_options;
+        let (layout, dest_template) = match &m.layout {
+            DestLayout::Mirror => ("mirror", None),
+            DestLayout::Flatten => ("flatten", None),
+            DestLayout::Template(t) => ("template", Some(t.as_str().to_string())),
+        };
+        let mut suffixes: Vec<u32> = suffix_parser::parse_suffixes(&opts.suffix_input)
+            .into_iter()
+            .collect();
+        suffixes.sort_unstable();
+        ReportOptions {
+            sources: opts.sources.clone(),
+            dest: opts.dest.clone(),
+            suffixes,
+            routes: opts.routes.clone(),
+            rejects: opts.rejects.clone(),
+            dry_run: opts.dry_run,
+            layout: layout.to_string(),
+            dest_template,
+            transfer_mode: m.transfer_mode,
+            duplicate_action: m.duplicate_action,
+            collision_pattern: m.collision_pattern.as_str().to_string(),
+            on_collision: m.on_collision,
+            dedup: opts.dedup,
+            exclude_globs: opts.exclude_globs.patterns().to_vec(),
+            verify_copies: m.verify_copies,
+            sanitize: m.sanitize,
+            read_only: m.read_only,
+        }
+    }
+}
+
+/// Everything written to the `--report` JSON file.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunReport {
+    /// RFC 3339 local timestamps.
+    pub started_at: String,
+    pub finished_at: String,
+    pub duration_ms: u64,
+    pub options: ReportOptions,
+    pub result: RunResult,
+}
+
+/// Write `report` to `path` atomically: a temporary file next to it is written, synced and
+/// renamed over `path`, so readers never see half a report.
+pub fn write_report(path: &Path, report: &RunReport) -> std::io::Result<()> {
+    write_atomically(path, &serde_json::to_vec_pretty(report)?)
+}
+
+/// Why the `--report` of a run cannot be checked or undone from.
+#[derive(Debug, thiserror::Error)]
+pub enum ReportError {
+    #[error("cannot read report {}: {reason}", path.display())]
+    Unreadable { path: PathBuf, reason: String },
+    #[error("{} is not a FrameMover run report: {reason}", path.display())]
+    Invalid { path: PathBuf, reason: String },
+    #[error("{} is the report of a dry run; it moved nothing", path.display())]
+    DryRun { path: PathBuf },
+    #[error("{} is the report of a run in {} mode; its sources were left where they were",
+        path.display(), mode.verb())]
+    NotMoved { path: PathBuf, mode: TransferMode },
+}
+
+impl ReportError {
+    /// The report file.
+    pub fn path(&self) -> &Path {
+        match self {
+            ReportError::Unreadable { path, .. }
+            | ReportError::Invalid { path, .. }
+            | ReportError::DryRun { path }
+            | ReportError::NotMoved { path, .. } => path,
+        }
+    }
+
+    /// Stable name of the variant, for scripts.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ReportError::Unreadable { .. } => "report_unreadable",
+            ReportError::Invalid { .. } => "report_invalid",
+            ReportError::DryRun { .. } => "report_dry_run",
+            ReportError::NotMoved { .. } => "report_not_moved",
+        }
+    }
+}
+
+/// Read back the report a run wrote with `RunOptions::report_path`; one of a dry run is
+/// refused, as it records moves that never happened.
+pub fn read_report(path: &Path) -> Result<RunReport, ReportError> {
+    let text = fs::read_to_string(path).map_err(|e| ReportError::Unreadable {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    let report: RunReport = serde_json::from_str(&text).map_err(|e| ReportError::Invalid {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    if report.options.dry_run {
+        return Err(ReportError::DryRun {
+            path: path.to_path_buf(),
+        });
+    }
+    Ok(report)
+}
+
+/// Write `data` to `path` by way of a temporary file next to it, renamed over `path` once
+/// complete, so a crash leaves the old file or the new one but never half of one. Creates the
+/// parent folders.
+pub fn write_atomically(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.tmp", name));
+    let result = fs::File::create(&tmp)
+        .and_then(|mut f| f.write_all(data).and_then(|_| f.sync_all()))
+        .and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        fs::remove_file(&tmp).ok();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_log_caps_records() {
+        let mut log = FileLog::default();
+        for _ in 0..MAX_FILE_RECORDS + 3 {
+            log.push(
+                Path::new("/cards/A/IMG_7612.JPG"),
+                None,
+                FileAction::Duplicate,
+                &[],
+            );
+        }
+        let (records, omitted) = log.finish();
+        assert_eq!(records.len(), MAX_FILE_RECORDS);
+        assert_eq!(omitted, 3);
+        assert_eq!(records[0].detail, None);
+    }
+
+    #[test]
+    fn test_duplicate_groups() {
+        let mut log = FileLog::default();
+        let a = Path::new("/cards/A/IMG_7612.JPG");
+        let b = Path::new("/cards/B/IMG_7612.JPG");
+        let c = Path::new("/cards/A/backup/IMG_7612.JPG");
+        log.push(
+            a,
+            Some(PathBuf::from("/archive/IMG_7612.JPG")),
+            FileAction::Moved,
+            &[],
+        );
+        log.push_duplicate(b, Some(a), None, &[]);
+        let at = Path::new("/archive/2024/IMG_7608.JPG");
+        log.push_duplicate(Path::new("/cards/B/IMG_7608.JPG"), None, Some(at), &[]);
+        log.push_duplicate(c, Some(a), None, &[]);
+        let (records, _) = log.finish();
+
+        let existing: Vec<_> = records.iter().map(|r| r.existing.as_deref()).collect();
+        let moved = Path::new("/archive/IMG_7612.JPG");
+        assert_eq!(existing, [None, Some(moved), Some(at), Some(moved)]);
+
+        assert_eq!(
+            duplicate_groups(&records),
+            [DuplicateGroup {
+                source: a.to_path_buf(),
+                dest: Some(PathBuf::from("/archive/IMG_7612.JPG")),
+                copies: vec![b.to_path_buf(), c.to_path_buf()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_suffix_table_counts_and_merges() {
+        let requested = HashSet::from([7620, 7612, 7608]);
+        let mut table = suffix_table(&requested, [7612, 7608, 7612]);
+        let counts: Vec<(u32, u64)> = table.iter().map(|r| (r.suffix, r.matched)).collect();
+        assert_eq!(counts, [(7608, 1), (7612, 2), (7620, 0)]);
+
+        add_suffix_counts(
+            &mut table,
+            [SuffixCount {
+                suffix: 7612,
+                matched: 1,
+                moved: 2,
+                skipped_duplicates: 1,
+                other_types: 0,
+            }],
+        );
+        assert_eq!(
+            (
+                table[1].matched,
+                table[1].moved,
+                table[1].skipped_duplicates
+            ),
+            (3, 2, 1)
+        );
+        assert_eq!(
+            unmatched_warning(&[7611, 7620]).unwrap(),
+            "No files found for: 7611, 7620"
+        );
+        assert_eq!(unmatched_warning(&[]), None);
+    }
+
+    #[test]
+    fn test_report_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reports").join("run.json");
+        let opts = RunOptions::new("/cards/A", "/archive", "7612, 7608").dry_run(true);
+        let mut result = RunResult {
+            moved: 1,
+            dry_run: true,
+            ..Default::default()
+        };
+        result.files.push(FileRecord {
+            source: PathBuf::from("/cards/A/IMG_7612.JPG"),
+            dest: Some(PathBuf::from("/archive/IMG_7612.JPG")),
+            action: FileAction::Moved,
+            detail: Some("would move".to_string()),
+            duplicate_of: None,
+            existing: None,
+            root: Some(PathBuf::from("/cards/A")),
+            route: None,
+            also_routed: Vec::new(),
+            replaced: None,
+            read_only: false,
+        });
+        result
+            .warnings
+            .push("Cannot read /cards/A/x: denied".to_string());
+        let report = RunReport {
+            started_at: "2024-05-06T14:22:33+02:00".to_string(),
+            finished_at: "2024-05-06T14:22:34+02:00".to_string(),
+            duration_ms: 1000,
+            options: ReportOptions::from_run_options(&opts),
+            result,
+        };
+
+        write_report(&path, &report).unwrap();
+        let back: RunReport = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+
+        assert_eq!(back.options, report.options);
+        assert_eq!(back.options.suffixes, [7608, 7612]);
+        assert_eq!(back.result.files, report.result.files);
+        assert_eq!(back.result.warnings, report.result.warnings);
+        assert_eq!(back.result.moved, 1);
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+    }
+}
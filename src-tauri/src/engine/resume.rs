@@ -0,0 +1,493 @@
+//! Resuming an interrupted run. While a run with `RunOptions::state_path` moves files, that file
+//! holds its plan (first line) and a journal line for every file done since; [`resume`] loads
+//! it, skips what was done and executes the rest. The file is removed once the run finishes.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::percent::Scale;
+use super::plan::{self, PlannedAction};
+use super::runlog::RunLog;
+use super::{
+    emit_done, emitter, setup_failed, with_report, MovePlan, ProgressFn, RunError, RunResult,
+};
+use crate::control::RunControl;
+use crate::mover;
+
+/// Written in the state file, to tell it from other JSON.
+const FORMAT: &str = "framemover-run-state";
+const VERSION: u32 = 1;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Header {
+    format: String,
+    version: u32,
+    created_at: String,
+    plan: MovePlan,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry {
+    done: PathBuf,
+}
+
+/// Why a state file cannot be resumed from.
+#[derive(Debug, thiserror::Error)]
+pub enum ResumeError {
+    #[error("cannot read run state {}: {reason}", path.display())]
+    Unreadable { path: PathBuf, reason: String },
+    #[error("run state {} is corrupt (line {line}): {reason}", path.display())]
+    Corrupt {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+    #[error("run state {} was written by another version of FrameMover", path.display())]
+    Unsupported { path: PathBuf },
+    #[error("run state {} no longer applies: {reason}", path.display())]
+    Stale { path: PathBuf, reason: String },
+}
+
+impl ResumeError {
+    /// The state file.
+    pub fn path(&self) -> &Path {
+        match self {
+            ResumeError::Unreadable { path, .. }
+            | ResumeError::Corrupt { path, .. }
+            | ResumeError::Unsupported { path }
+            | ResumeError::Stale { path, .. } => path,
+        }
+    }
+}
+
+/// An interrupted run, as loaded from its state file.
+#[derive(Clone, Debug)]
+pub struct SavedRun {
+    pub path: PathBuf,
+    /// When the run started moving (RFC 3339).
+    pub created_at: String,
+    pub plan: MovePlan,
+    /// Sources of the files done before the interruption.
+    pub done: HashSet<PathBuf>,
+}
+
+/// What the GUI shows when it offers to resume.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedRunSummary {
+    pub created_at: String,
+    pub sources: Vec<PathBuf>,
+    pub dest: PathBuf,
+    pub total: u64,
+    pub done: u64,
+}
+
+impl SavedRun {
+    pub fn summary(&self) -> SavedRunSummary {
+        SavedRunSummary {
+            created_at: self.created_at.clone(),
+            sources: self.plan.options.sources.clone(),
+            dest: self.plan.options.dest.clone(),
+            total: self.plan.files.len() as u64,
+            done: self.done.len() as u64,
+        }
+    }
+}
+
+/// Load the state file at `path` and check it still fits the folders it names. A last line
+/// cut short by a crash is ignored; anything else that doesn't parse is corruption.
+pub fn load_state(path: &Path) -> Result<SavedRun, ResumeError> {
+    let text = fs::read_to_string(path).map_err(|e| ResumeError::Unreadable {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    let corrupt = |line: usize, reason: String| ResumeError::Corrupt {
+        path: path.to_path_buf(),
+        line,
+        reason,
+    };
+    let mut lines = text.split_inclusive('\n').enumerate();
+    let (_, first) = lines
+        .next()
+        .ok_or_else(|| corrupt(1, "empty file".to_string()))?;
+    let value: serde_json::Value =
+        serde_json::from_str(first).map_err(|e| corrupt(1, e.to_string()))?;
+    if value.get("format").and_then(|f| f.as_str()) != Some(FORMAT) {
+        return Err(corrupt(1, "not a FrameMover run state".to_string()));
+    }
+    if value.get("version").and_then(|v| v.as_u64()) != Some(VERSION.into()) {
+        return Err(ResumeError::Unsupported {
+            path: path.to_path_buf(),
+        });
+    }
+    let header: Header = serde_json::from_value(value).map_err(|e| corrupt(1, e.to_string()))?;
+    if !header.plan.complete || header.plan.options.dry_run {
+        return Err(corrupt(1, "not a plan that was being moved".to_string()));
+    }
+
+    let planned: HashSet<&Path> = header
+        .plan
+        .files
+        .iter()
+        .map(|f| f.source.as_path())
+        .collect();
+    let mut done = HashSet::new();
+    for (i, line) in lines {
+        let entry = match serde_json::from_str::<Entry>(line) {
+            Ok(entry) => entry,
+            Err(_) if !line.ends_with('\n') => break,
+            Err(e) => return Err(corrupt(i + 1, e.to_string())),
+        };
+        if !planned.contains(entry.done.as_path()) {
+            let reason = format!("{} is not in the plan", entry.done.display());
+            return Err(corrupt(i + 1, reason));
+        }
+        done.insert(entry.done);
+    }
+
+    let stale = |reason: String| ResumeError::Stale {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let options = &header.plan.options;
+    if !options.dest.is_dir() {
+        let reason = format!("destination {} is gone", options.dest.display());
+        return Err(stale(reason));
+    }
+    if !header.plan.source_roots.iter().any(|r| r.is_dir()) {
+        return Err(stale("none of its source folders exists".to_string()));
+    }
+    Ok(SavedRun {
+        path: path.to_path_buf(),
+        created_at: header.created_at,
+        plan: header.plan,
+        done,
+    })
+}
+
+/// Resume the run whose state is at `state_path`: the files it had not done are re-checked
+/// (one that changed since is skipped, as when executing a plan) and moved. The result covers
+/// this session; `resumed` counts the files done before. A state file that cannot be used is
+/// an error, and nothing is moved.
+pub fn resume(
+    state_path: &Path,
+    control: &RunControl,
+    progress: Option<ProgressFn>,
+) -> Result<RunResult, RunError> {
+    let saved = load_state(state_path);
+    let options = match &saved {
+        Ok(saved) => saved.plan.options.clone(),
+        Err(_) => Default::default(),
+    };
+    control.set_throttle(options.throttle);
+    with_report(&options, |log, clock| {
+        let progress = Mutex::new(progress);
+        let emit = emitter(&progress);
+        let saved = match saved {
+            Ok(saved) => saved,
+            Err(e) => return Err(setup_failed(&emit, log, e.into())),
+        };
+        let journal = match Journal::reopen(state_path) {
+            Ok(journal) => journal,
+            Err(source) => {
+                let error = RunError::StateUnwritable {
+                    path: state_path.to_path_buf(),
+                    source,
+                };
+                return Err(setup_failed(&emit, log, error));
+            }
+        };
+        log.info(format_args!(
+            "Resuming the run of {}: {} of {} file(s) done",
+            saved.created_at,
+            saved.done.len(),
+            saved.plan.files.len()
+        ));
+        let (rest, resumed) = remaining(saved, &journal, log);
+        let scale = Scale::new(true);
+        let (mut result, bytes) =
+            plan::execute_with(&rest, control, &emit, log, &scale, Some(journal))?;
+        result.resumed = resumed;
+        emit_done(&emit, &scale, clock, &mut result, &bytes);
+        Ok(result)
+    })
+}
+
+/// The plan of what `saved` still has to do. A file whose source is gone but whose planned
+/// destination has its size was moved just before the interruption, too late to be journaled:
+/// it is journaled now and counted as done. Duplicates of files that were moved now copy
+/// destination content. Also returns the number of files done.
+fn remaining(saved: SavedRun, journal: &Journal, log: &RunLog) -> (MovePlan, u64) {
+    let mut done = saved.done;
+    let mut rest = saved.plan;
+    let planned_dest: HashMap<PathBuf, PathBuf> = rest
+        .files
+        .iter()
+        .filter_map(|f| Some((f.source.clone(), f.dest.clone()?)))
+        .collect();
+    rest.files.retain(|f| {
+        if done.contains(&f.source) {
+            return false;
+        }
+        let arrived = f.action == PlannedAction::Move
+            && !mover::long_path(&f.source).exists()
+            && f.dest.as_ref().is_some_and(|d| {
+                fs::metadata(mover::long_path(d)).is_ok_and(|m| m.len() == f.size)
+            });
+        if arrived {
+            log.info(format_args!(
+                "{} was moved before the run stopped",
+                f.source.display()
+            ));
+            journal.done(&f.source, log);
+            done.insert(f.source.clone());
+        }
+        !arrived
+    });
+    for f in &mut rest.files {
+        if f.duplicate_of.as_ref().is_some_and(|o| done.contains(o)) {
+            // Its original is in the destination now, where the plan put it.
+            f.existing = f
+                .duplicate_of
+                .take()
+                .and_then(|o| planned_dest.get(&o).cloned());
+        }
+    }
+    (rest, done.len() as u64)
+}
+
+/// The state file of a run being executed; workers add a line for each file they finish.
+pub(super) struct Journal {
+    path: PathBuf,
+    /// `None` once a write failed; the run goes on without a state to resume from.
+    file: Mutex<Option<File>>,
+}
+
+impl Journal {
+    /// Start the state file of `plan` at its `state_path`, replacing any older one. The plan is
+    /// written to a temporary file first, so a crash never leaves a half-written plan.
+    pub fn create(path: &Path, plan: &MovePlan) -> std::io::Result<Journal> {
+        let header = Header {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            created_at: chrono::Local::now().to_rfc3339(),
+            plan: plan.clone(),
+        };
+        let mut line = serde_json::to_vec(&header).map_err(std::io::Error::other)?;
+        line.push(b'\n');
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        let mut file = File::create(&temp)?;
+        file.write_all(&line)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temp, path)?;
+        Journal::reopen(path)
+    }
+
+    /// Append to an existing state file.
+    fn reopen(path: &Path) -> std::io::Result<Journal> {
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(Journal {
+            path: path.to_path_buf(),
+            file: Mutex::new(Some(file)),
+        })
+    }
+
+    /// Record that the file from `source` is done, whatever became of it.
+    pub fn done(&self, source: &Path, log: &RunLog) {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(f) = file.as_mut() else {
+            return;
+        };
+        let entry = Entry {
+            done: source.to_path_buf(),
+        };
+        let written = serde_json::to_vec(&entry)
+            .map_err(std::io::Error::other)
+            .and_then(|mut line| {
+                line.push(b'\n');
+                f.write_all(&line)
+            });
+        if let Err(e) = written {
+            log.warn(format_args!(
+                "Cannot write run state {}: {}; this run cannot be resumed",
+                self.path.display(),
+                e
+            ));
+            *file = None;
+        }
+    }
+
+    /// The run has ended: remove the state file unless it was cancelled or aborted, in which
+    /// case `result` says where it is.
+    pub fn close(self, result: &mut RunResult, log: &RunLog) {
+        let usable = self
+            .file
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some();
+        if (result.cancelled || result.aborted.is_some()) && usable {
+            log.info(format_args!(
+                "Run state kept in {} to resume from",
+                self.path.display()
+            ));
+            result.state_path = Some(self.path);
+        } else if let Err(e) = fs::remove_file(&self.path) {
+            let warning = format!("Cannot remove run state {}: {}", self.path.display(), e);
+            log.warn(&warning);
+            result.warnings.push(warning);
+        }
+    }
+}
+
+/// Start the state file of `plan` if its options ask for one; a file that cannot be written is
+/// a warning, and the run goes on without it.
+pub(super) fn start(plan: &MovePlan, log: &RunLog) -> (Option<Journal>, Option<String>) {
+    let Some(path) = plan.options.state_path.as_deref() else {
+        return (None, None);
+    };
+    match Journal::create(path, plan) {
+        Ok(journal) => (Some(journal), None),
+        Err(e) => {
+            let warning = format!("Cannot write run state {}: {}", path.display(), e);
+            log.warn(&warning);
+            (None, Some(warning))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{plan as make_plan, run, RunOptions};
+
+    fn options(src: &Path, dest: &Path, state: &Path) -> RunOptions {
+        RunOptions::new(src, dest, "7612 7608 7605").state_path(Some(state.to_path_buf()))
+    }
+
+    #[test]
+    fn test_interrupted_run_resumes_where_it_stopped() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let state = src.path().join("state").join("run.jsonl");
+        for (name, body) in [
+            ("IMG_7605.JPG", "a"),
+            ("IMG_7608.JPG", "bb"),
+            ("IMG_7612.JPG", "ccc"),
+        ] {
+            fs::write(src.path().join(name), body).unwrap();
+        }
+        let plan = make_plan(
+            &options(src.path(), dest.path(), &state),
+            &RunControl::new(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(plan.files.len(), 3);
+
+        // The first file was moved and journaled, the second moved just before a crash.
+        let journal = Journal::create(&state, &plan).unwrap();
+        for f in &plan.files[..2] {
+            fs::rename(&f.source, f.dest.as_ref().unwrap()).unwrap();
+        }
+        journal.done(&plan.files[0].source, &RunLog::new(false));
+        drop(journal);
+        let saved = load_state(&state).unwrap();
+        assert_eq!(saved.summary().done, 1);
+
+        let result = resume(&state, &RunControl::new(), None).unwrap();
+        assert_eq!((result.moved, result.resumed, result.errors), (1, 2, 0));
+        assert!(!state.exists());
+        for f in &plan.files {
+            assert!(f.dest.as_ref().unwrap().exists());
+        }
+
+        // A run that finishes leaves no state behind.
+        fs::write(src.path().join("IMG_7612.JPG"), "new").unwrap();
+        let result = run(
+            &options(src.path(), dest.path(), &state),
+            &RunControl::new(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(result.moved, 1);
+        assert!(!state.exists() && result.state_path.is_none());
+    }
+
+    #[test]
+    fn test_corrupt_or_stale_state_is_not_replayed() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let state = src.path().join("run.jsonl");
+        fs::write(src.path().join("IMG_7612.JPG"), "a").unwrap();
+        let plan = make_plan(
+            &options(src.path(), dest.path(), &state),
+            &RunControl::new(),
+            None,
+        )
+        .unwrap();
+        drop(Journal::create(&state, &plan).unwrap());
+        let header = fs::read_to_string(&state).unwrap();
+        let done = format!(
+            "{{\"done\":{}}}",
+            serde_json::to_string(&plan.files[0].source).unwrap()
+        );
+
+        // A line cut short by a crash is ignored.
+        fs::write(&state, format!("{}{}", header, &done[..10])).unwrap();
+        assert!(load_state(&state).unwrap().done.is_empty());
+
+        fs::write(&state, format!("{}garbage\n{}\n", header, done)).unwrap();
+        let err = load_state(&state).unwrap_err();
+        assert!(
+            matches!(err, ResumeError::Corrupt { line: 2, .. }),
+            "{}",
+            err
+        );
+
+        let other = header.replace(
+            &*plan.files[0].source.to_string_lossy(),
+            "/elsewhere/IMG_1.JPG",
+        );
+        fs::write(&state, format!("{}{}\n", other, done)).unwrap();
+        assert!(matches!(
+            load_state(&state),
+            Err(ResumeError::Corrupt { line: 2, .. })
+        ));
+
+        let newer = header.replacen("\"version\":1", "\"version\":9", 1);
+        fs::write(&state, &newer).unwrap();
+        assert!(matches!(
+            load_state(&state),
+            Err(ResumeError::Unsupported { .. })
+        ));
+
+        fs::write(&state, "{\"not\": \"a state\"}\n").unwrap();
+        assert!(matches!(
+            load_state(&state),
+            Err(ResumeError::Corrupt { line: 1, .. })
+        ));
+
+        fs::write(&state, &header).unwrap();
+        let gone = dest.path().to_path_buf();
+        drop(dest);
+        let err = load_state(&state).unwrap_err();
+        assert!(matches!(err, ResumeError::Stale { .. }), "{}", err);
+        let err = resume(&state, &RunControl::new(), None).unwrap_err();
+        assert!(matches!(
+            err,
+            RunError::StateUnusable(ResumeError::Stale { .. })
+        ));
+        assert!(src.path().join("IMG_7612.JPG").exists() && !gone.exists());
+        assert!(state.exists());
+    }
+}
@@ -0,0 +1,120 @@
+//! Diagnostics of a run: printed when verbose, written to the run's log file, passed to `log`.
+
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub(super) use log::Level;
+
+/// Where a run's messages go. Cheap to clone; clones share the log file.
+#[derive(Clone, Debug, Default)]
+pub(super) struct RunLog {
+    /// Print info and above to the console (stdout; warnings and errors to stderr).
+    console: bool,
+    /// Record everything at debug level, off the console: for a phase whose messages are
+    /// repeated later (planning before a real run).
+    demoted: bool,
+    file: Option<Arc<Mutex<File>>>,
+}
+
+impl RunLog {
+    /// Messages go to the console when `verbose`, and to the `log` crate.
+    pub fn new(verbose: bool) -> RunLog {
+        RunLog {
+            console: verbose,
+            ..Default::default()
+        }
+    }
+
+    /// Also write every message, with a timestamp, to `path`; a directory gets a timestamped
+    /// `framemover-*.log` in it. Returns the file's path.
+    pub fn open_file(&mut self, path: &Path) -> std::io::Result<PathBuf> {
+        let path = if path.is_dir() {
+            let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+            path.join(format!("framemover-{}.log", stamp))
+        } else {
+            path.to_path_buf()
+        };
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        self.file = Some(Arc::new(Mutex::new(file)));
+        Ok(path)
+    }
+
+    /// This log with every message moved down to debug level (see `demoted`).
+    pub fn demoted(&self) -> RunLog {
+        RunLog {
+            demoted: true,
+            ..self.clone()
+        }
+    }
+
+    pub fn log(&self, level: Level, message: impl Display) {
+        let level = if self.demoted { Level::Debug } else { level };
+        if self.console && level <= Level::Info {
+            match level {
+                Level::Info => println!("{}", message),
+                Level::Warn => eprintln!("Warning: {}", message),
+                _ => eprintln!("{}", message),
+            }
+        }
+        if let Some(file) = &self.file {
+            let stamp = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z");
+            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+            // A log that cannot be written must not stop the run.
+            let _ = writeln!(file, "{} {:<5} {}", stamp, level, message);
+        }
+        log::log!(target: "framemover", level, "{}", message);
+    }
+
+    pub fn error(&self, message: impl Display) {
+        self.log(Level::Error, message);
+    }
+
+    pub fn warn(&self, message: impl Display) {
+        self.log(Level::Warn, message);
+    }
+
+    pub fn info(&self, message: impl Display) {
+        self.log(Level::Info, message);
+    }
+
+    pub fn debug(&self, message: impl Display) {
+        self.log(Level::Debug, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_file_gets_every_level() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = RunLog::new(false);
+        let path = log.open_file(dir.path()).unwrap();
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("framemover-"));
+
+        log.info("moved a -> b");
+        log.demoted().warn("would stop");
+        log.error(format_args!("Move error {}", 7));
+
+        let text = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with(" INFO  moved a -> b"));
+        assert!(lines[1].ends_with(" DEBUG would stop"));
+        assert!(lines[2].ends_with(" ERROR Move error 7"));
+    }
+}
@@ -0,0 +1,130 @@
+//! Runs of files picked out of a preview rather than of every match. The run scans and plans
+//! as usual but keeps only the picked files, so duplicates, collisions, routes and the
+//! rejects folder work for them as they do in a whole run.
+
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::runlog::RunLog;
+use super::{RunError, Scanned};
+use crate::mover;
+
+/// The files of `paths`, as the scan of `roots` names them, after checking that each is
+/// still there and in one of the (canonical) source roots.
+pub(super) fn check(roots: &[PathBuf], paths: &[PathBuf]) -> Result<HashSet<PathBuf>, RunError> {
+    if paths.is_empty() {
+        return Err(RunError::EmptySelection);
+    }
+    let mut selected = HashSet::with_capacity(paths.len());
+    for path in paths {
+        let unavailable = |source| RunError::SelectionUnavailable {
+            path: path.clone(),
+            source,
+        };
+        let meta = std::fs::metadata(mover::long_path(path)).map_err(unavailable)?;
+        if !meta.is_file() {
+            return Err(unavailable(io::Error::other("not a file")));
+        }
+        // The folder resolved as the roots are, the name kept: a link is the link the scan
+        // found, not the file it points to.
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(RunError::NotInSources { path: path.clone() });
+        };
+        let parent = dunce::canonicalize(mover::long_path(parent)).map_err(unavailable)?;
+        let file = parent.join(name);
+        if !roots.iter().any(|root| file.starts_with(root)) {
+            return Err(RunError::NotInSources { path: path.clone() });
+        }
+        selected.insert(file);
+    }
+    Ok(selected)
+}
+
+/// Leave only the `selected` files in what the scan found; returns how many of them the scan
+/// did not take (an exclusion glob, a suffix no longer asked for, a skipped link), which stay
+/// where they are.
+pub(super) fn keep(scanned: &mut Scanned, selected: &HashSet<PathBuf>, log: &RunLog) -> u64 {
+    scanned.candidates.retain(|c| selected.contains(&c.path));
+    scanned.excluded.retain(|p| selected.contains(p));
+    scanned.other_types.clear();
+    let found: HashSet<&Path> = scanned
+        .candidates
+        .iter()
+        .map(|c| c.path.as_path())
+        .collect();
+    for path in selected.iter().filter(|p| !found.contains(p.as_path())) {
+        log.debug(format_args!(
+            "Selected {} is not a match of this run; left alone",
+            path.display()
+        ));
+    }
+    (selected.len() - found.len()) as u64
+}
+
+/// The warning about `count` selected files that were not matches.
+pub(super) fn unmatched_warning(count: u64) -> Option<String> {
+    (count > 0).then(|| {
+        format!(
+            "{} selected file(s) no longer match the suffixes, exclusions or link setting and were left alone",
+            count
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::super::{run_selected, RunOptions};
+    use super::*;
+    use crate::control::RunControl;
+
+    #[test]
+    fn test_only_the_selected_files_are_moved() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::create_dir_all(src.path().join("DCIM")).unwrap();
+        for name in [
+            "IMG_7612.JPG",
+            "IMG_7608.JPG",
+            "IMG_7600.JPG",
+            "IMG_7601.JPG",
+        ] {
+            fs::write(src.path().join("DCIM").join(name), name).unwrap();
+        }
+        fs::write(dest.path().join("IMG_7608.JPG"), "IMG_7608.JPG").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612 7608 7600").workers(1);
+        let picked = |name: &str| src.path().join("DCIM").join(name);
+        let selection = [
+            picked("IMG_7612.JPG"),
+            src.path().join("DCIM/./IMG_7608.JPG"),
+            picked("IMG_7601.JPG"),
+        ];
+
+        let result = run_selected(&options, &selection, &RunControl::new(), None).unwrap();
+
+        assert_eq!(
+            (result.matched, result.moved, result.skipped_duplicates),
+            (2, 1, 1)
+        );
+        assert!(dest.path().join("DCIM/IMG_7612.JPG").exists());
+        assert!(picked("IMG_7600.JPG").exists());
+        assert!(picked("IMG_7601.JPG").exists());
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| w.starts_with("1 selected file")));
+
+        let outside = dest.path().join("IMG_7608.JPG");
+        let failed = |paths: &[PathBuf]| {
+            run_selected(&options, paths, &RunControl::new(), None)
+                .unwrap_err()
+                .kind()
+        };
+        assert_eq!(failed(&[outside]), "not_in_sources");
+        assert_eq!(failed(&[picked("IMG_7612.JPG")]), "selection_unavailable");
+        assert_eq!(failed(&[src.path().join("DCIM")]), "selection_unavailable");
+        assert_eq!(failed(&[]), "empty_selection");
+    }
+}
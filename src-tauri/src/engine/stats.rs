@@ -0,0 +1,140 @@
+//! Timing and volume of a run: when it started and finished, how long each phase took, and how
+//! many bytes it moved or found already there.
+
+use std::time::Instant;
+
+/// How long each phase took, in milliseconds. Watch mode adds up its batches (time spent
+/// waiting for new files is in none of them).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseDurations {
+    #[cfg_attr(test, ts(type = "number"))]
+    pub scan_ms: u64,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub index_ms: u64,
+    /// Hashing the matches and deciding what happens to each.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub plan_ms: u64,
+    /// Moving, removing emptied folders included; 0 for a dry-run.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub move_ms: u64,
+    /// The check after moving, when there is one.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub verify_ms: u64,
+}
+
+impl PhaseDurations {
+    pub fn add(&mut self, other: PhaseDurations) {
+        self.scan_ms += other.scan_ms;
+        self.index_ms += other.index_ms;
+        self.plan_ms += other.plan_ms;
+        self.move_ms += other.move_ms;
+        self.verify_ms += other.verify_ms;
+    }
+}
+
+/// Timing and volume of a run, in its result and in the final progress event.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct RunStats {
+    /// Local time with offset (RFC 3339).
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    /// Wall-clock time from start to finish, pauses included.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub duration_ms: u64,
+    /// Bytes of the files moved (dry-run: that would be moved).
+    #[cfg_attr(test, ts(type = "number"))]
+    pub bytes_moved: u64,
+    /// Bytes of the source files skipped as duplicates.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub bytes_skipped_duplicates: u64,
+    /// Executing a saved plan: scanning, indexing and planning are those of the plan.
+    pub phases: PhaseDurations,
+    /// Images found in the destination when the run started.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub dest_indexed: u64,
+    /// Of those, the ones hashed: only files sharing their size with a match are read.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub dest_hashed: u64,
+    /// Of the indexed images, the ones whose hash an earlier run of the session had made, so
+    /// they were not read again (see `IndexCache`).
+    #[cfg_attr(test, ts(type = "number"))]
+    pub dest_reused: u64,
+}
+
+/// When a run started, to stamp its stats with once it ends.
+pub(super) struct RunClock {
+    started_at: chrono::DateTime<chrono::Local>,
+    start: Instant,
+}
+
+impl RunClock {
+    pub fn start() -> RunClock {
+        RunClock {
+            started_at: chrono::Local::now(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Set the start, the finish (now) and the duration in `stats`.
+    pub fn stamp(&self, stats: &mut RunStats) {
+        stats.started_at = Some(self.started_at.to_rfc3339());
+        stats.finished_at = Some(chrono::Local::now().to_rfc3339());
+        stats.duration_ms = self.start.elapsed().as_millis() as u64;
+    }
+}
+
+/// Run `f`, adding the milliseconds it took to `ms`.
+pub(super) fn timed<T>(ms: &mut u64, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let value = f();
+    *ms += start.elapsed().as_millis() as u64;
+    value
+}
+
+/// "850 ms", "12.3 s", "4 min 05 s", "2 h 03 min".
+pub fn human_duration(ms: u64) -> String {
+    let secs = ms / 1000;
+    match ms {
+        0..=999 => format!("{} ms", ms),
+        1000..=59_999 => format!("{:.1} s", ms as f64 / 1000.0),
+        60_000..=3_599_999 => format!("{} min {:02} s", secs / 60, secs % 60),
+        _ => format!("{} h {:02} min", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_human_duration() {
+        assert_eq!(human_duration(850), "850 ms");
+        assert_eq!(human_duration(12_340), "12.3 s");
+        assert_eq!(human_duration(245_000), "4 min 05 s");
+        assert_eq!(human_duration(7_380_000), "2 h 03 min");
+    }
+
+    #[test]
+    fn test_stamp_and_phases_add_up() {
+        let clock = RunClock::start();
+        let mut phases = PhaseDurations::default();
+        let n = timed(&mut phases.plan_ms, || 7);
+        assert_eq!(n, 7);
+        phases.add(PhaseDurations {
+            scan_ms: 5,
+            move_ms: 20,
+            ..Default::default()
+        });
+        assert_eq!((phases.scan_ms, phases.move_ms), (5, 20));
+
+        let mut stats = RunStats::default();
+        clock.stamp(&mut stats);
+        let started = chrono::DateTime::parse_from_rfc3339(stats.started_at.as_deref().unwrap());
+        let finished = chrono::DateTime::parse_from_rfc3339(stats.finished_at.as_deref().unwrap());
+        assert!(started.unwrap() <= finished.unwrap());
+    }
+}
@@ -0,0 +1,244 @@
+//! Undoing a finished run: its `--report` lists where every file went, so it serves as the
+//! run's journal, and [`undo`] moves the files it lists as moved back to where they were found.
+
+use std::path::Path;
+
+use super::report::{read_report, ErrorDetail, ErrorStage, FileAction, ReportError};
+use crate::control::RunControl;
+use crate::mover::{self, DestClaims, MoveOptions, MoveResult, TransferMode};
+
+/// Outcome of [`undo`].
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoResult {
+    pub dry_run: bool,
+    /// Files moved back to where the run found them (or that would be, in a dry run).
+    pub restored: u64,
+    /// Of those, files that had replaced an older destination file (`CollisionMode::Update`)
+    /// that was put back in their place from where the run kept it.
+    pub replaced: u64,
+    /// Files no longer at their destination, and files whose old place is taken again; both
+    /// are left as they are, and are among the `errors`.
+    pub missing: u64,
+    pub occupied: u64,
+    /// Moved files past the report's `MAX_FILE_RECORDS`, which it does not list; they stay.
+    pub not_listed: u64,
+    /// Files not looked at because the undo was cancelled.
+    pub not_attempted: u64,
+    /// Each file not moved back, and why.
+    pub errors: Vec<ErrorDetail>,
+    pub cancelled: bool,
+}
+
+/// Move the files the run of the report at `path` moved back to their source paths, the last
+/// moved first. A file is only put back where nothing is now: one whose old place is taken, or
+/// that is no longer at its destination, is left alone. A file that had replaced an older one
+/// makes way for it again: the older file is renamed back from where the run kept it. Read-only
+/// files are made writable again. Duplicates the run deleted or trashed are not brought back. With `dry_run`, nothing
+/// is moved. Refuses the report of a dry run or of a copy or link mode, whose sources are
+/// still in place.
+pub fn undo(path: &Path, dry_run: bool, control: &RunControl) -> Result<UndoResult, ReportError> {
+    let report = read_report(path)?;
+    if report.options.transfer_mode != TransferMode::Move {
+        return Err(ReportError::NotMoved {
+            path: path.to_path_buf(),
+            mode: report.options.transfer_mode,
+        });
+    }
+    let moved: Vec<_> = report
+        .result
+        .files
+        .iter()
+        .rev()
+        .filter(|f| f.action == FileAction::Moved)
+        .filter_map(|f| Some((f, f.dest.as_deref()?)))
+        .collect();
+    let mut out = UndoResult {
+        dry_run,
+        not_listed: report.result.files_omitted,
+        ..Default::default()
+    };
+    let options = MoveOptions::default();
+    let claims = DestClaims::new(options.normalize_unicode);
+    for (i, &(record, dest)) in moved.iter().enumerate() {
+        if !control.proceed() {
+            out.not_attempted = (moved.len() - i) as u64;
+            out.cancelled = true;
+            break;
+        }
+        let source = record.source.as_path();
+        let problem = if !exists(dest) {
+            out.missing += 1;
+            Some(format!("no longer at {}", dest.display()))
+        } else if exists(source) {
+            out.occupied += 1;
+            Some("a file is at its old place again".to_string())
+        } else if dry_run {
+            None
+        } else {
+            if record.read_only {
+                // Moving it back over the read-only flag fails on Windows.
+                let _ = mover::set_read_only(dest, false);
+            }
+            match mover::move_file(dest, source, "", &options, &claims, control) {
+                Ok(MoveResult::Moved { .. }) => None,
+                Ok(MoveResult::SourceKept { reason, .. }) => Some(format!(
+                    "copied back, but the destination file could not be removed: {}",
+                    reason
+                )),
+                Ok(_) => Some("was not moved back".to_string()),
+                Err(e) => Some(e.to_string()),
+            }
+        };
+        match problem {
+            Some(message) => {
+                let at = if exists(dest) { dest } else { source };
+                out.errors
+                    .push(ErrorDetail::new(Some(at), ErrorStage::Move, message));
+            }
+            None => {
+                out.restored += 1;
+                let Some(replaced) = &record.replaced else {
+                    continue;
+                };
+                match put_back(&replaced.kept_at, dest, dry_run) {
+                    Ok(()) => out.replaced += 1,
+                    Err(message) => out.errors.push(ErrorDetail::new(
+                        Some(&replaced.kept_at),
+                        ErrorStage::Move,
+                        message,
+                    )),
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Rename `kept_at`, where the run kept the file a moved file replaced, back to `dest`, the
+/// place the moved file was just taken away from.
+fn put_back(kept_at: &Path, dest: &Path, dry_run: bool) -> Result<(), String> {
+    if !exists(kept_at) {
+        return Err("the file it replaced is no longer there".to_string());
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if exists(dest) {
+        return Err(format!(
+            "the file it replaced was not put back: {} is taken again",
+            dest.display()
+        ));
+    }
+    std::fs::rename(mover::long_path(kept_at), mover::long_path(dest))
+        .map_err(|e| format!("the file it replaced could not be put back: {}", e))
+}
+
+/// Whether anything (a broken link too) is at `path`.
+fn exists(path: &Path) -> bool {
+    std::fs::symlink_metadata(mover::long_path(path)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{run, RunOptions};
+    use std::fs;
+
+    #[test]
+    fn test_undo_moves_files_back_and_leaves_taken_places() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let report = dest.path().join("report.json");
+        fs::create_dir(src.path().join("day1")).unwrap();
+        fs::write(src.path().join("day1/IMG_7612.JPG"), b"pick").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), b"another").unwrap();
+        fs::write(src.path().join("IMG_7605.JPG"), b"third").unwrap();
+        let options = RunOptions::new(src.path(), dest.path().join("picks"), "7612 7608 7605")
+            .remove_empty_dirs(true, false)
+            .report_path(Some(report.clone()));
+        assert_eq!(run(&options, &RunControl::new(), None).unwrap().moved, 3);
+        // One of them taken out of the archive, another's old place taken again.
+        fs::remove_file(dest.path().join("picks/IMG_7605.JPG")).unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), b"new file").unwrap();
+
+        let dry = undo(&report, true, &RunControl::new()).unwrap();
+        assert_eq!((dry.restored, dry.missing, dry.occupied), (1, 1, 1));
+        assert!(!src.path().join("day1").exists());
+
+        let undone = undo(&report, false, &RunControl::new()).unwrap();
+        assert_eq!(
+            (undone.restored, undone.missing, undone.occupied),
+            (1, 1, 1)
+        );
+        assert_eq!(undone.errors.len(), 2);
+        assert_eq!(
+            fs::read(src.path().join("day1/IMG_7612.JPG")).unwrap(),
+            b"pick"
+        );
+        assert!(!dest.path().join("picks/day1/IMG_7612.JPG").exists());
+        assert_eq!(
+            fs::read(src.path().join("IMG_7608.JPG")).unwrap(),
+            b"new file"
+        );
+        assert_eq!(
+            fs::read(dest.path().join("picks/IMG_7608.JPG")).unwrap(),
+            b"another"
+        );
+    }
+
+    #[test]
+    fn test_undo_puts_replaced_files_back() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let report = dest.path().join("report.json");
+        let archived = dest.path().join("IMG_7612.JPG");
+        fs::write(&archived, b"old edit").unwrap();
+        filetime::set_file_mtime(
+            &archived,
+            filetime::FileTime::from_unix_time(1_500_000_000, 0),
+        )
+        .unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"new edit").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612")
+            .on_collision(mover::CollisionMode::Update)
+            .report_path(Some(report.clone()));
+        let result = run(&options, &RunControl::new(), None).unwrap();
+        assert_eq!(result.replaced, 1);
+        let kept_at = dest.path().join("IMG_7612-1.JPG");
+        assert_eq!(fs::read(&kept_at).unwrap(), b"old edit");
+
+        let dry = undo(&report, true, &RunControl::new()).unwrap();
+        assert_eq!((dry.restored, dry.replaced), (1, 1));
+        assert!(kept_at.exists());
+
+        let undone = undo(&report, false, &RunControl::new()).unwrap();
+        assert_eq!((undone.restored, undone.replaced), (1, 1));
+        assert!(undone.errors.is_empty());
+        assert_eq!(
+            fs::read(src.path().join("IMG_7612.JPG")).unwrap(),
+            b"new edit"
+        );
+        assert_eq!(fs::read(&archived).unwrap(), b"old edit");
+        assert!(!kept_at.exists());
+    }
+
+    #[test]
+    fn test_undo_refuses_copies_and_dry_runs() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let report = dest.path().join("report.json");
+        fs::write(src.path().join("IMG_7612.JPG"), b"pick").unwrap();
+        let options = RunOptions::new(src.path(), dest.path().join("picks"), "7612")
+            .report_path(Some(report.clone()));
+        run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        let err = undo(&report, false, &RunControl::new()).unwrap_err();
+        assert_eq!(err.kind(), "report_dry_run");
+
+        let copy = options.transfer_mode(TransferMode::Copy);
+        run(&copy, &RunControl::new(), None).unwrap();
+        let err = undo(&report, false, &RunControl::new()).unwrap_err();
+        assert_eq!(err.kind(), "report_not_moved");
+        assert!(src.path().join("IMG_7612.JPG").exists());
+    }
+}
@@ -0,0 +1,303 @@
+//! Quick checks of a run's inputs while they are being filled in: the folders and the suffix
+//! text, without scanning and without creating anything.
+
+use std::path::{Path, PathBuf};
+
+use super::{parse_rules, RunOptions};
+use crate::mover::{self, TransferMode};
+
+/// Whether a finding stops a run or only deserves a look.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The run would fail or make no sense; Start should wait until it is fixed.
+    Error,
+    Warning,
+}
+
+/// What [`validate_inputs`] found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    NoSources,
+    SourceMissing,
+    SourceNotDirectory,
+    NoDestination,
+    DestinationNotDirectory,
+    /// The destination is one of the sources.
+    DestinationIsSource,
+    /// The destination lies inside a source, whose next scan finds the moved files again.
+    DestinationInsideSource,
+    /// A source is on another volume: its files are copied and then deleted, not renamed.
+    CrossVolume,
+    NoSuffixes,
+    DestinationNotWritable,
+}
+
+/// One problem with the inputs, for the frontend to show next to them.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Finding {
+    pub severity: Severity,
+    pub kind: FindingKind,
+    pub message: String,
+    /// The folder it is about, as given.
+    pub path: Option<PathBuf>,
+}
+
+impl Finding {
+    fn new(severity: Severity, kind: FindingKind, path: Option<&Path>, message: String) -> Finding {
+        Finding {
+            severity,
+            kind,
+            message,
+            path: path.map(Path::to_path_buf),
+        }
+    }
+}
+
+/// Check the sources, destinations and suffixes of `run_options` as far as can be done at once:
+/// folders that are missing or not folders, a destination that is a source or inside one or
+/// cannot be written, sources on another volume, suffix text without a number. Only metadata
+/// is read; nothing is created (a destination that does not exist yet is checked by the folder
+/// it would be created in), and the write check leaves nothing behind. With a tag to rename in
+/// place, there is no destination to check.
+pub fn validate_inputs(run_options: &RunOptions) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if run_options.sources.is_empty() {
+        findings.push(Finding::new(
+            Severity::Error,
+            FindingKind::NoSources,
+            None,
+            "No source folder selected".to_string(),
+        ));
+    }
+    let mut sources = Vec::new();
+    for source in &run_options.sources {
+        match std::fs::metadata(mover::long_path(source)) {
+            Ok(meta) if meta.is_dir() => sources.push((
+                source,
+                dunce::canonicalize(source).unwrap_or(source.clone()),
+            )),
+            Ok(_) => findings.push(Finding::new(
+                Severity::Error,
+                FindingKind::SourceNotDirectory,
+                Some(source),
+                format!("Source {} is not a folder", source.display()),
+            )),
+            Err(e) => findings.push(Finding::new(
+                Severity::Error,
+                FindingKind::SourceMissing,
+                Some(source),
+                format!("Source {} cannot be used: {}", source.display(), e),
+            )),
+        }
+    }
+    if let Err(e) = parse_rules(run_options) {
+        findings.push(Finding::new(
+            Severity::Error,
+            FindingKind::NoSuffixes,
+            None,
+            format!("No valid suffix numbers: {}", e),
+        ));
+    }
+    if run_options.move_options.tag.is_none() {
+        for dest in run_options.destinations() {
+            check_destination(run_options, dest, &sources, &mut findings);
+        }
+    }
+    findings
+}
+
+fn check_destination(
+    run_options: &RunOptions,
+    dest: &Path,
+    sources: &[(&PathBuf, PathBuf)],
+    findings: &mut Vec<Finding>,
+) {
+    if dest.as_os_str().is_empty() {
+        findings.push(Finding::new(
+            Severity::Error,
+            FindingKind::NoDestination,
+            None,
+            "No destination folder selected".to_string(),
+        ));
+        return;
+    }
+    let Some((existing, resolved)) = resolve(dest) else {
+        findings.push(Finding::new(
+            Severity::Error,
+            FindingKind::DestinationNotWritable,
+            Some(dest),
+            format!(
+                "Destination {} cannot be created: no folder of it exists",
+                dest.display()
+            ),
+        ));
+        return;
+    };
+    if existing == resolved && !existing.is_dir() {
+        findings.push(Finding::new(
+            Severity::Error,
+            FindingKind::DestinationNotDirectory,
+            Some(dest),
+            format!("Destination {} exists and is not a folder", dest.display()),
+        ));
+        return;
+    }
+    let mut overlaps = false;
+    for (source, canonical) in sources {
+        if resolved == *canonical {
+            overlaps = true;
+            findings.push(Finding::new(
+                Severity::Error,
+                FindingKind::DestinationIsSource,
+                Some(dest),
+                format!(
+                    "Destination {} is the source {}",
+                    dest.display(),
+                    source.display()
+                ),
+            ));
+        } else if resolved.starts_with(canonical) {
+            findings.push(Finding::new(
+                Severity::Warning,
+                FindingKind::DestinationInsideSource,
+                Some(dest),
+                format!(
+                    "Destination {} is inside the source {}: a later run from it finds the moved files again",
+                    dest.display(),
+                    source.display()
+                ),
+            ));
+        }
+    }
+    if overlaps {
+        return;
+    }
+    if let Err(e) = mover::probe_writable(&existing) {
+        let message = if existing == resolved {
+            format!("Destination {} is not writable: {}", dest.display(), e)
+        } else {
+            format!(
+                "Destination {} cannot be created in {}: {}",
+                dest.display(),
+                existing.display(),
+                e
+            )
+        };
+        findings.push(Finding::new(
+            Severity::Error,
+            FindingKind::DestinationNotWritable,
+            Some(dest),
+            message,
+        ));
+    }
+    let renames = matches!(
+        run_options.move_options.transfer_mode,
+        TransferMode::Move | TransferMode::Hardlink
+    );
+    for (source, canonical) in sources {
+        if renames && !mover::same_volume(canonical, &existing) {
+            findings.push(Finding::new(
+                Severity::Warning,
+                FindingKind::CrossVolume,
+                Some(source),
+                format!(
+                    "{} is on another volume than {}: its files are copied, checked and then deleted, which takes longer",
+                    source.display(),
+                    dest.display()
+                ),
+            ));
+        }
+    }
+}
+
+/// The nearest folder of `path` that exists (`path` itself when it does exist) and `path`
+/// resolved through it, both canonical; `None` when not even a root of it exists.
+pub(super) fn resolve(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let absolute = std::path::absolute(path).ok()?;
+    let mut existing = absolute.as_path();
+    loop {
+        if let Ok(canonical) = dunce::canonicalize(mover::long_path(existing)) {
+            let rest = absolute.strip_prefix(existing).ok()?;
+            let resolved = if rest.as_os_str().is_empty() {
+                canonical.clone()
+            } else {
+                canonical.join(rest)
+            };
+            return Some((canonical, resolved));
+        }
+        existing = existing.parent()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_inputs_finds_problems_without_creating_anything() {
+        let (src, archive) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let dest = archive.path().join("picks/2024");
+        let kinds = |options: &RunOptions| {
+            validate_inputs(options)
+                .into_iter()
+                .map(|f| (f.severity, f.kind))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kinds(&RunOptions::new(src.path(), &dest, "7612, 7608")), []);
+        assert!(!archive.path().join("picks").exists());
+
+        let file = src.path().join("IMG_7612.JPG");
+        std::fs::write(&file, "x").unwrap();
+        let options = RunOptions::new(src.path(), src.path(), "IMG").sources([
+            src.path().join("gone"),
+            file.clone(),
+            src.path().to_path_buf(),
+        ]);
+        assert_eq!(
+            kinds(&options),
+            [
+                (Severity::Error, FindingKind::SourceMissing),
+                (Severity::Error, FindingKind::SourceNotDirectory),
+                (Severity::Error, FindingKind::NoSuffixes),
+                (Severity::Error, FindingKind::DestinationIsSource),
+            ]
+        );
+        let inside = RunOptions::new(src.path(), src.path().join("picks"), "7612");
+        assert_eq!(
+            kinds(&inside),
+            [(Severity::Warning, FindingKind::DestinationInsideSource)]
+        );
+        assert_eq!(
+            kinds(&RunOptions::new(src.path(), &file, "7612")),
+            [(Severity::Error, FindingKind::DestinationNotDirectory)]
+        );
+        assert_eq!(
+            kinds(&RunOptions::new(src.path(), "", "7612").sources(Vec::<PathBuf>::new())),
+            [
+                (Severity::Error, FindingKind::NoSources),
+                (Severity::Error, FindingKind::NoDestination),
+            ]
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let locked = archive.path().join("locked");
+            std::fs::create_dir(&locked).unwrap();
+            std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o555)).unwrap();
+            // Root writes anyway; the check is only meaningful for other users.
+            if mover::probe_writable(&locked).is_err() {
+                let findings =
+                    validate_inputs(&RunOptions::new(src.path(), locked.join("new"), "7612"));
+                assert_eq!(findings[0].kind, FindingKind::DestinationNotWritable);
+                assert!(findings[0].message.contains("cannot be created in"));
+            }
+            std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        assert_eq!(std::fs::read_dir(archive.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,352 @@
+//! The optional check after moving: every file the run put in the destination is still there
+//! with its size, and with `VerifyMode::Content` still hashes as it did when it was moved.
+
+use std::path::{Path, PathBuf};
+
+use super::meter::ByteMeter;
+use super::percent::{self, Scale};
+use super::report::{read_report, ErrorDetail, ErrorStage, FileAction, ReportError};
+use super::runlog::RunLog;
+use super::{Emit, Phase, ProgressEvent, RunResult, VerifyMode};
+use crate::control::RunControl;
+use crate::hasher;
+use crate::mover;
+
+/// A file the run put in the destination, as the move recorded it.
+#[derive(Clone, Debug)]
+pub(super) struct PlacedFile {
+    pub dest: PathBuf,
+    pub hash: String,
+    pub size: u64,
+}
+
+/// Outcome of the check after moving.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct Verification {
+    /// Whether contents were re-hashed, not only sizes compared.
+    pub content: bool,
+    #[cfg_attr(test, ts(type = "number"))]
+    pub verified: u64,
+    /// Not at the recorded destination.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub missing: u64,
+    /// A different size or content, or a file that could not be read back.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub mismatched: u64,
+    /// Left unchecked because the run was cancelled.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub not_checked: u64,
+}
+
+impl Verification {
+    pub fn failed(&self) -> u64 {
+        self.missing + self.mismatched
+    }
+
+    /// Add a later batch's check (watch mode).
+    pub(super) fn absorb(&mut self, other: &Verification) {
+        self.content |= other.content;
+        self.verified += other.verified;
+        self.missing += other.missing;
+        self.mismatched += other.mismatched;
+        self.not_checked += other.not_checked;
+    }
+}
+
+/// The verification work of `sizes` files: a metadata look each, plus a read with `Content`.
+pub(super) fn work(mode: VerifyMode, sizes: impl Iterator<Item = u64>) -> u64 {
+    match mode {
+        VerifyMode::Off => 0,
+        VerifyMode::Exists => percent::file_work(sizes.map(|_| 0)),
+        VerifyMode::Content => percent::file_work(sizes),
+    }
+}
+
+/// Check `placed` and add the outcome to `result`; each failure is one of its errors, so a run
+/// whose moves all succeeded still fails when its files are not where it put them.
+pub(super) fn verify_placed(
+    mode: VerifyMode,
+    placed: &[PlacedFile],
+    result: &mut RunResult,
+    control: &RunControl,
+    emit: Emit,
+    log: &RunLog,
+    scale: &Scale,
+) {
+    if mode == VerifyMode::Off {
+        return;
+    }
+    let content = mode == VerifyMode::Content;
+    let mut check = Verification {
+        content,
+        ..Default::default()
+    };
+    let mut bytes = ByteMeter::new(if content {
+        placed.iter().map(|p| p.size).sum()
+    } else {
+        0
+    });
+    let total = placed.len() as u64;
+    for (i, file) in placed.iter().enumerate() {
+        if !control.proceed() {
+            check.not_checked = total - i as u64;
+            break;
+        }
+        let done = bytes.fraction().unwrap_or(i as f64 / total.max(1) as f64);
+        emit(ProgressEvent {
+            phase: Phase::Verifying,
+            current_file: Some(file.dest.display().to_string()),
+            scanned: result.scanned,
+            images: result.images,
+            matched: result.matched,
+            moved: result.moved,
+            skipped_duplicates: result.skipped_duplicates,
+            errors: result.errors,
+            percent: scale.percent(Phase::Verifying, done),
+            paused: control.is_paused(),
+            total_bytes: bytes.total,
+            bytes_done: bytes.done,
+            current_index: i as u64 + 1,
+            total_count: total,
+            throughput: bytes.throughput(),
+            eta_seconds: bytes.eta_seconds(),
+            ..Default::default()
+        });
+        let path = mover::long_path(&file.dest);
+        let problem = match std::fs::metadata(&path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                check.missing += 1;
+                Some("missing from its destination".to_string())
+            }
+            Err(e) => {
+                check.mismatched += 1;
+                Some(format!("cannot be checked: {}", e))
+            }
+            Ok(m) if m.len() != file.size => {
+                check.mismatched += 1;
+                Some(format!("has {} bytes, {} were moved", m.len(), file.size))
+            }
+            Ok(_) if !content => None,
+            Ok(_) => match hasher::hash_file_cancellable(&path, control) {
+                Ok(hash) if hash == file.hash => None,
+                Ok(_) => {
+                    check.mismatched += 1;
+                    Some("content differs from what was moved".to_string())
+                }
+                Err(_) if control.is_cancelled() => {
+                    check.not_checked = total - i as u64;
+                    break;
+                }
+                Err(e) => {
+                    check.mismatched += 1;
+                    Some(format!("cannot be read back: {}", e))
+                }
+            },
+        };
+        match problem {
+            Some(message) => {
+                let detail =
+                    ErrorDetail::new(Some(file.dest.as_path()), ErrorStage::Verify, message);
+                log.error(&detail);
+                result.errors += 1;
+                result.error_details.push(detail.clone());
+                emit(ProgressEvent {
+                    phase: Phase::Verifying,
+                    errors: result.errors,
+                    error: Some(detail),
+                    percent: scale.shown(),
+                    ..Default::default()
+                });
+            }
+            None => check.verified += 1,
+        }
+        bytes.add(if content { file.size } else { 0 });
+    }
+    if check.not_checked > 0 {
+        let warning = format!(
+            "Verification stopped: {} moved file(s) not checked",
+            check.not_checked
+        );
+        log.warn(&warning);
+        result.warnings.push(warning);
+    }
+    log.info(format_args!(
+        "Verified {} of {} moved file(s){}: {} missing, {} mismatched",
+        check.verified,
+        total,
+        if content { " by content" } else { "" },
+        check.missing,
+        check.mismatched
+    ));
+    match &mut result.verification {
+        Some(before) => before.absorb(&check),
+        None => result.verification = Some(check),
+    }
+}
+
+/// Outcome of [`verify_report`].
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportCheck {
+    /// Sizes and hashes are not in the report: only whether each file is there.
+    pub check: Verification,
+    /// Each file not at its destination, and why.
+    pub errors: Vec<ErrorDetail>,
+    /// Files past the report's `MAX_FILE_RECORDS`, which it does not list; not checked.
+    pub not_listed: u64,
+}
+
+/// Check after the fact that every file the run of the report at `path` put in a destination
+/// is still there. Read-only; a cancel on `control` leaves the rest `not_checked`.
+pub fn verify_report(path: &Path, control: &RunControl) -> Result<ReportCheck, ReportError> {
+    let report = read_report(path)?;
+    let placed: Vec<&Path> = report
+        .result
+        .files
+        .iter()
+        .filter(|f| matches!(f.action, FileAction::Moved | FileAction::SourceKept))
+        .filter_map(|f| f.dest.as_deref())
+        .collect();
+    let mut out = ReportCheck {
+        not_listed: report.result.files_omitted,
+        ..Default::default()
+    };
+    let check = &mut out.check;
+    for (i, dest) in placed.iter().enumerate() {
+        if !control.proceed() {
+            check.not_checked = (placed.len() - i) as u64;
+            break;
+        }
+        let problem = match std::fs::symlink_metadata(mover::long_path(dest)) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                check.missing += 1;
+                "missing from its destination".to_string()
+            }
+            Err(e) => {
+                check.mismatched += 1;
+                format!("cannot be checked: {}", e)
+            }
+            Ok(m) if m.is_dir() => {
+                check.mismatched += 1;
+                "is a folder now".to_string()
+            }
+            Ok(_) => {
+                check.verified += 1;
+                continue;
+            }
+        };
+        out.errors
+            .push(ErrorDetail::new(Some(dest), ErrorStage::Verify, problem));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{run, RunOptions};
+    use std::fs;
+
+    #[test]
+    fn test_run_verifies_moves_and_catches_missing_or_changed_files() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("IMG_7612.JPG"), b"new pick").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), b"another").unwrap();
+        let options = RunOptions::new(src.path(), dest.path(), "7612 7608");
+        let dry = run(&options.clone().dry_run(true), &RunControl::new(), None).unwrap();
+        assert!(dry.verification.is_none());
+        let result = run(
+            &options.verify(VerifyMode::Exists),
+            &RunControl::new(),
+            None,
+        )
+        .unwrap();
+        let check = result.verification.clone().unwrap();
+        assert_eq!(
+            (check.verified, check.failed(), check.content),
+            (2, 0, false)
+        );
+        assert_eq!(result.errors, 0);
+
+        let kept = dest.path().join("IMG_7612.JPG");
+        let changed = dest.path().join("IMG_7608.JPG");
+        let placed = [
+            PlacedFile {
+                dest: kept.clone(),
+                hash: hasher::hash_file(&kept).unwrap(),
+                size: 8,
+            },
+            PlacedFile {
+                hash: hasher::hash_file(&changed).unwrap(),
+                dest: changed.clone(),
+                size: 7,
+            },
+            PlacedFile {
+                dest: dest.path().join("IMG_7600.JPG"),
+                hash: String::new(),
+                size: 3,
+            },
+        ];
+        fs::write(&changed, b"ANOTHER").unwrap();
+        let mut result = RunResult::default();
+        let scale = Scale::new(true);
+        verify_placed(
+            VerifyMode::Content,
+            &placed,
+            &mut result,
+            &RunControl::new(),
+            &|_| {},
+            &RunLog::new(false),
+            &scale,
+        );
+        let check = result.verification.unwrap();
+        assert_eq!((check.verified, check.mismatched, check.missing), (1, 1, 1));
+        assert!(check.content);
+        assert_eq!(result.errors, 2);
+        assert!(result
+            .error_details
+            .iter()
+            .all(|d| d.stage == ErrorStage::Verify));
+        assert_eq!(
+            result.error_details[0].path.as_deref(),
+            Some(changed.as_path())
+        );
+    }
+
+    #[test]
+    fn test_verify_report_finds_files_gone_since() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let report = dest.path().join("report.json");
+        fs::write(src.path().join("IMG_7612.JPG"), b"pick").unwrap();
+        fs::write(src.path().join("IMG_7608.JPG"), b"another").unwrap();
+        let options = RunOptions::new(src.path(), dest.path().join("picks"), "7612 7608");
+        run(
+            &options.clone().report_path(Some(report.clone())),
+            &RunControl::new(),
+            None,
+        )
+        .unwrap();
+
+        let check = verify_report(&report, &RunControl::new()).unwrap();
+        assert_eq!((check.check.verified, check.check.failed()), (2, 0));
+        let gone = dest.path().join("picks/IMG_7608.JPG");
+        fs::remove_file(&gone).unwrap();
+        let check = verify_report(&report, &RunControl::new()).unwrap();
+        assert_eq!((check.check.verified, check.check.missing), (1, 1));
+        assert_eq!(check.errors[0].path.as_deref(), Some(gone.as_path()));
+
+        let dry = dest.path().join("dry.json");
+        let options = options.dry_run(true).report_path(Some(dry.clone()));
+        run(&options, &RunControl::new(), None).unwrap();
+        let err = verify_report(&dry, &RunControl::new()).unwrap_err();
+        assert_eq!(err.kind(), "report_dry_run");
+        let notes = src.path().join("notes.txt");
+        fs::write(&notes, "not a report").unwrap();
+        let err = verify_report(&notes, &RunControl::new()).unwrap_err();
+        assert_eq!(err.kind(), "report_invalid");
+    }
+}
@@ -0,0 +1,90 @@
+//! What the GUI shows next to a destination before a run: the room on its volume, the
+//! volume's name, and whether a source shares it, which makes a move a rename rather than a
+//! copy.
+
+use std::path::{Path, PathBuf};
+
+use super::validate::resolve;
+use crate::mover;
+
+/// Error for a path whose volume cannot be found.
+#[derive(Debug, thiserror::Error)]
+pub enum VolumeError {
+    #[error("Neither {} nor any folder above it exists", path.display())]
+    NotFound { path: PathBuf },
+}
+
+/// The volume a folder is on, as [`volume_info`] finds it.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeInfo {
+    /// The folder the figures are of: the given path, or the nearest folder above it that
+    /// exists when it is to be created.
+    pub folder: PathBuf,
+    pub mount_point: Option<PathBuf>,
+    /// The volume's label on Windows, elsewhere the last folder of its mount point; none for
+    /// the root.
+    pub name: Option<String>,
+    pub filesystem: Option<String>,
+    /// None when the system does not say.
+    pub free_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+    /// Whether the other path asked about is on this volume; none without one, or when
+    /// neither it nor a folder above it exists.
+    pub same_volume: Option<bool>,
+}
+
+/// The volume holding `path`, which need not exist yet (a destination to be created is on the
+/// volume of the nearest folder above it), and whether `other` shares it. Reads only
+/// metadata; uses the same calls as the run's pre-flight check.
+pub fn volume_info(path: &Path, other: Option<&Path>) -> Result<VolumeInfo, VolumeError> {
+    let (folder, _) = resolve(path).ok_or_else(|| VolumeError::NotFound {
+        path: path.to_path_buf(),
+    })?;
+    let long = mover::long_path(&folder);
+    let space = mover::volume_space(&long);
+    let label = mover::volume_label(&long);
+    let same_volume = other
+        .and_then(resolve)
+        .map(|(other, _)| mover::same_volume(&long, &mover::long_path(&other)));
+    Ok(VolumeInfo {
+        folder,
+        mount_point: label.mount_point,
+        name: label.name,
+        filesystem: label.filesystem,
+        free_bytes: space.map(|s| s.available),
+        total_bytes: space.map(|s| s.total),
+        same_volume,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_volume_info_of_a_folder_to_be_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let info = volume_info(&dir.path().join("picks/2024"), Some(dir.path())).unwrap();
+        assert_eq!(info.folder, dunce::canonicalize(dir.path()).unwrap());
+        assert_eq!(info.same_volume, Some(true));
+        assert!(!dir.path().join("picks").exists());
+        #[cfg(unix)]
+        {
+            let (free, total) = (info.free_bytes.unwrap(), info.total_bytes.unwrap());
+            assert!(total > 0 && free <= total);
+        }
+        #[cfg(target_os = "linux")]
+        {
+            assert!(info.filesystem.is_some());
+            assert!(info.folder.starts_with(info.mount_point.unwrap()));
+            assert_eq!(
+                mover::unescape_mount_field(r"/media/ana/My\040Card"),
+                "/media/ana/My Card"
+            );
+        }
+        let other_missing = volume_info(dir.path(), Some(Path::new("relative/gone")));
+        assert!(other_missing.unwrap().same_volume.is_some());
+        assert_eq!(volume_info(dir.path(), None).unwrap().same_volume, None);
+    }
+}
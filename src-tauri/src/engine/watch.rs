@@ -0,0 +1,613 @@
+//! Watch mode: keep moving matching files as they appear under the source roots.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use super::cache;
+use super::index::DestIndex;
+use super::meter::ByteMeter;
+use super::percent::{self, Scale};
+use super::plan::{
+    estimate_work, execute_batch, plan_batch, planning_pool, revise_moving_work, worker_count,
+};
+use super::report::{self, SuffixCount};
+use super::runlog::RunLog;
+use super::stats::{timed, PhaseDurations, RunClock};
+use super::verify;
+use super::{
+    check_destination, clean_up, count_suffixes, create_destinations, emit_done, emitter,
+    index_destination, log_excluded, pool, preflight_failed, scan_sources, set_up, setup_failed,
+    take_first, MatchBy, Phase, ProgressEvent, ProgressFn, RunError, RunOptions, RunResult,
+    RunStats, SymlinkPolicy,
+};
+use crate::control::RunControl;
+use crate::mover;
+use crate::scanner::{self, ImageEntry};
+
+/// How long a file must keep its size, with no events for it, before it is moved.
+const SETTLE: Duration = Duration::from_secs(1);
+
+/// How often pending files are checked when no events arrive.
+const TICK: Duration = Duration::from_millis(250);
+
+/// Files named in filesystem events, waiting for whoever writes them to finish.
+#[derive(Debug, Default)]
+struct Pending {
+    /// Size at the last check and when the file last changed.
+    files: HashMap<PathBuf, (Option<u64>, Instant)>,
+}
+
+impl Pending {
+    /// `path` was written to (or appeared); it has to settle again.
+    fn touch(&mut self, path: PathBuf, now: Instant) {
+        self.files.entry(path).or_insert((None, now)).1 = now;
+    }
+
+    /// Take the files whose size stayed the same for `SETTLE`, sorted. `size_of` returns `None`
+    /// for files that are gone; they are dropped quietly.
+    fn take_settled(
+        &mut self,
+        now: Instant,
+        size_of: impl Fn(&Path) -> Option<u64>,
+    ) -> Vec<PathBuf> {
+        let mut settled = Vec::new();
+        self.files.retain(|path, (size, since)| {
+            let Some(current) = size_of(path) else {
+                return false;
+            };
+            if *size != Some(current) {
+                *size = Some(current);
+                *since = now;
+                true
+            } else if now.duration_since(*since) >= SETTLE {
+                settled.push(path.clone());
+                false
+            } else {
+                true
+            }
+        });
+        settled.sort();
+        settled
+    }
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path)
+        .ok()
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+}
+
+/// Size and modification time, to tell a file that was handled from a new one under its name.
+fn stamp(path: &Path) -> Option<(u64, Option<SystemTime>)> {
+    mover::file_stamp(path).ok()
+}
+
+fn watching_event(total: &RunResult, paused: bool) -> ProgressEvent {
+    ProgressEvent {
+        phase: Phase::Watching,
+        scanned: total.scanned,
+        images: total.images,
+        matched: total.matched,
+        moved: total.moved,
+        skipped_duplicates: total.skipped_duplicates,
+        rejected: total.rejected,
+        replaced: total.replaced,
+        skipped_newer: total.skipped_newer,
+        tagged: total.tagged,
+        read_only: total.read_only,
+        errors: total.errors,
+        percent: 100.0,
+        paused,
+        ..Default::default()
+    }
+}
+
+/// Move what already matches, then keep watching the source roots and move new matching files
+/// once their size has settled, until `control` is cancelled. The destination index is built
+/// once and kept up to date, so later batches are deduplicated against everything before them.
+/// `on_batch` gets each batch's result; the returned result covers the whole session, and the
+/// report (with `report_path` set) is written when it ends. Fails as [`run`](super::run) does
+/// when the session could not start, or when the sources cannot be watched.
+pub fn watch(
+    run_options: &RunOptions,
+    control: &RunControl,
+    progress: Option<ProgressFn>,
+    on_batch: &mut dyn FnMut(&RunResult),
+) -> Result<RunResult, RunError> {
+    control.set_throttle(run_options.throttle);
+    super::with_report(run_options, |log, clock| {
+        watch_inner(run_options, control, progress, log, clock, on_batch)
+    })
+}
+
+fn watch_inner(
+    run_options: &RunOptions,
+    control: &RunControl,
+    progress: Option<ProgressFn>,
+    log: &RunLog,
+    clock: &RunClock,
+    on_batch: &mut dyn FnMut(&RunResult),
+) -> Result<RunResult, RunError> {
+    let dry_run = run_options.dry_run;
+    let progress = Mutex::new(progress);
+    let emit = emitter(&progress);
+
+    let setup = match set_up(run_options) {
+        Ok(s) => s,
+        Err(error) => return Err(setup_failed(&emit, log, error)),
+    };
+    if !dry_run {
+        create_destinations(run_options).map_err(|error| setup_failed(&emit, log, error))?;
+    }
+
+    // Start watching before the first scan so files written meanwhile are not missed.
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = match notify::recommended_watcher(tx) {
+        Ok(w) => w,
+        Err(e) => {
+            let error = RunError::WatcherUnavailable {
+                reason: e.to_string(),
+            };
+            return Err(setup_failed(&emit, log, error));
+        }
+    };
+    for root in &setup.source_roots {
+        if let Err(e) = watcher.watch(root, RecursiveMode::Recursive) {
+            let error = RunError::WatchFailed {
+                path: root.clone(),
+                reason: e.to_string(),
+            };
+            return Err(setup_failed(&emit, log, error));
+        }
+    }
+
+    let exclude = &run_options.exclude_globs;
+    let by_metadata = run_options.match_by == MatchBy::Metadata;
+    let mut phases = PhaseDurations::default();
+    let scanned = timed(&mut phases.scan_ms, || {
+        scan_sources(&setup, run_options, control, &emit, log)
+    });
+    let scanned = match scanned {
+        Ok(found) => found,
+        Err(error) => return Err(setup_failed(&emit, log, error)),
+    };
+    // Suffixes missing now may still turn up among the new files.
+    let (suffixes, other_type_extensions, scan_warnings) =
+        count_suffixes(&setup, &scanned, &emit, log);
+    let skipped_symlinks = scanned.skipped_symlinks;
+    let link_warnings = scanned.link_warnings;
+    let broken_links = link_warnings.len() as u64;
+    let (scan, candidates, excluded) = (scanned.count, scanned.candidates, scanned.excluded);
+    // Only the first pass is checked; later batches are small and fail file by file.
+    let (preflight, preflight_warning) =
+        match check_destination(run_options, log, &setup.source_roots, &candidates) {
+            Ok(found) => found,
+            Err((found, e)) => return Ok(preflight_failed(&emit, log, found, e)),
+        };
+    let scale = Scale::new(!dry_run);
+    scale.set_work(
+        Phase::ScanningSource,
+        candidates.len() as u64 * percent::SCAN_COST,
+    );
+    let index = timed(&mut phases.index_ms, || {
+        index_destination(
+            run_options,
+            log,
+            scan,
+            candidates.len() as u64,
+            control,
+            &emit,
+            &scale,
+        )
+    });
+
+    let options = &run_options.move_options;
+    let workers = worker_count(run_options, &setup);
+    // Each batch is planned against everything before it, then executed unless dry-run.
+    let mut planner = planning_pool(run_options, &setup, control, &emit, log, index, &scale);
+    planner.skip_vanished = true;
+    planner.scan = scan;
+    let mut pool = pool::Pool::new(run_options, &setup, control, &emit, log, &scale, Vec::new());
+    pool.skip_vanished = true;
+    pool.scan = scan;
+    // A batch cancelled or aborted (see `Pool::aborted`) ends the session.
+    let stopped = || {
+        planner.cancelled.load(Ordering::Relaxed)
+            || pool.cancelled.load(Ordering::Relaxed)
+            || pool.aborted.load(Ordering::Relaxed)
+    };
+    // Events for a destination (when it lies inside a source) are our own moves.
+    let dest_roots: Vec<PathBuf> = run_options
+        .destinations()
+        .map(|d| dunce::canonicalize(mover::long_path(d)).unwrap_or_else(|_| d.to_path_buf()))
+        .collect();
+
+    if let Some(warning) = &preflight_warning {
+        log.warn(warning);
+    }
+    let mut total = RunResult {
+        scanned: scan.files,
+        images: scan.images,
+        numbered: scan.numbered,
+        unnumbered: scan.unnumbered,
+        dry_run,
+        warnings: link_warnings
+            .into_iter()
+            .chain(scan_warnings)
+            .chain(preflight_warning)
+            .collect(),
+        // Every requested suffix, at zero; each batch adds its files. Matches of other file
+        // types are those of the first scan.
+        suffixes: suffixes
+            .iter()
+            .map(|s| SuffixCount {
+                suffix: s.suffix,
+                other_types: s.other_types,
+                ..Default::default()
+            })
+            .collect(),
+        other_type_extensions,
+        preflight: Some(preflight.clone()),
+        excluded: excluded.len() as u64,
+        skipped_symlinks,
+        broken_links,
+        stats: RunStats {
+            phases,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut would_remove = HashSet::new();
+    let mut last_bytes = ByteMeter::default();
+    let mut first_batch = true;
+    let mut batch = |entries: &[ImageEntry]| {
+        // The first pass continues the bar of scanning and indexing; later ones start over.
+        if !std::mem::take(&mut first_batch) {
+            scale.restart();
+        }
+        let roots = &setup.source_roots;
+        estimate_work(
+            &scale,
+            run_options,
+            &preflight,
+            roots,
+            entries,
+            &planner.dest,
+        );
+        let mut phases = PhaseDurations::default();
+        let (files, preview) = timed(&mut phases.plan_ms, || {
+            plan_batch(&planner, workers, entries)
+        });
+        let mut tally = if dry_run {
+            preview
+        } else if planner.cancelled.load(Ordering::Relaxed) {
+            pool::Tally {
+                matched: preview.matched,
+                cancelled: true,
+                ..Default::default()
+            }
+        } else {
+            revise_moving_work(&scale, run_options, &preflight, roots, &files);
+            timed(&mut phases.move_ms, || {
+                execute_batch(&pool, workers, &files, None)
+            })
+        };
+        would_remove.extend(std::mem::take(&mut tally.would_remove));
+        last_bytes = std::mem::take(&mut tally.bytes);
+        let by_suffix = std::mem::take(&mut tally.by_suffix);
+        let placed = std::mem::take(&mut tally.placed);
+        let mut result = RunResult::from_tally(tally, dry_run).finish(&setup.source_roots);
+        if !dry_run && !result.cancelled && result.aborted.is_none() {
+            timed(&mut phases.verify_ms, || {
+                let mode = run_options.verify;
+                verify::verify_placed(mode, &placed, &mut result, control, &emit, log, &scale)
+            });
+        }
+        result.stats.phases = phases;
+        let matches = entries.iter().filter(|e| setup.is_match(e));
+        result.suffixes = report::suffix_table(&setup.suffixes, matches.map(|e| e.suffix));
+        report::add_suffix_counts(&mut result.suffixes, by_suffix.into_values());
+        on_batch(&result);
+        result
+    };
+
+    // Files still being written wait with the new ones until they are `min_age` old, instead of
+    // being skipped as busy.
+    let min_age = Some(Duration::from_secs(run_options.min_age)).filter(|d| !d.is_zero());
+    let busy = |path: &Path| {
+        min_age.is_some_and(|age| pool::recently_modified(stamp(path).and_then(|s| s.1), age))
+    };
+    let (young, mut candidates): (Vec<ImageEntry>, Vec<ImageEntry>) =
+        candidates.into_iter().partition(|c| busy(&c.path));
+    // With a limit, the session ends once that many files were taken.
+    let allowance = |total: &RunResult| {
+        run_options
+            .file_limit()
+            .map(|n| n.saturating_sub(total.matched))
+    };
+    total.over_limit += take_first(&setup, &mut candidates, allowance(&total), log);
+    if !young.is_empty() {
+        log.info(format_args!(
+            "{} file(s) still being written; they are moved once unchanged for {} s",
+            young.len(),
+            run_options.min_age
+        ));
+    }
+
+    // Copy and link modes leave the sources in place; a file is only taken again once it changes.
+    // Excluded files are recorded the same way, so each is counted once.
+    let mut handled: HashMap<PathBuf, (u64, Option<SystemTime>)> = candidates
+        .iter()
+        .map(|c| &c.path)
+        .chain(&excluded)
+        .filter_map(|path| Some((path.clone(), stamp(path)?)))
+        .collect();
+    if control.is_cancelled() {
+        // The other types are in the total already.
+        let suffixes = suffixes
+            .into_iter()
+            .map(|s| SuffixCount {
+                other_types: 0,
+                ..s
+            })
+            .collect();
+        total.absorb(RunResult {
+            suffixes,
+            ..RunResult::cancelled_before(&setup, &candidates, scan)
+        });
+    } else if !candidates.is_empty() {
+        total.absorb(batch(&candidates));
+    }
+
+    log.info("Watching for new files (stop with Ctrl-C)");
+    emit(watching_event(&total, control.is_paused()));
+    let mut pending = Pending::default();
+    // With a rejects folder, new images that match no suffix go there.
+    let rejects = setup.rejects();
+    for entry in young {
+        pending.touch(entry.path, Instant::now());
+    }
+    let mut was_paused = false;
+    while !control.is_cancelled() && !stopped() && allowance(&total) != Some(0) {
+        let mut note = |event: notify::Result<notify::Event>| match event {
+            Ok(event) => {
+                if !matches!(
+                    event.kind,
+                    EventKind::Any | EventKind::Create(_) | EventKind::Modify(_)
+                ) {
+                    return;
+                }
+                let now = Instant::now();
+                for path in event.paths {
+                    if dest_roots.iter().any(|d| path.starts_with(d)) {
+                        continue;
+                    }
+                    if path.is_dir() {
+                        // A folder copied in at once: its files may predate its watch.
+                        // Excluded files are left to the check below, which counts each once.
+                        let found = scanner::scan_source_for_suffixes(
+                            &path,
+                            &setup.suffixes,
+                            exclude,
+                            rejects.is_some(),
+                            options.normalize_unicode,
+                            by_metadata,
+                            |_| true,
+                        );
+                        if let Ok(found) = found {
+                            let excluded = found.excluded.into_iter().map(|(p, _)| p);
+                            let images = found.entries.into_iter().chain(found.unmatched);
+                            for path in images.map(|e| e.path).chain(excluded) {
+                                pending.touch(path, now);
+                            }
+                        }
+                    } else {
+                        pending.touch(path, now);
+                    }
+                }
+            }
+            Err(e) => {
+                let warning = format!("Watch error: {}", e);
+                log.warn(&warning);
+                emit(ProgressEvent {
+                    warning: Some(warning.clone()),
+                    ..watching_event(&total, control.is_paused())
+                });
+                total.warnings.push(warning);
+            }
+        };
+        match rx.recv_timeout(TICK) {
+            Ok(event) => {
+                note(event);
+                while let Ok(event) = rx.try_recv() {
+                    note(event);
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        let paused = control.is_paused();
+        if paused != was_paused {
+            was_paused = paused;
+            emit(watching_event(&total, paused));
+        }
+        if paused {
+            continue;
+        }
+
+        let now = Instant::now();
+        let (young, settled): (Vec<PathBuf>, Vec<PathBuf>) = pending
+            .take_settled(now, file_size)
+            .into_iter()
+            .partition(|path| busy(path));
+        for path in young {
+            pending.touch(path, now);
+        }
+        let mut entries: Vec<ImageEntry> = settled
+            .into_iter()
+            .filter_map(|path| {
+                let root = setup
+                    .source_roots
+                    .iter()
+                    .position(|r| path.starts_with(r))?;
+                let name = scanner::match_path(&path, options.normalize_unicode);
+                let found = scanner::match_file(
+                    &path,
+                    &setup.suffixes,
+                    options.normalize_unicode,
+                    by_metadata,
+                );
+                let entry = match found {
+                    Some(entry) => ImageEntry {
+                        route: setup.route(entry.suffix),
+                        ..entry
+                    },
+                    None => ImageEntry {
+                        route: rejects?,
+                        ..scanner::unmatched_file(&path)?
+                    },
+                };
+                let stamp = stamp(&path)?;
+                if handled.get(&path) == Some(&stamp) {
+                    return None;
+                }
+                if let Some(glob) = exclude.matching(&name) {
+                    log_excluded(log, &path, glob);
+                    total.excluded += 1;
+                    handled.insert(path, stamp);
+                    return None;
+                }
+                if entry.symlink && options.symlinks == SymlinkPolicy::Skip {
+                    total.skipped_symlinks += 1;
+                    handled.insert(path, stamp);
+                    return None;
+                }
+                handled.insert(path, stamp);
+                Some(ImageEntry { root, ..entry })
+            })
+            .collect();
+        total.over_limit += take_first(&setup, &mut entries, allowance(&total), log);
+        if !entries.is_empty() {
+            log.info(format_args!("{} new file(s)", entries.len()));
+            total.absorb(batch(&entries));
+            emit(watching_event(&total, control.is_paused()));
+        }
+    }
+    drop(watcher);
+    if allowance(&total) == Some(0) {
+        log.info("Stopped watching: the limit of files to take was reached");
+    }
+
+    if options.remove_empty_dirs && !stopped() {
+        clean_up(
+            run_options,
+            &setup.source_roots,
+            &would_remove,
+            dry_run,
+            log,
+            &mut total,
+        );
+    }
+    total.stats.dest_indexed = planner.dest.iter().map(DestIndex::files).sum();
+    total.stats.dest_hashed = planner.dest.iter().map(DestIndex::hashed).sum();
+    total.stats.dest_reused = planner.dest.iter().map(DestIndex::reused).sum();
+    cache::keep_indexes(run_options, &planner.dest);
+    emit_done(&emit, &scale, clock, &mut total, &last_bytes);
+    Ok(total.finish(&setup.source_roots))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_pending_files_wait_until_settled() {
+        let t0 = Instant::now();
+        let mut pending = Pending::default();
+        let growing = PathBuf::from("/cards/A/IMG_7612.JPG");
+        let gone = PathBuf::from("/cards/A/IMG_7608.JPG");
+        pending.touch(growing.clone(), t0);
+        pending.touch(gone.clone(), t0);
+
+        let sizes = |size: u64| {
+            let growing = growing.clone();
+            move |p: &Path| (p == growing).then_some(size)
+        };
+        // First look records the size; the vanished file is dropped.
+        assert!(pending.take_settled(t0, sizes(10)).is_empty());
+        assert_eq!(pending.files.len(), 1);
+        // Still growing: the clock starts again.
+        assert!(pending
+            .take_settled(t0 + Duration::from_millis(900), sizes(20))
+            .is_empty());
+        assert!(pending
+            .take_settled(t0 + Duration::from_millis(1500), sizes(20))
+            .is_empty());
+        // A new event for it restarts the wait as well.
+        pending.touch(growing.clone(), t0 + Duration::from_millis(1600));
+        assert!(pending
+            .take_settled(t0 + Duration::from_millis(2000), sizes(20))
+            .is_empty());
+        assert_eq!(
+            pending.take_settled(t0 + Duration::from_millis(2700), sizes(20)),
+            [growing]
+        );
+        assert!(pending.files.is_empty());
+    }
+
+    #[test]
+    fn test_watch_moves_new_files_and_stops_on_cancel() {
+        let source = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(source.path().join("IMG_7612.JPG"), b"first").unwrap();
+        let control = RunControl::new();
+        let watching = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let seen = watching.clone();
+        let progress: ProgressFn = Box::new(move |ev: ProgressEvent| {
+            if matches!(ev.phase, Phase::Watching) {
+                seen.store(true, Ordering::SeqCst);
+            }
+        });
+        let options = RunOptions::new(source.path(), dest.path(), "7612");
+
+        let mut batches = Vec::new();
+        let result = std::thread::scope(|s| {
+            let run = s.spawn(|| {
+                watch(&options, &control, Some(progress), &mut |b: &RunResult| {
+                    batches.push(b.moved)
+                })
+                .unwrap()
+            });
+            let wait_for = |done: &dyn Fn() -> bool| {
+                let deadline = Instant::now() + Duration::from_secs(20);
+                while !done() && Instant::now() < deadline {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                assert!(done());
+            };
+            wait_for(&|| watching.load(Ordering::SeqCst));
+            // One file that stays, one that is gone again before it settles.
+            fs::create_dir(source.path().join("later")).unwrap();
+            fs::write(source.path().join("later/IMG_17612.JPG"), b"second").unwrap();
+            fs::write(source.path().join("IMG_27612.JPG"), b"temporary").unwrap();
+            fs::remove_file(source.path().join("IMG_27612.JPG")).unwrap();
+            wait_for(&|| dest.path().join("later/IMG_17612.JPG").exists());
+            control.cancel();
+            run.join().unwrap()
+        });
+
+        assert_eq!(batches, [1, 1]);
+        assert_eq!(result.moved, 2);
+        assert_eq!(result.errors, 0);
+        assert!(dest.path().join("IMG_7612.JPG").exists());
+        assert!(!source.path().join("later/IMG_17612.JPG").exists());
+    }
+}
@@ -0,0 +1,153 @@
+//! Exclusion globs: file name patterns like "*_thumb.jpg" that keep matching files out of a run.
+
+use std::path::Path;
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+/// Error for an exclusion pattern that cannot be used.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error("invalid exclude pattern \"{pattern}\": {reason}")]
+pub struct GlobError {
+    pub pattern: String,
+    pub reason: String,
+}
+
+/// Patterns matched against the file name alone (not its folders), ignoring case, so
+/// "*_thumb.jpg" also leaves out "IMG_7612_THUMB.JPG".
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct ExcludeGlobs {
+    patterns: Vec<String>,
+    set: GlobSet,
+    /// The pattern each glob in `set` was built from: a pattern with decomposed characters
+    /// has a second glob in normalization form C, for names matched in that form.
+    owners: Vec<usize>,
+}
+
+impl ExcludeGlobs {
+    /// Compile `patterns`; blank ones are dropped. The first invalid one is the error.
+    pub fn new(patterns: impl IntoIterator<Item = impl Into<String>>) -> Result<Self, GlobError> {
+        let patterns: Vec<String> = patterns
+            .into_iter()
+            .map(Into::into)
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect();
+        let mut builder = GlobSetBuilder::new();
+        let mut owners = Vec::new();
+        for (owner, pattern) in patterns.iter().enumerate() {
+            if pattern.contains(['/', '\\']) {
+                return Err(GlobError {
+                    pattern: pattern.clone(),
+                    reason: "patterns match file names and must not contain path separators"
+                        .to_string(),
+                });
+            }
+            let composed = crate::mover::nfc(pattern);
+            let forms = if composed == pattern.as_str() {
+                vec![pattern.as_str()]
+            } else {
+                vec![pattern.as_str(), &composed]
+            };
+            for form in forms {
+                let glob = GlobBuilder::new(form)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| GlobError {
+                        pattern: pattern.clone(),
+                        reason: e.kind().to_string(),
+                    })?;
+                builder.add(glob);
+                owners.push(owner);
+            }
+        }
+        let set = builder.build().map_err(|e| GlobError {
+            pattern: patterns.join(", "),
+            reason: e.kind().to_string(),
+        })?;
+        Ok(ExcludeGlobs {
+            patterns,
+            set,
+            owners,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// The first pattern the name of `path` matches, if any.
+    pub fn matching(&self, path: &Path) -> Option<&str> {
+        if self.is_empty() {
+            return None;
+        }
+        let name = path.file_name()?;
+        let matches = self.set.matches(name).into_iter();
+        let first = matches.map(|i| self.owners[i]).min()?;
+        Some(&self.patterns[first])
+    }
+}
+
+impl TryFrom<Vec<String>> for ExcludeGlobs {
+    type Error = GlobError;
+
+    fn try_from(patterns: Vec<String>) -> Result<Self, Self::Error> {
+        ExcludeGlobs::new(patterns)
+    }
+}
+
+impl From<ExcludeGlobs> for Vec<String> {
+    fn from(value: ExcludeGlobs) -> Vec<String> {
+        value.patterns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_file_names_ignoring_case() {
+        let globs = ExcludeGlobs::new(["*_thumb.jpg", " *-preview.* ", ""]).unwrap();
+        assert_eq!(globs.patterns(), ["*_thumb.jpg", "*-preview.*"]);
+
+        let m = |p: &str| globs.matching(Path::new(p));
+        assert_eq!(m("/exports/IMG_7612_thumb.jpg"), Some("*_thumb.jpg"));
+        assert_eq!(m("/exports/IMG_7612_THUMB.JPG"), Some("*_thumb.jpg"));
+        assert_eq!(m("/exports/IMG_7612-preview.png"), Some("*-preview.*"));
+        assert_eq!(m("/exports/IMG_7612.jpg"), None);
+        // Folders are not part of the match.
+        assert_eq!(m("/x_thumb.jpg/IMG_7612.jpg"), None);
+        assert_eq!(ExcludeGlobs::default().matching(Path::new("a.jpg")), None);
+    }
+
+    #[test]
+    fn test_decomposed_pattern_matches_composed_names() {
+        // Typed on macOS: "e" followed by a combining acute accent.
+        let globs = ExcludeGlobs::new(["cafe\u{301}_*"]).unwrap();
+        let m = |p: &str| globs.matching(Path::new(p));
+        assert_eq!(m("/exports/caf\u{e9}_7612.jpg"), Some("cafe\u{301}_*"));
+        assert_eq!(m("/exports/cafe\u{301}_7612.jpg"), Some("cafe\u{301}_*"));
+        assert_eq!(m("/exports/cafe_7612.jpg"), None);
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_named() {
+        let err = ExcludeGlobs::new(["*_thumb.jpg", "IMG_[7612.jpg"]).unwrap_err();
+        assert_eq!(err.pattern, "IMG_[7612.jpg");
+        assert!(err
+            .to_string()
+            .starts_with("invalid exclude pattern \"IMG_[7612.jpg\": "));
+
+        let err = ExcludeGlobs::new(["thumbs/*.jpg"]).unwrap_err();
+        assert_eq!(err.pattern, "thumbs/*.jpg");
+
+        let json = r#"["*_thumb.jpg", "{a,b"]"#;
+        let err = serde_json::from_str::<ExcludeGlobs>(json).unwrap_err();
+        assert!(err.to_string().contains("\"{a,b\""), "{}", err);
+    }
+}
@@ -0,0 +1,160 @@
+//! Minimal EXIF and XMP reads (capture date, original frame name) for image files that carry
+//! the data.
+
+use chrono::NaiveDateTime;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::Path;
+
+/// Extensions (lowercase) whose containers kamadak-exif can read.
+const EXIF_EXTENSIONS: &[&str] = &["jpg", "jpeg", "tiff", "tif", "heic", "png", "webp"];
+
+fn has_exif_container(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .map(|e| EXIF_EXTENSIONS.contains(&e.as_str()))
+        .unwrap_or(false)
+}
+
+/// Read EXIF DateTimeOriginal from `path`. Returns None when the file has no EXIF or the tag is missing/invalid.
+pub fn date_time_original(path: &Path) -> Option<NaiveDateTime> {
+    if !has_exif_container(path) {
+        return None;
+    }
+    let file = File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+    let field = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)?;
+    match &field.value {
+        exif::Value::Ascii(parts) => {
+            let raw = parts.first()?;
+            let text = std::str::from_utf8(raw).ok()?;
+            NaiveDateTime::parse_from_str(text.trim(), "%Y:%m:%d %H:%M:%S").ok()
+        }
+        _ => None,
+    }
+}
+
+/// Most bytes of a file read for its frame number: room for a JPEG's EXIF and XMP segments,
+/// which come first, without reading the picture.
+const FRAME_NAME_READ_LIMIT: u64 = 256 * 1024;
+
+/// XMP properties with the name the camera gave a file, most specific first.
+const XMP_NAME_PROPERTIES: &[&str] = &["OriginalFileName", "RawFileName"];
+
+/// What the camera called `path`, for matching suffixes against though another tool renamed it:
+/// the stem of its XMP OriginalFileName or RawFileName ("IMG_7612"), else its EXIF ImageNumber
+/// ("1007612"). Only the start of the file is read (`FRAME_NAME_READ_LIMIT`). `None` for a
+/// file that has none of them, that can't be read, or whose type carries no such data.
+pub fn frame_name(path: &Path) -> Option<String> {
+    if !has_exif_container(path) {
+        return None;
+    }
+    let mut head = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(FRAME_NAME_READ_LIMIT)
+        .read_to_end(&mut head)
+        .ok()?;
+    if let Some(name) = XMP_NAME_PROPERTIES
+        .iter()
+        .find_map(|property| xmp_value(&head, property))
+    {
+        let stem = Path::new(&name).file_stem()?.to_string_lossy().into_owned();
+        return Some(stem);
+    }
+    let exif = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(head))
+        .ok()?;
+    [exif::Context::Exif, exif::Context::Tiff]
+        .into_iter()
+        .find_map(|context| exif.get_field(exif::Tag(context, IMAGE_NUMBER), exif::In::PRIMARY))
+        .and_then(|field| field.value.get_uint(0))
+        .map(|number| number.to_string())
+}
+
+/// EXIF ImageNumber, which kamadak-exif has no name for.
+const IMAGE_NUMBER: u16 = 0x9211;
+
+/// The value of XMP `property` in `data`, written either as an attribute
+/// (`crs:RawFileName="IMG_7612.CR2"`) or as an element
+/// (`<crs:RawFileName>IMG_7612.CR2</crs:RawFileName>`).
+fn xmp_value(data: &[u8], property: &str) -> Option<String> {
+    let property = property.as_bytes();
+    let mut from = 0;
+    while let Some(at) = find(&data[from..], property) {
+        let after = from + at + property.len();
+        from = after;
+        // The whole name, with a namespace prefix: not the end of "MyOriginalFileName".
+        if !matches!(data[..after - property.len()].last(), Some(b':')) {
+            continue;
+        }
+        let rest = trim_start(&data[after..]);
+        let value = match rest.first() {
+            Some(b'=') => {
+                let rest = trim_start(&rest[1..]);
+                let quote = *rest.first().filter(|q| matches!(q, b'"' | b'\''))?;
+                let end = rest[1..].iter().position(|&b| b == quote)?;
+                &rest[1..1 + end]
+            }
+            Some(b'>') => {
+                let end = rest[1..].iter().position(|&b| b == b'<')?;
+                &rest[1..1 + end]
+            }
+            _ => continue,
+        };
+        let value = std::str::from_utf8(value).ok()?.trim();
+        if !value.is_empty() {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn trim_start(data: &[u8]) -> &[u8] {
+    let start = data
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(data.len());
+    &data[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_name_from_xmp_attribute_or_element() {
+        let attribute = br#"<rdf:Description crs:RawFileName = "IMG_7612.CR2" xmp:Rating="3"/>"#;
+        assert_eq!(
+            xmp_value(attribute, "RawFileName").as_deref(),
+            Some("IMG_7612.CR2")
+        );
+        let element = b"<photoshop:OriginalFileName>DSC_0045.NEF</photoshop:OriginalFileName>";
+        assert_eq!(
+            xmp_value(element, "OriginalFileName").as_deref(),
+            Some("DSC_0045.NEF")
+        );
+        assert_eq!(
+            xmp_value(b"xmp:MyRawFileName=\"x.jpg\"", "RawFileName"),
+            None
+        );
+        assert_eq!(xmp_value(b"crs:RawFileName=\"\"", "RawFileName"), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let renamed = dir.path().join("2024-03-01_wedding_0045.jpg");
+        let mut jpeg = b"\xff\xd8\xff\xe1\x00\x40http://ns.adobe.com/xap/1.0/\0".to_vec();
+        jpeg.extend_from_slice(element);
+        std::fs::write(&renamed, &jpeg).unwrap();
+        assert_eq!(frame_name(&renamed).as_deref(), Some("DSC_0045"));
+        // Types that carry no such data are not read.
+        let gif = dir.path().join("wedding_0046.gif");
+        std::fs::write(&gif, &jpeg).unwrap();
+        assert_eq!(frame_name(&gif), None);
+    }
+}
@@ -2,22 +2,88 @@
 
 use sha2::{Digest, Sha256};
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read};
 use std::path::Path;
 
+use crate::control::RunControl;
+
 const BUF_SIZE: usize = 64 * 1024;
 
+#[cfg(test)]
+thread_local! {
+    /// Files hashed on this thread, so tests can count full reads.
+    pub static FILES_HASHED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
 /// Compute SHA-256 hash of file at `path`. Returns hex string or error.
-pub fn hash_file(path: &Path) -> std::io::Result<String> {
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    hash_file_cancellable(path, &RunControl::new())
+}
+
+/// Like `hash_file`, but checks `control` between reads: waits while the run is paused, keeps
+/// to its throttle and fails with `Interrupted` once it is cancelled.
+pub fn hash_file_cancellable(path: &Path, control: &RunControl) -> io::Result<String> {
+    #[cfg(test)]
+    FILES_HASHED.with(|n| n.set(n.get() + 1));
     let mut f = File::open(path)?;
     let mut hasher = Sha256::new();
     let mut buf = [0u8; BUF_SIZE];
     loop {
+        if !control.proceed() {
+            return Err(cancelled());
+        }
         let n = f.read(&mut buf)?;
         if n == 0 {
             break;
         }
         hasher.update(&buf[..n]);
+        if !control.pace(n as u64) {
+            return Err(cancelled());
+        }
     }
     Ok(format!("{:x}", hasher.finalize()))
 }
+
+/// Whether the files at `a` and `b` have the same bytes, read side by side; stops at the first
+/// difference. Checks `control` between reads like `hash_file_cancellable`.
+pub fn same_bytes(a: &Path, b: &Path, control: &RunControl) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+    let (mut buf_a, mut buf_b) = ([0u8; BUF_SIZE], [0u8; BUF_SIZE]);
+    loop {
+        if !control.proceed() {
+            return Err(cancelled());
+        }
+        let n = fill(&mut a, &mut buf_a)?;
+        if n != fill(&mut b, &mut buf_b)? || buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+        if !control.pace(2 * n as u64) {
+            return Err(cancelled());
+        }
+    }
+}
+
+/// Read into `buf` until it is full or the file ends; returns the bytes read. Unlike a single
+/// `read`, two files of the same content then give the same chunks.
+fn fill(f: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match f.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(read) => n += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
+
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "cancelled")
+}
@@ -1,64 +1,945 @@
+mod cleanup;
+mod collision;
+pub mod config;
+mod control;
 pub mod engine;
+mod exclude;
+mod exif_info;
 mod hasher;
 mod mover;
+mod path_input;
+pub mod presets;
+pub mod reveal;
+mod sanitize;
 mod scanner;
+pub mod settings;
 mod suffix_parser;
+mod tag;
+mod template;
+pub mod thumbnail;
 
-use engine::{run as engine_run, ProgressEvent};
+use engine::{
+    audit as engine_audit, execute as engine_execute, plan as engine_plan, resume as engine_resume,
+    run as engine_run, run_selected as engine_run_selected, watch as engine_watch, FileEventQueue,
+    FrameAudit, HistoryRecord, IndexCache, MovePlan, ProgressEvent, ProgressFn, ProgressThrottle,
+    RunControl, RunError, RunHistory, RunKind, RunOptions, RunResult, RunSlot, SavedRunSummary,
+    ScanPreview,
+};
+use presets::PresetList;
+use settings::Settings;
 use std::path::PathBuf;
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter, Manager};
+use thumbnail::{Thumbnail, ThumbnailGate};
 
-/// Shared state for cancellation.
-struct CancelState {
-    cancel: Arc<AtomicBool>,
+/// The run going now, to pause, cancel or throttle it; one at a time (see `RunSlot`).
+#[derive(Default)]
+struct RunState {
+    slot: RunSlot,
 }
 
-#[tauri::command]
-fn start_move(
+/// Holds the run slot for the run `id` until dropped: moved into the run's thread, it frees
+/// the slot when the thread ends, however it ends.
+struct RunGuard {
     app: AppHandle,
-    source: String,
-    dest: String,
-    suffix_input: String,
-    dry_run: bool,
-    verbose: bool,
-) -> Result<(), String> {
-    let state = app.state::<CancelState>();
-    state.cancel.store(false, std::sync::atomic::Ordering::Relaxed);
-
-    let source_path = PathBuf::from(&source);
-    let dest_path = PathBuf::from(&dest);
-    if !source_path.is_dir() {
-        return Err("Source is not a directory".to_string());
-    }
-    if dest_path.exists() && !dest_path.is_dir() {
+    id: u64,
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        self.app.state::<RunState>().slot.end(self.id);
+        // The run may have moved files out of the sources the counts listed.
+        self.app.state::<CountState>().listings.clear();
+    }
+}
+
+/// Take the run slot for a new run: its guard and control, or the error that one is still
+/// going, for the frontend to show.
+fn begin_run(app: &AppHandle) -> Result<(RunGuard, Arc<RunControl>), String> {
+    let (id, control) = app
+        .state::<RunState>()
+        .slot
+        .begin()
+        .map_err(|e| e.to_string())?;
+    let guard = RunGuard {
+        app: app.clone(),
+        id,
+    };
+    Ok((guard, control))
+}
+
+/// The payload of an event of a run, with the run's id next to its own fields, so the
+/// frontend can tell a late event of an earlier run from the current one's.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RunEvent<'a, T: serde::Serialize> {
+    run_id: u64,
+    #[serde(flatten)]
+    event: &'a T,
+}
+
+// By hand: a derive would want `T: Clone`, which the borrowed event does not need.
+impl<T: serde::Serialize> Clone for RunEvent<'_, T> {
+    fn clone(&self) -> Self {
+        RunEvent {
+            run_id: self.run_id,
+            event: self.event,
+        }
+    }
+}
+
+fn emit_run_event<T: serde::Serialize>(app: &AppHandle, name: &str, run_id: u64, event: &T) {
+    let _ = app.emit(name, RunEvent { run_id, event });
+}
+
+/// The control of the latest `preview_scan`, apart from the run's: a new preview cancels the
+/// one before, whose inputs are stale, without touching a run.
+#[derive(Default)]
+struct PreviewState {
+    control: Mutex<Arc<RunControl>>,
+}
+
+/// The control of the latest `count_matches`, apart from the run's and the preview's, and the
+/// source listings the counts share.
+#[derive(Default)]
+struct CountState {
+    control: Mutex<Arc<RunControl>>,
+    listings: engine::SourceListings,
+}
+
+/// Files staged by `stage_selection` for the next `move_selected`.
+#[derive(Default)]
+struct SelectionState {
+    staged: Mutex<Vec<PathBuf>>,
+}
+
+/// An error of a command as the frontend gets it: the message, the error's kind and the folder
+/// or file it is about (see `RunError::kind`).
+#[derive(serde::Serialize)]
+struct CommandError {
+    message: String,
+    kind: &'static str,
+    path: Option<PathBuf>,
+}
+
+impl CommandError {
+    /// A folder `check_folders` turned down.
+    fn folders(message: String) -> Self {
+        CommandError {
+            message,
+            kind: "invalid_folders",
+            path: None,
+        }
+    }
+}
+
+impl From<&presets::PresetError> for CommandError {
+    fn from(error: &presets::PresetError) -> Self {
+        CommandError {
+            message: error.to_string(),
+            kind: error.kind(),
+            path: None,
+        }
+    }
+}
+
+impl From<&RunError> for CommandError {
+    fn from(error: &RunError) -> Self {
+        CommandError {
+            message: error.to_string(),
+            kind: error.kind(),
+            path: error.path().map(PathBuf::from),
+        }
+    }
+}
+
+/// Make the folders of `options` absolute and clean as `normalize_path` does, relative ones
+/// taken from `input_base`.
+fn normalize_folders(app: &AppHandle, options: &mut RunOptions) -> Result<(), String> {
+    let base = input_base(app);
+    let clean = |what: &str, path: &mut PathBuf| -> Result<(), String> {
+        *path = engine::normalize_path(&path.to_string_lossy(), &base)
+            .map_err(|e| format!("{} \"{}\": {}", what, path.display(), e))?;
+        Ok(())
+    };
+    for source in &mut options.sources {
+        clean("Source", source)?;
+    }
+    // None with a tag, which renames in place.
+    if !options.dest.as_os_str().is_empty() {
+        clean("Destination", &mut options.dest)?;
+    }
+    for route in &mut options.routes {
+        clean("Route destination", &mut route.dest)?;
+    }
+    if let Some(rejects) = &mut options.rejects {
+        clean("Rejects folder", rejects)?;
+    }
+    Ok(())
+}
+
+/// Normalize the folders of `options` (see `normalize_folders`) and check that they can be
+/// used, before a command hands them to the engine.
+fn check_folders(app: &AppHandle, options: &mut RunOptions) -> Result<(), String> {
+    normalize_folders(app, options)?;
+    if options.sources.is_empty() {
+        return Err("No source folder selected".to_string());
+    }
+    if let Some(bad) = options.sources.iter().find(|s| !s.is_dir()) {
+        return Err(format!("Source is not a directory: {}", bad.display()));
+    }
+    if options.dest.exists() && !options.dest.is_dir() {
         return Err("Destination exists and is not a directory".to_string());
     }
+    if let Some(bad) = options
+        .routes
+        .iter()
+        .map(|r| &r.dest)
+        .chain(&options.rejects)
+        .find(|d| d.exists() && !d.is_dir())
+    {
+        return Err(format!(
+            "Route or rejects folder is not a directory: {}",
+            bad.display()
+        ));
+    }
+    // Every run of the session shares the destination hashes it has already read.
+    options.index_cache = Some(app.state::<Arc<IndexCache>>().inner().clone());
+    Ok(())
+}
+
+/// What relative paths from the frontend are taken from: the home folder, or the app's
+/// working folder when there is none.
+fn input_base(app: &AppHandle) -> PathBuf {
+    app.path()
+        .home_dir()
+        .or_else(|_| std::env::current_dir())
+        .unwrap_or_default()
+}
+
+/// Tell the frontend why a run stopped before moving anything ("preflight-failed": the message
+/// plus the typed error).
+fn emit_preflight_error(app: &AppHandle, run_id: u64, result: &RunResult) {
+    if let Some(e) = &result.preflight_error {
+        let payload = serde_json::json!({ "message": e.to_string(), "error": e });
+        emit_run_event(app, "preflight-failed", run_id, &payload);
+    }
+}
+
+/// Tell the frontend why a run could not start ("run-failed": the message, the error's kind
+/// and the folder or file it is about). The final "progress" event carries it as well.
+fn emit_run_error(app: &AppHandle, run_id: u64, error: &RunError) {
+    emit_run_event(app, "run-failed", run_id, &CommandError::from(error));
+}
+
+/// The run history file, in the app's data folder.
+fn history_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("history.json"))
+        .map_err(|e| e.to_string())
+}
+
+/// Tell the frontend how a run ended: "run-finished" with the whole `RunResult` (and
+/// "preflight-failed" when those checks stopped it), or "run-failed" when it could not start.
+fn emit_outcome(app: &AppHandle, run_id: u64, outcome: &Result<RunResult, RunError>) {
+    match outcome {
+        Ok(result) => {
+            emit_preflight_error(app, run_id, result);
+            emit_run_event(app, "run-finished", run_id, result);
+        }
+        Err(e) => emit_run_error(app, run_id, e),
+    }
+}
+
+/// Post a system notification of how a run ended, unless the app's window is in front or
+/// notifications are off in the settings; the window asks for attention too (a bouncing Dock
+/// icon, a flashing taskbar button). Where the system wants to ask for permission first, it is
+/// asked once; a refusal is respected quietly.
+fn notify_outcome(
+    app: &AppHandle,
+    kind: RunKind,
+    options: &RunOptions,
+    outcome: &Result<RunResult, RunError>,
+) {
+    use tauri_plugin_notification::{NotificationExt, PermissionState};
+    let windows = app.webview_windows();
+    if windows.values().any(|w| w.is_focused().unwrap_or(false)) {
+        return;
+    }
+    let settings = settings_path(app)
+        .map(|path| settings::load_settings(&path))
+        .unwrap_or_default();
+    if settings.disable_notifications {
+        return;
+    }
+    let notification = app.notification();
+    let permitted = match notification.permission_state() {
+        Ok(PermissionState::Granted) => true,
+        Ok(PermissionState::Denied) | Err(_) => false,
+        Ok(_) => matches!(
+            notification.request_permission(),
+            Ok(PermissionState::Granted)
+        ),
+    };
+    if permitted {
+        let (title, body) = notification_text(kind, options, outcome);
+        let _ = notification.builder().title(title).body(body).show();
+    }
+    for window in windows.values() {
+        let _ = window.request_user_attention(Some(tauri::UserAttentionType::Informational));
+    }
+}
+
+/// Title and text of the notification of a run that ended with `outcome`, as in "Run
+/// finished" and "42 moved, 3 duplicates, 1 error".
+fn notification_text(
+    kind: RunKind,
+    options: &RunOptions,
+    outcome: &Result<RunResult, RunError>,
+) -> (String, String) {
+    let what = match kind {
+        RunKind::Watch => "Watch mode",
+        RunKind::Plan => "Reviewed run",
+        RunKind::Resume => "Resumed run",
+        RunKind::Selection => "Run of selected files",
+        RunKind::Run if options.dry_run => "Dry run",
+        RunKind::Run => "Run",
+    };
+    let result = match outcome {
+        Err(e) => return (format!("{} failed", what), e.to_string()),
+        Ok(result) => result,
+    };
+    if let Some(e) = &result.preflight_error {
+        return (
+            format!("{} stopped before moving anything", what),
+            e.to_string(),
+        );
+    }
+    let count =
+        |n: u64, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+    let mut parts = vec![
+        match (options.move_options.tag.is_some(), options.dry_run) {
+            (true, _) => format!("{} tagged", result.tagged),
+            (false, true) => format!("{} would be moved", result.matched),
+            (false, false) => format!("{} moved", result.moved),
+        },
+    ];
+    if result.skipped_duplicates > 0 {
+        parts.push(count(result.skipped_duplicates, "duplicate", "duplicates"));
+    }
+    if result.errors > 0 {
+        parts.push(count(result.errors, "error", "errors"));
+    }
+    let title = if result.cancelled {
+        format!("{} cancelled", what)
+    } else if result.aborted.is_some() {
+        format!("{} stopped", what)
+    } else {
+        format!("{} finished", what)
+    };
+    let mut body = parts.join(", ");
+    if let Some(abort) = &result.aborted {
+        body = format!("{}: {}", body, abort);
+    }
+    (title, body)
+}
+
+/// Emit how the run of `kind` with `options` ended, add it to the history and, when the app is
+/// in the background, post a notification of it.
+fn finish_run(
+    app: &AppHandle,
+    run_id: u64,
+    kind: RunKind,
+    options: &RunOptions,
+    outcome: &Result<RunResult, RunError>,
+) {
+    emit_outcome(app, run_id, outcome);
+    record_run(app, kind, options, outcome);
+    notify_outcome(app, kind, options, outcome);
+}
+
+/// Add the run of `kind` with `options` that ended with `outcome` to the history. A history
+/// that had to be started afresh, or one that cannot be written, is a "history-warning" event
+/// with the message; the run itself is not affected.
+fn record_run(
+    app: &AppHandle,
+    kind: RunKind,
+    options: &RunOptions,
+    outcome: &Result<RunResult, RunError>,
+) {
+    let record = HistoryRecord::new(kind, options, outcome);
+    let warning = history_path(app).and_then(|path| {
+        engine::append_history(&path, record)
+            .map_err(|e| format!("Cannot write run history {}: {}", path.display(), e))
+    });
+    if let Some(message) = warning.unwrap_or_else(Some) {
+        let _ = app.emit("history-warning", serde_json::json!({ "message": message }));
+    }
+}
+
+/// The progress callback of the run `run_id`: each event as "progress", and the files the run
+/// moves, skips as duplicates or fails on as "file-moved", "file-duplicate" and "file-error"
+/// events, batched (see `engine::FileEventQueue`); all tagged with the run's id. The progress
+/// events that only carry a file are not sent as "progress": the next one has their counts.
+/// Nor are most of the others on a fast run: at most about ten a second, with phase changes,
+/// problems and the final event always sent (see `engine::ProgressThrottle`).
+fn run_progress(app: &AppHandle, run_id: u64) -> Option<ProgressFn> {
+    let queues = Mutex::new((FileEventQueue::new(run_id), ProgressThrottle::new()));
+    let app = app.clone();
+    Some(Box::new(move |ev| {
+        let now = Instant::now();
+        let mut queues = queues.lock().unwrap_or_else(|e| e.into_inner());
+        let (queue, throttle) = &mut *queues;
+        for batch in queue.push(&ev, now) {
+            let _ = app.emit(batch.files[0].event_name(), &batch);
+        }
+        if ev.file.is_none() && throttle.admit(&ev, now) {
+            emit_run_event(&app, "progress", run_id, &ev);
+        }
+    }))
+}
+
+/// Start a run in the background and return its id, which its events carry as `runId`.
+/// `options` is the frontend's camelCase options object (see `engine::RunOptions`); fields it
+/// leaves out take their defaults. Fails while another run (or plan, watch or audit) is going.
+#[tauri::command]
+fn start_move(app: AppHandle, mut options: RunOptions) -> Result<u64, String> {
+    check_folders(&app, &mut options)?;
+    let (guard, control) = begin_run(&app)?;
+    let run_id = guard.id;
+    std::thread::spawn(move || {
+        let _guard = guard;
+        let progress = run_progress(&app, run_id);
+        let outcome = engine_run(&options, &control, progress);
+        finish_run(&app, run_id, RunKind::Run, &options, &outcome);
+    });
+    Ok(run_id)
+}
+
+/// Plan a run in the background without moving anything; emits "progress" and then
+/// "plan-ready" with the `MovePlan`, which the frontend can show, trim and pass to `execute_plan`
+/// (or "run-failed" when planning could not start).
+#[tauri::command]
+fn plan_move(app: AppHandle, mut options: RunOptions) -> Result<u64, String> {
+    check_folders(&app, &mut options)?;
+    let (guard, control) = begin_run(&app)?;
+    let run_id = guard.id;
+    std::thread::spawn(move || {
+        let _guard = guard;
+        let progress = run_progress(&app, run_id);
+        match engine_plan(&options, &control, progress) {
+            Ok(plan) => {
+                emit_preflight_error(&app, run_id, &plan.preview);
+                emit_run_event(&app, "plan-ready", run_id, &plan);
+            }
+            Err(e) => emit_run_error(&app, run_id, &e),
+        }
+    });
+    Ok(run_id)
+}
+
+/// Execute a plan from `plan_move` in the background; files that changed since are skipped.
+#[tauri::command]
+fn execute_plan(app: AppHandle, mut plan: MovePlan) -> Result<u64, String> {
+    check_folders(&app, &mut plan.options)?;
+    let (guard, control) = begin_run(&app)?;
+    let run_id = guard.id;
+    std::thread::spawn(move || {
+        let _guard = guard;
+        let progress = run_progress(&app, run_id);
+        let outcome = Ok(engine_execute(&plan, &control, progress));
+        finish_run(&app, run_id, RunKind::Plan, &plan.options, &outcome);
+    });
+    Ok(run_id)
+}
+
+/// Add `paths` to the files the next `move_selected` moves, after dropping those staged before
+/// with `clear`; returns how many are staged. A selection of thousands of files is sent in
+/// chunks this way rather than as one invoke argument.
+#[tauri::command]
+fn stage_selection(app: AppHandle, paths: Vec<PathBuf>, clear: bool) -> usize {
+    let state = app.state::<SelectionState>();
+    let mut staged = state.staged.lock().unwrap_or_else(|e| e.into_inner());
+    if clear {
+        staged.clear();
+    }
+    staged.extend(paths);
+    staged.len()
+}
+
+/// Start a run of only the selected files in the background: those staged with
+/// `stage_selection` and `paths`. They are checked to still exist in one of `options`'
+/// sources, then planned and moved as in `start_move`, with the same events.
+#[tauri::command]
+fn move_selected(
+    app: AppHandle,
+    mut options: RunOptions,
+    paths: Vec<PathBuf>,
+) -> Result<u64, String> {
+    check_folders(&app, &mut options)?;
+    let mut selection = std::mem::take(
+        &mut *app
+            .state::<SelectionState>()
+            .staged
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()),
+    );
+    selection.extend(paths);
+    let (guard, control) = begin_run(&app)?;
+    let run_id = guard.id;
+    std::thread::spawn(move || {
+        let _guard = guard;
+        let progress = run_progress(&app, run_id);
+        let outcome = engine_run_selected(&options, &selection, &control, progress);
+        finish_run(&app, run_id, RunKind::Selection, &options, &outcome);
+    });
+    Ok(run_id)
+}
+
+/// The unfinished run whose state is at `path` (the frontend's `statePath`), to offer resuming
+/// it; `None` when there is none. A state that cannot be resumed from is the error.
+#[tauri::command]
+fn saved_run(path: PathBuf) -> Result<Option<SavedRunSummary>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    engine::load_state(&path)
+        .map(|saved| Some(saved.summary()))
+        .map_err(|e| e.to_string())
+}
+
+/// Resume the run whose state is at `path` in the background; emits "progress" like a run.
+#[tauri::command]
+fn resume_run(app: AppHandle, path: PathBuf) -> Result<u64, String> {
+    let (guard, control) = begin_run(&app)?;
+    let run_id = guard.id;
+    std::thread::spawn(move || {
+        let _guard = guard;
+        let progress = run_progress(&app, run_id);
+        let options = engine::load_state(&path).map(|saved| saved.plan.options);
+        let outcome = engine_resume(&path, &control, progress);
+        match options {
+            Ok(options) => finish_run(&app, run_id, RunKind::Resume, &options, &outcome),
+            Err(_) => emit_outcome(&app, run_id, &outcome),
+        }
+    });
+    Ok(run_id)
+}
+
+/// Drop the unfinished run whose state is at `path`; the files it moved stay where they are.
+#[tauri::command]
+fn discard_saved_run(path: PathBuf) -> Result<(), String> {
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Start watch mode in the background: the usual run, then new matching files as they appear.
+/// Emits "progress" like a run, plus "watch-batch" with the result of every batch, until
+/// `stop_watch`.
+#[tauri::command]
+fn start_watch(app: AppHandle, mut options: RunOptions) -> Result<u64, String> {
+    check_folders(&app, &mut options)?;
+    let (guard, control) = begin_run(&app)?;
+    let run_id = guard.id;
+    std::thread::spawn(move || {
+        let _guard = guard;
+        let progress = run_progress(&app, run_id);
+        let result = engine_watch(&options, &control, progress, &mut |batch| {
+            emit_run_event(&app, "watch-batch", run_id, batch);
+        });
+        finish_run(&app, run_id, RunKind::Watch, &options, &result);
+    });
+    Ok(run_id)
+}
 
-    let cancel = state.cancel.clone();
-    let app_emit = app.clone();
+/// Look for identical images in `dest` in the background without changing anything; emits
+/// "progress" while hashing (not the final event; thinned out as for a run) and then "audit-ready" with the
+/// `AuditReport`. Cancel, pause and throttle apply as to a run.
+#[tauri::command]
+fn audit_destination(app: AppHandle, dest: PathBuf, workers: usize) -> Result<u64, String> {
+    if !dest.is_dir() {
+        return Err(format!(
+            "Destination is not a directory: {}",
+            dest.display()
+        ));
+    }
+    let (guard, control) = begin_run(&app)?;
+    let run_id = guard.id;
     std::thread::spawn(move || {
+        let _guard = guard;
+        let progress_emit = app.clone();
+        let throttle = Mutex::new(ProgressThrottle::new());
         let progress: Option<Box<dyn Fn(ProgressEvent) + Send>> = Some(Box::new(move |ev| {
-            let _ = app_emit.emit("progress", &ev);
+            let mut throttle = throttle.lock().unwrap_or_else(|e| e.into_inner());
+            if !matches!(ev.phase, engine::Phase::Done) && throttle.admit(&ev, Instant::now()) {
+                emit_run_event(&progress_emit, "progress", run_id, &ev);
+            }
         }));
-        engine_run(
-            &source_path,
-            &dest_path,
-            &suffix_input,
-            dry_run,
-            verbose,
-            &cancel,
-            progress,
-        );
+        let report = engine_audit(&dest, workers, false, &control, progress);
+        emit_run_event(&app, "audit-ready", run_id, &report);
     });
+    Ok(run_id)
+}
+
+/// Find where each frame of `suffixes` is, in the sources, `dest`, both or nowhere, and return
+/// the table (see `engine::locate_frames`). Nothing is created or changed, not even `dest`;
+/// Cancel stops the comparison of the copies. Takes the run slot like a run, so it fails while
+/// one is going.
+#[tauri::command]
+async fn audit_frames(
+    app: AppHandle,
+    sources: Vec<PathBuf>,
+    dest: PathBuf,
+    suffixes: String,
+) -> Result<FrameAudit, String> {
+    let (guard, control) = begin_run(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let _guard = guard;
+        engine::locate_frames(&sources, &dest, &suffixes, &control)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+/// Scan the sources of `options` for the files a run with them would take, without anything
+/// else (see `engine::preview_scan`): at most `max_entries` of them (a default when left out),
+/// with the totals of all. Cancels the preview before, which then returns `cancelled`; a
+/// preview can also be dropped with `cancel_preview`. Errors come back as a `CommandError`.
+#[tauri::command]
+async fn preview_scan(
+    app: AppHandle,
+    mut options: RunOptions,
+    max_entries: Option<usize>,
+) -> Result<ScanPreview, CommandError> {
+    check_folders(&app, &mut options).map_err(CommandError::folders)?;
+    let control = Arc::new(RunControl::new());
+    {
+        let state = app.state::<PreviewState>();
+        let mut latest = state.control.lock().unwrap_or_else(|e| e.into_inner());
+        latest.cancel();
+        *latest = control.clone();
+    }
+    let max_entries = max_entries.unwrap_or(engine::DEFAULT_PREVIEW_ENTRIES);
+    let preview = tauri::async_runtime::spawn_blocking(move || {
+        engine::preview_scan(&options, max_entries, &control)
+    })
+    .await
+    .map_err(|e| CommandError {
+        message: e.to_string(),
+        kind: "internal",
+        path: None,
+    })?;
+    preview.map_err(|e| CommandError::from(&e))
+}
+
+/// How many files a run of `options` would take and their bytes, for the suffix box as it is
+/// typed in (see `engine::count_matches`). Cancels the count before, which then returns
+/// `cancelled`; the sources are listed once and counted again from the listing until they
+/// change, a run ends or `refresh_source_listing` drops it.
+#[tauri::command]
+async fn count_matches(
+    app: AppHandle,
+    mut options: RunOptions,
+) -> Result<engine::MatchCount, CommandError> {
+    check_folders(&app, &mut options).map_err(CommandError::folders)?;
+    let control = Arc::new(RunControl::new());
+    {
+        let state = app.state::<CountState>();
+        let mut latest = state.control.lock().unwrap_or_else(|e| e.into_inner());
+        latest.cancel();
+        *latest = control.clone();
+    }
+    let count = tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<CountState>();
+        engine::count_matches(&options, &state.listings, &control)
+    })
+    .await
+    .map_err(|e| CommandError {
+        message: e.to_string(),
+        kind: "internal",
+        path: None,
+    })?;
+    count.map_err(|e| CommandError::from(&e))
+}
+
+/// Drop the source listing the match counts use, so the next count walks the sources again.
+#[tauri::command]
+fn refresh_source_listing(app: AppHandle) {
+    app.state::<CountState>().listings.clear();
+}
+
+/// Check the folders and suffixes of `options` while they are being filled in (see
+/// `engine::validate_inputs`): the findings, each an error that should keep Start disabled or
+/// a warning to show. Reads only metadata and creates nothing, not even the destination. The
+/// folders are normalized as for the other commands; their checks come back as findings.
+#[tauri::command]
+async fn validate_inputs(
+    app: AppHandle,
+    mut options: RunOptions,
+) -> Result<Vec<engine::Finding>, String> {
+    normalize_folders(&app, &mut options)?;
+    tauri::async_runtime::spawn_blocking(move || engine::validate_inputs(&options))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// The volume of `path` (see `engine::volume_info`): free and total bytes, its name and
+/// filesystem, and whether `other` (a source) is on it, so moves from it are renames. A path
+/// still to be created is taken as the folder above it that exists.
+#[tauri::command]
+async fn get_volume_info(
+    path: PathBuf,
+    other: Option<PathBuf>,
+) -> Result<engine::VolumeInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        engine::volume_info(&path, other.as_deref()).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// A small JPEG of the image at `path` for the app's tables, its longest edge at most
+/// `max_edge` pixels (256 when left out), cached by path and modification time in the app's
+/// cache folder. A file that cannot be shown is `no_preview`, not an error. Only a couple are
+/// made at once, and one while a run is going, so that previews do not slow it down.
+#[tauri::command]
+async fn get_thumbnail(
+    app: AppHandle,
+    path: PathBuf,
+    max_edge: Option<u32>,
+) -> Result<Thumbnail, String> {
+    let cache = app
+        .path()
+        .app_cache_dir()
+        .map(|dir| dir.join("thumbnails"))
+        .map_err(|e| e.to_string())?;
+    let max_edge = max_edge.unwrap_or(thumbnail::DEFAULT_THUMBNAIL_EDGE);
+    tauri::async_runtime::spawn_blocking(move || {
+        let limit = match app.state::<RunState>().slot.current() {
+            Some(_) => 1,
+            None => thumbnail::THUMBNAIL_WORKERS,
+        };
+        app.state::<ThumbnailGate>()
+            .run(limit, || thumbnail::thumbnail(&path, max_edge, &cache))
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Stop the preview in progress, if any (the inputs changed and no new one is wanted).
+#[tauri::command]
+fn cancel_preview(app: AppHandle) -> Result<(), String> {
+    let state = app.state::<PreviewState>();
+    state
+        .control
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .cancel();
     Ok(())
 }
 
+/// The past runs, newest first: `limit` of them (all when left out) after the first `offset`,
+/// with how many there are and a warning when the history file could not be read.
+#[tauri::command]
+fn get_run_history(
+    app: AppHandle,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<RunHistory, String> {
+    let path = history_path(&app)?;
+    Ok(engine::load_history(&path, offset.unwrap_or(0), limit))
+}
+
+/// The settings file, in the app's config folder.
+fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map(|dir| dir.join("settings.json"))
+        .map_err(|e| e.to_string())
+}
+
+/// The folders, suffixes and options saved by `save_settings`; the defaults on a first launch
+/// or when the file is missing or damaged.
+#[tauri::command]
+fn load_settings(app: AppHandle) -> Settings {
+    settings_path(&app)
+        .map(|path| settings::load_settings(&path))
+        .unwrap_or_default()
+}
+
+/// Save the app's settings; the frontend calls it whenever they change.
+#[tauri::command]
+fn save_settings(app: AppHandle, settings: Settings) -> Result<(), String> {
+    let path = settings_path(&app)?;
+    settings::save_settings(&path, settings)
+        .map_err(|e| format!("Cannot save settings to {}: {}", path.display(), e))
+}
+
+/// Forget the past runs; their reports and logs are left alone.
 #[tauri::command]
-fn cancel_move(app: AppHandle) -> Result<(), String> {
-    let state = app.state::<CancelState>();
-    state.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+fn clear_run_history(app: AppHandle) -> Result<(), String> {
+    let path = history_path(&app)?;
+    engine::clear_history(&path).map_err(|e| e.to_string())
+}
+
+/// Show `path` in the system file manager: a file selected in its folder (or just its folder
+/// where the file manager cannot select), a folder opened. A path that does not exist is the
+/// error.
+#[tauri::command]
+fn reveal_path(path: PathBuf) -> Result<(), String> {
+    reveal::reveal_path(&path).map_err(|e| e.to_string())
+}
+
+/// `path` as pasted, dropped or typed, cleaned up into an absolute path (see
+/// `engine::normalize_path`); a relative one is taken from the home folder. The error says what
+/// is wrong with the text.
+#[tauri::command]
+fn normalize_path(app: AppHandle, path: String) -> Result<PathBuf, String> {
+    engine::normalize_path(&path, &input_base(&app)).map_err(|e| e.to_string())
+}
+
+/// Run `command` on the suffix presets file, in the app's data folder, for the preset commands:
+/// each returns the presets afterwards, and its error's kind tells "exists" (ask, then try again
+/// with `overwrite`) from the others.
+fn with_presets(
+    app: &AppHandle,
+    command: impl FnOnce(&std::path::Path) -> Result<PresetList, presets::PresetError>,
+) -> Result<PresetList, CommandError> {
+    let path = app
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("presets.json"))
+        .map_err(|e| CommandError {
+            message: e.to_string(),
+            kind: "store",
+            path: None,
+        })?;
+    command(&path).map_err(|e| CommandError::from(&e))
+}
+
+/// The saved suffix presets, sorted by name; a damaged file is set aside, with a warning.
+#[tauri::command]
+fn list_presets(app: AppHandle) -> Result<PresetList, CommandError> {
+    with_presets(&app, presets::list_presets)
+}
+
+/// Save the suffix text as typed under `name`; fails with kind "exists" when there is a
+/// preset of that name, unless `overwrite`.
+#[tauri::command]
+fn save_preset(
+    app: AppHandle,
+    name: String,
+    suffix_input: String,
+    overwrite: Option<bool>,
+) -> Result<PresetList, CommandError> {
+    with_presets(&app, |path| {
+        presets::save_preset(path, &name, &suffix_input, overwrite.unwrap_or(false))
+    })
+}
+
+/// Rename the preset `from` to `to`; kind "exists" as for `save_preset`.
+#[tauri::command]
+fn rename_preset(
+    app: AppHandle,
+    from: String,
+    to: String,
+    overwrite: Option<bool>,
+) -> Result<PresetList, CommandError> {
+    with_presets(&app, |path| {
+        presets::rename_preset(path, &from, &to, overwrite.unwrap_or(false))
+    })
+}
+
+#[tauri::command]
+fn delete_preset(app: AppHandle, name: String) -> Result<PresetList, CommandError> {
+    with_presets(&app, |path| presets::delete_preset(path, &name))
+}
+
+/// Forget the destination hashes of the session's earlier runs, as after changing the
+/// archive outside the app: the next run of each destination reads what it needs again.
+#[tauri::command]
+fn refresh_index(app: AppHandle) -> usize {
+    let cache = app.state::<Arc<IndexCache>>();
+    let dropped = cache.len();
+    cache.clear();
+    dropped
+}
+
+/// Stop watch mode `run_id`; a batch in progress is cancelled like a run.
+#[tauri::command]
+fn stop_watch(app: AppHandle, run_id: u64) -> Result<(), String> {
+    app.state::<RunState>().slot.cancel(run_id);
+    Ok(())
+}
+
+/// Cancel the run `run_id` (the id its start returned). A run that has already ended is left
+/// alone, and so is the one started after it.
+#[tauri::command]
+fn cancel_move(app: AppHandle, run_id: u64) -> Result<(), String> {
+    app.state::<RunState>().slot.cancel(run_id);
+    Ok(())
+}
+
+/// How long closing the window waits for a cancelled run to finish its current file.
+const CLOSE_WAIT: Duration = Duration::from_secs(30);
+
+/// Close the window while a run is going, after the frontend asked (see "close-requested"):
+/// cancel the run, wait up to `CLOSE_WAIT` for it to finish its current file and send its
+/// final events, then close. Returns false, leaving the window open, when it is still going
+/// by then; with `force` the window closes without waiting.
+#[tauri::command]
+async fn cancel_and_close(window: tauri::Window, force: Option<bool>) -> Result<bool, String> {
+    let app = window.app_handle().clone();
+    let force = force.unwrap_or(false);
+    let ended = tauri::async_runtime::spawn_blocking(move || {
+        let slot = &app.state::<RunState>().slot;
+        slot.cancel_current();
+        slot.wait_ended(if force { Duration::ZERO } else { CLOSE_WAIT })
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    if ended || force {
+        window.destroy().map_err(|e| e.to_string())?;
+    }
+    Ok(ended)
+}
+
+/// The control of the run going now, if any.
+fn current_control(app: &AppHandle) -> Option<Arc<RunControl>> {
+    app.state::<RunState>()
+        .slot
+        .current()
+        .map(|(_, control)| control)
+}
+
+/// Pause the current run; it stops before the next file or copy chunk until resumed.
+#[tauri::command]
+fn pause_move(app: AppHandle) -> Result<(), String> {
+    if let Some(control) = current_control(&app) {
+        control.pause();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn resume_move(app: AppHandle) -> Result<(), String> {
+    if let Some(control) = current_control(&app) {
+        control.resume();
+    }
+    Ok(())
+}
+
+/// Change the read/write limit of the current run (bytes per second, 0 for none).
+#[tauri::command]
+fn set_throttle(app: AppHandle, bytes_per_second: u64) -> Result<(), String> {
+    if let Some(control) = current_control(&app) {
+        control.set_throttle(bytes_per_second);
+    }
     Ok(())
 }
 
@@ -66,10 +947,65 @@ fn cancel_move(app: AppHandle) -> Result<(), String> {
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
-        .manage(CancelState {
-            cancel: Arc::new(AtomicBool::new(false)),
+        .plugin(tauri_plugin_notification::init())
+        .manage(RunState::default())
+        .manage(PreviewState::default())
+        .manage(SelectionState::default())
+        .manage(CountState::default())
+        .manage(Arc::new(IndexCache::default()))
+        .manage(ThumbnailGate::default())
+        // Closing mid-run would leave the run going with no window, or a copy half done when
+        // the app exits: ask the frontend first, which calls `cancel_and_close`.
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                if let Some((run_id, _)) = window.state::<RunState>().slot.current() {
+                    api.prevent_close();
+                    emit_run_event(
+                        window.app_handle(),
+                        "close-requested",
+                        run_id,
+                        &serde_json::json!({}),
+                    );
+                }
+            }
         })
-        .invoke_handler(tauri::generate_handler![start_move, cancel_move])
+        .invoke_handler(tauri::generate_handler![
+            start_move,
+            cancel_move,
+            cancel_and_close,
+            pause_move,
+            resume_move,
+            start_watch,
+            stop_watch,
+            plan_move,
+            execute_plan,
+            stage_selection,
+            move_selected,
+            refresh_index,
+            set_throttle,
+            audit_destination,
+            audit_frames,
+            preview_scan,
+            cancel_preview,
+            count_matches,
+            refresh_source_listing,
+            validate_inputs,
+            get_volume_info,
+            get_thumbnail,
+            saved_run,
+            resume_run,
+            discard_saved_run,
+            get_run_history,
+            clear_run_history,
+            load_settings,
+            save_settings,
+            reveal_path,
+            normalize_path,
+            list_presets,
+            save_preset,
+            rename_preset,
+            delete_preset
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -1,78 +1,2222 @@
 //! FrameMover: move image files by filename suffix with deduplication.
-//! If CLI args (--source, --dest, --suffixes) are provided, runs headless and exits.
+//! With a subcommand, or the options of `move` (--source, --dest, --suffixes) on their own, runs
+//! headless and exits; without arguments, opens the app.
 
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser, ValueHint};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use photo_suffix_mover::config::{self, ConfigError, ConfigValue};
 use photo_suffix_mover::engine;
-use std::path::PathBuf;
-use std::sync::atomic::AtomicBool;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 #[derive(Parser, Debug)]
 #[command(name = "FrameMover")]
 #[command(about = "Move image files by filename suffix with deduplication")]
+#[command(
+    after_help = "Without a subcommand, the options of `move` move files as `move` does; \
+    without any arguments, the app opens."
+)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
-    #[arg(long)]
-    source: Option<PathBuf>,
-    #[arg(long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten, next_help_heading = "Options of `move`")]
+    run: MoveArgs,
+}
+
+/// The options of a move; also taken without the `move` subcommand, as before it existed.
+#[derive(clap::Args, Debug)]
+struct MoveArgs {
+    /// Source folder; repeat to take files from several (e.g. two card dumps) in one run
+    #[arg(long, value_hint = ValueHint::DirPath, value_parser = parse_path)]
+    source: Vec<PathBuf>,
+    #[arg(long, value_hint = ValueHint::DirPath, value_parser = parse_path)]
     dest: Option<PathBuf>,
-    #[arg(long)]
+    #[arg(long, value_hint = ValueHint::Other)]
     suffixes: Option<String>,
+    /// Read the suffixes from this file, written as for --suffixes (e.g. one per line)
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, value_parser = parse_path,
+        conflicts_with = "suffixes")]
+    suffixes_file: Option<PathBuf>,
     #[arg(long)]
     dry_run: bool,
+    /// Put every moved file directly in the destination root instead of mirroring source folders
+    #[arg(long, conflicts_with = "dest_template")]
+    flatten: bool,
+    /// Destination layout template, e.g. "{year}/{month}/{day}" or "{suffix}".
+    /// Tokens: {year} {month} {day} {suffix} {ext} {parent} {filename} {stem}
+    #[arg(long, value_hint = ValueHint::Other)]
+    dest_template: Option<String>,
+    /// Send files of some suffixes to another folder, e.g. "7600-7650=/archive/wedding"; repeat
+    /// for more. Each file follows the first rule that has its suffix, --suffixes/--dest first
+    #[arg(long = "route", value_name = "SUFFIXES=DIR", value_hint = ValueHint::Other,
+        value_parser = parse_route)]
+    routes: Vec<engine::Route>,
+    /// Move the images that match no suffix to this folder in the same run (cull a card: picks
+    /// to --dest, the rest here). Must be apart from the sources and destinations
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath, value_parser = parse_path)]
+    rejects: Option<PathBuf>,
+    /// Tag matches where they are instead of moving them: IMG_7612.JPG becomes
+    /// IMG_7612_pick.JPG with --tag _pick, in its own folder. No --dest is needed
+    #[arg(long, value_name = "TAG", value_hint = ValueHint::Other,
+        value_parser = engine::NameTag::parse,
+        conflicts_with_all = ["dest", "routes", "rejects", "flatten", "dest_template", "watch"])]
+    tag: Option<engine::NameTag>,
+    /// What to do with source files that already exist in the destination (by content)
+    #[arg(long, value_enum, default_value_t = engine::DuplicateAction::Leave)]
+    duplicates: engine::DuplicateAction,
+    /// How files are put into the destination; link modes fall back to a copy when unsupported
+    #[arg(long, value_enum, default_value_t = engine::TransferMode::Move)]
+    mode: engine::TransferMode,
+    /// Rename pattern for name collisions: "-{n}" (IMG-1.JPG), " ({n})" (IMG (1).JPG),
+    /// "-{ts}" (IMG-20240506-142233.JPG); presets "dash", "windows", "timestamp"
+    #[arg(long, default_value = "-{n}", allow_hyphen_values = true, value_hint = ValueHint::Other,
+        value_parser = engine::CollisionPattern::parse)]
+    collision_pattern: engine::CollisionPattern,
+    /// When a destination name holds other content: "rename" the new file with the collision
+    /// pattern, or "update" it, replacing the old file if the source is newer (else skipping it)
+    #[arg(long, value_enum, default_value_t = engine::CollisionMode::Rename)]
+    on_collision: engine::CollisionMode,
+    /// After moving, remove source directories left empty (never the source root itself)
+    #[arg(long)]
+    remove_empty_dirs: bool,
+    /// With --remove-empty-dirs, treat folders containing only .DS_Store/Thumbs.db as empty
+    #[arg(long, requires = "remove_empty_dirs")]
+    junk_as_empty: bool,
+    /// Skip re-hashing cross-volume copies before deleting the source
+    #[arg(long)]
+    no_verify: bool,
+    /// After the run, list every matched file with its action and destination
+    #[arg(long)]
+    list_files: bool,
+    /// How duplicates in the destination are found. same-path skips hashing the destination but
+    /// won't catch identical content stored under a different name or folder
+    #[arg(long, value_enum, default_value_t = engine::DedupMode::Content)]
+    dedup: engine::DedupMode,
+    /// Don't look for duplicates at all: no destination index, no hashing of the sources.
+    /// Every match is moved, renamed if its name is taken. Same as --dedup off; can't be
+    /// combined with --duplicates delete or trash
+    #[arg(long, conflicts_with = "dedup")]
+    no_dedupe: bool,
+    /// Before skipping a duplicate, compare it byte for byte with the file its hash matched; one
+    /// that differs is moved under a new name and reported as a hash mismatch
+    #[arg(long)]
+    paranoid: bool,
+    /// Leave out matching files whose name matches this glob, e.g. "thumb_*" (case is
+    /// ignored; repeat for more patterns)
+    #[arg(long = "exclude-glob", value_name = "GLOB", value_hint = ValueHint::Other,
+        value_parser = parse_glob)]
+    exclude_globs: Vec<String>,
+    /// Match images by their file "name" (default), or by the name the camera gave them in
+    /// their "metadata" (XMP OriginalFileName/RawFileName or EXIF ImageNumber) for files another
+    /// tool renamed; images without it are matched by name. Slower: each image is opened
+    #[arg(long, value_enum, default_value_t = engine::MatchBy::Name)]
+    match_by: engine::MatchBy,
+    /// Write a JSON report (options, counters, per-file actions, timing) to this path
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    report: Option<PathBuf>,
+    /// Log every action, warning and error with timestamps to this file; a directory gets a
+    /// timestamped framemover-*.log. Independent of --verbose
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::AnyPath)]
+    log: Option<PathBuf>,
+    /// Don't carry extended attributes (Finder tags, comments) over on copies (macOS)
+    #[arg(long)]
+    no_xattrs: bool,
+    /// Make each file read-only once it is in the destination (write permissions on Unix, the
+    /// read-only attribute on Windows); hardlinked files stay writable
+    #[arg(long)]
+    read_only: bool,
+    /// Make destination names valid for exFAT/FAT32/SMB; "auto" does so only on such filesystems
+    #[arg(long, value_enum, default_value_t = engine::SanitizeMode::Auto)]
+    sanitize: engine::SanitizeMode,
+    /// Matches that are symbolic links: "skip" them (default), move the "link" itself, or put
+    /// the image it points to ("target") at the destination
+    #[arg(long, value_enum, default_value_t = engine::SymlinkPolicy::Skip)]
+    symlinks: engine::SymlinkPolicy,
+    /// Matches of zero bytes, usually failed writes: "move" them with a warning (default),
+    /// "skip" them with a warning, or count each as an "error"
+    #[arg(long, value_enum, default_value_t = engine::EmptyFilePolicy::Move)]
+    empty_files: engine::EmptyFilePolicy,
+    /// Match names exactly as their bytes spell them: "café" written with a decomposed "é" (as
+    /// macOS does) is then a different name from the composed one
+    #[arg(long)]
+    no_normalize_unicode: bool,
+    /// Files to move at once; 0 (default) uses one for same-volume moves, a few otherwise
+    #[arg(long, default_value_t = 0)]
+    workers: usize,
+    /// Limit reading and writing to this many bytes per second, e.g. "50M" or "500K"
+    /// (decimal units); keeps the disk usable while the run goes on
+    #[arg(long, value_parser = parse_rate)]
+    throttle: Option<u64>,
+    /// Print a notice when one file takes longer than this many seconds (a hung network
+    /// share), and again each time as long again; 0 turns it off
+    #[arg(long, value_name = "SECS", default_value_t = engine::DEFAULT_STALL_WARNING_SECONDS)]
+    stall_warning: u64,
+    /// After moving, check that every moved file is at its destination with its size; a
+    /// missing or different file fails the run
+    #[arg(long)]
+    verify: bool,
+    /// With --verify (implied), also re-read every moved file and compare its hash
+    #[arg(long)]
+    verify_hash: bool,
+    /// Take only the first N matches (sorted by path within each source), e.g. to try the
+    /// settings on a few files first; the rest are counted but left alone
+    #[arg(long, value_name = "N")]
+    limit: Option<u64>,
+    /// Skip files modified less than this many seconds ago, which may still be written (a card
+    /// import or tethering app); they are listed at the end. Default 0, or 10 with --watch,
+    /// which waits for them instead
+    #[arg(long, value_name = "SECS")]
+    min_age: Option<u64>,
+    /// Stop at the first file that fails to move and leave the rest alone, for jobs where a
+    /// partial move is worse than none; exits non-zero
+    #[arg(long)]
+    fail_fast: bool,
+    /// Stop the run once this many moves in a row failed the same way (the destination was
+    /// unmounted); the files left are not attempted. 0 never stops
+    #[arg(long, value_name = "N", default_value_t = engine::DEFAULT_MAX_CONSECUTIVE_ERRORS)]
+    max_consecutive_errors: u32,
+    /// Run even when the destination looks too full or not writable
+    #[arg(long)]
+    skip_preflight: bool,
+    /// Keep the plan and a journal of the files done in this file while moving, so an
+    /// interrupted run can be continued with --resume; removed once the run finishes
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["dry_run", "watch"])]
+    state_file: Option<PathBuf>,
+    /// Continue the run interrupted while writing this --state-file: files it did are skipped,
+    /// the rest re-checked and moved. Takes its folders and options from the file
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["source", "dest", "suffixes", "dry_run", "watch", "state_file"])]
+    resume: Option<PathBuf>,
+    /// Keep running after the first pass and move new matching files as they appear (Ctrl-C stops)
+    #[arg(long)]
+    watch: bool,
+    /// Exit non-zero when a file was copied but its source could not be removed
+    #[arg(long)]
+    strict: bool,
+    /// Move without asking: skip the preview and the y/N question asked before moving when
+    /// the input is a terminal
+    #[arg(long, short = 'y')]
+    yes: bool,
+    /// For scripts: print nothing for people, but one JSON object per line, with a "type":
+    /// "progress" snapshots on stderr; "file", "error" and "warning" records and the final
+    /// "result" (a watch also a "batch" for each) on stdout. Exit codes are unchanged
+    #[arg(long)]
+    json: bool,
+    /// Show no progress while running (stall notices still), only the summary at the end
+    #[arg(long, short = 'q', conflicts_with = "json")]
+    quiet: bool,
+    /// Take defaults for these options from this TOML file instead of framemover.toml in the
+    /// config folder; each key is a flag's name, e.g. `collision-pattern = "windows"`. Flags
+    /// given here override the file
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, value_parser = parse_path)]
+    config: Option<PathBuf>,
+    /// Ignore the configuration file
+    #[arg(long, conflicts_with = "config")]
+    no_config: bool,
+    /// Print the options in effect as TOML, each with where it came from (the command line,
+    /// the configuration file or the default), and exit
+    #[arg(long)]
+    print_config: bool,
     #[arg(long, short = 'v')]
     verbose: bool,
 }
 
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Move (or copy, link or tag) the images whose names end in the suffixes from the sources
+    /// to the destination, skipping those already there. Also the default without a subcommand
+    Move(Box<MoveArgs>),
+    /// Print the path of each file a move would take, one per line, for piping to other
+    /// tools; the counts and warnings go to stderr. Read-only: the destination is not looked at
+    Scan(ScanArgs),
+    /// Check that every file a run put in a destination is still there, from the --report it
+    /// wrote. Read-only
+    Verify {
+        /// The --report file the run wrote
+        #[arg(value_name = "REPORT", value_hint = ValueHint::FilePath, value_parser = parse_path)]
+        report: PathBuf,
+        /// Print the outcome as JSON instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Move the files a run moved back to where it found them, from the --report it wrote.
+    /// Files whose old place is taken again, or that are gone from the destination, are left
+    Undo {
+        /// The --report file of the run: it lists where each file went
+        #[arg(value_name = "JOURNAL", value_hint = ValueHint::FilePath, value_parser = parse_path)]
+        journal: PathBuf,
+        /// Only check and count what would be moved back
+        #[arg(long)]
+        dry_run: bool,
+        /// Print the outcome as JSON instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show where each requested frame is: in the sources, the destination, both (and whether
+    /// the copies are identical) or nowhere. Read-only: nothing is created, moved or written
+    Audit {
+        /// Source folder; repeat for several
+        #[arg(long, required = true, value_hint = ValueHint::DirPath, value_parser = parse_path)]
+        source: Vec<PathBuf>,
+        #[arg(long, value_hint = ValueHint::DirPath, value_parser = parse_path)]
+        dest: PathBuf,
+        #[arg(long, value_hint = ValueHint::Other)]
+        suffixes: String,
+        /// Print the table as JSON instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// List the images in a destination that have identical content, and the bytes the extra
+    /// copies take. Read-only: nothing is moved or deleted
+    DedupeReport {
+        #[arg(long, value_hint = ValueHint::DirPath, value_parser = parse_path)]
+        dest: PathBuf,
+        /// Files to hash at once; 0 (default) uses a few
+        #[arg(long, default_value_t = 0)]
+        workers: usize,
+        /// Also write the groups as JSON to this path
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        report: Option<PathBuf>,
+        #[arg(long, short = 'v')]
+        verbose: bool,
+    },
+    /// Print a script that completes this command's options and folders in a shell, e.g.
+    /// `completions zsh > ~/.zfunc/_photo-suffix-mover`
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct ScanArgs {
+    /// Source folder; repeat for several
+    #[arg(long, required = true, value_hint = ValueHint::DirPath, value_parser = parse_path)]
+    source: Vec<PathBuf>,
+    #[arg(long, value_hint = ValueHint::Other, required_unless_present = "suffixes_file")]
+    suffixes: Option<String>,
+    /// Read the suffixes from this file, written as for --suffixes (e.g. one per line)
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, value_parser = parse_path,
+        conflicts_with = "suffixes")]
+    suffixes_file: Option<PathBuf>,
+    /// Leave out matching files whose name matches this glob, e.g. "thumb_*" (case is
+    /// ignored; repeat for more patterns)
+    #[arg(long = "exclude-glob", value_name = "GLOB", value_hint = ValueHint::Other,
+        value_parser = parse_glob)]
+    exclude_globs: Vec<String>,
+    /// Match images by their file "name" (default), or by the name the camera gave them in
+    /// their "metadata", as a move would
+    #[arg(long, value_enum, default_value_t = engine::MatchBy::Name)]
+    match_by: engine::MatchBy,
+    /// Matches that are symbolic links: left out with "skip" (default), listed otherwise
+    #[arg(long, value_enum, default_value_t = engine::SymlinkPolicy::Skip)]
+    symlinks: engine::SymlinkPolicy,
+    /// Match names exactly as their bytes spell them (see `move --help`)
+    #[arg(long)]
+    no_normalize_unicode: bool,
+    /// List only the first N matches, the ones `move --limit N` would take
+    #[arg(long, value_name = "N")]
+    limit: Option<u64>,
+    /// End each path with a NUL byte instead of a newline, for `xargs -0`
+    #[arg(long, short = '0')]
+    null: bool,
+}
+
 fn main() {
-    let cli = Cli::parse();
-    let run_cli = cli.source.is_some() && cli.dest.is_some() && cli.suffixes.is_some();
+    let command = Cli::command();
+    let (matches, config_path, from_file) = parse_with_config(&command);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| exit_usage(e, false));
+    let explicit = matches!(cli.command, Some(Command::Move(_)));
+    let run = match cli.command {
+        None => cli.run,
+        Some(Command::Move(run)) => *run,
+        Some(Command::Scan(scan)) => scan_files(scan),
+        Some(Command::Verify { report, json }) => verify_report(&report, json),
+        Some(Command::Undo {
+            journal,
+            dry_run,
+            json,
+        }) => undo_run(&journal, dry_run, json),
+        Some(Command::DedupeReport {
+            dest,
+            workers,
+            report,
+            verbose,
+        }) => dedupe_report(&dest, workers, report.as_deref(), verbose),
+        Some(Command::Audit {
+            source,
+            dest,
+            suffixes,
+            json,
+        }) => audit_frames(&source, &dest, &suffixes, json),
+        Some(Command::Completions { shell }) => {
+            write_completions(shell, &mut std::io::stdout());
+            std::process::exit(0);
+        }
+    };
+    if run.print_config {
+        let (command, matches) = move_command(&command, &matches).expect("a move's options");
+        print_config(command, matches, config_path.as_deref(), &from_file);
+    }
+    let runnable = run.resume.is_some()
+        || !run.source.is_empty()
+            && (run.dest.is_some() || run.tag.is_some())
+            && (run.suffixes.is_some() || run.suffixes_file.is_some());
+    if runnable {
+        move_files(run);
+    }
+    if explicit {
+        exit_setup(
+            run.json,
+            "invalid_arguments",
+            "move needs --source, --dest (or --tag) and --suffixes, or --resume",
+            None,
+            EXIT_SETUP,
+        );
+    }
+    photo_suffix_mover::run();
+}
 
-    if run_cli {
-        let source = cli.source.unwrap();
-        let dest = cli.dest.unwrap();
+/// Run a move (or resume one) with the options of `cli`, print its summary and exit.
+fn move_files(mut cli: MoveArgs) -> ! {
+    if let Some(ref path) = cli.suffixes_file {
+        cli.suffixes = Some(read_suffixes_file(path, cli.json));
+    }
+    let control = cancel_on_ctrl_c(cli.json);
+    let output = ProgressOutput::of(&cli);
+    let display = ProgressDisplay::new(output);
+    let result = if let Some(ref state) = cli.resume {
+        match engine::load_state(state) {
+            Ok(_) if cli.json => {}
+            Ok(saved) => println!(
+                "resuming the run of {}: {} of {} file(s) already done",
+                saved.created_at,
+                saved.done.len(),
+                saved.plan.files.len()
+            ),
+            Err(e) => exit_run_error(cli.json, &engine::RunError::from(e)),
+        }
+        engine::resume(state, &control, display.events())
+    } else {
+        let sources = cli.source.clone();
+        // Tagging in place has no destination; the engine uses the first source.
+        let dest = cli.dest.clone().unwrap_or_else(|| sources[0].clone());
         let suffixes = cli.suffixes.unwrap_or_default();
-        if !source.is_dir() {
-            eprintln!("Error: source is not a directory: {}", source.display());
-            std::process::exit(1);
+        if let Some(source) = sources.iter().find(|s| !s.is_dir()) {
+            exit_setup(
+                cli.json,
+                "source_unavailable",
+                format!("source is not a directory: {}", source.display()),
+                Some(source),
+                EXIT_SETUP,
+            );
         }
         if dest.exists() && !dest.is_dir() {
-            eprintln!("Error: dest exists and is not a directory: {}", dest.display());
-            std::process::exit(1);
-        }
-        let cancel = AtomicBool::new(false);
-        let progress: Option<Box<dyn Fn(engine::ProgressEvent) + Send>> = Some(Box::new(|ev: engine::ProgressEvent| {
-            let phase = match &ev.phase {
-                engine::Phase::ScanningSource => "scanning",
-                engine::Phase::IndexingDestination => "indexing",
-                engine::Phase::Moving => "moving",
-                engine::Phase::Done => "done",
-            };
-            if let Some(ref f) = ev.current_file {
-                let short: String = if f.len() > 60 {
-                    format!("...{}", &f[f.len().saturating_sub(57)..])
-                } else {
-                    f.clone()
-                };
-                print!("\r[{}] {}% | moved: {} dup: {} err: {} | {}", phase, ev.percent as u32, ev.moved, ev.skipped_duplicates, ev.errors, short);
+            exit_setup(
+                cli.json,
+                "destination_unwritable",
+                format!("dest exists and is not a directory: {}", dest.display()),
+                Some(&dest),
+                EXIT_SETUP,
+            );
+        }
+        let layout = if let Some(ref t) = cli.dest_template {
+            match engine::DestTemplate::parse(t) {
+                Ok(template) => engine::DestLayout::Template(template),
+                Err(e) => exit_setup(cli.json, "invalid_template", e, None, EXIT_SETUP),
+            }
+        } else if cli.flatten {
+            engine::DestLayout::Flatten
+        } else {
+            engine::DestLayout::Mirror
+        };
+        let options = engine::RunOptions::new(&sources[0], dest, suffixes)
+            .sources(sources)
+            .routes(cli.routes.clone())
+            .rejects(cli.rejects.clone())
+            .dry_run(cli.dry_run)
+            // With --json the console is the output's: --log still gets everything.
+            .verbose(cli.verbose && !cli.json)
+            .layout(layout)
+            .duplicate_action(cli.duplicates)
+            .transfer_mode(cli.mode)
+            .collision_pattern(cli.collision_pattern.clone())
+            .on_collision(cli.on_collision)
+            .tag(cli.tag.clone())
+            .remove_empty_dirs(cli.remove_empty_dirs, cli.junk_as_empty)
+            .verify_copies(!cli.no_verify)
+            .preserve_xattrs(!cli.no_xattrs)
+            .read_only(cli.read_only)
+            .sanitize(cli.sanitize)
+            .symlinks(cli.symlinks)
+            .empty_files(cli.empty_files)
+            .normalize_unicode(!cli.no_normalize_unicode)
+            .workers(cli.workers)
+            .exclude_globs(
+                engine::ExcludeGlobs::new(&cli.exclude_globs).expect("checked by parse_glob"),
+            )
+            .dedup(if cli.no_dedupe {
+                engine::DedupMode::Off
+            } else {
+                cli.dedup
+            })
+            .confirm_duplicates(cli.paranoid)
+            .match_by(cli.match_by)
+            .skip_preflight(cli.skip_preflight)
+            .throttle(cli.throttle.unwrap_or(0))
+            .stall_warning(cli.stall_warning)
+            .fail_fast(cli.fail_fast)
+            .max_consecutive_errors(cli.max_consecutive_errors)
+            .limit(cli.limit.unwrap_or(0))
+            .min_age(cli.min_age.unwrap_or(if cli.watch {
+                engine::DEFAULT_WATCH_MIN_AGE_SECONDS
+            } else {
+                0
+            }))
+            .verify(if cli.verify_hash {
+                engine::VerifyMode::Content
+            } else if cli.verify {
+                engine::VerifyMode::Exists
+            } else {
+                engine::VerifyMode::Off
+            })
+            .report_path(cli.report.clone())
+            .log_path(cli.log.clone())
+            .state_path(cli.state_file.clone());
+        if cli.watch {
+            engine::watch(&options, &control, display.events(), &mut |batch| {
+                if cli.json {
+                    json_line(&JsonLine::Batch(batch));
+                    return;
+                }
+                display.suspend(|| {
+                    println!(
+                        "batch: {} matched, {} moved, {} duplicate(s), {} error(s)",
+                        batch.matched, batch.moved, batch.skipped_duplicates, batch.errors
+                    );
+                    if cli.list_files {
+                        print_files(batch);
+                    }
+                })
+            })
+        } else if !cli.yes && !cli.dry_run && !cli.json && std::io::stdin().is_terminal() {
+            let confirmed = run_confirmed(&options, &control, &display, ask_to_go_ahead);
+            confirmed.map(|result| {
+                result.unwrap_or_else(|| {
+                    println!("Nothing was moved.");
+                    std::process::exit(declined_exit_code(control.is_cancelled()))
+                })
+            })
+        } else {
+            engine::run(&options, &control, display.events())
+        }
+    };
+    display.finish();
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => exit_run_error(cli.json, &e),
+    };
+    if cli.json {
+        json_line(&JsonLine::Result(&result));
+        std::process::exit(exit_code(&result, cli.strict));
+    }
+    if result.cancelled {
+        println!(
+            "cancelled: {} of {} file(s) processed, {} not attempted",
+            result.matched - result.not_attempted,
+            result.matched,
+            result.not_attempted
+        );
+    }
+    if let Some(ref abort) = result.aborted {
+        eprintln!("Error: {}", abort);
+        eprintln!(
+            "stopped: {} of {} file(s) processed, {} not attempted",
+            result.matched - result.not_attempted,
+            result.matched,
+            result.not_attempted
+        );
+    }
+    if result.over_limit > 0 {
+        println!(
+            "limit: {} of {} matched file(s) {}, {} left for a later run",
+            result.matched,
+            result.matched + result.over_limit,
+            if cli.dry_run { "planned" } else { "processed" },
+            result.over_limit
+        );
+    }
+    if let Some(ref state) = result.state_path {
+        println!("to continue later: --resume {}", state.display());
+    }
+    if result.resumed > 0 {
+        println!(
+            "resumed: {} file(s) were done before the interruption",
+            result.resumed
+        );
+    }
+    if let Some(ref e) = result.preflight_error {
+        eprintln!("Error: {}", e);
+        eprintln!("Nothing was moved. Use --skip-preflight to run anyway.");
+        std::process::exit(EXIT_SETUP);
+    }
+    println!(
+        "checked {} file(s) in the sources, {} image(s), {} matched",
+        result.scanned, result.images, result.matched
+    );
+    if cli.match_by == engine::MatchBy::Metadata {
+        println!(
+            "read the original frame name of {} image(s); {} had none and were matched by name",
+            result.numbered, result.unnumbered
+        );
+    }
+    print_stats(&result);
+    if let Some(ref preflight) = result.preflight {
+        if cli.mode == engine::TransferMode::Move {
+            for root in &preflight.cross_volume {
+                println!(
+                    "{} is on another volume: files were copied, verified and removed",
+                    root.display()
+                );
+            }
+        }
+    }
+    if cli.list_files && !cli.watch {
+        print_files(&result);
+    }
+    let verb = if cli.dry_run { "would remove" } else { "removed" };
+    if result.duplicates_removed > 0 {
+        println!("{} {} duplicate source file(s)", verb, result.duplicates_removed);
+    }
+    if result.removed_dirs > 0 {
+        println!("{} {} empty source folder(s)", verb, result.removed_dirs);
+    }
+    if result.verified > 0 {
+        println!("verified {} cross-volume copy(ies)", result.verified);
+    }
+    if result.sanitized > 0 {
+        let verb = if cli.dry_run {
+            "would rename"
+        } else {
+            "renamed"
+        };
+        println!(
+            "{} {} file(s) to names valid on the destination filesystem",
+            verb, result.sanitized
+        );
+    }
+    if cli.read_only {
+        println!(
+            "{} {} file(s) read-only",
+            if cli.dry_run { "would make" } else { "made" },
+            result.read_only
+        );
+    }
+    if result.source_duplicates > 0 {
+        println!(
+            "{} source file(s) were copies of other files in this batch:",
+            result.source_duplicates
+        );
+        for group in &result.duplicate_groups {
+            match &group.dest {
+                Some(d) => println!("  {} -> {}", group.source.display(), d.display()),
+                None => println!("  {}", group.source.display()),
+            }
+            for copy in &group.copies {
+                println!("    same as: {}", copy.display());
+            }
+        }
+    }
+    if result.rejects_found > 0 {
+        println!(
+            "rejects: {} image(s) matched no suffix, {} {}, {} already there",
+            result.rejects_found,
+            if cli.dry_run { "would move" } else { "moved" },
+            result.rejected,
+            result.rejected_duplicates
+        );
+    }
+    if result.replaced > 0 || result.skipped_newer > 0 {
+        println!(
+            "update: {} {} older file(s), kept {} where the destination was not older",
+            if cli.dry_run {
+                "would replace"
+            } else {
+                "replaced"
+            },
+            result.replaced,
+            result.skipped_newer
+        );
+    }
+    if result.hash_mismatches > 0 {
+        println!(
+            "{} file(s) had a duplicate's hash but different content; {} as new files",
+            result.hash_mismatches,
+            if cli.dry_run {
+                "would be moved"
             } else {
-                print!("\r[{}] {}% | moved: {} dup: {} err: {}   ", phase, ev.percent as u32, ev.moved, ev.skipped_duplicates, ev.errors);
+                "moved"
+            }
+        );
+    }
+    if result.source_kept > 0 {
+        println!(
+            "copied {} file(s) but could not remove the source (read-only or locked)",
+            result.source_kept
+        );
+    }
+    if let Some(ref tag) = cli.tag {
+        println!(
+            "{} {} file(s) with \"{}\" in place",
+            if cli.dry_run { "would tag" } else { "tagged" },
+            result.tagged,
+            tag
+        );
+    } else if result.dedup_disabled {
+        println!("duplicates not checked (duplicate detection off)");
+    } else {
+        println!("duplicates checked {}", cli.dedup.describe());
+    }
+    if result.excluded > 0 {
+        println!(
+            "excluded {} matching file(s) by --exclude-glob",
+            result.excluded
+        );
+    }
+    if result.empty_files > 0 {
+        println!(
+            "found {} empty (0-byte) file(s); see the warnings",
+            result.empty_files
+        );
+    }
+    if result.skipped_symlinks > 0 {
+        println!(
+            "skipped {} symbolic link(s) (see --symlinks)",
+            result.skipped_symlinks
+        );
+    }
+    if result.broken_links > 0 {
+        println!("skipped {} broken symbolic link(s)", result.broken_links);
+    }
+    if result.skipped_changed > 0 {
+        println!(
+            "skipped {} file(s) that changed or disappeared after planning",
+            result.skipped_changed
+        );
+    }
+    if result.skipped_busy > 0 {
+        println!(
+            "skipped {} file(s) still being written; run again once they are done:",
+            result.skipped_busy
+        );
+        for path in &result.busy_files {
+            println!("  {}", path.display());
+        }
+    }
+    if let Some(ref v) = result.verification {
+        println!(
+            "verified {} moved file(s) at the destination{}",
+            v.verified,
+            if v.content { " by content" } else { "" }
+        );
+        if v.failed() > 0 {
+            eprintln!(
+                "Error: verification failed: {} missing, {} mismatched",
+                v.missing, v.mismatched
+            );
+        }
+    }
+    let unmatched = result.unmatched_suffixes();
+    if !unmatched.is_empty() {
+        let list: Vec<String> = unmatched.iter().map(u32::to_string).collect();
+        println!("no files found for: {}", list.join(", "));
+    }
+    if result.other_types() > 0 {
+        let by_suffix: Vec<String> = result
+            .suffixes
+            .iter()
+            .filter(|s| s.other_types > 0)
+            .map(|s| format!("{} ×{}", s.suffix, s.other_types))
+            .collect();
+        println!(
+            "{} file(s) matched your numbers but were skipped by the file-type filter ({}): {}",
+            result.other_types(),
+            result.other_type_extensions.join(", "),
+            by_suffix.join(", ")
+        );
+    }
+    print_errors(&result);
+    match (&result.log_path, &cli.log) {
+        (Some(path), _) => println!("log: {}", path.display()),
+        (None, Some(path)) => {
+            eprintln!("Warning: could not write the log file {}", path.display())
+        }
+        (None, None) => {}
+    }
+    std::process::exit(exit_code(&result, cli.strict));
+}
+
+/// The suffixes in the file at `path`; one that cannot be read stops the CLI with
+/// `EXIT_SETUP`.
+fn read_suffixes_file(path: &Path, json: bool) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|e| {
+        exit_setup(
+            json,
+            "suffixes_file_unreadable",
+            format!("cannot read the suffixes file {}: {}", path.display(), e),
+            Some(path),
+            EXIT_SETUP,
+        )
+    })
+}
+
+/// Print the path of every file a move with the options of `scan` would take, and exit.
+fn scan_files(scan: ScanArgs) -> ! {
+    let suffixes = match scan.suffixes_file {
+        Some(ref path) => read_suffixes_file(path, false),
+        None => scan.suffixes.unwrap_or_default(),
+    };
+    let control = cancel_on_ctrl_c(false);
+    // The destination is not looked at; a scan's options need one all the same.
+    let options = engine::RunOptions::new(&scan.source[0], &scan.source[0], suffixes)
+        .sources(scan.source.clone())
+        .exclude_globs(
+            engine::ExcludeGlobs::new(&scan.exclude_globs).expect("checked by parse_glob"),
+        )
+        .match_by(scan.match_by)
+        .symlinks(scan.symlinks)
+        .normalize_unicode(!scan.no_normalize_unicode)
+        .limit(scan.limit.unwrap_or(0));
+    let preview = match engine::preview_scan(&options, usize::MAX, &control) {
+        Ok(preview) => preview,
+        Err(e) => exit_run_error(false, &e),
+    };
+    let mut out = std::io::stdout().lock();
+    for entry in &preview.entries {
+        let written = if scan.null {
+            write!(out, "{}\0", entry.path.display())
+        } else {
+            writeln!(out, "{}", entry.path.display())
+        };
+        // A closed pipe (`| head`) is not an error.
+        if written.is_err() {
+            break;
+        }
+    }
+    let _ = out.flush();
+    for warning in &preview.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    eprintln!(
+        "{} file(s) ({}) matched; checked {} file(s) in the sources, {} image(s)",
+        preview.matched,
+        engine::human_bytes(preview.total_bytes),
+        preview.scanned,
+        preview.images
+    );
+    if preview.over_limit > 0 {
+        eprintln!("limit: {} more match(es) not listed", preview.over_limit);
+    }
+    if preview.cancelled {
+        eprintln!("cancelled: the list is incomplete");
+        std::process::exit(EXIT_CANCELLED);
+    }
+    std::process::exit(0);
+}
+
+/// Check the files of the run that wrote `report` (`engine::verify_report`) and exit:
+/// `EXIT_ERRORS` if any is not at its destination.
+fn verify_report(report: &Path, json: bool) -> ! {
+    let control = cancel_on_ctrl_c(json);
+    let outcome = match engine::verify_report(report, &control) {
+        Ok(outcome) => outcome,
+        Err(e) => exit_setup(json, e.kind(), &e, Some(e.path()), EXIT_SETUP),
+    };
+    let check = &outcome.check;
+    if json {
+        print_json(&outcome);
+    } else {
+        for e in &outcome.errors {
+            eprintln!("Error: {}", e);
+        }
+        println!(
+            "{} file(s) at their destination, {} missing, {} could not be checked",
+            check.verified, check.missing, check.mismatched
+        );
+        if outcome.not_listed > 0 {
+            println!(
+                "{} more file(s) are not listed in the report and were not checked",
+                outcome.not_listed
+            );
+        }
+        if check.not_checked > 0 {
+            println!("cancelled: {} file(s) not checked", check.not_checked);
+        }
+    }
+    std::process::exit(if check.not_checked > 0 {
+        EXIT_CANCELLED
+    } else if check.failed() > 0 {
+        EXIT_ERRORS
+    } else {
+        0
+    });
+}
+
+/// Move the files of the run that wrote `journal` back (`engine::undo`) and exit:
+/// `EXIT_ERRORS` if any could not be.
+fn undo_run(journal: &Path, dry_run: bool, json: bool) -> ! {
+    let control = cancel_on_ctrl_c(json);
+    let undone = match engine::undo(journal, dry_run, &control) {
+        Ok(undone) => undone,
+        Err(e) => exit_setup(json, e.kind(), &e, Some(e.path()), EXIT_SETUP),
+    };
+    if json {
+        print_json(&undone);
+    } else {
+        for e in &undone.errors {
+            eprintln!("Error: {}", e);
+        }
+        println!(
+            "{} {} file(s) to where the run found them",
+            if dry_run {
+                "would move back"
+            } else {
+                "moved back"
+            },
+            undone.restored
+        );
+        if undone.missing + undone.occupied > 0 {
+            println!(
+                "left {} file(s) no longer in the destination and {} whose old place is taken",
+                undone.missing, undone.occupied
+            );
+        }
+        if undone.replaced > 0 {
+            println!(
+                "{} of them had replaced an older file in the destination, which is back in its place",
+                undone.replaced
+            );
+        }
+        if undone.not_listed > 0 {
+            println!(
+                "{} more moved file(s) are not listed in the report and stay in the destination",
+                undone.not_listed
+            );
+        }
+        if undone.cancelled {
+            println!("cancelled: {} file(s) not moved back", undone.not_attempted);
+        }
+    }
+    std::process::exit(if undone.cancelled {
+        EXIT_CANCELLED
+    } else if !undone.errors.is_empty() {
+        EXIT_ERRORS
+    } else {
+        0
+    });
+}
+
+/// `value` as pretty JSON on stdout, as `audit --json` prints.
+fn print_json(value: &impl serde::Serialize) {
+    match serde_json::to_string_pretty(value) {
+        Ok(text) => println!("{}", text),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+/// How the CLI shows a run's progress.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ProgressOutput {
+    /// A progress bar redrawn in place, for a terminal.
+    Bar,
+    /// A plain line now and then, for a log file or pipe (or next to `--verbose`'s lines).
+    Lines,
+    /// Only stall notices (`--quiet`).
+    Quiet,
+    /// `--json` progress snapshots.
+    Json,
+}
+
+impl ProgressOutput {
+    fn of(cli: &MoveArgs) -> ProgressOutput {
+        if cli.json {
+            ProgressOutput::Json
+        } else if cli.quiet {
+            ProgressOutput::Quiet
+        } else if cli.verbose || !std::io::stdout().is_terminal() {
+            ProgressOutput::Lines
+        } else {
+            ProgressOutput::Bar
+        }
+    }
+}
+
+/// Time between two progress lines of `ProgressOutput::Lines` within a phase.
+const PROGRESS_LINE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The CLI's progress output for one run (or one plan): a bar or plain lines and stall notices
+/// for people, or `--json` progress snapshots. The bar stays on screen once finished, above
+/// the summary.
+struct ProgressDisplay {
+    output: ProgressOutput,
+    bar: ProgressBar,
+}
+
+impl ProgressDisplay {
+    fn new(output: ProgressOutput) -> ProgressDisplay {
+        let bar = match output {
+            ProgressOutput::Bar => {
+                let bar = ProgressBar::with_draw_target(Some(100), ProgressDrawTarget::stdout());
+                bar.set_style(
+                    ProgressStyle::with_template("{prefix:>9} [{bar:25}] {percent:>3}% {wide_msg}")
+                        .expect("valid progress template")
+                        .progress_chars("=> "),
+                );
+                bar
+            }
+            _ => ProgressBar::hidden(),
+        };
+        ProgressDisplay { output, bar }
+    }
+
+    /// The progress callback to give the engine.
+    fn events(&self) -> Option<engine::ProgressFn> {
+        let output = self.output;
+        if output == ProgressOutput::Json {
+            let throttle = Mutex::new(engine::ProgressThrottle::new());
+            return Some(Box::new(move |ev| json_progress(ev, &throttle)));
+        }
+        let bar = self.bar.clone();
+        // The phase of the last plain line and when it was printed.
+        let last_line: Mutex<Option<(engine::Phase, Instant)>> = Mutex::new(None);
+        Some(Box::new(move |ev: engine::ProgressEvent| {
+            if let Some(ref s) = ev.stalled {
+                let doing = match ev.phase {
+                    engine::Phase::Planning => "reading",
+                    _ => "copying",
+                };
+                let notice = format!(
+                    "still {} {} ({})",
+                    doing,
+                    s.path,
+                    elapsed_text(s.elapsed_seconds)
+                );
+                match output {
+                    ProgressOutput::Bar => bar.println(notice),
+                    _ => println!("{}", notice),
+                }
+                return;
+            }
+            match output {
+                ProgressOutput::Bar => {
+                    bar.set_prefix(phase_name(ev.phase));
+                    bar.set_position(ev.percent as u64);
+                    bar.set_message(progress_message(&ev));
+                }
+                ProgressOutput::Lines => {
+                    let now = Instant::now();
+                    let mut last = last_line.lock().unwrap_or_else(|e| e.into_inner());
+                    let due = match *last {
+                        Some((phase, at)) => {
+                            phase != ev.phase
+                                || matches!(ev.phase, engine::Phase::Done)
+                                || now.duration_since(at) >= PROGRESS_LINE_INTERVAL
+                        }
+                        None => true,
+                    };
+                    if due {
+                        *last = Some((ev.phase, now));
+                        println!(
+                            "[{}] {}% | {}",
+                            phase_name(ev.phase),
+                            ev.percent as u32,
+                            progress_message(&ev)
+                        );
+                    }
+                }
+                ProgressOutput::Quiet | ProgressOutput::Json => {}
+            }
+        }))
+    }
+
+    /// Run `print` with the bar out of the way, for output in the middle of a run.
+    fn suspend<R>(&self, print: impl FnOnce() -> R) -> R {
+        self.bar.suspend(print)
+    }
+
+    /// Leave the bar as it is and move below it, before printing a summary.
+    fn finish(&self) {
+        if !self.bar.is_hidden() {
+            self.bar.finish();
+        }
+    }
+}
+
+fn phase_name(phase: engine::Phase) -> &'static str {
+    match phase {
+        engine::Phase::ScanningSource => "scanning",
+        engine::Phase::IndexingDestination => "indexing",
+        engine::Phase::Planning => "planning",
+        engine::Phase::Moving => "moving",
+        engine::Phase::Verifying => "verifying",
+        engine::Phase::Watching => "watching",
+        engine::Phase::Done => "done",
+    }
+}
+
+/// The counts of `ev` after the percent: files checked while scanning, else the files done,
+/// the bytes and the current file.
+fn progress_message(ev: &engine::ProgressEvent) -> String {
+    if matches!(ev.phase, engine::Phase::ScanningSource) {
+        return format!("{} file(s) checked, {} image(s)", ev.scanned, ev.images);
+    }
+    let mut message = format!(
+        "moved: {} dup: {} err: {}{}",
+        ev.moved,
+        ev.skipped_duplicates,
+        ev.errors,
+        byte_summary(ev)
+    );
+    if let Some(ref f) = ev.current_file {
+        message.push_str(" | ");
+        message.push_str(&engine::shorten_path(f, MAX_SHOWN_PATH));
+    }
+    message
+}
+
+/// Columns of a path shown in progress output.
+const MAX_SHOWN_PATH: usize = 60;
+
+/// Plan the run and execute the plan once `confirm` agrees to its preview; `None` when it
+/// declined or the run was cancelled meanwhile, before anything is moved or created. A plan
+/// with nothing to do is executed without asking, for its summary.
+fn run_confirmed(
+    options: &engine::RunOptions,
+    control: &engine::RunControl,
+    display: &ProgressDisplay,
+    confirm: impl FnOnce(&str) -> bool,
+) -> Result<Option<engine::RunResult>, engine::RunError> {
+    let planning_display = ProgressDisplay::new(display.output);
+    // Planning's final event would show the predicted counts as done.
+    let planning = planning_display
+        .events()
+        .map(|progress| -> engine::ProgressFn {
+            Box::new(move |ev| {
+                if !matches!(ev.phase, engine::Phase::Done) {
+                    progress(ev)
+                }
+            })
+        });
+    let plan = engine::plan(options, control, planning);
+    planning_display.finish();
+    let plan = plan?;
+    if !plan.complete {
+        return Ok(Some(plan.preview));
+    }
+    if let Some(preview) = plan_preview(&plan) {
+        if !confirm(&preview) || control.is_cancelled() {
+            return Ok(None);
+        }
+    }
+    Ok(Some(engine::execute(&plan, control, display.events())))
+}
+
+/// Print `preview` and ask on the terminal whether to go ahead.
+fn ask_to_go_ahead(preview: &str) -> bool {
+    println!("{}", preview);
+    print!("Go ahead? [y/N] ");
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+    let mut answer = String::new();
+    let _ = std::io::stdin().read_line(&mut answer);
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// What executing `plan` would do, in a sentence: "37 file(s) (1.8 GB) will be moved from X
+/// to Y; 3 look like duplicates"; `None` when it would change nothing.
+fn plan_preview(plan: &engine::MovePlan) -> Option<String> {
+    let options = &plan.options;
+    let move_options = &options.move_options;
+    // Routes are numbered from the destination, the rejects folder last.
+    let rejects_route = options.rejects.as_ref().map(|_| options.routes.len() + 1);
+    let (mut moves, mut bytes, mut rejects, mut duplicates, mut left) = (0, 0, 0, 0, 0);
+    for file in &plan.files {
+        match file.action {
+            engine::PlannedAction::Move if Some(file.route) == rejects_route => rejects += 1,
+            engine::PlannedAction::Move => {
+                moves += 1;
+                bytes += file.size;
+            }
+            engine::PlannedAction::Duplicate => duplicates += 1,
+            _ => left += 1,
+        }
+    }
+    let removes = move_options.duplicate_action != engine::DuplicateAction::Leave;
+    if moves == 0 && rejects == 0 && (duplicates == 0 || !removes) {
+        return None;
+    }
+
+    let sources = options
+        .sources
+        .iter()
+        .map(|s| s.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut preview = if move_options.tag.is_some() {
+        format!(
+            "{} file(s) ({}) will be renamed with the tag where they are, in {}",
+            moves,
+            engine::human_bytes(bytes),
+            sources
+        )
+    } else {
+        let verb = match move_options.transfer_mode {
+            engine::TransferMode::Move => "moved",
+            engine::TransferMode::Copy => "copied",
+            engine::TransferMode::Hardlink => "hard-linked",
+            engine::TransferMode::Reflink => "cloned",
+        };
+        let others = match options.routes.len() {
+            0 => String::new(),
+            n => format!(" and {} other folder(s)", n),
+        };
+        format!(
+            "{} file(s) ({}) will be {} from {} to {}{}",
+            moves,
+            engine::human_bytes(bytes),
+            verb,
+            sources,
+            options.dest.display(),
+            others
+        )
+    };
+    if duplicates > 0 {
+        let fate = match move_options.duplicate_action {
+            engine::DuplicateAction::Leave => "",
+            engine::DuplicateAction::Delete => ", which will be deleted",
+            engine::DuplicateAction::Trash => ", which will be moved to the trash",
+        };
+        preview += &format!("; {} look like duplicates{}", duplicates, fate);
+    }
+    if let (true, Some(dir)) = (rejects > 0, &options.rejects) {
+        preview += &format!("; {} reject(s) will go to {}", rejects, dir.display());
+    }
+    if left > 0 {
+        preview += &format!("; {} cannot be moved or will be left alone", left);
+    }
+    Some(preview)
+}
+
+/// Print where each frame of `suffixes` is (`engine::locate_frames`) and exit: `EXIT_ERRORS`
+/// if a file could not be compared.
+fn audit_frames(sources: &[PathBuf], dest: &std::path::Path, suffixes: &str, json: bool) -> ! {
+    let control = cancel_on_ctrl_c(false);
+    let audit = match engine::locate_frames(sources, dest, suffixes, &control) {
+        Ok(audit) => audit,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(EXIT_SETUP);
+        }
+    };
+    if json {
+        print_json(&audit);
+    } else {
+        println!("{:>8}  {:<26} source  dest", "suffix", "where");
+        for frame in &audit.frames {
+            let place = match frame.identical {
+                Some(true) => format!("{}, identical", frame.presence.describe()),
+                Some(false) => format!("{}, different", frame.presence.describe()),
+                None => frame.presence.describe().to_string(),
+            };
+            println!(
+                "{:>8}  {:<26} {:>6}  {:>4}",
+                frame.suffix,
+                place,
+                frame.source_files.len(),
+                frame.dest_files.len()
+            );
+            for path in frame.source_files.iter().chain(&frame.dest_files) {
+                println!("          {}", path.display());
+            }
+        }
+        println!(
+            "{} in both ({} identical), {} in the sources only, {} in the destination only, {} missing",
+            audit.count(engine::Presence::Both),
+            audit.identical(),
+            audit.count(engine::Presence::SourceOnly),
+            audit.count(engine::Presence::DestinationOnly),
+            audit.count(engine::Presence::Missing)
+        );
+        if audit.dest_missing {
+            println!("destination {} does not exist", dest.display());
+        }
+    }
+    for e in &audit.errors {
+        eprintln!("Error: {}", e);
+    }
+    if audit.cancelled {
+        println!("cancelled: the frames not compared yet show no result");
+        std::process::exit(EXIT_CANCELLED);
+    }
+    std::process::exit(if audit.errors.is_empty() {
+        0
+    } else {
+        EXIT_ERRORS
+    });
+}
+
+/// The completion script for `shell`, for the binary's own name.
+fn write_completions(shell: clap_complete::Shell, out: &mut dyn std::io::Write) {
+    clap_complete::generate(shell, &mut Cli::command(), env!("CARGO_BIN_NAME"), out);
+}
+
+/// Audit `dest` for identical files, print the groups (largest waste first) and exit.
+fn dedupe_report(
+    dest: &std::path::Path,
+    workers: usize,
+    report: Option<&std::path::Path>,
+    verbose: bool,
+) -> ! {
+    if !dest.is_dir() {
+        eprintln!("Error: dest is not a directory: {}", dest.display());
+        std::process::exit(EXIT_SETUP);
+    }
+    let control = cancel_on_ctrl_c(false);
+    let progress: Option<Box<dyn Fn(engine::ProgressEvent) + Send>> =
+        Some(Box::new(|ev: engine::ProgressEvent| {
+            if matches!(ev.phase, engine::Phase::IndexingDestination) {
+                print!(
+                    "\r[hashing] {:.0}% | {} of {}{}   ",
+                    ev.percent,
+                    ev.current_index,
+                    ev.total_count,
+                    byte_summary(&ev)
+                );
+                let _ = std::io::Write::flush(&mut std::io::stdout());
             }
-            let _ = std::io::Write::flush(&mut std::io::stdout());
         }));
-        let result = engine::run(
-            &source,
-            &dest,
-            &suffixes,
-            cli.dry_run,
-            cli.verbose,
-            &cancel,
-            progress,
+    let audit = engine::audit(dest, workers, verbose, &control, progress);
+    println!();
+    for group in &audit.groups {
+        println!(
+            "{} copies of {} ({} wasted), sha256 {}",
+            group.paths.len(),
+            engine::human_bytes(group.size),
+            engine::human_bytes(group.wasted_bytes()),
+            &group.hash[..12]
         );
-        println!();
-        if result.errors > 0 {
-            std::process::exit(1);
+        for path in &group.paths {
+            println!("  {}", path.display());
+        }
+    }
+    println!(
+        "{} duplicate file(s) in {} group(s), {} wasted; {} image(s), {} in {}",
+        audit.duplicate_files,
+        audit.groups.len(),
+        engine::human_bytes(audit.wasted_bytes),
+        audit.files,
+        engine::human_bytes(audit.bytes),
+        dest.display()
+    );
+    for e in &audit.errors {
+        eprintln!("Error: {}", e);
+    }
+    if let Some(path) = report {
+        let written = serde_json::to_string_pretty(&audit)
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(path, json));
+        match written {
+            Ok(()) => println!("report: {}", path.display()),
+            Err(e) => eprintln!("Warning: cannot write report {}: {}", path.display(), e),
         }
-        std::process::exit(0);
     }
+    if audit.cancelled {
+        println!("cancelled: only the files hashed so far are grouped");
+        std::process::exit(EXIT_CANCELLED);
+    }
+    std::process::exit(if audit.errors.is_empty() {
+        0
+    } else {
+        EXIT_ERRORS
+    });
+}
 
-    photo_suffix_mover::run();
+/// " | 4.2 GB of 18.7 GB at 85.3 MB/s, ~6 min left" for the progress line; empty when sizes
+/// are unknown.
+fn byte_summary(ev: &engine::ProgressEvent) -> String {
+    if ev.total_bytes == 0 {
+        return String::new();
+    }
+    let mut out = format!(
+        " | {} of {}",
+        engine::human_bytes(ev.bytes_done),
+        engine::human_bytes(ev.total_bytes)
+    );
+    if ev.throughput > 0.0 && !matches!(ev.phase, engine::Phase::Done) {
+        out.push_str(&format!(
+            " at {}/s",
+            engine::human_bytes(ev.throughput as u64)
+        ));
+    }
+    if let Some(secs) = ev
+        .eta_seconds
+        .filter(|_| !matches!(ev.phase, engine::Phase::Done))
+    {
+        out.push_str(&match secs {
+            0..=59 => format!(", ~{} s left", secs),
+            60..=3599 => format!(", ~{} min left", secs.div_ceil(60)),
+            _ => format!(", ~{} h {} min left", secs / 3600, secs % 3600 / 60),
+        });
+    }
+    out
+}
+
+/// "45 s" or "2 min": how long a stalled file has taken.
+fn elapsed_text(secs: u64) -> String {
+    if secs < 60 {
+        format!("{} s", secs)
+    } else {
+        format!("{} min", secs / 60)
+    }
+}
+
+/// One --exclude-glob pattern, checked on its own so the error names it.
+fn parse_glob(input: &str) -> Result<String, String> {
+    engine::ExcludeGlobs::new([input])
+        .map(|_| input.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// One --route rule: suffixes, "=", then the folder (which may itself contain "=").
+fn parse_route(input: &str) -> Result<engine::Route, String> {
+    match input.split_once('=') {
+        Some((suffixes, dest)) if !suffixes.trim().is_empty() && !dest.trim().is_empty() => {
+            Ok(engine::Route::new(suffixes.trim(), parse_path(dest)?))
+        }
+        _ => Err(format!(
+            "expected SUFFIXES=DIR, e.g. \"7600-7650=/archive/wedding\", not {:?}",
+            input
+        )),
+    }
+}
+
+/// A folder as given, cleaned up (quotes, `file://` URLs, `~`; see `engine::normalize_path`)
+/// and relative to the current directory.
+fn parse_path(input: &str) -> Result<PathBuf, String> {
+    let base = std::env::current_dir().map_err(|e| format!("no current directory: {}", e))?;
+    engine::normalize_path(input, &base).map_err(|e| e.to_string())
+}
+
+/// A byte rate like "50M", "1.5G", "800k" or "2000000" (an optional "B" or "/s" is ignored).
+fn parse_rate(input: &str) -> Result<u64, String> {
+    let s = input.trim();
+    let s = s.strip_suffix("/s").unwrap_or(s);
+    let s = s.strip_suffix(['B', 'b']).unwrap_or(s);
+    let (number, unit) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&s[..i], c.to_ascii_uppercase()),
+        _ => (s, ' '),
+    };
+    let scale = match unit {
+        ' ' => 1.0,
+        'K' => 1e3,
+        'M' => 1e6,
+        'G' => 1e9,
+        _ => return Err(format!("unknown unit in {:?}; use K, M or G", input)),
+    };
+    match number.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() && n >= 0.0 => Ok((n * scale) as u64),
+        _ => Err(format!("not a byte rate: {:?}", input)),
+    }
+}
+
+/// Exit code of a run that finished, but with errors: files that could not be moved or
+/// verified (with `--strict`, also sources that could not be removed), or a run stopped by
+/// `--fail-fast` or `--max-consecutive-errors`.
+const EXIT_ERRORS: i32 = 1;
+
+/// Exit code of a cancelled run (as for a shell's Ctrl-C), errors or not: the files it did
+/// not get to are still where they were.
+const EXIT_CANCELLED: i32 = 130;
+
+/// Exit code when the preview before moving was answered with no. Nothing was moved.
+const EXIT_DECLINED: i32 = 4;
+
+/// Exit code of a run that could not start: invalid arguments or configuration, bad suffixes,
+/// an unusable source or destination, a failed scan or pre-flight check or an unusable run
+/// state. Nothing was moved.
+const EXIT_SETUP: i32 = 3;
+
+/// Options a configuration file cannot set: they are about the file, or about a single run.
+const NOT_CONFIGURABLE: &[&str] = &[
+    "config",
+    "no-config",
+    "print-config",
+    "resume",
+    "help",
+    "version",
+];
+
+/// Parse the command line, with the options of the configuration file (`--config`, else
+/// `config::default_path` if there is one) that it does not set itself or conflict with one
+/// it sets: those the command line gives override the file. Returns the file read and the ids
+/// of the options taken from it. A file that cannot be used stops the CLI with `EXIT_SETUP`.
+/// Only a move (with `move` or without a subcommand) takes the file.
+fn parse_with_config(
+    command: &clap::Command,
+) -> (clap::ArgMatches, Option<PathBuf>, HashSet<String>) {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    merge_config(command, args, config::default_path())
+}
+
+/// `parse_with_config` for the command line `args`, with `default_config` as the file to
+/// read without `--config`. Without any arguments the app opens, and no file is read: a job's
+/// file would otherwise make a plain launch run that job.
+fn merge_config(
+    command: &clap::Command,
+    args: Vec<OsString>,
+    default_config: Option<PathBuf>,
+) -> (clap::ArgMatches, Option<PathBuf>, HashSet<String>) {
+    let json = args.iter().any(|arg| arg == "--json");
+    let matches = command
+        .clone()
+        .try_get_matches_from(&args)
+        .unwrap_or_else(|e| exit_usage(e, json));
+    let mut from_file = HashSet::new();
+    if args.len() <= 1 {
+        return (matches, None, from_file);
+    }
+    let Some((target, target_matches)) = move_command(command, &matches) else {
+        return (matches, None, from_file);
+    };
+    if target_matches.get_flag("no_config") {
+        return (matches, None, from_file);
+    }
+    let path = match target_matches.get_one::<PathBuf>("config") {
+        Some(path) => path.clone(),
+        None => match default_config.filter(|path| path.is_file()) {
+            Some(path) => path,
+            None => return (matches, None, from_file),
+        },
+    };
+    let failed = |e: ConfigError| -> ! { exit_setup(json, e.kind(), &e, Some(&path), EXIT_SETUP) };
+    let entries = config::load_config(&path).unwrap_or_else(|e| failed(e));
+
+    let given = |arg: &clap::Arg| {
+        target_matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+    };
+    let mut extra = Vec::new();
+    for entry in &entries {
+        let key = entry.key.as_str();
+        let invalid =
+            |message: &str| -> ! { failed(ConfigError::key(&path, key, entry.line, message)) };
+        let Some(arg) = target
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(key) && !NOT_CONFIGURABLE.contains(&key))
+        else {
+            invalid("is not an option that can be configured");
+        };
+        // Conflicts are declared on one of the two options, so look both ways.
+        let conflicts = |a: &clap::Arg, b: &clap::Arg| {
+            target
+                .get_arg_conflicts_with(a)
+                .iter()
+                .any(|c| c.get_id() == b.get_id())
+        };
+        let conflicting = target
+            .get_arguments()
+            .any(|other| given(other) && (conflicts(arg, other) || conflicts(other, arg)));
+        if given(arg) || conflicting {
+            continue;
+        }
+        let flag = matches!(arg.get_action(), ArgAction::SetTrue);
+        let repeatable = matches!(arg.get_action(), ArgAction::Append);
+        let values = match &entry.value {
+            ConfigValue::Flag(on) if flag => {
+                extra.extend(on.then(|| OsString::from(format!("--{}", key))));
+                from_file.insert(arg.get_id().to_string());
+                continue;
+            }
+            _ if flag => invalid("must be true or false"),
+            ConfigValue::Flag(_) => invalid("needs a value, not true or false"),
+            ConfigValue::List(items) if repeatable => items.clone(),
+            ConfigValue::List(_) => invalid("takes one value, not a list"),
+            ConfigValue::Text(text) => vec![text.clone()],
+        };
+        for value in values {
+            let value = OsString::from(format!("--{}={}", key, value));
+            // The option on its own, for its parser's error with the key's line.
+            let alone = [args[0].clone(), value.clone()];
+            if let Err(e) = command.clone().try_get_matches_from(alone) {
+                invalid(&format!("is invalid: {}", usage_message(&e)));
+            }
+            extra.push(value);
+        }
+        from_file.insert(arg.get_id().to_string());
+    }
+    let matches = command
+        .clone()
+        .try_get_matches_from(args.into_iter().chain(extra))
+        .unwrap_or_else(|e| exit_usage(e, json));
+    (matches, Some(path), from_file)
+}
+
+/// The command whose options run a move, with its matches: the top level without a
+/// subcommand, or `move`; `None` for the other subcommands, which take no configuration.
+fn move_command<'a>(
+    command: &'a clap::Command,
+    matches: &'a clap::ArgMatches,
+) -> Option<(&'a clap::Command, &'a clap::ArgMatches)> {
+    match matches.subcommand() {
+        None => Some((command, matches)),
+        Some(("move", sub)) => Some((command.find_subcommand("move")?, sub)),
+        Some(_) => None,
+    }
+}
+
+/// A command line clap rejects: its error, as a "setup_error" line with `--json`, and
+/// `EXIT_SETUP`. `--help` and `--version` print and exit as usual.
+fn exit_usage(error: clap::Error, json: bool) -> ! {
+    if !error.use_stderr() {
+        error.exit();
+    }
+    if json {
+        exit_setup(
+            true,
+            "invalid_arguments",
+            usage_message(&error),
+            None,
+            EXIT_SETUP,
+        );
+    }
+    let _ = error.print();
+    std::process::exit(EXIT_SETUP);
+}
+
+/// clap's message without its "error: " and advice, on one line.
+fn usage_message(error: &clap::Error) -> String {
+    let message = error.to_string();
+    let message = message.trim_start_matches("error: ");
+    let message = message.split("\n\n").next().unwrap_or_default();
+    message.replace("\n ", "")
+}
+
+/// `--print-config`: the options in effect, as a configuration file would give them, each
+/// with where it came from; then exit.
+fn print_config(
+    command: &clap::Command,
+    matches: &clap::ArgMatches,
+    path: Option<&Path>,
+    from_file: &HashSet<String>,
+) -> ! {
+    match path {
+        Some(path) => println!("# configuration file: {}", path.display()),
+        None => println!("# no configuration file"),
+    }
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        let Some(key) = arg.get_long().filter(|key| !NOT_CONFIGURABLE.contains(key)) else {
+            continue;
+        };
+        let Some(raw) = matches.get_raw(id) else {
+            continue;
+        };
+        let mut values = raw.map(|value| {
+            let value = value.to_string_lossy();
+            match value.parse::<i64>() {
+                Ok(n) => toml::Value::Integer(n),
+                Err(_) => toml::Value::String(value.into_owned()),
+            }
+        });
+        let value = match arg.get_action() {
+            ArgAction::SetTrue => toml::Value::Boolean(matches.get_flag(id)),
+            ArgAction::Append => toml::Value::Array(values.collect()),
+            _ => match values.next() {
+                Some(value) => value,
+                None => continue,
+            },
+        };
+        let source = if from_file.contains(id) {
+            "configuration file"
+        } else if matches.value_source(id) == Some(ValueSource::CommandLine) {
+            "command line"
+        } else {
+            "default"
+        };
+        println!("{} = {}  # {}", key, value, source);
+    }
+    std::process::exit(0);
+}
+
+/// Exit code of a run that went ahead: `EXIT_CANCELLED` when cancelled, else `EXIT_SETUP`
+/// for a failed pre-flight check, else `EXIT_ERRORS` with errors or files that failed the check
+/// after moving (with `strict`, also sources that could not be removed), else 0.
+fn exit_code(result: &engine::RunResult, strict: bool) -> i32 {
+    let unverified = result.verification.as_ref().map_or(0, |v| v.failed());
+    if result.cancelled {
+        EXIT_CANCELLED
+    } else if result.preflight_error.is_some() {
+        EXIT_SETUP
+    } else if result.errors > 0 || unverified > 0 || (strict && result.source_kept > 0) {
+        EXIT_ERRORS
+    } else {
+        0
+    }
+}
+
+/// Exit code of a run declined at the confirmation: `EXIT_CANCELLED` when it was cancelled
+/// meanwhile, else `EXIT_DECLINED`.
+fn declined_exit_code(cancelled: bool) -> i32 {
+    if cancelled {
+        EXIT_CANCELLED
+    } else {
+        EXIT_DECLINED
+    }
+}
+
+/// The control of the CLI's run, cancelled by Ctrl-C: the run stops at the next file or copy
+/// chunk (a partial copy is removed) and prints what it did. A second Ctrl-C exits at once.
+fn cancel_on_ctrl_c(json: bool) -> std::sync::Arc<engine::RunControl> {
+    let control = std::sync::Arc::new(engine::RunControl::new());
+    let on_interrupt = control.clone();
+    let handler = ctrlc::set_handler(move || {
+        if on_interrupt.is_cancelled() {
+            std::process::exit(EXIT_CANCELLED);
+        }
+        on_interrupt.cancel();
+        if !json {
+            eprintln!("\ncancelling (Ctrl-C again to quit at once)");
+        }
+    });
+    if let Err(e) = handler {
+        let message = format!("cannot handle Ctrl-C: {}", e);
+        if json {
+            json_line(&JsonLine::Warning { message: &message });
+        } else {
+            eprintln!("Warning: {}", message);
+        }
+    }
+    control
+}
+
+/// One line of `--json` output, tagged with its "type"; fields are named as in the app's
+/// events (camelCase).
+#[derive(serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JsonLine<'a> {
+    /// On stderr; its `file`, `error` and `warning` are lines of their own.
+    Progress(&'a engine::ProgressEvent),
+    /// A file moved, skipped as a duplicate or failed (tagged with its "kind"); none in a
+    /// dry-run, whose result lists what would be done in `files`.
+    File(&'a engine::FileEvent),
+    Error(&'a engine::ErrorDetail),
+    Warning {
+        message: &'a str,
+    },
+    /// A watch's batch of new files.
+    Batch(&'a engine::RunResult),
+    Result(&'a engine::RunResult),
+    /// The run could not start; see `RunError::kind`.
+    SetupError {
+        kind: &'static str,
+        message: String,
+        path: Option<&'a Path>,
+    },
+}
+
+impl JsonLine<'_> {
+    /// Whether the line goes to stderr: progress does, the rest goes to stdout.
+    fn on_stderr(&self) -> bool {
+        matches!(self, JsonLine::Progress(_))
+    }
+}
+
+/// Print `line` on its stream (see `JsonLine::on_stderr`).
+fn json_line(line: &JsonLine) {
+    let text = serde_json::to_string(line).expect("output lines serialize");
+    if line.on_stderr() {
+        eprintln!("{}", text);
+    } else {
+        println!("{}", text);
+    }
+}
+
+/// `--json` progress: the file, error and warning of `ev` as lines of their own at once, and
+/// the counts when `throttle` lets them through.
+fn json_progress(mut ev: engine::ProgressEvent, throttle: &Mutex<engine::ProgressThrottle>) {
+    if let Some(file) = ev.file.take() {
+        json_line(&JsonLine::File(&file));
+    }
+    if let Some(error) = ev.error.take() {
+        json_line(&JsonLine::Error(&error));
+    }
+    if let Some(message) = ev.warning.take() {
+        json_line(&JsonLine::Warning { message: &message });
+    }
+    let mut throttle = throttle.lock().unwrap_or_else(|e| e.into_inner());
+    if throttle.admit(&ev, Instant::now()) {
+        json_line(&JsonLine::Progress(&ev));
+    }
+}
+
+/// Report a run that could not start and exit with `code`: "Error: …" on stderr, or with
+/// `--json` a "setup_error" line.
+fn exit_setup(
+    json: bool,
+    kind: &'static str,
+    message: impl std::fmt::Display,
+    path: Option<&Path>,
+    code: i32,
+) -> ! {
+    if json {
+        json_line(&JsonLine::SetupError {
+            kind,
+            message: message.to_string(),
+            path,
+        });
+    } else {
+        eprintln!("Error: {}", message);
+    }
+    std::process::exit(code);
+}
+
+fn exit_run_error(json: bool, error: &engine::RunError) -> ! {
+    exit_setup(json, error.kind(), error, error.path(), EXIT_SETUP)
+}
+
+/// Errors listed after a run; the rest are summed up (the report has them all).
+const MAX_LISTED_ERRORS: usize = 20;
+
+fn print_errors(result: &engine::RunResult) {
+    if result.error_details.is_empty() {
+        return;
+    }
+    eprintln!("errors:");
+    for e in result.error_details.iter().take(MAX_LISTED_ERRORS) {
+        eprintln!("  {}", e);
+    }
+    let more = result.error_details.len().saturating_sub(MAX_LISTED_ERRORS);
+    if more > 0 {
+        eprintln!("  ... and {} more (see --report for the full list)", more);
+    }
+}
+
+/// Data volume and time: "moved 1.2 GB (300.0 MB skipped as duplicates) in 4 min 05 s (scan
+/// ..., index ..., plan ..., move ...)".
+fn print_stats(result: &engine::RunResult) {
+    let stats = &result.stats;
+    let phases = &stats.phases;
+    println!(
+        "{} {} ({} skipped as duplicates) in {} (scan {}, index {}, plan {}, move {}{})",
+        if result.dry_run {
+            "would move"
+        } else {
+            "moved"
+        },
+        engine::human_bytes(stats.bytes_moved),
+        engine::human_bytes(stats.bytes_skipped_duplicates),
+        engine::human_duration(stats.duration_ms),
+        engine::human_duration(phases.scan_ms),
+        engine::human_duration(phases.index_ms),
+        engine::human_duration(phases.plan_ms),
+        engine::human_duration(phases.move_ms),
+        match result.verification {
+            Some(_) => format!(", verify {}", engine::human_duration(phases.verify_ms)),
+            None => String::new(),
+        }
+    );
+    if stats.dest_indexed > 0 {
+        println!(
+            "destination: {} image(s), {} hashed (only sizes shared with a match are read)",
+            stats.dest_indexed, stats.dest_hashed
+        );
+        if stats.dest_reused > 0 {
+            println!(
+                "  {} hash(es) known from an earlier run were not read again",
+                stats.dest_reused
+            );
+        }
+    }
+}
+
+/// One line per file: action, source, destination and detail.
+fn print_files(result: &engine::RunResult) {
+    let prefix = if result.dry_run { "would be " } else { "" };
+    for f in &result.files {
+        let mut line = format!("{}{:<11} {}", prefix, f.action.as_str(), f.source.display());
+        if let Some(ref d) = f.dest {
+            line.push_str(&format!(" -> {}", d.display()));
+        }
+        if let Some(ref e) = f.existing {
+            line.push_str(&format!(" — already at {}", e.display()));
+        }
+        if let Some(ref detail) = f.detail {
+            line.push_str(&format!(" ({})", detail));
+        }
+        println!("{}", line);
+    }
+    if result.files_omitted > 0 {
+        println!("... and {} more file(s) not listed", result.files_omitted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_completions_for_every_shell() {
+        for shell in clap_complete::Shell::value_variants() {
+            let mut script = Vec::new();
+            write_completions(*shell, &mut script);
+            assert!(!script.is_empty(), "no script for {}", shell);
+        }
+        // Folders complete as folders; suffixes are not file names.
+        let mut zsh = Vec::new();
+        write_completions(clap_complete::Shell::Zsh, &mut zsh);
+        let zsh = String::from_utf8(zsh).unwrap();
+        let line = |flag: &str| {
+            zsh.lines()
+                .find(|line| line.contains(flag))
+                .unwrap_or_default()
+                .to_string()
+        };
+        assert!(line("--source=").contains("_files -/"));
+        assert!(!line("--suffixes=").contains("_files"));
+    }
+
+    #[test]
+    fn test_bare_options_move_as_move_does() {
+        Cli::command().debug_assert();
+        let args = [
+            "--source",
+            "/cards/A",
+            "--dest",
+            "/archive",
+            "--suffixes",
+            "7612",
+        ];
+        let bare = Cli::try_parse_from(["fm"].iter().chain(&args)).unwrap();
+        assert!(bare.command.is_none());
+        assert_eq!(bare.run.dest.as_deref(), Some(Path::new("/archive")));
+        let sub = Cli::try_parse_from(["fm", "move"].iter().chain(&args)).unwrap();
+        match sub.command {
+            Some(Command::Move(run)) => assert_eq!(run.dest, bare.run.dest),
+            other => panic!("not a move: {:?}", other),
+        }
+        // Options go with their subcommand, and each takes only its own.
+        assert!(Cli::try_parse_from(["fm", "--dry-run", "scan", "--source", "/a"]).is_err());
+        assert!(Cli::try_parse_from(["fm", "scan", "--source", "/a", "--suffixes", "1"]).is_ok());
+        assert!(Cli::try_parse_from([
+            "fm",
+            "scan",
+            "--source",
+            "/a",
+            "--suffixes",
+            "1",
+            "--dest",
+            "/b"
+        ])
+        .is_err());
+        let undo = Cli::try_parse_from(["fm", "undo", "/archive/report.json", "--dry-run"]);
+        assert!(matches!(
+            undo.unwrap().command,
+            Some(Command::Undo { dry_run: true, .. })
+        ));
+    }
+
+    #[test]
+    fn test_json_lines_are_tagged_and_named_as_in_the_app() {
+        let value = |line: &JsonLine| serde_json::to_value(line).unwrap();
+        let (card, archive) = ("/cards/A/IMG_7612.JPG", "/archive/IMG_7612.JPG");
+
+        let progress = engine::ProgressEvent {
+            phase: engine::Phase::Moving,
+            bytes_done: 5,
+            ..Default::default()
+        };
+        let line = value(&JsonLine::Progress(&progress));
+        assert_eq!(
+            (&line["type"], &line["phase"], &line["bytesDone"]),
+            (&"progress".into(), &"moving".into(), &5.into())
+        );
+
+        let moved = engine::FileEvent::Moved {
+            source: card.into(),
+            dest: archive.into(),
+        };
+        let line = value(&JsonLine::File(&moved));
+        assert_eq!(
+            (&line["type"], &line["kind"], &line["source"], &line["dest"]),
+            (
+                &"file".into(),
+                &"moved".into(),
+                &card.into(),
+                &archive.into()
+            )
+        );
+        let duplicate = engine::FileEvent::Duplicate {
+            source: card.into(),
+            duplicate_of: Some(archive.into()),
+        };
+        let line = value(&JsonLine::File(&duplicate));
+        assert_eq!(
+            (&line["kind"], &line["duplicateOf"]),
+            (&"duplicate".into(), &archive.into())
+        );
+
+        let error = engine::ErrorDetail::new(
+            Some(Path::new(card)),
+            engine::ErrorStage::Move,
+            "permission denied",
+        );
+        let line = value(&JsonLine::Error(&error));
+        assert_eq!(
+            (
+                &line["type"],
+                &line["path"],
+                &line["stage"],
+                &line["message"]
+            ),
+            (
+                &"error".into(),
+                &card.into(),
+                &"move".into(),
+                &"permission denied".into()
+            )
+        );
+
+        let line = value(&JsonLine::Warning { message: "slow" });
+        assert_eq!(
+            line,
+            serde_json::json!({"type": "warning", "message": "slow"})
+        );
+
+        let result = engine::RunResult {
+            moved: 3,
+            not_attempted: 1,
+            ..Default::default()
+        };
+        for (line, tag) in [
+            (JsonLine::Result(&result), "result"),
+            (JsonLine::Batch(&result), "batch"),
+        ] {
+            let line = value(&line);
+            assert_eq!(line["type"], tag);
+            assert_eq!(
+                (&line["moved"], &line["notAttempted"]),
+                (&3.into(), &1.into())
+            );
+            assert!(line.get("skippedDuplicates").is_some());
+        }
+
+        let line = value(&JsonLine::SetupError {
+            kind: "source_unavailable",
+            message: "no such folder".to_string(),
+            path: Some(Path::new("/cards/A")),
+        });
+        assert_eq!(
+            line,
+            serde_json::json!({
+                "type": "setup_error",
+                "kind": "source_unavailable",
+                "message": "no such folder",
+                "path": "/cards/A",
+            })
+        );
+    }
+
+    #[test]
+    fn test_only_progress_goes_to_stderr() {
+        let (progress, result) = (
+            engine::ProgressEvent::default(),
+            engine::RunResult::default(),
+        );
+        let moved = engine::FileEvent::Error {
+            source: "/cards/A/IMG_7612.JPG".into(),
+            message: "gone".to_string(),
+        };
+        let error = engine::ErrorDetail::new(None, engine::ErrorStage::Setup, "failed");
+        assert!(JsonLine::Progress(&progress).on_stderr());
+        for line in [
+            JsonLine::File(&moved),
+            JsonLine::Error(&error),
+            JsonLine::Warning { message: "slow" },
+            JsonLine::Batch(&result),
+            JsonLine::Result(&result),
+            JsonLine::SetupError {
+                kind: "no_suffixes",
+                message: String::new(),
+                path: None,
+            },
+        ] {
+            assert!(!line.on_stderr());
+        }
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let result = engine::RunResult::default();
+        let unverified = engine::Verification {
+            mismatched: 1,
+            ..Default::default()
+        };
+        let kept = engine::RunResult {
+            source_kept: 1,
+            ..Default::default()
+        };
+        let stopped = engine::RunResult {
+            preflight_error: Some(engine::PreflightError::NotWritable {
+                dest: "/archive".into(),
+                reason: "read-only".to_string(),
+            }),
+            ..Default::default()
+        };
+        let cancelled = engine::RunResult {
+            cancelled: true,
+            errors: 2,
+            ..Default::default()
+        };
+        let cases = [
+            ("done", exit_code(&result, false), 0),
+            (
+                "errors",
+                exit_code(
+                    &engine::RunResult {
+                        errors: 1,
+                        ..Default::default()
+                    },
+                    false,
+                ),
+                EXIT_ERRORS,
+            ),
+            (
+                "failed verification",
+                exit_code(
+                    &engine::RunResult {
+                        verification: Some(unverified),
+                        ..Default::default()
+                    },
+                    false,
+                ),
+                EXIT_ERRORS,
+            ),
+            ("source kept", exit_code(&kept, false), 0),
+            ("source kept, strict", exit_code(&kept, true), EXIT_ERRORS),
+            ("pre-flight", exit_code(&stopped, true), EXIT_SETUP),
+            ("cancelled", exit_code(&cancelled, true), EXIT_CANCELLED),
+            ("declined", declined_exit_code(false), EXIT_DECLINED),
+            (
+                "cancelled at the question",
+                declined_exit_code(true),
+                EXIT_CANCELLED,
+            ),
+        ];
+        for (case, code, expected) in cases {
+            assert_eq!(code, expected, "{}", case);
+        }
+    }
+
+    /// The move options of `args` merged with the configuration file `config` as the
+    /// default one, and the ids of those taken from it.
+    fn merged(args: &[&str], config: &Path) -> (Option<MoveArgs>, HashSet<String>) {
+        let command = Cli::command();
+        let args = ["fm"].iter().chain(args).map(OsString::from).collect();
+        let (matches, _, from_file) = merge_config(&command, args, Some(config.to_path_buf()));
+        let cli = Cli::from_arg_matches(&matches).unwrap();
+        let run = match cli.command {
+            None => Some(cli.run),
+            Some(Command::Move(run)) => Some(*run),
+            Some(_) => None,
+        };
+        (run, from_file)
+    }
+
+    #[test]
+    fn test_command_line_overrides_file_overrides_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join(config::CONFIG_FILE_NAME);
+        std::fs::write(
+            &config,
+            "source = [\"/cards/A\"]\ndest = \"/archive\"\nsuffixes = \"7612\"\n\
+             workers = 2\ndry-run = true\n",
+        )
+        .unwrap();
+
+        for args in [
+            &["--dest", "/elsewhere"][..],
+            &["move", "--dest", "/elsewhere"],
+        ] {
+            let (run, from_file) = merged(args, &config);
+            let run = run.unwrap();
+            // Given on the command line: the file's value is not taken.
+            assert_eq!(run.dest.as_deref(), Some(Path::new("/elsewhere")));
+            assert!(!from_file.contains("dest"));
+            // Only in the file.
+            assert_eq!(run.source, [PathBuf::from("/cards/A")]);
+            assert_eq!(run.suffixes.as_deref(), Some("7612"));
+            assert_eq!(run.workers, 2);
+            assert!(run.dry_run);
+            assert!(from_file.contains("workers"));
+            // In neither: the built-in default.
+            assert_eq!(run.mode, engine::TransferMode::Move);
+            assert!(!from_file.contains("mode"));
+        }
+        // An option conflicting with one given leaves the file's out too.
+        let (run, from_file) = merged(&["--suffixes-file", "/jobs/picks.txt"], &config);
+        let run = run.unwrap();
+        assert_eq!(run.suffixes, None);
+        assert!(!from_file.contains("suffixes"));
+        // Other subcommands take no configuration.
+        let (run, from_file) = merged(&["scan", "--source", "/a", "--suffixes", "1"], &config);
+        assert!(run.is_none() && from_file.is_empty());
+    }
+
+    #[test]
+    fn test_declining_the_preview_moves_and_creates_nothing() {
+        let (card, archive) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        std::fs::write(card.path().join("IMG_7612.JPG"), b"pick").unwrap();
+        let dest = archive.path().join("picks/2024");
+        let options = engine::RunOptions::new(card.path(), &dest, "7612");
+        let display = ProgressDisplay::new(ProgressOutput::Quiet);
+        let control = engine::RunControl::new();
+
+        let mut asked = None;
+        let declined = run_confirmed(&options, &control, &display, |preview| {
+            asked = Some(preview.to_string());
+            false
+        });
+        assert!(matches!(declined, Ok(None)));
+        assert!(asked.unwrap().contains("1 file(s)"));
+        assert!(card.path().join("IMG_7612.JPG").exists());
+        assert!(!archive.path().join("picks").exists());
+
+        let confirmed = run_confirmed(&options, &control, &display, |_| true);
+        let result = confirmed.unwrap().unwrap();
+        assert_eq!((result.moved, result.errors), (1, 0));
+        assert!(dest.join("IMG_7612.JPG").exists());
+    }
+
+    #[test]
+    fn test_bare_launch_ignores_the_configuration_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join(config::CONFIG_FILE_NAME);
+        std::fs::write(
+            &config,
+            "source = [\"/cards/A\"]\ndest = \"/archive\"\nsuffixes = \"7612\"\n",
+        )
+        .unwrap();
+
+        let (run, from_file) = merged(&[], &config);
+
+        let run = run.unwrap();
+        assert!(from_file.is_empty());
+        assert!(run.source.is_empty() && run.dest.is_none() && run.suffixes.is_none());
+    }
 }
@@ -1,93 +1,2097 @@
 //! Move files with collision handling and cross-volume fallback.
 
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::path::Path;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
 
+use filetime::FileTime;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::collision::{self, CollisionPattern};
+use crate::control::RunControl;
 use crate::hasher;
+use crate::sanitize::SanitizeMode;
+use crate::tag::NameTag;
+use crate::template::DestTemplate;
 
-/// Move `src` to `dest`. If same volume, uses atomic rename; otherwise copy+sync+delete.
-/// If `src` hash already exists anywhere in destination (dest_hash_index), skip as duplicate.
+/// Move `src` (whose content hash is `src_hash`) to `dest`. If same volume, uses atomic
+/// rename; otherwise copy+sync+delete. In link/copy modes (`options.transfer_mode`) the source
+/// is left in place. Checking `src_hash` against the destination index is the caller's job.
 /// If `dest` already exists:
 /// - If same content (hash), skip (caller should treat as duplicate).
-/// - Else rename to dest with "-1", "-2", ... before extension until available.
-/// Returns: Ok(()) if moved or skipped-as-duplicate, Err on failure.
+/// - Else rename using `options.collision_pattern` ("-1", "-2", ... by default) until available,
+///   or with `CollisionMode::Update` replace it when the source was modified more recently
+///   (see `update`).
+///
+/// `claims` holds names other workers are writing right now; they count as taken.
+/// `control` is checked between copy chunks (blocking while paused); a cancelled copy leaves
+/// no partial file behind.
+/// With `options.verify_copies`, a cross-volume copy is re-hashed before the source is deleted.
+/// An empty `src_hash` means the content is unknown (no duplicate check): the file at `dest` is
+/// then never taken for a duplicate, and a copy is checked against the source re-read.
+///
+/// Returns: Ok(MoveResult) if moved or skipped-as-duplicate, Err on failure.
 pub fn move_file(
     src: &Path,
     dest: &Path,
-    dest_hash_index: &std::collections::HashSet<String>,
+    src_hash: &str,
+    options: &MoveOptions,
+    claims: &DestClaims,
+    control: &RunControl,
 ) -> Result<MoveResult, std::io::Error> {
-    let src_hash = hasher::hash_file(src)?;
+    let src_fs = long_path(src);
+    let verify = options.verify_copies.then_some(src_hash);
 
-    if dest_hash_index.contains(&src_hash) {
+    if !src_hash.is_empty() && claims.same_content_at(dest, src_hash) {
         return Ok(MoveResult::SkippedDuplicate);
     }
+    if options.on_collision == CollisionMode::Update {
+        if let Some(existing) = claims.claim_existing(dest) {
+            let result = update(src, &existing, src_hash, options, claims, control);
+            claims.release(&existing);
+            return result;
+        }
+    }
+
+    // Ensure parent dir exists
+    if let Some(p) = dest.parent() {
+        fs::create_dir_all(long_path(p))?;
+    }
+    // Different content or a name in use: find unique name
+    let target = claims.claim(
+        dest,
+        &options.collision_pattern,
+        &collision::timestamp_now(),
+    );
+    let result = transfer_source(&src_fs, &long_path(&target), options, verify, control);
+    if result.is_ok() {
+        claims.placed(&target);
+    }
+    claims.release(&target);
+    result.map(|t| t.into_result(target, src_hash, None))
+}
+
+/// `CollisionMode::Update` for `src` and the file `existing` holding its name (claimed by the
+/// caller): unless the source was modified more recently, nothing is done. Otherwise the old
+/// file is renamed aside to a free collision name, where it is kept (see
+/// `ReplacedFile::kept_at`), and the source put in its place. If the transfer fails, the old
+/// file is renamed back.
+fn update(
+    src: &Path,
+    existing: &Path,
+    src_hash: &str,
+    options: &MoveOptions,
+    claims: &DestClaims,
+    control: &RunControl,
+) -> Result<MoveResult, std::io::Error> {
+    if !source_is_newer(src, existing) {
+        return Ok(MoveResult::KeptNewer {
+            existing: existing.to_path_buf(),
+        });
+    }
+    let existing_fs = long_path(existing);
+    let hash = hasher::hash_file_cancellable(&existing_fs, control)?;
+    let modified = file_stamp(existing)?
+        .1
+        .map(|t| chrono::DateTime::<chrono::Local>::from(t).to_rfc3339());
+    let aside = claims.claim(
+        existing,
+        &options.collision_pattern,
+        &collision::timestamp_now(),
+    );
+    let aside_fs = long_path(&aside);
+    let result = fs::rename(&existing_fs, &aside_fs).and_then(|()| {
+        let verify = options.verify_copies.then_some(src_hash);
+        let result = transfer_source(&long_path(src), &existing_fs, options, verify, control);
+        if result.is_err() {
+            let _ = fs::rename(&aside_fs, &existing_fs);
+        }
+        result
+    });
+    if result.is_ok() {
+        claims.placed(existing);
+    }
+    if exists(&aside) {
+        // Set aside by this run: never replaced by a later file of the same name.
+        claims.placed(&aside);
+    }
+    claims.release(&aside);
+    let replaced = ReplacedFile {
+        hash,
+        modified,
+        kept_at: aside,
+    };
+    result.map(|t| t.into_result(existing.to_path_buf(), src_hash, Some(replaced)))
+}
+
+/// Whether `src` was modified after `existing`; a file without a modification time is never
+/// taken for the newer one.
+pub fn source_is_newer(src: &Path, existing: &Path) -> bool {
+    match (file_stamp(src), file_stamp(existing)) {
+        (Ok((_, Some(new))), Ok((_, Some(old)))) => new > old,
+        _ => false,
+    }
+}
+
+/// Destination names reserved by transfers in flight, so parallel workers never pick the same
+/// free name. A name is claimed before its transfer starts and released once the file exists
+/// (or the transfer failed), after which `existing_variant` sees it on disk. Names this run
+/// wrote are kept apart too, so `CollisionMode::Update` only ever replaces older files.
+/// The filesystem is only looked at outside the locks.
+#[derive(Debug)]
+pub struct DestClaims {
+    names: Mutex<HashSet<PathBuf>>,
+    placed: Mutex<HashSet<PathBuf>>,
+    /// Each destination folder's names, listed once when first needed.
+    dirs: Mutex<HashMap<PathBuf, DirNames>>,
+    /// Names are compared in NFC (see `MoveOptions::normalize_unicode`).
+    normalize: bool,
+}
+
+impl DestClaims {
+    pub fn new(normalize: bool) -> DestClaims {
+        DestClaims {
+            names: Default::default(),
+            placed: Default::default(),
+            dirs: Default::default(),
+            normalize,
+        }
+    }
 
-    // If destination path exists, check content
-    if dest.exists() {
-        if let Ok(existing_hash) = hasher::hash_file(dest) {
-            if existing_hash == src_hash {
-                return Ok(MoveResult::SkippedDuplicate);
+    /// `dest` if free, else the first free collision name; the result is claimed.
+    pub fn claim(&self, dest: &Path, pattern: &CollisionPattern, ts: &str) -> PathBuf {
+        // Claims the name if it is free. A worker may finish writing it between the first
+        // look and the claim; it releases the name only once the file exists, so the second
+        // look sees the file.
+        let free = |p: &Path| {
+            if self.existing_variant(p).is_some() {
+                return false;
+            }
+            let key = self.key(p);
+            if !lock(&self.names).insert(key.clone()) {
+                return false;
+            }
+            if self.existing_variant(p).is_some() {
+                lock(&self.names).remove(&key);
+                return false;
             }
+            true
+        };
+        if free(dest) {
+            dest.to_path_buf()
+        } else {
+            collision_target_where(dest, pattern, ts, |p| !free(p))
         }
-        // Different content: find unique name
-        let (stem, ext) = split_stem_ext(dest);
-        for i in 1.. {
-            let candidate = if ext.is_empty() {
-                format!("{}-{}", stem, i)
-            } else {
-                format!("{}-{}.{}", stem, i, ext)
+    }
+
+    /// The file occupying `dest`'s name, claimed, if it may be replaced: it was there before
+    /// this run, and no worker is writing to that name.
+    pub fn claim_existing(&self, dest: &Path) -> Option<PathBuf> {
+        let existing = self.existing_variant(dest)?;
+        let key = self.key(&existing);
+        let mut names = lock(&self.names);
+        if names.contains(&key) || lock(&self.placed).contains(&key) {
+            return None;
+        }
+        names.insert(key);
+        Some(existing)
+    }
+
+    /// Record that this run put a file at `target`; called before releasing it.
+    pub fn placed(&self, target: &Path) {
+        let key = self.key(target);
+        if let (Some(parent), Some(name)) = (target.parent(), target.file_name()) {
+            if let Some(dir) = lock(&self.dirs).get_mut(parent) {
+                dir.add(name, self.normalize);
+            }
+        }
+        lock(&self.placed).insert(key);
+    }
+
+    pub fn release(&self, target: &Path) {
+        let key = self.key(target);
+        lock(&self.names).remove(&key);
+    }
+
+    /// The file occupying `path`'s name: `path` itself if it exists, otherwise an entry in the
+    /// same directory whose name differs only by case, on a case-insensitive volume, or with
+    /// `normalize` by Unicode normalization ("café" with a composed or a decomposed "é", as
+    /// macOS writes it). APFS and NTFS treat such names as the same file, so they are handled
+    /// as a collision everywhere to keep behaviour consistent.
+    pub fn existing_variant(&self, path: &Path) -> Option<PathBuf> {
+        if exists(path) {
+            return Some(path.to_path_buf());
+        }
+        let (parent, name) = (path.parent()?, path.file_name()?);
+        self.list(parent);
+        let found = parent.join(lock(&self.dirs).get(parent)?.find(name, self.normalize)?);
+        // Listed earlier; it may have gone since.
+        exists(&found).then_some(found)
+    }
+
+    /// Whether the file at `dest` (or a variant of its name, see `existing_variant`) exists
+    /// and has content `hash`.
+    pub fn same_content_at(&self, dest: &Path, hash: &str) -> bool {
+        self.existing_variant(dest)
+            .and_then(|existing| hasher::hash_file(&long_path(&existing)).ok())
+            .is_some_and(|existing_hash| existing_hash == hash)
+    }
+
+    /// List `dir` unless it was already.
+    fn list(&self, dir: &Path) {
+        if !lock(&self.dirs).contains_key(dir) {
+            let names = DirNames::read(dir, self.normalize);
+            lock(&self.dirs).entry(dir.to_path_buf()).or_insert(names);
+        }
+    }
+
+    /// Claims compare names the way `existing_variant` does (ignoring case where the volume
+    /// does and, with `normalize`, normalization).
+    fn key(&self, path: &Path) -> PathBuf {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return path.to_path_buf();
+        };
+        self.list(parent);
+        let fold_case = lock(&self.dirs).get(parent).is_some_and(|d| d.fold_case);
+        parent.join(name_key(name, fold_case, self.normalize))
+    }
+}
+
+impl Default for DestClaims {
+    fn default() -> Self {
+        DestClaims::new(true)
+    }
+}
+
+/// The names in a destination folder by their comparison key (see `name_key`). Only listed
+/// when names can differ and still be the same: on a case-insensitive volume, or when
+/// normalizing.
+#[derive(Debug)]
+struct DirNames {
+    fold_case: bool,
+    names: HashMap<String, OsString>,
+}
+
+impl DirNames {
+    fn read(dir: &Path, normalize: bool) -> DirNames {
+        let mut listed = DirNames {
+            fold_case: folds_case(dir),
+            names: HashMap::new(),
+        };
+        if listed.fold_case || normalize {
+            if let Ok(entries) = fs::read_dir(long_path(dir)) {
+                for entry in entries.flatten() {
+                    listed.add(&entry.file_name(), normalize);
+                }
+            }
+        }
+        listed
+    }
+
+    fn add(&mut self, name: &OsStr, normalize: bool) {
+        if self.fold_case || normalize {
+            let key = name_key(name, self.fold_case, normalize);
+            self.names.entry(key).or_insert_with(|| name.to_os_string());
+        }
+    }
+
+    fn find(&self, name: &OsStr, normalize: bool) -> Option<&OsString> {
+        self.names.get(&name_key(name, self.fold_case, normalize))
+    }
+}
+
+/// Whether the volume holding `dir` ignores case in names. Probed on the nearest existing
+/// folder with a letter in its name, which must then also be there with the letters' case
+/// swapped; without one, what the system usually does.
+fn folds_case(dir: &Path) -> bool {
+    for folder in dir.ancestors() {
+        let Some(name) = folder.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let swapped: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_lowercase() {
+                    c.to_ascii_uppercase()
+                } else {
+                    c.to_ascii_lowercase()
+                }
+            })
+            .collect();
+        if swapped == name {
+            continue;
+        }
+        let Ok(meta) = fs::metadata(long_path(folder)) else {
+            continue;
+        };
+        return fs::metadata(long_path(&folder.with_file_name(&swapped)))
+            .is_ok_and(|other| same_file(&meta, &other));
+    }
+    cfg!(any(target_os = "macos", windows))
+}
+
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+#[cfg(not(unix))]
+fn same_file(_: &fs::Metadata, _: &fs::Metadata) -> bool {
+    true
+}
+
+/// Whether anything is at `path`.
+fn exists(path: &Path) -> bool {
+    long_path(path).exists()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Path form to hand to filesystem calls. On Windows this is the extended-length form
+/// (`\\?\C:\...`, `\\?\UNC\server\share\...`) so deep trees are not capped at 260 characters;
+/// elsewhere the path is returned unchanged. Reported paths keep their normal form.
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> std::borrow::Cow<'_, Path> {
+    use std::borrow::Cow;
+    use std::path::Component;
+
+    let Some(text) = path.to_str() else {
+        return Cow::Borrowed(path);
+    };
+    if text.starts_with(r"\\?\") || text.starts_with(r"\\.\") {
+        return Cow::Borrowed(path);
+    }
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        match std::env::current_dir() {
+            Ok(cwd) => cwd.join(path),
+            Err(_) => return Cow::Borrowed(path),
+        }
+    };
+    // Windows does not normalize extended-length paths, so resolve "." / ".." and separators here.
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c.as_os_str()),
+        }
+    }
+    let normalized = normalized.to_string_lossy().replace('/', "\\");
+    let long = match normalized.strip_prefix(r"\\") {
+        Some(unc) => format!(r"\\?\UNC\{}", unc),
+        None => format!(r"\\?\{}", normalized),
+    };
+    Cow::Owned(PathBuf::from(long))
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> std::borrow::Cow<'_, Path> {
+    std::borrow::Cow::Borrowed(path)
+}
+
+/// First name for `dest` under `pattern` ("IMG_7612-1.JPG", "IMG_7612-2.JPG", ...) that is not
+/// `taken`.
+fn collision_target_where(
+    dest: &Path,
+    pattern: &CollisionPattern,
+    ts: &str,
+    taken: impl Fn(&Path) -> bool,
+) -> PathBuf {
+    let file_name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let parent = dest.parent().unwrap_or_else(|| Path::new(""));
+    for attempt in 1.. {
+        let candidate = parent.join(pattern.candidate(&file_name, attempt, ts));
+        if !taken(&candidate) {
+            return candidate;
+        }
+    }
+    unreachable!("collision counter exhausted")
+}
+
+/// Comparison key for file names: in NFC with `normalize`, and lowercase with `fold_case`.
+fn name_key(name: &OsStr, fold_case: bool, normalize: bool) -> String {
+    let name = name.to_string_lossy();
+    let name = if normalize {
+        nfc(&name).into_owned()
+    } else {
+        name.into_owned()
+    };
+    if fold_case {
+        name.to_lowercase()
+    } else {
+        name
+    }
+}
+
+/// `text` in Unicode normalization form C, the composed form most systems write names in.
+pub fn nfc(text: &str) -> std::borrow::Cow<'_, str> {
+    if unicode_normalization::is_nfc(text) {
+        std::borrow::Cow::Borrowed(text)
+    } else {
+        std::borrow::Cow::Owned(text.nfc().collect())
+    }
+}
+
+/// What `transfer` did for one file.
+#[derive(Debug)]
+struct Transferred {
+    method: TransferMethod,
+    /// The cross-volume copy was re-hashed and matched the source.
+    verified: bool,
+    /// The copy is in place but removing the source failed (read-only media, locked file).
+    source_error: Option<std::io::Error>,
+}
+
+impl Transferred {
+    fn by(method: TransferMethod) -> Transferred {
+        Transferred {
+            method,
+            verified: false,
+            source_error: None,
+        }
+    }
+
+    fn into_result(self, dest: PathBuf, hash: &str, replaced: Option<ReplacedFile>) -> MoveResult {
+        match self.source_error {
+            Some(e) => MoveResult::SourceKept {
+                dest,
+                verified: self.verified,
+                hash: hash.to_string(),
+                reason: e.to_string(),
+                replaced,
+            },
+            None => MoveResult::Moved {
+                dest,
+                method: self.method,
+                verified: self.verified,
+                hash: hash.to_string(),
+                replaced,
+            },
+        }
+    }
+}
+
+/// `transfer` for a matched file, which may be a symbolic link: `options.symlinks` then says
+/// whether the link or the image it points to goes to `dest` (see `SymlinkPolicy`).
+fn transfer_source(
+    src: &Path,
+    dest: &Path,
+    options: &MoveOptions,
+    verify: Option<&str>,
+    control: &RunControl,
+) -> Result<Transferred, std::io::Error> {
+    let (mode, xattrs) = (options.transfer_mode, options.preserve_xattrs);
+    if !is_symlink(src) {
+        return transfer(src, dest, mode, xattrs, verify, control);
+    }
+    match (options.symlinks, mode) {
+        (SymlinkPolicy::Skip, _) => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "is a symbolic link, and symbolic links are skipped",
+        )),
+        (SymlinkPolicy::Link, _) => relink(src, dest, mode),
+        // Reading through the link copies the image; removing the source removes the link.
+        (SymlinkPolicy::Target, TransferMode::Move) => {
+            copy_then_delete(src, dest, xattrs, verify, control).map(|source_error| Transferred {
+                method: TransferMethod::Copied,
+                verified: verify.is_some(),
+                source_error,
+            })
+        }
+        (SymlinkPolicy::Target, _) => {
+            let image = fs::canonicalize(src)?;
+            transfer(&image, dest, mode, xattrs, verify, control)
+        }
+    }
+}
+
+/// Whether `path` itself is a symbolic link (it is not followed).
+pub fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+}
+
+/// Put a symbolic link at `dest` to what the link `src` points to, removing `src` in move
+/// mode. A relative target is made absolute first, so the link still resolves from its new
+/// folder.
+fn relink(src: &Path, dest: &Path, mode: TransferMode) -> Result<Transferred, std::io::Error> {
+    let target = fs::read_link(src)?;
+    let target = match src.parent() {
+        Some(dir) if target.is_relative() => dir.join(target),
+        _ => target,
+    };
+    make_symlink(&target, dest)?;
+    let mut transferred = Transferred::by(TransferMethod::Symlinked);
+    if mode.removes_source() {
+        transferred.source_error = fs::remove_file(src).err();
+    }
+    Ok(transferred)
+}
+
+#[cfg(unix)]
+fn make_symlink(target: &Path, link: &Path) -> Result<(), std::io::Error> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn make_symlink(target: &Path, link: &Path) -> Result<(), std::io::Error> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn make_symlink(_target: &Path, _link: &Path) -> Result<(), std::io::Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "symbolic links are not supported here",
+    ))
+}
+
+/// Put `src` at `dest` according to `mode`; link modes fall back to a real copy.
+/// `verify` is the source hash to check a cross-volume move's copy against before deleting
+/// the source; `xattrs` carries extended attributes over on copies (macOS).
+fn transfer(
+    src: &Path,
+    dest: &Path,
+    mode: TransferMode,
+    xattrs: bool,
+    verify: Option<&str>,
+    control: &RunControl,
+) -> Result<Transferred, std::io::Error> {
+    let copied = |_| Transferred::by(TransferMethod::Copied);
+    match mode {
+        TransferMode::Move => do_move(src, dest, xattrs, verify, control),
+        TransferMode::Copy => copy_file(src, dest, xattrs, control).map(copied),
+        TransferMode::Hardlink => match fs::hard_link(src, dest) {
+            Ok(()) => Ok(Transferred::by(TransferMethod::Hardlinked)),
+            Err(_) => copy_file(src, dest, xattrs, control).map(copied),
+        },
+        TransferMode::Reflink => match reflink(src, dest) {
+            Ok(()) => {
+                copy_metadata(src, dest).ok();
+                Ok(Transferred::by(TransferMethod::Reflinked))
+            }
+            Err(_) => copy_file(src, dest, xattrs, control).map(copied),
+        },
+    }
+}
+
+fn do_move(
+    src: &Path,
+    dest: &Path,
+    xattrs: bool,
+    verify: Option<&str>,
+    control: &RunControl,
+) -> Result<Transferred, std::io::Error> {
+    // Try atomic rename first (same volume); only a cross-device failure justifies copying.
+    match fs::rename(src, dest) {
+        Ok(()) => Ok(Transferred::by(TransferMethod::Renamed)),
+        Err(e) if is_cross_device(&e) => {
+            copy_then_delete(src, dest, xattrs, verify, control).map(|source_error| Transferred {
+                method: TransferMethod::Copied,
+                verified: verify.is_some(),
+                source_error,
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether a rename failed because source and destination are on different volumes.
+fn is_cross_device(err: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        err.raw_os_error() == Some(libc::EXDEV)
+    }
+    #[cfg(windows)]
+    {
+        const ERROR_NOT_SAME_DEVICE: i32 = 17;
+        err.raw_os_error() == Some(ERROR_NOT_SAME_DEVICE)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = err;
+        false
+    }
+}
+
+/// Space on a volume, as [`volume_space`] reports it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VolumeSpace {
+    /// Bytes available to this user.
+    pub available: u64,
+    pub total: u64,
+}
+
+/// Space on the volume holding `path` (an existing directory), or `None` when the platform or
+/// filesystem doesn't say.
+pub fn volume_space(path: &Path) -> Option<VolumeSpace> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut st = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+        // SAFETY: valid NUL-terminated path and a buffer of the right type.
+        if unsafe { libc::statvfs(c_path.as_ptr(), st.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        // SAFETY: statvfs succeeded, so the struct is initialized.
+        let st = unsafe { st.assume_init() };
+        // The field types differ between platforms (u32 block counts on macOS).
+        #[allow(clippy::unnecessary_cast)]
+        Some(VolumeSpace {
+            available: (st.f_bavail as u64).saturating_mul(st.f_frsize as u64),
+            total: (st.f_blocks as u64).saturating_mul(st.f_frsize as u64),
+        })
+    }
+    #[cfg(windows)]
+    {
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetDiskFreeSpaceExW(
+                directory: *const u16,
+                free_to_caller: *mut u64,
+                total: *mut u64,
+                total_free: *mut u64,
+            ) -> i32;
+        }
+        let wide = wide_path(&long_path(path));
+        let (mut available, mut total) = (0u64, 0u64);
+        // SAFETY: NUL-terminated wide path; the total free we don't need may be null.
+        let ok = unsafe {
+            GetDiskFreeSpaceExW(
+                wide.as_ptr(),
+                &mut available,
+                &mut total,
+                std::ptr::null_mut(),
+            )
+        };
+        (ok != 0).then_some(VolumeSpace { available, total })
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Where a volume is mounted, what it is called and its filesystem, as far as the system says.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VolumeLabel {
+    pub mount_point: Option<PathBuf>,
+    /// The volume's label on Windows; elsewhere the last folder of its mount point
+    /// ("/Volumes/Archive" is "Archive"), none for the root.
+    pub name: Option<String>,
+    /// "apfs", "ext4", "NTFS", "exfat"...
+    pub filesystem: Option<String>,
+}
+
+/// The label of the volume holding `path`, an existing directory; its fields are `None` where
+/// the system does not say.
+pub fn volume_label(path: &Path) -> VolumeLabel {
+    #[cfg(target_os = "macos")]
+    {
+        use std::ffi::CStr;
+        use std::os::unix::ffi::OsStrExt;
+        let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+            return VolumeLabel::default();
+        };
+        let mut st = std::mem::MaybeUninit::<libc::statfs>::uninit();
+        // SAFETY: valid NUL-terminated path and a buffer of the right type.
+        if unsafe { libc::statfs(c_path.as_ptr(), st.as_mut_ptr()) } != 0 {
+            return VolumeLabel::default();
+        }
+        // SAFETY: statfs succeeded, so the struct is initialized and its names NUL-terminated.
+        let st = unsafe { st.assume_init() };
+        let text = |chars: &[libc::c_char]| {
+            // SAFETY: see above.
+            let text = unsafe { CStr::from_ptr(chars.as_ptr()) };
+            text.to_string_lossy().into_owned()
+        };
+        let mount_point = PathBuf::from(text(&st.f_mntonname));
+        mount_label(mount_point, Some(text(&st.f_fstypename)))
+    }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        // The longest mount point that `path` is under, from the kernel's list.
+        let Ok(mounts) = fs::read_to_string("/proc/self/mounts") else {
+            return VolumeLabel::default();
+        };
+        let path = dunce::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        mounts
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(' ');
+                let (_, mount_point, filesystem) = (fields.next()?, fields.next()?, fields.next()?);
+                Some((PathBuf::from(unescape_mount_field(mount_point)), filesystem))
+            })
+            .filter(|(mount_point, _)| path.starts_with(mount_point))
+            .max_by_key(|(mount_point, _)| mount_point.as_os_str().len())
+            .map(|(mount_point, filesystem)| mount_label(mount_point, Some(filesystem.to_string())))
+            .unwrap_or_default()
+    }
+    #[cfg(windows)]
+    {
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetVolumePathNameW(file_name: *const u16, volume_path: *mut u16, length: u32)
+                -> i32;
+            fn GetVolumeInformationW(
+                root: *const u16,
+                name: *mut u16,
+                name_size: u32,
+                serial: *mut u32,
+                max_component: *mut u32,
+                flags: *mut u32,
+                filesystem: *mut u16,
+                filesystem_size: u32,
+            ) -> i32;
+        }
+        let text = |buf: &[u16]| {
+            let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+            String::from_utf16_lossy(&buf[..len])
+        };
+        let wide = wide_path(&long_path(path));
+        let mut root = vec![0u16; 32_768];
+        // SAFETY: NUL-terminated wide path and a buffer of the given length.
+        if unsafe { GetVolumePathNameW(wide.as_ptr(), root.as_mut_ptr(), root.len() as u32) } == 0 {
+            return VolumeLabel::default();
+        }
+        let mount_point = PathBuf::from(text(&root));
+        let (mut name, mut filesystem) = ([0u16; 261], [0u16; 261]);
+        // SAFETY: NUL-terminated root from the call above, buffers of the given lengths; the
+        // values we don't need may be null.
+        let ok = unsafe {
+            GetVolumeInformationW(
+                root.as_ptr(),
+                name.as_mut_ptr(),
+                name.len() as u32,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                filesystem.as_mut_ptr(),
+                filesystem.len() as u32,
+            )
+        };
+        if ok == 0 {
+            return VolumeLabel {
+                mount_point: Some(mount_point),
+                ..VolumeLabel::default()
             };
-            let candidate_path = dest.parent().unwrap().join(&candidate);
-            if !candidate_path.exists() {
-                return do_move(src, &candidate_path).map(|_| MoveResult::Moved(candidate_path));
+        }
+        VolumeLabel {
+            mount_point: Some(mount_point),
+            name: Some(text(&name)).filter(|n| !n.is_empty()),
+            filesystem: Some(text(&filesystem)).filter(|f| !f.is_empty()),
+        }
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = path;
+        VolumeLabel::default()
+    }
+}
+
+#[cfg(unix)]
+fn mount_label(mount_point: PathBuf, filesystem: Option<String>) -> VolumeLabel {
+    VolumeLabel {
+        name: mount_point
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned()),
+        mount_point: Some(mount_point),
+        filesystem,
+    }
+}
+
+/// A field of /proc/self/mounts with its octal escapes ("\040" for a space) decoded.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) fn unescape_mount_field(field: &str) -> String {
+    let mut bytes = Vec::with_capacity(field.len());
+    let raw = field.as_bytes();
+    let mut i = 0;
+    while i < raw.len() {
+        let octal = raw.get(i + 1..i + 4).and_then(|digits| {
+            let digits = std::str::from_utf8(digits).ok()?;
+            u8::from_str_radix(digits, 8).ok()
+        });
+        match octal {
+            Some(byte) if raw[i] == b'\\' => {
+                bytes.push(byte);
+                i += 4;
+            }
+            _ => {
+                bytes.push(raw[i]);
+                i += 1;
             }
         }
     }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
 
-    // Ensure parent dir exists
-    if let Some(p) = dest.parent() {
-        fs::create_dir_all(p)?;
+#[cfg(windows)]
+fn wide_path(path: &Path) -> Vec<u16> {
+    use std::os::windows::ffi::OsStrExt;
+    path.as_os_str().encode_wide().chain(Some(0)).collect()
+}
+
+/// Check that files can be created in `dir` by creating and removing a small probe file.
+pub fn probe_writable(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(format!(".framemover-probe-{}", std::process::id()));
+    let probe = long_path(&probe);
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .and_then(|mut f| f.write_all(b"probe"))?;
+    fs::remove_file(&probe)
+}
+
+/// Size and modification time of `path`, to tell whether a file changed since it was looked at.
+pub fn file_stamp(path: &Path) -> std::io::Result<(u64, Option<std::time::SystemTime>)> {
+    let meta = fs::metadata(long_path(path))?;
+    Ok((meta.len(), meta.modified().ok()))
+}
+
+/// Make the file at `path` read-only, or writable again: the write permissions on Unix
+/// (writable again is for the owner only), the read-only attribute on Windows. Returns false
+/// when the call went through but the filesystem kept the file as it was, as FAT and many
+/// network shares do.
+pub fn set_read_only(path: &Path, read_only: bool) -> std::io::Result<bool> {
+    let path = long_path(path);
+    let mut permissions = fs::metadata(&path)?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = permissions.mode();
+        permissions.set_mode(if read_only {
+            mode & !0o222
+        } else {
+            mode | 0o200
+        });
     }
-    do_move(src, dest).map(|_| MoveResult::Moved(dest.to_path_buf()))
+    #[cfg(not(unix))]
+    permissions.set_readonly(read_only);
+    fs::set_permissions(&path, permissions)?;
+    Ok(fs::metadata(&path)?.permissions().readonly() == read_only)
 }
 
-fn split_stem_ext(path: &Path) -> (String, String) {
-    let stem = path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("")
-        .to_string();
-    let ext = path
-        .extension()
-        .and_then(|s| s.to_str())
-        .unwrap_or("")
-        .to_string();
-    (stem, ext)
+/// Whether `a` and `b` (both existing) are on the same volume, so a move is a plain rename.
+pub fn same_volume(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev(),
+            _ => false,
+        }
+    }
+    #[cfg(windows)]
+    {
+        use std::path::Component;
+        let prefix = |p: &Path| match dunce::canonicalize(p) {
+            Ok(p) => match p.components().next() {
+                Some(Component::Prefix(pre)) => {
+                    Some(pre.as_os_str().to_string_lossy().to_lowercase())
+                }
+                _ => None,
+            },
+            Err(_) => None,
+        };
+        matches!((prefix(a), prefix(b)), (Some(x), Some(y)) if x == y)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (a, b);
+        false
+    }
 }
 
-fn do_move(src: &Path, dest: &Path) -> Result<(), std::io::Error> {
-    // Try atomic rename first (same volume)
-    if fs::rename(src, dest).is_ok() {
-        return Ok(());
+/// Cross-volume fallback: copy, carry over timestamps and permissions, sync, then delete the source.
+/// The source is only removed once the copy has been renamed into its final name and, with
+/// `verify`, once the copy re-hashes to the source hash; a mismatched copy is deleted instead.
+/// Returns `Ok(Some(err))` when the copy is complete but the source could not be removed.
+fn copy_then_delete(
+    src: &Path,
+    dest: &Path,
+    xattrs: bool,
+    verify: Option<&str>,
+    control: &RunControl,
+) -> Result<Option<std::io::Error>, std::io::Error> {
+    copy_file(src, dest, xattrs, control)?;
+    if let Some(expected) = verify {
+        let hashes = hasher::hash_file_cancellable(dest, control).and_then(|actual| {
+            if expected.is_empty() {
+                Ok((actual, hasher::hash_file_cancellable(src, control)?))
+            } else {
+                Ok((actual, expected.to_string()))
+            }
+        });
+        let (actual, expected) = match hashes {
+            Ok(h) => h,
+            Err(e) => {
+                fs::remove_file(dest).ok();
+                return Err(e);
+            }
+        };
+        if actual != expected {
+            fs::remove_file(dest).ok();
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "copy verification failed: destination does not match source; source kept",
+            ));
+        }
     }
-    // Cross-volume: copy then delete
-    fs::copy(src, dest)?;
-    if let Ok(f) = fs::File::open(dest) {
-        f.sync_all().ok();
+    Ok(fs::remove_file(src).err())
+}
+
+const COPY_CHUNK: usize = 1024 * 1024;
+
+/// Suffix of the temporary file a copy is written to before being renamed into place.
+pub const PARTIAL_SUFFIX: &str = ".framemover-partial";
+
+/// Temporary path for copying to `dest`, e.g. ".IMG_7612.JPG.framemover-partial" next to it.
+pub fn partial_path(dest: &Path) -> PathBuf {
+    let name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    dest.with_file_name(format!(".{}{}", name, PARTIAL_SUFFIX))
+}
+
+fn cancelled_error() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Interrupted, "cancelled")
+}
+
+/// Copy `src` to `dest` via a partial file: write in chunks (checking `control` between them),
+/// apply extended attributes (with `xattrs`), timestamps and permissions, fsync, then rename
+/// into the final name. On error or cancel the partial file is deleted, so `dest` never holds
+/// a truncated copy.
+fn copy_file(
+    src: &Path,
+    dest: &Path,
+    xattrs: bool,
+    control: &RunControl,
+) -> Result<(), std::io::Error> {
+    let partial = partial_path(dest);
+    let result = copy_chunks(src, &partial, control).and_then(|_| {
+        if xattrs {
+            copy_xattrs(src, &partial).ok();
+        }
+        copy_metadata(src, &partial).ok();
+        fs::rename(&partial, dest)
+    });
+    if result.is_err() {
+        fs::remove_file(&partial).ok();
+    }
+    result
+}
+
+fn copy_chunks(src: &Path, to: &Path, control: &RunControl) -> Result<(), std::io::Error> {
+    let mut reader = fs::File::open(src)?;
+    let mut writer = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(to)?;
+    let mut buf = vec![0u8; COPY_CHUNK];
+    loop {
+        if !control.proceed() {
+            return Err(cancelled_error());
+        }
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buf[..n])?;
+        if !control.pace(n as u64) {
+            return Err(cancelled_error());
+        }
+    }
+    writer.sync_all()
+}
+
+/// Clone `src` to `dest` sharing data blocks (Btrfs/XFS via FICLONE).
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dest: &Path) -> Result<(), std::io::Error> {
+    use std::os::unix::io::AsRawFd;
+
+    let src_file = fs::File::open(src)?;
+    let dest_file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dest)?;
+    // SAFETY: both descriptors are open for the duration of the call.
+    let rc = unsafe { libc::ioctl(dest_file.as_raw_fd(), libc::FICLONE, src_file.as_raw_fd()) };
+    if rc != 0 {
+        let err = std::io::Error::last_os_error();
+        drop(dest_file);
+        fs::remove_file(dest).ok();
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Clone `src` to `dest` sharing data blocks (APFS via clonefile).
+#[cfg(target_os = "macos")]
+fn reflink(src: &Path, dest: &Path) -> Result<(), std::io::Error> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let src_c = CString::new(src.as_os_str().as_bytes())?;
+    let dest_c = CString::new(dest.as_os_str().as_bytes())?;
+    // SAFETY: both pointers are valid NUL-terminated strings for the duration of the call.
+    let rc = unsafe { libc::clonefile(src_c.as_ptr(), dest_c.as_ptr(), 0) };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn reflink(_src: &Path, _dest: &Path) -> Result<(), std::io::Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "reflink is not supported on this platform",
+    ))
+}
+
+/// Copy access/modification times (and permission bits on Unix) from `src` onto `dest`.
+/// A plain copy stamps the new file with "now", which breaks date-sorted views and backups.
+fn copy_metadata(src: &Path, dest: &Path) -> Result<(), std::io::Error> {
+    let meta = fs::metadata(src)?;
+    let atime = FileTime::from_last_access_time(&meta);
+    let mtime = FileTime::from_last_modification_time(&meta);
+    filetime::set_file_times(dest, atime, mtime)?;
+    #[cfg(unix)]
+    fs::set_permissions(dest, meta.permissions())?;
+    Ok(())
+}
+
+/// Copy extended attributes (Finder tags, comments, ...) from `src` onto `dest` via copyfile(3).
+/// The quarantine flag is dropped: the file was not downloaded, it only changed volumes.
+#[cfg(target_os = "macos")]
+fn copy_xattrs(src: &Path, dest: &Path) -> Result<(), std::io::Error> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let src_c = CString::new(src.as_os_str().as_bytes())?;
+    let dest_c = CString::new(dest.as_os_str().as_bytes())?;
+    // SAFETY: both pointers are valid NUL-terminated strings; a null state is allowed.
+    let rc = unsafe {
+        libc::copyfile(
+            src_c.as_ptr(),
+            dest_c.as_ptr(),
+            std::ptr::null_mut(),
+            libc::COPYFILE_XATTR,
+        )
+    };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: as above; failure (attribute absent) is fine.
+    unsafe {
+        libc::removexattr(dest_c.as_ptr(), c"com.apple.quarantine".as_ptr(), 0);
     }
-    fs::remove_file(src)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn copy_xattrs(_src: &Path, _dest: &Path) -> Result<(), std::io::Error> {
     Ok(())
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MoveResult {
-    /// File was moved; path is the actual destination (may be with -1, -2 if collision).
-    Moved(std::path::PathBuf),
+    /// File was placed at `dest` (may be with -1, -2 if collision) using `method`.
+    /// `verified` is set when a cross-volume copy was re-hashed before deleting the source.
+    /// `hash` is the content hash of the file now at `dest` (the source hash it was moved with).
+    /// `replaced` is set when it took the place of an older file (`CollisionMode::Update`).
+    Moved {
+        dest: std::path::PathBuf,
+        method: TransferMethod,
+        verified: bool,
+        hash: String,
+        replaced: Option<ReplacedFile>,
+    },
+    /// A copy was placed at `dest` but the source could not be removed afterwards, so the
+    /// file now exists in both places. Not a failed move: the destination copy is complete.
+    SourceKept {
+        dest: std::path::PathBuf,
+        verified: bool,
+        hash: String,
+        reason: String,
+        replaced: Option<ReplacedFile>,
+    },
     SkippedDuplicate,
+    /// `CollisionMode::Update`: the different file at `existing` is not older than the
+    /// source, so both were left as they are.
+    KeptNewer {
+        existing: PathBuf,
+    },
+}
+
+/// The destination file an update replaced, as it was: what overwrote it is in the record of
+/// the move, so the change can be checked or undone from the report.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ReplacedFile {
+    pub hash: String,
+    /// Its modification time (RFC 3339), if the filesystem has one.
+    pub modified: Option<String>,
+    /// Where it was renamed to, next to the file that took its place; `undo` puts it back.
+    pub kept_at: PathBuf,
+}
+
+/// What to do when the destination name holds a file with other content.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionMode {
+    /// Keep both: the new file gets a name from the collision pattern.
+    #[default]
+    Rename,
+    /// Replace the file if the source was modified more recently, otherwise skip the source;
+    /// the replaced file is kept under a collision name. Files this run put there are never
+    /// replaced.
+    Update,
+}
+
+/// What to do with a match that is a symbolic link to an image rather than the image.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkPolicy {
+    /// Leave the link where it is (counted as skipped).
+    #[default]
+    Skip,
+    /// Move the link itself: the destination gets a link to the same image.
+    Link,
+    /// Put the image it points to at the destination; in move mode the link is removed and
+    /// the image stays where it is.
+    Target,
+}
+
+/// What to do with a match of zero bytes. Every empty file has the same content, so none is
+/// ever taken for a duplicate; an empty "photo" is usually a failed write, so each one is
+/// warned about whatever the policy.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyFilePolicy {
+    /// Move it like any other file, with a warning.
+    #[default]
+    Move,
+    /// Leave it where it is, with a warning.
+    Skip,
+    /// Leave it where it is and count it as an error.
+    Error,
+}
+
+/// How files are put into the destination.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferMode {
+    /// Move the file (rename, or copy+delete across volumes).
+    #[default]
+    Move,
+    /// Copy the file, leaving the source in place.
+    Copy,
+    /// Hardlink the file, falling back to a copy (e.g. across volumes).
+    Hardlink,
+    /// Reflink/clone the file (APFS, Btrfs, XFS), falling back to a copy.
+    Reflink,
+}
+
+impl TransferMode {
+    /// Whether the source file is removed after a successful transfer.
+    pub fn removes_source(self) -> bool {
+        self == TransferMode::Move
+    }
+
+    /// Verb for log lines ("would move", "would hardlink", ...).
+    pub fn verb(self) -> &'static str {
+        match self {
+            TransferMode::Move => "move",
+            TransferMode::Copy => "copy",
+            TransferMode::Hardlink => "hardlink",
+            TransferMode::Reflink => "reflink",
+        }
+    }
+}
+
+/// The mechanism actually used for one file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferMethod {
+    Renamed,
+    Copied,
+    Hardlinked,
+    Reflinked,
+    /// A symbolic link was moved as a link (`SymlinkPolicy::Link`).
+    Symlinked,
+}
+
+impl TransferMethod {
+    /// Name for log lines, as serialized.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TransferMethod::Renamed => "renamed",
+            TransferMethod::Copied => "copied",
+            TransferMethod::Hardlinked => "hardlinked",
+            TransferMethod::Reflinked => "reflinked",
+            TransferMethod::Symlinked => "symlinked",
+        }
+    }
+}
+
+/// How destination paths are laid out under the destination root.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", content = "template", rename_all = "snake_case")]
+pub enum DestLayout {
+    /// Mirror the source's relative folder structure.
+    #[default]
+    Mirror,
+    /// Put every file directly in the destination root; same-named files are collision-renamed.
+    Flatten,
+    /// Place files according to a destination template, e.g. "{year}/{month}/{day}".
+    Template(DestTemplate),
+}
+
+/// What to do with a source file whose content already exists in the destination.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateAction {
+    /// Leave the source file where it is.
+    #[default]
+    Leave,
+    /// Delete the source file permanently.
+    Delete,
+    /// Send the source file to the OS trash / recycle bin.
+    Trash,
+}
+
+/// Options controlling where and how files are moved.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MoveOptions {
+    pub layout: DestLayout,
+    pub duplicate_action: DuplicateAction,
+    pub transfer_mode: TransferMode,
+    /// How colliding names are renamed, e.g. "-{n}" or " ({n})".
+    pub collision_pattern: CollisionPattern,
+    pub on_collision: CollisionMode,
+    /// After moving, remove source directories that are now empty (never the source root).
+    pub remove_empty_dirs: bool,
+    /// With `remove_empty_dirs`, treat directories holding only .DS_Store/Thumbs.db as empty.
+    pub junk_as_empty: bool,
+    /// Re-hash cross-volume copies and keep the source unless the copy matches (on by default).
+    pub verify_copies: bool,
+    /// Keep extended attributes such as Finder tags on copies (macOS only; on by default).
+    pub preserve_xattrs: bool,
+    /// Make destination names valid for exFAT/FAT32/SMB (`Auto`: only on such filesystems).
+    pub sanitize: SanitizeMode,
+    /// Matches that are symbolic links: skipped by default.
+    pub symlinks: SymlinkPolicy,
+    /// Matches of zero bytes: moved, with a warning, by default.
+    pub empty_files: EmptyFilePolicy,
+    /// Match names in Unicode normalization form C, and take destination names that differ from
+    /// the wanted one only in their normalization for the same name (see `existing_variant`);
+    /// on by default.
+    pub normalize_unicode: bool,
+    /// Make each file read-only once it is in place, timestamps and attributes applied, so
+    /// nothing in the archive changes it by accident (see `set_read_only`). Hardlinked files
+    /// and moved symbolic links stay writable, as the flag would be the source's too.
+    pub read_only: bool,
+    /// Tag-in-place mode: rename each match in its own folder, adding this before the
+    /// extension, instead of moving it to a destination. The layout, routes and rejects folder
+    /// do not apply, and no duplicates are looked for.
+    pub tag: Option<NameTag>,
+}
+
+impl Default for MoveOptions {
+    fn default() -> Self {
+        MoveOptions {
+            layout: DestLayout::default(),
+            duplicate_action: DuplicateAction::default(),
+            transfer_mode: TransferMode::default(),
+            collision_pattern: CollisionPattern::default(),
+            on_collision: CollisionMode::default(),
+            remove_empty_dirs: false,
+            junk_as_empty: false,
+            verify_copies: true,
+            preserve_xattrs: true,
+            sanitize: SanitizeMode::default(),
+            symlinks: SymlinkPolicy::default(),
+            empty_files: EmptyFilePolicy::default(),
+            normalize_unicode: true,
+            read_only: false,
+            tag: None,
+        }
+    }
+}
+
+/// Apply `action` to a source file that was skipped as a duplicate (full-content hash match).
+/// Returns Ok(true) if the file was removed, Ok(false) for `Leave`.
+pub fn dispose_duplicate(src: &Path, action: DuplicateAction) -> Result<bool, std::io::Error> {
+    match action {
+        DuplicateAction::Leave => Ok(false),
+        DuplicateAction::Delete => fs::remove_file(src).map(|_| true),
+        DuplicateAction::Trash => trash::delete(src)
+            .map(|_| true)
+            .map_err(|e| std::io::Error::other(e.to_string())),
+    }
+}
+
+/// Build the destination path for `file_path` according to `layout`.
+/// Mirror: source_root + rel => dest_root + rel. Flatten: dest_root + file name.
+/// Template: dest_root + rendered template; `suffix` is the matched suffix number.
+/// `source_root` must be in the same form as `file_path` (the engine canonicalizes it once);
+/// a file that is not under it is an error rather than a guessed path.
+pub fn dest_path_for(
+    source_root: &Path,
+    dest_root: &Path,
+    file_path: &Path,
+    suffix: Option<u32>,
+    layout: &DestLayout,
+) -> Result<PathBuf, DestPathError> {
+    let outside = || DestPathError::OutsideSource {
+        file: file_path.to_path_buf(),
+        root: source_root.to_path_buf(),
+    };
+    match layout {
+        DestLayout::Mirror => {
+            let rel = file_path.strip_prefix(source_root).map_err(|_| outside())?;
+            if rel.as_os_str().is_empty() {
+                return Err(outside());
+            }
+            Ok(dest_root.join(rel))
+        }
+        DestLayout::Flatten => match file_path.file_name() {
+            Some(name) => Ok(dest_root.join(name)),
+            None => Err(DestPathError::NoFileName(file_path.to_path_buf())),
+        },
+        DestLayout::Template(template) => Ok(dest_root.join(template.render(file_path, suffix))),
+    }
 }
 
-/// Build destination path preserving structure: source_root + rel => dest_root + rel.
-pub fn dest_path_for(source_root: &Path, dest_root: &Path, file_path: &Path) -> std::path::PathBuf {
-    let rel = file_path.strip_prefix(source_root).unwrap_or(file_path);
-    dest_root.join(rel)
+/// Error for a file whose destination path cannot be derived.
+#[derive(Debug, thiserror::Error)]
+pub enum DestPathError {
+    #[error("{} is not under the source folder {}", file.display(), root.display())]
+    OutsideSource { file: PathBuf, root: PathBuf },
+    #[error("{} has no file name", .0.display())]
+    NoFileName(PathBuf),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash_of(path: &Path) -> String {
+        hasher::hash_file(path).unwrap()
+    }
+
+    #[test]
+    fn test_copy_path_preserves_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_7612.JPG");
+        let dest = dir.path().join("out.JPG");
+        fs::write(&src, b"image bytes").unwrap();
+        let old = FileTime::from_unix_time(1_500_000_000, 0);
+        filetime::set_file_mtime(&src, old).unwrap();
+
+        copy_then_delete(&src, &dest, false, None, &RunControl::new()).unwrap();
+
+        assert!(!src.exists());
+        let meta = fs::metadata(&dest).unwrap();
+        assert_eq!(FileTime::from_last_modification_time(&meta), old);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_path_preserves_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_7608.JPG");
+        let dest = dir.path().join("out.JPG");
+        fs::write(&src, b"image bytes").unwrap();
+        fs::set_permissions(&src, fs::Permissions::from_mode(0o640)).unwrap();
+
+        copy_then_delete(&src, &dest, false, None, &RunControl::new()).unwrap();
+
+        let mode = fs::metadata(&dest).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+
+    #[test]
+    fn test_read_only_is_set_and_cleared() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("IMG_7612.JPG");
+        fs::write(&path, b"image bytes").unwrap();
+
+        assert!(set_read_only(&path, true).unwrap());
+        assert!(fs::metadata(&path).unwrap().permissions().readonly());
+        assert!(set_read_only(&path, false).unwrap());
+        assert!(!fs::metadata(&path).unwrap().permissions().readonly());
+        assert!(set_read_only(&dir.path().join("missing.JPG"), true).is_err());
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_copy_path_preserves_xattrs() {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        fn c(path: &Path) -> CString {
+            CString::new(path.as_os_str().as_bytes()).unwrap()
+        }
+        fn value(path: &Path, name: &std::ffi::CStr) -> Option<Vec<u8>> {
+            let mut buf = [0u8; 64];
+            // SAFETY: valid strings and a buffer of the given length.
+            let n = unsafe {
+                libc::getxattr(
+                    c(path).as_ptr(),
+                    name.as_ptr(),
+                    buf.as_mut_ptr().cast(),
+                    buf.len(),
+                    0,
+                    0,
+                )
+            };
+            (n >= 0).then(|| buf[..n as usize].to_vec())
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_7612.JPG");
+        let dest = dir.path().join("IMG_7612-moved.JPG");
+        fs::write(&src, b"image bytes").unwrap();
+        for (name, val) in [
+            (c"com.example.framemover.tag", &b"Red"[..]),
+            (c"com.apple.quarantine", &b"0081;00000000;;"[..]),
+        ] {
+            // SAFETY: valid strings and a buffer of the given length.
+            let rc = unsafe {
+                libc::setxattr(
+                    c(&src).as_ptr(),
+                    name.as_ptr(),
+                    val.as_ptr().cast(),
+                    val.len(),
+                    0,
+                    0,
+                )
+            };
+            assert_eq!(rc, 0);
+        }
+
+        copy_then_delete(&src, &dest, true, None, &RunControl::new()).unwrap();
+
+        assert_eq!(
+            value(&dest, c"com.example.framemover.tag").as_deref(),
+            Some(&b"Red"[..])
+        );
+        assert_eq!(value(&dest, c"com.apple.quarantine"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_only_exdev_is_cross_device() {
+        assert!(is_cross_device(&std::io::Error::from_raw_os_error(
+            libc::EXDEV
+        )));
+        assert!(!is_cross_device(&std::io::Error::from_raw_os_error(
+            libc::EACCES
+        )));
+        assert!(!is_cross_device(&std::io::Error::from_raw_os_error(
+            libc::EROFS
+        )));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rename_failure_is_not_turned_into_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_7612.JPG");
+        fs::write(&src, b"image bytes").unwrap();
+        // Destination is an existing non-empty directory: rename fails, but not with EXDEV.
+        let dest = dir.path().join("taken");
+        fs::create_dir(&dest).unwrap();
+        fs::write(dest.join("keep"), b"x").unwrap();
+
+        let err = do_move(&src, &dest, false, None, &RunControl::new()).unwrap_err();
+
+        assert_ne!(err.raw_os_error(), Some(libc::EXDEV));
+        assert!(src.exists());
+        assert!(!partial_path(&dest).exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_cancelled_copy_leaves_no_partial() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_7612.JPG");
+        let dest = dir.path().join("out").join("IMG_7612.JPG");
+        fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        fs::write(&src, vec![7u8; COPY_CHUNK * 3]).unwrap();
+        let partial = partial_path(&dest);
+
+        // At a byte a second, the copy waits in `pace` after its first chunk until cancelled.
+        let control = RunControl::new();
+        control.set_throttle(1);
+        let err = std::thread::scope(|s| {
+            let copy = s.spawn(|| copy_then_delete(&src, &dest, false, None, &control));
+            let first_chunk = || fs::metadata(&partial).is_ok_and(|m| m.len() > 0);
+            while !first_chunk() {
+                assert!(!copy.is_finished(), "the copy ended before its first chunk");
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            assert_eq!(fs::metadata(&partial).unwrap().len(), COPY_CHUNK as u64);
+            control.cancel();
+            copy.join().unwrap().unwrap_err()
+        });
+
+        assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+        assert!(!dest.exists());
+        assert!(!partial.exists());
+        assert_eq!(fs::read(&src).unwrap(), vec![7u8; COPY_CHUNK * 3]);
+    }
+
+    #[test]
+    fn test_verified_copy_deletes_source_only_on_match() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_7612.JPG");
+        let dest = dir.path().join("IMG_7612-moved.JPG");
+        fs::write(&src, b"image bytes").unwrap();
+        let control = RunControl::new();
+
+        let wrong = "0".repeat(64);
+        let err = copy_then_delete(&src, &dest, false, Some(&wrong), &control).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(src.exists());
+        assert!(!dest.exists());
+
+        let hash = hasher::hash_file(&src).unwrap();
+        let source_error = copy_then_delete(&src, &dest, false, Some(&hash), &control).unwrap();
+        assert!(source_error.is_none());
+        assert!(!src.exists());
+        assert_eq!(fs::read(&dest).unwrap(), b"image bytes");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_source_removal_failure_is_reported_as_source_kept() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let (card, image) = (dir.path().join("card"), dir.path().join("IMG_7612.JPG"));
+        fs::create_dir(&card).unwrap();
+        fs::write(&image, b"image bytes").unwrap();
+        // Moving the image a link points to copies it and then removes the link.
+        let src = card.join("IMG_7612.JPG");
+        std::os::unix::fs::symlink(&image, &src).unwrap();
+        let dest = dir.path().join("out/IMG_7612.JPG");
+        let options = MoveOptions {
+            symlinks: SymlinkPolicy::Target,
+            ..Default::default()
+        };
+        let hash = hash_of(&image);
+        let lock = |mode| fs::set_permissions(&card, fs::Permissions::from_mode(mode)).unwrap();
+        lock(0o555);
+        // Root removes it anyway; the failure only happens for other users.
+        if probe_writable(&card).is_ok() {
+            lock(0o755);
+            return;
+        }
+
+        let (claims, control) = (DestClaims::default(), RunControl::new());
+        let result = move_file(&src, &dest, &hash, &options, &claims, &control);
+        lock(0o755);
+
+        match result.unwrap() {
+            MoveResult::SourceKept {
+                dest: kept,
+                verified,
+                hash: kept_hash,
+                replaced: None,
+                ..
+            } => {
+                assert_eq!((kept, kept_hash), (dest.clone(), hash));
+                assert!(verified);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(fs::read(&dest).unwrap(), b"image bytes");
+        assert!(!partial_path(&dest).exists());
+        assert!(is_symlink(&src));
+    }
+
+    #[test]
+    fn test_copy_renames_partial_into_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_7612.JPG");
+        let dest = dir.path().join("IMG_7612-copy.JPG");
+        let data: Vec<u8> = (0..COPY_CHUNK * 2 + 17).map(|i| (i % 251) as u8).collect();
+        fs::write(&src, &data).unwrap();
+
+        copy_file(&src, &dest, false, &RunControl::new()).unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), data);
+        assert!(!partial_path(&dest).exists());
+        assert!(src.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_mode_keeps_source() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_7612.JPG");
+        let dest = dir.path().join("out").join("IMG_7612.JPG");
+        fs::write(&src, b"image bytes").unwrap();
+        let options = MoveOptions {
+            transfer_mode: TransferMode::Hardlink,
+            ..Default::default()
+        };
+
+        let control = RunControl::new();
+        let hash = hash_of(&src);
+        let result =
+            move_file(&src, &dest, &hash, &options, &Default::default(), &control).unwrap();
+
+        assert_eq!(
+            result,
+            MoveResult::Moved {
+                dest: dest.clone(),
+                method: TransferMethod::Hardlinked,
+                verified: false,
+                hash,
+                replaced: None,
+            }
+        );
+        assert!(src.exists());
+        assert_eq!(
+            fs::metadata(&src).unwrap().ino(),
+            fs::metadata(&dest).unwrap().ino()
+        );
+    }
+
+    #[test]
+    fn test_reflink_mode_clones_or_falls_back_to_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_7608.JPG");
+        let dest = dir.path().join("IMG_7608-copy.JPG");
+        fs::write(&src, b"image bytes").unwrap();
+
+        let method = transfer(
+            &src,
+            &dest,
+            TransferMode::Reflink,
+            false,
+            None,
+            &RunControl::new(),
+        )
+        .unwrap()
+        .method;
+
+        assert!(matches!(
+            method,
+            TransferMethod::Reflinked | TransferMethod::Copied
+        ));
+        assert!(src.exists());
+        assert_eq!(fs::read(&dest).unwrap(), b"image bytes");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_long_path_forms() {
+        assert_eq!(
+            long_path(Path::new(r"C:\photos\..\archive\IMG_7612.JPG")),
+            Path::new(r"\\?\C:\archive\IMG_7612.JPG")
+        );
+        assert_eq!(
+            long_path(Path::new(r"\\nas\share\picks")),
+            Path::new(r"\\?\UNC\nas\share\picks")
+        );
+        assert_eq!(
+            long_path(Path::new(r"\\?\D:\already")),
+            Path::new(r"\\?\D:\already")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_move_into_destination_longer_than_260_chars() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_7612.JPG");
+        fs::write(&src, b"image bytes").unwrap();
+        let mut dest = dir.path().join("archive");
+        while dest.as_os_str().len() < 300 {
+            dest.push("a-rather-long-folder-name-from-the-camera");
+        }
+        let dest = dest.join("IMG_7612.JPG");
+        let control = RunControl::new();
+
+        let result = move_file(
+            &src,
+            &dest,
+            &hash_of(&src),
+            &Default::default(),
+            &Default::default(),
+            &control,
+        );
+
+        assert!(matches!(result, Ok(MoveResult::Moved { .. })));
+        assert!(long_path(&dest).exists());
+        assert!(!src.exists());
+    }
+
+    #[test]
+    fn test_consecutive_collisions_follow_pattern() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("IMG_7612.JPG");
+        fs::write(&dest, b"existing").unwrap();
+        let control = RunControl::new();
+
+        for pattern in ["-{n}", " ({n})"] {
+            let options = MoveOptions {
+                collision_pattern: CollisionPattern::parse(pattern).unwrap(),
+                ..Default::default()
+            };
+            let mut landed = Vec::new();
+            for i in 0..3 {
+                let src = dir.path().join(format!("src-{}.JPG", i));
+                fs::write(&src, format!("{} {}", pattern, i)).unwrap();
+                match move_file(
+                    &src,
+                    &dest,
+                    &hash_of(&src),
+                    &options,
+                    &Default::default(),
+                    &control,
+                )
+                .unwrap()
+                {
+                    MoveResult::Moved { dest, .. } => landed.push(dest),
+                    other => panic!("unexpected {:?}", other),
+                }
+            }
+            let expected: Vec<PathBuf> = (1..=3)
+                .map(|n| {
+                    let inserted = pattern.replace("{n}", &n.to_string());
+                    dir.path().join(format!("IMG_7612{}.JPG", inserted))
+                })
+                .collect();
+            assert_eq!(landed, expected);
+        }
+    }
+
+    #[test]
+    fn test_claimed_names_are_not_handed_out_twice() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("IMG_7612.JPG");
+        let claims = DestClaims::default();
+        let pattern = CollisionPattern::default();
+
+        let first = claims.claim(&dest, &pattern, "");
+        let second = claims.claim(&dir.path().join("img_7612.jpg"), &pattern, "");
+        claims.release(&first);
+        let third = claims.claim(&dest, &pattern, "");
+
+        assert_eq!(first, dest);
+        let second_name = if folds_case(dir.path()) {
+            "img_7612-1.jpg"
+        } else {
+            "img_7612.jpg"
+        };
+        assert_eq!(second, dir.path().join(second_name));
+        assert_eq!(third, dest);
+    }
+
+    #[test]
+    fn test_case_sensitivity_is_that_of_the_volume() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("Probe");
+        fs::create_dir(&folder).unwrap();
+        fs::write(folder.join("img_7612.jpg"), b"pick").unwrap();
+
+        // Files the volume finds under another case are the same file there.
+        let insensitive = folder.join("IMG_7612.JPG").exists();
+        assert_eq!(folds_case(&folder), insensitive);
+        assert_eq!(
+            folds_case(&folder.join("not yet").join("made")),
+            insensitive
+        );
+        let claims = DestClaims::new(false);
+        let variant = claims.existing_variant(&folder.join("Img_7612.Jpg"));
+        assert_eq!(variant.is_some(), insensitive);
+    }
+
+    #[test]
+    fn test_case_variant_with_same_content_is_duplicate() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("img_7612.jpg"), b"same").unwrap();
+        let src_dir = tempfile::tempdir().unwrap();
+        let src = src_dir.path().join("IMG_7612.JPG");
+        fs::write(&src, b"same").unwrap();
+        let dest = dir.path().join("IMG_7612.JPG");
+        let control = RunControl::new();
+
+        let result = move_file(
+            &src,
+            &dest,
+            &hash_of(&src),
+            &Default::default(),
+            &Default::default(),
+            &control,
+        );
+
+        if folds_case(dir.path()) {
+            assert_eq!(result.unwrap(), MoveResult::SkippedDuplicate);
+            assert!(src.exists());
+        } else {
+            // Another file where names differ by case.
+            assert!(matches!(result.unwrap(), MoveResult::Moved { .. }));
+            assert!(dest.exists());
+        }
+    }
+
+    #[test]
+    fn test_case_variant_with_different_content_is_renamed() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("img_7612.jpg");
+        fs::write(&existing, b"other").unwrap();
+        let src_dir = tempfile::tempdir().unwrap();
+        let src = src_dir.path().join("IMG_7612.JPG");
+        fs::write(&src, b"new").unwrap();
+        let dest = dir.path().join("IMG_7612.JPG");
+        let control = RunControl::new();
+
+        let result = move_file(
+            &src,
+            &dest,
+            &hash_of(&src),
+            &Default::default(),
+            &Default::default(),
+            &control,
+        );
+
+        let name = if folds_case(dir.path()) {
+            "IMG_7612-1.JPG"
+        } else {
+            "IMG_7612.JPG"
+        };
+        match result.unwrap() {
+            MoveResult::Moved { dest, .. } => assert_eq!(dest, dir.path().join(name)),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(fs::read(&existing).unwrap(), b"other");
+    }
+
+    #[test]
+    fn test_decomposed_name_is_the_same_name_when_normalizing() {
+        let dir = tempfile::tempdir().unwrap();
+        // "café_7612.jpg" as macOS writes it: "e" followed by a combining acute accent.
+        let existing = dir.path().join("cafe\u{301}_7612.jpg");
+        fs::write(&existing, b"other").unwrap();
+        let src_dir = tempfile::tempdir().unwrap();
+        let src = src_dir.path().join("caf\u{e9}_7612.jpg");
+        fs::write(&src, b"new").unwrap();
+        let dest = dir.path().join("caf\u{e9}_7612.jpg");
+
+        let options = MoveOptions::default();
+        assert_eq!(
+            DestClaims::new(true).existing_variant(&dest),
+            Some(existing.clone())
+        );
+        let claims = DestClaims::new(options.normalize_unicode);
+        let result = move_file(
+            &src,
+            &dest,
+            &hash_of(&src),
+            &options,
+            &claims,
+            &RunControl::new(),
+        );
+        match result.unwrap() {
+            MoveResult::Moved { dest, .. } => {
+                assert_eq!(dest, dir.path().join("caf\u{e9}_7612-1.jpg"))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(fs::read(&existing).unwrap(), b"other");
+        assert_eq!(nfc("cafe\u{301}"), "caf\u{e9}");
+    }
+
+    #[test]
+    fn test_update_replaces_only_older_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("IMG_7612.JPG");
+        fs::write(&dest, b"old edit").unwrap();
+        filetime::set_file_mtime(&dest, FileTime::from_unix_time(1_500_000_000, 0)).unwrap();
+        let old_hash = hash_of(&dest);
+        let src_dir = tempfile::tempdir().unwrap();
+        let (older, newer) = (src_dir.path().join("a.JPG"), src_dir.path().join("b.JPG"));
+        fs::write(&older, b"older edit").unwrap();
+        filetime::set_file_mtime(&older, FileTime::from_unix_time(1_400_000_000, 0)).unwrap();
+        fs::write(&newer, b"newer edit").unwrap();
+        let options = MoveOptions {
+            on_collision: CollisionMode::Update,
+            ..Default::default()
+        };
+        let (claims, control) = (DestClaims::default(), RunControl::new());
+
+        let result = move_file(&older, &dest, &hash_of(&older), &options, &claims, &control);
+        assert_eq!(
+            result.unwrap(),
+            MoveResult::KeptNewer {
+                existing: dest.clone()
+            }
+        );
+        assert!(older.exists());
+
+        let result = move_file(&newer, &dest, &hash_of(&newer), &options, &claims, &control);
+        match result.unwrap() {
+            MoveResult::Moved {
+                dest: landed,
+                replaced: Some(replaced),
+                ..
+            } => {
+                assert_eq!(landed, dest);
+                assert_eq!(replaced.hash, old_hash);
+                assert!(replaced.modified.is_some());
+                assert_eq!(replaced.kept_at, dir.path().join("IMG_7612-1.JPG"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(fs::read(&dest).unwrap(), b"newer edit");
+        assert_eq!(
+            fs::read(dir.path().join("IMG_7612-1.JPG")).unwrap(),
+            b"old edit"
+        );
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        // Files this run put there or set aside are renamed around, not replaced.
+        fs::write(&older, b"another edit").unwrap();
+        let result = move_file(&older, &dest, &hash_of(&older), &options, &claims, &control);
+        match result.unwrap() {
+            MoveResult::Moved { dest, replaced, .. } => {
+                assert_eq!(dest, dir.path().join("IMG_7612-2.JPG"));
+                assert!(replaced.is_none());
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_dispose_duplicate_leave_and_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_7605.JPG");
+        fs::write(&src, b"dup").unwrap();
+
+        assert!(!dispose_duplicate(&src, DuplicateAction::Leave).unwrap());
+        assert!(src.exists());
+        assert!(dispose_duplicate(&src, DuplicateAction::Delete).unwrap());
+        assert!(!src.exists());
+    }
+
+    #[test]
+    fn test_dest_path_mirror_and_flatten() {
+        let src_root = Path::new("/cards/A");
+        let dest_root = Path::new("/archive");
+        let file = Path::new("/cards/A/DCIM/100CANON/IMG_7612.JPG");
+        assert_eq!(
+            dest_path_for(src_root, dest_root, file, None, &DestLayout::Mirror).unwrap(),
+            Path::new("/archive/DCIM/100CANON/IMG_7612.JPG")
+        );
+        assert_eq!(
+            dest_path_for(src_root, dest_root, file, None, &DestLayout::Flatten).unwrap(),
+            Path::new("/archive/IMG_7612.JPG")
+        );
+    }
+
+    #[test]
+    fn test_dest_path_mirror_trailing_slash_and_outside_root() {
+        let dest_root = Path::new("/archive");
+        let file = Path::new("/cards/A/DCIM/IMG_7612.JPG");
+        assert_eq!(
+            dest_path_for(
+                Path::new("/cards/A/"),
+                dest_root,
+                file,
+                None,
+                &DestLayout::Mirror
+            )
+            .unwrap(),
+            Path::new("/archive/DCIM/IMG_7612.JPG")
+        );
+        // A form mismatch ("./cards/A" vs "/cards/A") must not invent "/archive/cards/A/..."
+        // or, via an absolute join, a path outside the destination.
+        for root in ["./cards/A", "cards/A", "/cards/B"] {
+            assert!(matches!(
+                dest_path_for(Path::new(root), dest_root, file, None, &DestLayout::Mirror),
+                Err(DestPathError::OutsideSource { .. })
+            ));
+        }
+    }
 }
@@ -0,0 +1,265 @@
+//! Paths as people paste, drop or type them: in quotes, with stray whitespace, as `file://`
+//! URLs, starting with `~` or relative. Cleaned up into absolute paths, or a message that
+//! says what is wrong with them rather than "not a directory".
+
+use std::path::{Path, PathBuf};
+
+/// Error for text that cannot be made into a path.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum PathInputError {
+    #[error("no path given")]
+    Empty,
+    #[error("\"{input}\" is not a usable file URL: {reason}")]
+    BadFileUrl { input: String, reason: &'static str },
+    #[error("\"{input}\" is on another computer ({host}); mount it and use the local path")]
+    RemoteFileUrl { input: String, host: String },
+    #[error("cannot expand \"~\" in \"{input}\": the home folder is not known")]
+    NoHome { input: String },
+    #[error("\"{input}\": \"~name\" for another user's home is not supported; give the full path")]
+    OtherUserHome { input: String },
+}
+
+/// Which paths the text is read as: the system's own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PathStyle {
+    Posix,
+    Windows,
+}
+
+impl PathStyle {
+    const NATIVE: PathStyle = if cfg!(windows) {
+        PathStyle::Windows
+    } else {
+        PathStyle::Posix
+    };
+
+    fn separator(self) -> char {
+        match self {
+            PathStyle::Posix => '/',
+            PathStyle::Windows => '\\',
+        }
+    }
+}
+
+/// Clean up `input` into an absolute path: surrounding whitespace and quotes go, a `file://`
+/// URL is decoded (a Windows one with a host becomes a UNC path), a leading `~` is the home
+/// folder, a relative path is taken from `base`, and repeated or trailing separators and `.`
+/// folders are dropped. Nothing is looked up on disk: the path may not exist.
+pub fn normalize_path(input: &str, base: &Path) -> Result<PathBuf, PathInputError> {
+    let home = std::env::var(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+        .ok()
+        .filter(|h| !h.is_empty());
+    normalize(
+        input,
+        PathStyle::NATIVE,
+        home.as_deref(),
+        &base.to_string_lossy(),
+    )
+    .map(PathBuf::from)
+}
+
+fn normalize(
+    input: &str,
+    style: PathStyle,
+    home: Option<&str>,
+    base: &str,
+) -> Result<String, PathInputError> {
+    let mut text = input.trim();
+    while text.len() >= 2
+        && [('"', '"'), ('\'', '\'')]
+            .iter()
+            .any(|&(open, close)| text.starts_with(open) && text.ends_with(close))
+    {
+        text = text[1..text.len() - 1].trim();
+    }
+    if text.is_empty() {
+        return Err(PathInputError::Empty);
+    }
+    let path = if text.len() >= 5 && text[..5].eq_ignore_ascii_case("file:") {
+        from_file_url(text, style)?
+    } else if let Some(rest) = text.strip_prefix('~') {
+        let in_home = rest.is_empty()
+            || rest.starts_with('/')
+            || style == PathStyle::Windows && rest.starts_with('\\');
+        if !in_home {
+            return Err(PathInputError::OtherUserHome {
+                input: text.to_string(),
+            });
+        }
+        let home = home.ok_or_else(|| PathInputError::NoHome {
+            input: text.to_string(),
+        })?;
+        format!("{}/{}", home, rest)
+    } else {
+        text.to_string()
+    };
+    let path = match style {
+        PathStyle::Posix => path,
+        PathStyle::Windows => path.replace('/', "\\"),
+    };
+    let path = match root_of(&path, style) {
+        Some(_) => path,
+        // Rooted but without a drive: on the drive of the base.
+        None if style == PathStyle::Windows && path.starts_with('\\') => {
+            format!(
+                "{}{}",
+                root_of(base, style).unwrap_or("").trim_end_matches('\\'),
+                path
+            )
+        }
+        None => format!("{}{}{}", base, style.separator(), path),
+    };
+    Ok(tidy(&path, style))
+}
+
+/// The path of a `file:` URL: `file:///photos`, `file:/photos`, `file://localhost/photos`, and
+/// on Windows `file:///C:/photos` and `file://server/share` (a UNC path). Percent escapes are
+/// decoded.
+fn from_file_url(url: &str, style: PathStyle) -> Result<String, PathInputError> {
+    let bad = |reason| PathInputError::BadFileUrl {
+        input: url.to_string(),
+        reason,
+    };
+    let rest = &url[5..];
+    let (host, encoded) = match rest.strip_prefix("//") {
+        Some(after) => match after.find('/') {
+            Some(slash) => after.split_at(slash),
+            None => (after, ""),
+        },
+        None => ("", rest),
+    };
+    if !encoded.starts_with('/') {
+        return Err(bad("it has no path"));
+    }
+    let path = percent_decode(encoded).ok_or_else(|| bad("its %-escapes are not valid UTF-8"))?;
+    let local = host.is_empty() || host.eq_ignore_ascii_case("localhost");
+    match style {
+        PathStyle::Posix if local => Ok(path),
+        PathStyle::Posix => Err(PathInputError::RemoteFileUrl {
+            input: url.to_string(),
+            host: host.to_string(),
+        }),
+        PathStyle::Windows if local => {
+            // "/C:/photos" is "C:/photos".
+            let drive = path.as_bytes().get(1..3);
+            match drive {
+                Some([letter, b':']) if letter.is_ascii_alphabetic() => Ok(path[1..].to_string()),
+                _ => Ok(path),
+            }
+        }
+        PathStyle::Windows => Ok(format!("//{}{}", host, path)),
+    }
+}
+
+/// `text` with its `%XX` escapes decoded; `None` when one is malformed or the bytes are not
+/// UTF-8.
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// The root `path` starts with, when it is absolute: "/", or on Windows "C:\" or the "\\" of a
+/// UNC (or `\\?\`) path.
+fn root_of(path: &str, style: PathStyle) -> Option<&str> {
+    match style {
+        PathStyle::Posix => path.starts_with('/').then(|| &path[..1]),
+        PathStyle::Windows if path.starts_with("\\\\") => Some(&path[..2]),
+        PathStyle::Windows => {
+            let b = path.as_bytes();
+            let drive = b.len() >= 3 && b[0].is_ascii_alphabetic() && b[1] == b':' && b[2] == b'\\';
+            drive.then(|| &path[..3])
+        }
+    }
+}
+
+/// `path` without empty or `.` components, so without repeated or trailing separators; its
+/// root stays as it is.
+fn tidy(path: &str, style: PathStyle) -> String {
+    let sep = style.separator();
+    let root = root_of(path, style).unwrap_or("");
+    let components: Vec<&str> = path[root.len()..]
+        .split(sep)
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect();
+    format!("{}{}", root, components.join(&sep.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_posix_paths_are_cleaned_up() {
+        let posix = |input: &str| normalize(input, PathStyle::Posix, Some("/home/ana"), "/work");
+        let ok = |input: &str| posix(input).unwrap();
+        assert_eq!(ok("  \"/Volumes/CARD/DCIM/\" \n"), "/Volumes/CARD/DCIM");
+        assert_eq!(ok("'/photos//2024/./picks'"), "/photos/2024/picks");
+        assert_eq!(ok("file:///Volumes/CARD/DCIM"), "/Volumes/CARD/DCIM");
+        assert_eq!(
+            ok("FILE://localhost/photos/%C3%89t%C3%A9%202024/"),
+            "/photos/Été 2024"
+        );
+        assert_eq!(ok("file:/photos/a%25b"), "/photos/a%b");
+        assert_eq!(ok("~/Pictures/"), "/home/ana/Pictures");
+        assert_eq!(ok("~"), "/home/ana");
+        assert_eq!(ok("card/DCIM"), "/work/card/DCIM");
+        assert_eq!(ok("./card"), "/work/card");
+        assert_eq!(ok("/"), "/");
+
+        assert_eq!(posix("  \"\" "), Err(PathInputError::Empty));
+        assert!(
+            matches!(posix("file://nas/photos"), Err(PathInputError::RemoteFileUrl { host, .. }) if host == "nas")
+        );
+        assert!(matches!(
+            posix("file:///photos/%E9t%C3"),
+            Err(PathInputError::BadFileUrl { .. })
+        ));
+        assert!(matches!(
+            posix("file:///photos/%zz"),
+            Err(PathInputError::BadFileUrl { .. })
+        ));
+        assert!(matches!(
+            posix("file:photos"),
+            Err(PathInputError::BadFileUrl { .. })
+        ));
+        assert!(matches!(
+            posix("~ana/Pictures"),
+            Err(PathInputError::OtherUserHome { .. })
+        ));
+        let homeless = normalize("~/Pictures", PathStyle::Posix, None, "/work");
+        assert!(matches!(homeless, Err(PathInputError::NoHome { .. })));
+    }
+
+    #[test]
+    fn test_windows_paths_are_cleaned_up() {
+        let windows = |input: &str| {
+            normalize(input, PathStyle::Windows, Some(r"C:\Users\ana"), r"D:\work").unwrap()
+        };
+        assert_eq!(windows(r#" "C:\photos\" "#), r"C:\photos");
+        assert_eq!(windows("C:/photos/2024/"), r"C:\photos\2024");
+        assert_eq!(windows(r"C:\"), r"C:\");
+        assert_eq!(
+            windows("file:///C:/Users/ana/My%20Pictures"),
+            r"C:\Users\ana\My Pictures"
+        );
+        assert_eq!(windows("file://nas/photos/2024"), r"\\nas\photos\2024");
+        assert_eq!(windows("file:////nas/photos"), r"\\nas\photos");
+        assert_eq!(windows(r"\\nas\photos\\2024\"), r"\\nas\photos\2024");
+        assert_eq!(windows(r"\\?\C:\photos"), r"\\?\C:\photos");
+        assert_eq!(windows(r"~\Pictures"), r"C:\Users\ana\Pictures");
+        assert_eq!(windows(r"card\DCIM"), r"D:\work\card\DCIM");
+        assert_eq!(windows(r"\photos"), r"D:\photos");
+    }
+}
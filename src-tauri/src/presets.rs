@@ -0,0 +1,281 @@
+//! Named suffix lists kept for reuse ("Smith wedding picks"), in a JSON file next to the run
+//! history. A preset keeps the text as typed, so loading it back changes nothing.
+
+use std::fs;
+use std::path::Path;
+
+use crate::engine::write_atomically;
+use crate::suffix_parser;
+
+/// One saved suffix list.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuffixPreset {
+    pub name: String,
+    /// As typed into the suffix box, ranges and separators included.
+    pub suffix_input: String,
+    /// Suffix numbers in it, ranges expanded.
+    pub suffix_count: usize,
+    /// RFC 3339, local time.
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// The presets, sorted by name, as every preset command returns them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresetList {
+    pub presets: Vec<SuffixPreset>,
+    /// The file could not be parsed and was set aside; the list started afresh.
+    pub warning: Option<String>,
+}
+
+/// Error of a preset command.
+#[derive(Debug, thiserror::Error)]
+pub enum PresetError {
+    #[error("A preset needs a name")]
+    EmptyName,
+    /// Saving or renaming onto a preset of that name without `overwrite`; the frontend asks
+    /// and tries again.
+    #[error("A preset named \"{name}\" already exists")]
+    Exists { name: String },
+    #[error("There is no preset named \"{name}\"")]
+    NotFound { name: String },
+    #[error("\"{input}\" has no suffix numbers to save")]
+    NoSuffixes { input: String },
+    #[error("Cannot {action} the presets in {}: {source}", path.display())]
+    Store {
+        action: &'static str,
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+}
+
+impl PresetError {
+    /// Stable name of the error for the frontend: "empty_name", "exists", "not_found",
+    /// "no_suffixes" or "store".
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PresetError::EmptyName => "empty_name",
+            PresetError::Exists { .. } => "exists",
+            PresetError::NotFound { .. } => "not_found",
+            PresetError::NoSuffixes { .. } => "no_suffixes",
+            PresetError::Store { .. } => "store",
+        }
+    }
+}
+
+/// The presets at `path`; none when the file does not exist yet.
+pub fn list_presets(path: &Path) -> Result<PresetList, PresetError> {
+    let (presets, warning) = read(path)?;
+    Ok(PresetList { presets, warning })
+}
+
+/// Save `suffix_input` as the preset `name`; an existing one of that name (compared without
+/// case) is replaced only with `overwrite`, keeping its creation time.
+pub fn save_preset(
+    path: &Path,
+    name: &str,
+    suffix_input: &str,
+    overwrite: bool,
+) -> Result<PresetList, PresetError> {
+    let name = checked_name(name)?;
+    let suffix_count = suffix_parser::parse_suffixes(suffix_input).len();
+    if suffix_count == 0 {
+        return Err(PresetError::NoSuffixes {
+            input: suffix_input.trim().to_string(),
+        });
+    }
+    let (mut presets, warning) = read(path)?;
+    let now = chrono::Local::now().to_rfc3339();
+    let mut created_at = now.clone();
+    if let Some(i) = position(&presets, name) {
+        if !overwrite {
+            return Err(PresetError::Exists {
+                name: presets[i].name.clone(),
+            });
+        }
+        created_at = presets.remove(i).created_at;
+    }
+    presets.push(SuffixPreset {
+        name: name.to_string(),
+        suffix_input: suffix_input.to_string(),
+        suffix_count,
+        created_at,
+        updated_at: now,
+    });
+    write(path, presets, warning)
+}
+
+/// Rename the preset `from` to `to`; a different preset already named `to` is replaced only
+/// with `overwrite`.
+pub fn rename_preset(
+    path: &Path,
+    from: &str,
+    to: &str,
+    overwrite: bool,
+) -> Result<PresetList, PresetError> {
+    let to = checked_name(to)?;
+    let (mut presets, warning) = read(path)?;
+    let i = position(&presets, from).ok_or_else(|| PresetError::NotFound {
+        name: from.trim().to_string(),
+    })?;
+    let mut preset = presets.remove(i);
+    if let Some(j) = position(&presets, to) {
+        if !overwrite {
+            return Err(PresetError::Exists {
+                name: presets[j].name.clone(),
+            });
+        }
+        presets.remove(j);
+    }
+    preset.name = to.to_string();
+    preset.updated_at = chrono::Local::now().to_rfc3339();
+    presets.push(preset);
+    write(path, presets, warning)
+}
+
+/// Delete the preset `name`.
+pub fn delete_preset(path: &Path, name: &str) -> Result<PresetList, PresetError> {
+    let (mut presets, warning) = read(path)?;
+    let i = position(&presets, name).ok_or_else(|| PresetError::NotFound {
+        name: name.trim().to_string(),
+    })?;
+    presets.remove(i);
+    write(path, presets, warning)
+}
+
+fn checked_name(name: &str) -> Result<&str, PresetError> {
+    match name.trim() {
+        "" => Err(PresetError::EmptyName),
+        name => Ok(name),
+    }
+}
+
+fn position(presets: &[SuffixPreset], name: &str) -> Option<usize> {
+    let name = name.trim().to_lowercase();
+    presets.iter().position(|p| p.name.to_lowercase() == name)
+}
+
+/// The presets at `path`. A file that cannot be parsed is set aside next to it (as
+/// `<name>.corrupt`) and the list starts empty, with the warning saying so.
+fn read(path: &Path) -> Result<(Vec<SuffixPreset>, Option<String>), PresetError> {
+    let store = |action, source| PresetError::Store {
+        action,
+        path: path.to_path_buf(),
+        source,
+    };
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), None)),
+        Err(e) => return Err(store("read", e)),
+    };
+    match serde_json::from_slice(&data) {
+        Ok(presets) => Ok((presets, None)),
+        Err(e) => {
+            let mut aside = path.as_os_str().to_owned();
+            aside.push(".corrupt");
+            fs::rename(path, &aside).map_err(|e| store("set aside", e))?;
+            let warning = format!(
+                "Presets {} were unreadable ({}); the file was moved to {} and the presets started afresh",
+                path.display(),
+                e,
+                Path::new(&aside).display()
+            );
+            Ok((Vec::new(), Some(warning)))
+        }
+    }
+}
+
+fn write(
+    path: &Path,
+    mut presets: Vec<SuffixPreset>,
+    warning: Option<String>,
+) -> Result<PresetList, PresetError> {
+    presets.sort_by_key(|p| p.name.to_lowercase());
+    let json = serde_json::to_vec_pretty(&presets).map_err(std::io::Error::other);
+    json.and_then(|json| write_atomically(path, &json))
+        .map_err(|source| PresetError::Store {
+            action: "save",
+            path: path.to_path_buf(),
+            source,
+        })?;
+    Ok(PresetList { presets, warning })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_are_saved_renamed_and_deleted_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app/presets.json");
+        assert_eq!(list_presets(&path).unwrap(), PresetList::default());
+
+        let typed = "7612, 7608\n7600-7603 ";
+        let list = save_preset(&path, " Smith wedding picks ", typed, false).unwrap();
+        let saved = list.presets[0].clone();
+        assert_eq!(saved.name, "Smith wedding picks");
+        assert_eq!(
+            (saved.suffix_input.as_str(), saved.suffix_count),
+            (typed, 6)
+        );
+        save_preset(&path, "may trip", "101", false).unwrap();
+        let names = |list: PresetList| list.presets.into_iter().map(|p| p.name).collect::<Vec<_>>();
+        assert_eq!(
+            names(list_presets(&path).unwrap()),
+            ["may trip", "Smith wedding picks"]
+        );
+
+        let err = save_preset(&path, "smith WEDDING picks", "1", false).unwrap_err();
+        assert!(matches!(err, PresetError::Exists { ref name } if name == "Smith wedding picks"));
+        assert_eq!(err.kind(), "exists");
+        let list = save_preset(&path, "Smith wedding picks", "1, 2", true).unwrap();
+        let replaced = &list.presets[1];
+        assert_eq!(
+            (replaced.suffix_count, &replaced.created_at),
+            (2, &saved.created_at)
+        );
+        assert_eq!(
+            save_preset(&path, "  ", "1", false).unwrap_err().kind(),
+            "empty_name"
+        );
+        assert_eq!(
+            save_preset(&path, "none", "IMG", false).unwrap_err().kind(),
+            "no_suffixes"
+        );
+
+        let err = rename_preset(&path, "May trip", "Smith wedding picks", false).unwrap_err();
+        assert_eq!(err.kind(), "exists");
+        assert_eq!(
+            names(rename_preset(&path, "May trip", "May trip selects", false).unwrap()),
+            ["May trip selects", "Smith wedding picks"]
+        );
+        let list = rename_preset(&path, "may trip selects", "Smith wedding picks", true).unwrap();
+        assert_eq!(list.presets.len(), 1);
+        assert_eq!(list.presets[0].suffix_input, "101");
+        assert_eq!(
+            rename_preset(&path, "gone", "x", false).unwrap_err().kind(),
+            "not_found"
+        );
+        assert!(delete_preset(&path, "SMITH wedding picks")
+            .unwrap()
+            .presets
+            .is_empty());
+        assert_eq!(
+            delete_preset(&path, "Smith wedding picks")
+                .unwrap_err()
+                .kind(),
+            "not_found"
+        );
+
+        fs::write(&path, "[{ truncated").unwrap();
+        let list = list_presets(&path).unwrap();
+        assert!(list.presets.is_empty());
+        assert!(list.warning.unwrap().contains("presets.json.corrupt"));
+        assert!(dir.path().join("app/presets.json.corrupt").exists());
+        let list = save_preset(&path, "after", "7612", false).unwrap();
+        assert_eq!((list.presets.len(), list.warning), (1, None));
+    }
+}
@@ -0,0 +1,167 @@
+//! Showing a file or folder in the system file manager: Finder, Explorer, or whichever one the
+//! Linux desktop has. A file is shown selected in its folder; a folder is opened.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Error for a path that cannot be shown.
+#[derive(Debug, thiserror::Error)]
+pub enum RevealError {
+    #[error("{} does not exist (it may have been moved or deleted since)", path.display())]
+    NotFound { path: PathBuf },
+    #[error("Cannot open the file manager for {}: {reason}", path.display())]
+    Launch { path: PathBuf, reason: String },
+}
+
+/// One way to show a path, tried in turn until one works.
+#[derive(Debug)]
+struct Launch {
+    program: &'static str,
+    args: Vec<OsString>,
+    /// Whether a failed exit status means it did not work; Explorer exits with 1 even when
+    /// it opened the window.
+    check_status: bool,
+}
+
+/// Show `path` in the file manager: a file selected in its folder, a folder opened. Where the
+/// file manager cannot select a file, its folder is opened instead.
+pub fn reveal_path(path: &Path) -> Result<(), RevealError> {
+    let resolved = dunce::canonicalize(path).map_err(|_| RevealError::NotFound {
+        path: path.to_path_buf(),
+    })?;
+    let mut reason = String::from("no file manager found");
+    for launch in launches(&resolved, resolved.is_dir()) {
+        match launch_status(&launch) {
+            Ok(status) if status.success() || !launch.check_status => return Ok(()),
+            Ok(status) => reason = format!("{} failed ({})", launch.program, status),
+            Err(e) => reason = format!("cannot run {}: {}", launch.program, e),
+        }
+    }
+    Err(RevealError::Launch {
+        path: path.to_path_buf(),
+        reason,
+    })
+}
+
+fn launch_status(launch: &Launch) -> std::io::Result<std::process::ExitStatus> {
+    let mut command = Command::new(launch.program);
+    #[cfg(windows)]
+    {
+        // Explorer parses its own command line: "/select," and the quoted path must stay one
+        // argument, which the usual quoting of arguments breaks.
+        use std::os::windows::process::CommandExt;
+        for arg in &launch.args {
+            command.raw_arg(arg);
+        }
+    }
+    #[cfg(not(windows))]
+    command.args(&launch.args);
+    command.status()
+}
+
+#[cfg(target_os = "macos")]
+fn launches(path: &Path, is_dir: bool) -> Vec<Launch> {
+    let args = if is_dir {
+        vec![path.into()]
+    } else {
+        vec!["-R".into(), path.into()]
+    };
+    vec![Launch {
+        program: "open",
+        args,
+        check_status: true,
+    }]
+}
+
+#[cfg(windows)]
+fn launches(path: &Path, is_dir: bool) -> Vec<Launch> {
+    let mut arg = OsString::from(if is_dir { "\"" } else { "/select,\"" });
+    arg.push(path);
+    arg.push("\"");
+    vec![Launch {
+        program: "explorer",
+        args: vec![arg],
+        check_status: false,
+    }]
+}
+
+/// The file manager's D-Bus interface selects files (Nautilus, Dolphin, Nemo, Thunar...);
+/// without it, the folder is opened with the desktop's default application.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn launches(path: &Path, is_dir: bool) -> Vec<Launch> {
+    let folder = if is_dir {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    };
+    let mut launches = Vec::new();
+    if !is_dir {
+        let mut item = OsString::from("array:string:");
+        item.push(file_uri(path));
+        launches.push(Launch {
+            program: "dbus-send",
+            args: vec![
+                "--session".into(),
+                "--print-reply".into(),
+                "--dest=org.freedesktop.FileManager1".into(),
+                "--type=method_call".into(),
+                "/org/freedesktop/FileManager1".into(),
+                "org.freedesktop.FileManager1.ShowItems".into(),
+                item,
+                "string:".into(),
+            ],
+            check_status: true,
+        });
+    }
+    launches.push(Launch {
+        program: "xdg-open",
+        args: vec![folder.into()],
+        check_status: true,
+    });
+    launches
+}
+
+/// `file://` URI of an absolute path, its bytes percent-encoded but for the unreserved ones.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn file_uri(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    let mut uri = String::from("file://");
+    for &b in path.as_os_str().as_bytes() {
+        if b.is_ascii_alphanumeric() || b"/-._~".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reveal_checks_the_path_and_picks_how_to_show_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let gone = dir.path().join("IMG_7612.JPG");
+        let err = reveal_path(&gone).unwrap_err();
+        assert!(matches!(err, RevealError::NotFound { ref path } if path == &gone));
+        assert!(err.to_string().contains("IMG_7612.JPG does not exist"));
+
+        let file = dir.path().join("2024/05/IMG_7612-1.JPG");
+        assert!(!launches(&file, false).is_empty());
+        assert!(!launches(dir.path(), true).is_empty());
+        #[cfg(not(any(target_os = "macos", windows)))]
+        {
+            let uri = file_uri(Path::new("/photos/Été 2024/IMG_7612.JPG"));
+            assert_eq!(uri, "file:///photos/%C3%89t%C3%A9%202024/IMG_7612.JPG");
+            let folder = launches(dir.path(), true);
+            assert_eq!(folder.len(), 1);
+            assert_eq!(folder[0].args, [dir.path().as_os_str()]);
+            let selected = launches(&file, false);
+            assert_eq!(selected[0].program, "dbus-send");
+            assert_eq!(selected[1].args, [file.parent().unwrap().as_os_str()]);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Make file names safe for exFAT/FAT32/SMB destinations.
+
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+
+/// When destination names are sanitized.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum SanitizeMode {
+    /// Sanitize only when the destination is on exFAT, FAT or an SMB share.
+    #[default]
+    Auto,
+    /// Always sanitize.
+    On,
+    /// Keep names as they are.
+    Off,
+}
+
+impl SanitizeMode {
+    /// Whether names written under `dest_root` should be sanitized.
+    pub fn applies_to(self, dest_root: &Path) -> bool {
+        match self {
+            SanitizeMode::On => true,
+            SanitizeMode::Off => false,
+            SanitizeMode::Auto => is_restricted_fs(dest_root),
+        }
+    }
+}
+
+const INVALID_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Sanitize one path component: invalid and control characters become "_", trailing dots and
+/// spaces are trimmed, and Windows reserved names get a trailing "_" ("CON.jpg" -> "CON_.jpg").
+pub fn sanitize_name(name: &str) -> Cow<'_, str> {
+    let mut out: String = name
+        .chars()
+        .map(|c| {
+            if INVALID_CHARS.contains(&c) || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    out.truncate(out.trim_end_matches(['.', ' ']).len());
+    if out.is_empty() {
+        out.push('_');
+    }
+    let base = out.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(base)) {
+        out.insert(base.len(), '_');
+    }
+    if out == name {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(out)
+    }
+}
+
+/// Sanitize every component of `dest` below `dest_root`; `dest_root` itself is kept as given.
+pub fn sanitize_under(dest_root: &Path, dest: &Path) -> PathBuf {
+    let Ok(rel) = dest.strip_prefix(dest_root) else {
+        return dest.to_path_buf();
+    };
+    let mut out = dest_root.to_path_buf();
+    for component in rel.components() {
+        match component {
+            Component::Normal(name) => out.push(sanitize_name(&name.to_string_lossy()).as_ref()),
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+/// Whether `path` is on a filesystem with Windows-style name restrictions.
+#[cfg(target_os = "linux")]
+fn is_restricted_fs(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    const MSDOS: u32 = 0x4d44;
+    const EXFAT: u32 = 0x2011_bab0;
+    const SMB: u32 = 0x517b;
+    const CIFS: u32 = 0xff53_4d42;
+    const SMB2: u32 = 0xfe53_4d42;
+
+    let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut st = std::mem::MaybeUninit::<libc::statfs>::uninit();
+    // SAFETY: valid NUL-terminated path and a buffer of the right type.
+    if unsafe { libc::statfs(c_path.as_ptr(), st.as_mut_ptr()) } != 0 {
+        return false;
+    }
+    // SAFETY: statfs succeeded, so the struct is initialized.
+    let f_type = unsafe { st.assume_init() }.f_type as u32;
+    matches!(f_type, MSDOS | EXFAT | SMB | CIFS | SMB2)
+}
+
+/// Whether `path` is on a filesystem with Windows-style name restrictions.
+#[cfg(target_os = "macos")]
+fn is_restricted_fs(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut st = std::mem::MaybeUninit::<libc::statfs>::uninit();
+    // SAFETY: valid NUL-terminated path and a buffer of the right type.
+    if unsafe { libc::statfs(c_path.as_ptr(), st.as_mut_ptr()) } != 0 {
+        return false;
+    }
+    // SAFETY: statfs succeeded, so the struct (and its NUL-terminated type name) is initialized.
+    let st = unsafe { st.assume_init() };
+    let name = unsafe { std::ffi::CStr::from_ptr(st.f_fstypename.as_ptr()) };
+    matches!(name.to_bytes(), b"exfat" | b"msdos" | b"smbfs")
+}
+
+/// Windows already refuses such names at the source, so there is nothing to detect.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn is_restricted_fs(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(sanitize_name("IMG_7612.JPG"), "IMG_7612.JPG");
+        assert!(matches!(sanitize_name("IMG_7612.JPG"), Cow::Borrowed(_)));
+        assert_eq!(
+            sanitize_name("2024-05-06 14:22:33.jpg"),
+            "2024-05-06 14_22_33.jpg"
+        );
+        assert_eq!(sanitize_name("what?*.jpg"), "what__.jpg");
+        assert_eq!(sanitize_name("tab\there.jpg"), "tab_here.jpg");
+        assert_eq!(sanitize_name("edited. . "), "edited");
+        assert_eq!(sanitize_name("..."), "_");
+    }
+
+    #[test]
+    fn test_reserved_names() {
+        assert_eq!(sanitize_name("CON.jpg"), "CON_.jpg");
+        assert_eq!(sanitize_name("com1"), "com1_");
+        assert_eq!(sanitize_name("aux.tar.gz"), "aux_.tar.gz");
+        assert_eq!(sanitize_name("CONSOLE.jpg"), "CONSOLE.jpg");
+    }
+
+    #[test]
+    fn test_sanitize_under_keeps_root() {
+        let root = Path::new("/Volumes/SD: card");
+        let dest = root.join("trip: day 1").join("IMG 7612.JPG.");
+        assert_eq!(
+            sanitize_under(root, &dest),
+            root.join("trip_ day 1").join("IMG 7612.JPG")
+        );
+    }
+}
@@ -1,9 +1,14 @@
 //! Scan directories for image files and filter by filename suffix.
 
+use std::borrow::Cow;
 use std::collections::HashSet;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+use crate::exclude::ExcludeGlobs;
+use crate::exif_info;
+use crate::mover::nfc;
+
 /// Image extensions (lowercase) we consider for matching and hashing.
 pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "heic", "gif", "tiff", "tif", "webp"];
 
@@ -12,58 +17,309 @@ fn is_image_extension(ext: &std::ffi::OsStr) -> bool {
     IMAGE_EXTENSIONS.contains(&ext.as_str())
 }
 
-/// Check if the file's stem (filename without extension) ends with any of the suffix numbers.
-pub fn stem_ends_with_suffix(stem: &str, suffixes: &HashSet<u32>) -> bool {
-    for & suffix in suffixes {
-        if stem.ends_with(&suffix.to_string()) {
-            return true;
+/// Return the suffix number the file's stem (filename without extension) ends with.
+/// When several match (e.g. 612 and 7612 for IMG_7612), the longest, most specific one wins.
+pub fn matched_suffix(stem: &str, suffixes: &HashSet<u32>) -> Option<u32> {
+    let mut best: Option<(usize, u32)> = None;
+    for &suffix in suffixes {
+        let digits = suffix.to_string();
+        if stem.ends_with(&digits) && best.is_none_or(|(len, _)| digits.len() > len) {
+            best = Some((digits.len(), suffix));
         }
     }
-    false
+    best.map(|(_, suffix)| suffix)
 }
 
 /// One candidate image file (path relative to source root is computed by caller if needed).
 #[derive(Clone, Debug)]
 pub struct ImageEntry {
     pub path: std::path::PathBuf,
+    /// The requested suffix number this file matched; 0 for an image that matched none
+    /// (see `Scan::unmatched`).
+    pub suffix: u32,
+    /// Size in bytes at scan time, for byte-based progress.
+    pub size: u64,
+    /// Index of the source root the file was found under (set by the engine; runs can have
+    /// several roots).
+    pub root: usize,
+    /// Index of the destination it goes to (set by the engine; see `RunOptions::routes`).
+    pub route: usize,
+    /// The path is a symbolic link to the image; `size` is the image's.
+    pub symlink: bool,
+}
+
+/// How many files a scan looked at: all regular files, and those with an image extension.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScanCount {
+    pub files: u64,
+    pub images: u64,
+    /// Matching by metadata: the images whose metadata had a frame number, and those whose
+    /// had none (or could not be read), matched by name instead.
+    pub numbered: u64,
+    pub unnumbered: u64,
 }
 
-/// Recursively scan `source_dir` for image files whose stem ends with any of `suffixes`.
-/// Returns paths in arbitrary order.
+/// Files between two calls of a scan's progress callback.
+pub const SCAN_PROGRESS_EVERY: u64 = 1000;
+
+/// What a scan found, in arbitrary order.
+#[derive(Debug, Default)]
+pub struct Scan {
+    /// Every file visited, matching or not.
+    pub count: ScanCount,
+    pub entries: Vec<ImageEntry>,
+    /// Files that matched a suffix but whose name matched an exclusion glob, with that glob.
+    pub excluded: Vec<(PathBuf, String)>,
+    /// Files whose stem matched a suffix but whose extension is not an image one: the suffix
+    /// and the extension, lowercase.
+    pub other_types: Vec<(u32, String)>,
+    /// With `keep_unmatched`: the image files that matched no suffix (and no exclusion glob).
+    pub unmatched: Vec<ImageEntry>,
+    /// Symbolic links whose name matched a suffix but which point to nothing.
+    pub broken_links: Vec<PathBuf>,
+    /// `progress` asked to stop: what is here is what was found before.
+    pub stopped: bool,
+}
+
+/// Recursively scan `source_dir` for image files whose stem ends with any of `suffixes`,
+/// leaving out those whose name matches `exclude`. With `keep_unmatched` the other images are
+/// returned too. With `normalize`, names are matched in NFC (see `match_path`); with
+/// `by_metadata`, images are matched by the frame number in their metadata when they have one
+/// (see `frame_suffix`). `progress` gets the count so far every `SCAN_PROGRESS_EVERY` files,
+/// and stops the walk by returning false (see `Scan::stopped`).
 pub fn scan_source_for_suffixes(
     source_dir: &Path,
     suffixes: &HashSet<u32>,
-) -> std::io::Result<Vec<ImageEntry>> {
-    let mut out = Vec::new();
+    exclude: &ExcludeGlobs,
+    keep_unmatched: bool,
+    normalize: bool,
+    by_metadata: bool,
+    mut progress: impl FnMut(ScanCount) -> bool,
+) -> std::io::Result<Scan> {
+    let mut out = Scan::default();
     for entry in WalkDir::new(source_dir)
         .follow_links(false)
         .into_iter()
         .filter_map(|e| e.ok())
     {
         let path = entry.path();
+        let name = match_path(path, normalize);
+        // Walking does not follow links; their target is looked at here.
+        let symlink = entry.path_is_symlink();
+        let target = if symlink {
+            match std::fs::metadata(path) {
+                Ok(meta) => Some(meta),
+                Err(_) => {
+                    if image_suffix(&name, suffixes).is_some() {
+                        out.broken_links.push(path.to_path_buf());
+                    }
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
         if !path.is_file() {
             continue;
         }
-        let ext = match path.extension() {
-            Some(e) => e,
-            None => continue,
+        out.count.files += 1;
+        if out.count.files % SCAN_PROGRESS_EVERY == 0 && !progress(out.count) {
+            out.stopped = true;
+            break;
+        }
+        let Some(ext) = name.extension() else {
+            continue;
         };
-        if !is_image_extension(ext) {
+        if is_image_extension(ext) {
+            out.count.images += 1;
+        } else {
+            // Only names that match are worth reporting; no other work for most files.
+            let stem = name.file_stem().and_then(|s| s.to_str());
+            if let Some(suffix) = stem.and_then(|s| matched_suffix(s, suffixes)) {
+                out.other_types
+                    .push((suffix, ext.to_string_lossy().to_lowercase()));
+            }
             continue;
         }
-        let stem = match path.file_stem().and_then(|s| s.to_str()) {
-            Some(s) => s,
-            None => continue,
+        let (suffix, numbered) = frame_suffix(path, &name, suffixes, by_metadata);
+        match numbered {
+            Some(true) => out.count.numbered += 1,
+            Some(false) => out.count.unnumbered += 1,
+            None => {}
+        }
+        if suffix.is_none() && !keep_unmatched {
+            continue;
+        }
+        if let Some(glob) = exclude.matching(&name) {
+            if suffix.is_some() {
+                out.excluded.push((path.to_path_buf(), glob.to_string()));
+            }
+            continue;
+        }
+        let size = match &target {
+            Some(meta) => meta.len(),
+            None => entry.metadata().map(|m| m.len()).unwrap_or(0),
+        };
+        let image = ImageEntry {
+            path: path.to_path_buf(),
+            suffix: suffix.unwrap_or(0),
+            size,
+            root: 0,
+            route: 0,
+            symlink,
         };
-        if stem_ends_with_suffix(stem, suffixes) {
-            out.push(ImageEntry {
-                path: path.to_path_buf(),
-            });
+        if suffix.is_some() {
+            out.entries.push(image);
+        } else {
+            out.unmatched.push(image);
         }
     }
     Ok(out)
 }
 
+/// An image file as `list_source_images` found it: what matching it against suffixes needs,
+/// so that it can be matched again without walking its folder.
+#[derive(Clone, Debug)]
+pub struct ListedImage {
+    pub path: PathBuf,
+    /// The image's size; for a link, its target's.
+    pub size: u64,
+    pub symlink: bool,
+    /// Listed `by_metadata`: the name the camera gave it, if its metadata has one.
+    pub frame: Option<String>,
+}
+
+impl ListedImage {
+    /// The suffix it matches, as `scan_source_for_suffixes` would match it.
+    pub fn suffix(&self, suffixes: &HashSet<u32>, normalize: bool) -> Option<u32> {
+        match &self.frame {
+            Some(frame) => matched_suffix(frame, suffixes),
+            None => image_suffix(&match_path(&self.path, normalize), suffixes),
+        }
+    }
+}
+
+/// Recursively list the image files under `source_dir`, links to images included and broken
+/// ones left out, sorted by path: what `scan_source_for_suffixes` would look at for any
+/// suffixes. `by_metadata` reads the camera's name of each (see `frame_suffix`). `progress`
+/// gets the number of files visited every `SCAN_PROGRESS_EVERY`; `None` when it stopped the
+/// walk by returning false.
+pub fn list_source_images(
+    source_dir: &Path,
+    by_metadata: bool,
+    mut progress: impl FnMut(u64) -> bool,
+) -> Option<Vec<ListedImage>> {
+    let mut images = Vec::new();
+    let mut files = 0;
+    for entry in WalkDir::new(source_dir)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        // Following a link here, as the scan does: a broken one is not a file.
+        let Ok(meta) = std::fs::metadata(path) else {
+            continue;
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        files += 1;
+        if files % SCAN_PROGRESS_EVERY == 0 && !progress(files) {
+            return None;
+        }
+        if !path.extension().is_some_and(is_image_extension) {
+            continue;
+        }
+        images.push(ListedImage {
+            path: path.to_path_buf(),
+            size: meta.len(),
+            symlink: entry.path_is_symlink(),
+            frame: by_metadata.then(|| exif_info::frame_name(path)).flatten(),
+        });
+    }
+    images.sort_by(|a, b| a.path.cmp(&b.path));
+    Some(images)
+}
+
+/// The suffix an image file's name matches, or `None` for other files.
+fn image_suffix(path: &Path, suffixes: &HashSet<u32>) -> Option<u32> {
+    if !is_image_extension(path.extension()?) {
+        return None;
+    }
+    matched_suffix(path.file_stem()?.to_str()?, suffixes)
+}
+
+/// The suffix an image matches, by `name` (`path` as matched; see `match_path`) or, with
+/// `by_metadata`, by the name the camera gave it (see `exif_info::frame_name`) when its
+/// metadata has one: then that decides, whatever the file is called now. The second value says
+/// whether it had one; `None` without `by_metadata`.
+fn frame_suffix(
+    path: &Path,
+    name: &Path,
+    suffixes: &HashSet<u32>,
+    by_metadata: bool,
+) -> (Option<u32>, Option<bool>) {
+    if !by_metadata {
+        return (image_suffix(name, suffixes), None);
+    }
+    match exif_info::frame_name(path) {
+        Some(frame) => (matched_suffix(&frame, suffixes), Some(true)),
+        None => (image_suffix(name, suffixes), Some(false)),
+    }
+}
+
+/// `path` with its file name in Unicode normalization form C when `normalize` is set: the form
+/// names are matched in, so one written decomposed (as macOS does) matches the same suffixes and
+/// exclusion globs as one typed elsewhere. Only for matching; files keep their own names.
+pub fn match_path(path: &Path, normalize: bool) -> Cow<'_, Path> {
+    let name = path.file_name().and_then(|n| n.to_str());
+    match name.map(nfc) {
+        Some(Cow::Owned(composed)) if normalize => Cow::Owned(path.with_file_name(composed)),
+        _ => Cow::Borrowed(path),
+    }
+}
+
+/// The entry for a single file, if it is an image whose stem ends with one of `suffixes`
+/// (matched as by `scan_source_for_suffixes`).
+pub fn match_file(
+    path: &Path,
+    suffixes: &HashSet<u32>,
+    normalize: bool,
+    by_metadata: bool,
+) -> Option<ImageEntry> {
+    if !is_image_extension(path.extension()?) {
+        return None;
+    }
+    let name = match_path(path, normalize);
+    let suffix = frame_suffix(path, &name, suffixes, by_metadata).0?;
+    entry_for(path, suffix)
+}
+
+/// The entry for a single image file that matched no suffix, as in `Scan::unmatched`.
+pub fn unmatched_file(path: &Path) -> Option<ImageEntry> {
+    if !is_image_extension(path.extension()?) {
+        return None;
+    }
+    entry_for(path, 0)
+}
+
+fn entry_for(path: &Path, suffix: u32) -> Option<ImageEntry> {
+    let symlink = std::fs::symlink_metadata(path)
+        .ok()?
+        .file_type()
+        .is_symlink();
+    let meta = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+    Some(ImageEntry {
+        path: path.to_path_buf(),
+        suffix,
+        size: meta.len(),
+        root: 0,
+        route: 0,
+        symlink,
+    })
+}
+
 /// Recursively list all image files under `dir` (for building destination hash index).
 pub fn list_images_under(dir: &Path) -> std::io::Result<Vec<std::path::PathBuf>> {
     let mut out = Vec::new();
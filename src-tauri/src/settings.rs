@@ -0,0 +1,110 @@
+//! What the app remembers between launches: the folders, suffixes and options of the last run
+//! and the folders picked recently, in a JSON file written on every change.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::engine::write_atomically;
+
+/// Folders each recent list keeps, the latest first.
+pub const MAX_RECENT_FOLDERS: usize = 10;
+
+/// The app's saved state. Every field has a default, so a file from an older version, or one
+/// missing fields, loads with the rest.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    pub sources: Vec<PathBuf>,
+    pub dest: Option<PathBuf>,
+    pub suffix_input: String,
+    pub dry_run: bool,
+    pub verbose: bool,
+    /// No system notification when a run ends while the window is in the background.
+    pub disable_notifications: bool,
+    /// The app's other options by name, as it sent them: options added later are kept without
+    /// a change here, and those it no longer knows are ignored when loaded.
+    pub options: serde_json::Map<String, serde_json::Value>,
+    pub recent_sources: Vec<PathBuf>,
+    pub recent_dests: Vec<PathBuf>,
+}
+
+impl Settings {
+    /// The recent lists without repeats or empty entries, cut to `MAX_RECENT_FOLDERS`.
+    fn tidied(mut self) -> Settings {
+        for recent in [&mut self.recent_sources, &mut self.recent_dests] {
+            let mut seen = Vec::new();
+            recent.retain(|f| {
+                let keep = !f.as_os_str().is_empty() && !seen.contains(f);
+                seen.push(f.clone());
+                keep
+            });
+            recent.truncate(MAX_RECENT_FOLDERS);
+        }
+        self
+    }
+}
+
+/// The settings saved at `path`; the defaults when there are none or they cannot be read, as
+/// on a first launch.
+pub fn load_settings(path: &Path) -> Settings {
+    fs::read(path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<Settings>(&data).ok())
+        .map(Settings::tidied)
+        .unwrap_or_default()
+}
+
+/// Save `settings` at `path`, replacing the file at once so a crash mid-write leaves the
+/// previous settings; creates the folder.
+pub fn save_settings(path: &Path, settings: Settings) -> std::io::Result<()> {
+    let json = serde_json::to_vec_pretty(&settings.tidied()).map_err(std::io::Error::other)?;
+    write_atomically(path, &json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_round_trip_and_fall_back_to_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config/settings.json");
+        assert_eq!(load_settings(&path), Settings::default());
+
+        let mut settings = Settings {
+            sources: vec![PathBuf::from("/card/DCIM")],
+            dest: Some(PathBuf::from("/photos/picks")),
+            suffix_input: "7612, 7608".to_string(),
+            dry_run: true,
+            ..Default::default()
+        };
+        settings
+            .options
+            .insert("layout".to_string(), serde_json::json!("by-date"));
+        settings.recent_sources = (0..MAX_RECENT_FOLDERS + 2)
+            .map(|i| PathBuf::from(format!("/s{}", i)))
+            .collect();
+        settings.recent_dests = vec!["/d".into(), "".into(), "/d".into(), "/e".into()];
+        save_settings(&path, settings.clone()).unwrap();
+        let loaded = load_settings(&path);
+        assert_eq!(
+            loaded.recent_sources[..],
+            settings.recent_sources[..MAX_RECENT_FOLDERS]
+        );
+        assert_eq!(loaded.recent_dests, [PathBuf::from("/d"), "/e".into()]);
+        assert_eq!(
+            Settings {
+                recent_sources: loaded.recent_sources.clone(),
+                recent_dests: loaded.recent_dests.clone(),
+                ..settings
+            },
+            loaded
+        );
+        assert!(!dir.path().join("config/.settings.json.tmp").exists());
+
+        fs::write(&path, r#"{"suffixInput": "7612", "newerOption": 3}"#).unwrap();
+        assert_eq!(load_settings(&path).suffix_input, "7612");
+        fs::write(&path, "{ truncated").unwrap();
+        assert_eq!(load_settings(&path), Settings::default());
+    }
+}
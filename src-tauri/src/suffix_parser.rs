@@ -2,8 +2,12 @@
 
 use std::collections::HashSet;
 
-/// Parses a string of suffix numbers separated by commas, spaces, or newlines.
-/// Returns a set of unique positive numbers; invalid tokens are skipped.
+/// Most numbers one range ("7600-7650") may stand for; a longer one is taken for a typo.
+const MAX_RANGE: u32 = 10_000;
+
+/// Parses a string of suffix numbers separated by commas, spaces, or newlines; "7600-7650"
+/// stands for every number from 7600 to 7650. Returns a set of unique positive numbers;
+/// invalid tokens are skipped.
 pub fn parse_suffixes(input: &str) -> HashSet<u32> {
     let mut set = HashSet::new();
     for token in input.split(|c: char| c == ',' || c.is_whitespace()) {
@@ -13,11 +17,19 @@ pub fn parse_suffixes(input: &str) -> HashSet<u32> {
         }
         if let Ok(n) = token.parse::<u32>() {
             set.insert(n);
+        } else if let Some((first, last)) = parse_range(token) {
+            set.extend(first..=last);
         }
     }
     set
 }
 
+fn parse_range(token: &str) -> Option<(u32, u32)> {
+    let (first, last) = token.split_once('-')?;
+    let (first, last) = (first.parse::<u32>().ok()?, last.parse::<u32>().ok()?);
+    (first <= last && last - first < MAX_RANGE).then_some((first, last))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -54,4 +66,12 @@ mod tests {
         assert!(s.contains(&7605));
         assert_eq!(s.len(), 3);
     }
+
+    #[test]
+    fn test_parse_ranges() {
+        let s = parse_suffixes("7600-7603, 7612");
+        assert_eq!(s.len(), 5);
+        assert!(s.contains(&7600) && s.contains(&7603) && s.contains(&7612));
+        assert!(parse_suffixes("7650-7600 1-99999 7600-").is_empty());
+    }
 }
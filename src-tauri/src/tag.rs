@@ -0,0 +1,98 @@
+//! Name tags for tag-in-place runs: "IMG_7612.JPG" tagged "_pick" becomes "IMG_7612_pick.JPG"
+//! in the folder it is in.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::collision::split_stem_ext;
+
+/// Error for a tag that cannot be used.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TagError {
+    #[error("tag must not be empty")]
+    Empty,
+    #[error("tag must not contain path separators")]
+    Separator,
+}
+
+/// Text inserted between the stem and the extension of a matched file's name.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NameTag(String);
+
+impl NameTag {
+    pub fn parse(input: &str) -> Result<NameTag, TagError> {
+        if input.trim().is_empty() {
+            return Err(TagError::Empty);
+        }
+        if input.contains(['/', '\\']) {
+            return Err(TagError::Separator);
+        }
+        Ok(NameTag(input.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// `path` with the tag added to its file name, in the same folder.
+    pub fn apply(&self, path: &Path) -> PathBuf {
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (stem, ext) = split_stem_ext(&file_name);
+        let tagged = if ext.is_empty() {
+            format!("{}{}", stem, self.0)
+        } else {
+            format!("{}{}.{}", stem, self.0, ext)
+        };
+        path.with_file_name(tagged)
+    }
+}
+
+impl TryFrom<String> for NameTag {
+    type Error = TagError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        NameTag::parse(&input)
+    }
+}
+
+impl From<NameTag> for String {
+    fn from(value: NameTag) -> String {
+        value.0
+    }
+}
+
+impl fmt::Display for NameTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_goes_before_the_extension() {
+        let tag = NameTag::parse("_pick").unwrap();
+        assert_eq!(
+            tag.apply(Path::new("/cards/A/DCIM/IMG_7612.JPG")),
+            Path::new("/cards/A/DCIM/IMG_7612_pick.JPG")
+        );
+        assert_eq!(
+            tag.apply(Path::new("IMG.7612.cr3")),
+            Path::new("IMG.7612_pick.cr3")
+        );
+        assert_eq!(tag.apply(Path::new("noext")), Path::new("noext_pick"));
+    }
+
+    #[test]
+    fn test_unusable_tags_are_refused() {
+        assert_eq!(NameTag::parse(" "), Err(TagError::Empty));
+        assert_eq!(NameTag::parse("_a/b"), Err(TagError::Separator));
+        assert!(serde_json::from_str::<NameTag>("\"\"").is_err());
+    }
+}
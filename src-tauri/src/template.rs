@@ -0,0 +1,289 @@
+//! Destination path templates such as "{year}/{month}/{day}" or "{suffix}".
+
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::exif_info;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+    Year,
+    Month,
+    Day,
+    Suffix,
+    Ext,
+    Parent,
+    Filename,
+    Stem,
+}
+
+impl Token {
+    fn from_name(name: &str) -> Option<Token> {
+        Some(match name {
+            "year" => Token::Year,
+            "month" => Token::Month,
+            "day" => Token::Day,
+            "suffix" => Token::Suffix,
+            "ext" => Token::Ext,
+            "parent" => Token::Parent,
+            "filename" => Token::Filename,
+            "stem" => Token::Stem,
+            _ => return None,
+        })
+    }
+
+    fn is_date(self) -> bool {
+        matches!(self, Token::Year | Token::Month | Token::Day)
+    }
+
+    fn names_file(self) -> bool {
+        matches!(self, Token::Filename | Token::Stem)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Token(Token),
+}
+
+/// Error for a template that cannot be used.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TemplateError {
+    #[error("destination template is empty")]
+    Empty,
+    #[error("unknown token {{{0}}} in destination template (valid: {{year}} {{month}} {{day}} {{suffix}} {{ext}} {{parent}} {{filename}} {{stem}})")]
+    UnknownToken(String),
+    #[error("unclosed '{{' in destination template")]
+    Unclosed,
+    #[error("unmatched '}}' in destination template")]
+    UnmatchedClose,
+    #[error("destination template must be a relative path")]
+    Absolute,
+    #[error("destination template segment '{0}' is not allowed")]
+    BadSegment(String),
+}
+
+/// A parsed destination template. Each `/`-separated segment becomes one path component.
+/// If the last segment uses {filename} or {stem} it names the file; otherwise the original
+/// file name is appended, so "{year}/{month}" puts IMG_7612.JPG in "2024/05/IMG_7612.JPG".
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DestTemplate {
+    source: String,
+    segments: Vec<Vec<Piece>>,
+}
+
+impl DestTemplate {
+    pub fn parse(input: &str) -> Result<DestTemplate, TemplateError> {
+        let trimmed = input.trim();
+        if trimmed.is_empty() {
+            return Err(TemplateError::Empty);
+        }
+        let normalized = trimmed.replace('\\', "/");
+        let bytes = normalized.as_bytes();
+        if normalized.starts_with('/') || (bytes.len() > 1 && bytes[1] == b':') {
+            return Err(TemplateError::Absolute);
+        }
+        let mut segments = Vec::new();
+        for segment in normalized.trim_end_matches('/').split('/') {
+            if segment.is_empty() || segment == "." || segment == ".." {
+                return Err(TemplateError::BadSegment(segment.to_string()));
+            }
+            segments.push(parse_segment(segment)?);
+        }
+        Ok(DestTemplate {
+            source: trimmed.to_string(),
+            segments,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    fn uses(&self, pred: impl Fn(Token) -> bool) -> bool {
+        self.segments
+            .iter()
+            .flatten()
+            .any(|p| matches!(p, Piece::Token(t) if pred(*t)))
+    }
+
+    /// Render the path (relative to the destination root) for `file_path`.
+    /// `suffix` is the matched suffix number; the date comes from EXIF DateTimeOriginal, else mtime.
+    pub fn render(&self, file_path: &Path, suffix: Option<u32>) -> PathBuf {
+        let date = if self.uses(Token::is_date) {
+            capture_date(file_path)
+        } else {
+            None
+        };
+        let file_name = file_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut out = PathBuf::new();
+        for segment in &self.segments {
+            let mut rendered = String::new();
+            for piece in segment {
+                match piece {
+                    Piece::Literal(s) => rendered.push_str(s),
+                    Piece::Token(t) => rendered.push_str(&token_value(*t, file_path, suffix, date)),
+                }
+            }
+            out.push(rendered);
+        }
+        let names_file = self
+            .segments
+            .last()
+            .map(|s| s.iter().any(|p| matches!(p, Piece::Token(t) if t.names_file())))
+            .unwrap_or(false);
+        if !names_file {
+            out.push(file_name);
+        }
+        out
+    }
+}
+
+impl TryFrom<String> for DestTemplate {
+    type Error = TemplateError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        DestTemplate::parse(&input)
+    }
+}
+
+impl From<DestTemplate> for String {
+    fn from(value: DestTemplate) -> String {
+        value.source
+    }
+}
+
+impl fmt::Display for DestTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn parse_segment(segment: &str) -> Result<Vec<Piece>, TemplateError> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut chars = segment.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(TemplateError::Unclosed),
+                    }
+                }
+                let token = Token::from_name(name.trim())
+                    .ok_or_else(|| TemplateError::UnknownToken(name.clone()))?;
+                if !literal.is_empty() {
+                    pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                }
+                pieces.push(Piece::Token(token));
+            }
+            '}' => return Err(TemplateError::UnmatchedClose),
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    Ok(pieces)
+}
+
+fn token_value(token: Token, file_path: &Path, suffix: Option<u32>, date: Option<NaiveDate>) -> String {
+    let unknown = || "unknown".to_string();
+    match token {
+        Token::Year => date.map(|d| format!("{:04}", d.year())).unwrap_or_else(unknown),
+        Token::Month => date.map(|d| format!("{:02}", d.month())).unwrap_or_else(unknown),
+        Token::Day => date.map(|d| format!("{:02}", d.day())).unwrap_or_else(unknown),
+        Token::Suffix => suffix.map(|s| s.to_string()).unwrap_or_else(unknown),
+        Token::Ext => file_path
+            .extension()
+            .map(|e| e.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        Token::Parent => file_path
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(unknown),
+        Token::Filename => file_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(unknown),
+        Token::Stem => file_path
+            .file_stem()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(unknown),
+    }
+}
+
+/// Capture date: EXIF DateTimeOriginal when present, otherwise the local-time modification date.
+fn capture_date(path: &Path) -> Option<NaiveDate> {
+    if let Some(dt) = exif_info::date_time_original(path) {
+        return Some(dt.date());
+    }
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    Some(DateTime::<Local>::from(modified).date_naive())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rejects_bad_templates() {
+        assert_eq!(DestTemplate::parse("  "), Err(TemplateError::Empty));
+        assert_eq!(
+            DestTemplate::parse("{year}/{colour}"),
+            Err(TemplateError::UnknownToken("colour".to_string()))
+        );
+        assert_eq!(DestTemplate::parse("{year"), Err(TemplateError::Unclosed));
+        assert_eq!(DestTemplate::parse("year}"), Err(TemplateError::UnmatchedClose));
+        assert_eq!(DestTemplate::parse("/abs/{year}"), Err(TemplateError::Absolute));
+        assert_eq!(DestTemplate::parse("C:\\{year}"), Err(TemplateError::Absolute));
+        assert_eq!(
+            DestTemplate::parse("{year}/../x"),
+            Err(TemplateError::BadSegment("..".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_render_appends_file_name() {
+        let t = DestTemplate::parse("picks/{suffix}/").unwrap();
+        let out = t.render(Path::new("/cards/A/DCIM/IMG_7612.JPG"), Some(7612));
+        assert_eq!(out, Path::new("picks/7612/IMG_7612.JPG"));
+    }
+
+    #[test]
+    fn test_render_named_file() {
+        let t = DestTemplate::parse("{parent}/{stem}_{suffix}.{ext}").unwrap();
+        let out = t.render(Path::new("/cards/A/100CANON/IMG_7612.JPG"), Some(7612));
+        assert_eq!(out, Path::new("100CANON/IMG_7612_7612.JPG"));
+    }
+
+    #[test]
+    fn test_render_date_from_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("IMG_7608.png");
+        std::fs::write(&file, b"not really a png").unwrap();
+        // Noon local time, so the date is the same in every time zone.
+        let noon = NaiveDate::from_ymd_opt(2021, 6, 5)
+            .and_then(|d| d.and_hms_opt(12, 0, 0))
+            .and_then(|t| t.and_local_timezone(Local).single())
+            .unwrap();
+        let mtime = filetime::FileTime::from_unix_time(noon.timestamp(), 0);
+        filetime::set_file_mtime(&file, mtime).unwrap();
+        let t = DestTemplate::parse("{year}/{month}/{day}").unwrap();
+        assert_eq!(
+            t.render(&file, Some(7608)),
+            PathBuf::from("2021/06/05/IMG_7608.png")
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Small previews of images for the app's tables: decoded and scaled down to a JPEG, or for
+//! HEIC and camera raw files the preview the camera embedded. Kept in an on-disk cache; a file
+//! that cannot be previewed is a result of its own, which the app shows as a placeholder.
+
+use std::fs;
+use std::io::{BufReader, Cursor};
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+
+use base64::Engine as _;
+use image::{DynamicImage, ImageDecoder, ImageReader};
+use sha2::{Digest, Sha256};
+
+use crate::engine::write_atomically;
+
+/// Longest edge of a thumbnail when the app asks for none, and the range it may ask for.
+pub const DEFAULT_THUMBNAIL_EDGE: u32 = 256;
+const MIN_EDGE: u32 = 16;
+const MAX_EDGE: u32 = 1024;
+
+/// Thumbnails made at once; while a run is going, only one, so that it keeps the disk.
+pub const THUMBNAIL_WORKERS: usize = 2;
+
+const JPEG_QUALITY: u8 = 80;
+
+/// Extensions (lowercase) decoded in full.
+const DECODED_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "tif", "tiff"];
+
+/// A preview, or why there is none.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Thumbnail {
+    #[serde(rename_all = "camelCase")]
+    Image {
+        /// `data:image/jpeg;base64,...`, for an `<img src>`.
+        data_url: String,
+        width: u32,
+        height: u32,
+    },
+    /// Not an error: the file is not an image the app can show, or it is damaged, missing or
+    /// has no embedded preview.
+    NoPreview { reason: String },
+}
+
+/// Limits how many thumbnails are made at once (see `THUMBNAIL_WORKERS`).
+#[derive(Debug, Default)]
+pub struct ThumbnailGate {
+    busy: Mutex<usize>,
+    freed: Condvar,
+}
+
+impl ThumbnailGate {
+    /// Wait until fewer than `limit` thumbnails are being made, then run `make`.
+    pub fn run<T>(&self, limit: usize, make: impl FnOnce() -> T) -> T {
+        let busy = self.busy.lock().unwrap_or_else(|e| e.into_inner());
+        let mut busy = self
+            .freed
+            .wait_while(busy, |busy| *busy >= limit.max(1))
+            .unwrap_or_else(|e| e.into_inner());
+        *busy += 1;
+        drop(busy);
+        // Freed however `make` ends, a panicking decoder included.
+        struct Leave<'a>(&'a ThumbnailGate);
+        impl Drop for Leave<'_> {
+            fn drop(&mut self) {
+                *self.0.busy.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+                self.0.freed.notify_one();
+            }
+        }
+        let _leave = Leave(self);
+        make()
+    }
+}
+
+/// A thumbnail of `path` whose longest edge is at most `max_edge` pixels (clamped to 16-1024),
+/// from `cache_dir` when it was made before from the same file (same path, size and
+/// modification time). A new one is saved there; the cache failing to save only costs time.
+pub fn thumbnail(path: &Path, max_edge: u32, cache_dir: &Path) -> Thumbnail {
+    let max_edge = max_edge.clamp(MIN_EDGE, MAX_EDGE);
+    let meta = match fs::metadata(path) {
+        Ok(meta) if meta.is_file() => meta,
+        Ok(_) => return no_preview("not a file"),
+        Err(e) => return no_preview(&e.to_string()),
+    };
+    let cached = cache_dir.join(cache_key(path, &meta, max_edge));
+    if let Ok(jpeg) = fs::read(&cached) {
+        if let Some(thumbnail) = from_jpeg(&jpeg) {
+            return thumbnail;
+        }
+    }
+    match make(path, max_edge) {
+        Ok(jpeg) => {
+            let _ = write_atomically(&cached, &jpeg);
+            from_jpeg(&jpeg).unwrap_or_else(|| no_preview("the thumbnail cannot be read back"))
+        }
+        Err(reason) => no_preview(&reason),
+    }
+}
+
+fn no_preview(reason: &str) -> Thumbnail {
+    Thumbnail::NoPreview {
+        reason: reason.to_string(),
+    }
+}
+
+/// Cache file name of `path` as it is now, at `max_edge`.
+fn cache_key(path: &Path, meta: &fs::Metadata, max_edge: u32) -> String {
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let mut hash = Sha256::new();
+    hash.update(path.as_os_str().as_encoded_bytes());
+    hash.update(format!("\0{}\0{}\0{}", meta.len(), modified, max_edge));
+    let digest = hash.finalize();
+    let hex: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}.jpg", hex)
+}
+
+fn from_jpeg(jpeg: &[u8]) -> Option<Thumbnail> {
+    let (width, height) = ImageReader::with_format(Cursor::new(jpeg), image::ImageFormat::Jpeg)
+        .into_dimensions()
+        .ok()?;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(jpeg);
+    Some(Thumbnail::Image {
+        data_url: format!("data:image/jpeg;base64,{}", encoded),
+        width,
+        height,
+    })
+}
+
+/// The JPEG thumbnail of `path`: the whole picture decoded where its type allows, else the
+/// preview in its EXIF data (HEIC, TIFF-based raw files such as CR2, NEF, ARW and DNG).
+fn make(path: &Path, max_edge: u32) -> Result<Vec<u8>, String> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let image = if DECODED_EXTENSIONS.contains(&ext.as_str()) {
+        decode(path).or_else(|e| embedded_preview(path).map_err(|_| e))?
+    } else {
+        embedded_preview(path)?
+    };
+    // Smaller pictures are kept at their size.
+    let small = match image.width().max(image.height()) > max_edge {
+        true => image.thumbnail(max_edge, max_edge).into_rgb8(),
+        false => image.into_rgb8(),
+    };
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+        .encode_image(&small)
+        .map_err(|e| e.to_string())?;
+    Ok(jpeg)
+}
+
+/// `path` decoded, turned upright by its EXIF orientation.
+fn decode(path: &Path) -> Result<DynamicImage, String> {
+    let reader = ImageReader::open(path)
+        .and_then(ImageReader::with_guessed_format)
+        .map_err(|e| e.to_string())?;
+    let mut decoder = reader.into_decoder().map_err(|e| e.to_string())?;
+    let orientation = decoder.orientation().map_err(|e| e.to_string())?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// The JPEG preview the camera stored in the EXIF data of `path`, decoded.
+fn embedded_preview(path: &Path) -> Result<DynamicImage, String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .map_err(|_| "no embedded preview".to_string())?;
+    let field = |tag| {
+        exif.get_field(tag, exif::In::THUMBNAIL)
+            .and_then(|f| f.value.get_uint(0))
+            .map(|v| v as usize)
+    };
+    let (Some(offset), Some(len)) = (
+        field(exif::Tag::JPEGInterchangeFormat),
+        field(exif::Tag::JPEGInterchangeFormatLength),
+    ) else {
+        return Err("no embedded preview".to_string());
+    };
+    let jpeg = exif
+        .buf()
+        .get(offset..offset.saturating_add(len))
+        .ok_or("the embedded preview is cut off")?;
+    image::load_from_memory_with_format(jpeg, image::ImageFormat::Jpeg)
+        .map_err(|e| format!("the embedded preview cannot be decoded: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbnails_are_scaled_cached_and_placeholders_for_the_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let png = dir.path().join("IMG_7612.PNG");
+        image::RgbaImage::from_pixel(400, 200, image::Rgba([200, 30, 30, 255]))
+            .save_with_format(&png, image::ImageFormat::Png)
+            .unwrap();
+
+        let made = thumbnail(&png, 100, &cache);
+        let Thumbnail::Image {
+            data_url,
+            width,
+            height,
+        } = &made
+        else {
+            panic!("{:?}", made);
+        };
+        assert_eq!((*width, *height), (100, 50));
+        assert!(data_url.starts_with("data:image/jpeg;base64,/9j/"));
+        assert_eq!(fs::read_dir(&cache).unwrap().count(), 1);
+        assert_eq!(thumbnail(&png, 100, &cache), made);
+        // Another size, or a changed file, is made anew.
+        assert!(matches!(
+            thumbnail(&png, 5000, &cache),
+            Thumbnail::Image { width: 400, .. }
+        ));
+        assert_eq!(fs::read_dir(&cache).unwrap().count(), 2);
+        image::RgbaImage::from_pixel(100, 300, image::Rgba([0, 0, 0, 255]))
+            .save_with_format(&png, image::ImageFormat::Png)
+            .unwrap();
+        filetime::set_file_mtime(&png, filetime::FileTime::from_unix_time(1_700_000_000, 0))
+            .unwrap();
+        assert!(matches!(
+            thumbnail(&png, 100, &cache),
+            Thumbnail::Image { height: 100, .. }
+        ));
+
+        let broken = dir.path().join("IMG_7608.JPG");
+        fs::write(&broken, "not a picture").unwrap();
+        for path in [
+            broken,
+            dir.path().join("IMG_7605.HEIC"),
+            dir.path().to_path_buf(),
+        ] {
+            let made = thumbnail(&path, 100, &cache);
+            assert!(matches!(made, Thumbnail::NoPreview { .. }), "{:?}", made);
+        }
+
+        let gate = ThumbnailGate::default();
+        let peak = Mutex::new((0, 0));
+        std::thread::scope(|s| {
+            for _ in 0..6 {
+                s.spawn(|| {
+                    gate.run(2, || {
+                        let mut p = peak.lock().unwrap();
+                        p.0 += 1;
+                        p.1 = p.1.max(p.0);
+                        drop(p);
+                        std::thread::sleep(std::time::Duration::from_millis(20));
+                        peak.lock().unwrap().0 -= 1;
+                    })
+                });
+            }
+        });
+        assert!((1..=2).contains(&peak.into_inner().unwrap().1));
+    }
+}
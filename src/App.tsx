@@ -1,37 +1,481 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
+import { appDataDir, appLogDir, join } from "@tauri-apps/api/path";
 import { open } from "@tauri-apps/plugin-dialog";
 import { useCallback, useEffect, useRef, useState } from "react";
+import type {
+  ErrorDetail,
+  FileEvent,
+  FileEventBatch,
+  Phase,
+  ProgressEvent,
+  RunResult,
+  StalledFile,
+} from "./bindings";
 
-interface ProgressEvent {
-  phase: string;
-  currentFile: string | null;
-  scanned: number;
-  matched: number;
+const LAYOUT_PRESETS: { label: string; value: string }[] = [
+  { label: "Mirror source folders", value: "mirror" },
+  { label: "Flatten (all files in destination root)", value: "flatten" },
+  { label: "By date: {year}/{month}/{day}", value: "{year}/{month}/{day}" },
+  { label: "By month: {year}/{month}", value: "{year}/{month}" },
+  { label: "Per pick: {suffix}", value: "{suffix}" },
+  { label: "Custom template…", value: "custom" },
+];
+
+/** What the progress area shows: the latest progress event, or "idle" before any run. */
+type ProgressState = Omit<ProgressEvent, "phase"> & { phase: Phase | "idle" };
+
+/** Error details the summary lists; the report and log have them all. */
+const MAX_SUMMARY_ERRORS = 20;
+
+/** The part of a run result the log shows for each watch-mode batch. */
+type BatchResult = Pick<RunResult, "matched" | "moved" | "skippedDuplicates" | "errors" | "files">;
+
+/** One file of a plan from `plan_move`. */
+interface PlannedFile {
+  source: string;
+  dest: string | null;
+  action: "move" | "duplicate" | "error" | "busy" | "empty";
+  renamed: boolean;
+  duplicateOf: string | null;
+  existing: string | null;
+  details: string[];
+}
+
+/** A plan from `plan_move`; passed back as is to `execute_plan`, minus any files left out. */
+interface MovePlan {
+  options: { dryRun: boolean; [field: string]: unknown };
+  files: PlannedFile[];
+  complete: boolean;
+  [field: string]: unknown;
+}
+
+/** Images with the same content, from `audit_destination`. */
+interface IdenticalFiles {
+  hash: string;
+  size: number;
+  paths: string[];
+}
+
+/** Where one requested frame is, from `audit_frames`. */
+interface FrameLocation {
+  suffix: number;
+  presence: "source_only" | "destination_only" | "both" | "missing";
+  sourceFiles: string[];
+  destFiles: string[];
+  identical: boolean | null;
+}
+
+interface FrameAudit {
+  destMissing: boolean;
+  frames: FrameLocation[];
+  errors: ErrorDetail[];
+  cancelled: boolean;
+}
+
+const PRESENCE_LABELS: Record<FrameLocation["presence"], string> = {
+  source_only: "Source only",
+  destination_only: "Destination only",
+  both: "Both",
+  missing: "Missing",
+};
+
+/** The id every event of a run carries, next to its own fields. */
+interface RunTagged {
+  runId: number;
+}
+
+/** Paths sent to `stage_selection` at once. */
+const SELECTION_CHUNK = 2000;
+
+/** Rows the activity feed keeps. */
+const MAX_ACTIVITY_ROWS = 1000;
+
+function activityText(f: FileEvent): string {
+  switch (f.kind) {
+    case "moved":
+      return `${baseName(f.source)} → ${f.dest}`;
+    case "duplicate":
+      return `${baseName(f.source)} is a duplicate${f.duplicateOf ? ` of ${f.duplicateOf}` : ""}`;
+    case "error":
+      return `${baseName(f.source)}: ${f.message}`;
+  }
+}
+
+/** One past run, from `get_run_history`. */
+interface HistoryRecord {
+  startedAt: string;
+  finishedAt: string;
+  kind: "run" | "plan" | "resume" | "watch" | "selection";
+  outcome: "completed" | "cancelled" | "aborted" | "failed";
+  error: string | null;
+  sources: string[];
+  dest: string;
+  suffixInput: string;
+  dryRun: boolean;
   moved: number;
   skippedDuplicates: number;
   errors: number;
-  percent: number;
+  bytesMoved: number;
+  reportPath: string | null;
+  logPath: string | null;
+}
+
+interface RunHistory {
+  records: HistoryRecord[];
+  total: number;
+  warning: string | null;
+}
+
+/** What `get_volume_info` returns. */
+interface VolumeInfo {
+  folder: string;
+  mountPoint: string | null;
+  name: string | null;
+  filesystem: string | null;
+  freeBytes: number | null;
+  totalBytes: number | null;
+  sameVolume: boolean | null;
+}
+
+interface SuffixPreset {
+  name: string;
+  suffixInput: string;
+  suffixCount: number;
+  createdAt: string;
+  updatedAt: string;
+}
+
+interface PresetList {
+  presets: SuffixPreset[];
+  warning: string | null;
+}
+
+/** What `load_settings` returns and `save_settings` takes. */
+interface Settings {
+  sources: string[];
+  dest: string | null;
+  suffixInput: string;
+  dryRun: boolean;
+  verbose: boolean;
+  disableNotifications: boolean;
+  /** The other options, by the names of their state below. */
+  options: Record<string, unknown>;
+  recentSources: string[];
+  recentDests: string[];
+}
+
+/** Folders each recent list keeps; the backend cuts the lists to this as well. */
+const MAX_RECENT_FOLDERS = 10;
+
+/** `recent` with `folders` first, without repeats. */
+function remember(recent: string[], folders: string[]): string[] {
+  return [...folders, ...recent.filter((f) => !folders.includes(f))].slice(0, MAX_RECENT_FOLDERS);
+}
+
+/** Past runs the history section shows at first. */
+const HISTORY_PAGE = 50;
+
+/** One file a run would take, from `preview_scan`. */
+interface PreviewEntry {
+  path: string;
+  size: number;
+  modified: string | null;
+  suffix: number;
+}
+
+interface ScanPreview {
+  entries: PreviewEntry[];
+  matched: number;
+  totalBytes: number;
+  truncated: boolean;
+  scanned: number;
+  excluded: number;
+  skippedSymlinks: number;
+  overLimit: number;
+  warnings: string[];
+  cancelled: boolean;
+}
+
+/** What a run would take, from `count_matches`, shown under the suffixes as they are typed. */
+interface MatchCount {
+  matched: number;
+  totalBytes: number;
+  excluded: number;
+  skippedSymlinks: number;
+  overLimit: number;
+  cached: boolean;
+  cancelled: boolean;
+}
+
+/** A problem with the inputs, from `validate_inputs`; errors keep Start disabled. */
+interface Finding {
+  severity: "error" | "warning";
+  kind: string;
+  message: string;
+  path: string | null;
+}
+
+/** A command's error as `preview_scan` returns it. */
+interface CommandError {
+  message: string;
+  kind: string;
+  path: string | null;
+}
+
+type PreviewSort = "path" | "size" | "modified" | "suffix";
+
+function sortedPreview(entries: PreviewEntry[], key: PreviewSort, ascending: boolean): PreviewEntry[] {
+  const sign = ascending ? 1 : -1;
+  return [...entries].sort((a, b) => {
+    const x = a[key] ?? "";
+    const y = b[key] ?? "";
+    return (x < y ? -1 : x > y ? 1 : 0) * sign;
+  });
+}
+
+interface AuditReport {
+  dest: string;
+  files: number;
+  bytes: number;
+  groups: IdenticalFiles[];
+  duplicateFiles: number;
+  wastedBytes: number;
+  errors: ErrorDetail[];
+  cancelled: boolean;
+}
+
+/** What `get_thumbnail` returns. */
+type Thumbnail = { kind: "image"; dataUrl: string; width: number; height: number } | { kind: "no_preview"; reason: string };
+
+const THUMBNAIL_EDGE = 96;
+
+/** A small preview of the image at `path`, asked for once the row scrolls into view; a grey
+ * box for a file that has none. */
+function Thumb({ path }: { path: string }) {
+  const box = useRef<HTMLDivElement>(null);
+  const [visible, setVisible] = useState(false);
+  const [thumb, setThumb] = useState<Thumbnail | null>(null);
+  useEffect(() => {
+    const el = box.current;
+    if (!el) return;
+    const observer = new IntersectionObserver((entries) => {
+      if (entries.some((e) => e.isIntersecting)) {
+        setVisible(true);
+        observer.disconnect();
+      }
+    });
+    observer.observe(el);
+    return () => observer.disconnect();
+  }, []);
+  useEffect(() => {
+    if (!visible) return;
+    let current = true;
+    invoke<Thumbnail>("get_thumbnail", { path, maxEdge: THUMBNAIL_EDGE })
+      .then((t) => current && setThumb(t))
+      .catch((e) => current && setThumb({ kind: "no_preview", reason: String(e) }));
+    return () => {
+      current = false;
+    };
+  }, [visible, path]);
+  return (
+    <div ref={box} className="thumb" title={thumb?.kind === "no_preview" ? `No preview: ${thumb.reason}` : undefined}>
+      {thumb?.kind === "image" && <img src={thumb.dataUrl} width={thumb.width} height={thumb.height} alt="" />}
+    </div>
+  );
+}
+
+function plannedStatus(f: PlannedFile, files: PlannedFile[]): string {
+  if (f.action === "error") return `error: ${f.details.join("; ")}`;
+  if (f.action === "busy") return "skipped: still being written";
+  if (f.action === "empty") return "left alone: empty file (0 bytes)";
+  if (f.action === "duplicate") {
+    if (f.existing) return `already at ${f.existing}`;
+    if (!f.duplicateOf) return "already in destination";
+    const original = files.find((o) => o.source === f.duplicateOf);
+    return `duplicate of ${f.duplicateOf}${original?.dest ? `, going to ${original.dest}` : ""}`;
+  }
+  return f.renamed ? "move (name taken, renamed)" : "move";
+}
+
+/** An unfinished run found at startup, from `saved_run`. */
+interface SavedRunSummary {
+  createdAt: string;
+  sources: string[];
+  dest: string;
+  total: number;
+  done: number;
+}
+
+/** Where runs keep the state they can be resumed from. */
+async function statePath(): Promise<string> {
+  return join(await appDataDir(), "unfinished-run.jsonl");
+}
+
+function formatBytes(bytes: number): string {
+  const units = ["KB", "MB", "GB", "TB"];
+  if (bytes < 1000) return `${bytes} B`;
+  let value = bytes / 1000;
+  let unit = 0;
+  while (value >= 1000 && unit < units.length - 1) {
+    value /= 1000;
+    unit += 1;
+  }
+  return `${value.toFixed(1)} ${units[unit]}`;
+}
+
+/** Last component of a path, for either separator. */
+function baseName(path: string): string {
+  return path.split(/[\\/]/).pop() || path;
+}
+
+function formatElapsed(seconds: number): string {
+  return seconds < 60 ? `${seconds} s` : `${Math.floor(seconds / 60)} min`;
+}
+
+function formatDuration(ms: number): string {
+  if (ms < 1000) return `${ms} ms`;
+  if (ms < 60_000) return `${(ms / 1000).toFixed(1)} s`;
+  const secs = Math.floor(ms / 1000);
+  if (secs < 3600) return `${Math.floor(secs / 60)} min ${String(secs % 60).padStart(2, "0")} s`;
+  return `${Math.floor(secs / 3600)} h ${String(Math.floor((secs % 3600) / 60)).padStart(2, "0")} min`;
+}
+
+function formatEta(seconds: number): string {
+  if (seconds < 60) return `~${seconds} s left`;
+  if (seconds < 3600) return `~${Math.ceil(seconds / 60)} min left`;
+  return `~${Math.floor(seconds / 3600)} h ${Math.floor((seconds % 3600) / 60)} min left`;
 }
 
 export default function App() {
-  const [sourcePath, setSourcePath] = useState("");
+  const [sourcePaths, setSourcePaths] = useState<string[]>([]);
   const [destPath, setDestPath] = useState("");
+  /** Rename matches where they are with this tag instead of moving them; empty to move. */
+  const [tag, setTag] = useState("");
+  const [tagInPlace, setTagInPlace] = useState(false);
   const [suffixInput, setSuffixInput] = useState("");
+  /** Suffixes sent to folders of their own; the first rule with a file's suffix wins. */
+  const [routes, setRoutes] = useState<{ suffixes: string; dest: string }[]>([]);
+  /** Folder for the images that match no suffix; empty for none. */
+  const [rejectsPath, setRejectsPath] = useState("");
   const [dryRun, setDryRun] = useState(false);
   const [verbose, setVerbose] = useState(false);
+  const [notifyWhenDone, setNotifyWhenDone] = useState(true);
+  const [layout, setLayout] = useState("mirror");
+  const [customTemplate, setCustomTemplate] = useState("");
+  const [duplicateAction, setDuplicateAction] = useState("leave");
+  const [transferMode, setTransferMode] = useState("move");
+  const [collisionPattern, setCollisionPattern] = useState("-{n}");
+  /** "rename" or "update": replace destination files older than the source. */
+  const [onCollision, setOnCollision] = useState("rename");
+  /** Comma-separated file name globs to leave out. */
+  const [excludeGlobs, setExcludeGlobs] = useState("");
+  const [removeEmptyDirs, setRemoveEmptyDirs] = useState(false);
+  const [junkAsEmpty, setJunkAsEmpty] = useState(false);
+  const [verifyCopies, setVerifyCopies] = useState(true);
+  const [verifyAfter, setVerifyAfter] = useState(false);
+  const [verifyHash, setVerifyHash] = useState(false);
+  const [preserveXattrs, setPreserveXattrs] = useState(true);
+  const [normalizeUnicode, setNormalizeUnicode] = useState(true);
+  const [readOnly, setReadOnly] = useState(false);
+  const [sanitize, setSanitize] = useState("auto");
+  const [symlinks, setSymlinks] = useState("skip");
+  const [emptyFiles, setEmptyFiles] = useState("move");
+  const [matchBy, setMatchBy] = useState("name");
+  const [saveReport, setSaveReport] = useState(false);
+  const [writeLog, setWriteLog] = useState(false);
+  const [workers, setWorkers] = useState(0);
+  /** Read/write limit in MB/s; 0 for none. */
+  const [throttleMb, setThrottleMb] = useState(0);
+  /** Matches to take at most; 0 for all. */
+  const [limit, setLimit] = useState(0);
+  /** Seconds a file must be unmodified to be taken; -1 for 10 s when watching, else none. */
+  const [minAge, setMinAge] = useState(-1);
+  const [skipPreflight, setSkipPreflight] = useState(false);
+  const [failFast, setFailFast] = useState(false);
+  const [dedupMode, setDedupMode] = useState("content");
+  const [noDedupe, setNoDedupe] = useState(false);
+  const [confirmDuplicates, setConfirmDuplicates] = useState(false);
   const [running, setRunning] = useState(false);
-  const [progress, setProgress] = useState<ProgressEvent>({
+  const [paused, setPaused] = useState(false);
+  const [watching, setWatching] = useState(false);
+  const [plan, setPlan] = useState<MovePlan | null>(null);
+  const [audit, setAudit] = useState<AuditReport | null>(null);
+  const [frameAudit, setFrameAudit] = useState<FrameAudit | null>(null);
+  const [preview, setPreview] = useState<ScanPreview | null>(null);
+  /** Preview files unticked, which "Move selected" leaves alone. */
+  const [unticked, setUnticked] = useState<Set<string>>(new Set());
+  const [previewError, setPreviewError] = useState<CommandError | null>(null);
+  const [previewing, setPreviewing] = useState(false);
+  const [previewSort, setPreviewSort] = useState<{ key: PreviewSort; ascending: boolean }>({
+    key: "path",
+    ascending: true,
+  });
+  const previewSeq = useRef(0);
+  const [matchCount, setMatchCount] = useState<MatchCount | null>(null);
+  const countSeq = useRef(0);
+  /** Bumped by Refresh under the suffixes so the count is made again, from a new listing. */
+  const [listingVersion, setListingVersion] = useState(0);
+  const [savedRun, setSavedRun] = useState<SavedRunSummary | null>(null);
+  const [savedRunError, setSavedRunError] = useState<string | null>(null);
+  const [progress, setProgress] = useState<ProgressState>({
     phase: "idle",
     currentFile: null,
     scanned: 0,
+    images: 0,
     matched: 0,
     moved: 0,
+    tagged: 0,
+    readOnly: 0,
     skippedDuplicates: 0,
+    rejected: 0,
+    replaced: 0,
+    skippedNewer: 0,
     errors: 0,
     percent: 0,
+    paused: false,
+    totalBytes: 0,
+    bytesDone: 0,
+    currentIndex: 0,
+    totalCount: 0,
+    throughput: 0,
+    etaSeconds: null,
+    error: null,
+    warning: null,
+    stalled: null,
+    problemsNotSent: 0,
+    cancelled: false,
+    aborted: null,
+    overLimit: 0,
+    hashMismatches: 0,
+    stats: null,
+    unmatchedSuffixes: [],
+    verification: null,
+    file: null,
   });
+  /** Requested suffixes the last run found no files for. */
+  const [unmatched, setUnmatched] = useState<number[]>([]);
+  /** Latest stall notice, until another file starts or the run ends. */
+  const [stall, setStall] = useState<{ file: StalledFile; reading: boolean } | null>(null);
   const [logLines, setLogLines] = useState<string[]>([]);
+  const [activity, setActivity] = useState<{ runId: number; file: FileEvent }[]>([]);
+  const [history, setHistory] = useState<RunHistory | null>(null);
+  const [lastResult, setLastResult] = useState<RunResult | null>(null);
+  /** Id of the latest run started; events of earlier runs that arrive late are ignored. */
+  const latestRun = useRef(0);
+  /** What Cancel stops: the run started last, or with `null` (an audit of frames, which has no
+   * id) whatever is going. */
+  const cancelId = useRef<number | null>(null);
+  const isStale = (event: { payload: RunTagged }) => {
+    if (event.payload.runId < latestRun.current) return true;
+    latestRun.current = event.payload.runId;
+    return false;
+  };
+  const [recentSources, setRecentSources] = useState<string[]>([]);
+  const [recentDests, setRecentDests] = useState<string[]>([]);
+  /** Set once the saved settings are in, so the defaults are not saved over them first. */
+  const [settingsLoaded, setSettingsLoaded] = useState(false);
+  const [historyLimit, setHistoryLimit] = useState<number | null>(HISTORY_PAGE);
   const [error, setError] = useState<string | null>(null);
   const logEndRef = useRef<HTMLDivElement>(null);
 
@@ -43,14 +487,307 @@ export default function App() {
     logEndRef.current?.scrollIntoView({ behavior: "smooth" });
   }, [logLines]);
 
+  // Options saved with the settings besides the named ones, with their setters.
+  const savedOptions: Record<string, unknown> = {
+    tag, tagInPlace, routes, rejectsPath, layout, customTemplate, duplicateAction, transferMode,
+    collisionPattern, onCollision, excludeGlobs, removeEmptyDirs, junkAsEmpty, verifyCopies,
+    verifyAfter, verifyHash, preserveXattrs, normalizeUnicode, readOnly, sanitize, symlinks,
+    emptyFiles, matchBy, saveReport, writeLog, workers, throttleMb, limit, minAge, skipPreflight,
+    failFast, dedupMode, noDedupe, confirmDuplicates,
+  };
+  const optionSetters: Record<string, (value: never) => void> = {
+    tag: setTag, tagInPlace: setTagInPlace, routes: setRoutes, rejectsPath: setRejectsPath,
+    layout: setLayout, customTemplate: setCustomTemplate, duplicateAction: setDuplicateAction,
+    transferMode: setTransferMode, collisionPattern: setCollisionPattern, onCollision: setOnCollision,
+    excludeGlobs: setExcludeGlobs, removeEmptyDirs: setRemoveEmptyDirs, junkAsEmpty: setJunkAsEmpty,
+    verifyCopies: setVerifyCopies, verifyAfter: setVerifyAfter, verifyHash: setVerifyHash,
+    preserveXattrs: setPreserveXattrs, normalizeUnicode: setNormalizeUnicode, readOnly: setReadOnly,
+    sanitize: setSanitize, symlinks: setSymlinks, emptyFiles: setEmptyFiles, matchBy: setMatchBy,
+    saveReport: setSaveReport, writeLog: setWriteLog, workers: setWorkers, throttleMb: setThrottleMb,
+    limit: setLimit, minAge: setMinAge, skipPreflight: setSkipPreflight, failFast: setFailFast,
+    dedupMode: setDedupMode, noDedupe: setNoDedupe, confirmDuplicates: setConfirmDuplicates,
+  };
+  const settings: Settings = {
+    sources: sourcePaths,
+    dest: destPath || null,
+    suffixInput,
+    dryRun,
+    verbose,
+    disableNotifications: !notifyWhenDone,
+    options: savedOptions,
+    recentSources,
+    recentDests,
+  };
+  const settingsJson = JSON.stringify(settings);
+
+  const [presets, setPresets] = useState<SuffixPreset[]>([]);
+  const [presetName, setPresetName] = useState("");
+  /** The preset last loaded or saved, which Rename and Delete act on. */
+  const [currentPreset, setCurrentPreset] = useState<string | null>(null);
+  /** A save or rename refused because the name is taken, waiting for "Overwrite". */
+  const [presetConflict, setPresetConflict] = useState<{ action: "save" | "rename"; name: string } | null>(null);
+
+  const presetCommand = useCallback(
+    async (command: string, args: Record<string, unknown>, action?: "save" | "rename") => {
+      try {
+        const list = await invoke<PresetList>(command, args);
+        setPresets(list.presets);
+        if (list.warning) addLog(`Warning: ${list.warning}`);
+        setPresetConflict(null);
+        return true;
+      } catch (e) {
+        const err = e as { message?: string; kind?: string };
+        if (action && err.kind === "exists") {
+          setPresetConflict({ action, name: presetName.trim() });
+        } else {
+          setError(err.message ?? String(e));
+        }
+        return false;
+      }
+    },
+    [addLog, presetName]
+  );
+
+  useEffect(() => {
+    presetCommand("list_presets", {});
+    // Only once, at launch.
+  }, []);
+
+  const loadPreset = (name: string) => {
+    const preset = presets.find((p) => p.name === name);
+    if (!preset) return;
+    setSuffixInput(preset.suffixInput);
+    setPresetName(preset.name);
+    setCurrentPreset(preset.name);
+    setPresetConflict(null);
+  };
+
+  const savePreset = async (overwrite = false) => {
+    const name = presetName.trim();
+    if (await presetCommand("save_preset", { name, suffixInput, overwrite }, "save")) {
+      setCurrentPreset(name);
+      addLog(`Saved preset "${name}"`);
+    }
+  };
+
+  const renamePreset = async (overwrite = false) => {
+    if (!currentPreset) return;
+    const to = presetName.trim();
+    if (await presetCommand("rename_preset", { from: currentPreset, to, overwrite }, "rename")) {
+      setCurrentPreset(to);
+    }
+  };
+
+  const deletePreset = async () => {
+    if (currentPreset && (await presetCommand("delete_preset", { name: currentPreset }))) {
+      addLog(`Deleted preset "${currentPreset}"`);
+      setCurrentPreset(null);
+    }
+  };
+
+  // The last session's settings; saved options of another type than now (from an older
+  // version) are left at their defaults.
+  useEffect(() => {
+    invoke<Settings>("load_settings")
+      .then((saved) => {
+        setSourcePaths(saved.sources);
+        setDestPath(saved.dest ?? "");
+        setSuffixInput(saved.suffixInput);
+        setDryRun(saved.dryRun);
+        setVerbose(saved.verbose);
+        setNotifyWhenDone(!saved.disableNotifications);
+        setRecentSources(saved.recentSources);
+        setRecentDests(saved.recentDests);
+        for (const [name, value] of Object.entries(saved.options)) {
+          const current = savedOptions[name];
+          if (name in optionSetters && typeof value === typeof current && Array.isArray(value) === Array.isArray(current)) {
+            optionSetters[name](value as never);
+          }
+        }
+      })
+      .catch((e) => addLog(`Cannot load settings: ${String(e)}`))
+      .finally(() => setSettingsLoaded(true));
+    // Only once, at launch.
+  }, []);
+
+  // Saved shortly after every change, so a crash loses at most the last keystrokes.
+  useEffect(() => {
+    if (!settingsLoaded) return;
+    const timer = setTimeout(() => {
+      invoke("save_settings", { settings: JSON.parse(settingsJson) }).catch((e) => addLog(String(e)));
+    }, 300);
+    return () => clearTimeout(timer);
+  }, [settingsJson, settingsLoaded, addLog]);
+
+  // The history gets a record when a run ends, so reload it then.
+  useEffect(() => {
+    if (running) return;
+    invoke<RunHistory>("get_run_history", { offset: 0, limit: historyLimit })
+      .then(setHistory)
+      .catch((e) => addLog(`Cannot load run history: ${String(e)}`));
+  }, [running, historyLimit, addLog]);
+
+  useEffect(() => {
+    const unlisten = listen<{ message: string }>("history-warning", (event) => {
+      addLog(`Warning: ${event.payload.message}`);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [addLog]);
+
+  /** The window's close button was used mid-run: "ask" to confirm, "stopping" while the
+   * cancelled run finishes its file, "stuck" when it did not in time. */
+  const [closeRequest, setCloseRequest] = useState<"ask" | "stopping" | "stuck" | null>(null);
+  useEffect(() => {
+    const unlisten = listen<RunTagged>("close-requested", () => setCloseRequest((prev) => prev ?? "ask"));
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  const stopAndClose = async (force = false) => {
+    setCloseRequest("stopping");
+    try {
+      const closed = await invoke<boolean>("cancel_and_close", { force });
+      if (!closed) setCloseRequest("stuck");
+    } catch (e) {
+      setCloseRequest(null);
+      setError(String(e));
+    }
+  };
+
+  /** Show a file selected in the file manager, or open a folder. */
+  const reveal = (path: string) => {
+    invoke("reveal_path", { path }).catch((e) => setError(String(e)));
+  };
+
+  /** Forget the destination hashes earlier runs of this session read. */
+  const refreshIndex = async () => {
+    try {
+      const dropped = await invoke<number>("refresh_index");
+      addLog(`Destination index refreshed: ${dropped} cached hash(es) dropped; the next run reads what it needs again`);
+    } catch (e) {
+      addLog(`Cannot refresh the destination index: ${String(e)}`);
+    }
+  };
+
+  const clearHistory = async () => {
+    try {
+      await invoke("clear_run_history");
+      setHistory({ records: [], total: 0, warning: null });
+    } catch (e) {
+      addLog(`Cannot clear run history: ${String(e)}`);
+    }
+  };
+
   useEffect(() => {
-    const unlisten = listen<ProgressEvent>("progress", (event) => {
+    const add = (event: { payload: FileEventBatch }) => {
+      if (isStale(event)) return;
+      const { runId, files } = event.payload;
+      setActivity((prev) => [...prev, ...files.map((file) => ({ runId, file }))].slice(-MAX_ACTIVITY_ROWS));
+    };
+    const unlisten = ["file-moved", "file-duplicate", "file-error"].map((name) => listen<FileEventBatch>(name, add));
+    return () => {
+      unlisten.forEach((u) => u.then((fn) => fn()));
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = listen<ProgressEvent & RunTagged>("progress", (event) => {
+      if (isStale(event)) return;
       setProgress(event.payload);
+      const { error, warning, stalled, unmatchedSuffixes } = event.payload;
+      if (unmatchedSuffixes.length > 0 || event.payload.phase === "done") {
+        setUnmatched(unmatchedSuffixes);
+      }
+      if (stalled) {
+        setStall({ file: stalled, reading: event.payload.phase === "planning" });
+      } else if (event.payload.currentFile || event.payload.phase === "done") {
+        setStall(null);
+      }
+      if (error) {
+        addLog(`Error (${error.stage})${error.path ? ` ${error.path}` : ""}: ${error.message}`);
+      }
+      if (warning) addLog(`Warning: ${warning}`);
       if (event.payload.phase === "done") {
         setRunning(false);
+        setPaused(false);
+        setWatching(false);
         addLog(
-          `Done. Moved: ${event.payload.moved}, Duplicates skipped: ${event.payload.skippedDuplicates}, Errors: ${event.payload.errors}`
+          `Checked ${event.payload.scanned.toLocaleString()} files (${event.payload.images.toLocaleString()} images), ${event.payload.matched.toLocaleString()} matched`
         );
+        const aborted = event.payload.aborted;
+        if (aborted) {
+          const left = event.payload.totalCount - event.payload.currentIndex;
+          setError(
+            aborted.kind === "fail_fast"
+              ? `Stopped at the first error: ${aborted.path}: ${aborted.message}`
+              : `Stopped: ${aborted.dest} appears to be unavailable (${aborted.failures} moves in a row failed: ${aborted.message}).`
+          );
+          addLog(
+            `Stopped ${aborted.kind === "fail_fast" ? "at the first error" : `after ${aborted.failures} failed moves in a row`} — ${event.payload.currentIndex} of ${event.payload.totalCount} files processed, ${left} not attempted. Moved: ${event.payload.moved}, Errors: ${event.payload.errors}`
+          );
+        } else if (event.payload.cancelled) {
+          addLog(
+            `Cancelled — ${event.payload.currentIndex} of ${event.payload.totalCount} files processed. Moved: ${event.payload.moved}, Errors: ${event.payload.errors}`
+          );
+        } else if (tagInPlace) {
+          addLog(`Done. Tagged in place: ${event.payload.tagged}, Errors: ${event.payload.errors}`);
+        } else {
+          const checked = noDedupe
+            ? "not checked: duplicate detection was off"
+            : `checked ${dedupMode === "same_path" ? "at the same path only" : "by content"}`;
+          addLog(
+            `Done. Moved: ${event.payload.moved}, Duplicates skipped: ${event.payload.skippedDuplicates} (${checked}), Errors: ${event.payload.errors}`
+          );
+        }
+        if (event.payload.readOnly > 0) {
+          addLog(`${dryRun ? "Would make" : "Made"} read-only: ${event.payload.readOnly}`);
+        }
+        if (event.payload.rejected > 0) {
+          addLog(`Rejected (matched no suffix): ${event.payload.rejected}`);
+        }
+        if (event.payload.replaced > 0 || event.payload.skippedNewer > 0) {
+          addLog(
+            `Replaced older files: ${event.payload.replaced}, kept where the destination was not older: ${event.payload.skippedNewer}`
+          );
+        }
+        if (event.payload.overLimit > 0) {
+          addLog(
+            `${event.payload.matched} of ${event.payload.matched + event.payload.overLimit} matched files ${dryRun ? "planned" : "processed"} (limit); ${event.payload.overLimit} left for a later run`
+          );
+        }
+        if (event.payload.hashMismatches > 0) {
+          addLog(
+            `${event.payload.hashMismatches} file(s) had a duplicate's hash but different bytes; ${dryRun ? "they would be moved" : "moved"} under a new name instead of skipped`
+          );
+        }
+        const check = event.payload.verification;
+        if (check) {
+          const failed = check.missing + check.mismatched;
+          addLog(
+            failed > 0
+              ? `Verification FAILED: ${check.missing} missing, ${check.mismatched} mismatched (${check.verified} verified)`
+              : `Verified ${check.verified} moved file(s)${check.content ? " by content" : ""}`
+          );
+        }
+        const stats = event.payload.stats;
+        if (stats) {
+          const { scanMs, indexMs, planMs, moveMs } = stats.phases;
+          addLog(
+            `${dryRun ? "Would move" : "Moved"} ${formatBytes(stats.bytesMoved)} (${formatBytes(stats.bytesSkippedDuplicates)} skipped as duplicates) in ${formatDuration(stats.durationMs)} — scan ${formatDuration(scanMs)}, index ${formatDuration(indexMs)}, plan ${formatDuration(planMs)}, move ${formatDuration(moveMs)}`
+          );
+          if (stats.destIndexed > 0) {
+            addLog(
+              `Destination: ${stats.destIndexed} images, ${stats.destHashed} hashed (only sizes shared with a match are read)` +
+                (stats.destReused > 0 ? `, ${stats.destReused} known from an earlier run` : "")
+            );
+          }
+        }
+        if (event.payload.problemsNotSent > 0) {
+          addLog(`… and ${event.payload.problemsNotSent} more errors/warnings (see the report)`);
+        }
       }
       if (event.payload.currentFile && verbose) {
         addLog(event.payload.currentFile);
@@ -59,22 +796,134 @@ export default function App() {
     return () => {
       unlisten.then((fn) => fn());
     };
+  }, [verbose, dedupMode, noDedupe, dryRun, addLog]);
+
+  useEffect(() => {
+    (async () => {
+      try {
+        setSavedRun(await invoke<SavedRunSummary | null>("saved_run", { path: await statePath() }));
+      } catch (e) {
+        setSavedRunError(String(e));
+      }
+    })();
+  }, []);
+
+  useEffect(() => {
+    const unlisten = listen<RunResult & RunTagged>("run-finished", (event) => {
+      if (isStale(event)) return;
+      setLastResult(event.payload);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = listen<{ message: string } & RunTagged>("preflight-failed", (event) => {
+      if (isStale(event)) return;
+      setError(`Nothing was moved: ${event.payload.message}`);
+      addLog(`Pre-flight check failed: ${event.payload.message}`);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [addLog]);
+
+  useEffect(() => {
+    const unlisten = listen<{ message: string; kind: string } & RunTagged>("run-failed", (event) => {
+      if (isStale(event)) return;
+      setError(`Could not start: ${event.payload.message}`);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = listen<MovePlan & RunTagged>("plan-ready", (event) => {
+      if (isStale(event)) return;
+      const { runId: _, ...ready } = event.payload;
+      if (!ready.complete) {
+        addLog("Planning stopped; nothing to review.");
+        return;
+      }
+      const count = (action: string) => ready.files.filter((f) => f.action === action).length;
+      addLog(
+        `Plan ready: ${count("move")} to move, ${count("duplicate")} duplicate(s), ${count("error")} error(s). Review it below.`
+      );
+      setPlan(ready);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [addLog]);
+
+  useEffect(() => {
+    const unlisten = listen<AuditReport & RunTagged>("audit-ready", (event) => {
+      if (isStale(event)) return;
+      const report = event.payload;
+      setRunning(false);
+      setPaused(false);
+      setProgress((p) => ({ ...p, phase: "done", percent: 100, currentFile: null }));
+      report.errors.forEach((e) => addLog(`Error (${e.stage})${e.path ? ` ${e.path}` : ""}: ${e.message}`));
+      addLog(
+        `${report.cancelled ? "Audit cancelled" : "Audit done"}: ${report.duplicateFiles} duplicate file(s) in ${report.groups.length} group(s), ${formatBytes(report.wastedBytes)} wasted (${report.files} images, ${formatBytes(report.bytes)}). Nothing was changed.`
+      );
+      setAudit(report);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [addLog]);
+
+  useEffect(() => {
+    const unlisten = listen<BatchResult & RunTagged>("watch-batch", (event) => {
+      if (isStale(event)) return;
+      const batch = event.payload;
+      addLog(
+        `New files: ${batch.matched}. Moved: ${batch.moved}, Duplicates skipped: ${batch.skippedDuplicates}, Errors: ${batch.errors}`
+      );
+      if (verbose) {
+        batch.files.forEach((f) =>
+          addLog(
+            `${f.action} ${f.source}${f.dest ? ` -> ${f.dest}` : ""}${f.existing ? ` — already at ${f.existing}` : ""}${f.detail ? ` (${f.detail})` : ""}`
+          )
+        );
+      }
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
   }, [verbose, addLog]);
 
+  const addSources = useCallback(
+    (paths: string[]) => {
+      setSourcePaths((prev) => [...prev, ...paths.filter((p) => !prev.includes(p))]);
+      setRecentSources((prev) => remember(prev, paths));
+      paths.forEach((p) => addLog(`Source: ${p}`));
+    },
+    [addLog]
+  );
+
   const pickSource = async () => {
     setError(null);
     const selected = await open({
       directory: true,
-      multiple: false,
+      multiple: true,
       recursive: true,
-      title: "Select Source Folder",
+      title: "Add Source Folders",
     });
-    if (selected && typeof selected === "string") {
-      setSourcePath(selected);
-      addLog(`Source: ${selected}`);
+    if (Array.isArray(selected)) {
+      addSources(selected);
+    } else if (typeof selected === "string") {
+      addSources([selected]);
     }
   };
 
+  const removeSource = (path: string) => {
+    setSourcePaths((prev) => prev.filter((p) => p !== path));
+  };
+
   const pickDest = async () => {
     setError(null);
     const selected = await open({
@@ -84,23 +933,359 @@ export default function App() {
       title: "Select Destination Folder",
     });
     if (selected && typeof selected === "string") {
-      setDestPath(selected);
-      addLog(`Destination: ${selected}`);
+      chooseDest(selected);
+    }
+  };
+
+  const chooseDest = (path: string) => {
+    setDestPath(path);
+    setRecentDests((prev) => remember(prev, [path]));
+    addLog(`Destination: ${path}`);
+  };
+
+  const updateRoute = (i: number, change: Partial<{ suffixes: string; dest: string }>) => {
+    setRoutes((prev) => prev.map((r, j) => (j === i ? { ...r, ...change } : r)));
+  };
+
+  const pickRouteDest = async (i: number) => {
+    setError(null);
+    const selected = await open({
+      directory: true,
+      multiple: false,
+      recursive: true,
+      title: "Select Route Folder",
+    });
+    if (selected && typeof selected === "string") {
+      updateRoute(i, { dest: selected });
     }
   };
 
-  const start = async () => {
+  const pickRejects = async () => {
     setError(null);
-    setLogLines((prev) => [...prev, "Starting…"]);
+    const selected = await open({
+      directory: true,
+      multiple: false,
+      recursive: true,
+      title: "Select Rejects Folder",
+    });
+    if (selected && typeof selected === "string") {
+      setRejectsPath(selected);
+      addLog(`Rejects: ${selected}`);
+    }
+  };
+
+  const destTemplate =
+    layout === "mirror" || layout === "flatten"
+      ? null
+      : layout === "custom"
+        ? customTemplate.trim() || null
+        : layout;
+  const destLayout = destTemplate
+    ? { kind: "template", template: destTemplate }
+    : { kind: layout === "flatten" ? "flatten" : "mirror" };
+
+  /** Where a run puts its files: the destination, or the first source when tagging in place. */
+  const target = tagInPlace ? sourcePaths[0] ?? "" : destPath;
+  const [findings, setFindings] = useState<Finding[]>([]);
+  const [destVolume, setDestVolume] = useState<VolumeInfo | null>(null);
+  const inputErrors = findings.some((f) => f.severity === "error");
+  const canStart =
+    sourcePaths.length > 0 &&
+    suffixInput.trim() !== "" &&
+    (tagInPlace ? tag.trim() !== "" : destPath !== "") &&
+    !inputErrors;
+
+  // The destination's volume, for the room on it and whether moves from the first source are
+  // renames; read again after each run, which changes the free space.
+  useEffect(() => {
+    if (running || !destPath || tagInPlace) {
+      if (!running) setDestVolume(null);
+      return;
+    }
+    let current = true;
+    invoke<VolumeInfo>("get_volume_info", { path: destPath, other: sourcePaths[0] ?? null })
+      .then((info) => current && setDestVolume(info))
+      .catch(() => current && setDestVolume(null));
+    return () => {
+      current = false;
+    };
+  }, [running, destPath, tagInPlace, sourcePaths]);
+
+  // The folders and suffixes checked again shortly after they change; nothing is scanned.
+  useEffect(() => {
+    if (running || (sourcePaths.length === 0 && !target && !suffixInput.trim())) {
+      setFindings([]);
+      return;
+    }
+    let current = true;
+    const timer = setTimeout(() => {
+      invoke<Finding[]>("validate_inputs", {
+        options: {
+          sources: sourcePaths,
+          dest: target,
+          tag: tagInPlace ? tag : undefined,
+          suffixInput: suffixInput.trim(),
+          routes: tagInPlace
+            ? []
+            : routes
+                .filter((r) => r.suffixes.trim() && r.dest)
+                .map((r) => ({ suffixes: r.suffixes.trim(), dest: r.dest })),
+          rejects: tagInPlace ? undefined : rejectsPath || undefined,
+          transferMode: transferMode,
+        },
+      })
+        .then((found) => current && setFindings(found))
+        .catch((e) => current && setFindings([{ severity: "warning", kind: "internal", message: String(e), path: null }]));
+    }, 250);
+    return () => {
+      current = false;
+      clearTimeout(timer);
+    };
+  }, [running, sourcePaths, target, tagInPlace, tag, suffixInput, routes, rejectsPath, transferMode]);
+
+  // The match count under the suffix box, again on each key: the sources are listed once and
+  // counted from the listing until they change. It does not wait for a run to end; the listing
+  // is made afresh after one.
+  useEffect(() => {
+    const seq = ++countSeq.current;
+    if (sourcePaths.length === 0 || suffixInput.trim() === "") {
+      setMatchCount(null);
+      return;
+    }
+    const timer = setTimeout(() => {
+      invoke<MatchCount>("count_matches", {
+        options: {
+          sources: sourcePaths,
+          dest: target,
+          tag: tagInPlace ? tag : undefined,
+          suffixInput: suffixInput.trim(),
+          routes: tagInPlace
+            ? []
+            : routes
+                .filter((r) => r.suffixes.trim() && r.dest)
+                .map((r) => ({ suffixes: r.suffixes.trim(), dest: r.dest })),
+          excludeGlobs: excludeGlobs
+            .split(/[,\n]/)
+            .map((g) => g.trim())
+            .filter((g) => g.length > 0),
+          normalizeUnicode: normalizeUnicode,
+          symlinks: symlinks,
+          matchBy: matchBy,
+          limit: limit,
+        },
+      })
+        .then((found) => seq === countSeq.current && !found.cancelled && setMatchCount(found))
+        // The findings and the preview say what is wrong with the inputs.
+        .catch(() => seq === countSeq.current && setMatchCount(null));
+    }, 100);
+    return () => clearTimeout(timer);
+  }, [running, sourcePaths, target, tagInPlace, tag, suffixInput, routes, excludeGlobs, normalizeUnicode, symlinks, matchBy, limit, listingVersion]);
+
+  // What a run would take, scanned again (the last scan cancelled) shortly after the inputs
+  // that decide it change.
+  useEffect(() => {
+    const seq = ++previewSeq.current;
+    if (running || sourcePaths.length === 0 || suffixInput.trim() === "") {
+      invoke("cancel_preview").catch(() => {});
+      setPreview(null);
+      setPreviewError(null);
+      setPreviewing(false);
+      return;
+    }
+    const timer = setTimeout(async () => {
+      setPreviewing(true);
+      try {
+        const found = await invoke<ScanPreview>("preview_scan", {
+          options: {
+            sources: sourcePaths,
+            dest: target,
+            tag: tagInPlace ? tag : undefined,
+            suffixInput: suffixInput.trim(),
+            routes: tagInPlace
+              ? []
+              : routes
+                  .filter((r) => r.suffixes.trim() && r.dest)
+                  .map((r) => ({ suffixes: r.suffixes.trim(), dest: r.dest })),
+            excludeGlobs: excludeGlobs
+              .split(/[,\n]/)
+              .map((g) => g.trim())
+              .filter((g) => g.length > 0),
+            normalizeUnicode: normalizeUnicode,
+            symlinks: symlinks,
+            matchBy: matchBy,
+            limit: limit,
+          },
+        });
+        if (seq !== previewSeq.current || found.cancelled) return;
+        setPreview(found);
+        setUnticked(new Set());
+        setPreviewError(null);
+      } catch (e) {
+        if (seq !== previewSeq.current) return;
+        setPreview(null);
+        setPreviewError(typeof e === "object" && e !== null ? (e as CommandError) : { message: String(e), kind: "internal", path: null });
+      }
+      setPreviewing(false);
+    }, 500);
+    return () => clearTimeout(timer);
+  }, [running, sourcePaths, target, tagInPlace, tag, suffixInput, routes, excludeGlobs, normalizeUnicode, symlinks, matchBy, limit]);
+
+  const sortPreviewBy = (key: PreviewSort) =>
+    setPreviewSort((s) => ({ key, ascending: s.key === key ? !s.ascending : true }));
+
+  /** A run's start returned its id: from now on, its events are the ones shown. */
+  const started = (runId: number) => {
+    latestRun.current = Math.max(latestRun.current, runId);
+    cancelId.current = runId;
+  };
+
+  /** Start a run of the ticked preview files, sending the paths in chunks (see `stage_selection`). */
+  const moveSelected = async (options: Record<string, unknown>) => {
+    const paths = (preview?.entries ?? []).map((f) => f.path).filter((p) => !unticked.has(p));
+    let sent = 0;
+    for (; paths.length - sent > SELECTION_CHUNK; sent += SELECTION_CHUNK) {
+      await invoke<number>("stage_selection", { paths: paths.slice(sent, sent + SELECTION_CHUNK), clear: sent === 0 });
+    }
+    if (sent === 0) await invoke<number>("stage_selection", { paths: [], clear: true });
+    return invoke<number>("move_selected", { options, paths: paths.slice(sent) });
+  };
+
+  const start = async (mode: "move" | "watch" | "plan" | "selected") => {
+    const watch = mode === "watch";
+    setError(null);
+    setPlan(null);
+    setUnmatched([]);
+    setLogLines((prev) => [
+      ...prev,
+      watch ? "Starting watch mode…" : mode === "plan" ? "Planning…" : "Starting…",
+    ]);
     setRunning(true);
+    setPaused(false);
+    setLastResult(null);
+    setWatching(watch);
     try {
-      await invoke("start_move", {
-        source: sourcePath,
-        dest: destPath,
+      const stamp = new Date().toISOString().replace(/[:.]/g, "-");
+      const reportPath = saveReport
+        ? await join(target, `framemover-report-${stamp}.json`)
+        : undefined;
+      if (reportPath) addLog(`Report: ${reportPath}`);
+      const logPath = writeLog
+        ? await join(await appLogDir(), `framemover-${stamp}.log`)
+        : undefined;
+      if (logPath) addLog(`Log file: ${logPath}`);
+      setSavedRun(null);
+      setSavedRunError(null);
+      const options = {
+        sources: sourcePaths,
+        dest: target,
+        tag: tagInPlace ? tag : undefined,
         suffixInput: suffixInput.trim(),
+        routes: tagInPlace
+          ? []
+          : routes
+              .filter((r) => r.suffixes.trim() && r.dest)
+              .map((r) => ({ suffixes: r.suffixes.trim(), dest: r.dest })),
+        rejects: tagInPlace ? undefined : rejectsPath || undefined,
         dryRun: dryRun,
         verbose: verbose,
-      });
+        layout: destLayout,
+        duplicateAction: noDedupe ? "leave" : duplicateAction,
+        transferMode: transferMode,
+        collisionPattern: collisionPattern.trim() || undefined,
+        onCollision: onCollision,
+        excludeGlobs: excludeGlobs
+          .split(/[,\n]/)
+          .map((g) => g.trim())
+          .filter((g) => g.length > 0),
+        removeEmptyDirs: removeEmptyDirs,
+        junkAsEmpty: junkAsEmpty,
+        verifyCopies: verifyCopies,
+        verify: verifyAfter ? (verifyHash ? "content" : "exists") : "off",
+        preserveXattrs: preserveXattrs,
+        normalizeUnicode: normalizeUnicode,
+        readOnly: readOnly,
+        sanitize: sanitize,
+        symlinks: symlinks,
+        emptyFiles: emptyFiles,
+        matchBy: matchBy,
+        reportPath: reportPath,
+        logPath: logPath,
+        statePath: watch || dryRun ? undefined : await statePath(),
+        workers: workers,
+        throttle: throttleMb * 1_000_000,
+        limit: limit,
+        minAge: minAge >= 0 ? minAge : watch ? 10 : 0,
+        skipPreflight: skipPreflight,
+        failFast: failFast,
+        dedup: noDedupe ? "off" : dedupMode,
+        confirmDuplicates: confirmDuplicates && !noDedupe,
+      };
+      const runId =
+        mode === "selected"
+          ? await moveSelected(options)
+          : await invoke<number>({ move: "start_move", watch: "start_watch", plan: "plan_move" }[mode], { options });
+      started(runId);
+    } catch (e) {
+      setError(String(e));
+      setRunning(false);
+      setWatching(false);
+      addLog(`Error: ${e}`);
+    }
+  };
+
+  const executePlan = async () => {
+    if (!plan) return;
+    setError(null);
+    addLog(`Moving ${plan.files.length} planned file(s)…`);
+    setRunning(true);
+    setPaused(false);
+    setLastResult(null);
+    try {
+      started(await invoke<number>("execute_plan", { plan }));
+      setPlan(null);
+    } catch (e) {
+      setError(String(e));
+      setRunning(false);
+      addLog(`Error: ${e}`);
+    }
+  };
+
+  const resumeSavedRun = async () => {
+    if (!savedRun) return;
+    setError(null);
+    addLog(`Resuming the run of ${new Date(savedRun.createdAt).toLocaleString()}…`);
+    setSavedRun(null);
+    setRunning(true);
+    setPaused(false);
+    setLastResult(null);
+    try {
+      started(await invoke<number>("resume_run", { path: await statePath() }));
+    } catch (e) {
+      setError(String(e));
+      setRunning(false);
+      addLog(`Error: ${e}`);
+    }
+  };
+
+  const discardSavedRun = async () => {
+    try {
+      await invoke("discard_saved_run", { path: await statePath() });
+      setSavedRun(null);
+      setSavedRunError(null);
+    } catch (e) {
+      addLog(`Error: ${e}`);
+    }
+  };
+
+  const startAudit = async () => {
+    setError(null);
+    setAudit(null);
+    addLog(`Looking for duplicates in ${destPath}…`);
+    setRunning(true);
+    setPaused(false);
+    setLastResult(null);
+    try {
+      started(await invoke<number>("audit_destination", { dest: destPath, workers }));
     } catch (e) {
       setError(String(e));
       setRunning(false);
@@ -108,35 +1293,117 @@ export default function App() {
     }
   };
 
+  const startFrameAudit = async () => {
+    setError(null);
+    setFrameAudit(null);
+    addLog(`Looking for frames ${suffixInput.trim()}…`);
+    setRunning(true);
+    cancelId.current = null;
+    try {
+      const report = await invoke<FrameAudit>("audit_frames", {
+        sources: sourcePaths,
+        dest: destPath,
+        suffixes: suffixInput,
+      });
+      const count = (p: FrameLocation["presence"]) => report.frames.filter((f) => f.presence === p).length;
+      report.errors.forEach((e) => addLog(`Error (${e.stage})${e.path ? ` ${e.path}` : ""}: ${e.message}`));
+      addLog(
+        `Frames: ${count("both")} in both, ${count("source_only")} in the sources only, ${count("destination_only")} in the destination only, ${count("missing")} missing. Nothing was changed.`
+      );
+      setFrameAudit(report);
+    } catch (e) {
+      setError(String(e));
+      addLog(`Error: ${e}`);
+    }
+    setRunning(false);
+  };
+
+  const changeThrottle = async (mb: number) => {
+    setThrottleMb(mb);
+    if (!running) return;
+    try {
+      // Applies to the run in progress from its next chunk on.
+      await invoke("set_throttle", { bytesPerSecond: mb * 1_000_000 });
+    } catch (e) {
+      addLog(`Throttle error: ${e}`);
+    }
+  };
+
+  const togglePause = async () => {
+    try {
+      await invoke(paused ? "resume_move" : "pause_move");
+      setPaused(!paused);
+      addLog(paused ? "Resumed." : "Paused.");
+    } catch (e) {
+      addLog(`Pause error: ${e}`);
+    }
+  };
+
   const cancel = async () => {
+    // By id, so a cancel that arrives after the run ended cannot stop another one.
+    const runId = cancelId.current;
+    if (runId === null) {
+      addLog("The run has not started yet; cancel again in a moment.");
+      return;
+    }
     try {
-      await invoke("cancel_move");
-      addLog("Cancel requested.");
+      await invoke(watching ? "stop_watch" : "cancel_move", { runId });
+      addLog(watching ? "Stopping watch mode." : "Cancel requested.");
     } catch (e) {
       addLog(`Cancel error: ${e}`);
     }
   };
 
+  // Dropped or pasted text: one path per line, quoted, a file:// URL or starting with ~; the
+  // backend cleans each up and says what is wrong with the ones it cannot use.
+  const addPastedSources = useCallback(
+    async (lines: string[]) => {
+      const texts = lines.filter((l) => l.trim() !== "" && !l.startsWith("#"));
+      const paths: string[] = [];
+      const problems: string[] = [];
+      for (const text of texts) {
+        try {
+          paths.push(await invoke<string>("normalize_path", { path: text }));
+        } catch (e) {
+          problems.push(String(e));
+        }
+      }
+      if (paths.length > 0) addSources(paths);
+      if (problems.length > 0) setError(problems.join("; "));
+    },
+    [addSources]
+  );
+
   const [dragOver, setDragOver] = useState(false);
   const handleDrop = useCallback(
     (e: React.DragEvent) => {
       e.preventDefault();
       setDragOver(false);
-      const items = e.dataTransfer?.items;
-      if (!items || items.length === 0) return;
-      const item = items[0];
-      if (item.kind !== "file") return;
-      const file = item.getAsFile();
-      if (file) {
+      const transfer = e.dataTransfer;
+      if (!transfer) return;
+      const paths: string[] = [];
+      for (const file of Array.from(transfer.files)) {
         // Tauri/webview may expose path on the File object for native drops
         const path = (file as File & { path?: string }).path;
-        if (path) {
-          setSourcePath(path);
-          addLog(`Source (from drop): ${path}`);
-        }
+        if (path) paths.push(path);
+      }
+      if (paths.length === 0) {
+        // Dragged from a browser or a terminal: file URLs or paths as text.
+        const text = transfer.getData("text/uri-list") || transfer.getData("text/plain");
+        paths.push(...text.split(/\r?\n/));
       }
+      addPastedSources(paths);
     },
-    [addLog]
+    [addPastedSources]
+  );
+  const handlePaste = useCallback(
+    (e: React.ClipboardEvent) => {
+      const text = e.clipboardData.getData("text/plain");
+      if (!text) return;
+      e.preventDefault();
+      addPastedSources(text.split(/\r?\n/));
+    },
+    [addPastedSources]
   );
   const handleDragOver = useCallback((e: React.DragEvent) => {
     e.preventDefault();
@@ -155,35 +1422,168 @@ export default function App() {
         Move image files whose filename ends with the given suffix numbers. Preserves folder structure and skips duplicates by content hash.
       </p>
 
+      {closeRequest && (
+        <div className="saved-run">
+          {closeRequest === "ask" && (
+            <span>A run is still going. Closing stops it after the file it is on; files not reached stay where they are.</span>
+          )}
+          {closeRequest === "stopping" && <span>Stopping the run after the current file…</span>}
+          {closeRequest === "stuck" && (
+            <span className="warning-msg">
+              The run has not stopped yet. Closing now may leave a partly copied file in the destination.
+            </span>
+          )}
+          <div className="actions">
+            {closeRequest === "ask" && (
+              <button type="button" className="btn-primary" onClick={() => stopAndClose()}>
+                Stop the run and close
+              </button>
+            )}
+            {closeRequest === "stuck" && (
+              <button type="button" className="btn-primary" onClick={() => stopAndClose(true)}>
+                Close anyway
+              </button>
+            )}
+            {closeRequest !== "stopping" && (
+              <button type="button" className="btn-secondary" onClick={() => setCloseRequest(null)}>
+                {closeRequest === "ask" ? "Keep running" : "Wait"}
+              </button>
+            )}
+          </div>
+        </div>
+      )}
+
+      {(savedRun || savedRunError) && (
+        <div className="saved-run">
+          {savedRun ? (
+            <span>
+              An unfinished run from {new Date(savedRun.createdAt).toLocaleString()} ({savedRun.sources.join(", ")} →{" "}
+              {savedRun.dest}): {savedRun.done} of {savedRun.total} files done.
+            </span>
+          ) : (
+            <span className="error-msg">The last unfinished run cannot be resumed: {savedRunError}</span>
+          )}
+          <div className="actions">
+            {savedRun && (
+              <button type="button" className="btn-primary" onClick={resumeSavedRun} disabled={running}>
+                Resume last run
+              </button>
+            )}
+            <button type="button" className="btn-secondary" onClick={discardSavedRun} disabled={running}>
+              Discard
+            </button>
+          </div>
+        </div>
+      )}
+
       <div className="section">
-        <label>Source folder (drag-and-drop or pick)</label>
+        <label>Source folders (drag-and-drop, paste or add; files from all of them go to one destination)</label>
         <div
           className={`drop-zone ${dragOver ? "drag-over" : ""}`}
           onDrop={handleDrop}
           onDragOver={handleDragOver}
           onDragLeave={handleDragLeave}
+          onPaste={handlePaste}
+          tabIndex={0}
         >
+          {sourcePaths.map((path) => (
+            <div className="row" key={path} style={{ justifyContent: "center" }}>
+              <span className="path-display filled" style={{ maxWidth: "100%" }}>
+                {path}
+              </span>
+              <button
+                type="button"
+                className="btn-secondary"
+                onClick={() => removeSource(path)}
+                disabled={running}
+              >
+                Remove
+              </button>
+            </div>
+          ))}
           <div className="row" style={{ justifyContent: "center" }}>
-            <span className="path-display filled" style={{ maxWidth: "100%" }}>
-              {sourcePath || "No folder selected"}
-            </span>
-            <button type="button" className="btn-secondary" onClick={pickSource}>
-              Browse…
+            {sourcePaths.length === 0 && (
+              <span className="path-display filled" style={{ maxWidth: "100%" }}>
+                No folder selected
+              </span>
+            )}
+            <button type="button" className="btn-secondary" onClick={pickSource} disabled={running}>
+              Add folder…
             </button>
+            {recentSources.some((f) => !sourcePaths.includes(f)) && (
+              <select
+                value=""
+                onChange={(e) => e.target.value && addSources([e.target.value])}
+                disabled={running}
+              >
+                <option value="">Recent…</option>
+                {recentSources
+                  .filter((f) => !sourcePaths.includes(f))
+                  .map((f) => (
+                    <option key={f} value={f}>
+                      {f}
+                    </option>
+                  ))}
+              </select>
+            )}
           </div>
         </div>
       </div>
 
       <div className="section">
-        <label>Destination folder</label>
+        <label>{tagInPlace ? "Tag added to matched file names" : "Destination folder"}</label>
         <div className="row">
-          <span className={`path-display ${destPath ? "filled" : ""}`}>
-            {destPath || "No folder selected"}
-          </span>
-          <button type="button" className="btn-secondary" onClick={pickDest}>
-            Browse…
-          </button>
+          <select
+            value={tagInPlace ? "tag" : "move"}
+            onChange={(e) => setTagInPlace(e.target.value === "tag")}
+            disabled={running}
+          >
+            <option value="move">Move to destination</option>
+            <option value="tag">Tag in place</option>
+          </select>
+          {tagInPlace ? (
+            <input
+              type="text"
+              placeholder="e.g. _pick (IMG_7612.JPG → IMG_7612_pick.JPG)"
+              value={tag}
+              onChange={(e) => setTag(e.target.value)}
+              disabled={running}
+            />
+          ) : (
+            <>
+              <span className={`path-display ${destPath ? "filled" : ""}`}>
+                {destPath || "No folder selected"}
+              </span>
+              <button type="button" className="btn-secondary" onClick={pickDest}>
+                Browse…
+              </button>
+              {recentDests.some((f) => f !== destPath) && (
+                <select value="" onChange={(e) => e.target.value && chooseDest(e.target.value)} disabled={running}>
+                  <option value="">Recent…</option>
+                  {recentDests
+                    .filter((f) => f !== destPath)
+                    .map((f) => (
+                      <option key={f} value={f}>
+                        {f}
+                      </option>
+                    ))}
+                </select>
+              )}
+            </>
+          )}
         </div>
+        {destVolume && !tagInPlace && (
+          <div className="progress-stats">
+            {destVolume.freeBytes !== null && `${formatBytes(destVolume.freeBytes)} free`}
+            {destVolume.totalBytes !== null && ` of ${formatBytes(destVolume.totalBytes)}`}
+            {` on ${destVolume.name ? `'${destVolume.name}'` : destVolume.mountPoint ?? "its volume"}`}
+            {destVolume.filesystem && ` (${destVolume.filesystem})`}
+            {destVolume.sameVolume === false &&
+              (transferMode === "move" || transferMode === "hardlink") &&
+              ` — different volume, files will be copied${transferMode === "move" ? " and then deleted" : ""}`}
+            {destVolume.sameVolume === true && transferMode === "move" && " — same volume as the source, moves are instant renames"}
+          </div>
+        )}
       </div>
 
       <div className="section">
@@ -193,6 +1593,376 @@ export default function App() {
           value={suffixInput}
           onChange={(e) => setSuffixInput(e.target.value)}
         />
+        {matchCount && (
+          <div className="progress-stats">
+            {`Matches ${matchCount.matched} file${matchCount.matched === 1 ? "" : "s"} (${formatBytes(matchCount.totalBytes)})`}
+            {matchCount.excluded > 0 && `, ${matchCount.excluded} excluded`}
+            {matchCount.skippedSymlinks > 0 && `, ${matchCount.skippedSymlinks} links skipped`}
+            {matchCount.overLimit > 0 && `, ${matchCount.overLimit} past the limit`}{" "}
+            <button
+              type="button"
+              className="btn-secondary"
+              onClick={() => {
+                invoke("refresh_source_listing")
+                  .then(() => setListingVersion((v) => v + 1))
+                  .catch(() => {});
+              }}
+              title="Counts reuse a listing of the sources made once. Refresh after adding or removing files in them outside the app."
+            >
+              Refresh
+            </button>
+          </div>
+        )}
+        <div className="row">
+          {presets.length > 0 && (
+            <select value="" onChange={(e) => e.target.value && loadPreset(e.target.value)} disabled={running}>
+              <option value="">Load preset…</option>
+              {presets.map((p) => (
+                <option key={p.name} value={p.name}>
+                  {p.name} ({p.suffixCount} suffixes)
+                </option>
+              ))}
+            </select>
+          )}
+          <input
+            type="text"
+            placeholder="Preset name"
+            value={presetName}
+            onChange={(e) => {
+              setPresetName(e.target.value);
+              setPresetConflict(null);
+            }}
+          />
+          <button
+            type="button"
+            className="btn-secondary"
+            onClick={() => savePreset()}
+            disabled={!presetName.trim() || !suffixInput.trim()}
+          >
+            Save as preset
+          </button>
+          {currentPreset && presetName.trim() !== currentPreset && presetName.trim() !== "" && (
+            <button type="button" className="btn-secondary" onClick={() => renamePreset()}>
+              Rename “{currentPreset}”
+            </button>
+          )}
+          {currentPreset && (
+            <button type="button" className="btn-secondary" onClick={deletePreset}>
+              Delete “{currentPreset}”
+            </button>
+          )}
+        </div>
+        {presetConflict && (
+          <div className="warning-msg">
+            A preset named “{presetConflict.name}” already exists.{" "}
+            <button
+              type="button"
+              className="btn-secondary"
+              onClick={() => (presetConflict.action === "save" ? savePreset(true) : renamePreset(true))}
+            >
+              Overwrite
+            </button>{" "}
+            <button type="button" className="btn-secondary" onClick={() => setPresetConflict(null)}>
+              Cancel
+            </button>
+          </div>
+        )}
+        {unmatched.length > 0 && (
+          <div className="unmatched-suffixes">
+            No files found for:{" "}
+            {unmatched.map((s) => (
+              <span key={s} className="suffix-chip">
+                {s}
+              </span>
+            ))}
+          </div>
+        )}
+      </div>
+
+      {!tagInPlace && (
+        <>
+          <div className="section">
+            <label>Rejects folder (optional): images that match no suffix are moved here</label>
+            <div className="row">
+              <span className={`path-display ${rejectsPath ? "filled" : ""}`}>{rejectsPath || "Not used"}</span>
+              <button type="button" className="btn-secondary" onClick={pickRejects} disabled={running}>
+                Browse…
+              </button>
+              {rejectsPath && (
+                <button type="button" className="btn-secondary" onClick={() => setRejectsPath("")} disabled={running}>
+                  Clear
+                </button>
+              )}
+            </div>
+          </div>
+
+          <div className="section">
+            <label>Routes: other suffixes to other folders (a file follows the first rule listing it)</label>
+            {routes.map((r, i) => (
+              <div className="row" key={i}>
+                <input
+                  type="text"
+                  placeholder="e.g. 7600-7650"
+                  value={r.suffixes}
+                  onChange={(e) => updateRoute(i, { suffixes: e.target.value })}
+                  disabled={running}
+                />
+                <span className={`path-display ${r.dest ? "filled" : ""}`}>{r.dest || "No folder selected"}</span>
+                <button type="button" className="btn-secondary" onClick={() => pickRouteDest(i)} disabled={running}>
+                  Browse…
+                </button>
+                <button
+                  type="button"
+                  className="btn-secondary"
+                  onClick={() => setRoutes((prev) => prev.filter((_, j) => j !== i))}
+                  disabled={running}
+                >
+                  Remove
+                </button>
+              </div>
+            ))}
+            <div className="row">
+              <button
+                type="button"
+                className="btn-secondary"
+                onClick={() => setRoutes((prev) => [...prev, { suffixes: "", dest: "" }])}
+                disabled={running}
+              >
+                Add route…
+              </button>
+            </div>
+          </div>
+        </>
+      )}
+
+      <div className="section">
+        <label>Destination layout</label>
+        <div className="row">
+          <select value={layout} onChange={(e) => setLayout(e.target.value)} disabled={running}>
+            {LAYOUT_PRESETS.map((p) => (
+              <option key={p.value} value={p.value}>
+                {p.label}
+              </option>
+            ))}
+          </select>
+          {layout === "custom" && (
+            <input
+              type="text"
+              placeholder="e.g. {year}/{month}/{parent}"
+              title="Tokens: {year} {month} {day} {suffix} {ext} {parent} {filename} {stem}"
+              value={customTemplate}
+              onChange={(e) => setCustomTemplate(e.target.value)}
+              disabled={running}
+            />
+          )}
+        </div>
+      </div>
+
+      <div className="section">
+        <label>Transfer mode</label>
+        <select
+          value={transferMode}
+          onChange={(e) => setTransferMode(e.target.value)}
+          disabled={running}
+        >
+          <option value="move">Move</option>
+          <option value="copy">Copy (keep source)</option>
+          <option value="hardlink">Hardlink (same volume, falls back to copy)</option>
+          <option value="reflink">Reflink clone (APFS/Btrfs/XFS, falls back to copy)</option>
+        </select>
+      </div>
+
+      <div className="section">
+        <label>Rename pattern for name clashes ({"{n}"} = counter, {"{ts}"} = timestamp)</label>
+        <div className="row">
+          <select
+            value={["-{n}", " ({n})", "-{ts}"].includes(collisionPattern) ? collisionPattern : "custom"}
+            onChange={(e) => setCollisionPattern(e.target.value === "custom" ? "" : e.target.value)}
+            disabled={running}
+          >
+            <option value="-{n}">IMG_7612-1.JPG</option>
+            <option value=" ({n})">IMG_7612 (1).JPG</option>
+            <option value="-{ts}">IMG_7612-20240506-142233.JPG</option>
+            <option value="custom">Custom…</option>
+          </select>
+          <select value={onCollision} onChange={(e) => setOnCollision(e.target.value)} disabled={running}>
+            <option value="rename">Keep both (rename the new file)</option>
+            <option value="update">Update: replace if the source is newer</option>
+          </select>
+          {!["-{n}", " ({n})", "-{ts}"].includes(collisionPattern) && (
+            <input
+              type="text"
+              placeholder="e.g. _dup{n}"
+              value={collisionPattern}
+              onChange={(e) => setCollisionPattern(e.target.value)}
+              disabled={running}
+            />
+          )}
+        </div>
+      </div>
+
+      <div className="section">
+        <label>Leave out files named like (comma-separated, case is ignored)</label>
+        <input
+          type="text"
+          placeholder="e.g. thumb_*, *_preview_*"
+          value={excludeGlobs}
+          onChange={(e) => setExcludeGlobs(e.target.value)}
+          disabled={running}
+        />
+      </div>
+
+      <div className="section">
+        <label>Find duplicates</label>
+        <select
+          value={dedupMode}
+          onChange={(e) => setDedupMode(e.target.value)}
+          disabled={running || noDedupe}
+          title="Same path only skips hashing the whole destination. It only recognizes a file already at the exact place it would be moved to; identical content under another name or folder is not caught and is moved again."
+        >
+          <option value="content">By content, anywhere in the destination</option>
+          <option value="same_path">Same path only (faster, no destination index)</option>
+        </select>
+        <label
+          className="toggle-wrap"
+          title="Nothing is hashed and nothing counts as a duplicate: every match is moved, renamed if its name is taken. Meant for a new, empty destination."
+        >
+          <input
+            type="checkbox"
+            checked={noDedupe}
+            onChange={(e) => setNoDedupe(e.target.checked)}
+            disabled={running}
+          />
+          Don't check for duplicates (empty destination)
+        </label>
+        <label
+          className="toggle-wrap"
+          title="Before a file is skipped as a duplicate, it is read again alongside the file it matched. If the bytes differ, it is moved under a new name instead."
+        >
+          <input
+            type="checkbox"
+            checked={confirmDuplicates && !noDedupe}
+            onChange={(e) => setConfirmDuplicates(e.target.checked)}
+            disabled={running || noDedupe}
+          />
+          Compare duplicates byte by byte before skipping them
+        </label>
+        <button
+          type="button"
+          className="btn-secondary"
+          onClick={refreshIndex}
+          disabled={running}
+          title="Runs of this session reuse the destination hashes earlier ones read, as long as each file's size and modification time are unchanged. Refresh after changing the destination in a way that keeps both, or to read it afresh."
+        >
+          Refresh index
+        </button>
+      </div>
+
+      <div className="section">
+        <label>Source files already in the destination</label>
+        <select
+          value={noDedupe ? "leave" : duplicateAction}
+          onChange={(e) => setDuplicateAction(e.target.value)}
+          disabled={running || noDedupe}
+        >
+          <option value="leave">Leave them in the source</option>
+          <option value="trash">Move them to the trash</option>
+          <option value="delete">Delete them permanently</option>
+        </select>
+      </div>
+
+      <div className="section">
+        <label>Make names valid for exFAT/FAT32/SMB destinations</label>
+        <select value={sanitize} onChange={(e) => setSanitize(e.target.value)} disabled={running}>
+          <option value="auto">When the destination needs it</option>
+          <option value="on">Always</option>
+          <option value="off">Never</option>
+        </select>
+      </div>
+
+      <div className="section">
+        <label>Matches that are symbolic links</label>
+        <select value={symlinks} onChange={(e) => setSymlinks(e.target.value)} disabled={running}>
+          <option value="skip">Skip them</option>
+          <option value="link">Move the link itself</option>
+          <option value="target">Move the image it points to</option>
+        </select>
+      </div>
+
+      <div className="section">
+        <label>Match files by</label>
+        <select value={matchBy} onChange={(e) => setMatchBy(e.target.value)} disabled={running}>
+          <option value="name">Their file name</option>
+          <option value="metadata">The camera's file name in their metadata (for renamed files; slower)</option>
+        </select>
+      </div>
+
+      <div className="section">
+        <label>Empty (0-byte) files</label>
+        <select value={emptyFiles} onChange={(e) => setEmptyFiles(e.target.value)} disabled={running}>
+          <option value="move">Move them, with a warning</option>
+          <option value="skip">Skip them, with a warning</option>
+          <option value="error">Count them as errors</option>
+        </select>
+      </div>
+
+      <div className="section">
+        <label>Files moved at once</label>
+        <select
+          value={workers}
+          onChange={(e) => setWorkers(Number(e.target.value))}
+          disabled={running}
+        >
+          <option value={0}>Automatic</option>
+          <option value={1}>1 (one after another)</option>
+          <option value={2}>2</option>
+          <option value={4}>4</option>
+          <option value={8}>8</option>
+        </select>
+      </div>
+
+      <div className="section">
+        <label>Take at most this many files (0 for all)</label>
+        <input
+          type="number"
+          min={0}
+          value={limit}
+          onChange={(e) => setLimit(Math.max(0, Math.floor(Number(e.target.value) || 0)))}
+          disabled={running}
+          title="Try the settings on the first matches before moving them all"
+        />
+      </div>
+
+      <div className="section">
+        <label>Skip files still being written (modified in the last…)</label>
+        <select
+          value={minAge}
+          onChange={(e) => setMinAge(Number(e.target.value))}
+          disabled={running}
+          title="Files an import or tethering app may still be writing are left for the next run, or waited for in watch mode"
+        >
+          <option value={-1}>Automatic (10 s when watching)</option>
+          <option value={0}>Off</option>
+          <option value={10}>10 s</option>
+          <option value={30}>30 s</option>
+          <option value={60}>1 min</option>
+          <option value={300}>5 min</option>
+        </select>
+      </div>
+
+      <div className="section">
+        <label>
+          Speed limit: {throttleMb === 0 ? "none" : `${throttleMb} MB/s`}
+        </label>
+        <input
+          type="range"
+          min={0}
+          max={200}
+          step={5}
+          value={throttleMb}
+          onChange={(e) => changeThrottle(Number(e.target.value))}
+          title="Limit reading and writing so the disk stays usable for other work; can be changed during a run"
+        />
       </div>
 
       <div className="section toggles">
@@ -214,48 +1984,608 @@ export default function App() {
           />
           Verbose log
         </label>
+        <label className="toggle-wrap" title="A system notification when a run ends while the app is in the background">
+          <input type="checkbox" checked={notifyWhenDone} onChange={(e) => setNotifyWhenDone(e.target.checked)} />
+          Notify when done
+        </label>
+        <label className="toggle-wrap">
+          <input
+            type="checkbox"
+            checked={removeEmptyDirs}
+            onChange={(e) => setRemoveEmptyDirs(e.target.checked)}
+            disabled={running}
+          />
+          Remove empty source folders
+        </label>
+        <label className="toggle-wrap">
+          <input
+            type="checkbox"
+            checked={junkAsEmpty}
+            onChange={(e) => setJunkAsEmpty(e.target.checked)}
+            disabled={running || !removeEmptyDirs}
+          />
+          Ignore .DS_Store / Thumbs.db
+        </label>
+        <label className="toggle-wrap">
+          <input
+            type="checkbox"
+            checked={verifyCopies}
+            onChange={(e) => setVerifyCopies(e.target.checked)}
+            disabled={running}
+          />
+          Verify cross-volume copies
+        </label>
+        <label className="toggle-wrap">
+          <input
+            type="checkbox"
+            checked={verifyAfter}
+            onChange={(e) => setVerifyAfter(e.target.checked)}
+            disabled={running}
+          />
+          Check moved files at the end
+        </label>
+        <label className="toggle-wrap">
+          <input
+            type="checkbox"
+            checked={verifyHash}
+            onChange={(e) => setVerifyHash(e.target.checked)}
+            disabled={running || !verifyAfter}
+          />
+          Re-hash them (reads every file again)
+        </label>
+        <label className="toggle-wrap">
+          <input
+            type="checkbox"
+            checked={preserveXattrs}
+            onChange={(e) => setPreserveXattrs(e.target.checked)}
+            disabled={running}
+          />
+          Keep Finder tags on copies (macOS)
+        </label>
+        <label className="toggle-wrap" title="Names written by macOS spell accented letters differently; this treats both spellings as the same name">
+          <input
+            type="checkbox"
+            checked={normalizeUnicode}
+            onChange={(e) => setNormalizeUnicode(e.target.checked)}
+            disabled={running}
+          />
+          Treat accented names the same however they are encoded
+        </label>
+        <label className="toggle-wrap" title="Hardlinked files stay writable, as the source would turn read-only too">
+          <input
+            type="checkbox"
+            checked={readOnly}
+            onChange={(e) => setReadOnly(e.target.checked)}
+            disabled={running}
+          />
+          Make moved files read-only
+        </label>
+        <label className="toggle-wrap">
+          <input
+            type="checkbox"
+            checked={saveReport}
+            onChange={(e) => setSaveReport(e.target.checked)}
+            disabled={running}
+          />
+          Save a JSON report in the destination
+        </label>
+        <label className="toggle-wrap">
+          <input
+            type="checkbox"
+            checked={writeLog}
+            onChange={(e) => setWriteLog(e.target.checked)}
+            disabled={running}
+          />
+          Write a log file (app log folder)
+        </label>
+        <label className="toggle-wrap">
+          <input
+            type="checkbox"
+            checked={skipPreflight}
+            onChange={(e) => setSkipPreflight(e.target.checked)}
+            disabled={running}
+          />
+          Skip free-space and write checks
+        </label>
+        <label className="toggle-wrap">
+          <input
+            type="checkbox"
+            checked={failFast}
+            onChange={(e) => setFailFast(e.target.checked)}
+            disabled={running}
+          />
+          Stop at the first error (leave the rest untouched)
+        </label>
       </div>
 
       <div className="actions">
         <button
           type="button"
           className="btn-primary"
-          onClick={start}
-          disabled={running || !sourcePath || !destPath || !suffixInput.trim()}
+          onClick={() => start("move")}
+          disabled={running || !canStart}
         >
           Start
         </button>
+        <button
+          type="button"
+          className="btn-secondary"
+          onClick={() => start("watch")}
+          disabled={running || !canStart || tagInPlace}
+          title="Move what matches now, then keep moving new matching files as they appear"
+        >
+          Watch
+        </button>
+        <button
+          type="button"
+          className="btn-secondary"
+          onClick={() => start("plan")}
+          disabled={running || !canStart}
+          title="Work out where every file goes, then move them only once you confirm"
+        >
+          Review
+        </button>
+        <button
+          type="button"
+          className="btn-secondary"
+          onClick={startAudit}
+          disabled={running || !destPath || tagInPlace}
+          title="List identical images already in the destination; nothing is moved or deleted"
+        >
+          Find duplicates
+        </button>
+        <button
+          type="button"
+          className="btn-secondary"
+          onClick={startFrameAudit}
+          disabled={running || sourcePaths.length === 0 || !destPath || !suffixInput.trim() || tagInPlace}
+          title="Show which frames are in the sources, the destination, both or neither; nothing is moved or created"
+        >
+          Where are my picks?
+        </button>
+        <button
+          type="button"
+          className="btn-secondary"
+          onClick={togglePause}
+          disabled={!running}
+        >
+          {paused ? "Resume" : "Pause"}
+        </button>
         <button
           type="button"
           className="btn-secondary"
           onClick={cancel}
           disabled={!running}
         >
-          Cancel
+          {watching ? "Stop watching" : "Cancel"}
+        </button>
+        <button
+          type="button"
+          className="btn-secondary"
+          onClick={() => reveal(target)}
+          disabled={!target}
+          title="Open the destination folder in the file manager"
+        >
+          Open destination
         </button>
       </div>
 
       {error && <p className="error-msg">{error}</p>}
 
+      {findings.map((f, i) => (
+        <p key={i} className={f.severity === "error" ? "error-msg" : "warning-msg"}>
+          {f.message}
+        </p>
+      ))}
+
+      {previewError && (
+        <p className="error-msg">
+          Cannot preview: {previewError.message}
+        </p>
+      )}
+
+      {preview && !plan && (
+        <div className="section">
+          <label>
+            Files a run would take: {preview.matched.toLocaleString()} ({formatBytes(preview.totalBytes)})
+            {previewing && " — updating…"}
+            {preview.truncated && `, the first ${preview.entries.length.toLocaleString()} shown`}
+            {preview.excluded > 0 && `, ${preview.excluded} excluded`}
+            {preview.skippedSymlinks > 0 && `, ${preview.skippedSymlinks} symbolic link(s) skipped`}
+            {preview.overLimit > 0 && `, ${preview.overLimit} past the limit`}
+          </label>
+          {preview.warnings.map((w) => (
+            <div key={w} className="unmatched-suffixes">
+              {w}
+            </div>
+          ))}
+          <div className="plan-view">
+            <table>
+              <thead>
+                <tr>
+                  <th>
+                    <input
+                      type="checkbox"
+                      checked={unticked.size === 0}
+                      onChange={(e) =>
+                        setUnticked(e.target.checked ? new Set() : new Set(preview.entries.map((f) => f.path)))
+                      }
+                      title="Tick or untick every file"
+                    />
+                  </th>
+                  <th />
+                  {(
+                    [
+                      ["path", "File"],
+                      ["suffix", "Suffix"],
+                      ["size", "Size"],
+                      ["modified", "Modified"],
+                    ] as [PreviewSort, string][]
+                  ).map(([key, label]) => (
+                    <th key={key} className="sortable" onClick={() => sortPreviewBy(key)}>
+                      {label}
+                      {previewSort.key === key && (previewSort.ascending ? " ▲" : " ▼")}
+                    </th>
+                  ))}
+                </tr>
+              </thead>
+              <tbody>
+                {sortedPreview(preview.entries, previewSort.key, previewSort.ascending).map((f) => (
+                  <tr key={f.path}>
+                    <td>
+                      <input
+                        type="checkbox"
+                        checked={!unticked.has(f.path)}
+                        onChange={(e) =>
+                          setUnticked((prev) => {
+                            const next = new Set(prev);
+                            if (e.target.checked) next.delete(f.path);
+                            else next.add(f.path);
+                            return next;
+                          })
+                        }
+                      />
+                    </td>
+                    <td>
+                      <Thumb path={f.path} />
+                    </td>
+                    <td>{f.path}</td>
+                    <td>{f.suffix}</td>
+                    <td>{formatBytes(f.size)}</td>
+                    <td>{f.modified ? new Date(f.modified).toLocaleString() : ""}</td>
+                  </tr>
+                ))}
+              </tbody>
+            </table>
+          </div>
+          <button
+            type="button"
+            className="btn-secondary"
+            onClick={() => start("selected")}
+            disabled={running || !canStart || unticked.size === preview.entries.length}
+            title={
+              preview.truncated
+                ? "Moves the ticked files of those shown; the rest of the matches stay where they are"
+                : "Moves only the ticked files; the others stay where they are"
+            }
+          >
+            Move {(preview.entries.length - unticked.size).toLocaleString()} selected
+          </button>
+        </div>
+      )}
+
+      {plan && (
+        <div className="section">
+          <label>
+            Planned files ({plan.files.length}){plan.options.readOnly === true && " — each is made read-only once moved"}
+          </label>
+          <div className="plan-view">
+            <table>
+              <thead>
+                <tr>
+                  <th />
+                  <th>Source</th>
+                  <th>Destination</th>
+                  <th>Status</th>
+                </tr>
+              </thead>
+              <tbody>
+                {plan.files.map((f) => (
+                  <tr key={f.source} className={`plan-${f.action}`}>
+                    <td>
+                      <Thumb path={f.source} />
+                    </td>
+                    <td>{f.source}</td>
+                    <td>{f.action === "move" ? f.dest : ""}</td>
+                    <td>{plannedStatus(f, plan.files)}</td>
+                  </tr>
+                ))}
+              </tbody>
+            </table>
+          </div>
+          <div className="actions">
+            <button
+              type="button"
+              className="btn-primary"
+              onClick={executePlan}
+              disabled={running || plan.options.dryRun || plan.files.length === 0}
+              title={plan.options.dryRun ? "Planned as a dry-run; plan again without it to move" : undefined}
+            >
+              Move planned files
+            </button>
+            <button
+              type="button"
+              className="btn-secondary"
+              onClick={() => setPlan(null)}
+              disabled={running}
+            >
+              Discard plan
+            </button>
+          </div>
+        </div>
+      )}
+
+      {frameAudit && (
+        <div className="section">
+          <label>
+            Where the frames are{frameAudit.destMissing && " (the destination does not exist yet)"}
+            {frameAudit.cancelled && " (cancelled before every copy was compared)"}
+          </label>
+          <div className="plan-view">
+            <table>
+              <thead>
+                <tr>
+                  <th>Suffix</th>
+                  <th>Where</th>
+                  <th>Files</th>
+                </tr>
+              </thead>
+              <tbody>
+                {frameAudit.frames.map((f) => (
+                  <tr key={f.suffix}>
+                    <td>{f.suffix}</td>
+                    <td>
+                      {PRESENCE_LABELS[f.presence]}
+                      {f.identical === true && ", identical"}
+                      {f.identical === false && ", different"}
+                    </td>
+                    <td>
+                      {[...f.sourceFiles, ...f.destFiles].map((p) => (
+                        <div key={p}>{p}</div>
+                      ))}
+                    </td>
+                  </tr>
+                ))}
+              </tbody>
+            </table>
+          </div>
+          <div className="actions">
+            <button type="button" className="btn-secondary" onClick={() => setFrameAudit(null)}>
+              Close
+            </button>
+          </div>
+        </div>
+      )}
+
+      {audit && (
+        <div className="section">
+          <label>
+            Duplicates in the destination ({audit.groups.length} group(s), {formatBytes(audit.wastedBytes)} wasted)
+          </label>
+          <div className="plan-view">
+            <table>
+              <thead>
+                <tr>
+                  <th>Files</th>
+                  <th>Size</th>
+                  <th>Wasted</th>
+                </tr>
+              </thead>
+              <tbody>
+                {audit.groups.map((g) => (
+                  <tr key={g.hash}>
+                    <td>
+                      {g.paths.map((p) => (
+                        <div key={p}>{p}</div>
+                      ))}
+                    </td>
+                    <td>{formatBytes(g.size)}</td>
+                    <td>{formatBytes(g.size * (g.paths.length - 1))}</td>
+                  </tr>
+                ))}
+              </tbody>
+            </table>
+          </div>
+          <div className="actions">
+            <button type="button" className="btn-secondary" onClick={() => setAudit(null)}>
+              Close
+            </button>
+          </div>
+        </div>
+      )}
+
       <div className="progress-section">
-        <label>Progress</label>
+        <label>
+          Progress{paused && " (Paused)"}
+          {progress.phase === "watching" && !paused && " (Watching for new files)"}
+          {progress.phase === "verifying" && !paused && " (Checking moved files)"}
+          {progress.phase === "done" && progress.cancelled &&
+            ` (Cancelled — ${progress.currentIndex} of ${progress.totalCount} files processed)`}
+        </label>
         <div className="progress-bar-wrap">
           <div
-            className="progress-bar"
+            className={progress.cancelled ? "progress-bar cancelled" : "progress-bar"}
             style={{ width: `${progress.percent}%` }}
           />
         </div>
         <div className="progress-stats">
-          <span><strong>Scanned / Matched:</strong> {progress.scanned} / {progress.matched}</span>
-          <span><strong>Moved:</strong> {progress.moved}</span>
+          <span>
+            <strong>Checked:</strong> {progress.scanned.toLocaleString()} files,{" "}
+            {progress.images.toLocaleString()} images, {progress.matched.toLocaleString()} matched
+          </span>
+          {tagInPlace ? (
+            <span><strong>Tagged:</strong> {progress.tagged}</span>
+          ) : (
+            <span><strong>Moved:</strong> {progress.moved}</span>
+          )}
           <span><strong>Skipped (duplicates):</strong> {progress.skippedDuplicates}</span>
+          {rejectsPath && <span><strong>Rejected:</strong> {progress.rejected}</span>}
+          {onCollision === "update" && <span><strong>Replaced:</strong> {progress.replaced}</span>}
           <span><strong>Errors:</strong> {progress.errors}</span>
         </div>
-        {progress.currentFile && (
-          <div className="current-file">{progress.currentFile}</div>
+        {progress.totalBytes > 0 && (
+          <div className="progress-stats">
+            <span>
+              {formatBytes(progress.bytesDone)} of {formatBytes(progress.totalBytes)}
+              {progress.totalCount > 0 && ` · file ${progress.currentIndex} of ${progress.totalCount}`}
+            </span>
+            {progress.throughput > 0 && <span>{formatBytes(progress.throughput)}/s</span>}
+            {progress.etaSeconds !== null && progress.phase !== "done" && (
+              <span>{formatEta(progress.etaSeconds)}</span>
+            )}
+          </div>
+        )}
+        {stall ? (
+          <div className="current-file stalled" title={stall.file.path}>
+            {`still ${stall.reading ? "reading" : "copying"} ${baseName(stall.file.path)} (${formatElapsed(stall.file.elapsedSeconds)})…`}
+          </div>
+        ) : (
+          progress.currentFile && <div className="current-file">{progress.currentFile}</div>
         )}
       </div>
 
+      {lastResult && !running && (
+        <div className="section">
+          <label>
+            {lastResult.dryRun ? "Dry run" : "Run"}{" "}
+            {lastResult.cancelled ? "cancelled" : lastResult.aborted ? "stopped" : "finished"} in{" "}
+            {formatDuration(lastResult.stats.durationMs)}
+          </label>
+          <div className="progress-stats">
+            <span>Matched: {lastResult.matched.toLocaleString()}</span>
+            <span>
+              {lastResult.dryRun ? "Would move" : "Moved"}: {lastResult.moved.toLocaleString()} (
+              {formatBytes(lastResult.stats.bytesMoved)})
+            </span>
+            <span>Duplicates skipped: {lastResult.skippedDuplicates.toLocaleString()}</span>
+            {lastResult.rejected > 0 && <span>Rejected: {lastResult.rejected.toLocaleString()}</span>}
+            <span>Errors: {lastResult.errors.toLocaleString()}</span>
+            {lastResult.notAttempted > 0 && <span>Not attempted: {lastResult.notAttempted.toLocaleString()}</span>}
+          </div>
+          {lastResult.errorDetails.length > 0 && (
+            <div className="plan-view">
+              <table>
+                <tbody>
+                  {lastResult.errorDetails.slice(0, MAX_SUMMARY_ERRORS).map((e, i) => (
+                    <tr key={i} className="plan-error">
+                      <td>{e.stage}</td>
+                      <td>{e.path ? `${e.path}: ${e.message}` : e.message}</td>
+                    </tr>
+                  ))}
+                </tbody>
+              </table>
+            </div>
+          )}
+          {lastResult.warnings.map((w, i) => (
+            <div key={i} className="error-msg">
+              {w}
+            </div>
+          ))}
+          <div className="actions">
+            {lastResult.reportPath && (
+              <button type="button" className="btn-secondary" onClick={() => reveal(lastResult.reportPath!)}>
+                Show report
+              </button>
+            )}
+            {lastResult.logPath && (
+              <button type="button" className="btn-secondary" onClick={() => reveal(lastResult.logPath!)}>
+                Show log
+              </button>
+            )}
+          </div>
+        </div>
+      )}
+
+      {activity.length > 0 && (
+        <div className="section">
+          <label>Activity</label>
+          <div className="plan-view">
+            <table>
+              <tbody>
+                {[...activity].reverse().map(({ runId, file }, i) => (
+                  <tr key={`${runId}-${activity.length - i}`} className={`plan-${file.kind}`}>
+                    <td>{file.kind}</td>
+                    <td
+                      className="revealable"
+                      title="Show in the file manager"
+                      onClick={() => reveal(file.kind === "moved" ? file.dest : file.source)}
+                    >
+                      {activityText(file)}
+                    </td>
+                  </tr>
+                ))}
+              </tbody>
+            </table>
+          </div>
+          <div className="actions">
+            <button type="button" className="btn-secondary" onClick={() => setActivity([])} disabled={running}>
+              Clear
+            </button>
+          </div>
+        </div>
+      )}
+
+      {history && (history.total > 0 || history.warning) && (
+        <div className="section">
+          <label>History</label>
+          {history.warning && <div className="error-msg">{history.warning}</div>}
+          <div className="plan-view">
+            <table>
+              <tbody>
+                {history.records.map((r) => (
+                  <tr key={`${r.finishedAt}-${r.dest}`} className={`plan-${r.outcome === "completed" ? "moved" : "error"}`}>
+                    <td title={r.startedAt}>{new Date(r.finishedAt).toLocaleString()}</td>
+                    <td title={r.error ?? undefined}>
+                      {r.kind}
+                      {r.dryRun ? " (dry run)" : ""}: {r.outcome}
+                    </td>
+                    <td title={r.sources.join("\n")}>
+                      {r.sources.map(baseName).join(", ")} → {r.dest}
+                    </td>
+                    <td>{r.suffixInput}</td>
+                    <td>
+                      {`${r.moved.toLocaleString()} moved (${formatBytes(r.bytesMoved)}), ${r.skippedDuplicates.toLocaleString()} duplicate(s), ${r.errors.toLocaleString()} error(s)`}
+                    </td>
+                    <td>
+                      {r.reportPath && (
+                        <div className="revealable" title="Show in the file manager" onClick={() => reveal(r.reportPath!)}>
+                          report: {r.reportPath}
+                        </div>
+                      )}
+                      {r.logPath && (
+                        <div className="revealable" title="Show in the file manager" onClick={() => reveal(r.logPath!)}>
+                          log: {r.logPath}
+                        </div>
+                      )}
+                    </td>
+                  </tr>
+                ))}
+              </tbody>
+            </table>
+          </div>
+          <div className="actions">
+            {history.records.length < history.total && (
+              <button type="button" className="btn-secondary" onClick={() => setHistoryLimit(null)}>
+                Show all {history.total.toLocaleString()}
+              </button>
+            )}
+            <button type="button" className="btn-secondary" onClick={clearHistory} disabled={running}>
+              Clear history
+            </button>
+          </div>
+        </div>
+      )}
+
       <div className="section">
         <label>Log</label>
         <div className="log-view">
@@ -0,0 +1,508 @@
+// Generated from the Rust types by `UPDATE_BINDINGS=1 cargo test` (src-tauri/src/engine/bindings.rs). Do not edit.
+
+/**
+ * Progress event payload for frontend.
+ */
+export type ProgressEvent = { phase: Phase, currentFile: string | null, 
+/**
+ * Files the scan of the sources looked at so far, and the images among them (see
+ * `RunResult::scanned`); they go up while scanning and stay put after.
+ */
+scanned: number, images: number, matched: number, moved: number, skippedDuplicates: number, 
+/**
+ * Files moved to the rejects folder (see `RunResult::rejected`).
+ */
+rejected: number, 
+/**
+ * See `RunResult::replaced`, `RunResult::skipped_newer`, `RunResult::tagged` and
+ * `RunResult::read_only`.
+ */
+replaced: number, skippedNewer: number, tagged: number, readOnly: number, errors: number, percent: number, 
+/**
+ * Set while the run is paused; counts and phase are those at the time of pausing.
+ */
+paused: boolean, 
+/**
+ * Bytes this phase goes through: destination files while indexing, candidates while
+ * planning and moving.
+ */
+totalBytes: number, bytesDone: number, 
+/**
+ * 1-based position of the current file among the phase's `total_count` files. Files for
+ * the rejects folder come after the matches and are not counted in either.
+ */
+currentIndex: number, totalCount: number, 
+/**
+ * Bytes per second over the last few seconds; 0 until measured.
+ */
+throughput: number, 
+/**
+ * Estimated seconds left in the phase, once there is a throughput.
+ */
+etaSeconds: number | null, 
+/**
+ * An error that just happened; only the first `MAX_PROBLEM_EVENTS` errors and warnings
+ * of a run are sent.
+ */
+error: ErrorDetail | null, warning: string | null, 
+/**
+ * A file that has been worked on longer than the run's stall warning; sent again each
+ * time that much more has passed. Not counted against `MAX_PROBLEM_EVENTS`.
+ */
+stalled: StalledFile | null, 
+/**
+ * In the final event: errors and warnings that were not sent; the result has them all.
+ */
+problemsNotSent: number, 
+/**
+ * In the final event: the run was cancelled; `current_index` of the `total_count` files
+ * were processed.
+ */
+cancelled: boolean, 
+/**
+ * In the final event: matches left out by `RunOptions::limit`; they are not in `matched`.
+ */
+overLimit: number, 
+/**
+ * In the final event: the result's `hash_mismatches`.
+ */
+hashMismatches: number, 
+/**
+ * In the final event: why the run stopped by itself, with `current_index` of the
+ * `total_count` files processed.
+ */
+aborted: Abort | null, 
+/**
+ * In the final event: the result's timing and byte counts.
+ */
+stats: RunStats | null, 
+/**
+ * Requested suffixes no file matched, in the event after scanning (with the warning) and
+ * in the final one.
+ */
+unmatchedSuffixes: Array<number>, 
+/**
+ * In the final event: the result's check after moving, if there was one.
+ */
+verification: Verification | null, 
+/**
+ * A file just moved, skipped as a duplicate or failed, for a live activity log: sent in
+ * an event of its own, with the counts after it. Not while planning (nor in a dry-run).
+ */
+file: FileEvent | null, };
+
+/**
+ * Progress phase for UI/CLI.
+ */
+export type Phase = "scanning_source" | "indexing_destination" | "planning" | "moving" | "verifying" | "watching" | "done";
+
+/**
+ * A file still being read or written after a while, as in "still copying X (2 min)".
+ */
+export type StalledFile = { path: string, 
+/**
+ * Time spent on the file so far, not counting time paused.
+ */
+elapsedSeconds: number, };
+
+/**
+ * What just happened to one file of a run (see `ProgressEvent::file`).
+ */
+export type FileEvent = { "kind": "moved", source: string, dest: string, } | { "kind": "duplicate", source: string, duplicateOf: string | null, } | { "kind": "error", source: string, message: string, };
+
+/**
+ * File events of one kind sent at once (see `FileEventQueue`), tagged with the run they are
+ * of so a log can tell runs apart.
+ */
+export type FileEventBatch = { runId: number, 
+/**
+ * In the order they happened.
+ */
+files: Array<FileEvent>, };
+
+/**
+ * Result of a single run.
+ */
+export type RunResult = { 
+/**
+ * Files the scan of the sources looked at, of any type, and how many of them have an image
+ * extension; `matched` are among those. In watch mode, the count of the first scan.
+ */
+scanned: number, images: number, 
+/**
+ * With `MatchBy::Metadata`: the images matched by the frame name in their metadata, and
+ * those that had none or could not be read, matched by name.
+ */
+numbered: number, unnumbered: number, matched: number, moved: number, 
+/**
+ * With `CollisionMode::Update`: files moved over an older file of the same name (not in
+ * `moved`), and files left alone because the one holding their name was not older.
+ */
+replaced: number, skippedNewer: number, 
+/**
+ * With `MoveOptions::tag`: files renamed with the tag in their own folder. Nothing is
+ * moved then, so `moved` stays 0.
+ */
+tagged: number, skippedDuplicates: number, 
+/**
+ * Duplicate source files deleted or trashed (predicted in dry-run).
+ */
+duplicatesRemoved: number, 
+/**
+ * Empty source directories removed after moving (predicted in dry-run).
+ */
+removedDirs: number, 
+/**
+ * Cross-volume copies re-hashed and confirmed before their source was deleted.
+ */
+verified: number, 
+/**
+ * Files copied to the destination whose source could not be removed (e.g. read-only media).
+ */
+sourceKept: number, 
+/**
+ * Destination names changed to suit exFAT/FAT32/SMB (predicted in dry-run).
+ */
+sanitized: number, 
+/**
+ * Files made read-only in the destination, with `RunOptions::read_only` (predicted in
+ * dry-run); one the filesystem kept writable is a warning instead.
+ */
+readOnly: number, errors: number, 
+/**
+ * What each error was, in the order they happened.
+ */
+errorDetails: Array<ErrorDetail>, 
+/**
+ * Files matching a suffix that were left out by the exclusion globs.
+ */
+excluded: number, 
+/**
+ * Matches that are symbolic links, left where they are with `SymlinkPolicy::Skip`.
+ */
+skippedSymlinks: number, 
+/**
+ * Symbolic links whose name matched but which point to nothing; each is a warning.
+ */
+brokenLinks: number, 
+/**
+ * `RunOptions::limit`, when one was set.
+ */
+limit: number | null, 
+/**
+ * Matches past that limit, left for a later run; they are not in `matched`.
+ */
+overLimit: number, 
+/**
+ * A run of selected files (`run_selected`): those the scan did not take as matches, left
+ * where they are.
+ */
+selectedUnmatched: number, 
+/**
+ * Extensions (".CR3") of the files whose name matched but whose type is not an image
+ * one; how many of each suffix is in `suffixes`.
+ */
+otherTypeExtensions: Array<string>, 
+/**
+ * Planned files left alone because they changed or disappeared before they were moved.
+ */
+skippedChanged: number, 
+/**
+ * Matches of zero bytes, each warned about; `MoveOptions::empty_files` says whether they
+ * were moved (and are in `moved` too), skipped or counted in `errors`.
+ */
+emptyFiles: number, 
+/**
+ * Files skipped because they were modified within `RunOptions::min_age`; they are in
+ * `busy_files` and get moved by a later run.
+ */
+skippedBusy: number, busyFiles: Array<string>, 
+/**
+ * Duplicates that copied another file of this run rather than destination content.
+ */
+sourceDuplicates: number, 
+/**
+ * Files whose hash matched other content but whose bytes did not (or could not be
+ * compared), with `RunOptions::confirm_duplicates`; they were moved instead of skipped.
+ */
+hashMismatches: number, 
+/**
+ * With `RunOptions::rejects`: images that matched no suffix, taken for the rejects
+ * folder. They are not in `matched`, nor in `moved` or `skipped_duplicates`.
+ */
+rejectsFound: number, 
+/**
+ * Of those, the ones moved to the rejects folder, and the ones whose content was there
+ * already (handled like any duplicate).
+ */
+rejected: number, rejectedDuplicates: number, 
+/**
+ * Those in-run duplicates grouped under the file that was moved (from the kept records).
+ */
+duplicateGroups: Array<DuplicateGroup>, 
+/**
+ * Matched, moved and duplicate files of each requested suffix, sorted by suffix.
+ */
+suffixes: Array<SuffixCount>, 
+/**
+ * Whether this was a dry-run; `files` then describes what would have happened.
+ */
+dryRun: boolean, 
+/**
+ * Per-file actions, up to `MAX_FILE_RECORDS`.
+ */
+files: Array<FileRecord>, 
+/**
+ * Files left out of `files` because of the cap.
+ */
+filesOmitted: number, 
+/**
+ * The run was cancelled before it finished; the counts cover what was done until then.
+ */
+cancelled: boolean, 
+/**
+ * Cancelled or aborted runs: matched files that were never attempted; like `matched`, it
+ * leaves out the files for the rejects folder.
+ */
+notAttempted: number, 
+/**
+ * Why the run stopped by itself before attempting every file; not a cancel.
+ */
+aborted: Abort | null, 
+/**
+ * Non-fatal problems, e.g. folders that could not be cleaned up or a report that failed.
+ */
+warnings: Array<string>, 
+/**
+ * What the checks before moving found: bytes to write, free space, cross-volume roots.
+ */
+preflight: Preflight | null, 
+/**
+ * Set when those checks stopped the run before anything was moved.
+ */
+preflightError: PreflightError | null, 
+/**
+ * The log file this run wrote, when one was asked for and could be opened.
+ */
+logPath: string | null, 
+/**
+ * The JSON report of this run, when one was asked for and could be written.
+ */
+reportPath: string | null, 
+/**
+ * When the run started and finished, how long each phase took and how many bytes it
+ * moved; for a cancelled run, up to the point it stopped.
+ */
+stats: RunStats, 
+/**
+ * The check after moving, when `RunOptions::verify` asked for one; its failures are also
+ * counted in `errors`.
+ */
+verification: Verification | null, 
+/**
+ * The state file to resume this run from (`resume`), kept because it was cancelled.
+ */
+statePath: string | null, 
+/**
+ * Resumed runs: files done before the interruption, which the other counts leave out.
+ */
+resumed: number, 
+/**
+ * Run with `DedupMode::Off`: no file was checked for duplicates, so none were skipped.
+ */
+dedupDisabled: boolean, };
+
+/**
+ * One error of a run: which file (if any), at what stage, and what went wrong.
+ */
+export type ErrorDetail = { path: string | null, stage: ErrorStage, message: string, };
+
+/**
+ * Where in a run an error happened.
+ */
+export type ErrorStage = "setup" | "scan" | "preflight" | "destination" | "read" | "move" | "duplicate" | "verify";
+
+/**
+ * Why a run stopped before attempting all its files, other than a cancel. The files it did
+ * not get to are counted in `RunResult::not_attempted`, and a run with a state file keeps it
+ * to resume from.
+ */
+export type Abort = { "kind": "destination_unavailable", dest: string, failures: number, message: string, } | { "kind": "fail_fast", path: string, message: string, };
+
+/**
+ * Timing and volume of a run, in its result and in the final progress event.
+ */
+export type RunStats = { 
+/**
+ * Local time with offset (RFC 3339).
+ */
+startedAt: string | null, finishedAt: string | null, 
+/**
+ * Wall-clock time from start to finish, pauses included.
+ */
+durationMs: number, 
+/**
+ * Bytes of the files moved (dry-run: that would be moved).
+ */
+bytesMoved: number, 
+/**
+ * Bytes of the source files skipped as duplicates.
+ */
+bytesSkippedDuplicates: number, 
+/**
+ * Executing a saved plan: scanning, indexing and planning are those of the plan.
+ */
+phases: PhaseDurations, 
+/**
+ * Images found in the destination when the run started.
+ */
+destIndexed: number, 
+/**
+ * Of those, the ones hashed: only files sharing their size with a match are read.
+ */
+destHashed: number, 
+/**
+ * Of the indexed images, the ones whose hash an earlier run of the session had made, so
+ * they were not read again (see `IndexCache`).
+ */
+destReused: number, };
+
+/**
+ * How long each phase took, in milliseconds. Watch mode adds up its batches (time spent
+ * waiting for new files is in none of them).
+ */
+export type PhaseDurations = { scanMs: number, indexMs: number, 
+/**
+ * Hashing the matches and deciding what happens to each.
+ */
+planMs: number, 
+/**
+ * Moving, removing emptied folders included; 0 for a dry-run.
+ */
+moveMs: number, 
+/**
+ * The check after moving, when there is one.
+ */
+verifyMs: number, };
+
+/**
+ * Outcome of the check after moving.
+ */
+export type Verification = { 
+/**
+ * Whether contents were re-hashed, not only sizes compared.
+ */
+content: boolean, verified: number, 
+/**
+ * Not at the recorded destination.
+ */
+missing: number, 
+/**
+ * A different size or content, or a file that could not be read back.
+ */
+mismatched: number, 
+/**
+ * Left unchecked because the run was cancelled.
+ */
+notChecked: number, };
+
+/**
+ * One line of the per-file report.
+ */
+export type FileRecord = { source: string, 
+/**
+ * Where the file went (or would go); `None` when it never got a destination.
+ */
+dest: string | null, action: FileAction, 
+/**
+ * Short explanation, e.g. "name taken, renamed" or the error message.
+ */
+detail: string | null, 
+/**
+ * For a duplicate of another file in this run: that file's source path.
+ */
+duplicateOf: string | null, 
+/**
+ * For a duplicate: the destination file with its content, where known; for a copy of
+ * another file of this run, where that file went.
+ */
+existing: string | null, 
+/**
+ * The source root the file was found under.
+ */
+root: string | null, 
+/**
+ * With routes: the destination folder the file's suffix was routed to.
+ */
+route: string | null, 
+/**
+ * Destinations of later routes that list the suffix too; the first rule was followed.
+ */
+alsoRouted: Array<string>, 
+/**
+ * The older file that was at `dest` before this one replaced it.
+ */
+replaced: ReplacedFile | null, 
+/**
+ * The file at `dest` was made read-only (`RunOptions::read_only`); moving it back should
+ * clear that first.
+ */
+readOnly: boolean, };
+
+/**
+ * What happened to one matched file (or would happen, in dry-run).
+ */
+export type FileAction = "moved" | "duplicate" | "source_kept" | "skipped" | "error";
+
+/**
+ * The destination file an update replaced, as it was: what overwrote it is in the record of
+ * the move, so the change can be checked or undone from the report.
+ */
+export type ReplacedFile = { hash: string, 
+/**
+ * Its modification time (RFC 3339), if the filesystem has one.
+ */
+modified: string | null, 
+/**
+ * Where it was renamed to, next to the file that took its place; `undo` puts it back.
+ */
+keptAt: string, };
+
+/**
+ * Source files of one run with identical content: `source` was moved to `dest`, the `copies`
+ * were skipped as its duplicates.
+ */
+export type DuplicateGroup = { source: string, dest: string | null, copies: Array<string>, };
+
+/**
+ * What became of the files of one requested suffix.
+ */
+export type SuffixCount = { suffix: number, matched: number, moved: number, skippedDuplicates: number, 
+/**
+ * Files whose name matched but whose type is not an image one (e.g. .CR3), left out.
+ */
+otherTypes: number, };
+
+/**
+ * What the pre-flight found out about the destination.
+ */
+export type Preflight = { 
+/**
+ * Bytes the run writes to the destination volume: every candidate that is copied rather
+ * than renamed or hard-linked. Duplicates are not known yet, so they are included.
+ */
+neededBytes: number, 
+/**
+ * Free bytes on the destination volume, when the system reports them.
+ */
+freeBytes: number | null, 
+/**
+ * Source roots on another volume than the destination; their files are copied, verified
+ * and then deleted instead of renamed.
+ */
+crossVolume: Array<string>, };
+
+/**
+ * Why a run was stopped before moving anything.
+ */
+export type PreflightError = { "kind": "insufficient_space", dest: string, needed: number, available: number, } | { "kind": "not_writable", dest: string, reason: string, };